@@ -1039,12 +1039,14 @@ export const LiveGames_FeedEntryPlayersFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'user' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
               ],
@@ -1066,6 +1068,7 @@ export const LiveGames_FeedEntryMapAndTypeFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           {
             kind: 'Field',
@@ -1073,6 +1076,7 @@ export const LiveGames_FeedEntryMapAndTypeFragmentFragmentDoc = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                 {
@@ -1081,6 +1085,7 @@ export const LiveGames_FeedEntryMapAndTypeFragmentFragmentDoc = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image256Url' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image512Url' } },
@@ -1110,12 +1115,14 @@ export const LiveGames_FeedEntryMapAndTypeFragmentFragmentDoc = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       {
                         kind: 'Field',
                         name: { kind: 'Name', value: 'gameSourceExtra' },
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'matchmakingType' } },
                           ],
                         },
@@ -1141,6 +1148,7 @@ export const LiveGames_FeedEntryFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           { kind: 'Field', name: { kind: 'Name', value: 'startTime' } },
           {
@@ -1149,6 +1157,7 @@ export const LiveGames_FeedEntryFragmentFragmentDoc = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                 {
@@ -1157,6 +1166,7 @@ export const LiveGames_FeedEntryFragmentFragmentDoc = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image256Url' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image512Url' } },
@@ -1186,12 +1196,14 @@ export const LiveGames_FeedEntryFragmentFragmentDoc = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       {
                         kind: 'Field',
                         name: { kind: 'Name', value: 'gameSourceExtra' },
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'matchmakingType' } },
                           ],
                         },
@@ -1202,12 +1214,14 @@ export const LiveGames_FeedEntryFragmentFragmentDoc = {
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             {
                               kind: 'Field',
                               name: { kind: 'Name', value: 'user' },
                               selectionSet: {
                                 kind: 'SelectionSet',
                                 selections: [
+                                  { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                                   { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                                 ],
                               },
@@ -1239,12 +1253,14 @@ export const LiveGames_FeedEntryFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'user' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
               ],
@@ -1261,6 +1277,7 @@ export const LiveGames_FeedEntryFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           {
             kind: 'Field',
@@ -1268,6 +1285,7 @@ export const LiveGames_FeedEntryFragmentFragmentDoc = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                 {
@@ -1276,6 +1294,7 @@ export const LiveGames_FeedEntryFragmentFragmentDoc = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image256Url' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image512Url' } },
@@ -1305,12 +1324,14 @@ export const LiveGames_FeedEntryFragmentFragmentDoc = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       {
                         kind: 'Field',
                         name: { kind: 'Name', value: 'gameSourceExtra' },
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'matchmakingType' } },
                           ],
                         },
@@ -1336,12 +1357,14 @@ export const LiveGames_FeedFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'liveGames' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 {
                   kind: 'FragmentSpread',
@@ -1360,12 +1383,14 @@ export const LiveGames_FeedFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'user' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
               ],
@@ -1382,6 +1407,7 @@ export const LiveGames_FeedFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           {
             kind: 'Field',
@@ -1389,6 +1415,7 @@ export const LiveGames_FeedFragmentFragmentDoc = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                 {
@@ -1397,6 +1424,7 @@ export const LiveGames_FeedFragmentFragmentDoc = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image256Url' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image512Url' } },
@@ -1426,12 +1454,14 @@ export const LiveGames_FeedFragmentFragmentDoc = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       {
                         kind: 'Field',
                         name: { kind: 'Name', value: 'gameSourceExtra' },
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'matchmakingType' } },
                           ],
                         },
@@ -1452,6 +1482,7 @@ export const LiveGames_FeedFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           { kind: 'Field', name: { kind: 'Name', value: 'startTime' } },
           {
@@ -1460,6 +1491,7 @@ export const LiveGames_FeedFragmentFragmentDoc = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                 {
@@ -1468,6 +1500,7 @@ export const LiveGames_FeedFragmentFragmentDoc = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image256Url' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image512Url' } },
@@ -1497,12 +1530,14 @@ export const LiveGames_FeedFragmentFragmentDoc = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       {
                         kind: 'Field',
                         name: { kind: 'Name', value: 'gameSourceExtra' },
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'matchmakingType' } },
                           ],
                         },
@@ -1513,12 +1548,14 @@ export const LiveGames_FeedFragmentFragmentDoc = {
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             {
                               kind: 'Field',
                               name: { kind: 'Name', value: 'user' },
                               selectionSet: {
                                 kind: 'SelectionSet',
                                 selections: [
+                                  { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                                   { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                                 ],
                               },
@@ -1555,6 +1592,7 @@ export const UrgentMessage_HomeDisplayFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           { kind: 'Field', name: { kind: 'Name', value: 'title' } },
           { kind: 'Field', name: { kind: 'Name', value: 'message' } },
@@ -1573,6 +1611,7 @@ export const Leagues_LeagueBadgeFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'name' } },
           { kind: 'Field', name: { kind: 'Name', value: 'badgeUrl' } },
         ],
@@ -1590,6 +1629,7 @@ export const Leagues_HomeFeedEntryFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           { kind: 'Field', name: { kind: 'Name', value: 'name' } },
           { kind: 'Field', name: { kind: 'Name', value: 'matchmakingType' } },
@@ -1606,6 +1646,7 @@ export const Leagues_HomeFeedEntryFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'name' } },
           { kind: 'Field', name: { kind: 'Name', value: 'badgeUrl' } },
         ],
@@ -1623,12 +1664,14 @@ export const Leagues_HomeFeedFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'activeLeagues' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 {
                   kind: 'FragmentSpread',
@@ -1643,6 +1686,7 @@ export const Leagues_HomeFeedFragmentFragmentDoc = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 {
                   kind: 'FragmentSpread',
@@ -1661,6 +1705,7 @@ export const Leagues_HomeFeedFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'name' } },
           { kind: 'Field', name: { kind: 'Name', value: 'badgeUrl' } },
         ],
@@ -1673,6 +1718,7 @@ export const Leagues_HomeFeedFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           { kind: 'Field', name: { kind: 'Name', value: 'name' } },
           { kind: 'Field', name: { kind: 'Name', value: 'matchmakingType' } },
@@ -1694,6 +1740,7 @@ export const News_HomeFeedFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'newsPosts' },
@@ -1707,18 +1754,21 @@ export const News_HomeFeedFragmentFragmentDoc = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 {
                   kind: 'Field',
                   name: { kind: 'Name', value: 'edges' },
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       {
                         kind: 'Field',
                         name: { kind: 'Name', value: 'node' },
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'title' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'summary' } },
@@ -1749,6 +1799,7 @@ export const AccountSettings_CurrentUserFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           { kind: 'Field', name: { kind: 'Name', value: 'name' } },
           { kind: 'Field', name: { kind: 'Name', value: 'loginName' } },
@@ -1774,6 +1825,7 @@ export const LiveStreams_FeedEntryFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'twitchLogin' } },
           { kind: 'Field', name: { kind: 'Name', value: 'twitchDisplayName' } },
           { kind: 'Field', name: { kind: 'Name', value: 'title' } },
@@ -1786,6 +1838,7 @@ export const LiveStreams_FeedEntryFragmentFragmentDoc = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
               ],
@@ -1806,12 +1859,14 @@ export const LiveStreams_FeedFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'liveStreams' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'twitchLogin' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'viewerCount' } },
                 {
@@ -1831,6 +1886,7 @@ export const LiveStreams_FeedFragmentFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'twitchLogin' } },
           { kind: 'Field', name: { kind: 'Name', value: 'twitchDisplayName' } },
           { kind: 'Field', name: { kind: 'Name', value: 'title' } },
@@ -1843,6 +1899,7 @@ export const LiveStreams_FeedFragmentFragmentDoc = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
               ],
@@ -1863,6 +1920,7 @@ export const AdminUserProfile_PermissionsFragmentDoc = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           {
             kind: 'Field',
@@ -1870,6 +1928,7 @@ export const AdminUserProfile_PermissionsFragmentDoc = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'editPermissions' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'debug' } },
@@ -1897,6 +1956,7 @@ export const AdminUserProfile_PermissionsFragmentDoc = {
   ],
 } as unknown as DocumentNode<AdminUserProfile_PermissionsFragment, unknown>
 export const AdminNewsListDocument = {
+  __meta__: { hash: '4db65d6e7f62e069f6b923dc6c82c801867a4a6a34fa6d367a3270817aa7aa2c' },
   kind: 'Document',
   definitions: [
     {
@@ -1918,6 +1978,7 @@ export const AdminNewsListDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'newsPosts' },
@@ -1941,18 +2002,21 @@ export const AdminNewsListDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 {
                   kind: 'Field',
                   name: { kind: 'Name', value: 'edges' },
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       {
                         kind: 'Field',
                         name: { kind: 'Name', value: 'node' },
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'title' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'summary' } },
@@ -1964,6 +2028,7 @@ export const AdminNewsListDocument = {
                               selectionSet: {
                                 kind: 'SelectionSet',
                                 selections: [
+                                  { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                                   { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                                   { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                                 ],
@@ -1981,6 +2046,7 @@ export const AdminNewsListDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'hasNextPage' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'endCursor' } },
                     ],
@@ -1995,6 +2061,7 @@ export const AdminNewsListDocument = {
   ],
 } as unknown as DocumentNode<AdminNewsListQuery, AdminNewsListQueryVariables>
 export const AdminNewsPostDocument = {
+  __meta__: { hash: 'ce0b1f8677666418c12e5c6291a2f5ba64fbef2998ae1950f93c0f6b3a405e91' },
   kind: 'Document',
   definitions: [
     {
@@ -2014,6 +2081,7 @@ export const AdminNewsPostDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'newsPost' },
@@ -2027,6 +2095,7 @@ export const AdminNewsPostDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'title' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'summary' } },
@@ -2040,6 +2109,7 @@ export const AdminNewsPostDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                     ],
@@ -2054,6 +2124,7 @@ export const AdminNewsPostDocument = {
   ],
 } as unknown as DocumentNode<AdminNewsPostQuery, AdminNewsPostQueryVariables>
 export const AdminNewsHistoryDocument = {
+  __meta__: { hash: '03e04511291dd7ed60b650cff421750fbb9143d1588a42ad0c6af091e1f52adb' },
   kind: 'Document',
   definitions: [
     {
@@ -2073,6 +2144,7 @@ export const AdminNewsHistoryDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'newsPost' },
@@ -2086,6 +2158,7 @@ export const AdminNewsHistoryDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'title' } },
                 {
@@ -2094,6 +2167,7 @@ export const AdminNewsHistoryDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'title' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'summary' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'content' } },
@@ -2106,6 +2180,7 @@ export const AdminNewsHistoryDocument = {
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                           ],
@@ -2123,6 +2198,7 @@ export const AdminNewsHistoryDocument = {
   ],
 } as unknown as DocumentNode<AdminNewsHistoryQuery, AdminNewsHistoryQueryVariables>
 export const NewsCreatePostDocument = {
+  __meta__: { hash: 'ed04ef6f036d72334ecfa3a08138b33774f153681db0a7685d7aad97e485ca32' },
   kind: 'Document',
   definitions: [
     {
@@ -2142,6 +2218,7 @@ export const NewsCreatePostDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'newsCreatePost' },
@@ -2154,7 +2231,10 @@ export const NewsCreatePostDocument = {
             ],
             selectionSet: {
               kind: 'SelectionSet',
-              selections: [{ kind: 'Field', name: { kind: 'Name', value: 'id' } }],
+              selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
+                { kind: 'Field', name: { kind: 'Name', value: 'id' } },
+              ],
             },
           },
         ],
@@ -2163,6 +2243,7 @@ export const NewsCreatePostDocument = {
   ],
 } as unknown as DocumentNode<NewsCreatePostMutation, NewsCreatePostMutationVariables>
 export const NewsUpdatePostDocument = {
+  __meta__: { hash: '0c1c0b336232afd637080710c7ecf635a336cb9aa086f24b63641f37da313168' },
   kind: 'Document',
   definitions: [
     {
@@ -2190,6 +2271,7 @@ export const NewsUpdatePostDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'newsUpdatePost' },
@@ -2208,6 +2290,7 @@ export const NewsUpdatePostDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'title' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'summary' } },
@@ -2226,6 +2309,7 @@ export const NewsUpdatePostDocument = {
   ],
 } as unknown as DocumentNode<NewsUpdatePostMutation, NewsUpdatePostMutationVariables>
 export const NewsDeletePostDocument = {
+  __meta__: { hash: 'f521974f92c7aebc445d8cf960e216866655072c64f0e68011f7b0a1db10196d' },
   kind: 'Document',
   definitions: [
     {
@@ -2245,6 +2329,7 @@ export const NewsDeletePostDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'newsDeletePost' },
@@ -2262,6 +2347,7 @@ export const NewsDeletePostDocument = {
   ],
 } as unknown as DocumentNode<NewsDeletePostMutation, NewsDeletePostMutationVariables>
 export const AdminBlockedStreamsDocument = {
+  __meta__: { hash: 'ed98544c240faa51cf093b5ebda838bec8e86f81742b7e1aaa356c7d223a9ead' },
   kind: 'Document',
   definitions: [
     {
@@ -2271,12 +2357,14 @@ export const AdminBlockedStreamsDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'blockedStreams' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'createdAt' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'twitchLogin' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'twitchDisplayName' } },
@@ -2286,6 +2374,7 @@ export const AdminBlockedStreamsDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                     ],
@@ -2297,6 +2386,7 @@ export const AdminBlockedStreamsDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                     ],
@@ -2311,6 +2401,7 @@ export const AdminBlockedStreamsDocument = {
   ],
 } as unknown as DocumentNode<AdminBlockedStreamsQuery, AdminBlockedStreamsQueryVariables>
 export const AdminUnblockStreamDocument = {
+  __meta__: { hash: 'e33e8819108d08614a5d78a390ce09055535096ff52da22f2ede95d83200f6c8' },
   kind: 'Document',
   definitions: [
     {
@@ -2330,6 +2421,7 @@ export const AdminUnblockStreamDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'unblockStream' },
@@ -2347,6 +2439,7 @@ export const AdminUnblockStreamDocument = {
   ],
 } as unknown as DocumentNode<AdminUnblockStreamMutation, AdminUnblockStreamMutationVariables>
 export const AdminMatchmakingConfigDocument = {
+  __meta__: { hash: '6939564a40597c0033fb2b009a85ae5a4500421c19b3fcda61746882d407c735' },
   kind: 'Document',
   definitions: [
     {
@@ -2356,12 +2449,14 @@ export const AdminMatchmakingConfigDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'matchmakingConfig' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'searchIntervalSeconds' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'maxPlayersExamined' } },
                 {
@@ -2370,6 +2465,7 @@ export const AdminMatchmakingConfigDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'weightRatingVariance' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'weightWinProb' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'weightLatency' } },
@@ -2390,6 +2486,7 @@ export const AdminMatchmakingConfigDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'matchmakingType' } },
                       {
                         kind: 'Field',
@@ -2397,6 +2494,7 @@ export const AdminMatchmakingConfigDocument = {
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             {
                               kind: 'Field',
                               name: { kind: 'Name', value: 'weightRatingVariance' },
@@ -2429,6 +2527,7 @@ export const AdminMatchmakingConfigDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'searchIntervalSeconds' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'maxPlayersExamined' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'weightRatingVariance' } },
@@ -2454,6 +2553,7 @@ export const AdminMatchmakingConfigDocument = {
   ],
 } as unknown as DocumentNode<AdminMatchmakingConfigQuery, AdminMatchmakingConfigQueryVariables>
 export const AdminUpdateMatchmakingConfigDocument = {
+  __meta__: { hash: '9e9bedac19c6b740face842ac943c30330c8b3f7787ce8ebc1b5685b70a5a9a0' },
   kind: 'Document',
   definitions: [
     {
@@ -2473,6 +2573,7 @@ export const AdminUpdateMatchmakingConfigDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'updateMatchmakingConfig' },
@@ -2486,6 +2587,7 @@ export const AdminUpdateMatchmakingConfigDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'searchIntervalSeconds' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'maxPlayersExamined' } },
                 {
@@ -2493,7 +2595,10 @@ export const AdminUpdateMatchmakingConfigDocument = {
                   name: { kind: 'Name', value: 'global' },
                   selectionSet: {
                     kind: 'SelectionSet',
-                    selections: [{ kind: 'Field', name: { kind: 'Name', value: 'minQuality' } }],
+                    selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
+                      { kind: 'Field', name: { kind: 'Name', value: 'minQuality' } },
+                    ],
                   },
                 },
               ],
@@ -2508,6 +2613,7 @@ export const AdminUpdateMatchmakingConfigDocument = {
   AdminUpdateMatchmakingConfigMutationVariables
 >
 export const RestrictedNamesDocument = {
+  __meta__: { hash: '72272729aac87446c2e7e055d6b4ad99999fe84aaeaddbd3edc734cd7ae714fc' },
   kind: 'Document',
   definitions: [
     {
@@ -2517,12 +2623,14 @@ export const RestrictedNamesDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'restrictedNames' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'pattern' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'kind' } },
@@ -2533,7 +2641,10 @@ export const RestrictedNamesDocument = {
                   name: { kind: 'Name', value: 'createdBy' },
                   selectionSet: {
                     kind: 'SelectionSet',
-                    selections: [{ kind: 'Field', name: { kind: 'Name', value: 'id' } }],
+                    selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
+                      { kind: 'Field', name: { kind: 'Name', value: 'id' } },
+                    ],
                   },
                 },
               ],
@@ -2545,6 +2656,7 @@ export const RestrictedNamesDocument = {
   ],
 } as unknown as DocumentNode<RestrictedNamesQuery, RestrictedNamesQueryVariables>
 export const DeleteRestrictedNameDocument = {
+  __meta__: { hash: '8fa90d5439b3e892fd882bbae69fccd3f5121e4369d6af074b0e1b6c618fc560' },
   kind: 'Document',
   definitions: [
     {
@@ -2564,6 +2676,7 @@ export const DeleteRestrictedNameDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'userDeleteRestrictedName' },
@@ -2581,6 +2694,7 @@ export const DeleteRestrictedNameDocument = {
   ],
 } as unknown as DocumentNode<DeleteRestrictedNameMutation, DeleteRestrictedNameMutationVariables>
 export const AddRestrictedNameDocument = {
+  __meta__: { hash: 'c0808f7236b3a8fd9d00b67b3cf3aff3e6c9aa0b213435f11964c3e79245ff45' },
   kind: 'Document',
   definitions: [
     {
@@ -2616,6 +2730,7 @@ export const AddRestrictedNameDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'userAddRestrictedName' },
@@ -2639,6 +2754,7 @@ export const AddRestrictedNameDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'pattern' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'kind' } },
@@ -2649,7 +2765,10 @@ export const AddRestrictedNameDocument = {
                   name: { kind: 'Name', value: 'createdBy' },
                   selectionSet: {
                     kind: 'SelectionSet',
-                    selections: [{ kind: 'Field', name: { kind: 'Name', value: 'id' } }],
+                    selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
+                      { kind: 'Field', name: { kind: 'Name', value: 'id' } },
+                    ],
                   },
                 },
               ],
@@ -2661,6 +2780,7 @@ export const AddRestrictedNameDocument = {
   ],
 } as unknown as DocumentNode<AddRestrictedNameMutation, AddRestrictedNameMutationVariables>
 export const TestRestrictedNameDocument = {
+  __meta__: { hash: '5e86208964e4a85665119b63af35b80cdaed30e6e3d40701607cd2b74a6c1ad0' },
   kind: 'Document',
   definitions: [
     {
@@ -2680,6 +2800,7 @@ export const TestRestrictedNameDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'userTestRestrictedName' },
@@ -2693,6 +2814,7 @@ export const TestRestrictedNameDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'pattern' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'kind' } },
@@ -2706,6 +2828,7 @@ export const TestRestrictedNameDocument = {
   ],
 } as unknown as DocumentNode<TestRestrictedNameMutation, TestRestrictedNameMutationVariables>
 export const SignupCodesDocument = {
+  __meta__: { hash: '22256b4a3c2adbc20dcd80620bd0237b522c591de1fc726354a8002c9d3dd7de' },
   kind: 'Document',
   definitions: [
     {
@@ -2722,6 +2845,7 @@ export const SignupCodesDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'signupCodes' },
@@ -2735,6 +2859,7 @@ export const SignupCodesDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'code' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'createdAt' } },
@@ -2744,6 +2869,7 @@ export const SignupCodesDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                     ],
@@ -2763,6 +2889,7 @@ export const SignupCodesDocument = {
   ],
 } as unknown as DocumentNode<SignupCodesQuery, SignupCodesQueryVariables>
 export const CreateSignupCodeDocument = {
+  __meta__: { hash: 'f01ecc6b59dbda968cd492bb662859a4b04c134135c301b2b7dad591c8d0bd37' },
   kind: 'Document',
   definitions: [
     {
@@ -2782,6 +2909,7 @@ export const CreateSignupCodeDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'createSignupCode' },
@@ -2795,6 +2923,7 @@ export const CreateSignupCodeDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'code' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'createdAt' } },
@@ -2803,7 +2932,10 @@ export const CreateSignupCodeDocument = {
                   name: { kind: 'Name', value: 'createdByUser' },
                   selectionSet: {
                     kind: 'SelectionSet',
-                    selections: [{ kind: 'Field', name: { kind: 'Name', value: 'id' } }],
+                    selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
+                      { kind: 'Field', name: { kind: 'Name', value: 'id' } },
+                    ],
                   },
                 },
                 { kind: 'Field', name: { kind: 'Name', value: 'expiresAt' } },
@@ -2820,6 +2952,7 @@ export const CreateSignupCodeDocument = {
   ],
 } as unknown as DocumentNode<CreateSignupCodeMutation, CreateSignupCodeMutationVariables>
 export const SetUrgentMessageDocument = {
+  __meta__: { hash: '8889b294a2356b57ba17580dfd5e8042eb11ff7f1f5dc1a0a9e626aba53b99f4' },
   kind: 'Document',
   definitions: [
     {
@@ -2836,6 +2969,7 @@ export const SetUrgentMessageDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'newsSetUrgentMessage' },
@@ -2853,6 +2987,7 @@ export const SetUrgentMessageDocument = {
   ],
 } as unknown as DocumentNode<SetUrgentMessageMutation, SetUrgentMessageMutationVariables>
 export const AdminGameReportsListDocument = {
+  __meta__: { hash: '3721d9b1da7516c6f8ec569f597887abe323de0632e2518454f96a880f148e4a' },
   kind: 'Document',
   definitions: [
     {
@@ -2879,6 +3014,7 @@ export const AdminGameReportsListDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'gameReports' },
@@ -2902,18 +3038,21 @@ export const AdminGameReportsListDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 {
                   kind: 'Field',
                   name: { kind: 'Name', value: 'edges' },
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       {
                         kind: 'Field',
                         name: { kind: 'Name', value: 'node' },
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'reason' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'details' } },
@@ -2926,6 +3065,7 @@ export const AdminGameReportsListDocument = {
                               selectionSet: {
                                 kind: 'SelectionSet',
                                 selections: [
+                                  { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                                   { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                                 ],
                               },
@@ -2936,6 +3076,7 @@ export const AdminGameReportsListDocument = {
                               selectionSet: {
                                 kind: 'SelectionSet',
                                 selections: [
+                                  { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                                   { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                                 ],
                               },
@@ -2952,6 +3093,7 @@ export const AdminGameReportsListDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'hasNextPage' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'endCursor' } },
                     ],
@@ -2966,6 +3108,7 @@ export const AdminGameReportsListDocument = {
   ],
 } as unknown as DocumentNode<AdminGameReportsListQuery, AdminGameReportsListQueryVariables>
 export const AdminGameReportDocument = {
+  __meta__: { hash: '40420116666c400dd43a65f6010bcfa1b7a46b80e7b3ea7488479fa5b23d4007' },
   kind: 'Document',
   definitions: [
     {
@@ -2985,6 +3128,7 @@ export const AdminGameReportDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'gameReport' },
@@ -2998,6 +3142,7 @@ export const AdminGameReportDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'reason' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'details' } },
@@ -3011,6 +3156,7 @@ export const AdminGameReportDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                     ],
@@ -3022,6 +3168,7 @@ export const AdminGameReportDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                     ],
@@ -3032,7 +3179,10 @@ export const AdminGameReportDocument = {
                   name: { kind: 'Name', value: 'resolver' },
                   selectionSet: {
                     kind: 'SelectionSet',
-                    selections: [{ kind: 'Field', name: { kind: 'Name', value: 'id' } }],
+                    selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
+                      { kind: 'Field', name: { kind: 'Name', value: 'id' } },
+                    ],
                   },
                 },
                 {
@@ -3041,6 +3191,7 @@ export const AdminGameReportDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       {
                         kind: 'Field',
@@ -3058,12 +3209,17 @@ export const AdminGameReportDocument = {
                               selectionSet: {
                                 kind: 'SelectionSet',
                                 selections: [
+                                  { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                                   {
                                     kind: 'Field',
                                     name: { kind: 'Name', value: 'teams' },
                                     selectionSet: {
                                       kind: 'SelectionSet',
                                       selections: [
+                                        {
+                                          kind: 'Field',
+                                          name: { kind: 'Name', value: '__typename' },
+                                        },
                                         {
                                           kind: 'Field',
                                           name: { kind: 'Name', value: 'isComputer' },
@@ -3074,6 +3230,10 @@ export const AdminGameReportDocument = {
                                           selectionSet: {
                                             kind: 'SelectionSet',
                                             selections: [
+                                              {
+                                                kind: 'Field',
+                                                name: { kind: 'Name', value: '__typename' },
+                                              },
                                               {
                                                 kind: 'Field',
                                                 name: { kind: 'Name', value: 'id' },
@@ -3100,12 +3260,17 @@ export const AdminGameReportDocument = {
                               selectionSet: {
                                 kind: 'SelectionSet',
                                 selections: [
+                                  { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                                   {
                                     kind: 'Field',
                                     name: { kind: 'Name', value: 'teams' },
                                     selectionSet: {
                                       kind: 'SelectionSet',
                                       selections: [
+                                        {
+                                          kind: 'Field',
+                                          name: { kind: 'Name', value: '__typename' },
+                                        },
                                         {
                                           kind: 'Field',
                                           name: { kind: 'Name', value: 'isComputer' },
@@ -3116,6 +3281,10 @@ export const AdminGameReportDocument = {
                                           selectionSet: {
                                             kind: 'SelectionSet',
                                             selections: [
+                                              {
+                                                kind: 'Field',
+                                                name: { kind: 'Name', value: '__typename' },
+                                              },
                                               {
                                                 kind: 'Field',
                                                 name: { kind: 'Name', value: 'id' },
@@ -3145,6 +3314,7 @@ export const AdminGameReportDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'replayFileId' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'hash' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'url' } },
@@ -3157,6 +3327,7 @@ export const AdminGameReportDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'total' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'actioned' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'dismissed' } },
@@ -3172,6 +3343,7 @@ export const AdminGameReportDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'total' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'actioned' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'dismissed' } },
@@ -3187,6 +3359,7 @@ export const AdminGameReportDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'reason' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'details' } },
@@ -3198,7 +3371,10 @@ export const AdminGameReportDocument = {
                         name: { kind: 'Name', value: 'reporter' },
                         selectionSet: {
                           kind: 'SelectionSet',
-                          selections: [{ kind: 'Field', name: { kind: 'Name', value: 'id' } }],
+                          selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
+                            { kind: 'Field', name: { kind: 'Name', value: 'id' } },
+                          ],
                         },
                       },
                     ],
@@ -3213,6 +3389,7 @@ export const AdminGameReportDocument = {
   ],
 } as unknown as DocumentNode<AdminGameReportQuery, AdminGameReportQueryVariables>
 export const ResolveGameReportDocument = {
+  __meta__: { hash: 'd68dccc26c79ca6db644cb3e5a4e24776fdcd6fbaa3a77b9d89a12e8f5110b2c' },
   kind: 'Document',
   definitions: [
     {
@@ -3245,6 +3422,7 @@ export const ResolveGameReportDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'resolveGameReport' },
@@ -3268,6 +3446,7 @@ export const ResolveGameReportDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'resolvedAt' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'resolution' } },
@@ -3277,7 +3456,10 @@ export const ResolveGameReportDocument = {
                   name: { kind: 'Name', value: 'resolver' },
                   selectionSet: {
                     kind: 'SelectionSet',
-                    selections: [{ kind: 'Field', name: { kind: 'Name', value: 'id' } }],
+                    selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
+                      { kind: 'Field', name: { kind: 'Name', value: 'id' } },
+                    ],
                   },
                 },
               ],
@@ -3289,6 +3471,7 @@ export const ResolveGameReportDocument = {
   ],
 } as unknown as DocumentNode<ResolveGameReportMutation, ResolveGameReportMutationVariables>
 export const ResolveSiblingReportsDocument = {
+  __meta__: { hash: '36c9d6e04e5d8a867513b6ded6c5c1874a58c5508257665a9797cb173b397a77' },
   kind: 'Document',
   definitions: [
     {
@@ -3321,6 +3504,7 @@ export const ResolveSiblingReportsDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'resolveSiblingReports' },
@@ -3348,6 +3532,7 @@ export const ResolveSiblingReportsDocument = {
   ],
 } as unknown as DocumentNode<ResolveSiblingReportsMutation, ResolveSiblingReportsMutationVariables>
 export const GamesPageContentDocument = {
+  __meta__: { hash: 'e938d2ee6e85aa70350ffec00535de35ff8ff769de1c0cdf92c5575118a885f7' },
   kind: 'Document',
   definitions: [
     {
@@ -3357,6 +3542,7 @@ export const GamesPageContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'FragmentSpread', name: { kind: 'Name', value: 'LiveGames_FeedFragment' } },
         ],
       },
@@ -3368,12 +3554,14 @@ export const GamesPageContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'user' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
               ],
@@ -3390,6 +3578,7 @@ export const GamesPageContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           {
             kind: 'Field',
@@ -3397,6 +3586,7 @@ export const GamesPageContentDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                 {
@@ -3405,6 +3595,7 @@ export const GamesPageContentDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image256Url' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image512Url' } },
@@ -3434,12 +3625,14 @@ export const GamesPageContentDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       {
                         kind: 'Field',
                         name: { kind: 'Name', value: 'gameSourceExtra' },
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'matchmakingType' } },
                           ],
                         },
@@ -3460,6 +3653,7 @@ export const GamesPageContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           { kind: 'Field', name: { kind: 'Name', value: 'startTime' } },
           {
@@ -3468,6 +3662,7 @@ export const GamesPageContentDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                 {
@@ -3476,6 +3671,7 @@ export const GamesPageContentDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image256Url' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image512Url' } },
@@ -3505,12 +3701,14 @@ export const GamesPageContentDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       {
                         kind: 'Field',
                         name: { kind: 'Name', value: 'gameSourceExtra' },
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'matchmakingType' } },
                           ],
                         },
@@ -3521,12 +3719,14 @@ export const GamesPageContentDocument = {
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             {
                               kind: 'Field',
                               name: { kind: 'Name', value: 'user' },
                               selectionSet: {
                                 kind: 'SelectionSet',
                                 selections: [
+                                  { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                                   { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                                 ],
                               },
@@ -3558,12 +3758,14 @@ export const GamesPageContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'liveGames' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 {
                   kind: 'FragmentSpread',
@@ -3578,6 +3780,7 @@ export const GamesPageContentDocument = {
   ],
 } as unknown as DocumentNode<GamesPageContentQuery, GamesPageContentQueryVariables>
 export const ReportGameDocument = {
+  __meta__: { hash: '8b5650eae369df3f7f679963932a2af2207ee7af5a90a3e33df7dbe69356610b' },
   kind: 'Document',
   definitions: [
     {
@@ -3597,6 +3800,7 @@ export const ReportGameDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'reportGame' },
@@ -3609,7 +3813,10 @@ export const ReportGameDocument = {
             ],
             selectionSet: {
               kind: 'SelectionSet',
-              selections: [{ kind: 'Field', name: { kind: 'Name', value: 'id' } }],
+              selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
+                { kind: 'Field', name: { kind: 'Name', value: 'id' } },
+              ],
             },
           },
         ],
@@ -3618,6 +3825,7 @@ export const ReportGameDocument = {
   ],
 } as unknown as DocumentNode<ReportGameMutation, ReportGameMutationVariables>
 export const HomePageContentDocument = {
+  __meta__: { hash: '5364c8306717d8ce446fc03f81d24aeb7b5795c5c08e91e7d8215dfe95d98282' },
   kind: 'Document',
   definitions: [
     {
@@ -3627,12 +3835,14 @@ export const HomePageContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'urgentMessage' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 {
                   kind: 'FragmentSpread',
                   name: { kind: 'Name', value: 'UrgentMessage_HomeDisplayFragment' },
@@ -3652,6 +3862,7 @@ export const HomePageContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'name' } },
           { kind: 'Field', name: { kind: 'Name', value: 'badgeUrl' } },
         ],
@@ -3664,6 +3875,7 @@ export const HomePageContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           { kind: 'Field', name: { kind: 'Name', value: 'name' } },
           { kind: 'Field', name: { kind: 'Name', value: 'matchmakingType' } },
@@ -3680,6 +3892,7 @@ export const HomePageContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           { kind: 'Field', name: { kind: 'Name', value: 'title' } },
           { kind: 'Field', name: { kind: 'Name', value: 'message' } },
@@ -3693,12 +3906,14 @@ export const HomePageContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'activeLeagues' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 {
                   kind: 'FragmentSpread',
@@ -3713,6 +3928,7 @@ export const HomePageContentDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 {
                   kind: 'FragmentSpread',
@@ -3731,6 +3947,7 @@ export const HomePageContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'newsPosts' },
@@ -3744,18 +3961,21 @@ export const HomePageContentDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 {
                   kind: 'Field',
                   name: { kind: 'Name', value: 'edges' },
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       {
                         kind: 'Field',
                         name: { kind: 'Name', value: 'node' },
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'title' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'summary' } },
@@ -3777,6 +3997,7 @@ export const HomePageContentDocument = {
   ],
 } as unknown as DocumentNode<HomePageContentQuery, HomePageContentQueryVariables>
 export const HomePageLiveContentDocument = {
+  __meta__: { hash: 'ef8e860cd8a84d61e8ca23c47f133dc518e6a0ab4beaf97c745e77936854b3af' },
   kind: 'Document',
   definitions: [
     {
@@ -3786,6 +4007,7 @@ export const HomePageLiveContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'FragmentSpread', name: { kind: 'Name', value: 'LiveGames_FeedFragment' } },
           { kind: 'FragmentSpread', name: { kind: 'Name', value: 'LiveStreams_FeedFragment' } },
         ],
@@ -3798,12 +4020,14 @@ export const HomePageLiveContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'user' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
               ],
@@ -3820,6 +4044,7 @@ export const HomePageLiveContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           {
             kind: 'Field',
@@ -3827,6 +4052,7 @@ export const HomePageLiveContentDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                 {
@@ -3835,6 +4061,7 @@ export const HomePageLiveContentDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image256Url' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image512Url' } },
@@ -3864,12 +4091,14 @@ export const HomePageLiveContentDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       {
                         kind: 'Field',
                         name: { kind: 'Name', value: 'gameSourceExtra' },
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'matchmakingType' } },
                           ],
                         },
@@ -3890,6 +4119,7 @@ export const HomePageLiveContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           { kind: 'Field', name: { kind: 'Name', value: 'startTime' } },
           {
@@ -3898,6 +4128,7 @@ export const HomePageLiveContentDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                 {
@@ -3906,6 +4137,7 @@ export const HomePageLiveContentDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image256Url' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'image512Url' } },
@@ -3935,12 +4167,14 @@ export const HomePageLiveContentDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       {
                         kind: 'Field',
                         name: { kind: 'Name', value: 'gameSourceExtra' },
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'matchmakingType' } },
                           ],
                         },
@@ -3951,12 +4185,14 @@ export const HomePageLiveContentDocument = {
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             {
                               kind: 'Field',
                               name: { kind: 'Name', value: 'user' },
                               selectionSet: {
                                 kind: 'SelectionSet',
                                 selections: [
+                                  { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                                   { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                                 ],
                               },
@@ -3988,6 +4224,7 @@ export const HomePageLiveContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'twitchLogin' } },
           { kind: 'Field', name: { kind: 'Name', value: 'twitchDisplayName' } },
           { kind: 'Field', name: { kind: 'Name', value: 'title' } },
@@ -4000,6 +4237,7 @@ export const HomePageLiveContentDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
               ],
@@ -4015,12 +4253,14 @@ export const HomePageLiveContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'liveGames' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 {
                   kind: 'FragmentSpread',
@@ -4039,12 +4279,14 @@ export const HomePageLiveContentDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'liveStreams' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'twitchLogin' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'viewerCount' } },
                 {
@@ -4060,6 +4302,7 @@ export const HomePageLiveContentDocument = {
   ],
 } as unknown as DocumentNode<HomePageLiveContentQuery, HomePageLiveContentQueryVariables>
 export const NewsArchiveDocument = {
+  __meta__: { hash: 'c02da199ae46d1289bcd238d7c10b7eaf1922b348c61883e27b0dc6ef15688e0' },
   kind: 'Document',
   definitions: [
     {
@@ -4081,6 +4324,7 @@ export const NewsArchiveDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'newsPosts' },
@@ -4099,18 +4343,21 @@ export const NewsArchiveDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 {
                   kind: 'Field',
                   name: { kind: 'Name', value: 'edges' },
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       {
                         kind: 'Field',
                         name: { kind: 'Name', value: 'node' },
                         selectionSet: {
                           kind: 'SelectionSet',
                           selections: [
+                            { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'title' } },
                             { kind: 'Field', name: { kind: 'Name', value: 'summary' } },
@@ -4127,6 +4374,7 @@ export const NewsArchiveDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'hasNextPage' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'endCursor' } },
                     ],
@@ -4141,6 +4389,7 @@ export const NewsArchiveDocument = {
   ],
 } as unknown as DocumentNode<NewsArchiveQuery, NewsArchiveQueryVariables>
 export const NewsPostDocument = {
+  __meta__: { hash: '64c7152836acf651f1c889247e449bf286e35cbb82e2bbb8f2771777a8516638' },
   kind: 'Document',
   definitions: [
     {
@@ -4160,6 +4409,7 @@ export const NewsPostDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'newsPost' },
@@ -4173,6 +4423,7 @@ export const NewsPostDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'title' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'content' } },
@@ -4185,6 +4436,7 @@ export const NewsPostDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'name' } },
                     ],
@@ -4199,6 +4451,7 @@ export const NewsPostDocument = {
   ],
 } as unknown as DocumentNode<NewsPostQuery, NewsPostQueryVariables>
 export const AccountSettingsDocument = {
+  __meta__: { hash: '245684861cf1dcf4a2154b14fa7962650488cac3e8acf1f69e5fdd964b78338f' },
   kind: 'Document',
   definitions: [
     {
@@ -4208,12 +4461,14 @@ export const AccountSettingsDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'currentUser' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 {
                   kind: 'FragmentSpread',
                   name: { kind: 'Name', value: 'AccountSettings_CurrentUser' },
@@ -4231,6 +4486,7 @@ export const AccountSettingsDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           { kind: 'Field', name: { kind: 'Name', value: 'name' } },
           { kind: 'Field', name: { kind: 'Name', value: 'loginName' } },
@@ -4247,6 +4503,7 @@ export const AccountSettingsDocument = {
   ],
 } as unknown as DocumentNode<AccountSettingsQuery, AccountSettingsQueryVariables>
 export const AccountSettingsChangePasswordDocument = {
+  __meta__: { hash: '8e2a736b5e3c65d105c72eae1314f908a86376b46b498fa11c290971622cf978' },
   kind: 'Document',
   definitions: [
     {
@@ -4274,6 +4531,7 @@ export const AccountSettingsChangePasswordDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'userUpdateCurrent' },
@@ -4301,6 +4559,7 @@ export const AccountSettingsChangePasswordDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 {
                   kind: 'FragmentSpread',
                   name: { kind: 'Name', value: 'AccountSettings_CurrentUser' },
@@ -4318,6 +4577,7 @@ export const AccountSettingsChangePasswordDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           { kind: 'Field', name: { kind: 'Name', value: 'name' } },
           { kind: 'Field', name: { kind: 'Name', value: 'loginName' } },
@@ -4337,6 +4597,7 @@ export const AccountSettingsChangePasswordDocument = {
   AccountSettingsChangePasswordMutationVariables
 >
 export const AccountSettingsChangeEmailDocument = {
+  __meta__: { hash: '5f403a85ea6331e97eb6631c11bfdf5009dedafbe84f5f71ecc3abe4f0761cf0' },
   kind: 'Document',
   definitions: [
     {
@@ -4364,6 +4625,7 @@ export const AccountSettingsChangeEmailDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'userUpdateCurrent' },
@@ -4391,6 +4653,7 @@ export const AccountSettingsChangeEmailDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 {
                   kind: 'FragmentSpread',
                   name: { kind: 'Name', value: 'AccountSettings_CurrentUser' },
@@ -4408,6 +4671,7 @@ export const AccountSettingsChangeEmailDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           { kind: 'Field', name: { kind: 'Name', value: 'name' } },
           { kind: 'Field', name: { kind: 'Name', value: 'loginName' } },
@@ -4427,6 +4691,7 @@ export const AccountSettingsChangeEmailDocument = {
   AccountSettingsChangeEmailMutationVariables
 >
 export const AccountSettingsChangeDisplayNameDocument = {
+  __meta__: { hash: '6b97abf4f5d3140013b4809beaee3bb8f38ace9da592605655b80c9c6c869779' },
   kind: 'Document',
   definitions: [
     {
@@ -4454,6 +4719,7 @@ export const AccountSettingsChangeDisplayNameDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'userUpdateCurrent' },
@@ -4481,6 +4747,7 @@ export const AccountSettingsChangeDisplayNameDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 {
                   kind: 'FragmentSpread',
                   name: { kind: 'Name', value: 'AccountSettings_CurrentUser' },
@@ -4498,6 +4765,7 @@ export const AccountSettingsChangeDisplayNameDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           { kind: 'Field', name: { kind: 'Name', value: 'name' } },
           { kind: 'Field', name: { kind: 'Name', value: 'loginName' } },
@@ -4517,6 +4785,7 @@ export const AccountSettingsChangeDisplayNameDocument = {
   AccountSettingsChangeDisplayNameMutationVariables
 >
 export const AccountSettingsChangeLoginNameDocument = {
+  __meta__: { hash: '249bd21b36e0f35efce62511b7afdc237a5f01699735de8e47e933b44fc487bc' },
   kind: 'Document',
   definitions: [
     {
@@ -4544,6 +4813,7 @@ export const AccountSettingsChangeLoginNameDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'userUpdateCurrent' },
@@ -4571,6 +4841,7 @@ export const AccountSettingsChangeLoginNameDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 {
                   kind: 'FragmentSpread',
                   name: { kind: 'Name', value: 'AccountSettings_CurrentUser' },
@@ -4588,6 +4859,7 @@ export const AccountSettingsChangeLoginNameDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           { kind: 'Field', name: { kind: 'Name', value: 'name' } },
           { kind: 'Field', name: { kind: 'Name', value: 'loginName' } },
@@ -4607,6 +4879,7 @@ export const AccountSettingsChangeLoginNameDocument = {
   AccountSettingsChangeLoginNameMutationVariables
 >
 export const ConnectionSettingsDocument = {
+  __meta__: { hash: 'ddf8ddc632625cde0fa1c3f14819d14c8bf60af61be61aa9543602685a332d47' },
   kind: 'Document',
   definitions: [
     {
@@ -4616,12 +4889,14 @@ export const ConnectionSettingsDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'myTwitchConnection' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'twitchUserId' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'twitchLogin' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'twitchDisplayName' } },
@@ -4635,6 +4910,7 @@ export const ConnectionSettingsDocument = {
   ],
 } as unknown as DocumentNode<ConnectionSettingsQuery, ConnectionSettingsQueryVariables>
 export const ConnectionSettingsStartTwitchLinkDocument = {
+  __meta__: { hash: 'b0c170e6cf1acc9c28800bccee1a195e00bf4d7501e9b23b32591aed0773be8f' },
   kind: 'Document',
   definitions: [
     {
@@ -4654,6 +4930,7 @@ export const ConnectionSettingsStartTwitchLinkDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'twitchStartLink' },
@@ -4666,7 +4943,10 @@ export const ConnectionSettingsStartTwitchLinkDocument = {
             ],
             selectionSet: {
               kind: 'SelectionSet',
-              selections: [{ kind: 'Field', name: { kind: 'Name', value: 'url' } }],
+              selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
+                { kind: 'Field', name: { kind: 'Name', value: 'url' } },
+              ],
             },
           },
         ],
@@ -4678,6 +4958,7 @@ export const ConnectionSettingsStartTwitchLinkDocument = {
   ConnectionSettingsStartTwitchLinkMutationVariables
 >
 export const ConnectionSettingsCompleteTwitchLinkDocument = {
+  __meta__: { hash: 'b334d2e3399ae76b85a189ba6cff12fde736967af1b1e2b52cbd3248e2222687' },
   kind: 'Document',
   definitions: [
     {
@@ -4705,6 +4986,7 @@ export const ConnectionSettingsCompleteTwitchLinkDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'twitchCompleteLink' },
@@ -4723,6 +5005,7 @@ export const ConnectionSettingsCompleteTwitchLinkDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'twitchUserId' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'twitchLogin' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'twitchDisplayName' } },
@@ -4739,6 +5022,7 @@ export const ConnectionSettingsCompleteTwitchLinkDocument = {
   ConnectionSettingsCompleteTwitchLinkMutationVariables
 >
 export const ConnectionSettingsUnlinkTwitchDocument = {
+  __meta__: { hash: '99524643633e55c8fdaafa026f4c95a25ba855b50f08cdda857ac5d8efa44486' },
   kind: 'Document',
   definitions: [
     {
@@ -4747,7 +5031,10 @@ export const ConnectionSettingsUnlinkTwitchDocument = {
       name: { kind: 'Name', value: 'ConnectionSettingsUnlinkTwitch' },
       selectionSet: {
         kind: 'SelectionSet',
-        selections: [{ kind: 'Field', name: { kind: 'Name', value: 'twitchUnlink' } }],
+        selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
+          { kind: 'Field', name: { kind: 'Name', value: 'twitchUnlink' } },
+        ],
       },
     },
   ],
@@ -4756,6 +5043,7 @@ export const ConnectionSettingsUnlinkTwitchDocument = {
   ConnectionSettingsUnlinkTwitchMutationVariables
 >
 export const LiveUserIdsDocument = {
+  __meta__: { hash: '49df3cb6aa6855307052bf073301d89eb7c1c43ff17e4203d8782f026d80b0e5' },
   kind: 'Document',
   definitions: [
     {
@@ -4764,12 +5052,16 @@ export const LiveUserIdsDocument = {
       name: { kind: 'Name', value: 'LiveUserIds' },
       selectionSet: {
         kind: 'SelectionSet',
-        selections: [{ kind: 'Field', name: { kind: 'Name', value: 'liveStreamUserIds' } }],
+        selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
+          { kind: 'Field', name: { kind: 'Name', value: 'liveStreamUserIds' } },
+        ],
       },
     },
   ],
 } as unknown as DocumentNode<LiveUserIdsQuery, LiveUserIdsQueryVariables>
 export const BlockStreamDocument = {
+  __meta__: { hash: '0d541623d47d1f46d0af9a67e2ea67821c7c99c720bb3bd23bca85c0885a190a' },
   kind: 'Document',
   definitions: [
     {
@@ -4789,6 +5081,7 @@ export const BlockStreamDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'blockStream' },
@@ -4806,6 +5099,7 @@ export const BlockStreamDocument = {
   ],
 } as unknown as DocumentNode<BlockStreamMutation, BlockStreamMutationVariables>
 export const UnblockStreamDocument = {
+  __meta__: { hash: '0209e6ecd705e045ccdcf567424946bf36b7fb51263257db5b89c6a07b1aace2' },
   kind: 'Document',
   definitions: [
     {
@@ -4825,6 +5119,7 @@ export const UnblockStreamDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'unblockStream' },
@@ -4842,6 +5137,7 @@ export const UnblockStreamDocument = {
   ],
 } as unknown as DocumentNode<UnblockStreamMutation, UnblockStreamMutationVariables>
 export const LiveStreamsPageDocument = {
+  __meta__: { hash: '03d913b3b719837c96aa80d20b472d0df1d7afde1680e99328bbb10f869f902d' },
   kind: 'Document',
   definitions: [
     {
@@ -4851,6 +5147,7 @@ export const LiveStreamsPageDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'FragmentSpread', name: { kind: 'Name', value: 'LiveStreams_FeedFragment' } },
         ],
       },
@@ -4862,6 +5159,7 @@ export const LiveStreamsPageDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'twitchLogin' } },
           { kind: 'Field', name: { kind: 'Name', value: 'twitchDisplayName' } },
           { kind: 'Field', name: { kind: 'Name', value: 'title' } },
@@ -4874,6 +5172,7 @@ export const LiveStreamsPageDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'name' } },
               ],
@@ -4889,12 +5188,14 @@ export const LiveStreamsPageDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'liveStreams' },
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'twitchLogin' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'viewerCount' } },
                 {
@@ -4910,6 +5211,7 @@ export const LiveStreamsPageDocument = {
   ],
 } as unknown as DocumentNode<LiveStreamsPageQuery, LiveStreamsPageQueryVariables>
 export const UserNameAuditHistoryDocument = {
+  __meta__: { hash: '831059fa97da3934395a5db24afcba9dc0b29e3bb7a6b04e3100440d4c6ccd21' },
   kind: 'Document',
   definitions: [
     {
@@ -4949,6 +5251,7 @@ export const UserNameAuditHistoryDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'userDisplayNameAuditHistory' },
@@ -4972,6 +5275,7 @@ export const UserNameAuditHistoryDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'oldName' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'newName' } },
@@ -4981,7 +5285,10 @@ export const UserNameAuditHistoryDocument = {
                   name: { kind: 'Name', value: 'changedByUser' },
                   selectionSet: {
                     kind: 'SelectionSet',
-                    selections: [{ kind: 'Field', name: { kind: 'Name', value: 'id' } }],
+                    selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
+                      { kind: 'Field', name: { kind: 'Name', value: 'id' } },
+                    ],
                   },
                 },
                 { kind: 'Field', name: { kind: 'Name', value: 'changeReason' } },
@@ -5014,6 +5321,7 @@ export const UserNameAuditHistoryDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'oldLoginName' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'newLoginName' } },
@@ -5030,6 +5338,7 @@ export const UserNameAuditHistoryDocument = {
   ],
 } as unknown as DocumentNode<UserNameAuditHistoryQuery, UserNameAuditHistoryQueryVariables>
 export const AdminUserProfileDocument = {
+  __meta__: { hash: 'f17e523b1c36afd750e6f4679eca2cc313e58c2622854cf54e0fd16e961890d2' },
   kind: 'Document',
   definitions: [
     {
@@ -5057,6 +5366,7 @@ export const AdminUserProfileDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'user' },
@@ -5070,6 +5380,7 @@ export const AdminUserProfileDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 {
                   kind: 'FragmentSpread',
@@ -5104,6 +5415,7 @@ export const AdminUserProfileDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           {
             kind: 'Field',
@@ -5111,6 +5423,7 @@ export const AdminUserProfileDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'editPermissions' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'debug' } },
@@ -5138,6 +5451,7 @@ export const AdminUserProfileDocument = {
   ],
 } as unknown as DocumentNode<AdminUserProfileQuery, AdminUserProfileQueryVariables>
 export const AdminUpdateUserPermissionsDocument = {
+  __meta__: { hash: '5b144236b1d3f4835d4cf21e65d3f0ccc2402d0ea310570a4d0e6f0ed3ff788f' },
   kind: 'Document',
   definitions: [
     {
@@ -5165,6 +5479,7 @@ export const AdminUpdateUserPermissionsDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'userUpdatePermissions' },
@@ -5183,6 +5498,7 @@ export const AdminUpdateUserPermissionsDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 {
                   kind: 'FragmentSpread',
                   name: { kind: 'Name', value: 'AdminUserProfile_Permissions' },
@@ -5200,6 +5516,7 @@ export const AdminUpdateUserPermissionsDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           { kind: 'Field', name: { kind: 'Name', value: 'id' } },
           {
             kind: 'Field',
@@ -5207,6 +5524,7 @@ export const AdminUpdateUserPermissionsDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'editPermissions' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'debug' } },
@@ -5237,6 +5555,7 @@ export const AdminUpdateUserPermissionsDocument = {
   AdminUpdateUserPermissionsMutationVariables
 >
 export const UserProfileOverlayLiveDocument = {
+  __meta__: { hash: 'd84653cba86002c4d4b65ac08c189669989fce40299b9fda03ed2c35aa264c6f' },
   kind: 'Document',
   definitions: [
     {
@@ -5256,6 +5575,7 @@ export const UserProfileOverlayLiveDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'user' },
@@ -5269,6 +5589,7 @@ export const UserProfileOverlayLiveDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 {
                   kind: 'Field',
@@ -5276,6 +5597,7 @@ export const UserProfileOverlayLiveDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'twitchLogin' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'title' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'viewerCount' } },
@@ -5291,6 +5613,7 @@ export const UserProfileOverlayLiveDocument = {
   ],
 } as unknown as DocumentNode<UserProfileOverlayLiveQuery, UserProfileOverlayLiveQueryVariables>
 export const UserRankedModesDocument = {
+  __meta__: { hash: '6bee9c80e140f5f8bc9c960d750177e7bf59724777b9e879e356eeb649d6f53e' },
   kind: 'Document',
   definitions: [
     {
@@ -5310,6 +5633,7 @@ export const UserRankedModesDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'userRankedModes' },
@@ -5323,6 +5647,7 @@ export const UserRankedModesDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'matchmakingType' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'totalGames' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'wins' } },
@@ -5338,6 +5663,7 @@ export const UserRankedModesDocument = {
   ],
 } as unknown as DocumentNode<UserRankedModesQuery, UserRankedModesQueryVariables>
 export const UserRatingHistoryDocument = {
+  __meta__: { hash: 'e5a6ebb41581251714783720ddd9bf154780d95a0c9c3f64f5c1590983606834' },
   kind: 'Document',
   definitions: [
     {
@@ -5365,6 +5691,7 @@ export const UserRatingHistoryDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'userRatingHistory' },
@@ -5383,6 +5710,7 @@ export const UserRatingHistoryDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'matchmakingType' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'totalGames' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'downsampled' } },
@@ -5392,6 +5720,7 @@ export const UserRatingHistoryDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'changeDate' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'rating' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'points' } },
@@ -5408,6 +5737,7 @@ export const UserRatingHistoryDocument = {
   ],
 } as unknown as DocumentNode<UserRatingHistoryQuery, UserRatingHistoryQueryVariables>
 export const UserProfileTwitchDocument = {
+  __meta__: { hash: '22104047429f8ae2299c4f37d53ad61f041833ae4947e52c5e8c6c88e5250c25' },
   kind: 'Document',
   definitions: [
     {
@@ -5427,6 +5757,7 @@ export const UserProfileTwitchDocument = {
       selectionSet: {
         kind: 'SelectionSet',
         selections: [
+          { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
           {
             kind: 'Field',
             name: { kind: 'Name', value: 'user' },
@@ -5440,6 +5771,7 @@ export const UserProfileTwitchDocument = {
             selectionSet: {
               kind: 'SelectionSet',
               selections: [
+                { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                 { kind: 'Field', name: { kind: 'Name', value: 'id' } },
                 {
                   kind: 'Field',
@@ -5447,6 +5779,7 @@ export const UserProfileTwitchDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'twitchLogin' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'twitchDisplayName' } },
                     ],
@@ -5458,6 +5791,7 @@ export const UserProfileTwitchDocument = {
                   selectionSet: {
                     kind: 'SelectionSet',
                     selections: [
+                      { kind: 'Field', name: { kind: 'Name', value: '__typename' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'twitchLogin' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'title' } },
                       { kind: 'Field', name: { kind: 'Name', value: 'gameName' } },
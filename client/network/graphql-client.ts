@@ -5,6 +5,7 @@ import { SbUserId } from '../../common/users/sb-user-id'
 import schema from '../gql/schema.json'
 import { CREDENTIAL_STORAGE } from './fetch'
 import { requestPolicyExchange } from './improved-request-policy-exchange'
+import { persistedQueryExchange } from './persisted-query-exchange'
 
 export function createGraphqlClient(
   serverConfig: ServerConfig,
@@ -23,6 +24,7 @@ export function createGraphqlClient(
         ttl: 60 * 1000,
         shouldUpgrade: operation => operation.context.requestPolicy !== 'cache-only',
       }),
+      persistedQueryExchange,
       cacheExchange({ schema, updates: cacheUpdates, keys: cacheKeys }),
       fetchExchange,
     ],
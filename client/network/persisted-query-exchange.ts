@@ -0,0 +1,47 @@
+import type { Exchange, Operation } from '@urql/core'
+import { makeOperation } from '@urql/core'
+import { map, pipe } from 'wonka'
+
+/**
+ * The metadata GraphQL codegen embeds in documents when persisted documents are enabled (see
+ * `graphql-codegen.ts`). The hash is the key of the document in `persisted-documents.json`.
+ */
+interface PersistedDocumentMeta {
+  __meta__?: {
+    hash?: string
+  }
+}
+
+/**
+ * Exchange that tags operations with the hash of their persisted document (in the
+ * `persistedQuery` extension), so the server can run them even when it only accepts queries from
+ * its allowlist. This must come before any exchange that rewrites the document (e.g. the cache
+ * exchange), since the hash is only present on the original.
+ */
+export const persistedQueryExchange: Exchange =
+  ({ forward }) =>
+  ops$ => {
+    const processIncomingOperation = (operation: Operation): Operation => {
+      if (operation.kind === 'teardown') {
+        return operation
+      }
+      const hash = (operation.query as PersistedDocumentMeta).__meta__?.hash
+      if (!hash) {
+        return operation
+      }
+
+      return makeOperation(
+        operation.kind,
+        {
+          ...operation,
+          extensions: {
+            ...operation.extensions,
+            persistedQuery: { version: 1, sha256Hash: hash },
+          },
+        },
+        operation.context,
+      )
+    }
+
+    return forward(pipe(ops$, map(processIncomingOperation)))
+  }
@@ -12,6 +12,20 @@ export interface CheckAllowedNameResponse {
   reason?: RestrictedNameReason
 }
 
+export interface ImageUploadResponse {
+  /** The path to save to refer to this image (e.g. as a news post's `coverImagePath`). */
+  path: string
+  url: string
+  /** A JPEG version of the image, for places that can't display WebP. */
+  jpegUrl: string
+  /** The URL of the half-resolution variant, for kinds that have one. */
+  smallUrl?: string
+}
+
+export interface MapUploadResponse {
+  id: TypeshareTypes.SbMapId
+}
+
 /**
  * All of the matchmaking types that we support. These values match the enum values used in the
  * database.
@@ -131,6 +145,30 @@ export type PublishedGameReportMessage =
       }
     }
 
+/**
+ * Messages about live-stream state, published on the `liveStream` channel. These only say what to
+ * re-check rather than carrying the new state, so listeners always act on what's in Redis, even if
+ * messages arrive out of order.
+ */
+export type PublishedLiveStreamMessage =
+  /**
+   * The live state of these users' streams was written (they went live or offline, or their
+   * stream details were refreshed).
+   */
+  | {
+      type: 'streamsChanged'
+      data: {
+        userIds: TypeshareTypes.SbUserId[]
+      }
+    }
+  /** These users were blocked from or unblocked in the live-streams feed. */
+  | {
+      type: 'feedBlocksChanged'
+      data: {
+        userIds: TypeshareTypes.SbUserId[]
+      }
+    }
+
 /** Messages published to the Redis `"matchmaking"` channel. */
 export type PublishedMatchmakingMessage = { type: 'matchFound'; data: MatchFoundMessage }
 
@@ -139,6 +177,7 @@ export type PublishedMessage =
   | { type: 'user'; data: PublishedUserMessage }
   | { type: 'matchmaking'; data: PublishedMatchmakingMessage }
   | { type: 'gameReport'; data: PublishedGameReportMessage }
+  | { type: 'liveStream'; data: PublishedLiveStreamMessage }
 
 export type PublishedNewsMessage =
   { type: 'urgentMessageChanged'; data: undefined } | { type: 'newsPostsChanged'; data: undefined }
@@ -158,6 +197,18 @@ export type PublishedUserMessage =
         email: string
       }
     }
+  /**
+   * A user was banned for evading another account's ban. `banned_user_ids` includes the user
+   * along with any accounts connected to it, which were banned at the same time.
+   */
+  | {
+      type: 'bannedForBanEvasion'
+      data: {
+        userId: TypeshareTypes.SbUserId
+        bannedUserIds: TypeshareTypes.SbUserId[]
+        banLengthHours: number
+      }
+    }
 
 /**
  * Error codes the matchmaking HTTP API returns in the `code` field of its JSON error bodies. This
@@ -175,4 +226,9 @@ export enum RsMatchmakerErrorCode {
   InvalidTicket = 'invalidTicket',
   /** A requeue ticket was issued by a previous process — server-rs has restarted since. */
   StaleTicket = 'staleTicket',
+  /**
+   * The queue request's map selections for a mode didn't fit that mode's active map pool (e.g.
+   * a map that isn't in the pool, or more selections than the pool allows).
+   */
+  InvalidMapSelections = 'invalidMapSelections',
 }
@@ -63,7 +63,7 @@ SET IMG=%NAME%:%gitsha%
 SET VERSION_TAG=%NAME%:%version%
 SET LATEST_TAG=%NAME%:latest
 
-docker buildx build --platform linux/amd64 -t %IMG% -t %VERSION_TAG% -t %LATEST_TAG% --push ./server-rs/
+docker buildx build --platform linux/amd64 -t %IMG% -t %VERSION_TAG% -t %LATEST_TAG% --build-context gql=./client/gql --push ./server-rs/
 if errorlevel 1 (
   echo Error building the shieldbattery/server-rs image
   goto exit
@@ -4,31 +4,62 @@ import path from 'path'
 import React from 'react'
 import { render } from 'react-email'
 import { EmailProps } from './email-props'
+import i18n, { i18nextPromise } from './i18n/i18next'
 
 const OUTPUT_DIR = path.resolve(__dirname, '..', 'server', 'email')
+/**
+ * Where the templates used by server-rs are written. These are rendered in every language we have
+ * email translations for, with a directory per language (matching the `locales` directory).
+ */
+const RUST_OUTPUT_DIR = path.resolve(__dirname, '..', 'server-rs', 'templates', 'email')
 
 async function generateTemplates() {
   console.log('Generating email templates...\n')
 
+  await i18nextPromise
+  const languages = (
+    await fs.readdir(path.resolve(__dirname, 'locales'), { withFileTypes: true })
+  )
+    .filter(entry => entry.isDirectory())
+    .map(entry => entry.name)
+    .sort()
+
   const templateGlob = path.resolve(__dirname, 'templates', '*.tsx')
   const templateFiles = await glob(templateGlob, { windowsPathsNoEscape: true })
 
-  const oldTemplatesGlob = path.resolve(OUTPUT_DIR, '*.html')
-  const oldTemplateFiles = await glob(oldTemplatesGlob, { windowsPathsNoEscape: true })
+  const oldTemplatesGlobs = [
+    path.resolve(OUTPUT_DIR, '*.html'),
+    path.resolve(RUST_OUTPUT_DIR, '*', '*.html'),
+  ]
+  const oldTemplateFiles = await glob(oldTemplatesGlobs, { windowsPathsNoEscape: true })
 
   await Promise.all(oldTemplateFiles.map(f => fs.unlink(f)))
 
   for (const file of templateFiles) {
     const fileName = path.basename(file, path.extname(file))
-    const outputFile = path.resolve(OUTPUT_DIR, fileName + '.html')
-    console.log(`templates/${fileName} -> ../server/email/${fileName}.html`)
-
     const Component = ((await import(file)) as any)
       .default as React.JSXElementConstructor<EmailProps>
-    // TODO(tec27): Render in all supported languages
-    const html = await render(<Component lang='en' dir='ltr' />)
 
-    await fs.writeFile(outputFile, html, { encoding: 'utf-8' })
+    for (const lang of languages) {
+      await i18n.changeLanguage(lang)
+      const html = await render(<Component lang={lang} dir='ltr' />)
+
+      // The Node server uploads its templates to Mailgun, which only supports a single language
+      if (lang === 'en') {
+        const outputFile = path.resolve(OUTPUT_DIR, fileName + '.html')
+        console.log(`templates/${fileName} -> ../server/email/${fileName}.html`)
+        await fs.writeFile(outputFile, html, { encoding: 'utf-8' })
+      }
+
+      const rustOutputDir = path.resolve(RUST_OUTPUT_DIR, lang)
+      await fs.mkdir(rustOutputDir, { recursive: true })
+      console.log(
+        `templates/${fileName} -> ../server-rs/templates/email/${lang}/${fileName}.html`,
+      )
+      await fs.writeFile(path.resolve(rustOutputDir, fileName + '.html'), html, {
+        encoding: 'utf-8',
+      })
+    }
   }
 
   console.log('\nDone!')
@@ -0,0 +1,52 @@
+import { Section } from 'react-email'
+import { TransWithoutContext as Trans } from 'react-i18next'
+import { EmailProps } from '../email-props'
+import { TransInterpolation, t } from '../i18n/i18next'
+import { EmailContainer, EmailHeading, EmailSignature, EmailText, SbEmail } from '../ui/email-ui'
+
+export default function DataExport(props: EmailProps) {
+  const title = t('dataExport.title', 'ShieldBattery Data Export Ready')
+
+  const username = '{{username}}'
+  const expiresInDays = '{{expiresInDays}}'
+
+  return (
+    <SbEmail
+      {...props}
+      title={title}
+      preview={t(
+        'dataExport.preview',
+        'The data export you requested for {{username}} on ShieldBattery is ready to download.',
+        { username },
+      )}>
+      <EmailContainer>
+        <EmailHeading>{title}</EmailHeading>
+
+        <Section>
+          <EmailText>
+            <Trans t={t} i18nKey='dataExport.intro'>
+              The data export you requested for{' '}
+              <span style={{ fontWeight: 500 }}>{{ username } as TransInterpolation}</span> on
+              ShieldBattery is ready to download.
+            </Trans>
+          </EmailText>
+
+          <EmailText>
+            <Trans t={t} i18nKey='dataExport.body'>
+              You can download it using <a href='{{{downloadUrl}}}'>this link</a>, which will
+              expire in {{ expiresInDays } as TransInterpolation} days.
+            </Trans>
+          </EmailText>
+
+          <EmailText>
+            <Trans t={t} i18nKey='dataExport.notYou'>
+              If you did not request this export, you should change your password by visiting{' '}
+              <a href='{{{HOST}}}/forgot-password'>this link</a>.
+            </Trans>
+          </EmailText>
+        </Section>
+        <EmailSignature />
+      </EmailContainer>
+    </SbEmail>
+  )
+}
@@ -1,6 +1,7 @@
 // Configuration for @graphql-codegen/cli
 
 import { CodegenConfig } from '@graphql-codegen/cli'
+import { addTypenameSelectionDocumentTransform } from '@graphql-codegen/client-preset'
 
 export default {
   schema: 'schema.graphql',
@@ -9,6 +10,16 @@ export default {
   generates: {
     'client/gql/': {
       preset: 'client',
+      presetConfig: {
+        // Writes `persisted-documents.json`, the allowlist of queries the server accepts when it's
+        // enforcing persisted queries (SB_GQL_ENFORCE_PERSISTED_QUERIES)
+        persistedDocuments: {
+          hashAlgorithm: 'sha256',
+        },
+      },
+      // The server runs the persisted text of a query rather than what the client sent, and the
+      // cache exchange needs `__typename` on every selection set, so the documents must include it
+      documentTransforms: [addTypenameSelectionDocumentTransform],
       hooks: {
         afterOneFileWrite: ['prettier --write'],
       },
@@ -0,0 +1,36 @@
+-- Account data export ("takeout") requests. Each row is both the user's request and the job that
+-- builds it: server-rs polls for rows that haven't completed or failed, claims one by setting
+-- `started_at` (with FOR UPDATE SKIP LOCKED, so multiple instances don't pick the same row), builds a
+-- zip of the user's data, uploads it to the file store and emails a signed download link. See the
+-- data_export module in server-rs.
+CREATE TABLE user_data_exports (
+  id uuid PRIMARY KEY DEFAULT sb_uuid(),
+  -- CASCADE: an export has no purpose once the account it's for is gone.
+  user_id integer NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  requested_at timestamptz NOT NULL DEFAULT now(),
+  -- Set when a worker claims the job. A claim that's been held too long without completing is
+  -- treated as abandoned (the instance holding it died) and becomes claimable again.
+  started_at timestamptz,
+  completed_at timestamptz,
+  -- Set once the job has exhausted its attempts; such a request is never retried and the user is
+  -- free to request a new export immediately.
+  failed_at timestamptz,
+  attempts integer NOT NULL DEFAULT 0,
+  -- The file store path of the built archive. Kept so archives can be cleaned up after their
+  -- download links expire.
+  file_path text,
+  -- The last error encountered building this export, for debugging.
+  error text
+);
+
+-- Per-user "latest request" lookups (rate limiting and the status query).
+CREATE INDEX user_data_exports_user_requested_index ON user_data_exports (user_id, requested_at DESC);
+-- A user can only have one export queued at a time. This also serves the job queue (only rows that
+-- still need processing), and is partial so it stays tiny.
+CREATE UNIQUE INDEX user_data_exports_pending_index ON user_data_exports (user_id)
+  WHERE completed_at IS NULL AND failed_at IS NULL;
+-- Lets server-rs find export archives whose download links have expired so it can delete them from
+-- the file store. `file_path` is cleared once an archive is deleted, so this only covers archives
+-- that still exist.
+CREATE INDEX user_data_exports_archive_index ON user_data_exports (completed_at)
+  WHERE file_path IS NOT NULL;
@@ -0,0 +1,23 @@
+-- User-initiated account deletion. A request is scheduled for after a cooling-off period; once that
+-- elapses, server-rs anonymizes the account in place (see the users::deletion module): PII is
+-- scrubbed, the names are replaced with tombstones, credentials, sessions and the Twitch
+-- connection are removed. The `users` row itself is kept so that games, ratings and reports that
+-- reference it (including other players' histories) stay intact.
+CREATE TABLE user_deletion_requests (
+  -- A user can only have one deletion in flight, and once completed the account can't request
+  -- another, so the user id is the primary key.
+  user_id integer PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+  requested_at timestamptz NOT NULL DEFAULT now(),
+  -- When the account becomes eligible for anonymization. Cancelling before this deletes the row.
+  scheduled_for timestamptz NOT NULL,
+  -- Set when the anonymization has run. The row is kept afterwards as a record that it happened.
+  completed_at timestamptz
+);
+
+-- The job queue: requests that haven't run yet, by when they're due.
+CREATE INDEX user_deletion_requests_pending_index ON user_deletion_requests (scheduled_for)
+  WHERE completed_at IS NULL;
+
+-- Marks accounts that have been anonymized, so they can be displayed (and excluded) accordingly.
+-- Adding a nullable column with no default is metadata-only, so this doesn't rewrite the table.
+ALTER TABLE users ADD COLUMN deleted_at timestamptz;
@@ -0,0 +1,23 @@
+-- External identities (Discord, Google, GitHub) attached to a ShieldBattery account via OAuth (see
+-- the oauth module in server-rs). A linked identity can be used to sign in instead of a password.
+-- As with twitch_connections, only the provider's stable identity is stored: the access tokens are
+-- used once to look up who the user is and then discarded.
+CREATE TYPE oauth_provider AS ENUM ('discord', 'google', 'github');
+
+CREATE TABLE user_oauth_identities (
+  user_id integer NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  provider oauth_provider NOT NULL,
+  -- The provider's stable, immutable id for the account (Discord snowflake, OIDC `sub`, GitHub
+  -- numeric id). Usernames and emails can change, so sign-in only ever matches on this.
+  provider_user_id text NOT NULL,
+  -- The provider-side username and email, cached for display and refreshed on each sign-in.
+  provider_username text NOT NULL,
+  email text,
+  linked_at timestamptz NOT NULL DEFAULT now(),
+  last_login_at timestamptz,
+  -- One identity per provider per SB user.
+  PRIMARY KEY (user_id, provider),
+  -- A single external account can't be attached to two different SB accounts, and this is the
+  -- lookup used when signing in.
+  UNIQUE (provider, provider_user_id)
+);
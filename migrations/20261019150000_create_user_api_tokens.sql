@@ -0,0 +1,32 @@
+-- Personal API tokens, which let users give third-party tools and bots scoped access to the
+-- GraphQL API on their behalf (see the users::api_tokens module in server-rs). Only a hash of each
+-- token is stored; the token itself is shown to the user once, when it's created.
+CREATE TYPE api_token_scope AS ENUM (
+  'read_profile',
+  'read_games',
+  'read_ratings',
+  'write_game_reports'
+);
+
+CREATE TABLE user_api_tokens (
+  id uuid PRIMARY KEY DEFAULT sb_uuid(),
+  user_id integer NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  -- A user-chosen label so they can tell their tokens apart.
+  name text NOT NULL,
+  -- SHA-256 of the full token. Tokens are high-entropy random values, so a fast unsalted hash is
+  -- sufficient and lets requests look up their token directly.
+  token_hash bytea NOT NULL UNIQUE,
+  -- The start of the token, kept so the user can recognize it in their token list.
+  token_prefix text NOT NULL,
+  scopes api_token_scope[] NOT NULL,
+  -- How many requests per minute the token may make. Defaults to something reasonable for a bot;
+  -- staff can raise it for specific tools.
+  rate_limit_per_minute integer NOT NULL DEFAULT 60,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  expires_at timestamptz,
+  -- Only updated about once a minute, so this is approximate.
+  last_used_at timestamptz,
+  revoked_at timestamptz
+);
+
+CREATE INDEX user_api_tokens_user_id_index ON user_api_tokens (user_id);
@@ -0,0 +1,14 @@
+-- Named campaigns (e.g. a tournament or a streamer partnership) that signup codes can be created
+-- in bulk for, so we can see how many people each one brought in and whether they stuck around.
+CREATE TABLE signup_code_campaigns (
+  id uuid PRIMARY KEY DEFAULT sb_uuid(),
+  name text NOT NULL UNIQUE,
+  description text,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  created_by integer REFERENCES users (id) ON DELETE SET NULL
+);
+
+ALTER TABLE user_signup_codes
+ADD COLUMN campaign_id uuid REFERENCES signup_code_campaigns (id) ON DELETE SET NULL;
+
+CREATE INDEX user_signup_codes_campaign_id_idx ON user_signup_codes (campaign_id);
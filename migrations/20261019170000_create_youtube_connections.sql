@@ -0,0 +1,15 @@
+-- Persistent link between a ShieldBattery user and their YouTube channel, established via Google
+-- OAuth (see the youtube GraphQL module in server-rs). Like twitch_connections, only the channel's
+-- identity is stored: live state is tracked in Redis (alongside Twitch streams), and OAuth tokens
+-- aren't persisted since live polling only needs our API key plus the channel id.
+CREATE TABLE youtube_connections (
+  user_id integer PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+  -- YouTube's stable channel id (UC...). UNIQUE so a channel can't be claimed by two SB accounts.
+  channel_id text NOT NULL UNIQUE,
+  -- The channel's handle (without the leading @, or the channel id if it has none) and title,
+  -- cached for display and refreshed periodically.
+  channel_login text NOT NULL,
+  channel_display_name text NOT NULL,
+  linked_at timestamptz NOT NULL DEFAULT now(),
+  updated_at timestamptz NOT NULL DEFAULT now()
+);
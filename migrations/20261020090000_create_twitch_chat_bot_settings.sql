@@ -0,0 +1,15 @@
+-- Per-streamer settings for the Twitch chat bot, which announces a linked streamer's matchmaking
+-- games (start and result) in their Twitch chat (see the twitch_chat module in server-rs). The bot
+-- is opt-in, so a user with no row here (or with enabled = false) gets no announcements.
+CREATE TABLE twitch_chat_bot_settings (
+  user_id integer PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+  enabled boolean NOT NULL DEFAULT false,
+  -- Message templates with {placeholder} substitutions. NULL uses the built-in default message.
+  start_template text,
+  result_template text,
+  updated_at timestamptz NOT NULL DEFAULT now()
+);
+
+-- The result announcer polls for new rating changes by date, joined against opted-in streamers.
+CREATE INDEX twitch_chat_bot_settings_enabled_index ON twitch_chat_bot_settings (user_id)
+  WHERE enabled;
@@ -0,0 +1,40 @@
+-- Historical record of linked streamers' broadcasts, built from the periodic live-stream refresh
+-- (see the stream_analytics module in server-rs). The live state itself stays in Redis; this is
+-- only for analytics.
+CREATE TYPE streaming_provider AS ENUM ('twitch', 'youtube');
+
+CREATE TABLE stream_sessions (
+  id uuid PRIMARY KEY DEFAULT sb_uuid(),
+  user_id integer NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  provider streaming_provider NOT NULL,
+  channel_id text NOT NULL,
+  -- When the broadcast started, according to the provider. Stable for the life of a broadcast, so
+  -- it identifies the session along with the user and provider.
+  started_at timestamptz NOT NULL,
+  -- The last time a refresh saw the broadcast live. Sessions are closed (ended_at set to this) by
+  -- the first refresh that doesn't see them, so durations are accurate to the refresh interval.
+  last_seen_at timestamptz NOT NULL,
+  ended_at timestamptz,
+  -- The most recent title and category. Earlier ones are in stream_session_changes.
+  title text NOT NULL,
+  category text NOT NULL,
+  -- Whether the broadcast was a StarCraft stream at any point.
+  is_starcraft boolean NOT NULL,
+  peak_viewers integer NOT NULL,
+  -- Viewer counts are sampled once per refresh; the average is viewer_sum / viewer_samples.
+  viewer_samples integer NOT NULL,
+  viewer_sum bigint NOT NULL,
+  UNIQUE (user_id, provider, started_at)
+);
+
+CREATE INDEX stream_sessions_started_at_index ON stream_sessions (started_at DESC);
+CREATE INDEX stream_sessions_open_index ON stream_sessions (provider) WHERE ended_at IS NULL;
+
+-- Every title/category a session has had, including the one it started with.
+CREATE TABLE stream_session_changes (
+  session_id uuid NOT NULL REFERENCES stream_sessions (id) ON DELETE CASCADE,
+  changed_at timestamptz NOT NULL,
+  title text NOT NULL,
+  category text NOT NULL,
+  PRIMARY KEY (session_id, changed_at)
+);
@@ -0,0 +1,19 @@
+-- Draft support for news posts. A post is only public once it's published *and* its published_at
+-- has passed, so published_at doubles as the scheduled publish time. Posts without a published_at
+-- were already hidden, so those become drafts.
+CREATE TYPE news_post_status AS ENUM ('draft', 'published');
+
+ALTER TABLE news_posts ADD COLUMN status news_post_status NOT NULL DEFAULT 'published';
+UPDATE news_posts SET status = 'draft' WHERE published_at IS NULL;
+ALTER TABLE news_posts ALTER COLUMN status SET DEFAULT 'draft';
+
+-- Secret token for sharing a not-yet-public post with people who can't manage news. NULL means the
+-- post has no preview link.
+ALTER TABLE news_posts ADD COLUMN preview_token text UNIQUE;
+
+ALTER TABLE news_post_edits ADD COLUMN status news_post_status NOT NULL DEFAULT 'published';
+UPDATE news_post_edits SET status = 'draft' WHERE published_at IS NULL;
+ALTER TABLE news_post_edits ALTER COLUMN status DROP DEFAULT;
+
+-- The edit whose content an edit restored, for edits made by restoring an earlier snapshot.
+ALTER TABLE news_post_edits ADD COLUMN restored_from uuid;
@@ -0,0 +1,16 @@
+-- Translations of news posts into the client's other languages. The post itself (in news_posts) is
+-- the default-language (English) version, and is what's shown when there's no translation for the
+-- reader's language.
+CREATE TABLE news_post_translations (
+  post_id uuid NOT NULL REFERENCES news_posts (id) ON DELETE CASCADE,
+  -- A TranslationLanguage code, e.g. 'ko' or 'zh-Hans'.
+  locale text NOT NULL,
+  title text NOT NULL,
+  summary text NOT NULL,
+  content text NOT NULL,
+  updated_at timestamptz NOT NULL DEFAULT now(),
+  PRIMARY KEY (post_id, locale)
+);
+
+-- Which translation an edit snapshotted. NULL is the default-language post.
+ALTER TABLE news_post_edits ADD COLUMN locale text;
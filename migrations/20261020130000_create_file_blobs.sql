@@ -0,0 +1,118 @@
+-- Files uploaded through the Rust server's deduplicating upload path. A blob is the group of files
+-- written for one upload (e.g. an image plus its resized and fallback variants), identified by the
+-- path that referencing rows store (its primary file's path). Blob paths are derived from their
+-- content hash, so uploading the same content again reuses the existing blob.
+--
+-- Files written before this table existed, or by the Node server, aren't tracked here and so are
+-- never garbage collected.
+CREATE TABLE file_blobs (
+  path text PRIMARY KEY,
+  -- SHA-256 of the primary file's content
+  content_hash bytea NOT NULL,
+  -- Number of referencing rows (see track_file_blob_refs below)
+  ref_count integer NOT NULL DEFAULT 0 CHECK (ref_count >= 0),
+  -- When ref_count last dropped to 0 (or when the blob was uploaded, if it has never been
+  -- referenced). NULL while the blob is referenced. Blobs are only garbage collected once they've
+  -- been unreferenced for a grace period, so uploads have time to be saved somewhere.
+  unreferenced_since timestamptz DEFAULT now(),
+  created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX file_blobs_content_hash_idx ON file_blobs (content_hash);
+CREATE INDEX file_blobs_unreferenced_idx ON file_blobs (unreferenced_since) WHERE ref_count = 0;
+
+-- Every file that makes up a blob (including the primary file).
+CREATE TABLE file_blob_files (
+  path text PRIMARY KEY,
+  blob_path text NOT NULL REFERENCES file_blobs (path) ON DELETE CASCADE,
+  content_type text NOT NULL,
+  size bigint NOT NULL
+);
+
+CREATE INDEX file_blob_files_blob_path_idx ON file_blob_files (blob_path);
+
+-- Keeps file_blobs.ref_count up to date for the columns named in the trigger's arguments. Paths
+-- that aren't blobs (e.g. untracked legacy files) are ignored.
+CREATE OR REPLACE FUNCTION track_file_blob_refs()
+RETURNS TRIGGER AS $$
+DECLARE
+    col text;
+    old_path text;
+    new_path text;
+BEGIN
+    FOREACH col IN ARRAY TG_ARGV LOOP
+        old_path := CASE WHEN TG_OP IN ('UPDATE', 'DELETE') THEN to_jsonb(OLD) ->> col END;
+        new_path := CASE WHEN TG_OP IN ('INSERT', 'UPDATE') THEN to_jsonb(NEW) ->> col END;
+
+        IF old_path IS DISTINCT FROM new_path THEN
+            IF old_path IS NOT NULL THEN
+                UPDATE file_blobs
+                SET
+                    ref_count = ref_count - 1,
+                    unreferenced_since = CASE WHEN ref_count = 1 THEN now() END
+                WHERE path = old_path;
+            END IF;
+            IF new_path IS NOT NULL THEN
+                UPDATE file_blobs
+                SET ref_count = ref_count + 1, unreferenced_since = NULL
+                WHERE path = new_path;
+            END IF;
+        END IF;
+    END LOOP;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Edits are included so that restoring an old cover image can't point at a deleted file
+CREATE TRIGGER news_posts_file_blob_refs
+    AFTER INSERT OR UPDATE OF cover_image_path OR DELETE ON news_posts
+    FOR EACH ROW
+    EXECUTE FUNCTION track_file_blob_refs('cover_image_path');
+
+CREATE TRIGGER news_post_edits_file_blob_refs
+    AFTER INSERT OR UPDATE OF cover_image_path OR DELETE ON news_post_edits
+    FOR EACH ROW
+    EXECUTE FUNCTION track_file_blob_refs('cover_image_path');
+
+CREATE TRIGGER leagues_file_blob_refs
+    AFTER INSERT OR UPDATE OF image_path, badge_path OR DELETE ON leagues
+    FOR EACH ROW
+    EXECUTE FUNCTION track_file_blob_refs('image_path', 'badge_path');
+
+CREATE TRIGGER channels_file_blob_refs
+    AFTER INSERT OR UPDATE OF banner_path, badge_path OR DELETE ON channels
+    FOR EACH ROW
+    EXECUTE FUNCTION track_file_blob_refs('banner_path', 'badge_path');
+
+CREATE TRIGGER users_file_blob_refs
+    AFTER INSERT OR UPDATE OF avatar_path OR DELETE ON users
+    FOR EACH ROW
+    EXECUTE FUNCTION track_file_blob_refs('avatar_path');
+
+-- Map and replay files are stored as blobs too, so that they're deleted once nothing refers to
+-- them. Neither table stores the path of its file, since that's derived from other columns, so it's
+-- added as a generated column for track_file_blob_refs to read. The paths
+-- must match `map_path` in server-rs/src/maps/mod.rs and `replayPath` in
+-- server/lib/replays/paths.ts.
+--
+-- Files stored before this table existed have no file_blobs row, so they stay untracked (and are never deleted).
+ALTER TABLE maps ADD COLUMN file_path text GENERATED ALWAYS AS (
+  'maps/' || substr(encode(hash, 'hex'), 1, 2) || '/' || substr(encode(hash, 'hex'), 3, 2) || '/' ||
+    encode(hash, 'hex') || '.' || rtrim(extension)
+) STORED;
+
+ALTER TABLE replay_files ADD COLUMN file_path text GENERATED ALWAYS AS (
+  'replays/' || id::text || '.rep'
+) STORED;
+
+-- The columns the paths are derived from never change, so only inserts and deletes are tracked
+CREATE TRIGGER maps_file_blob_refs
+    AFTER INSERT OR DELETE ON maps
+    FOR EACH ROW
+    EXECUTE FUNCTION track_file_blob_refs('file_path');
+
+CREATE TRIGGER replay_files_file_blob_refs
+    AFTER INSERT OR DELETE ON replay_files
+    FOR EACH ROW
+    EXECUTE FUNCTION track_file_blob_refs('file_path');
@@ -0,0 +1,5 @@
+-- The version of the renderer that generated each map's preview images (see `MAP_RENDERER_VERSION`
+-- in the Rust server). Images generated by the Node server are version 1, and version 0 means the
+-- map has no images yet (e.g. it was uploaded while the renderer wasn't configured). Maps with an
+-- older version are re-rendered in the background, bumping their image_version once done.
+ALTER TABLE maps ADD COLUMN renderer_version integer NOT NULL DEFAULT 1;
@@ -0,0 +1,7 @@
+-- The most maps a player may pick in a pool for a mode that uses positive map selection. NULL means
+-- players may pick any number of the pool's maps.
+ALTER TABLE matchmaking_map_pools ADD COLUMN max_pick_count integer;
+
+-- Finding the current and upcoming pools for a type scans by start date within the type
+CREATE INDEX matchmaking_map_pools_type_start_date_index
+    ON matchmaking_map_pools (matchmaking_type, start_date DESC);
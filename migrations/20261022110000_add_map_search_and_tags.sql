@@ -0,0 +1,20 @@
+-- pg_trgm is a trusted extension, so the database owner can create it here. It's also created by
+-- the DB init script (and listed in GETTING_STARTED) for new databases.
+CREATE EXTENSION IF NOT EXISTS pg_trgm WITH SCHEMA public;
+
+-- Map search matches substrings of names and descriptions (`ILIKE '%query%'`), which btree indexes
+-- can't help with
+CREATE INDEX uploaded_maps_name_trgm_index ON uploaded_maps USING gin (name gin_trgm_ops);
+CREATE INDEX uploaded_maps_description_trgm_index
+    ON uploaded_maps USING gin (description gin_trgm_ops);
+
+-- Tags applied to uploaded maps by map curators (users with the `manage_maps` permission)
+CREATE TABLE uploaded_map_tags (
+    map_id uuid NOT NULL REFERENCES uploaded_maps (id) ON DELETE CASCADE,
+    tag text NOT NULL,
+    tagged_by integer NOT NULL REFERENCES users (id),
+    tagged_at timestamp with time zone NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (map_id, tag)
+);
+
+CREATE INDEX uploaded_map_tags_tag_index ON uploaded_map_tags (tag);
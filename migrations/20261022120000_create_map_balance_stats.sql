@@ -0,0 +1,37 @@
+-- Per-map balance statistics for matchmaking, aggregated from `games`/`games_users` per season by
+-- a periodic job in the Rust server. Rows for a season are replaced wholesale each time it's
+-- refreshed, so they're only as current as that season's entry in `map_stats_refreshes`.
+
+-- Results per assigned matchup (e.g. `t-z` or `pt-zz`, as in `games.assigned_matchup`). Team A is
+-- the first team in the matchup string. For mirror matchups both teams have the same races, so
+-- both win counts include every decided game.
+CREATE TABLE map_matchup_stats (
+    map_id uuid NOT NULL REFERENCES uploaded_maps (id) ON DELETE CASCADE,
+    matchmaking_type matchmaking_type NOT NULL,
+    season_id integer NOT NULL REFERENCES matchmaking_seasons (id) ON DELETE CASCADE,
+    matchup text NOT NULL,
+    games integer NOT NULL,
+    team_a_wins integer NOT NULL,
+    team_b_wins integer NOT NULL,
+    -- The sum of `games.game_length` (in milliseconds) across the `timed_games` games that have one
+    total_game_length bigint NOT NULL,
+    timed_games integer NOT NULL,
+    PRIMARY KEY (map_id, matchmaking_type, season_id, matchup)
+);
+
+-- Results per start location, identified by the BW player slot that owns it (which is the
+-- `bwPlayerId` the player was given in game)
+CREATE TABLE map_start_location_stats (
+    map_id uuid NOT NULL REFERENCES uploaded_maps (id) ON DELETE CASCADE,
+    matchmaking_type matchmaking_type NOT NULL,
+    season_id integer NOT NULL REFERENCES matchmaking_seasons (id) ON DELETE CASCADE,
+    start_location smallint NOT NULL,
+    games integer NOT NULL,
+    wins integer NOT NULL,
+    PRIMARY KEY (map_id, matchmaking_type, season_id, start_location)
+);
+
+CREATE TABLE map_stats_refreshes (
+    season_id integer PRIMARY KEY REFERENCES matchmaking_seasons (id) ON DELETE CASCADE,
+    refreshed_at timestamp with time zone NOT NULL
+);
@@ -0,0 +1,53 @@
+CREATE TYPE email_outbox_status AS ENUM (
+  'pending',
+  'sent',
+  'failed',
+  'suppressed',
+  'bounced',
+  'complained'
+);
+
+-- Emails waiting to be sent by server-rs, and a record of recently sent ones. Emails are inserted
+-- in the same transaction as the change they're about, then a background job claims rows that are
+-- due (with FOR UPDATE SKIP LOCKED, so multiple instances don't send the same email), renders them
+-- from the local templates and hands them to the configured transport. See the email::outbox module
+-- in server-rs.
+CREATE TABLE email_outbox (
+  id uuid PRIMARY KEY DEFAULT sb_uuid(),
+  recipient text NOT NULL,
+  -- The template name and the data it's rendered with (a serialized `EmailTemplate`). Rendering
+  -- happens at send time, so template fixes apply to emails that are still queued.
+  message jsonb NOT NULL,
+  -- The recipient's locale, used to pick which language the email is rendered in.
+  locale text,
+  status email_outbox_status NOT NULL DEFAULT 'pending',
+  attempts integer NOT NULL DEFAULT 0,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  -- When the email should next be tried. Claiming a row pushes this forward, so an email claimed by
+  -- an instance that dies mid-send gets retried once that lease runs out.
+  next_attempt_at timestamptz NOT NULL DEFAULT now(),
+  sent_at timestamptz,
+  -- The ID the transport gave the message (if it gives one), for matching up delivery events.
+  provider_message_id text,
+  -- The last error encountered sending this email, for debugging.
+  last_error text
+);
+
+-- The job queue: only emails that still need sending, soonest first. Partial so it stays tiny.
+CREATE INDEX email_outbox_pending_index ON email_outbox (next_attempt_at)
+  WHERE status = 'pending';
+-- For pruning old entries.
+CREATE INDEX email_outbox_created_at_index ON email_outbox (created_at);
+
+CREATE TYPE email_suppression_reason AS ENUM ('bounce', 'complaint');
+
+-- Addresses we shouldn't send email to anymore because they permanently bounced or their owner
+-- marked our mail as spam (as reported by our email provider's webhooks). Sending to these hurts
+-- our sender reputation, so queued emails for them are dropped instead.
+CREATE TABLE email_suppressions (
+  email citext PRIMARY KEY,
+  reason email_suppression_reason NOT NULL,
+  -- Whatever the provider told us about why, for debugging.
+  details text,
+  created_at timestamptz NOT NULL DEFAULT now()
+);
@@ -1,9 +1,10 @@
-use crate::email::MailgunSettings;
+use crate::email::{EmailTransportSettings, FileTransportSettings, MailgunSettings, SmtpSettings};
 use color_eyre::eyre;
 use color_eyre::eyre::{WrapErr, eyre};
 use reqwest::Url;
 use secrecy::{ExposeSecret, SecretString};
 use serde::Deserialize;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 /// The environment the application is running in.
@@ -21,16 +22,32 @@ pub struct Settings {
     pub app_port: u16,
     pub database: DatabaseSettings,
     pub redis: RedisSettings,
-    pub mailgun: Option<MailgunSettings>,
+    /// How emails are delivered. `None` disables sending emails entirely.
+    pub email_transport: Option<EmailTransportSettings>,
     pub canonical_host: String,
     pub reverse_proxied: bool,
     pub datadog_api_key: Option<SecretString>,
+    /// Where to keep Datadog logs that couldn't be sent during an outage. `None` drops them.
+    pub datadog_spool: Option<DatadogSpoolSettings>,
+    /// Where to export traces over OTLP. `None` disables trace export.
+    pub otlp: Option<OtlpSettings>,
+    /// Restrictions on the queries the GraphQL API will run.
+    pub graphql_limits: GraphqlLimitsSettings,
     pub jwt_secret: SecretString,
     pub session_ttl: Duration,
     pub file_store: FileStoreSettings,
     /// Twitch integration credentials. `None` disables the integration entirely (account linking
     /// errors out and the live-streams feed stays empty), so dev/CI can run without Twitch creds.
     pub twitch: Option<TwitchSettings>,
+    /// Credentials for the Twitch account that announces streamers' games in their chat. `None`
+    /// disables the chat bot. Requires the Twitch integration to be configured.
+    pub twitch_chat_bot: Option<TwitchChatBotSettings>,
+    /// YouTube integration credentials. `None` disables linking YouTube channels and polling them
+    /// for live streams.
+    pub youtube: Option<YoutubeSettings>,
+    /// Credentials for the external identity providers users can sign in with or link. Each
+    /// provider is independently optional; unconfigured ones are simply not offered.
+    pub oauth: OAuthSettings,
     /// The public origin of this (GraphQL) server, e.g. `https://gql.shieldbattery.net`. Only
     /// required when the Twitch integration is configured, since Twitch delivers EventSub
     /// webhooks to `<gql_origin>/twitch/eventsub`.
@@ -40,6 +57,51 @@ pub struct Settings {
     /// matchmaker's backbone table is then built from `SB_REGION_BACKBONE_RTT_JSON` alone, which is the
     /// correct dev-loopback posture. Empty/whitespace is treated as unset.
     pub rp2_coordinator_url: Option<String>,
+    /// The directory containing StarCraft's `tileset/` data files (the same `SB_SPRITE_DATA` the
+    /// Node server reads), used to render map images. `None` disables rendering, so maps are stored
+    /// without images.
+    pub bw_data_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DatadogSpoolSettings {
+    pub dir: PathBuf,
+    /// The maximum size of the spool on disk. Once it's full, the oldest logs are dropped.
+    pub max_bytes: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct OtlpSettings {
+    /// The OTLP/HTTP traces endpoint of the collector, e.g. `http://localhost:4318/v1/traces`.
+    pub traces_endpoint: String,
+    /// The fraction of traces to export, from 0 to 1. Traces are picked by their ID, so this applies
+    /// to traces continued from other services too, whether or not they sampled them.
+    pub sample_ratio: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphqlLimitsSettings {
+    /// The maximum total cost of the fields selected in a single request.
+    pub max_complexity: usize,
+    /// The maximum nesting depth of a single request.
+    pub max_depth: usize,
+    /// The `persisted-documents.json` generated by the client build, listing the queries it
+    /// makes. `None` means no queries are persisted.
+    pub persisted_queries: Option<PathBuf>,
+    /// Whether to reject queries (other than those made with API tokens) that aren't in
+    /// `persisted_queries`.
+    pub enforce_persisted_queries: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct YoutubeSettings {
+    /// The OAuth client ID of our Google Cloud project, used to link a user's YouTube channel.
+    pub client_id: String,
+    /// The OAuth client secret of our Google Cloud project.
+    pub client_secret: SecretString,
+    /// The API key used for YouTube Data API requests that aren't made on behalf of a user (the
+    /// live-stream polling and channel identity refreshes).
+    pub api_key: SecretString,
 }
 
 #[derive(Debug, Clone)]
@@ -56,6 +118,32 @@ pub struct TwitchSettings {
     pub eventsub_secret: SecretString,
 }
 
+#[derive(Debug, Clone)]
+pub struct TwitchChatBotSettings {
+    /// The login name of the bot's Twitch account.
+    pub login: String,
+    /// A refresh token for the bot's account, issued to our Twitch application with the
+    /// `chat:read` and `chat:edit` scopes. Access tokens are obtained from it as needed.
+    pub refresh_token: SecretString,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OAuthSettings {
+    pub discord: Option<OAuthProviderSettings>,
+    pub google: Option<OAuthProviderSettings>,
+    pub github: Option<OAuthProviderSettings>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OAuthProviderSettings {
+    /// The OAuth client ID of our registered application. Public (embedded in authorize URLs the
+    /// client opens), so it's a plain String.
+    pub client_id: String,
+    /// The OAuth client secret of our registered application, only used server-side for the
+    /// authorization-code token exchange.
+    pub client_secret: SecretString,
+}
+
 #[derive(Debug, Clone)]
 pub struct DatabaseSettings {
     pub user: String,
@@ -146,6 +234,23 @@ fn env_var_non_empty(name: &str) -> Option<String> {
         .filter(|s| !s.is_empty())
 }
 
+/// Reads the `SB_<PREFIX>_CLIENT_ID`/`SB_<PREFIX>_CLIENT_SECRET` pair for an OAuth provider, which
+/// must be set or unset together.
+fn oauth_provider_settings(prefix: &str) -> eyre::Result<Option<OAuthProviderSettings>> {
+    let client_id = env_var_non_empty(&format!("SB_{prefix}_CLIENT_ID"));
+    let client_secret = env_var_non_empty(&format!("SB_{prefix}_CLIENT_SECRET"));
+    match (client_id, client_secret) {
+        (Some(client_id), Some(client_secret)) => Ok(Some(OAuthProviderSettings {
+            client_id,
+            client_secret: client_secret.into(),
+        })),
+        (None, None) => Ok(None),
+        _ => Err(eyre!(
+            "SB_{prefix}_CLIENT_ID and SB_{prefix}_CLIENT_SECRET must both be set or both unset"
+        )),
+    }
+}
+
 pub fn get_configuration() -> eyre::Result<Settings> {
     #[cfg(test)]
     let env = Env::Test;
@@ -173,16 +278,64 @@ pub fn get_configuration() -> eyre::Result<Settings> {
         .ok()
         .map(|s| s.parse::<Url>().wrap_err("Failed to parse SB_MAILGUN_URL"))
         .transpose()?;
+    let mailgun_webhook_signing_key = env_var_non_empty("SB_MAILGUN_WEBHOOK_SIGNING_KEY");
 
     let mailgun = mailgun_key.map(|key| {
-        MailgunSettings::new(
+        let settings = MailgunSettings::new(
             key,
             mailgun_domain.unwrap(),
             mailgun_from.unwrap(),
             mailgun_url,
-        )
+        );
+        match mailgun_webhook_signing_key {
+            Some(signing_key) => settings.with_webhook_signing_key(signing_key),
+            None => settings,
+        }
     });
 
+    // Defaults to Mailgun (if it's configured) so existing deployments keep working unchanged
+    let email_transport = match env_var_non_empty("SB_EMAIL_TRANSPORT").as_deref() {
+        None => mailgun.map(EmailTransportSettings::Mailgun),
+        Some("mailgun") => Some(EmailTransportSettings::Mailgun(mailgun.ok_or_else(
+            || eyre!("SB_MAILGUN_KEY must be set when SB_EMAIL_TRANSPORT is mailgun"),
+        )?)),
+        Some("smtp") => {
+            let (Some(url), Some(from)) = (
+                env_var_non_empty("SB_SMTP_URL"),
+                env_var_non_empty("SB_EMAIL_FROM"),
+            ) else {
+                return Err(eyre!(
+                    "SB_SMTP_URL and SB_EMAIL_FROM must be set when SB_EMAIL_TRANSPORT is smtp"
+                ));
+            };
+            Some(EmailTransportSettings::Smtp(SmtpSettings {
+                url: url.into(),
+                from,
+            }))
+        }
+        Some("file") => {
+            let dir = env_var_non_empty("SB_EMAIL_DIR")
+                .ok_or_else(|| eyre!("SB_EMAIL_DIR must be set when SB_EMAIL_TRANSPORT is file"))?;
+            // Relative to the repo root, like the other paths we share with the Node server
+            let dir = Path::new(&dir);
+            let dir = if dir.is_absolute() {
+                dir.to_path_buf()
+            } else {
+                Path::new("../").join(dir)
+            };
+            Some(EmailTransportSettings::File(FileTransportSettings {
+                dir,
+                from: env_var_non_empty("SB_EMAIL_FROM")
+                    .unwrap_or_else(|| "ShieldBattery <noreply@localhost>".into()),
+            }))
+        }
+        Some(other) => {
+            return Err(eyre!(
+                "SB_EMAIL_TRANSPORT must be one of mailgun, smtp, or file (got {other})"
+            ));
+        }
+    };
+
     let file_store = std::env::var("SB_FILE_STORE")
         .wrap_err("Failed to read SB_FILE_STORE")
         .and_then(|s| {
@@ -217,8 +370,138 @@ pub fn get_configuration() -> eyre::Result<Settings> {
         eventsub_secret: twitch_eventsub_secret.unwrap().into(),
     });
 
+    let twitch_chat_bot_login = env_var_non_empty("SB_TWITCH_CHAT_BOT_LOGIN");
+    let twitch_chat_bot_refresh_token = env_var_non_empty("SB_TWITCH_CHAT_BOT_REFRESH_TOKEN");
+    if twitch_chat_bot_login.is_some() != twitch_chat_bot_refresh_token.is_some() {
+        return Err(eyre!(
+            "SB_TWITCH_CHAT_BOT_LOGIN and SB_TWITCH_CHAT_BOT_REFRESH_TOKEN must both be set or both \
+             unset"
+        ));
+    }
+    if twitch_chat_bot_login.is_some() && twitch.is_none() {
+        return Err(eyre!(
+            "The Twitch integration (SB_TWITCH_CLIENT_ID etc.) must be configured to use the Twitch \
+             chat bot"
+        ));
+    }
+    let twitch_chat_bot = twitch_chat_bot_login.map(|login| TwitchChatBotSettings {
+        login,
+        refresh_token: twitch_chat_bot_refresh_token.unwrap().into(),
+    });
+
+    let youtube_client_id = env_var_non_empty("SB_YOUTUBE_CLIENT_ID");
+    let youtube_client_secret = env_var_non_empty("SB_YOUTUBE_CLIENT_SECRET");
+    let youtube_api_key = env_var_non_empty("SB_YOUTUBE_API_KEY");
+    if youtube_client_id.is_some() != youtube_client_secret.is_some()
+        || youtube_client_id.is_some() != youtube_api_key.is_some()
+    {
+        return Err(eyre!(
+            "SB_YOUTUBE_CLIENT_ID, SB_YOUTUBE_CLIENT_SECRET, and SB_YOUTUBE_API_KEY must all be set \
+             or all unset"
+        ));
+    }
+    let youtube = youtube_client_id.map(|client_id| YoutubeSettings {
+        client_id,
+        client_secret: youtube_client_secret.unwrap().into(),
+        api_key: youtube_api_key.unwrap().into(),
+    });
+
+    let oauth = OAuthSettings {
+        discord: oauth_provider_settings("DISCORD")?,
+        google: oauth_provider_settings("GOOGLE")?,
+        github: oauth_provider_settings("GITHUB")?,
+    };
+
     let rp2_coordinator_url = env_var_non_empty("SB_RP2_COORDINATOR_URL");
 
+    let datadog_spool = env_var_non_empty("SB_DATADOG_SPOOL_DIR")
+        .map(|dir| {
+            let max_mb: u64 =
+                match env_var_non_empty("SB_DATADOG_SPOOL_MAX_MB") {
+                    Some(value) => value.parse().ok().filter(|&mb| mb > 0).ok_or_else(|| {
+                        eyre!("SB_DATADOG_SPOOL_MAX_MB must be a positive integer")
+                    })?,
+                    None => 256,
+                };
+            Ok::<_, eyre::Report>(DatadogSpoolSettings {
+                dir: PathBuf::from(dir),
+                max_bytes: max_mb * 1024 * 1024,
+            })
+        })
+        .transpose()?;
+
+    let otlp = env_var_non_empty("SB_OTLP_TRACES_ENDPOINT")
+        .map(|traces_endpoint| {
+            let sample_ratio = match env_var_non_empty("SB_OTLP_TRACES_SAMPLE_RATIO") {
+                Some(value) => value
+                    .parse()
+                    .ok()
+                    .filter(|r: &f64| (0.0..=1.0).contains(r))
+                    .ok_or_else(|| {
+                        eyre!("SB_OTLP_TRACES_SAMPLE_RATIO must be a number between 0 and 1")
+                    })?,
+                None => 1.0,
+            };
+            Ok::<_, eyre::Report>(OtlpSettings {
+                traces_endpoint,
+                sample_ratio,
+            })
+        })
+        .transpose()?;
+
+    let graphql_limits = GraphqlLimitsSettings {
+        max_complexity: match env_var_non_empty("SB_GQL_MAX_COMPLEXITY") {
+            Some(value) => value
+                .parse()
+                .ok()
+                .filter(|&c: &usize| c > 0)
+                .ok_or_else(|| eyre!("SB_GQL_MAX_COMPLEXITY must be a positive integer"))?,
+            // Sized with ample headroom over the largest legitimate client query (the admin name
+            // history page, with both of its lists at their maximum length). GQLi introspection
+            // selects a very large number of fields, so we allow much greater in dev mode.
+            None if env == Env::Production => 2000,
+            None => 999999,
+        },
+        max_depth: match env_var_non_empty("SB_GQL_MAX_DEPTH") {
+            Some(value) => value
+                .parse()
+                .ok()
+                .filter(|&d: &usize| d > 0)
+                .ok_or_else(|| eyre!("SB_GQL_MAX_DEPTH must be a positive integer"))?,
+            // NOTE(tec27): GQLi introspection is a pretty deep query so we allow much greater in
+            // dev mode
+            None if env == Env::Production => 10,
+            None => 999999,
+        },
+        // Relative to the repo root, like the other paths we share with the Node server
+        persisted_queries: env_var_non_empty("SB_GQL_PERSISTED_QUERIES").map(|path| {
+            let path = Path::new(&path);
+            if path.is_absolute() {
+                path.to_path_buf()
+            } else {
+                Path::new("../").join(path)
+            }
+        }),
+        enforce_persisted_queries: env_var_non_empty("SB_GQL_ENFORCE_PERSISTED_QUERIES")
+            .is_some_and(|v| v.eq_ignore_ascii_case("true")),
+    };
+    if graphql_limits.enforce_persisted_queries && graphql_limits.persisted_queries.is_none() {
+        return Err(eyre!(
+            "SB_GQL_PERSISTED_QUERIES must be set when SB_GQL_ENFORCE_PERSISTED_QUERIES is true"
+        ));
+    }
+
+    // Like the local file store path, this is relative to the repo root (the Node server's CWD),
+    // while this server runs from `server-rs`
+    let bw_data_path = env_var_non_empty("SB_SPRITE_DATA").map(|path| {
+        let path = Path::new(&path);
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            Path::new("../").join(path)
+        }
+    });
+
     let gql_origin = env_var_non_empty("SB_GQL_ORIGIN");
     if twitch.is_some() && gql_origin.is_none() {
         return Err(eyre!(
@@ -279,13 +562,16 @@ pub fn get_configuration() -> eyre::Result<Settings> {
             host: std::env::var("SB_REDIS_HOST").wrap_err("SB_REDIS_HOST is not set")?,
             port: std::env::var("SB_REDIS_PORT").wrap_err("SB_REDIS_PORT is not set")?,
         },
-        mailgun,
+        email_transport,
         canonical_host: std::env::var("SB_CANONICAL_HOST")
             .wrap_err("SB_CANONICAL_HOST is not set")?,
         reverse_proxied: std::env::var("SB_HTTPS_REVERSE_PROXY")
             .unwrap_or("false".into())
             .eq_ignore_ascii_case("true"),
         datadog_api_key: std::env::var("SB_DATADOG_KEY").ok().map(Into::into),
+        datadog_spool,
+        otlp,
+        graphql_limits,
         jwt_secret: std::env::var("SB_JWT_SECRET")
             .wrap_err("SB_JWT_SECRET is not set")?
             .into(),
@@ -297,7 +583,11 @@ pub fn get_configuration() -> eyre::Result<Settings> {
         ),
         file_store,
         twitch,
+        twitch_chat_bot,
+        youtube,
+        oauth,
         gql_origin,
         rp2_coordinator_url,
+        bw_data_path,
     })
 }
@@ -0,0 +1,264 @@
+//! Emails sent to users. Emails are queued in a Postgres-backed outbox (in the same transaction as
+//! whatever change they're about, where there is one), and then rendered from our local templates
+//! and handed to the configured transport by a background job that retries failed deliveries.
+
+pub mod outbox;
+pub mod templates;
+pub mod transport;
+pub mod webhooks;
+
+use color_eyre::eyre;
+use serde::{Deserialize, Serialize, Serializer};
+use sqlx::PgExecutor;
+
+pub use transport::{EmailTransportSettings, FileTransportSettings, MailgunSettings, SmtpSettings};
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailChangeData {
+    pub username: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailVerificationData {
+    pub code: String,
+    pub username: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordChangeData {
+    pub username: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginNameChangeData {
+    pub username: String,
+    pub old_login_name: String,
+    pub new_login_name: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordResetData {
+    pub username: String,
+    pub token: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UsernameRecoveryData {
+    pub email: String,
+    /// The template lists these as `{{#each usernames}}{{username}}{{/each}}`, so they're
+    /// serialized as objects.
+    #[serde(serialize_with = "serialize_recovered_usernames")]
+    #[serde(deserialize_with = "deserialize_recovered_usernames")]
+    pub usernames: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecoveredUsername {
+    username: String,
+}
+
+fn serialize_recovered_usernames<S: Serializer>(
+    usernames: &[String],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(usernames.iter().map(|username| RecoveredUsername {
+        username: username.clone(),
+    }))
+}
+
+fn deserialize_recovered_usernames<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    let usernames = Vec::<RecoveredUsername>::deserialize(deserializer)?;
+    Ok(usernames.into_iter().map(|u| u.username).collect())
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DataExportData {
+    pub username: String,
+    /// A signed URL the export archive can be downloaded from.
+    pub download_url: String,
+    /// How many days `download_url` remains valid for.
+    pub expires_in_days: i64,
+}
+
+/// An email we can send, along with the data its template needs. Serialized as
+/// `{ "template": <name>, "data": <data> }` when stored in the outbox.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "template", content = "data", rename_all = "kebab-case")]
+pub enum EmailTemplate {
+    EmailChange(EmailChangeData),
+    EmailVerification(EmailVerificationData),
+    PasswordChange(PasswordChangeData),
+    LoginNameChange(LoginNameChangeData),
+    PasswordReset(PasswordResetData),
+    UsernameRecovery(UsernameRecoveryData),
+    DataExport(DataExportData),
+}
+
+impl EmailTemplate {
+    /// The subject to use if the rendered template doesn't specify one (in its `<title>`).
+    fn fallback_subject(&self) -> &'static str {
+        match self {
+            EmailTemplate::EmailChange(_) => "ShieldBattery Email Changed",
+            EmailTemplate::EmailVerification(_) => "ShieldBattery Email Verification",
+            EmailTemplate::PasswordChange(_) => "ShieldBattery Password Changed",
+            EmailTemplate::LoginNameChange(_) => "ShieldBattery Login Name Changed",
+            EmailTemplate::PasswordReset(_) => "ShieldBattery Password Reset",
+            EmailTemplate::UsernameRecovery(_) => "ShieldBattery Username Recovery",
+            EmailTemplate::DataExport(_) => "ShieldBattery Data Export Ready",
+        }
+    }
+
+    /// The name of the template file (without extension) this email is rendered from.
+    pub fn template_name(&self) -> &'static str {
+        match self {
+            EmailTemplate::EmailChange(_) => "email-change",
+            EmailTemplate::EmailVerification(_) => "email-verification",
+            EmailTemplate::PasswordChange(_) => "password-change",
+            EmailTemplate::LoginNameChange(_) => "login-name-change",
+            EmailTemplate::PasswordReset(_) => "password-reset",
+            EmailTemplate::UsernameRecovery(_) => "username-recovery",
+            EmailTemplate::DataExport(_) => "data-export",
+        }
+    }
+
+    /// The variables the template is rendered with.
+    pub fn data(&self) -> serde_json::Result<serde_json::Value> {
+        match self {
+            EmailTemplate::EmailChange(data) => serde_json::to_value(data),
+            EmailTemplate::EmailVerification(data) => serde_json::to_value(data),
+            EmailTemplate::PasswordChange(data) => serde_json::to_value(data),
+            EmailTemplate::LoginNameChange(data) => serde_json::to_value(data),
+            EmailTemplate::PasswordReset(data) => serde_json::to_value(data),
+            EmailTemplate::UsernameRecovery(data) => serde_json::to_value(data),
+            EmailTemplate::DataExport(data) => serde_json::to_value(data),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EmailMessage {
+    /// The email address the message will be sent to.
+    pub to: String,
+    /// The recipient's locale, which selects the language the email is written in. Falls back to
+    /// English if unset or if we don't have translated emails for it.
+    pub locale: Option<String>,
+    /// The template and associated data to use to construct the email subject/body.
+    pub template: EmailTemplate,
+}
+
+/// Queues emails to be sent by [outbox::email_outbox_loop].
+#[derive(Debug, Clone)]
+pub struct Mailer {
+    enabled: bool,
+}
+
+impl Mailer {
+    /// Creates a mailer. If `enabled` is false (no email transport is configured), messages are
+    /// dropped instead of being queued.
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    /// Queues `message` for delivery. Pass the transaction that makes the change the email is
+    /// about (if there is one), so it's only sent if that change is committed.
+    pub async fn queue<'e>(
+        &self,
+        executor: impl PgExecutor<'e>,
+        message: EmailMessage,
+    ) -> eyre::Result<()> {
+        if !self.enabled {
+            tracing::warn!(
+                "Skipping sending email because no email transport is configured: {message:?}"
+            );
+            return Ok(());
+        }
+
+        outbox::insert(executor, &message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[test]
+    fn stores_templates_with_their_names() {
+        let template = EmailTemplate::LoginNameChange(LoginNameChangeData {
+            username: "user".into(),
+            old_login_name: "old".into(),
+            new_login_name: "new".into(),
+        });
+
+        let stored = serde_json::to_value(&template).unwrap();
+        assert_eq!(
+            stored,
+            json!({
+                "template": "login-name-change",
+                "data": {
+                    "username": "user",
+                    "oldLoginName": "old",
+                    "newLoginName": "new",
+                },
+            })
+        );
+        assert_eq!(stored["template"].as_str(), Some(template.template_name()));
+
+        let loaded: EmailTemplate = serde_json::from_value(stored).unwrap();
+        assert_eq!(loaded.data().unwrap(), template.data().unwrap());
+    }
+
+    #[test]
+    fn serializes_recovered_usernames_as_objects() {
+        let template = EmailTemplate::UsernameRecovery(UsernameRecoveryData {
+            email: "user@example.org".into(),
+            usernames: vec!["one".into(), "two".into()],
+        });
+
+        assert_eq!(
+            template.data().unwrap(),
+            json!({
+                "email": "user@example.org",
+                "usernames": [{ "username": "one" }, { "username": "two" }],
+            })
+        );
+        let loaded: EmailTemplate =
+            serde_json::from_value(serde_json::to_value(&template).unwrap()).unwrap();
+        let EmailTemplate::UsernameRecovery(data) = loaded else {
+            panic!("Wrong template: {loaded:?}");
+        };
+        assert_eq!(data.usernames, vec!["one", "two"]);
+    }
+
+    #[tokio::test]
+    async fn skips_queueing_when_not_configured() {
+        // Never actually connects, since nothing should be queued
+        let db = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        let res = Mailer::new(false)
+            .queue(
+                &db,
+                EmailMessage {
+                    to: "user@example.org".into(),
+                    locale: None,
+                    template: EmailTemplate::EmailVerification(EmailVerificationData {
+                        code: "asdf1234".into(),
+                        username: "user".into(),
+                    }),
+                },
+            )
+            .await;
+
+        assert!(res.is_ok(), "{:?}", res);
+    }
+}
@@ -0,0 +1,523 @@
+//! The email outbox: emails are stored in `email_outbox` when they're queued, then a background job
+//! renders and sends them, retrying with exponential backoff if the transport fails. Jobs are
+//! claimed with `FOR UPDATE SKIP LOCKED` so every server instance can run the loop without sending
+//! an email twice.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use color_eyre::eyre::{self, WrapErr};
+use sqlx::postgres::PgListener;
+use sqlx::types::Json;
+use sqlx::{PgExecutor, PgPool};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::email::templates::EmailRenderer;
+use crate::email::transport::{EmailTransport, OutgoingEmail, TransportError};
+use crate::email::{EmailMessage, EmailTemplate};
+
+/// The Postgres channel notified when emails are queued, so they can be sent right away.
+const NOTIFY_CHANNEL: &str = "email_outbox";
+/// How often the job loop checks for emails that are due, for retries (and in case a notification
+/// is missed).
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// How many emails are claimed at once.
+const BATCH_SIZE: i64 = 20;
+/// How long a claimed email is reserved for the instance that claimed it. If it hasn't been sent or
+/// rescheduled by then, the instance is assumed to have died and another one will pick it up.
+const CLAIM_LEASE: Duration = Duration::from_secs(5 * 60);
+/// How many times sending an email will be attempted before it's marked as failed.
+const MAX_ATTEMPTS: i32 = 10;
+/// The delay before the first retry, doubled for each attempt after that.
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(30);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+/// How often old outbox entries are cleaned up.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How long sent (or otherwise finished) emails are kept, for debugging delivery issues.
+const RETENTION_DAYS: i32 = 30;
+
+const EMAILS_SENT_TOTAL: &str = "emails_sent_total";
+const EMAIL_DELIVERY_FAILURES_TOTAL: &str = "email_delivery_failures_total";
+const EMAILS_SUPPRESSED_TOTAL: &str = "emails_suppressed_total";
+
+pub fn describe_metrics() {
+    use ::metrics::Unit;
+
+    ::metrics::describe_counter!(
+        EMAILS_SENT_TOTAL,
+        Unit::Count,
+        "Emails handed off to the email transport, per template and transport"
+    );
+    ::metrics::describe_counter!(
+        EMAIL_DELIVERY_FAILURES_TOTAL,
+        Unit::Count,
+        "Failed attempts at sending emails, per template and kind (transient failures are retried)"
+    );
+    ::metrics::describe_counter!(
+        EMAILS_SUPPRESSED_TOTAL,
+        Unit::Count,
+        "Queued emails dropped because their recipient previously bounced or complained"
+    );
+}
+
+pub(super) async fn insert<'e>(
+    executor: impl PgExecutor<'e>,
+    message: &EmailMessage,
+) -> eyre::Result<()> {
+    // Notifications are only delivered once the transaction commits, so the loop won't wake up to
+    // find an email it can't see yet
+    sqlx::query!(
+        r#"
+            WITH queued AS (
+                INSERT INTO email_outbox (recipient, message, locale)
+                VALUES ($1, $2, $3)
+                RETURNING id
+            )
+            SELECT pg_notify($4, id::text) FROM queued
+        "#,
+        message.to,
+        Json(&message.template) as _,
+        message.locale,
+        NOTIFY_CHANNEL,
+    )
+    .execute(executor)
+    .await
+    .wrap_err("Failed to queue email")?;
+
+    Ok(())
+}
+
+/// The delay before retrying an email that has been attempted `attempts` times.
+fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    BASE_RETRY_DELAY
+        .saturating_mul(2u32.pow(exponent))
+        .min(MAX_RETRY_DELAY)
+}
+
+/// Runs forever, sending any queued emails. Meant to be spawned once per server instance.
+pub async fn email_outbox_loop(
+    db: PgPool,
+    transport: Arc<dyn EmailTransport>,
+    renderer: Arc<EmailRenderer>,
+) {
+    let mut listener = listen_for_queued_emails(&db).await;
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    let mut prune_interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = wait_for_queued_email(&mut listener) => {}
+            _ = prune_interval.tick() => {
+                if let Err(e) = prune_outbox(&db).await {
+                    error!("Pruning email outbox failed: {e:?}");
+                }
+                continue;
+            }
+        }
+
+        // Keep going while there are full batches, so a backlog is cleared quickly
+        loop {
+            match process_batch(&db, transport.as_ref(), &renderer).await {
+                Ok(count) if count >= BATCH_SIZE as usize => continue,
+                Ok(_) => break,
+                Err(e) => {
+                    error!("Processing email outbox failed: {e:?}");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn listen_for_queued_emails(db: &PgPool) -> Option<PgListener> {
+    async fn listen(db: &PgPool) -> sqlx::Result<PgListener> {
+        let mut listener = PgListener::connect_with(db).await?;
+        listener.listen(NOTIFY_CHANNEL).await?;
+        Ok(listener)
+    }
+
+    match listen(db).await {
+        Ok(listener) => Some(listener),
+        Err(e) => {
+            warn!("Failed to listen for queued emails, falling back to polling: {e:?}");
+            None
+        }
+    }
+}
+
+/// Waits until an email is queued (from any server instance). Never returns if we aren't able to
+/// listen for them.
+async fn wait_for_queued_email(listener: &mut Option<PgListener>) {
+    let Some(l) = listener else {
+        return std::future::pending().await;
+    };
+    if let Err(e) = l.recv().await {
+        warn!("Listening for queued emails failed, falling back to polling: {e:?}");
+        *listener = None;
+    }
+}
+
+struct ClaimedEmail {
+    id: Uuid,
+    recipient: String,
+    message: Json<EmailTemplate>,
+    locale: Option<String>,
+    attempts: i32,
+    suppressed: bool,
+}
+
+enum Outcome {
+    Sent(Option<String>),
+    Retry(eyre::Error),
+    Failed(eyre::Error),
+    Suppressed,
+}
+
+/// Claims and sends a batch of emails that are due. Returns how many were claimed.
+async fn process_batch(
+    db: &PgPool,
+    transport: &dyn EmailTransport,
+    renderer: &EmailRenderer,
+) -> eyre::Result<usize> {
+    let claimed = sqlx::query_as!(
+        ClaimedEmail,
+        r#"
+            UPDATE email_outbox o
+            SET
+                attempts = o.attempts + 1,
+                next_attempt_at = NOW() + make_interval(secs => $2)
+            WHERE o.id IN (
+                SELECT id
+                FROM email_outbox
+                WHERE status = 'pending' AND next_attempt_at <= NOW()
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING
+                o.id,
+                o.recipient,
+                o.message as "message: Json<EmailTemplate>",
+                o.locale,
+                o.attempts,
+                EXISTS (
+                    SELECT 1 FROM email_suppressions s WHERE s.email = o.recipient::citext
+                ) as "suppressed!"
+        "#,
+        BATCH_SIZE,
+        CLAIM_LEASE.as_secs_f64(),
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to claim queued emails")?;
+
+    let count = claimed.len();
+    for email in claimed {
+        let template_name = email.message.template_name();
+        let outcome = send_email(transport, renderer, &email).await;
+        match &outcome {
+            Outcome::Sent(_) => {
+                ::metrics::counter!(
+                    EMAILS_SENT_TOTAL,
+                    "template" => template_name,
+                    "transport" => transport.name(),
+                )
+                .increment(1);
+            }
+            Outcome::Retry(e) | Outcome::Failed(e) => {
+                let kind = if matches!(outcome, Outcome::Retry(_)) {
+                    "transient"
+                } else {
+                    "permanent"
+                };
+                ::metrics::counter!(
+                    EMAIL_DELIVERY_FAILURES_TOTAL,
+                    "template" => template_name,
+                    "kind" => kind,
+                )
+                .increment(1);
+                warn!(
+                    "Sending {template_name} email {} failed (attempt {}, {kind}): {e:?}",
+                    email.id, email.attempts
+                );
+            }
+            Outcome::Suppressed => {
+                ::metrics::counter!(EMAILS_SUPPRESSED_TOTAL).increment(1);
+                info!(
+                    "Dropping {template_name} email {} because its recipient is suppressed",
+                    email.id
+                );
+            }
+        }
+
+        record_outcome(db, &email, outcome).await?;
+    }
+
+    Ok(count)
+}
+
+async fn send_email(
+    transport: &dyn EmailTransport,
+    renderer: &EmailRenderer,
+    email: &ClaimedEmail,
+) -> Outcome {
+    if email.suppressed {
+        return Outcome::Suppressed;
+    }
+
+    let rendered = match renderer.render(&email.message, email.locale.as_deref()) {
+        Ok(rendered) => rendered,
+        // Rendering is deterministic, so there's no point in retrying
+        Err(e) => return Outcome::Failed(e),
+    };
+    let result = transport
+        .send(OutgoingEmail {
+            id: email.id,
+            to: &email.recipient,
+            template: &email.message,
+            rendered: &rendered,
+        })
+        .await;
+
+    match result {
+        Ok(provider_id) => Outcome::Sent(provider_id),
+        Err(TransportError::Transient(e)) if email.attempts < MAX_ATTEMPTS => Outcome::Retry(e),
+        Err(TransportError::Transient(e) | TransportError::Permanent(e)) => Outcome::Failed(e),
+    }
+}
+
+/// Records what happened to a claimed email. Once an email won't be sent (again), only its template
+/// name is kept: its data can include secrets (e.g. password reset tokens), which shouldn't sit
+/// around for as long as the rest of the entry is kept.
+async fn record_outcome(db: &PgPool, email: &ClaimedEmail, outcome: Outcome) -> eyre::Result<()> {
+    match outcome {
+        Outcome::Sent(provider_id) => {
+            sqlx::query!(
+                r#"
+                    UPDATE email_outbox
+                    SET
+                        status = 'sent',
+                        sent_at = NOW(),
+                        provider_message_id = $2,
+                        message = message - 'data'
+                    WHERE id = $1
+                "#,
+                email.id,
+                provider_id,
+            )
+            .execute(db)
+            .await
+        }
+        Outcome::Retry(e) => {
+            sqlx::query!(
+                r#"
+                    UPDATE email_outbox
+                    SET next_attempt_at = NOW() + make_interval(secs => $2), last_error = $3
+                    WHERE id = $1
+                "#,
+                email.id,
+                retry_delay(email.attempts).as_secs_f64(),
+                format!("{e:#}"),
+            )
+            .execute(db)
+            .await
+        }
+        Outcome::Failed(e) => {
+            sqlx::query!(
+                r#"
+                    UPDATE email_outbox
+                    SET status = 'failed', last_error = $2, message = message - 'data'
+                    WHERE id = $1
+                "#,
+                email.id,
+                format!("{e:#}"),
+            )
+            .execute(db)
+            .await
+        }
+        Outcome::Suppressed => {
+            sqlx::query!(
+                r#"
+                    UPDATE email_outbox
+                    SET status = 'suppressed', message = message - 'data'
+                    WHERE id = $1
+                "#,
+                email.id,
+            )
+            .execute(db)
+            .await
+        }
+    }
+    .wrap_err("Failed to record email outcome")?;
+
+    Ok(())
+}
+
+async fn prune_outbox(db: &PgPool) -> eyre::Result<()> {
+    // Finished emails normally have their data removed when their outcome is recorded, this catches
+    // any that didn't (e.g. ones finished by an older version)
+    sqlx::query!(
+        r#"
+            UPDATE email_outbox
+            SET message = message - 'data'
+            WHERE status != 'pending' AND message ? 'data'
+        "#,
+    )
+    .execute(db)
+    .await
+    .wrap_err("Failed to remove the data of finished emails")?;
+
+    let result = sqlx::query!(
+        r#"
+            DELETE FROM email_outbox
+            WHERE status != 'pending' AND created_at < NOW() - make_interval(days => $1)
+        "#,
+        RETENTION_DAYS,
+    )
+    .execute(db)
+    .await
+    .wrap_err("Failed to delete old emails")?;
+
+    if result.rows_affected() > 0 {
+        info!("Pruned {} old email outbox entries", result.rows_affected());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::email::PasswordResetData;
+
+    /// Accepts every email, keeping track of who they were sent to.
+    #[derive(Default)]
+    struct RecordingTransport {
+        sent_to: Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl EmailTransport for RecordingTransport {
+        fn name(&self) -> &'static str {
+            "recording"
+        }
+
+        async fn send(&self, email: OutgoingEmail<'_>) -> Result<Option<String>, TransportError> {
+            self.sent_to.lock().unwrap().push(email.to.to_string());
+            Ok(Some(format!("provider-{}", email.id)))
+        }
+    }
+
+    async fn queue_password_reset(db: &PgPool, to: &str) {
+        insert(
+            db,
+            &EmailMessage {
+                to: to.into(),
+                locale: None,
+                template: EmailTemplate::PasswordReset(PasswordResetData {
+                    username: "user".into(),
+                    token: "secret-token".into(),
+                }),
+            },
+        )
+        .await
+        .unwrap();
+    }
+
+    async fn stored_emails(db: &PgPool) -> Vec<(String, Value)> {
+        sqlx::query_as::<_, (String, Value)>(
+            "SELECT status::text, message FROM email_outbox ORDER BY created_at",
+        )
+        .fetch_all(db)
+        .await
+        .unwrap()
+    }
+
+    async fn process(db: &PgPool, transport: &RecordingTransport) -> usize {
+        let renderer = EmailRenderer::new("https://example.org").unwrap();
+        process_batch(db, transport, &renderer).await.unwrap()
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn sent_emails_only_keep_their_template_name(db: PgPool) {
+        queue_password_reset(&db, "user@example.org").await;
+        assert_eq!(
+            stored_emails(&db).await[0].1["data"]["token"],
+            "secret-token"
+        );
+
+        let transport = RecordingTransport::default();
+        assert_eq!(process(&db, &transport).await, 1);
+
+        assert_eq!(*transport.sent_to.lock().unwrap(), vec!["user@example.org"]);
+        assert_eq!(
+            stored_emails(&db).await,
+            vec![("sent".into(), json!({ "template": "password-reset" }))]
+        );
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn suppressions_apply_regardless_of_case(db: PgPool) {
+        sqlx::query(
+            "INSERT INTO email_suppressions (email, reason) VALUES ('User@Example.org', 'bounce')",
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        queue_password_reset(&db, "user@example.org").await;
+
+        let transport = RecordingTransport::default();
+        assert_eq!(process(&db, &transport).await, 1);
+
+        assert!(transport.sent_to.lock().unwrap().is_empty());
+        assert_eq!(
+            stored_emails(&db).await,
+            vec![("suppressed".into(), json!({ "template": "password-reset" }))]
+        );
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn pruning_removes_data_from_finished_emails(db: PgPool) {
+        queue_password_reset(&db, "pending@example.org").await;
+        queue_password_reset(&db, "failed@example.org").await;
+        sqlx::query("UPDATE email_outbox SET status = 'failed' WHERE recipient = $1")
+            .bind("failed@example.org")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        prune_outbox(&db).await.unwrap();
+
+        let emails = stored_emails(&db).await;
+        assert_eq!(emails.len(), 2);
+        assert_eq!(emails[0].0, "pending");
+        assert_eq!(emails[0].1["data"]["token"], "secret-token");
+        assert_eq!(
+            emails[1],
+            ("failed".into(), json!({ "template": "password-reset" }))
+        );
+    }
+
+    #[test]
+    fn retry_delay_backs_off_exponentially() {
+        assert_eq!(retry_delay(1), Duration::from_secs(30));
+        assert_eq!(retry_delay(2), Duration::from_secs(60));
+        assert_eq!(retry_delay(3), Duration::from_secs(120));
+        assert_eq!(retry_delay(7), Duration::from_secs(1920));
+        assert_eq!(retry_delay(8), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(MAX_ATTEMPTS), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(i32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn retries_cover_a_provider_outage() {
+        // An email should keep being retried for a good while before we give up on it
+        let total: Duration = (1..MAX_ATTEMPTS).map(retry_delay).sum();
+        assert!(total >= Duration::from_secs(3 * 60 * 60), "{total:?}");
+    }
+}
@@ -0,0 +1,281 @@
+//! Rendering of emails from the templates generated by `pnpm gen-emails` (see the `email` directory
+//! in the repo root). The templates are Handlebars HTML, one set per language we have email
+//! translations for, and are embedded into the binary so they're always in sync with it.
+
+use color_eyre::eyre::{self, OptionExt, WrapErr};
+use handlebars::Handlebars;
+
+use crate::email::EmailTemplate;
+use crate::i18n::TranslationLanguage;
+
+/// The width plain text versions of emails are wrapped to.
+const TEXT_WIDTH: usize = 78;
+
+macro_rules! language_templates {
+    ($lang:literal: $($name:literal),+ $(,)?) => {
+        &[$((
+            $lang,
+            $name,
+            include_str!(concat!("../../templates/email/", $lang, "/", $name, ".html")),
+        )),+]
+    };
+}
+
+/// `(language code, template name, contents)` for every generated template.
+const TEMPLATES: &[&[(&str, &str, &str)]] = &[language_templates!(
+    "en":
+    "data-export",
+    "email-change",
+    "email-verification",
+    "login-name-change",
+    "password-change",
+    "password-reset",
+    "username-recovery",
+)];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+pub struct EmailRenderer {
+    registry: Handlebars<'static>,
+    canonical_host: String,
+}
+
+fn registry_key(language: TranslationLanguage, template_name: &str) -> String {
+    format!("{}/{template_name}", language.code())
+}
+
+impl EmailRenderer {
+    pub fn new(canonical_host: impl Into<String>) -> eyre::Result<Self> {
+        let mut registry = Handlebars::new();
+        for (lang, name, contents) in TEMPLATES.iter().copied().flatten() {
+            registry
+                .register_template_string(&format!("{lang}/{name}"), contents)
+                .wrap_err_with(|| format!("Failed to parse email template {lang}/{name}"))?;
+        }
+
+        Ok(Self {
+            registry,
+            canonical_host: canonical_host.into(),
+        })
+    }
+
+    /// Renders `template` in the language for `locale`, or in English if we don't have translated
+    /// emails for it.
+    pub fn render(
+        &self,
+        template: &EmailTemplate,
+        locale: Option<&str>,
+    ) -> eyre::Result<RenderedEmail> {
+        let name = template.template_name();
+        let language = locale
+            .and_then(TranslationLanguage::from_locale)
+            .filter(|&language| self.registry.has_template(&registry_key(language, name)))
+            .unwrap_or(TranslationLanguage::DEFAULT);
+
+        let html = self
+            .registry
+            .render(
+                &registry_key(language, name),
+                &self.template_data(template)?,
+            )
+            .wrap_err_with(|| format!("Failed to render email template {name}"))?;
+        let subject = extract_title(&html)
+            .filter(|title| !title.is_empty())
+            .unwrap_or_else(|| template.fallback_subject().to_owned());
+        let text = html_to_text(&html)?;
+
+        Ok(RenderedEmail {
+            subject,
+            html,
+            text,
+        })
+    }
+
+    /// The variables `template` is rendered with: its data, plus the variables every template has
+    /// access to.
+    fn template_data(&self, template: &EmailTemplate) -> eyre::Result<serde_json::Value> {
+        let mut data = template
+            .data()
+            .wrap_err("Failed to serialize template data structure")?;
+        let object = data
+            .as_object_mut()
+            .ok_or_eyre("Expected serialized template data to be an Object")?;
+        object.insert(
+            "HOST".into(),
+            serde_json::Value::String(self.canonical_host.clone()),
+        );
+        Ok(data)
+    }
+}
+
+/// Returns the (decoded) text of the HTML document's `<title>`, if it has one.
+fn extract_title(html: &str) -> Option<String> {
+    let start = html.find("<title>")? + "<title>".len();
+    let end = start + html[start..].find("</title>")?;
+    html2text::config::plain()
+        .string_from_read(&html.as_bytes()[start..end], usize::MAX)
+        .ok()
+        .map(|title| title.trim().to_owned())
+}
+
+/// Converts a rendered email to plain text for its `text/plain` part, skipping anything that's
+/// hidden when displayed (like the preview text shown in inbox listings).
+fn html_to_text(html: &str) -> eyre::Result<String> {
+    let text = html2text::config::plain()
+        .use_doc_css()
+        .string_from_read(html.as_bytes(), TEXT_WIDTH)
+        .wrap_err("Failed to convert email to plain text")?;
+    Ok(text.trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::email::{
+        DataExportData, EmailVerificationData, PasswordChangeData, UsernameRecoveryData,
+    };
+
+    fn renderer() -> EmailRenderer {
+        EmailRenderer::new("https://example.com").unwrap()
+    }
+
+    #[test]
+    fn every_template_is_generated() {
+        let templates = [
+            EmailTemplate::EmailChange(Default::default()),
+            EmailTemplate::EmailVerification(Default::default()),
+            EmailTemplate::PasswordChange(Default::default()),
+            EmailTemplate::LoginNameChange(Default::default()),
+            EmailTemplate::PasswordReset(Default::default()),
+            EmailTemplate::UsernameRecovery(Default::default()),
+            EmailTemplate::DataExport(Default::default()),
+        ];
+        let renderer = renderer();
+        for template in templates {
+            let rendered = renderer.render(&template, None);
+            assert!(
+                rendered.is_ok(),
+                "{}: {rendered:?}",
+                template.template_name()
+            );
+        }
+    }
+
+    #[test]
+    fn adds_host_to_template_data() {
+        let data = renderer()
+            .template_data(&EmailTemplate::EmailVerification(EmailVerificationData {
+                code: "asdf1234".into(),
+                username: "user".into(),
+            }))
+            .unwrap();
+
+        assert_eq!(
+            data,
+            json!({
+                "HOST": "https://example.com",
+                "code": "asdf1234",
+                "username": "user",
+            })
+        )
+    }
+
+    #[test]
+    fn renders_html_and_text() {
+        let rendered = renderer()
+            .render(
+                &EmailTemplate::EmailVerification(EmailVerificationData {
+                    code: "asdf1234".into(),
+                    username: "<user>".into(),
+                }),
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(rendered.subject, "ShieldBattery Email Verification");
+        assert!(rendered.html.contains("asdf1234"));
+        assert!(!rendered.html.contains("{{"));
+        assert!(rendered.text.contains("asdf1234"), "{}", rendered.text);
+        assert!(
+            rendered
+                .text
+                .contains("Thank you for signing up to ShieldBattery!"),
+            "{}",
+            rendered.text
+        );
+        // The preview text is hidden in the HTML, so it shouldn't show up in the text either
+        assert!(
+            !rendered
+                .text
+                .contains("Please confirm your email address by entering the code"),
+            "{}",
+            rendered.text
+        );
+    }
+
+    #[test]
+    fn escapes_variables_except_urls() {
+        let rendered = renderer()
+            .render(
+                &EmailTemplate::PasswordChange(PasswordChangeData {
+                    username: "<b>user</b>".into(),
+                }),
+                None,
+            )
+            .unwrap();
+        assert!(rendered.html.contains("&lt;b&gt;user&lt;/b&gt;"));
+        assert!(!rendered.html.contains("<b>user</b>"));
+
+        let rendered = renderer()
+            .render(
+                &EmailTemplate::DataExport(DataExportData {
+                    username: "user".into(),
+                    download_url: "https://files.example.com/export.zip?a=1&b=2".into(),
+                    expires_in_days: 7,
+                }),
+                None,
+            )
+            .unwrap();
+        assert!(
+            rendered
+                .html
+                .contains("href=\"https://files.example.com/export.zip?a=1&b=2\"")
+        );
+    }
+
+    #[test]
+    fn lists_recovered_usernames() {
+        let rendered = renderer()
+            .render(
+                &EmailTemplate::UsernameRecovery(UsernameRecoveryData {
+                    email: "user@example.org".into(),
+                    usernames: vec!["first".into(), "second".into()],
+                }),
+                None,
+            )
+            .unwrap();
+        assert!(rendered.html.contains("<li>first</li>"));
+        assert!(rendered.html.contains("<li>second</li>"));
+    }
+
+    #[test]
+    fn falls_back_to_english() {
+        let template = EmailTemplate::PasswordChange(PasswordChangeData {
+            username: "user".into(),
+        });
+        let renderer = renderer();
+        let english = renderer.render(&template, None).unwrap();
+
+        assert_eq!(renderer.render(&template, Some("en-US")).unwrap(), english);
+        // Languages we don't have email translations for (yet), and ones we don't support at all
+        assert_eq!(renderer.render(&template, Some("ko")).unwrap(), english);
+        assert_eq!(renderer.render(&template, Some("de-DE")).unwrap(), english);
+    }
+}
@@ -0,0 +1,348 @@
+//! Webhooks our email provider calls to tell us about delivery problems. Permanent bounces and spam
+//! complaints add the address to `email_suppressions`, so we stop sending to it (which would
+//! otherwise hurt our sender reputation).
+
+use std::sync::Arc;
+
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use chrono::Utc;
+use color_eyre::eyre::{self, WrapErr};
+use hmac::{Hmac, KeyInit, Mac};
+use secrecy::ExposeSecret;
+use serde::Deserialize;
+use sha2::Sha256;
+use sqlx::PgPool;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::configuration::Settings;
+use crate::state::AppState;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Webhooks signed longer ago than this are rejected, so captured requests can't be replayed.
+const WEBHOOK_MAX_AGE_SECONDS: i64 = 15 * 60;
+
+const EMAIL_WEBHOOK_EVENTS_TOTAL: &str = "email_webhook_events_total";
+
+fn describe_metrics() {
+    ::metrics::describe_counter!(
+        EMAIL_WEBHOOK_EVENTS_TOTAL,
+        ::metrics::Unit::Count,
+        "Email provider webhook requests received, per event and result"
+    );
+}
+
+fn record_webhook_event(result: &'static str, event: &str) {
+    // Events are reported as-is only for the ones we know about, to keep label cardinality bounded
+    let event = match event {
+        "failed" | "complained" => event.to_owned(),
+        _ => "other".to_owned(),
+    };
+    ::metrics::counter!(EMAIL_WEBHOOK_EVENTS_TOTAL, "event" => event, "result" => result)
+        .increment(1);
+}
+
+pub fn create_email_api() -> Router<AppState> {
+    describe_metrics();
+    Router::new().route("/webhooks/mailgun", post(mailgun_webhook))
+}
+
+#[derive(Deserialize)]
+struct MailgunWebhook {
+    signature: MailgunSignature,
+    #[serde(rename = "event-data")]
+    event_data: MailgunEventData,
+}
+
+#[derive(Deserialize)]
+struct MailgunSignature {
+    timestamp: String,
+    token: String,
+    signature: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct MailgunEventData {
+    event: String,
+    recipient: Option<String>,
+    /// For `failed` events, whether Mailgun will keep retrying (`temporary`) or has given up
+    /// (`permanent`).
+    severity: Option<String>,
+    reason: Option<String>,
+    #[serde(default)]
+    delivery_status: MailgunDeliveryStatus,
+    #[serde(default)]
+    user_variables: MailgunUserVariables,
+}
+
+#[derive(Deserialize, Default)]
+struct MailgunDeliveryStatus {
+    description: Option<String>,
+    message: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+struct MailgunUserVariables {
+    outbox_id: Option<Uuid>,
+}
+
+/// Verifies the HMAC-SHA256 signature Mailgun attaches to webhooks. The signed message is the
+/// concatenation of the timestamp and the (random) token.
+fn verify_signature(signing_key: &str, timestamp: &str, token: &str, signature: &str) -> bool {
+    let Ok(expected) = data_encoding::HEXLOWER_PERMISSIVE.decode(signature.as_bytes()) else {
+        return false;
+    };
+    let Ok(mut mac) = HmacSha256::new_from_slice(signing_key.as_bytes()) else {
+        return false;
+    };
+    mac.update(timestamp.as_bytes());
+    mac.update(token.as_bytes());
+    mac.verify_slice(&expected).is_ok()
+}
+
+fn is_fresh(timestamp: &str) -> bool {
+    timestamp
+        .parse::<i64>()
+        .is_ok_and(|ts| (Utc::now().timestamp() - ts).abs() <= WEBHOOK_MAX_AGE_SECONDS)
+}
+
+/// What a delivery event means for the recipient's address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Suppression {
+    Bounce,
+    Complaint,
+}
+
+impl Suppression {
+    fn from_event(event: &MailgunEventData) -> Option<Self> {
+        match event.event.as_str() {
+            // Temporary failures are retried by Mailgun, so they don't tell us anything yet
+            "failed" if event.severity.as_deref() == Some("permanent") => Some(Suppression::Bounce),
+            "complained" => Some(Suppression::Complaint),
+            _ => None,
+        }
+    }
+}
+
+async fn mailgun_webhook(
+    State(settings): State<Arc<Settings>>,
+    State(db): State<PgPool>,
+    body: Bytes,
+) -> Response {
+    let Some(signing_key) = settings
+        .email_transport
+        .as_ref()
+        .and_then(|t| t.mailgun())
+        .and_then(|m| m.webhook_signing_key())
+    else {
+        return (StatusCode::NOT_FOUND, "Not Found").into_response();
+    };
+
+    let webhook = match serde_json::from_slice::<MailgunWebhook>(&body) {
+        Ok(webhook) => webhook,
+        Err(e) => {
+            warn!("Failed to parse Mailgun webhook: {e:?}");
+            record_webhook_event("invalid_body", "unknown");
+            // Mailgun won't retry webhooks that get a 406
+            return (StatusCode::NOT_ACCEPTABLE, "Invalid body").into_response();
+        }
+    };
+    let event = &webhook.event_data.event;
+
+    let MailgunSignature {
+        timestamp,
+        token,
+        signature,
+    } = &webhook.signature;
+    if !verify_signature(signing_key.expose_secret(), timestamp, token, signature) {
+        warn!("Mailgun webhook signature verification failed");
+        record_webhook_event("bad_signature", event);
+        return (StatusCode::FORBIDDEN, "Invalid signature").into_response();
+    }
+    if !is_fresh(timestamp) {
+        warn!("Rejecting stale Mailgun webhook");
+        record_webhook_event("stale", event);
+        return (StatusCode::FORBIDDEN, "Stale message").into_response();
+    }
+
+    let Some(suppression) = Suppression::from_event(&webhook.event_data) else {
+        record_webhook_event("ignored", event);
+        return StatusCode::OK.into_response();
+    };
+    let Some(recipient) = webhook.event_data.recipient.as_deref() else {
+        record_webhook_event("invalid_body", event);
+        return (StatusCode::NOT_ACCEPTABLE, "Missing recipient").into_response();
+    };
+
+    match suppress_address(&db, recipient, suppression, &webhook.event_data).await {
+        Ok(()) => {
+            info!("Suppressing emails to an address because of a {suppression:?}");
+            record_webhook_event("ok", event);
+            StatusCode::OK.into_response()
+        }
+        Err(e) => {
+            error!("Failed to handle Mailgun webhook: {e:?}");
+            record_webhook_event("error", event);
+            // Mailgun will retry this later
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+async fn suppress_address(
+    db: &PgPool,
+    recipient: &str,
+    suppression: Suppression,
+    event: &MailgunEventData,
+) -> eyre::Result<()> {
+    let details = [
+        event.reason.as_deref(),
+        event.delivery_status.description.as_deref(),
+        event.delivery_status.message.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|s| !s.is_empty())
+    .collect::<Vec<_>>()
+    .join(": ");
+    let (reason, status) = match suppression {
+        Suppression::Bounce => ("bounce", "bounced"),
+        Suppression::Complaint => ("complaint", "complained"),
+    };
+
+    let mut tx = db.begin().await?;
+    // A complaint is the stronger signal, so it replaces an earlier bounce but not vice versa
+    sqlx::query!(
+        r#"
+            INSERT INTO email_suppressions (email, reason, details)
+            VALUES ($1, $2::text::email_suppression_reason, $3)
+            ON CONFLICT (email) DO UPDATE
+            SET reason = EXCLUDED.reason, details = EXCLUDED.details, created_at = NOW()
+            WHERE EXCLUDED.reason = 'complaint'
+        "#,
+        recipient,
+        reason,
+        details,
+    )
+    .execute(&mut *tx)
+    .await
+    .wrap_err("Failed to add email suppression")?;
+
+    if let Some(outbox_id) = event.user_variables.outbox_id {
+        sqlx::query!(
+            r#"
+                UPDATE email_outbox
+                SET status = $2::text::email_outbox_status
+                WHERE id = $1
+            "#,
+            outbox_id,
+            status,
+        )
+        .execute(&mut *tx)
+        .await
+        .wrap_err("Failed to update outbox entry")?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign(key: &str, timestamp: &str, token: &str) -> String {
+        let mut mac = HmacSha256::new_from_slice(key.as_bytes()).unwrap();
+        mac.update(timestamp.as_bytes());
+        mac.update(token.as_bytes());
+        data_encoding::HEXLOWER.encode(&mac.finalize().into_bytes())
+    }
+
+    #[test]
+    fn verify_signature_accepts_a_valid_signature() {
+        let key = "key-shieldbattery-test";
+        let timestamp = "1529006854";
+        let token = "a8ce0edb2dd8301dee6c2405235584e45aa91d1e9f979f3de0";
+        let signature = sign(key, timestamp, token);
+
+        assert!(verify_signature(key, timestamp, token, &signature));
+        assert!(verify_signature(
+            key,
+            timestamp,
+            token,
+            &signature.to_uppercase()
+        ));
+    }
+
+    #[test]
+    fn verify_signature_rejects_tampering_and_bad_input() {
+        let key = "key-shieldbattery-test";
+        let timestamp = "1529006854";
+        let token = "a8ce0edb2dd8301dee6c2405235584e45aa91d1e9f979f3de0";
+        let signature = sign(key, timestamp, token);
+
+        // Wrong key.
+        assert!(!verify_signature("other-key", timestamp, token, &signature));
+        // Replayed with a new timestamp.
+        assert!(!verify_signature(key, "1529006855", token, &signature));
+        // Malformed signature.
+        assert!(!verify_signature(key, timestamp, token, "not hex"));
+        assert!(!verify_signature(key, timestamp, token, ""));
+    }
+
+    #[test]
+    fn rejects_stale_timestamps() {
+        let now = Utc::now().timestamp();
+        assert!(is_fresh(&now.to_string()));
+        assert!(is_fresh(&(now - 60).to_string()));
+        assert!(!is_fresh(&(now - WEBHOOK_MAX_AGE_SECONDS - 60).to_string()));
+        assert!(!is_fresh("yesterday"));
+    }
+
+    #[test]
+    fn only_suppresses_for_permanent_problems() {
+        let parse =
+            |json: serde_json::Value| -> MailgunEventData { serde_json::from_value(json).unwrap() };
+
+        let bounce = parse(serde_json::json!({
+            "event": "failed",
+            "severity": "permanent",
+            "recipient": "user@example.org",
+            "reason": "bounce",
+            "delivery-status": { "message": "No such mailbox", "code": 550 },
+            "user-variables": { "outbox-id": "0190d4c2-6a5e-7a4c-9d7e-1f2b3c4d5e6f" },
+        }));
+        assert_eq!(Suppression::from_event(&bounce), Some(Suppression::Bounce));
+        assert!(bounce.user_variables.outbox_id.is_some());
+
+        let temporary = parse(serde_json::json!({
+            "event": "failed",
+            "severity": "temporary",
+            "recipient": "user@example.org",
+        }));
+        assert_eq!(Suppression::from_event(&temporary), None);
+
+        let complaint = parse(serde_json::json!({
+            "event": "complained",
+            "recipient": "user@example.org",
+        }));
+        assert_eq!(
+            Suppression::from_event(&complaint),
+            Some(Suppression::Complaint)
+        );
+
+        let delivered = parse(serde_json::json!({
+            "event": "delivered",
+            "recipient": "user@example.org",
+        }));
+        assert_eq!(Suppression::from_event(&delivered), None);
+    }
+}
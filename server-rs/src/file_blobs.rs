@@ -0,0 +1,609 @@
+//! Reference-counted, content-addressed uploads in the [`FileStore`].
+//!
+//! Each upload is stored as a blob: a group of files (e.g. an image and its variants, or a map and
+//! its rendered images) identified by the path that rows referencing it store. Blob paths are
+//! derived from a hash of their content, so uploading the same content twice reuses the first
+//! upload rather than writing new files. Database triggers count the rows that reference each blob
+//! (see the `create_file_blobs` migration), and [`file_blob_gc_loop`] deletes blobs that have been
+//! unreferenced for longer than a grace period.
+
+use std::time::Duration;
+
+use async_graphql::{Context, Object, SimpleObject};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{self, WrapErr};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::file_store::{FileStore, FileVisibility};
+use crate::users::permissions::RequiredPermission;
+
+/// How long a blob can go unreferenced before it's deleted. This needs to comfortably cover the
+/// time between uploading a file and saving whatever refers to it (e.g. writing a news post).
+const GC_GRACE_PERIOD: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+const GC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How many blobs a single GC pass will delete, so one pass can't run for too long.
+const GC_BATCH_SIZE: i64 = 500;
+
+/// Returns the SHA-256 hash of some file content.
+pub fn content_hash(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+/// One file to be written as part of a blob.
+#[derive(Debug)]
+pub struct BlobFile {
+    pub path: String,
+    pub data: Vec<u8>,
+    pub content_type: &'static str,
+    pub visibility: FileVisibility,
+}
+
+/// Writes the files of a blob to the file store, unless a blob with the same path already exists. `path` should be derived from `content_hash` so that identical content
+/// maps to the same blob. Returns whether an existing blob was reused.
+///
+/// New blobs start out unreferenced, and will be garbage collected if nothing references them
+/// within the grace period. Reusing an unreferenced blob restarts its grace period.
+pub async fn store_blob(
+    db: &PgPool,
+    file_store: &FileStore,
+    path: &str,
+    content_hash: [u8; 32],
+    files: Vec<BlobFile>,
+) -> eyre::Result<bool> {
+    // Touching the row (rather than just checking for it) means this waits for any GC of the blob
+    // that's in progress, and then sees that it's gone.
+    let existing = sqlx::query_scalar!(
+        r#"
+            UPDATE file_blobs
+            SET unreferenced_since = CASE WHEN ref_count = 0 THEN NOW() END
+            WHERE path = $1
+            RETURNING path
+        "#,
+        path,
+    )
+    .fetch_optional(db)
+    .await
+    .wrap_err("Failed to check for an existing blob")?;
+    if existing.is_some() {
+        return Ok(true);
+    }
+
+    let mut file_rows = Vec::with_capacity(files.len());
+    for file in files {
+        let size = file.data.len() as i64;
+        file_store
+            .write_with_visibility(&file.path, file.data, file.content_type, file.visibility)
+            .await
+            .wrap_err_with(|| format!("Failed to write blob file {}", file.path))?;
+        file_rows.push((file.path, file.content_type, size));
+    }
+
+    let mut tx = db.begin().await.wrap_err("Failed to start transaction")?;
+    // A concurrent upload of the same content may have won the race, in which case it wrote the
+    // same files and its rows can be kept as they are.
+    let inserted = sqlx::query!(
+        r#"
+            INSERT INTO file_blobs (path, content_hash)
+            VALUES ($1, $2)
+            ON CONFLICT (path) DO NOTHING
+        "#,
+        path,
+        &content_hash[..],
+    )
+    .execute(&mut *tx)
+    .await
+    .wrap_err("Failed to insert blob")?
+    .rows_affected()
+        > 0;
+    if inserted {
+        for (file_path, content_type, size) in file_rows {
+            sqlx::query!(
+                r#"
+                    INSERT INTO file_blob_files (path, blob_path, content_type, size)
+                    VALUES ($1, $2, $3, $4)
+                    ON CONFLICT (path) DO NOTHING
+                "#,
+                file_path,
+                path,
+                content_type,
+                size,
+            )
+            .execute(&mut *tx)
+            .await
+            .wrap_err("Failed to insert blob file")?;
+        }
+    }
+    tx.commit().await.wrap_err("Failed to commit transaction")?;
+
+    Ok(!inserted)
+}
+
+/// Writes `files` to the file store and adds them to the existing blob at `blob_path` (replacing any
+/// of its files at the same paths), e.g. for images that are rendered after the blob was stored. If
+/// there's no such blob (it predates blob tracking), the files are still written but stay untracked.
+pub async fn add_blob_files(
+    db: &PgPool,
+    file_store: &FileStore,
+    blob_path: &str,
+    files: Vec<BlobFile>,
+) -> eyre::Result<()> {
+    for file in files {
+        let size = file.data.len() as i64;
+        file_store
+            .write_with_visibility(&file.path, file.data, file.content_type, file.visibility)
+            .await
+            .wrap_err_with(|| format!("Failed to write blob file {}", file.path))?;
+        sqlx::query!(
+            r#"
+                INSERT INTO file_blob_files (path, blob_path, content_type, size)
+                SELECT $1, path, $3, $4
+                FROM file_blobs
+                WHERE path = $2
+                ON CONFLICT (path) DO UPDATE
+                SET content_type = EXCLUDED.content_type, size = EXCLUDED.size
+            "#,
+            file.path,
+            blob_path,
+            file.content_type,
+            size,
+        )
+        .execute(db)
+        .await
+        .wrap_err("Failed to insert blob file")?;
+    }
+    Ok(())
+}
+
+/// A blob that garbage collection deleted (or would delete, for a dry run).
+#[derive(SimpleObject, Debug, Clone)]
+pub struct CollectedBlob {
+    pub path: String,
+    pub files: Vec<String>,
+    /// Total size of the blob's files, in bytes.
+    pub size: i64,
+    pub unreferenced_since: DateTime<Utc>,
+}
+
+#[derive(SimpleObject, Debug, Clone, Default)]
+pub struct GarbageCollectionReport {
+    pub blobs: Vec<CollectedBlob>,
+    /// Total size of the collected blobs' files, in bytes.
+    pub total_size: i64,
+}
+
+impl GarbageCollectionReport {
+    fn new(blobs: Vec<CollectedBlob>) -> Self {
+        Self {
+            total_size: blobs.iter().map(|b| b.size).sum(),
+            blobs,
+        }
+    }
+}
+
+/// Finds the blobs that were unreferenced as of `cutoff` (oldest first).
+async fn load_collectable_blobs(
+    db: &PgPool,
+    cutoff: DateTime<Utc>,
+) -> eyre::Result<Vec<CollectedBlob>> {
+    sqlx::query_as!(
+        CollectedBlob,
+        r#"
+            SELECT b.path, b.unreferenced_since as "unreferenced_since!",
+                COALESCE(array_agg(f.path) FILTER (WHERE f.path IS NOT NULL), '{}') as "files!",
+                COALESCE(SUM(f.size), 0)::bigint as "size!"
+            FROM file_blobs b
+            LEFT JOIN file_blob_files f ON f.blob_path = b.path
+            WHERE b.ref_count = 0 AND b.unreferenced_since < $1
+            GROUP BY b.path
+            ORDER BY b.unreferenced_since
+            LIMIT $2
+        "#,
+        cutoff,
+        GC_BATCH_SIZE,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load unreferenced blobs")
+}
+
+/// Deletes blobs that have been unreferenced for longer than `grace_period`. With `dry_run`,
+/// nothing is deleted and the report lists what would have been.
+pub async fn collect_garbage(
+    db: &PgPool,
+    file_store: &FileStore,
+    grace_period: Duration,
+    dry_run: bool,
+) -> eyre::Result<GarbageCollectionReport> {
+    let cutoff = Utc::now() - grace_period;
+    let candidates = load_collectable_blobs(db, cutoff).await?;
+    if dry_run {
+        return Ok(GarbageCollectionReport::new(candidates));
+    }
+
+    let mut collected = Vec::with_capacity(candidates.len());
+    for blob in candidates {
+        match delete_blob(db, file_store, &blob.path, cutoff).await {
+            Ok(true) => collected.push(blob),
+            Ok(false) => {}
+            Err(e) => error!("Failed to delete blob {}: {e:?}", blob.path),
+        }
+    }
+    Ok(GarbageCollectionReport::new(collected))
+}
+
+/// Deletes a blob and its files, if it's still unreferenced as of `cutoff`. Returns whether it was
+/// deleted.
+async fn delete_blob(
+    db: &PgPool,
+    file_store: &FileStore,
+    path: &str,
+    cutoff: DateTime<Utc>,
+) -> eyre::Result<bool> {
+    let mut tx = db.begin().await.wrap_err("Failed to start transaction")?;
+    // The row lock keeps new references (and re-uploads) from landing on the blob while its files
+    // are being deleted. Anything that gets blocked on it will see the blob as gone afterwards.
+    let locked = sqlx::query_scalar!(
+        r#"
+            SELECT path
+            FROM file_blobs
+            WHERE path = $1 AND ref_count = 0 AND unreferenced_since < $2
+            FOR UPDATE SKIP LOCKED
+        "#,
+        path,
+        cutoff,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .wrap_err("Failed to lock blob")?;
+    if locked.is_none() {
+        return Ok(false);
+    }
+
+    let files = sqlx::query_scalar!(
+        "SELECT path FROM file_blob_files WHERE blob_path = $1",
+        path
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .wrap_err("Failed to load blob files")?;
+    for file in &files {
+        file_store.delete(file).await?;
+    }
+
+    sqlx::query!("DELETE FROM file_blobs WHERE path = $1", path)
+        .execute(&mut *tx)
+        .await
+        .wrap_err("Failed to delete blob")?;
+    tx.commit().await.wrap_err("Failed to commit transaction")?;
+    Ok(true)
+}
+
+/// Runs forever, periodically deleting unreferenced blobs. Meant to be spawned once per server
+/// instance.
+pub async fn file_blob_gc_loop(db: PgPool, file_store: FileStore) {
+    let mut interval = tokio::time::interval(GC_INTERVAL);
+    loop {
+        interval.tick().await;
+        match collect_garbage(&db, &file_store, GC_GRACE_PERIOD, false).await {
+            Ok(report) if !report.blobs.is_empty() => info!(
+                "Deleted {} unreferenced blobs ({} bytes)",
+                report.blobs.len(),
+                report.total_size
+            ),
+            Ok(_) => {}
+            Err(e) => error!("Collecting unreferenced blobs failed: {e:?}"),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct FileBlobsQuery;
+
+#[Object]
+impl FileBlobsQuery {
+    /// Lists the uploads that the next garbage collection pass would delete, without deleting
+    /// anything.
+    #[graphql(guard = RequiredPermission::Debug)]
+    async fn file_blob_garbage(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<GarbageCollectionReport> {
+        Ok(collect_garbage(
+            ctx.data::<PgPool>()?,
+            ctx.data::<FileStore>()?,
+            GC_GRACE_PERIOD,
+            true,
+        )
+        .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::users::SbUserId;
+
+    #[test]
+    fn report_totals_blob_sizes() {
+        let blob = |path: &str, size| CollectedBlob {
+            path: path.to_owned(),
+            files: vec![path.to_owned()],
+            size,
+            unreferenced_since: Utc::now(),
+        };
+        let report = GarbageCollectionReport::new(vec![blob("a.webp", 100), blob("b.webp", 23)]);
+        assert_eq!(report.total_size, 123);
+        assert_eq!(GarbageCollectionReport::default().total_size, 0);
+    }
+
+    fn blob_files(path: &str, data: &[u8]) -> Vec<BlobFile> {
+        vec![
+            BlobFile {
+                path: path.to_owned(),
+                data: data.to_vec(),
+                content_type: "image/webp",
+                visibility: FileVisibility::Public,
+            },
+            BlobFile {
+                path: format!("{path}.jpg"),
+                data: data.to_vec(),
+                content_type: "image/jpeg",
+                visibility: FileVisibility::Public,
+            },
+        ]
+    }
+
+    async fn ref_count(db: &PgPool, path: &str) -> (i32, bool) {
+        sqlx::query_as(
+            "SELECT ref_count, unreferenced_since IS NOT NULL FROM file_blobs WHERE path = $1",
+        )
+        .bind(path)
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    async fn set_avatar(db: &PgPool, user_id: SbUserId, path: Option<&str>) {
+        sqlx::query("UPDATE users SET avatar_path = $2 WHERE id = $1")
+            .bind(user_id.0)
+            .bind(path)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    async fn backdate_unreferenced(db: &PgPool, path: &str, days: i32) {
+        sqlx::query(
+            r#"
+                UPDATE file_blobs
+                SET unreferenced_since = NOW() - make_interval(days => $2)
+                WHERE path = $1
+            "#,
+        )
+        .bind(path)
+        .bind(days)
+        .execute(db)
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn reference_counts_follow_referencing_rows(db: PgPool) {
+        let dir = tempfile::tempdir().unwrap();
+        let file_store = crate::test_utils::local_file_store(dir.path());
+        let first = crate::test_utils::create_user(&db, "first").await;
+        let second = crate::test_utils::create_user(&db, "second").await;
+        let path = "avatars/ab/cd/abcd.webp";
+
+        let reused = store_blob(
+            &db,
+            &file_store,
+            path,
+            content_hash(b"a"),
+            blob_files(path, b"a"),
+        )
+        .await
+        .unwrap();
+        assert!(!reused);
+        assert_eq!(ref_count(&db, path).await, (0, true));
+
+        set_avatar(&db, first, Some(path)).await;
+        set_avatar(&db, second, Some(path)).await;
+        assert_eq!(ref_count(&db, path).await, (2, false));
+
+        // Uploading the same content again reuses the blob without touching its references
+        let reused = store_blob(
+            &db,
+            &file_store,
+            path,
+            content_hash(b"a"),
+            blob_files(path, b"a"),
+        )
+        .await
+        .unwrap();
+        assert!(reused);
+        assert_eq!(ref_count(&db, path).await, (2, false));
+
+        set_avatar(&db, first, None).await;
+        assert_eq!(ref_count(&db, path).await, (1, false));
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(second.0)
+            .execute(&db)
+            .await
+            .unwrap();
+        assert_eq!(ref_count(&db, path).await, (0, true));
+
+        // Paths that aren't blobs are ignored
+        set_avatar(&db, first, Some("avatars/legacy.png")).await;
+        assert_eq!(ref_count(&db, path).await, (0, true));
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn collects_blobs_unreferenced_past_the_grace_period(db: PgPool) {
+        let dir = tempfile::tempdir().unwrap();
+        let file_store = crate::test_utils::local_file_store(dir.path());
+        let user = crate::test_utils::create_user(&db, "pachi").await;
+        let referenced = "avatars/referenced.webp";
+        let fresh = "avatars/fresh.webp";
+        let stale = "avatars/stale.webp";
+        for path in [referenced, fresh, stale] {
+            store_blob(
+                &db,
+                &file_store,
+                path,
+                content_hash(path.as_bytes()),
+                blob_files(path, b"image"),
+            )
+            .await
+            .unwrap();
+            backdate_unreferenced(&db, path, 2).await;
+        }
+        set_avatar(&db, user, Some(referenced)).await;
+        backdate_unreferenced(&db, stale, 8).await;
+
+        let report = collect_garbage(&db, &file_store, GC_GRACE_PERIOD, true)
+            .await
+            .unwrap();
+        assert_eq!(
+            report
+                .blobs
+                .iter()
+                .map(|b| b.path.as_str())
+                .collect::<Vec<_>>(),
+            vec![stale]
+        );
+        assert_eq!(report.total_size, 10);
+        // A dry run doesn't delete anything
+        assert!(file_store.read(stale).await.is_ok());
+
+        let report = collect_garbage(&db, &file_store, GC_GRACE_PERIOD, false)
+            .await
+            .unwrap();
+        assert_eq!(report.blobs.len(), 1);
+        let mut files = report.blobs[0].files.clone();
+        files.sort();
+        assert_eq!(files, vec![stale.to_owned(), format!("{stale}.jpg")]);
+        for file in files {
+            assert!(file_store.read(&file).await.is_err());
+        }
+        let remaining: Vec<String> =
+            sqlx::query_scalar("SELECT path FROM file_blobs ORDER BY path")
+                .fetch_all(&db)
+                .await
+                .unwrap();
+        assert_eq!(remaining, vec![fresh.to_owned(), referenced.to_owned()]);
+        let orphaned_files: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM file_blob_files WHERE blob_path = $1")
+                .bind(stale)
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!(orphaned_files, 0);
+        assert!(file_store.read(referenced).await.is_ok());
+        assert!(file_store.read(fresh).await.is_ok());
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn delete_blob_skips_blobs_that_were_referenced_again(db: PgPool) {
+        let dir = tempfile::tempdir().unwrap();
+        let file_store = crate::test_utils::local_file_store(dir.path());
+        let user = crate::test_utils::create_user(&db, "pachi").await;
+        let path = "avatars/blob.webp";
+        store_blob(
+            &db,
+            &file_store,
+            path,
+            content_hash(b"a"),
+            blob_files(path, b"a"),
+        )
+        .await
+        .unwrap();
+        backdate_unreferenced(&db, path, 8).await;
+        let cutoff = Utc::now() - GC_GRACE_PERIOD;
+
+        // Picked up as a candidate, but referenced before it could be deleted
+        set_avatar(&db, user, Some(path)).await;
+        assert!(!delete_blob(&db, &file_store, path, cutoff).await.unwrap());
+        assert!(file_store.read(path).await.is_ok());
+
+        // Unreferenced again, which restarts its grace period
+        set_avatar(&db, user, None).await;
+        assert!(!delete_blob(&db, &file_store, path, cutoff).await.unwrap());
+        assert!(
+            delete_blob(&db, &file_store, path, Utc::now())
+                .await
+                .unwrap()
+        );
+        assert!(file_store.read(path).await.is_err());
+        assert!(file_store.read(&format!("{path}.jpg")).await.is_err());
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn files_can_be_added_to_existing_blobs(db: PgPool) {
+        let dir = tempfile::tempdir().unwrap();
+        let file_store = crate::test_utils::local_file_store(dir.path());
+        let path = "maps/blob.scm";
+        store_blob(
+            &db,
+            &file_store,
+            path,
+            content_hash(b"a"),
+            blob_files(path, b"a"),
+        )
+        .await
+        .unwrap();
+
+        add_blob_files(
+            &db,
+            &file_store,
+            path,
+            blob_files("maps/blob-512", b"image"),
+        )
+        .await
+        .unwrap();
+        // Replacing a file updates it rather than adding another
+        add_blob_files(
+            &db,
+            &file_store,
+            path,
+            blob_files("maps/blob-512", b"bigger image"),
+        )
+        .await
+        .unwrap();
+        // Files for a blob that isn't tracked are still written
+        add_blob_files(
+            &db,
+            &file_store,
+            "maps/legacy.scm",
+            blob_files("maps/legacy-512", b"x"),
+        )
+        .await
+        .unwrap();
+        assert!(file_store.read("maps/legacy-512").await.is_ok());
+
+        let files: Vec<(String, i64)> =
+            sqlx::query_as("SELECT path, size FROM file_blob_files ORDER BY path")
+                .fetch_all(&db)
+                .await
+                .unwrap();
+        assert_eq!(
+            files,
+            vec![
+                ("maps/blob-512".to_owned(), 12),
+                ("maps/blob-512.jpg".to_owned(), 12),
+                ("maps/blob.scm".to_owned(), 1),
+                ("maps/blob.scm.jpg".to_owned(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn content_hashes_are_sha256() {
+        assert_eq!(
+            data_encoding::HEXLOWER.encode(&content_hash(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
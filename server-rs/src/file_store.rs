@@ -5,7 +5,10 @@ use std::{
 };
 
 use aws_config::{BehaviorVersion, Region};
-use aws_sdk_s3::{config::Credentials, presigning::PresigningConfig};
+use aws_sdk_s3::{
+    config::Credentials, presigning::PresigningConfig, primitives::ByteStream,
+    types::ObjectCannedAcl,
+};
 use color_eyre::eyre::{self, Context as _, bail};
 use secrecy::ExposeSecret;
 use url::Url;
@@ -66,6 +69,68 @@ impl FileStore {
             }
         }
     }
+
+    /// Writes `data` to the store under `filename`, replacing any existing file there. Files are
+    /// written privately, so they are only reachable through a signed URL.
+    pub async fn write(
+        &self,
+        filename: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> eyre::Result<()> {
+        self.write_with_visibility(filename, data, content_type, FileVisibility::Private)
+            .await
+    }
+
+    /// Writes `data` to the store under `filename`, replacing any existing file there. The file is
+    /// publicly readable, so it can be served through its unsigned [`url`](Self::url).
+    pub async fn write_public(
+        &self,
+        filename: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> eyre::Result<()> {
+        self.write_with_visibility(filename, data, content_type, FileVisibility::Public)
+            .await
+    }
+
+    /// Reads the full contents of the file at `filename`.
+    pub async fn read(&self, filename: &str) -> eyre::Result<Vec<u8>> {
+        match self {
+            FileStore::Local(store) => store.read(filename).await,
+            FileStore::Spaces(store) => store.read(filename).await,
+        }
+    }
+
+    /// Deletes the file at `filename`. Deleting a file that doesn't exist is not an error.
+    pub async fn delete(&self, filename: &str) -> eyre::Result<()> {
+        match self {
+            FileStore::Local(store) => store.delete(filename).await,
+            FileStore::Spaces(store) => store.delete(filename).await,
+        }
+    }
+
+    /// Writes `data` to the store under `filename` with the given `visibility`, replacing any
+    /// existing file there.
+    pub async fn write_with_visibility(
+        &self,
+        filename: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        visibility: FileVisibility,
+    ) -> eyre::Result<()> {
+        match self {
+            FileStore::Local(store) => store.write(filename, data, content_type, visibility).await,
+            FileStore::Spaces(store) => store.write(filename, data, content_type, visibility).await,
+        }
+    }
+}
+
+/// Who can read a written file without a signed URL.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FileVisibility {
+    Private,
+    Public,
 }
 
 trait FileStoreImpl {
@@ -82,6 +147,15 @@ trait FileStoreImpl {
         download_filename: &str,
         expires_in: Duration,
     ) -> eyre::Result<String>;
+    async fn write(
+        &self,
+        filename: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        visibility: FileVisibility,
+    ) -> eyre::Result<()>;
+    async fn read(&self, filename: &str) -> eyre::Result<Vec<u8>>;
+    async fn delete(&self, filename: &str) -> eyre::Result<()>;
 }
 
 pub async fn file_store_from_config(
@@ -101,7 +175,6 @@ pub async fn file_store_from_config(
 
 #[derive(Debug, Clone)]
 pub struct LocalFileStore {
-    #[allow(dead_code)]
     path: PathBuf,
     canonical_host: String,
     start_instant: Instant,
@@ -129,7 +202,6 @@ impl LocalFileStore {
 
     /// Returns the full path to a file, combining it with this file store's base path and ensuring
     /// that the file is a descendant of the base path.
-    #[allow(dead_code)]
     fn get_full_path(&self, filename: &str) -> eyre::Result<PathBuf> {
         let full_path = self.path.join(filename);
         let canonical = full_path
@@ -198,6 +270,58 @@ impl FileStoreImpl for LocalFileStore {
         // The dev file store can't set response headers; the filename is only cosmetic here.
         self.signed_url(filename).await
     }
+
+    async fn write(
+        &self,
+        filename: &str,
+        data: Vec<u8>,
+        _content_type: &str,
+        // Everything in the dev file store is served publicly
+        _visibility: FileVisibility,
+    ) -> eyre::Result<()> {
+        // NOTE: `get_full_path` can't be used here since the file (and possibly its parent
+        // directories) doesn't exist yet to be canonicalized. `normalize_path` rejects absolute
+        // and leading-traversal paths, so we just need to catch any `..` in the middle.
+        let normalized = self.normalize_path(filename)?;
+        let relative = Path::new(&normalized);
+        if relative
+            .components()
+            .any(|c| matches!(c, std::path::Component::ParentDir))
+        {
+            bail!("Path traversal detected");
+        }
+
+        let full_path = self.path.join(relative);
+        if let Some(parent) = full_path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .wrap_err("Failed to create parent directories")?;
+        }
+        tokio::fs::write(&full_path, data)
+            .await
+            .wrap_err("Failed to write file")
+    }
+
+    async fn read(&self, filename: &str) -> eyre::Result<Vec<u8>> {
+        let full_path = self.get_full_path(filename)?;
+        tokio::fs::read(&full_path)
+            .await
+            .wrap_err("Failed to read file")
+    }
+
+    async fn delete(&self, filename: &str) -> eyre::Result<()> {
+        // `get_full_path` fails to canonicalize files that don't exist, and there's nothing to do
+        // for those anyway
+        if !self.path.join(self.normalize_path(filename)?).exists() {
+            return Ok(());
+        }
+        let full_path = self.get_full_path(filename)?;
+        match tokio::fs::remove_file(&full_path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).wrap_err("Failed to delete file"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -352,4 +476,60 @@ impl FileStoreImpl for SpacesFileStore {
             Ok(url.to_owned())
         }
     }
+
+    async fn write(
+        &self,
+        filename: &str,
+        data: Vec<u8>,
+        content_type: &str,
+        visibility: FileVisibility,
+    ) -> eyre::Result<()> {
+        let normalized = self.normalize_path(filename)?;
+        let acl = match visibility {
+            FileVisibility::Private => ObjectCannedAcl::Private,
+            FileVisibility::Public => ObjectCannedAcl::PublicRead,
+        };
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(&normalized)
+            .acl(acl)
+            .content_type(content_type)
+            .body(ByteStream::from(data))
+            .send()
+            .await
+            .wrap_err("Failed to upload file")?;
+        Ok(())
+    }
+
+    async fn read(&self, filename: &str) -> eyre::Result<Vec<u8>> {
+        let normalized = self.normalize_path(filename)?;
+        let object = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(&normalized)
+            .send()
+            .await
+            .wrap_err("Failed to download file")?;
+        let data = object
+            .body
+            .collect()
+            .await
+            .wrap_err("Failed to read file body")?;
+        Ok(data.into_bytes().to_vec())
+    }
+
+    async fn delete(&self, filename: &str) -> eyre::Result<()> {
+        let normalized = self.normalize_path(filename)?;
+        // S3 treats deleting a nonexistent key as a success
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(&normalized)
+            .send()
+            .await
+            .wrap_err("Failed to delete file")?;
+        Ok(())
+    }
 }
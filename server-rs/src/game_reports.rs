@@ -16,6 +16,7 @@ use uuid::Uuid;
 use crate::file_store::FileStore;
 use crate::games::{Game, GamesLoader};
 use crate::graphql::errors::graphql_error;
+use crate::graphql::limits::page_complexity;
 use crate::graphql::schema_builder::SchemaBuilderModule;
 use crate::redis::RedisPool;
 use crate::users::permissions::RequiredPermission;
@@ -339,7 +340,10 @@ impl GameReportsQuery {
 
     /// Lists game reports for moderation. Unresolved-only by default (newest first); pass
     /// `includeResolved` to see everything, or `reportedUserId` to see reports against one player.
-    #[graphql(guard = RequiredPermission::ManageGameReports)]
+    #[graphql(
+        guard = RequiredPermission::ManageGameReports,
+        complexity = "page_complexity(first.or(last), 25, child_complexity)"
+    )]
     async fn game_reports(
         &self,
         ctx: &Context<'_>,
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use async_graphql::futures_util::TryStreamExt;
 use async_graphql::{
@@ -16,6 +16,7 @@ use crate::{
     graphql::{errors::graphql_error, schema_builder::SchemaBuilderModule},
     maps::{MapsLoader, SbMapId, UploadedMap},
     matchmaking::MatchmakingType,
+    twitch::{FeedBlockLoader, LiveStream, LiveStreamLoader},
     users::{SbUser, SbUserId, UsersLoader},
 };
 
@@ -37,6 +38,10 @@ impl SchemaBuilderModule for GamesModule {
                 GamesLoader::new(self.db_pool.clone()),
                 tokio::spawn,
             ))
+            .data(DataLoader::new(
+                CurrentGameLoader::new(self.db_pool.clone()),
+                tokio::spawn,
+            ))
     }
 }
 
@@ -86,6 +91,57 @@ impl Game {
         let map = maps_loader.load_one(self.map_id).await?;
         map.ok_or_else(|| graphql_error("NOT_FOUND", "Map not found"))
     }
+
+    /// Participants of this game who are live-streaming it right now, ordered by viewer count
+    /// (highest first). Always empty once the game has finished.
+    async fn live_streams(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Vec<LiveStream>> {
+        if self.game_length.is_some() {
+            return Ok(Vec::new());
+        }
+
+        let participants = self.config.human_player_ids();
+        let current_games = ctx
+            .data::<DataLoader<CurrentGameLoader>>()?
+            .load_many(participants)
+            .await?;
+        // Streamers blocked from the live-streams feed are left out here too, so a block can't be
+        // gotten around by finding the streamer's game
+        let blocked = ctx
+            .data::<DataLoader<FeedBlockLoader>>()?
+            .load_many(current_games.keys().copied())
+            .await?
+            .into_keys()
+            .collect();
+        let playing = streaming_candidates(current_games, self.id, &blocked);
+
+        let mut streams = ctx
+            .data::<DataLoader<LiveStreamLoader>>()?
+            .load_many(playing)
+            .await?
+            .into_values()
+            .collect::<Vec<_>>();
+        streams.sort_by_key(|s| std::cmp::Reverse(s.viewer_count));
+        Ok(streams)
+    }
+}
+
+/// The participants whose live streams should be shown for `game_id`, given each participant's
+/// current game. A player is only broadcasting the game if it's still their current one (they
+/// haven't left or reported yet, and the game isn't a stale one that never completed), and players
+/// blocked from the live-streams feed are never shown.
+fn streaming_candidates(
+    current_games: HashMap<SbUserId, Uuid>,
+    game_id: Uuid,
+    blocked: &HashSet<SbUserId>,
+) -> Vec<SbUserId> {
+    current_games
+        .into_iter()
+        .filter(|&(user_id, current_game)| current_game == game_id && !blocked.contains(&user_id))
+        .map(|(user_id, _)| user_id)
+        .collect()
 }
 
 #[derive(Debug, Copy, Clone, SimpleObject)]
@@ -379,6 +435,22 @@ pub enum GameConfig {
     Matchmaking(GameConfigData<MatchmakingExtra>),
 }
 
+impl GameConfig {
+    /// The IDs of every non-computer player in the game, across all teams.
+    pub fn human_player_ids(&self) -> Vec<SbUserId> {
+        let teams = match self {
+            GameConfig::Lobby(data) => &data.teams,
+            GameConfig::Matchmaking(data) => &data.teams,
+        };
+        teams
+            .iter()
+            .flatten()
+            .filter(|p| !p.is_computer)
+            .map(|p| p.id)
+            .collect()
+    }
+}
+
 pub struct GamesRepo {
     db: PgPool,
 }
@@ -455,3 +527,89 @@ impl Loader<Uuid> for GamesLoader {
         .await?)
     }
 }
+
+/// Batches lookups of the game each user is currently playing, keyed by user ID. A user is in a
+/// game if they're a participant of one that hasn't completed, that they haven't left or reported
+/// results for, and that started within the last hour (older unfinished games are ones that never
+/// reported a completion, and would otherwise look live forever). Users not in a game are omitted.
+pub struct CurrentGameLoader {
+    db: PgPool,
+}
+
+impl CurrentGameLoader {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+impl Loader<SbUserId> for CurrentGameLoader {
+    type Value = Uuid;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[SbUserId]) -> Result<HashMap<SbUserId, Uuid>, Self::Error> {
+        Ok(sqlx::query!(
+            r#"
+            SELECT DISTINCT ON (gu.user_id) gu.user_id as "user_id: SbUserId", gu.game_id
+            FROM games_users gu
+            JOIN games g ON g.id = gu.game_id
+            WHERE
+                gu.user_id = ANY($1)
+                AND g.game_length IS NULL
+                AND g.start_time > now() - interval '1 hour'
+                AND gu.reported_at IS NULL
+                AND gu.departure_time IS NULL
+            ORDER BY gu.user_id, g.start_time DESC
+            "#,
+            keys as _,
+        )
+        .fetch(&self.db)
+        .map_ok(|r| (r.user_id, r.game_id))
+        .try_collect()
+        .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn human_player_ids_skips_computers() {
+        let config: GameConfig = serde_json::from_value(serde_json::json!({
+            "gameSource": "LOBBY",
+            "gameType": "melee",
+            "gameSubType": 0,
+            "teams": [
+                [
+                    { "id": 1, "race": "p", "isComputer": false },
+                    { "id": -1, "race": "z", "isComputer": true },
+                ],
+                [{ "id": 7, "race": "t", "isComputer": false }],
+            ],
+            "gameSourceExtra": { "turnRate": null, "useLegacyLimits": null },
+        }))
+        .unwrap();
+
+        assert_eq!(config.human_player_ids(), vec![SbUserId(1), SbUserId(7)]);
+    }
+
+    #[test]
+    fn streaming_candidates_skip_other_games_and_blocked_streamers() {
+        let game_id = Uuid::from_u128(1);
+        let current_games = HashMap::from([
+            (SbUserId(1), game_id),
+            (SbUserId(2), Uuid::from_u128(2)),
+            (SbUserId(3), game_id),
+        ]);
+
+        let mut candidates = streaming_candidates(current_games.clone(), game_id, &HashSet::new());
+        candidates.sort_by_key(|id| id.0);
+        assert_eq!(candidates, vec![SbUserId(1), SbUserId(3)]);
+
+        let blocked = HashSet::from([SbUserId(3)]);
+        assert_eq!(
+            streaming_candidates(current_games, game_id, &blocked),
+            vec![SbUserId(1)]
+        );
+    }
+}
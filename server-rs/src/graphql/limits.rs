@@ -0,0 +1,495 @@
+//! Restrictions on which queries the GraphQL API will run: a maximum cost (complexity) and depth
+//! per request, and optionally an allowlist of persisted queries registered by the client build.
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest, NextValidation,
+};
+use async_graphql::{Pos, Request, ServerError, ServerResult, ValidationResult};
+use color_eyre::eyre::{self, WrapErr};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::graphql::errors::graphql_error;
+use crate::sessions::SbSession;
+
+const GRAPHQL_REJECTED_QUERIES_TOTAL: &str = "graphql_rejected_queries_total";
+const GRAPHQL_UNPERSISTED_QUERIES_TOTAL: &str = "graphql_unpersisted_queries_total";
+
+/// The most items a paginated field will return in one request.
+const MAX_PAGE_SIZE: usize = 100;
+
+/// Registers metric descriptions (the HELP/TYPE text on `/metrics`). Safe to call once at startup;
+/// recording a metric without describing it still works, this just produces nicer output.
+pub fn describe_metrics() {
+    use ::metrics::Unit;
+
+    ::metrics::describe_counter!(
+        GRAPHQL_REJECTED_QUERIES_TOTAL,
+        Unit::Count,
+        "GraphQL requests rejected before execution, per reason"
+    );
+    ::metrics::describe_counter!(
+        GRAPHQL_UNPERSISTED_QUERIES_TOTAL,
+        Unit::Count,
+        "GraphQL requests executed with a query that isn't in the persisted query allowlist \
+         (only counted while the allowlist isn't enforced)"
+    );
+}
+
+/// Complexity of a field that returns a page of items: the cost of a single item, times the number
+/// of items that can be returned. Page sizes are clamped the same way the resolvers clamp them.
+pub fn page_complexity(
+    page_size: Option<i32>,
+    default_page_size: usize,
+    child_complexity: usize,
+) -> usize {
+    let page_size = page_size
+        .map(|s| s.clamp(1, MAX_PAGE_SIZE as i32) as usize)
+        .unwrap_or(default_page_size);
+    page_size.saturating_mul(child_complexity)
+}
+
+fn rejection(reason: &'static str, code: &'static str, message: impl Into<String>) -> ServerError {
+    ::metrics::counter!(GRAPHQL_REJECTED_QUERIES_TOTAL, "reason" => reason).increment(1);
+
+    let mut err = graphql_error(code, message).into_server_error(Pos::default());
+    err.locations.clear();
+    err
+}
+
+/// Rejects queries whose total complexity or depth is over the configured maximums. Field costs
+/// default to 1, with list fields declaring their own via `#[graphql(complexity = ...)]` (see
+/// [page_complexity]).
+pub struct QueryLimits {
+    max_complexity: usize,
+    max_depth: usize,
+}
+
+impl QueryLimits {
+    pub fn new(max_complexity: usize, max_depth: usize) -> Self {
+        Self {
+            max_complexity,
+            max_depth,
+        }
+    }
+}
+
+impl ExtensionFactory for QueryLimits {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(QueryLimitsExtension {
+            max_complexity: self.max_complexity,
+            max_depth: self.max_depth,
+        })
+    }
+}
+
+struct QueryLimitsExtension {
+    max_complexity: usize,
+    max_depth: usize,
+}
+
+#[async_trait::async_trait]
+impl Extension for QueryLimitsExtension {
+    async fn validation(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        next: NextValidation<'_>,
+    ) -> Result<ValidationResult, Vec<ServerError>> {
+        let result = next.run(ctx).await?;
+
+        if result.complexity > self.max_complexity {
+            return Err(vec![rejection(
+                "complexity",
+                "QUERY_TOO_COMPLEX",
+                format!(
+                    "Query has a cost of {}, which is over the limit of {}",
+                    result.complexity, self.max_complexity
+                ),
+            )]);
+        }
+        if result.depth > self.max_depth {
+            return Err(vec![rejection(
+                "depth",
+                "QUERY_TOO_DEEP",
+                format!(
+                    "Query is nested {} levels deep, which is over the limit of {}",
+                    result.depth, self.max_depth
+                ),
+            )]);
+        }
+
+        Ok(result)
+    }
+}
+
+/// The queries the client is allowed to make, keyed by the (hex-encoded) SHA-256 hash of their
+/// text. This is the `persisted-documents.json` generated alongside the client's GraphQL types.
+#[derive(Debug, Default)]
+pub struct PersistedQueryManifest {
+    queries: HashMap<String, String>,
+}
+
+impl PersistedQueryManifest {
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        let contents = std::fs::read_to_string(path).wrap_err_with(|| {
+            format!(
+                "Failed to read persisted query manifest at {}",
+                path.display()
+            )
+        })?;
+        Self::parse(&contents)
+    }
+
+    fn parse(contents: &str) -> eyre::Result<Self> {
+        let queries: HashMap<String, String> = serde_json::from_str(contents)
+            .wrap_err("Persisted query manifest must be a JSON object of hash -> query")?;
+        Ok(Self {
+            queries: queries
+                .into_iter()
+                .map(|(hash, query)| (hash.to_ascii_lowercase(), query))
+                .collect(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.queries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queries.is_empty()
+    }
+
+    fn get(&self, hash: &str) -> Option<&str> {
+        self.queries
+            .get(&hash.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    fn contains_query(&self, query: &str) -> bool {
+        self.queries.contains_key(&hash_query(query))
+    }
+}
+
+fn hash_query(query: &str) -> String {
+    use std::fmt::Write;
+    Sha256::digest(query.as_bytes())
+        .iter()
+        .fold(String::with_capacity(64), |mut acc, b| {
+            let _ = write!(acc, "{b:02x}");
+            acc
+        })
+}
+
+/// The `persistedQuery` request extension, as sent by Apollo-style persisted query clients.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PersistedQueryRequest {
+    version: u32,
+    sha256_hash: String,
+}
+
+/// Runs queries from the [PersistedQueryManifest] when requests reference them by hash (in the
+/// `persistedQuery` extension), and, if `enforce` is set, rejects any query that isn't in it.
+///
+/// Requests made with API tokens are exempt from enforcement, since third-party tools can't be
+/// expected to register their queries with us. They're still subject to [QueryLimits].
+pub struct PersistedQueries {
+    manifest: Arc<PersistedQueryManifest>,
+    enforce: bool,
+}
+
+impl PersistedQueries {
+    pub fn new(manifest: PersistedQueryManifest, enforce: bool) -> Self {
+        Self {
+            manifest: Arc::new(manifest),
+            enforce,
+        }
+    }
+}
+
+impl ExtensionFactory for PersistedQueries {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(PersistedQueriesExtension {
+            manifest: self.manifest.clone(),
+            enforce: self.enforce,
+        })
+    }
+}
+
+struct PersistedQueriesExtension {
+    manifest: Arc<PersistedQueryManifest>,
+    enforce: bool,
+}
+
+impl PersistedQueriesExtension {
+    fn apply(&self, request: &mut Request) -> ServerResult<()> {
+        let is_api_token = request
+            .data
+            .get(&TypeId::of::<SbSession>())
+            .and_then(|session| session.downcast_ref::<SbSession>())
+            .is_some_and(|session| matches!(session, SbSession::ApiToken(_)));
+        let enforce = self.enforce && !is_api_token;
+
+        if let Some(value) = request.extensions.remove("persistedQuery") {
+            let persisted = async_graphql::from_value::<PersistedQueryRequest>(value)
+                .ok()
+                .filter(|p| p.version == 1)
+                .ok_or_else(|| {
+                    rejection(
+                        "invalid_persisted_query",
+                        "PERSISTED_QUERY_INVALID",
+                        "Only version 1 of the `persistedQuery` extension is supported",
+                    )
+                })?;
+
+            if let Some(query) = self.manifest.get(&persisted.sha256_hash) {
+                // Always run the registered text, regardless of what was sent alongside the hash
+                request.query = query.to_owned();
+                return Ok(());
+            } else if enforce || request.query.is_empty() {
+                return Err(rejection(
+                    "unknown_persisted_query",
+                    "PERSISTED_QUERY_NOT_FOUND",
+                    "PersistedQueryNotFound",
+                ));
+            }
+        } else if self.manifest.contains_query(&request.query) {
+            return Ok(());
+        } else if enforce {
+            return Err(rejection(
+                "not_persisted",
+                "PERSISTED_QUERY_REQUIRED",
+                "Only persisted queries are allowed",
+            ));
+        }
+
+        if !is_api_token {
+            ::metrics::counter!(GRAPHQL_UNPERSISTED_QUERIES_TOTAL).increment(1);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for PersistedQueriesExtension {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        mut request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        self.apply(&mut request)?;
+        next.run(ctx, request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_graphql::extensions::NextExecute;
+    use async_graphql::{
+        EmptyMutation, EmptySubscription, Object, Schema, SimpleObject, Value, value,
+    };
+
+    const QUERY: &str = "query Answer { answer }";
+
+    struct Query;
+
+    #[derive(SimpleObject, Clone)]
+    struct Answer {
+        value: i32,
+    }
+
+    #[Object]
+    impl Query {
+        async fn answer(&self) -> i32 {
+            42
+        }
+
+        #[graphql(complexity = "page_complexity(first, 10, child_complexity)")]
+        async fn answers(&self, first: Option<i32>) -> Vec<Answer> {
+            vec![Answer { value: 42 }; first.unwrap_or(10) as usize]
+        }
+    }
+
+    fn manifest() -> PersistedQueryManifest {
+        PersistedQueryManifest::parse(&format!(r#"{{"{}": "{QUERY}"}}"#, hash_query(QUERY)))
+            .unwrap()
+    }
+
+    fn schema(extension: impl ExtensionFactory) -> Schema<Query, EmptyMutation, EmptySubscription> {
+        Schema::build(Query, EmptyMutation, EmptySubscription)
+            .extension(extension)
+            .finish()
+    }
+
+    fn error_code(response: &async_graphql::Response) -> Option<String> {
+        let extensions = response.errors.first()?.extensions.as_ref()?;
+        match extensions.get("code")? {
+            Value::String(code) => Some(code.clone()),
+            _ => None,
+        }
+    }
+
+    fn with_hash(request: Request, hash: &str) -> Request {
+        let mut request = request;
+        request.extensions.insert(
+            "persistedQuery".into(),
+            value!({ "version": 1, "sha256Hash": hash }),
+        );
+        request
+    }
+
+    #[tokio::test]
+    async fn runs_persisted_queries_by_hash() {
+        let schema = schema(PersistedQueries::new(manifest(), true));
+
+        let response = schema
+            .execute(with_hash(Request::new(""), &hash_query(QUERY)))
+            .await;
+        assert_eq!(response.data, value!({ "answer": 42 }));
+
+        // The registered text is what runs, not whatever was sent with the hash
+        let response = schema
+            .execute(with_hash(
+                Request::new("{ answers(first: 100) { value } }"),
+                &hash_query(QUERY),
+            ))
+            .await;
+        assert_eq!(response.data, value!({ "answer": 42 }));
+    }
+
+    #[tokio::test]
+    async fn enforces_persisted_queries() {
+        let schema = schema(PersistedQueries::new(manifest(), true));
+
+        let response = schema.execute(QUERY).await;
+        assert!(response.errors.is_empty());
+
+        let response = schema.execute("{ answers { value } }").await;
+        assert_eq!(
+            error_code(&response).as_deref(),
+            Some("PERSISTED_QUERY_REQUIRED")
+        );
+
+        let response = schema
+            .execute(with_hash(
+                Request::new("{ answers { value } }"),
+                &hash_query("{ answers { value } }"),
+            ))
+            .await;
+        assert_eq!(
+            error_code(&response).as_deref(),
+            Some("PERSISTED_QUERY_NOT_FOUND")
+        );
+    }
+
+    #[tokio::test]
+    async fn allows_unpersisted_queries_when_not_enforced() {
+        let schema = schema(PersistedQueries::new(manifest(), false));
+
+        let response = schema.execute("{ answers(first: 2) { value } }").await;
+        assert_eq!(
+            response.data,
+            value!({ "answers": [{ "value": 42 }, { "value": 42 }] })
+        );
+
+        let response = schema
+            .execute(with_hash(
+                Request::new(""),
+                &hash_query("{ answers { value } }"),
+            ))
+            .await;
+        assert_eq!(
+            error_code(&response).as_deref(),
+            Some("PERSISTED_QUERY_NOT_FOUND")
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_queries_over_limits() {
+        let schema = schema(QueryLimits::new(50, 5));
+
+        let response = schema.execute("{ answers(first: 50) { value } }").await;
+        assert!(response.errors.is_empty());
+
+        let response = schema.execute("{ answers(first: 51) { value } }").await;
+        assert_eq!(error_code(&response).as_deref(), Some("QUERY_TOO_COMPLEX"));
+
+        // Page sizes are clamped, so huge values don't overflow or count for more than a max page
+        let response = schema
+            .execute("{ answers(first: 2147483647) { value } }")
+            .await;
+        assert_eq!(error_code(&response).as_deref(), Some("QUERY_TOO_COMPLEX"));
+        assert!(
+            response.errors[0].message.contains("cost of 100"),
+            "{}",
+            response.errors[0].message
+        );
+
+        let response = schema
+            .execute("{ __schema { types { fields { type { ofType { name } } } } } }")
+            .await;
+        assert_eq!(error_code(&response).as_deref(), Some("QUERY_TOO_DEEP"));
+    }
+
+    /// Ends requests once they've been validated, so queries can be checked against a schema
+    /// without being run.
+    struct ValidateOnly;
+
+    impl ExtensionFactory for ValidateOnly {
+        fn create(&self) -> Arc<dyn Extension> {
+            Arc::new(ValidateOnly)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Extension for ValidateOnly {
+        async fn execute(
+            &self,
+            _ctx: &ExtensionContext<'_>,
+            _operation_name: Option<&str>,
+            _next: NextExecute<'_>,
+        ) -> async_graphql::Response {
+            async_graphql::Response::default()
+        }
+    }
+
+    /// The manifest written by `pnpm gen-graphql`, which is shipped in the server's image. If this
+    /// fails, the client's queries no longer match the schema (or the manifest is out of date).
+    #[tokio::test]
+    async fn client_manifest_matches_the_schema() {
+        let manifest = PersistedQueryManifest::load(Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../client/gql/persisted-documents.json"
+        )))
+        .unwrap();
+        assert!(!manifest.is_empty());
+
+        let schema = crate::schema::build_schema()
+            .extension(ValidateOnly)
+            .finish();
+        for (hash, query) in &manifest.queries {
+            assert_eq!(&hash_query(query), hash, "{query}");
+            let response = schema.execute(query.as_str()).await;
+            assert!(response.errors.is_empty(), "{query}: {:?}", response.errors);
+        }
+    }
+
+    #[test]
+    fn manifest_hashes_are_case_insensitive() {
+        let manifest = manifest();
+        assert_eq!(manifest.len(), 1);
+        assert_eq!(
+            manifest.get(&hash_query(QUERY).to_ascii_uppercase()),
+            Some(QUERY)
+        );
+        assert!(manifest.contains_query(QUERY));
+        assert!(!manifest.contains_query("{ answer }"));
+    }
+}
@@ -1,2 +1,3 @@
 pub mod errors;
+pub mod limits;
 pub mod schema_builder;
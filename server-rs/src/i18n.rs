@@ -0,0 +1,95 @@
+//! The languages the client is translated into, matching `TranslationLanguage` in `common/i18n.ts`.
+
+/// A language we have translations for. English is the default, and is what untranslated content
+/// is written in.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TranslationLanguage {
+    ChineseSimplified,
+    #[default]
+    English,
+    Korean,
+    Russian,
+    Spanish,
+}
+
+impl TranslationLanguage {
+    pub const DEFAULT: TranslationLanguage = TranslationLanguage::English;
+
+    /// The language's code, as used by the client and stored in the database.
+    pub fn code(self) -> &'static str {
+        match self {
+            TranslationLanguage::ChineseSimplified => "zh-Hans",
+            TranslationLanguage::English => "en",
+            TranslationLanguage::Korean => "ko",
+            TranslationLanguage::Russian => "ru",
+            TranslationLanguage::Spanish => "es",
+        }
+    }
+
+    /// Finds the language to use for a locale (e.g. `es-419`, `ko_KR`, `zh-Hans-CN`), or `None` if
+    /// we don't have translations for it. Only the language (and, for Chinese, the script) matter,
+    /// so regional variants all map to the same language.
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        let locale = locale.trim().to_ascii_lowercase().replace('_', "-");
+        let mut subtags = locale.split('-');
+        match subtags.next()? {
+            "en" => Some(TranslationLanguage::English),
+            "es" => Some(TranslationLanguage::Spanish),
+            "ko" => Some(TranslationLanguage::Korean),
+            "ru" => Some(TranslationLanguage::Russian),
+            "zh" => {
+                // Traditional Chinese (explicitly, or implied by the region) isn't something we
+                // have, and Simplified isn't a good substitute.
+                let traditional = subtags.any(|s| matches!(s, "hant" | "tw" | "hk" | "mo"));
+                (!traditional).then_some(TranslationLanguage::ChineseSimplified)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locales_map_to_languages() {
+        let cases = [
+            ("en", Some(TranslationLanguage::English)),
+            ("en-US", Some(TranslationLanguage::English)),
+            ("es-419", Some(TranslationLanguage::Spanish)),
+            ("ko_KR", Some(TranslationLanguage::Korean)),
+            ("RU", Some(TranslationLanguage::Russian)),
+            ("zh", Some(TranslationLanguage::ChineseSimplified)),
+            ("zh-Hans", Some(TranslationLanguage::ChineseSimplified)),
+            ("zh-CN", Some(TranslationLanguage::ChineseSimplified)),
+            ("zh-Hant", None),
+            ("zh-TW", None),
+            ("de-DE", None),
+            ("", None),
+        ];
+        for (locale, expected) in cases {
+            assert_eq!(
+                TranslationLanguage::from_locale(locale),
+                expected,
+                "locale: {locale}"
+            );
+        }
+    }
+
+    #[test]
+    fn codes_round_trip() {
+        for language in [
+            TranslationLanguage::ChineseSimplified,
+            TranslationLanguage::English,
+            TranslationLanguage::Korean,
+            TranslationLanguage::Russian,
+            TranslationLanguage::Spanish,
+        ] {
+            assert_eq!(
+                TranslationLanguage::from_locale(language.code()),
+                Some(language)
+            );
+        }
+    }
+}
@@ -0,0 +1,555 @@
+//! Processing and storage of uploaded images (news covers, league images/badges, avatars).
+//!
+//! Uploads are decoded and re-encoded rather than stored as-is. That lets us resize them to the
+//! sizes the client actually displays, and drops any metadata (EXIF location data, camera info,
+//! embedded thumbnails, etc.) since only pixels make it into the stored files. Each size is stored
+//! as WebP, plus a JPEG fallback for consumers that can't display WebP (e.g. some feed readers and
+//! link previews).
+
+use std::io::Cursor;
+
+use axum::{
+    Json, Router,
+    extract::{DefaultBodyLimit, Multipart, Path, State},
+    http::StatusCode,
+    routing::post,
+};
+use color_eyre::eyre::{self, WrapErr};
+use data_encoding::HEXLOWER;
+use image::{
+    DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits, Rgba, RgbaImage,
+    codecs::jpeg::JpegEncoder, imageops::FilterType,
+};
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::error;
+use typeshare::typeshare;
+
+use crate::async_rayon::spawn_rayon;
+use crate::file_blobs::{BlobFile, content_hash, store_blob};
+use crate::file_store::{FileStore, FileVisibility};
+use crate::state::AppState;
+use crate::users::CurrentUser;
+use crate::users::permissions::SbPermissions;
+
+// NOTE: If you change this, also change `MAX_IMAGE_SIZE_BYTES` in `common/images.ts`.
+/// The largest image file we accept.
+pub const MAX_IMAGE_SIZE_BYTES: usize = 5 * 1000 * 1000;
+/// The largest width or height we'll decode. Anything bigger than this is far beyond what we
+/// display, and decoding it would just be a way to make us allocate a lot of memory.
+const MAX_IMAGE_DIMENSION: u32 = 8192;
+/// The smallest width or height we accept. Images smaller than this are almost certainly a mistake
+/// (or a tracking pixel) and would look terrible once displayed.
+const MIN_IMAGE_DIMENSION: u32 = 16;
+const JPEG_QUALITY: u8 = 85;
+const WEBP_QUALITY: f32 = 80.0;
+/// Color that transparent areas are flattened onto for the JPEG variants, since JPEG has no alpha.
+const JPEG_BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// What an uploaded image will be used for, which determines where it's stored, what sizes it's
+/// resized to and who can upload it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageKind {
+    NewsCover,
+    LeagueImage,
+    LeagueBadge,
+    Avatar,
+}
+
+/// How an image is fit into a variant's size. Images are never enlarged.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Fit {
+    /// Scales the image down to at most this width, keeping its aspect ratio.
+    MaxWidth(u32),
+    /// Scales and center-crops the image to fill exactly this width and height. Images smaller
+    /// than this are only cropped to the same aspect ratio.
+    Cover(u32, u32),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct VariantSpec {
+    /// Appended to the image's file name (before the extension) for this variant.
+    suffix: &'static str,
+    fit: Fit,
+}
+
+impl ImageKind {
+    /// Parses the kind from the upload endpoint's path. Avatars aren't uploadable through the
+    /// endpoint, since avatar updates go through moderation checks (restrictions, safe search) in
+    /// the Node server.
+    fn from_upload_path(kind: &str) -> Option<Self> {
+        match kind {
+            "news-cover" => Some(ImageKind::NewsCover),
+            "league-image" => Some(ImageKind::LeagueImage),
+            "league-badge" => Some(ImageKind::LeagueBadge),
+            _ => None,
+        }
+    }
+
+    fn root_folder(self) -> &'static str {
+        match self {
+            ImageKind::NewsCover => "news-images",
+            ImageKind::LeagueImage | ImageKind::LeagueBadge => "league-images",
+            ImageKind::Avatar => "user-avatars",
+        }
+    }
+
+    // NOTE: These sizes need to match the ones in `common/` (and the news `srcSet`s in the client).
+    fn variants(self) -> &'static [VariantSpec] {
+        match self {
+            // Large + a half-resolution `_0.5x` sibling (see `small_variant_path` in news.rs)
+            ImageKind::NewsCover => &[
+                VariantSpec {
+                    suffix: "",
+                    fit: Fit::MaxWidth(1600),
+                },
+                VariantSpec {
+                    suffix: "_0.5x",
+                    fit: Fit::MaxWidth(800),
+                },
+            ],
+            // LEAGUE_IMAGE_WIDTH/HEIGHT
+            ImageKind::LeagueImage => &[VariantSpec {
+                suffix: "",
+                fit: Fit::Cover(704 * 2, 288 * 2),
+            }],
+            // LEAGUE_BADGE_WIDTH/HEIGHT
+            ImageKind::LeagueBadge => &[VariantSpec {
+                suffix: "",
+                fit: Fit::Cover(80 * 4, 80 * 4),
+            }],
+            // USER_AVATAR_SIZE
+            ImageKind::Avatar => &[VariantSpec {
+                suffix: "",
+                fit: Fit::Cover(256, 256),
+            }],
+        }
+    }
+
+    fn can_upload(self, permissions: &SbPermissions) -> bool {
+        match self {
+            ImageKind::NewsCover => permissions.manage_news,
+            ImageKind::LeagueImage | ImageKind::LeagueBadge => permissions.manage_leagues,
+            ImageKind::Avatar => false,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImageError {
+    #[error("image is larger than the maximum allowed size")]
+    TooLarge,
+    #[error("image type is not supported")]
+    UnsupportedType,
+    #[error("image dimensions ({0}x{1}) are outside of the allowed range")]
+    InvalidDimensions(u32, u32),
+    #[error("image could not be decoded")]
+    Decode(#[source] image::ImageError),
+    #[error("image could not be encoded")]
+    Encode(#[source] eyre::Report),
+}
+
+/// One size of a processed image, encoded in each of the formats we store.
+#[derive(Debug)]
+pub struct ProcessedVariant {
+    pub suffix: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub webp: Vec<u8>,
+    pub jpeg: Vec<u8>,
+}
+
+/// Validates an uploaded image and produces each of the variants needed for `kind`. This is
+/// CPU-heavy, so it should be run off of the async runtime (e.g. with [`spawn_rayon`]).
+pub fn process_image(data: &[u8], kind: ImageKind) -> Result<Vec<ProcessedVariant>, ImageError> {
+    if data.len() > MAX_IMAGE_SIZE_BYTES {
+        return Err(ImageError::TooLarge);
+    }
+    // The format is determined by sniffing the data rather than trusting the uploaded file name
+    // or content type.
+    let format = image::guess_format(data).map_err(|_| ImageError::UnsupportedType)?;
+    if !matches!(
+        format,
+        ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP | ImageFormat::Gif
+    ) {
+        return Err(ImageError::UnsupportedType);
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    let mut reader = ImageReader::with_format(Cursor::new(data), format);
+    reader.limits(limits);
+    let mut decoder = reader.into_decoder().map_err(ImageError::Decode)?;
+
+    let (width, height) = decoder.dimensions();
+    if width < MIN_IMAGE_DIMENSION || height < MIN_IMAGE_DIMENSION {
+        return Err(ImageError::InvalidDimensions(width, height));
+    }
+    // Orientation is the one piece of metadata that affects how the image looks, so it gets
+    // applied to the pixels before the rest is discarded.
+    let orientation = decoder.orientation().map_err(ImageError::Decode)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(ImageError::Decode)?;
+    image.apply_orientation(orientation);
+
+    kind.variants()
+        .iter()
+        .map(|spec| {
+            let resized = resize(&image, spec.fit).into_rgba8();
+            Ok(ProcessedVariant {
+                suffix: spec.suffix,
+                width: resized.width(),
+                height: resized.height(),
+                webp: encode_webp(&resized),
+                jpeg: encode_jpeg(&resized).map_err(ImageError::Encode)?,
+            })
+        })
+        .collect()
+}
+
+fn resize(image: &DynamicImage, fit: Fit) -> DynamicImage {
+    match fit {
+        Fit::MaxWidth(max_width) => {
+            if image.width() <= max_width {
+                image.clone()
+            } else {
+                // Height is left unbounded so only the width constrains the result
+                image.resize(max_width, u32::MAX, FilterType::Lanczos3)
+            }
+        }
+        Fit::Cover(width, height) => {
+            if image.width() >= width && image.height() >= height {
+                image.resize_to_fill(width, height, FilterType::Lanczos3)
+            } else {
+                let (crop_width, crop_height) =
+                    cover_crop_size(image.width(), image.height(), width, height);
+                image.crop_imm(
+                    (image.width() - crop_width) / 2,
+                    (image.height() - crop_height) / 2,
+                    crop_width,
+                    crop_height,
+                )
+            }
+        }
+    }
+}
+
+/// Returns the largest size with the same aspect ratio as `target_width`x`target_height` that fits
+/// within an image of the given size.
+fn cover_crop_size(width: u32, height: u32, target_width: u32, target_height: u32) -> (u32, u32) {
+    // Compare width/height to target_width/target_height without floating point
+    if u64::from(width) * u64::from(target_height) > u64::from(height) * u64::from(target_width) {
+        // Wider than the target, so the height is kept and the sides are cropped
+        let crop_width = u64::from(height) * u64::from(target_width) / u64::from(target_height);
+        ((crop_width as u32).max(1), height)
+    } else {
+        let crop_height = u64::from(width) * u64::from(target_height) / u64::from(target_width);
+        (width, (crop_height as u32).max(1))
+    }
+}
+
+fn encode_webp(image: &RgbaImage) -> Vec<u8> {
+    webp::Encoder::from_rgba(image.as_raw(), image.width(), image.height())
+        .encode(WEBP_QUALITY)
+        .to_vec()
+}
+
+fn encode_jpeg(image: &RgbaImage) -> eyre::Result<Vec<u8>> {
+    let mut flattened = RgbaImage::from_pixel(image.width(), image.height(), JPEG_BACKGROUND);
+    image::imageops::overlay(&mut flattened, image, 0, 0);
+    let flattened = DynamicImage::ImageRgba8(flattened).into_rgb8();
+
+    let mut data = Vec::new();
+    JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY)
+        .encode_image(&flattened)
+        .wrap_err("Failed to encode JPEG")?;
+    Ok(data)
+}
+
+/// Returns the path of the JPEG fallback for a stored (WebP) image path.
+pub fn jpeg_fallback_path(path: &str) -> String {
+    match path.strip_suffix(".webp") {
+        Some(base) => format!("{base}.jpg"),
+        None => path.to_owned(),
+    }
+}
+
+/// Where an image with a particular ID and variant suffix is stored (in WebP form). The ID is split
+/// into a few levels of directories to keep any one directory from getting too large.
+fn image_path(kind: ImageKind, id: &str, suffix: &str) -> String {
+    format!(
+        "{}/{}/{}/{id}{suffix}.webp",
+        kind.root_folder(),
+        &id[0..2],
+        &id[2..4]
+    )
+}
+
+/// An image that has been written to the file store.
+#[derive(Debug, Clone)]
+pub struct StoredImage {
+    /// The path of the image's first (largest) variant, in WebP form. This is the path that should
+    /// be saved to refer to the image; other variants and formats are derived from it.
+    pub path: String,
+    /// The path of each variant, in WebP form, in the same order as the kind's variants.
+    pub variant_paths: Vec<String>,
+}
+
+/// Writes each variant of a processed image to the file store (publicly readable, since these
+/// images are displayed on public pages) as a single blob. The image's ID is the hash of its
+/// largest variant, so re-uploading an image reuses the files from the first upload.
+pub async fn store_image(
+    db: &PgPool,
+    file_store: &FileStore,
+    kind: ImageKind,
+    variants: Vec<ProcessedVariant>,
+) -> eyre::Result<StoredImage> {
+    let primary = variants
+        .first()
+        .ok_or_else(|| eyre::eyre!("Image kind has no variants"))?;
+    let hash = content_hash(&primary.webp);
+    let id = HEXLOWER.encode(&hash);
+
+    let mut variant_paths = Vec::with_capacity(variants.len());
+    let mut files = Vec::with_capacity(variants.len() * 2);
+    for variant in variants {
+        let path = image_path(kind, &id, variant.suffix);
+        files.push(BlobFile {
+            path: jpeg_fallback_path(&path),
+            data: variant.jpeg,
+            content_type: "image/jpeg",
+            visibility: FileVisibility::Public,
+        });
+        files.push(BlobFile {
+            path: path.clone(),
+            data: variant.webp,
+            content_type: "image/webp",
+            visibility: FileVisibility::Public,
+        });
+        variant_paths.push(path);
+    }
+
+    let path = variant_paths[0].clone();
+    store_blob(db, file_store, &path, hash, files)
+        .await
+        .wrap_err("Failed to store image")?;
+
+    Ok(StoredImage {
+        path,
+        variant_paths,
+    })
+}
+
+pub fn create_images_api() -> Router<AppState> {
+    Router::new()
+        .route("/{kind}", post(upload_image))
+        // Leave some room for the multipart framing around the image
+        .layer(DefaultBodyLimit::max(MAX_IMAGE_SIZE_BYTES + 64 * 1024))
+}
+
+#[typeshare]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageUploadResponse {
+    /// The path to save to refer to this image (e.g. as a news post's `coverImagePath`).
+    pub path: String,
+    pub url: String,
+    /// A JPEG version of the image, for places that can't display WebP.
+    pub jpeg_url: String,
+    /// The URL of the half-resolution variant, for kinds that have one.
+    pub small_url: Option<String>,
+}
+
+/// Accepts a multipart upload with the image in an `image` field.
+async fn upload_image(
+    Path(kind): Path<String>,
+    State(db): State<PgPool>,
+    State(file_store): State<FileStore>,
+    user: CurrentUser,
+    mut multipart: Multipart,
+) -> Result<Json<ImageUploadResponse>, (StatusCode, &'static str)> {
+    let kind = ImageKind::from_upload_path(&kind).ok_or((StatusCode::NOT_FOUND, "Not found"))?;
+    if !kind.can_upload(&user.permissions) {
+        return Err((StatusCode::FORBIDDEN, "Forbidden"));
+    }
+
+    let mut image = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid multipart body"))?
+    {
+        if field.name() != Some("image") {
+            continue;
+        }
+        if image.is_some() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "only one image file can be uploaded",
+            ));
+        }
+        image = Some(
+            field
+                .bytes()
+                .await
+                .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid multipart body"))?,
+        );
+    }
+    let image = image.ok_or((StatusCode::BAD_REQUEST, "an image file must be provided"))?;
+
+    let variants = spawn_rayon(move || process_image(&image, kind))
+        .await
+        .map_err(|e| match e {
+            ImageError::TooLarge => (StatusCode::PAYLOAD_TOO_LARGE, "Image is too large"),
+            ImageError::UnsupportedType => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Image must be a JPEG, PNG, WebP or GIF",
+            ),
+            ImageError::InvalidDimensions(..) => (
+                StatusCode::BAD_REQUEST,
+                "Image dimensions are too small or too large",
+            ),
+            ImageError::Decode(_) => (StatusCode::BAD_REQUEST, "Image could not be read"),
+            ImageError::Encode(e) => {
+                error!("Failed to encode uploaded image: {e:?}");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+            }
+        })?;
+
+    let internal_error = |e: eyre::Report| {
+        error!("Failed to store uploaded image: {e:?}");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+    };
+    let stored = store_image(&db, &file_store, kind, variants)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(ImageUploadResponse {
+        url: file_store.url(&stored.path).map_err(internal_error)?,
+        jpeg_url: file_store
+            .url(&jpeg_fallback_path(&stored.path))
+            .map_err(internal_error)?,
+        small_url: stored
+            .variant_paths
+            .get(1)
+            .map(|p| file_store.url(p))
+            .transpose()
+            .map_err(internal_error)?,
+        path: stored.path,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+
+    fn encoded(width: u32, height: u32, format: ImageFormat) -> Vec<u8> {
+        let image = ImageBuffer::from_fn(width, height, |x, y| {
+            Rgb([(x % 256) as u8, (y % 256) as u8, 128])
+        });
+        let mut data = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(image)
+            .write_to(&mut data, format)
+            .unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn news_covers_are_resized_to_both_widths_without_enlarging() {
+        let variants =
+            process_image(&encoded(2000, 1000, ImageFormat::Png), ImageKind::NewsCover).unwrap();
+        let sizes = variants
+            .iter()
+            .map(|v| (v.suffix, v.width, v.height))
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, [("", 1600, 800), ("_0.5x", 800, 400)]);
+
+        let variants =
+            process_image(&encoded(600, 300, ImageFormat::Jpeg), ImageKind::NewsCover).unwrap();
+        assert!(variants.iter().all(|v| v.width == 600 && v.height == 300));
+    }
+
+    #[test]
+    fn cover_variants_match_the_target_aspect_ratio() {
+        let variants = process_image(
+            &encoded(1000, 500, ImageFormat::Png),
+            ImageKind::LeagueBadge,
+        )
+        .unwrap();
+        assert_eq!((variants[0].width, variants[0].height), (320, 320));
+
+        // Smaller than the target, so it's only cropped
+        let variants =
+            process_image(&encoded(300, 100, ImageFormat::Png), ImageKind::LeagueBadge).unwrap();
+        assert_eq!((variants[0].width, variants[0].height), (100, 100));
+
+        assert_eq!(cover_crop_size(1000, 1000, 1408, 576), (1000, 409));
+    }
+
+    #[test]
+    fn outputs_are_webp_and_jpeg() {
+        let variants =
+            process_image(&encoded(64, 64, ImageFormat::Gif), ImageKind::Avatar).unwrap();
+        assert_eq!(
+            image::guess_format(&variants[0].webp).unwrap(),
+            ImageFormat::WebP
+        );
+        assert_eq!(
+            image::guess_format(&variants[0].jpeg).unwrap(),
+            ImageFormat::Jpeg
+        );
+    }
+
+    #[test]
+    fn metadata_is_stripped() {
+        let mut jpeg = encoded(64, 64, ImageFormat::Jpeg);
+        // Splice an EXIF (APP1) segment in right after the SOI marker
+        let exif = b"Exif\0\0secret location";
+        let mut segment = vec![0xFF, 0xE1];
+        segment.extend_from_slice(&((exif.len() + 2) as u16).to_be_bytes());
+        segment.extend_from_slice(exif);
+        jpeg.splice(2..2, segment);
+
+        let variants = process_image(&jpeg, ImageKind::Avatar).unwrap();
+        let contains = |haystack: &[u8]| {
+            haystack
+                .windows(b"secret location".len())
+                .any(|w| w == b"secret location")
+        };
+        assert!(contains(&jpeg));
+        assert!(!contains(&variants[0].jpeg));
+        assert!(!contains(&variants[0].webp));
+    }
+
+    #[test]
+    fn invalid_images_are_rejected() {
+        assert!(matches!(
+            process_image(b"definitely not an image", ImageKind::Avatar),
+            Err(ImageError::UnsupportedType)
+        ));
+        assert!(matches!(
+            process_image(b"BM\0\0\0\0\0\0\0\0\0\0\0\0", ImageKind::Avatar),
+            Err(ImageError::UnsupportedType)
+        ));
+        assert!(matches!(
+            process_image(&encoded(8, 64, ImageFormat::Png), ImageKind::Avatar),
+            Err(ImageError::InvalidDimensions(8, 64))
+        ));
+        assert!(matches!(
+            process_image(&vec![0; MAX_IMAGE_SIZE_BYTES + 1], ImageKind::Avatar),
+            Err(ImageError::TooLarge)
+        ));
+    }
+
+    #[test]
+    fn paths() {
+        let path = image_path(ImageKind::NewsCover, "abcdef", "_0.5x");
+        assert_eq!(path, "news-images/ab/cd/abcdef_0.5x.webp");
+        assert_eq!(
+            jpeg_fallback_path(&path),
+            "news-images/ab/cd/abcdef_0.5x.jpg"
+        );
+        assert_eq!(ImageKind::from_upload_path("avatar"), None);
+    }
+}
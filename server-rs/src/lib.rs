@@ -1,14 +1,24 @@
+// The merged GraphQL schema nests deeply enough to exceed the default limit when computing the
+// layout of its resolver futures
+#![recursion_limit = "256"]
+
 pub mod async_rayon;
 pub mod configuration;
 pub mod email;
+pub mod file_blobs;
 pub mod file_store;
 pub mod game_reports;
 pub mod games;
 pub mod graphql;
+pub mod i18n;
+pub mod images;
 pub mod leagues;
+pub mod live_stream_feed;
 pub mod maps;
 pub mod matchmaking;
 pub mod news;
+pub mod news_feed;
+pub mod oauth;
 pub mod pubsub;
 pub mod random_code;
 pub mod redis;
@@ -16,6 +26,12 @@ pub mod routes;
 pub mod schema;
 pub mod sessions;
 pub mod state;
+pub mod stream_analytics;
+pub mod streaming;
 pub mod telemetry;
+#[cfg(test)]
+mod test_utils;
 pub mod twitch;
+pub mod twitch_chat;
 pub mod users;
+pub mod youtube;
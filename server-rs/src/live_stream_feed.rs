@@ -0,0 +1,423 @@
+//! Pushes changes to the `liveStreams` feed to clients over GraphQL subscriptions, so they don't
+//! have to poll it.
+//!
+//! Whichever server writes a live-stream change (an EventSub notification, the periodic refresh, or
+//! an admin changing a feed block) publishes the affected user IDs over Redis pub/sub. Each server
+//! runs one [`LiveStreamFeed`] that listens for those, re-reads the current state from Redis, and
+//! works out how the feed changed by comparing against its own copy of the feed. Only real changes
+//! are broadcast to that server's subscribers, and the same filtering as `liveStreams` applies
+//! (StarCraft streams only, feed-blocked users omitted).
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_graphql::futures_util::{Stream, StreamExt, stream};
+use async_graphql::{Context, SimpleObject, Subscription, Union};
+use color_eyre::eyre::{self, WrapErr};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, warn};
+use typeshare::typeshare;
+
+use crate::pubsub::PublishedMessage;
+use crate::redis::RedisPool;
+use crate::twitch::{
+    LiveStream, LiveStreamSummary, load_feed_blocked_user_ids, load_live_streams,
+    load_live_streams_for_users,
+};
+use crate::users::SbUserId;
+
+/// How many feed events can be buffered for a subscriber before it's considered lagging and its
+/// subscription is ended.
+const EVENT_BUFFER_SIZE: usize = 256;
+/// How long to wait before reconnecting after losing the Redis subscription.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// The pub/sub channel [`PublishedLiveStreamMessage`]s are sent on (see
+/// [`PublishedMessage::channel`]).
+const LIVE_STREAM_CHANNEL: &str = "liveStream";
+
+/// Messages about live-stream state, published on the `liveStream` channel. These only say what to
+/// re-check rather than carrying the new state, so listeners always act on what's in Redis, even if
+/// messages arrive out of order.
+#[typeshare]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "camelCase")]
+pub enum PublishedLiveStreamMessage {
+    /// The live state of these users' streams was written (they went live or offline, or their
+    /// stream details were refreshed).
+    #[serde(rename_all = "camelCase")]
+    StreamsChanged { user_ids: Vec<SbUserId> },
+    /// These users were blocked from or unblocked in the live-streams feed.
+    #[serde(rename_all = "camelCase")]
+    FeedBlocksChanged { user_ids: Vec<SbUserId> },
+}
+
+impl From<PublishedLiveStreamMessage> for PublishedMessage {
+    fn from(value: PublishedLiveStreamMessage) -> Self {
+        Self::LiveStream(value)
+    }
+}
+
+/// A stream was added to the feed (it went live, switched to StarCraft, or was unblocked).
+#[derive(Clone, Debug, PartialEq, SimpleObject)]
+pub struct LiveStreamOnline {
+    pub stream: LiveStream,
+}
+
+/// Details of a stream in the feed other than just its viewer count changed (e.g. its title).
+#[derive(Clone, Debug, PartialEq, SimpleObject)]
+pub struct LiveStreamUpdated {
+    pub stream: LiveStream,
+}
+
+/// Only the viewer count of a stream in the feed changed.
+#[derive(Clone, Debug, PartialEq, SimpleObject)]
+pub struct LiveStreamViewerCountChanged {
+    pub user_id: SbUserId,
+    pub viewer_count: i32,
+}
+
+/// A stream was removed from the feed (it went offline, switched away from StarCraft, or was
+/// blocked).
+#[derive(Clone, Debug, PartialEq, SimpleObject)]
+pub struct LiveStreamOffline {
+    pub user_id: SbUserId,
+}
+
+#[derive(Clone, Debug, PartialEq, Union)]
+pub enum LiveStreamFeedEvent {
+    Online(LiveStreamOnline),
+    Updated(LiveStreamUpdated),
+    ViewerCountChanged(LiveStreamViewerCountChanged),
+    Offline(LiveStreamOffline),
+}
+
+/// This server's view of the `liveStreams` feed, used to turn "something about these users changed"
+/// into the specific changes subscribers need to apply.
+#[derive(Default)]
+struct FeedState {
+    blocked: HashSet<SbUserId>,
+    streams: HashMap<SbUserId, LiveStream>,
+}
+
+impl FeedState {
+    /// Updates the feed entry for `user_id` given their current live stream (if any), returning the
+    /// resulting change to the feed, if there was one.
+    fn apply(
+        &mut self,
+        user_id: SbUserId,
+        summary: Option<LiveStreamSummary>,
+    ) -> Option<LiveStreamFeedEvent> {
+        let current = summary
+            .filter(|s| s.is_starcraft() && !self.blocked.contains(&user_id))
+            .map(|s| LiveStream::from_summary(user_id, s));
+
+        match (self.streams.get(&user_id), current) {
+            (None, None) => None,
+            (None, Some(stream)) => {
+                self.streams.insert(user_id, stream.clone());
+                Some(LiveStreamFeedEvent::Online(LiveStreamOnline { stream }))
+            }
+            (Some(_), None) => {
+                self.streams.remove(&user_id);
+                Some(LiveStreamFeedEvent::Offline(LiveStreamOffline { user_id }))
+            }
+            (Some(previous), Some(stream)) => {
+                let only_viewers_changed = LiveStream {
+                    viewer_count: previous.viewer_count,
+                    ..stream.clone()
+                } == *previous;
+                let event = if *previous == stream {
+                    None
+                } else if only_viewers_changed {
+                    Some(LiveStreamFeedEvent::ViewerCountChanged(
+                        LiveStreamViewerCountChanged {
+                            user_id,
+                            viewer_count: stream.viewer_count,
+                        },
+                    ))
+                } else {
+                    Some(LiveStreamFeedEvent::Updated(LiveStreamUpdated {
+                        stream: stream.clone(),
+                    }))
+                };
+                self.streams.insert(user_id, stream);
+                event
+            }
+        }
+    }
+
+    /// Replaces the whole feed (e.g. after reconnecting, when messages may have been missed),
+    /// returning the changes from the previous state.
+    fn resync(
+        &mut self,
+        blocked: HashSet<SbUserId>,
+        live: Vec<(SbUserId, LiveStreamSummary)>,
+    ) -> Vec<LiveStreamFeedEvent> {
+        self.blocked = blocked;
+        let mut live = live.into_iter().collect::<HashMap<_, _>>();
+        let user_ids = self
+            .streams
+            .keys()
+            .chain(live.keys())
+            .copied()
+            .collect::<HashSet<_>>();
+
+        user_ids
+            .into_iter()
+            .filter_map(|user_id| {
+                let summary = live.remove(&user_id);
+                self.apply(user_id, summary)
+            })
+            .collect()
+    }
+}
+
+/// Broadcasts changes to the `liveStreams` feed to this server's GraphQL subscribers. The changes
+/// themselves are computed by [`live_stream_feed_loop`].
+pub struct LiveStreamFeed {
+    sender: broadcast::Sender<LiveStreamFeedEvent>,
+}
+
+impl LiveStreamFeed {
+    pub fn new() -> Arc<Self> {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER_SIZE);
+        Arc::new(Self { sender })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<LiveStreamFeedEvent> {
+        self.sender.subscribe()
+    }
+
+    fn send(&self, event: LiveStreamFeedEvent) {
+        // This only fails if there are no subscribers, in which case there's nobody to tell
+        let _ = self.sender.send(event);
+    }
+}
+
+/// Listens for published live-stream changes and feeds them to `feed`, reconnecting if the Redis
+/// subscription is lost.
+pub async fn live_stream_feed_loop(feed: Arc<LiveStreamFeed>, db: PgPool, redis: RedisPool) {
+    let mut state = FeedState::default();
+    loop {
+        if let Err(e) = run_feed(&feed, &mut state, &db, &redis).await {
+            error!("Live stream feed subscription failed: {e:?}");
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn run_feed(
+    feed: &LiveStreamFeed,
+    state: &mut FeedState,
+    db: &PgPool,
+    redis: &RedisPool,
+) -> eyre::Result<()> {
+    let mut pubsub = redis.subscriber().await?;
+    pubsub
+        .subscribe(LIVE_STREAM_CHANNEL)
+        .await
+        .wrap_err("Failed to subscribe to live stream changes")?;
+
+    // Anything published before the subscription started was missed, so (re)load the full feed
+    // now that we're subscribed. Changes made in between just get applied twice, which is a no-op.
+    let blocked = load_feed_blocked_user_ids(db).await?;
+    let live = load_live_streams(redis).await?;
+    for event in state.resync(blocked, live) {
+        feed.send(event);
+    }
+
+    let mut messages = pubsub.into_on_message();
+    while let Some(msg) = messages.next().await {
+        let payload: String = match msg.get_payload() {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Received a non-string live stream message: {e:?}");
+                continue;
+            }
+        };
+        let message = match serde_json::from_str::<PublishedMessage>(&payload) {
+            Ok(PublishedMessage::LiveStream(message)) => message,
+            Ok(_) => continue,
+            Err(e) => {
+                warn!("Failed to parse live stream message: {e:?}");
+                continue;
+            }
+        };
+
+        let user_ids = match message {
+            PublishedLiveStreamMessage::StreamsChanged { user_ids } => user_ids,
+            PublishedLiveStreamMessage::FeedBlocksChanged { user_ids } => {
+                state.blocked = load_feed_blocked_user_ids(db).await?;
+                user_ids
+            }
+        };
+        let mut live = load_live_streams_for_users(redis, &user_ids)
+            .await?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        for user_id in user_ids {
+            if let Some(event) = state.apply(user_id, live.remove(&user_id)) {
+                feed.send(event);
+            }
+        }
+    }
+
+    Err(eyre::eyre!("Live stream subscription closed"))
+}
+
+#[derive(Default)]
+pub struct LiveStreamsSubscription;
+
+#[Subscription]
+impl LiveStreamsSubscription {
+    /// Changes to the `liveStreams` feed as they happen. Clients should subscribe and then load
+    /// `liveStreams`, applying these events on top. If a subscriber falls too far behind, the
+    /// subscription is ended and the client should resubscribe and reload the feed.
+    async fn live_stream_feed(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<impl Stream<Item = LiveStreamFeedEvent>> {
+        let receiver = ctx.data::<Arc<LiveStreamFeed>>()?.subscribe();
+        Ok(stream::unfold(receiver, |mut receiver| async move {
+            match receiver.recv().await {
+                Ok(event) => Some((event, receiver)),
+                Err(RecvError::Lagged(_) | RecvError::Closed) => None,
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::streaming::StreamingProvider;
+
+    fn summary(game_id: &str, viewer_count: i64) -> LiveStreamSummary {
+        LiveStreamSummary {
+            provider: StreamingProvider::Twitch,
+            channel_id: "1234".into(),
+            channel_login: "streamer".into(),
+            channel_display_name: "Streamer".into(),
+            stream_id: None,
+            title: "Ladder".into(),
+            game_id: game_id.into(),
+            game_name: "StarCraft".into(),
+            viewer_count,
+            started_at: Utc::now(),
+            thumbnail_url: "https://example.org/{width}x{height}.jpg".into(),
+        }
+    }
+
+    const STARCRAFT: &str = "11989";
+
+    #[test]
+    fn apply_reports_feed_transitions() {
+        let mut state = FeedState::default();
+        let user = SbUserId(5);
+        let live = summary(STARCRAFT, 10);
+
+        assert!(matches!(
+            state.apply(user, Some(live.clone())),
+            Some(LiveStreamFeedEvent::Online(_))
+        ));
+        assert_eq!(state.apply(user, Some(live.clone())), None);
+        assert_eq!(
+            state.apply(
+                user,
+                Some(LiveStreamSummary {
+                    viewer_count: 25,
+                    ..live.clone()
+                })
+            ),
+            Some(LiveStreamFeedEvent::ViewerCountChanged(
+                LiveStreamViewerCountChanged {
+                    user_id: user,
+                    viewer_count: 25,
+                }
+            ))
+        );
+        assert!(matches!(
+            state.apply(
+                user,
+                Some(LiveStreamSummary {
+                    title: "Team games".into(),
+                    ..live.clone()
+                })
+            ),
+            Some(LiveStreamFeedEvent::Updated(_))
+        ));
+        assert_eq!(
+            state.apply(user, None),
+            Some(LiveStreamFeedEvent::Offline(LiveStreamOffline {
+                user_id: user
+            }))
+        );
+        assert_eq!(state.apply(user, None), None);
+    }
+
+    #[test]
+    fn apply_treats_category_changes_as_entering_or_leaving_the_feed() {
+        let mut state = FeedState::default();
+        let user = SbUserId(5);
+
+        assert_eq!(state.apply(user, Some(summary("509658", 10))), None);
+        assert!(matches!(
+            state.apply(user, Some(summary(STARCRAFT, 10))),
+            Some(LiveStreamFeedEvent::Online(_))
+        ));
+        assert_eq!(
+            state.apply(user, Some(summary("509658", 10))),
+            Some(LiveStreamFeedEvent::Offline(LiveStreamOffline {
+                user_id: user
+            }))
+        );
+    }
+
+    #[test]
+    fn resync_respects_blocks_and_removes_missing_streams() {
+        let mut state = FeedState::default();
+        let events = state.resync(
+            HashSet::new(),
+            vec![
+                (SbUserId(1), summary(STARCRAFT, 1)),
+                (SbUserId(2), summary(STARCRAFT, 2)),
+            ],
+        );
+        assert_eq!(events.len(), 2);
+
+        let mut events = state.resync(
+            HashSet::from([SbUserId(2)]),
+            vec![
+                (SbUserId(2), summary(STARCRAFT, 2)),
+                (SbUserId(3), summary(STARCRAFT, 3)),
+            ],
+        );
+        events.sort_by_key(|e| match e {
+            LiveStreamFeedEvent::Online(e) => e.stream.user_id.0,
+            LiveStreamFeedEvent::Offline(e) => e.user_id.0,
+            _ => panic!("unexpected event: {e:?}"),
+        });
+        assert_eq!(
+            events[0],
+            LiveStreamFeedEvent::Offline(LiveStreamOffline {
+                user_id: SbUserId(1)
+            })
+        );
+        assert_eq!(
+            events[1],
+            LiveStreamFeedEvent::Offline(LiveStreamOffline {
+                user_id: SbUserId(2)
+            })
+        );
+        assert!(
+            matches!(&events[2], LiveStreamFeedEvent::Online(e) if e.stream.user_id == SbUserId(3))
+        );
+        assert_eq!(state.streams.len(), 1);
+    }
+}
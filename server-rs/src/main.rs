@@ -14,7 +14,7 @@ use server::redis::RedisPool;
 use server::routes::create_app;
 #[cfg(debug_assertions)]
 use server::schema::write_schema;
-use server::telemetry::init_subscriber;
+use server::telemetry::{flush_datadog_logs, init_subscriber, shutdown_traces};
 use tower::Layer;
 use tower_http::normalize_path::NormalizePathLayer;
 
@@ -52,7 +52,8 @@ async fn main() -> eyre::Result<()> {
         "debug"
     };
 
-    init_subscriber(&settings, "server", env_filter, std::io::stdout);
+    init_subscriber(&settings, "server", env_filter, std::io::stdout)
+        .wrap_err("Failed to initialize tracing")?;
 
     tracing::info!("Settings: {settings:?}");
 
@@ -85,12 +86,49 @@ async fn main() -> eyre::Result<()> {
 
     let router = create_app(db_pool, redis_pool, settings).await?;
     let app = NormalizePathLayer::trim_trailing_slash().layer(router);
-    axum::serve(
+    let result = axum::serve(
         listener,
         ServiceExt::<Request>::into_make_service_with_connect_info::<SocketAddr>(app),
     )
+    .with_graceful_shutdown(shutdown_signal())
     .await
-    .wrap_err("axum failure")?;
+    .wrap_err("axum failure");
 
-    Ok(())
+    // Buffered logs and spans would otherwise be lost, since the global subscriber is never dropped
+    flush_datadog_logs().await;
+    if let Err(e) = tokio::task::spawn_blocking(shutdown_traces).await {
+        tracing::warn!("Failed to shut down trace export: {e:?}");
+    }
+
+    result
+}
+
+/// Resolves once the process has been asked to stop (by Ctrl+C, or by `SIGTERM` from Docker).
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl+C: {e:?}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {e:?}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {},
+        () = terminate => {},
+    }
+    tracing::info!("Shutting down");
 }
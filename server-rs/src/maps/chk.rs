@@ -0,0 +1,581 @@
+//! Parsing of CHK data, the scenario file inside of a map archive that holds everything about the
+//! map (terrain, units, players, triggers, strings, etc.). Only the sections needed to list and
+//! host a map are looked at.
+//!
+//! Like the MPQ reader, this tries to accept anything StarCraft would: sections can appear in any
+//! order, be repeated, or be cut short, and protected maps do all of those.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use super::{MapForce, MapForcePlayer, MapForcePlayerRace};
+
+const SECTION_HEADER_SIZE: usize = 8;
+
+/// Player slot types (from the `OWNR` section).
+const SLOT_COMPUTER: u8 = 5;
+const SLOT_HUMAN: u8 = 6;
+
+/// The number of real player slots (the rest are neutral/unused).
+const NUM_PLAYERS: usize = 8;
+const NUM_FORCES: usize = 4;
+
+const UNIT_SIZE: usize = 36;
+pub const UNIT_START_LOCATION: u16 = 214;
+
+const TRIGGER_SIZE: usize = 2400;
+const TRIGGER_CONDITIONS: usize = 16;
+const CONDITION_SIZE: usize = 20;
+const TRIGGER_ACTIONS: usize = 64;
+const ACTION_SIZE: usize = 32;
+const ACTIONS_OFFSET: usize = TRIGGER_CONDITIONS * CONDITION_SIZE;
+/// Set on conditions/actions that have been disabled in the editor.
+const TRIGGER_ENTRY_DISABLED: u8 = 0x02;
+const CONDITION_DEATHS: u8 = 15;
+const ACTION_SET_DEATHS: u8 = 45;
+/// Death counts are stored in an array indexed by unit type and player, so any unit or player
+/// outside of these ranges reads or writes some other part of the game's memory.
+const FIRST_INVALID_DEATHS_UNIT: u16 = 228;
+const FIRST_INVALID_DEATHS_PLAYER: u32 = 27;
+
+#[derive(Debug, Error)]
+pub enum ChkError {
+    #[error("missing required section: {0}")]
+    MissingSection(&'static str),
+    #[error("invalid map dimensions: {0}x{1}")]
+    InvalidDimensions(u16, u16),
+}
+
+/// The information we use from a map's CHK data.
+#[derive(Debug, Clone)]
+pub struct ParsedChk {
+    pub title: String,
+    pub description: String,
+    pub width: u16,
+    pub height: u16,
+    pub tileset: u16,
+    /// The number of players that can play the map in melee games (its start locations).
+    pub melee_players: u8,
+    /// The number of human players that can play the map in UMS games.
+    pub ums_players: u8,
+    pub is_eud: bool,
+    /// The forces used to set up UMS lobbies, with empty forces removed.
+    pub forces: Vec<MapForce>,
+    /// The map's terrain, as `width * height` tile IDs in row-major order.
+    pub tiles: Vec<u16>,
+    /// The units placed on the map in the editor.
+    pub units: Vec<PlacedUnit>,
+}
+
+/// A unit placed on the map in the editor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlacedUnit {
+    pub unit_id: u16,
+    /// The position of the unit's center, in pixels.
+    pub x: u16,
+    pub y: u16,
+    pub owner: u8,
+}
+
+impl PlacedUnit {
+    fn parse(data: &[u8]) -> Self {
+        Self {
+            x: read_u16(data, 4).unwrap(),
+            y: read_u16(data, 6).unwrap(),
+            unit_id: read_u16(data, 8).unwrap(),
+            owner: data[16],
+        }
+    }
+}
+
+/// The sections of CHK data, keyed by name, in the order they appeared.
+struct Sections<'a>(HashMap<[u8; 4], Vec<&'a [u8]>>);
+
+impl<'a> Sections<'a> {
+    fn parse(data: &'a [u8]) -> Self {
+        let mut sections = HashMap::new();
+        let mut pos = 0;
+        while let Some(header) = data.get(pos..pos + SECTION_HEADER_SIZE) {
+            let name: [u8; 4] = header[..4].try_into().unwrap();
+            let len = i32::from_le_bytes(header[4..].try_into().unwrap());
+            pos += SECTION_HEADER_SIZE;
+            // StarCraft seeks backwards for negative lengths, which protectors use to make sections
+            // overlap. None of the sections we read are protected that way in practice, so we stop
+            // rather than risk looping forever.
+            let Ok(len) = usize::try_from(len) else {
+                break;
+            };
+            let end = pos.saturating_add(len).min(data.len());
+            sections
+                .entry(name)
+                .or_insert_with(Vec::new)
+                .push(&data[pos..end]);
+            pos = end;
+        }
+        Self(sections)
+    }
+
+    /// Returns the data of a section. When a section is repeated, StarCraft uses the last one.
+    fn get(&self, name: &[u8; 4]) -> Option<&'a [u8]> {
+        self.0.get(name).and_then(|s| s.last().copied())
+    }
+
+    /// Returns the data of a section that StarCraft reads every copy of into the same buffer, in
+    /// order, so a shorter copy only overwrites the start of the ones before it. Any of the buffer
+    /// that no copy covers is left zeroed.
+    fn get_layered(&self, name: &[u8; 4], len: usize) -> Vec<u8> {
+        let mut result = vec![0; len];
+        for section in self.0.get(name).into_iter().flatten() {
+            let copy_len = section.len().min(len);
+            result[..copy_len].copy_from_slice(&section[..copy_len]);
+        }
+        result
+    }
+
+    fn require(&self, name: &'static str) -> Result<&'a [u8], ChkError> {
+        let key: &[u8; 4] = name.as_bytes().try_into().unwrap();
+        self.get(key).ok_or(ChkError::MissingSection(name))
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+/// The map's string table. Strings are referenced elsewhere by 1-based index, with 0 meaning "no
+/// string".
+enum StringTable<'a> {
+    /// The original format, with 16-bit offsets.
+    Legacy(&'a [u8]),
+    /// The extended format used by newer editors, with 32-bit offsets.
+    Extended(&'a [u8]),
+    Missing,
+}
+
+impl StringTable<'_> {
+    fn get(&self, id: u32) -> Option<String> {
+        let index = usize::try_from(id.checked_sub(1)?).ok()?;
+        let (data, offset) = match *self {
+            StringTable::Legacy(data) => {
+                if index >= read_u16(data, 0)? as usize {
+                    return None;
+                }
+                (data, read_u16(data, 2 + index * 2)? as usize)
+            }
+            StringTable::Extended(data) => {
+                if index >= read_u32(data, 0)? as usize {
+                    return None;
+                }
+                (data, read_u32(data, 4 + index * 4)? as usize)
+            }
+            StringTable::Missing => return None,
+        };
+        let bytes = data.get(offset..)?;
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        Some(filter_color_codes(&decode_string(&bytes[..len])))
+    }
+}
+
+/// Decodes a string from the map. Maps don't specify their encoding: newer editors write UTF-8,
+/// while older maps use whatever code page the map maker's system used, which in practice means
+/// either Korean (CP949) or Western European (windows-1252).
+fn decode_string(bytes: &[u8]) -> String {
+    if let Ok(s) = std::str::from_utf8(bytes) {
+        return s.to_owned();
+    }
+    let (korean, _, had_errors) = encoding_rs::EUC_KR.decode(bytes);
+    if !had_errors && korean.chars().any(is_hangul) {
+        return korean.into_owned();
+    }
+    encoding_rs::WINDOWS_1252.decode(bytes).0.into_owned()
+}
+
+fn is_hangul(c: char) -> bool {
+    matches!(c, '\u{AC00}'..='\u{D7A3}' | '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}')
+}
+
+/// Removes the control characters that StarCraft uses for text colors/formatting, keeping line
+/// breaks. This matches `filterColorCodes` in `common/maps.ts`.
+pub fn filter_color_codes(s: &str) -> String {
+    s.chars()
+        .filter(|&c| c as u32 > 0x1F || c == '\n' || c == '\r')
+        .collect()
+}
+
+/// Converts a race from the `SIDE` section. Other values (e.g. user selectable) show up in some
+/// maps, but since this is only used to set up UMS lobbies where race mostly decides the music and
+/// console, falling back to terran is fine. See `createLobbyInitData` in the Node server.
+fn race_from_id(id: u8) -> MapForcePlayerRace {
+    match id {
+        0 => MapForcePlayerRace::Zerg,
+        1 => MapForcePlayerRace::Terran,
+        2 => MapForcePlayerRace::Protoss,
+        5 => MapForcePlayerRace::Any,
+        _ => MapForcePlayerRace::Terran,
+    }
+}
+
+pub fn parse_chk(data: &[u8]) -> Result<ParsedChk, ChkError> {
+    let sections = Sections::parse(data);
+
+    let dimensions = sections.require("DIM ")?;
+    let (width, height) = (
+        read_u16(dimensions, 0).unwrap_or(0),
+        read_u16(dimensions, 2).unwrap_or(0),
+    );
+    if !(1..=256).contains(&width) || !(1..=256).contains(&height) {
+        return Err(ChkError::InvalidDimensions(width, height));
+    }
+    // Only the low bits select the tileset; StarCraft ignores the rest
+    let tileset = read_u16(sections.require("ERA ")?, 0).unwrap_or(0) & 0x7;
+
+    let strings = match (sections.get(b"STRx"), sections.get(b"STR ")) {
+        (Some(data), _) => StringTable::Extended(data),
+        (None, Some(data)) => StringTable::Legacy(data),
+        (None, None) => StringTable::Missing,
+    };
+    let scenario_props = sections.get(b"SPRP").unwrap_or_default();
+    let title = read_u16(scenario_props, 0)
+        .and_then(|id| strings.get(id as u32))
+        .unwrap_or_default();
+    let description = read_u16(scenario_props, 2)
+        .and_then(|id| strings.get(id as u32))
+        .unwrap_or_default();
+
+    let owners = sections.require("OWNR")?;
+    let sides = sections.get(b"SIDE").unwrap_or_default();
+    let slot_type = |player: usize| owners.get(player).copied().unwrap_or(0);
+
+    let mut force_data = [0u8; 20];
+    let forc = sections.get(b"FORC").unwrap_or_default();
+    let forc_len = forc.len().min(force_data.len());
+    force_data[..forc_len].copy_from_slice(&forc[..forc_len]);
+
+    let mut forces = (0..NUM_FORCES)
+        .map(|i| {
+            let name = read_u16(&force_data, 8 + i * 2)
+                .and_then(|id| strings.get(id as u32))
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| format!("Force {}", i + 1));
+            MapForce {
+                name,
+                team_id: i as i32 + 1,
+                players: Vec::new(),
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut ums_players = 0;
+    for (player, &force) in force_data[..NUM_PLAYERS].iter().enumerate() {
+        let slot = slot_type(player);
+        if slot != SLOT_COMPUTER && slot != SLOT_HUMAN {
+            continue;
+        }
+        if slot == SLOT_HUMAN {
+            ums_players += 1;
+        }
+        // StarCraft puts players with an invalid force in the first one
+        let force = if (force as usize) < NUM_FORCES {
+            force as usize
+        } else {
+            0
+        };
+        forces[force].players.push(MapForcePlayer {
+            player_id: player as i32,
+            race: race_from_id(sides.get(player).copied().unwrap_or(0)),
+            type_id: slot as i32,
+            is_computer: slot == SLOT_COMPUTER,
+        });
+    }
+    forces.retain(|f| !f.players.is_empty());
+
+    let tiles = sections
+        .get_layered(b"MTXM", width as usize * height as usize * 2)
+        .chunks_exact(2)
+        .map(|t| u16::from_le_bytes([t[0], t[1]]))
+        .collect();
+    let units = sections
+        .get(b"UNIT")
+        .unwrap_or_default()
+        .chunks_exact(UNIT_SIZE)
+        .map(PlacedUnit::parse)
+        .collect::<Vec<_>>();
+
+    Ok(ParsedChk {
+        title,
+        description,
+        width,
+        height,
+        tileset,
+        melee_players: count_start_locations(&units),
+        ums_players,
+        is_eud: has_eud_triggers(sections.get(b"TRIG").unwrap_or_default()),
+        forces,
+        tiles,
+        units,
+    })
+}
+
+/// Counts the players that have a start location, which is how many players melee games on the
+/// map can seat (slot types are ignored in melee).
+fn count_start_locations(units: &[PlacedUnit]) -> u8 {
+    let mut has_start = [false; NUM_PLAYERS];
+    for unit in units {
+        let owner = unit.owner as usize;
+        if unit.unit_id == UNIT_START_LOCATION && owner < NUM_PLAYERS {
+            has_start[owner] = true;
+        }
+    }
+    has_start.iter().filter(|&&s| s).count() as u8
+}
+
+/// Returns whether any trigger reads or writes memory outside of the death table (an "EUD"),
+/// which maps use to change the game in ways that normal triggers can't.
+fn has_eud_triggers(triggers: &[u8]) -> bool {
+    triggers.chunks_exact(TRIGGER_SIZE).any(|trigger| {
+        let conditions = trigger[..ACTIONS_OFFSET]
+            .chunks_exact(CONDITION_SIZE)
+            // StarCraft stops at the first empty condition/action
+            .take_while(|c| c[15] != 0)
+            .filter(|c| c[17] & TRIGGER_ENTRY_DISABLED == 0)
+            .any(|c| c[15] == CONDITION_DEATHS && is_eud_target(read_u32(c, 4), read_u16(c, 12)));
+        let actions = || {
+            trigger[ACTIONS_OFFSET..ACTIONS_OFFSET + TRIGGER_ACTIONS * ACTION_SIZE]
+                .chunks_exact(ACTION_SIZE)
+                .take_while(|a| a[26] != 0)
+                .filter(|a| a[28] & TRIGGER_ENTRY_DISABLED == 0)
+                .any(|a| {
+                    a[26] == ACTION_SET_DEATHS && is_eud_target(read_u32(a, 16), read_u16(a, 24))
+                })
+        };
+        conditions || actions()
+    })
+}
+
+fn is_eud_target(player: Option<u32>, unit: Option<u16>) -> bool {
+    player.is_some_and(|p| p >= FIRST_INVALID_DEATHS_PLAYER)
+        || unit.is_some_and(|u| u >= FIRST_INVALID_DEATHS_UNIT)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// Builds CHK data out of (name, data) sections.
+    pub(in crate::maps) fn build_chk(sections: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let mut chk = Vec::new();
+        for (name, data) in sections {
+            chk.extend_from_slice(*name);
+            chk.extend_from_slice(&(data.len() as i32).to_le_bytes());
+            chk.extend_from_slice(data);
+        }
+        chk
+    }
+
+    /// Builds a legacy string table out of the given (already encoded) strings.
+    fn build_strings(strings: &[&[u8]]) -> Vec<u8> {
+        let header_len = 2 + strings.len() * 2;
+        let mut offsets = Vec::new();
+        let mut data = Vec::new();
+        for s in strings {
+            offsets.extend_from_slice(&((header_len + data.len()) as u16).to_le_bytes());
+            data.extend_from_slice(s);
+            data.push(0);
+        }
+        let mut table = (strings.len() as u16).to_le_bytes().to_vec();
+        table.extend(offsets);
+        table.extend(data);
+        table
+    }
+
+    fn unit(unit_id: u16, owner: u8) -> Vec<u8> {
+        let mut unit = vec![0; UNIT_SIZE];
+        unit[8..10].copy_from_slice(&unit_id.to_le_bytes());
+        unit[16] = owner;
+        unit
+    }
+
+    /// A basic 4 player map: players 1-3 are humans and 4 is a computer. Players 1 and 2 are on the
+    /// first force, the rest on the third.
+    pub(in crate::maps) fn basic_map_sections() -> Vec<(&'static [u8; 4], Vec<u8>)> {
+        let mut dim = 128u16.to_le_bytes().to_vec();
+        dim.extend_from_slice(&96u16.to_le_bytes());
+        let mut sprp = 1u16.to_le_bytes().to_vec();
+        sprp.extend_from_slice(&2u16.to_le_bytes());
+        let mut forc = vec![0, 0, 2, 2, 0, 0, 0, 0];
+        for name in [3u16, 0, 0, 0] {
+            forc.extend_from_slice(&name.to_le_bytes());
+        }
+        forc.extend_from_slice(&[0; 4]);
+
+        vec![
+            (b"VER ", 205u16.to_le_bytes().to_vec()),
+            (b"DIM ", dim),
+            // The high bits of the tileset should be ignored
+            (b"ERA ", 0x0F04u16.to_le_bytes().to_vec()),
+            (b"OWNR", vec![6, 6, 6, 5, 0, 0, 0, 0, 7, 7, 7, 7]),
+            (b"SIDE", vec![0, 1, 2, 5, 0, 0, 0, 0, 7, 7, 7, 7]),
+            (b"FORC", forc),
+            (
+                b"STR ",
+                build_strings(&[
+                    b"\x03Fighting \x04Spirit".as_slice(),
+                    b"A map\r\nfor testing",
+                    b"Top",
+                ]),
+            ),
+            (b"SPRP", sprp),
+            (
+                b"UNIT",
+                [
+                    unit(214, 0),
+                    unit(214, 1),
+                    unit(0, 2),
+                    unit(214, 1),
+                    unit(214, 12),
+                ]
+                .concat(),
+            ),
+        ]
+    }
+
+    #[test]
+    fn parses_basic_map() {
+        let parsed = parse_chk(&build_chk(&basic_map_sections())).unwrap();
+        assert_eq!(parsed.title, "Fighting Spirit");
+        assert_eq!(parsed.description, "A map\r\nfor testing");
+        assert_eq!((parsed.width, parsed.height), (128, 96));
+        assert_eq!(parsed.tileset, 4);
+        assert_eq!(parsed.melee_players, 2);
+        assert_eq!(parsed.ums_players, 3);
+        assert!(!parsed.is_eud);
+
+        assert_eq!(parsed.forces.len(), 2);
+        assert_eq!(parsed.forces[0].name, "Top");
+        assert_eq!(parsed.forces[0].team_id, 1);
+        assert_eq!(
+            parsed.forces[0]
+                .players
+                .iter()
+                .map(|p| (p.player_id, p.race))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, MapForcePlayerRace::Zerg),
+                (1, MapForcePlayerRace::Terran)
+            ]
+        );
+        assert_eq!(parsed.forces[1].name, "Force 3");
+        assert_eq!(parsed.forces[1].team_id, 3);
+        let computer = &parsed.forces[1].players[1];
+        assert_eq!(computer.player_id, 3);
+        assert_eq!(computer.race, MapForcePlayerRace::Any);
+        assert_eq!(computer.type_id, 5);
+        assert!(computer.is_computer);
+    }
+
+    #[test]
+    fn later_sections_override_earlier_ones() {
+        let mut sections = basic_map_sections();
+        let mut dim = 64u16.to_le_bytes().to_vec();
+        dim.extend_from_slice(&64u16.to_le_bytes());
+        sections.push((b"DIM ", dim));
+        // A truncated trailing section shouldn't cause an error
+        let mut chk = build_chk(&sections);
+        chk.extend_from_slice(b"MTXM");
+        chk.extend_from_slice(&1000i32.to_le_bytes());
+        chk.extend_from_slice(&[0; 10]);
+
+        let parsed = parse_chk(&chk).unwrap();
+        assert_eq!((parsed.width, parsed.height), (64, 64));
+    }
+
+    #[test]
+    fn layers_terrain_sections() {
+        let tiles = |tile: u16, count| tile.to_le_bytes().repeat(count);
+        let mut sections = basic_map_sections();
+        sections.push((b"MTXM", tiles(1, 4)));
+        sections.push((b"MTXM", tiles(2, 2)));
+
+        let parsed = parse_chk(&build_chk(&sections)).unwrap();
+        assert_eq!(parsed.tiles.len(), 128 * 96);
+        assert_eq!(parsed.tiles[..5], [2, 2, 1, 1, 0]);
+        assert_eq!(
+            parsed.units[0],
+            PlacedUnit {
+                unit_id: UNIT_START_LOCATION,
+                x: 0,
+                y: 0,
+                owner: 0
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_maps_without_required_sections() {
+        let sections = basic_map_sections()
+            .into_iter()
+            .filter(|(name, _)| *name != b"DIM ")
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            parse_chk(&build_chk(&sections)),
+            Err(ChkError::MissingSection("DIM "))
+        ));
+    }
+
+    #[test]
+    fn prefers_extended_strings() {
+        let mut strx = 1u32.to_le_bytes().to_vec();
+        strx.extend_from_slice(&8u32.to_le_bytes());
+        strx.extend_from_slice("Extended ✓\0".as_bytes());
+        let mut sections = basic_map_sections();
+        sections.push((b"STRx", strx));
+
+        let parsed = parse_chk(&build_chk(&sections)).unwrap();
+        assert_eq!(parsed.title, "Extended ✓");
+        // String 2 doesn't exist in the extended table
+        assert_eq!(parsed.description, "");
+    }
+
+    #[test]
+    fn decodes_legacy_encodings() {
+        // "투혼" in CP949
+        assert_eq!(decode_string(&[0xC5, 0xF5, 0xC8, 0xA5]), "투혼");
+        assert_eq!(decode_string(b"Caf\xe9"), "Café");
+        assert_eq!(decode_string("Café".as_bytes()), "Café");
+    }
+
+    #[test]
+    fn detects_eud_triggers() {
+        let trigger = |condition: Option<(u32, u16)>, action: Option<(u32, u16)>| {
+            let mut trigger = vec![0; TRIGGER_SIZE];
+            if let Some((player, unit)) = condition {
+                let c = &mut trigger[..CONDITION_SIZE];
+                c[4..8].copy_from_slice(&player.to_le_bytes());
+                c[12..14].copy_from_slice(&unit.to_le_bytes());
+                c[15] = CONDITION_DEATHS;
+            }
+            if let Some((player, unit)) = action {
+                let a = &mut trigger[ACTIONS_OFFSET..ACTIONS_OFFSET + ACTION_SIZE];
+                a[16..20].copy_from_slice(&player.to_le_bytes());
+                a[24..26].copy_from_slice(&unit.to_le_bytes());
+                a[26] = ACTION_SET_DEATHS;
+            }
+            trigger
+        };
+
+        assert!(!has_eud_triggers(&trigger(Some((0, 0)), Some((17, 227)))));
+        assert!(has_eud_triggers(&trigger(Some((0xFFFF_F000, 0)), None)));
+        assert!(has_eud_triggers(&trigger(None, Some((0, 228)))));
+        // Conditions after an empty one are never checked
+        let mut hidden = trigger(None, None);
+        hidden[CONDITION_SIZE + 4..CONDITION_SIZE + 8].copy_from_slice(&100u32.to_le_bytes());
+        hidden[CONDITION_SIZE + 15] = CONDITION_DEATHS;
+        assert!(!has_eud_triggers(&hidden));
+    }
+}
@@ -1,4 +1,5 @@
 use std::fmt::Display;
+use std::str::FromStr;
 
 use async_graphql::futures_util::TryStreamExt;
 use async_graphql::{
@@ -10,16 +11,32 @@ use chrono::{DateTime, Utc};
 use data_encoding::BASE64;
 use data_encoding::HEXLOWER;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
 use typeshare::typeshare;
 use uuid::Uuid;
 
 use crate::file_store::FileStore;
+use crate::matchmaking::MatchmakingType;
 use crate::{
     graphql::{errors::graphql_error, schema_builder::SchemaBuilderModule},
-    users::{SbUser, SbUserId, UsersLoader},
+    users::{CurrentUser, SbUser, SbUserId, UsersLoader},
 };
 
+pub mod chk;
+pub mod mpq;
+pub mod render;
+pub mod search;
+pub mod stats;
+pub mod store;
+
+// NOTE: The Node server's `MAP_PARSER_VERSION` (in `server/lib/maps/parser-version.ts`) covers
+// versions before this one, and should not be bumped to or past it.
+/// The current version of the map parser. Increase this any time the parser changes in a way that
+/// invalidates previously parsed data (e.g. adds features, or changes results for maps that parsed
+/// successfully before), and [`store::map_reparse_loop`] will re-parse the existing maps.
+pub const MAP_PARSER_VERSION: i32 = 3;
+
 pub struct MapsModule {
     db_pool: PgPool,
 }
@@ -32,10 +49,23 @@ impl MapsModule {
 
 impl SchemaBuilderModule for MapsModule {
     fn apply<Q, M, S>(&self, builder: SchemaBuilder<Q, M, S>) -> SchemaBuilder<Q, M, S> {
-        builder.data(DataLoader::new(
-            MapsLoader::new(self.db_pool.clone()),
-            tokio::spawn,
-        ))
+        builder
+            .data(DataLoader::new(
+                MapsLoader::new(self.db_pool.clone()),
+                tokio::spawn,
+            ))
+            .data(DataLoader::new(
+                search::MapTagsLoader::new(self.db_pool.clone()),
+                tokio::spawn,
+            ))
+            .data(DataLoader::new(
+                search::MapFavoritesLoader::new(self.db_pool.clone()),
+                tokio::spawn,
+            ))
+            .data(DataLoader::new(
+                stats::MapStatsLoader::new(self.db_pool.clone()),
+                tokio::spawn,
+            ))
     }
 }
 
@@ -81,7 +111,7 @@ impl Display for SbMapId {
 #[sqlx(transparent)]
 pub struct MapHash(pub [u8; 32]);
 
-#[derive(Debug, Clone, SimpleObject)]
+#[derive(Debug, Clone, SimpleObject, sqlx::FromRow)]
 #[graphql(complex)]
 pub struct UploadedMap {
     pub id: SbMapId,
@@ -110,6 +140,39 @@ impl UploadedMap {
             .map(|m| m.into())
             .ok_or_else(|| graphql_error("NOT_FOUND", "Map file not found"))
     }
+
+    /// The tags map curators have applied to this map, alphabetically.
+    async fn tags(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<Vec<String>> {
+        let loader = ctx.data_unchecked::<DataLoader<search::MapTagsLoader>>();
+        Ok(loader.load_one(self.id).await?.unwrap_or_default())
+    }
+
+    /// Whether the current user has favorited this map. Always `false` if not logged in.
+    async fn is_favorited(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<bool> {
+        let Some(user) = ctx.data::<Option<CurrentUser>>()? else {
+            return Ok(false);
+        };
+        let loader = ctx.data_unchecked::<DataLoader<search::MapFavoritesLoader>>();
+        Ok(loader.load_one((user.id, self.id)).await?.is_some())
+    }
+
+    /// Balance stats for this map in a matchmaking type, computed from completed games. Defaults
+    /// to the current season. `None` if that season doesn't exist (or hasn't started).
+    async fn balance_stats(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        matchmaking_type: MatchmakingType,
+        season_id: Option<i32>,
+    ) -> async_graphql::Result<Option<stats::MapBalanceStats>> {
+        let loader = ctx.data_unchecked::<DataLoader<stats::MapStatsLoader>>();
+        loader
+            .load_one(stats::MapStatsKey {
+                map_id: self.id,
+                matchmaking_type,
+                season_id,
+            })
+            .await
+    }
 }
 
 /// The privacy level for a map. This determines who can use the map for creating games.
@@ -149,7 +212,102 @@ pub struct DbMapFile {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LobbyInitData {
-    forces: Vec<MapForce>,
+    pub forces: Vec<MapForce>,
+}
+
+/// The file formats that maps can be uploaded in. Both are MPQ archives containing CHK data, but
+/// `Scm` maps can only use original StarCraft content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapExtension {
+    Scm,
+    Scx,
+}
+
+impl MapExtension {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MapExtension::Scm => "scm",
+            MapExtension::Scx => "scx",
+        }
+    }
+}
+
+impl FromStr for MapExtension {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "scm" => Ok(MapExtension::Scm),
+            "scx" => Ok(MapExtension::Scx),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MapParseError {
+    #[error("invalid map archive")]
+    Archive(#[from] mpq::MpqError),
+    #[error("invalid map data")]
+    Chk(#[from] chk::ChkError),
+}
+
+/// A map file's hash along with the data parsed from it.
+#[derive(Debug, Clone)]
+pub struct ParsedMap {
+    pub hash: MapHash,
+    pub extension: MapExtension,
+    pub data: chk::ParsedChk,
+}
+
+impl ParsedMap {
+    pub fn lobby_init_data(&self) -> LobbyInitData {
+        LobbyInitData {
+            forces: self.data.forces.clone(),
+        }
+    }
+}
+
+/// Returns the hash that identifies a map file. The extension is included since the same data
+/// would be handled differently as an `scm` vs. an `scx` (matching the Node server's hashes).
+pub fn map_hash(data: &[u8], extension: MapExtension) -> MapHash {
+    let mut hasher = Sha256::new();
+    hasher.update(extension.as_str());
+    hasher.update(data);
+    MapHash(hasher.finalize().into())
+}
+
+/// Parses a map file (an MPQ archive containing CHK data). This is CPU-bound, so it should be run
+/// off of the async runtime (e.g. with [`spawn_rayon`](crate::async_rayon::spawn_rayon)).
+pub fn parse_map(data: &[u8], extension: MapExtension) -> Result<ParsedMap, MapParseError> {
+    let chk = mpq::Mpq::new(data)?.read_file(mpq::SCENARIO_CHK_PATH)?;
+    Ok(ParsedMap {
+        hash: map_hash(data, extension),
+        extension,
+        data: chk::parse_chk(&chk)?,
+    })
+}
+
+/// Returns the path in the file store of a map's preview image with the given width (one of
+/// [`render::MAP_IMAGE_SIZES`]).
+pub fn map_image_path(hash: &MapHash, size: u32) -> String {
+    let hash = HEXLOWER.encode(&hash.0);
+    format!(
+        "map_images/{}/{}/{hash}-{size}.jpg",
+        &hash[0..2],
+        &hash[2..4]
+    )
+}
+
+/// Returns the path in the file store of a map file.
+pub fn map_path(hash: &MapHash, extension: MapExtension) -> String {
+    let hash = HEXLOWER.encode(&hash.0);
+    format!(
+        "maps/{}/{}/{hash}.{}",
+        &hash[0..2],
+        &hash[2..4],
+        extension.as_str()
+    )
 }
 
 #[derive(Debug, Clone, SimpleObject)]
@@ -178,14 +336,8 @@ impl MapFile {
     }
 
     #[graphql(skip)]
-    fn image_path(&self, size: usize) -> String {
-        let hash = HEXLOWER.encode(&self.hash.0);
-        format!(
-            "map_images/{}/{}/{}-{size}.jpg",
-            &hash[0..2],
-            &hash[2..4],
-            hash
-        )
+    fn image_path(&self, size: u32) -> String {
+        map_image_path(&self.hash, size)
     }
 
     async fn image_256_url(
@@ -356,3 +508,38 @@ impl Loader<MapHash> for MapsLoader {
         .await?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_map_archives() {
+        let chk = chk::tests::build_chk(&chk::tests::basic_map_sections());
+        let archive = mpq::tests::build_archive(mpq::SCENARIO_CHK_PATH, &chk);
+
+        let parsed = parse_map(&archive, MapExtension::Scx).unwrap();
+        assert_eq!(parsed.data.title, "Fighting Spirit");
+        assert_eq!(parsed.hash, map_hash(&archive, MapExtension::Scx));
+        assert_ne!(parsed.hash, map_hash(&archive, MapExtension::Scm));
+        assert_eq!(parsed.lobby_init_data().forces.len(), 2);
+
+        assert!(matches!(
+            parse_map(&chk, MapExtension::Scx),
+            Err(MapParseError::Archive(mpq::MpqError::NoHeader))
+        ));
+    }
+
+    #[test]
+    fn map_paths_are_sharded_by_hash() {
+        let mut hash = [0u8; 32];
+        hash[0] = 0xAB;
+        hash[1] = 0xCD;
+        let path = map_path(&MapHash(hash), MapExtension::Scm);
+        assert!(path.starts_with("maps/ab/cd/abcd00"));
+        assert!(path.ends_with(".scm"));
+        assert!(map_image_path(&MapHash(hash), 512).ends_with("-512.jpg"));
+        assert_eq!("SCX".parse::<MapExtension>(), Ok(MapExtension::Scx));
+        assert!("zip".parse::<MapExtension>().is_err());
+    }
+}
@@ -0,0 +1,586 @@
+//! A minimal reader for the MPQ archives that StarCraft maps (.scm/.scx) are stored in.
+//!
+//! Only what's needed to extract the map's CHK data is supported (no listfiles, patches, or newer
+//! archive formats). Map "protectors" deliberately corrupt the parts of the archive that StarCraft
+//! doesn't look at (header sizes, table sizes, unused hash entries, etc.), so this tries to be
+//! exactly as lenient as StarCraft itself, rather than validating the archive.
+
+use std::io::Read;
+
+use thiserror::Error;
+
+/// The path of the CHK file inside a map archive.
+pub const SCENARIO_CHK_PATH: &str = "staredit\\scenario.chk";
+
+const HEADER_MAGIC: &[u8; 4] = b"MPQ\x1a";
+/// Archive headers are always aligned to this within the file.
+const HEADER_ALIGNMENT: usize = 0x200;
+const HEADER_SIZE: usize = 0x20;
+const TABLE_ENTRY_SIZE: usize = 16;
+/// The largest (uncompressed) file we'll read out of an archive. This is far beyond the size of
+/// any real CHK, and keeps malicious archives from making us allocate huge buffers.
+const MAX_FILE_SIZE: usize = 64 * 1024 * 1024;
+
+const HASH_ENTRY_EMPTY: u32 = 0xFFFF_FFFF;
+const HASH_ENTRY_DELETED: u32 = 0xFFFF_FFFE;
+
+const FILE_IMPLODE: u32 = 0x0000_0100;
+const FILE_COMPRESS: u32 = 0x0000_0200;
+const FILE_ENCRYPTED: u32 = 0x0001_0000;
+const FILE_FIX_KEY: u32 = 0x0002_0000;
+const FILE_SINGLE_UNIT: u32 = 0x0100_0000;
+const FILE_EXISTS: u32 = 0x8000_0000;
+
+const COMPRESSION_ZLIB: u8 = 0x02;
+const COMPRESSION_IMPLODE: u8 = 0x08;
+const COMPRESSION_BZIP2: u8 = 0x10;
+
+/// Hash types used with [`hash_string`].
+const HASH_TABLE_OFFSET: u32 = 0;
+const HASH_NAME_A: u32 = 1;
+const HASH_NAME_B: u32 = 2;
+const HASH_FILE_KEY: u32 = 3;
+
+#[derive(Debug, Error)]
+pub enum MpqError {
+    #[error("no MPQ header found")]
+    NoHeader,
+    #[error("file not found in archive")]
+    FileNotFound,
+    #[error("file data is outside of the archive")]
+    Truncated,
+    #[error("file is too large")]
+    TooLarge,
+    #[error("unsupported compression type: {0:#04x}")]
+    UnsupportedCompression(u8),
+    #[error("failed to decompress file data")]
+    Decompression(#[source] std::io::Error),
+    #[error("failed to decompress imploded file data")]
+    Explode(#[source] explode::Error),
+}
+
+const fn build_crypt_table() -> [u32; 0x500] {
+    let mut table = [0u32; 0x500];
+    let mut seed: u32 = 0x0010_0001;
+    let mut i = 0;
+    while i < 0x100 {
+        let mut j = 0;
+        while j < 5 {
+            seed = (seed * 125 + 3) % 0x2A_AAAB;
+            let high = (seed & 0xFFFF) << 16;
+            seed = (seed * 125 + 3) % 0x2A_AAAB;
+            let low = seed & 0xFFFF;
+            table[i + j * 0x100] = high | low;
+            j += 1;
+        }
+        i += 1;
+    }
+    table
+}
+
+static CRYPT_TABLE: [u32; 0x500] = build_crypt_table();
+
+/// Hashes a file name (or table name) the way Storm does. Names are case-insensitive and treat `/`
+/// and `\` the same.
+fn hash_string(name: &str, hash_type: u32) -> u32 {
+    let mut seed1: u32 = 0x7FED_7FED;
+    let mut seed2: u32 = 0xEEEE_EEEE;
+    for b in name.bytes() {
+        let b = match b.to_ascii_uppercase() {
+            b'/' => b'\\',
+            b => b,
+        } as u32;
+        seed1 = CRYPT_TABLE[(hash_type * 0x100 + b) as usize] ^ seed1.wrapping_add(seed2);
+        seed2 = b
+            .wrapping_add(seed1)
+            .wrapping_add(seed2)
+            .wrapping_add(seed2 << 5)
+            .wrapping_add(3);
+    }
+    seed1
+}
+
+/// Decrypts `data` in place. Any trailing bytes that don't make up a full `u32` are left as-is,
+/// since Storm doesn't encrypt them.
+fn decrypt(data: &mut [u8], mut key: u32) {
+    let mut seed: u32 = 0xEEEE_EEEE;
+    for chunk in data.chunks_exact_mut(4) {
+        seed = seed.wrapping_add(CRYPT_TABLE[0x400 + (key & 0xFF) as usize]);
+        let value = u32::from_le_bytes(chunk.try_into().unwrap()) ^ key.wrapping_add(seed);
+        key = ((!key) << 21).wrapping_add(0x1111_1111) | (key >> 11);
+        seed = value
+            .wrapping_add(seed)
+            .wrapping_add(seed << 5)
+            .wrapping_add(3);
+        chunk.copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset.checked_add(4)?)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct HashEntry {
+    name_a: u32,
+    name_b: u32,
+    block_index: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BlockEntry {
+    offset: u32,
+    compressed_size: u32,
+    file_size: u32,
+    flags: u32,
+}
+
+/// An MPQ archive held in memory.
+pub struct Mpq<'a> {
+    /// The archive's data, starting at its header.
+    data: &'a [u8],
+    sector_size: usize,
+    /// The hash table size the header claims, which lookups wrap around. Fewer entries than this
+    /// may actually be present in the file.
+    hash_table_size: u32,
+    hash_table: Vec<HashEntry>,
+    block_table: Vec<BlockEntry>,
+}
+
+impl<'a> Mpq<'a> {
+    pub fn new(file: &'a [u8]) -> Result<Self, MpqError> {
+        let start = (0..file.len())
+            .step_by(HEADER_ALIGNMENT)
+            .find(|&offset| file[offset..].starts_with(HEADER_MAGIC))
+            .ok_or(MpqError::NoHeader)?;
+        let data = &file[start..];
+        if data.len() < HEADER_SIZE {
+            return Err(MpqError::NoHeader);
+        }
+
+        // StarCraft ignores the header size, archive size and format version, so they're often
+        // garbage in protected maps
+        let sector_size_shift = u16::from_le_bytes([data[14], data[15]]).min(16);
+        let hash_table_offset = read_u32(data, 16).unwrap();
+        let block_table_offset = read_u32(data, 20).unwrap();
+        let hash_table_size = read_u32(data, 24).unwrap();
+        let block_table_size = read_u32(data, 28).unwrap();
+
+        let hash_table = read_table(
+            data,
+            hash_table_offset,
+            hash_table_size,
+            hash_string("(hash table)", HASH_FILE_KEY),
+        )
+        .into_iter()
+        .map(|entry| HashEntry {
+            name_a: entry[0],
+            name_b: entry[1],
+            // entry[2] holds the locale and platform, which StarCraft doesn't check
+            block_index: entry[3],
+        })
+        .collect();
+        let block_table = read_table(
+            data,
+            block_table_offset,
+            block_table_size,
+            hash_string("(block table)", HASH_FILE_KEY),
+        )
+        .into_iter()
+        .map(|entry| BlockEntry {
+            offset: entry[0],
+            compressed_size: entry[1],
+            file_size: entry[2],
+            flags: entry[3],
+        })
+        .collect();
+
+        Ok(Self {
+            data,
+            sector_size: 0x200 << sector_size_shift,
+            hash_table_size,
+            hash_table,
+            block_table,
+        })
+    }
+
+    /// Reads the contents of the file at `path` in the archive.
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>, MpqError> {
+        let block = self.find_block(path).ok_or(MpqError::FileNotFound)?;
+        let file_size = block.file_size as usize;
+        if file_size > MAX_FILE_SIZE {
+            return Err(MpqError::TooLarge);
+        }
+        let key = (block.flags & FILE_ENCRYPTED != 0).then(|| {
+            let file_name = path.rsplit(['\\', '/']).next().unwrap_or(path);
+            let key = hash_string(file_name, HASH_FILE_KEY);
+            if block.flags & FILE_FIX_KEY != 0 {
+                key.wrapping_add(block.offset) ^ block.file_size
+            } else {
+                key
+            }
+        });
+        let file_data = self
+            .data
+            .get(block.offset as usize..)
+            .ok_or(MpqError::Truncated)?;
+
+        if block.flags & FILE_SINGLE_UNIT != 0 {
+            let mut unit = file_data
+                .get(..block.compressed_size as usize)
+                .ok_or(MpqError::Truncated)?
+                .to_vec();
+            if let Some(key) = key {
+                decrypt(&mut unit, key);
+            }
+            let mut result = decompress_unit(unit, file_size, block.flags)?;
+            result.truncate(file_size);
+            return Ok(result);
+        }
+
+        let num_sectors = file_size.div_ceil(self.sector_size);
+        let compressed = block.flags & (FILE_COMPRESS | FILE_IMPLODE) != 0;
+        let sector_offsets = if compressed {
+            let table_len = (num_sectors + 1) * 4;
+            let mut table = file_data
+                .get(..table_len)
+                .ok_or(MpqError::Truncated)?
+                .to_vec();
+            if let Some(key) = key {
+                decrypt(&mut table, key.wrapping_sub(1));
+            }
+            table
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+                .collect::<Vec<_>>()
+        } else {
+            (0..=num_sectors)
+                .map(|i| (i * self.sector_size).min(file_size))
+                .collect()
+        };
+
+        let mut result = Vec::with_capacity(file_size);
+        for (i, bounds) in sector_offsets.windows(2).enumerate() {
+            let expected_size = self.sector_size.min(file_size - result.len());
+            let mut sector = file_data
+                .get(bounds[0]..bounds[1])
+                .ok_or(MpqError::Truncated)?
+                .to_vec();
+            if let Some(key) = key {
+                decrypt(&mut sector, key.wrapping_add(i as u32));
+            }
+            let sector = if compressed {
+                decompress_unit(sector, expected_size, block.flags)?
+            } else {
+                sector
+            };
+            result.extend_from_slice(&sector[..sector.len().min(expected_size)]);
+            if result.len() >= file_size {
+                break;
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn find_block(&self, path: &str) -> Option<BlockEntry> {
+        if self.hash_table_size == 0 {
+            return None;
+        }
+        let start = hash_string(path, HASH_TABLE_OFFSET);
+        let name_a = hash_string(path, HASH_NAME_A);
+        let name_b = hash_string(path, HASH_NAME_B);
+        for i in 0..self.hash_table_size {
+            // Storm requires power of 2 table sizes and just masks the index, so do the same for
+            // any archive that lies about that
+            let index = start.wrapping_add(i) & (self.hash_table_size - 1);
+            let entry = self.hash_table.get(index as usize)?;
+            if entry.block_index == HASH_ENTRY_EMPTY {
+                return None;
+            }
+            if entry.block_index == HASH_ENTRY_DELETED
+                || entry.name_a != name_a
+                || entry.name_b != name_b
+            {
+                continue;
+            }
+
+            // Protectors add extra entries with the same name that point at nothing, which
+            // StarCraft skips over
+            if let Some(&block) = self.block_table.get(entry.block_index as usize)
+                && block.flags & FILE_EXISTS != 0
+            {
+                return Some(block);
+            }
+        }
+        None
+    }
+}
+
+/// Reads and decrypts a hash or block table. Tables that extend past the end of the file are cut
+/// short rather than treated as an error, since their sizes are often inflated by protectors.
+fn read_table(data: &[u8], offset: u32, size: u32, key: u32) -> Vec<[u32; 4]> {
+    let table = data.get(offset as usize..).unwrap_or_default();
+    let len = (size as usize)
+        .saturating_mul(TABLE_ENTRY_SIZE)
+        .min(table.len() - table.len() % TABLE_ENTRY_SIZE);
+    let mut table = table[..len].to_vec();
+    decrypt(&mut table, key);
+    table
+        .chunks_exact(TABLE_ENTRY_SIZE)
+        .map(|entry| {
+            std::array::from_fn(|i| u32::from_le_bytes(entry[i * 4..i * 4 + 4].try_into().unwrap()))
+        })
+        .collect()
+}
+
+/// Decompresses a single sector (or the whole file, for single unit files). Units that are as big
+/// as their expected size were stored uncompressed.
+fn decompress_unit(unit: Vec<u8>, expected_size: usize, flags: u32) -> Result<Vec<u8>, MpqError> {
+    if unit.len() >= expected_size {
+        return Ok(unit);
+    }
+
+    if flags & FILE_IMPLODE != 0 {
+        return explode_capped(&unit, expected_size);
+    }
+
+    let Some((&compression, compressed)) = unit.split_first() else {
+        return Err(MpqError::Truncated);
+    };
+    let mut result = Vec::with_capacity(expected_size);
+    match compression {
+        COMPRESSION_ZLIB => {
+            flate2::read::ZlibDecoder::new(compressed)
+                .take(expected_size as u64)
+                .read_to_end(&mut result)
+                .map_err(MpqError::Decompression)?;
+        }
+        COMPRESSION_BZIP2 => {
+            bzip2::read::BzDecoder::new(compressed)
+                .take(expected_size as u64)
+                .read_to_end(&mut result)
+                .map_err(MpqError::Decompression)?;
+        }
+        COMPRESSION_IMPLODE => {
+            result = explode_capped(compressed, expected_size)?;
+        }
+        other => return Err(MpqError::UnsupportedCompression(other)),
+    }
+    Ok(result)
+}
+
+/// Explodes `data`, failing as soon as the output grows past `max_size`. Unlike the zlib and bzip2
+/// readers, `explode::explode` has no way to cap its output, and a tiny unit can expand to an
+/// enormous one.
+fn explode_capped(data: &[u8], max_size: usize) -> Result<Vec<u8>, MpqError> {
+    let mut decoder = explode::Explode::new();
+    let mut buf = [0; 4096];
+    let mut output = decoder.with_buffer(&mut buf);
+    let mut result = Vec::with_capacity(max_size);
+    let mut i = 0;
+    while i < data.len() {
+        // The same byte has to be fed again until the decoder asks for more input
+        match output.feed(data[i]) {
+            Ok(()) => {
+                if result.len() + output.len() > max_size {
+                    return Err(MpqError::TooLarge);
+                }
+                result.extend_from_slice(output.get());
+                if output.done() {
+                    return Ok(result);
+                }
+                output.reset();
+            }
+            Err(explode::Error::IncompleteInput) => i += 1,
+            Err(e) => return Err(MpqError::Explode(e)),
+        }
+    }
+    Err(MpqError::Explode(explode::Error::IncompleteInput))
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn encrypt(data: &mut [u8], mut key: u32) {
+        let mut seed: u32 = 0xEEEE_EEEE;
+        for chunk in data.chunks_exact_mut(4) {
+            let value = u32::from_le_bytes(chunk.try_into().unwrap());
+            seed = seed.wrapping_add(CRYPT_TABLE[0x400 + (key & 0xFF) as usize]);
+            chunk.copy_from_slice(&(value ^ key.wrapping_add(seed)).to_le_bytes());
+            key = ((!key) << 21).wrapping_add(0x1111_1111) | (key >> 11);
+            seed = value
+                .wrapping_add(seed)
+                .wrapping_add(seed << 5)
+                .wrapping_add(3);
+        }
+    }
+
+    fn table_bytes(entries: &[[u32; 4]], key: u32) -> Vec<u8> {
+        let mut bytes = entries
+            .iter()
+            .flatten()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        encrypt(&mut bytes, key);
+        bytes
+    }
+
+    /// Builds an archive containing `contents` at `path`, stored as encrypted, zlib-compressed
+    /// sectors. A garbage prefix is included to check that the header is searched for.
+    pub(in crate::maps) fn build_archive(path: &str, contents: &[u8]) -> Vec<u8> {
+        const SECTOR_SIZE: usize = 0x200 << 3;
+        const HASH_TABLE_SIZE: u32 = 4;
+        const FILE_OFFSET: usize = HEADER_SIZE;
+
+        let num_sectors = contents.len().div_ceil(SECTOR_SIZE);
+        let file_name = path.rsplit('\\').next().unwrap();
+        let key = (hash_string(file_name, HASH_FILE_KEY).wrapping_add(FILE_OFFSET as u32))
+            ^ contents.len() as u32;
+
+        let mut sectors = Vec::new();
+        let mut offsets = vec![((num_sectors + 1) * 4) as u32];
+        for (i, chunk) in contents.chunks(SECTOR_SIZE).enumerate() {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+            encoder.write_all(chunk).unwrap();
+            let mut sector = vec![COMPRESSION_ZLIB];
+            sector.extend(encoder.finish().unwrap());
+            encrypt(&mut sector, key.wrapping_add(i as u32));
+            sectors.extend(sector);
+            offsets.push(offsets[0] + sectors.len() as u32);
+        }
+        let mut file_data = offsets
+            .iter()
+            .flat_map(|o| o.to_le_bytes())
+            .collect::<Vec<_>>();
+        encrypt(&mut file_data, key.wrapping_sub(1));
+        file_data.extend(sectors);
+
+        let hash_table_offset = FILE_OFFSET + file_data.len();
+        let mut hash_table = [[HASH_ENTRY_EMPTY; 4]; HASH_TABLE_SIZE as usize];
+        let index = (hash_string(path, HASH_TABLE_OFFSET) & (HASH_TABLE_SIZE - 1)) as usize;
+        // A decoy entry for the same file that points at a nonexistent block
+        hash_table[index] = [
+            hash_string(path, HASH_NAME_A),
+            hash_string(path, HASH_NAME_B),
+            0,
+            1,
+        ];
+        hash_table[(index + 1) % HASH_TABLE_SIZE as usize] = [
+            hash_string(path, HASH_NAME_A),
+            hash_string(path, HASH_NAME_B),
+            0,
+            0,
+        ];
+        let hash_table = table_bytes(&hash_table, hash_string("(hash table)", HASH_FILE_KEY));
+        let block_table_offset = hash_table_offset + hash_table.len();
+        let block_table = table_bytes(
+            &[[
+                FILE_OFFSET as u32,
+                file_data.len() as u32,
+                contents.len() as u32,
+                FILE_EXISTS | FILE_COMPRESS | FILE_ENCRYPTED | FILE_FIX_KEY,
+            ]],
+            hash_string("(block table)", HASH_FILE_KEY),
+        );
+
+        let mut archive = vec![0xAB; HEADER_ALIGNMENT];
+        archive.extend_from_slice(HEADER_MAGIC);
+        // Garbage header size and archive size, like a protected map would have
+        archive.extend_from_slice(&0xDEAD_BEEFu32.to_le_bytes());
+        archive.extend_from_slice(&0xDEAD_BEEFu32.to_le_bytes());
+        archive.extend_from_slice(&0u16.to_le_bytes());
+        archive.extend_from_slice(&3u16.to_le_bytes());
+        archive.extend_from_slice(&(hash_table_offset as u32).to_le_bytes());
+        archive.extend_from_slice(&(block_table_offset as u32).to_le_bytes());
+        archive.extend_from_slice(&HASH_TABLE_SIZE.to_le_bytes());
+        // An inflated block table size, which should be clamped to what's actually present
+        archive.extend_from_slice(&1000u32.to_le_bytes());
+        archive.extend(file_data);
+        archive.extend(hash_table);
+        archive.extend(block_table);
+        archive
+    }
+
+    #[test]
+    fn hashes_match_storm() {
+        assert_eq!(hash_string("(hash table)", HASH_FILE_KEY), 0xC3AF_3770);
+        assert_eq!(hash_string("(block table)", HASH_FILE_KEY), 0xEC83_B3A3);
+        assert_eq!(
+            hash_string("staredit\\scenario.chk", HASH_NAME_A),
+            hash_string("STAREDIT/Scenario.CHK", HASH_NAME_A)
+        );
+    }
+
+    #[test]
+    fn decrypt_reverses_encrypt() {
+        let original = (0..=255u8).collect::<Vec<_>>();
+        let mut data = original.clone();
+        encrypt(&mut data, 0x1234_5678);
+        assert_ne!(data, original);
+        decrypt(&mut data, 0x1234_5678);
+        assert_eq!(data, original);
+    }
+
+    #[test]
+    fn reads_compressed_encrypted_file() {
+        let contents = (0..10_000u32)
+            .flat_map(|i| (i % 251).to_le_bytes())
+            .collect::<Vec<_>>();
+        let archive = build_archive(SCENARIO_CHK_PATH, &contents);
+        let mpq = Mpq::new(&archive).unwrap();
+        assert_eq!(mpq.read_file(SCENARIO_CHK_PATH).unwrap(), contents);
+        assert!(matches!(
+            mpq.read_file("staredit\\missing.chk"),
+            Err(MpqError::FileNotFound)
+        ));
+    }
+
+    #[test]
+    fn rejects_non_archives() {
+        assert!(matches!(
+            Mpq::new(b"definitely not a map"),
+            Err(MpqError::NoHeader)
+        ));
+    }
+
+    /// "AIAIAIAIAIAIA" (13 bytes), imploded.
+    const IMPLODED: &[u8] = &[0x00, 0x04, 0x82, 0x24, 0x25, 0x8f, 0x80, 0x7f];
+
+    #[test]
+    fn imploded_units_are_exploded() {
+        assert_eq!(
+            decompress_unit(IMPLODED.to_vec(), 13, FILE_IMPLODE).unwrap(),
+            b"AIAIAIAIAIAIA"
+        );
+        let mut unit = vec![COMPRESSION_IMPLODE];
+        unit.extend_from_slice(IMPLODED);
+        assert_eq!(
+            decompress_unit(unit, 13, FILE_COMPRESS).unwrap(),
+            b"AIAIAIAIAIAIA"
+        );
+        assert!(matches!(
+            decompress_unit(IMPLODED[..6].to_vec(), 13, FILE_IMPLODE),
+            Err(MpqError::Explode(explode::Error::IncompleteInput))
+        ));
+    }
+
+    #[test]
+    fn exploded_output_is_capped_at_the_expected_size() {
+        assert!(matches!(
+            decompress_unit(IMPLODED.to_vec(), 12, FILE_IMPLODE),
+            Err(MpqError::TooLarge)
+        ));
+        let mut unit = vec![COMPRESSION_IMPLODE];
+        unit.extend_from_slice(IMPLODED);
+        assert!(matches!(
+            decompress_unit(unit, 10, FILE_COMPRESS),
+            Err(MpqError::TooLarge)
+        ));
+    }
+}
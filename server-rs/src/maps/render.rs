@@ -0,0 +1,437 @@
+//! Rendering of map preview images from a map's terrain, using the tileset graphics from
+//! StarCraft's data files (the `tileset/` directory extracted from its MPQs, see "Set up map
+//! system" in `docs/GETTING_STARTED.md`).
+//!
+//! Terrain is drawn as it appears in game, but units and doodad sprites aren't: only the things
+//! that matter when picking a map (start locations and resources) are drawn, as simple markers.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use image::{ImageError, Rgb, RgbImage, codecs::jpeg::JpegEncoder, imageops::FilterType};
+use thiserror::Error;
+
+use super::chk::{ParsedChk, PlacedUnit, UNIT_START_LOCATION};
+
+/// The current version of the map renderer. Increase this any time the renderer changes in a way
+/// that should be reflected in existing maps' images, and
+/// [`map_image_render_loop`](super::store::map_image_render_loop) will re-render them. Version 1
+/// was the Node server's renderer.
+pub const MAP_RENDERER_VERSION: i32 = 2;
+
+/// The widths of the images rendered for each map. Heights follow the map's aspect ratio.
+pub const MAP_IMAGE_SIZES: [u32; 4] = [256, 512, 1024, 2048];
+const JPEG_QUALITY: u8 = 90;
+
+/// File names (without extension) of the tilesets in the data files, indexed by tileset ID.
+const TILESET_NAMES: [&str; 8] = [
+    "badlands", "platform", "install", "AshWorld", "Jungle", "Desert", "Ice", "Twilight",
+];
+
+/// The size of a tile (a "megatile") in game, in pixels.
+const TILE_SIZE: u32 = 32;
+/// Tiles are made up of 4x4 minitiles, each 8x8 pixels.
+const MINITILES_PER_TILE: usize = 16;
+const MINITILE_SIZE: u32 = 8;
+const MINITILE_DATA_SIZE: usize = (MINITILE_SIZE * MINITILE_SIZE) as usize;
+/// Each CV5 entry describes a group of 16 tiles, whose megatile IDs start at this offset.
+const CV5_ENTRY_SIZE: usize = 52;
+const CV5_MEGATILES_OFFSET: usize = 20;
+
+const UNIT_MINERAL_FIELDS: [u16; 3] = [176, 177, 178];
+const UNIT_VESPENE_GEYSER: u16 = 188;
+const MINERAL_COLOR: Rgb<u8> = Rgb([72, 188, 252]);
+const GEYSER_COLOR: Rgb<u8> = Rgb([40, 220, 96]);
+/// The in-game colors of players 1-8.
+const PLAYER_COLORS: [Rgb<u8>; 8] = [
+    Rgb([244, 4, 4]),
+    Rgb([12, 72, 204]),
+    Rgb([44, 180, 148]),
+    Rgb([136, 64, 156]),
+    Rgb([248, 140, 20]),
+    Rgb([112, 48, 20]),
+    Rgb([204, 224, 208]),
+    Rgb([252, 252, 56]),
+];
+
+#[derive(Debug, Error)]
+pub enum RenderError {
+    #[error("unknown tileset: {0}")]
+    UnknownTileset(u16),
+    #[error("failed to load tileset {name}")]
+    LoadTileset {
+        name: &'static str,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("failed to encode map image")]
+    Encode(#[from] ImageError),
+}
+
+/// The graphics for one tileset.
+pub struct Tileset {
+    /// The megatile IDs of each tile group.
+    groups: Vec<[u16; MINITILES_PER_TILE]>,
+    /// The minitiles that make up each megatile. The low bit flips the minitile horizontally, and
+    /// the rest is an index into `minitiles`.
+    megatiles: Vec<[u32; MINITILES_PER_TILE]>,
+    /// Palette indexes of each minitile's pixels.
+    minitiles: Vec<u8>,
+    palette: [Rgb<u8>; 256],
+}
+
+impl Tileset {
+    /// Creates a tileset from the contents of its data files. `extended_vx4` specifies whether
+    /// `vx4` came from a `.vx4ex` file (which uses 32-bit minitile references) rather than `.vx4`.
+    pub fn from_data(cv5: &[u8], vx4: &[u8], extended_vx4: bool, vr4: Vec<u8>, wpe: &[u8]) -> Self {
+        let groups = cv5
+            .chunks_exact(CV5_ENTRY_SIZE)
+            .map(|entry| {
+                std::array::from_fn(|i| {
+                    let offset = CV5_MEGATILES_OFFSET + i * 2;
+                    u16::from_le_bytes([entry[offset], entry[offset + 1]])
+                })
+            })
+            .collect();
+        let megatiles = if extended_vx4 {
+            vx4.chunks_exact(MINITILES_PER_TILE * 4)
+                .map(|m| {
+                    std::array::from_fn(|i| {
+                        u32::from_le_bytes(m[i * 4..i * 4 + 4].try_into().unwrap())
+                    })
+                })
+                .collect()
+        } else {
+            vx4.chunks_exact(MINITILES_PER_TILE * 2)
+                .map(|m| {
+                    std::array::from_fn(|i| u16::from_le_bytes([m[i * 2], m[i * 2 + 1]]) as u32)
+                })
+                .collect()
+        };
+        let mut palette = [Rgb([0, 0, 0]); 256];
+        for (color, entry) in palette.iter_mut().zip(wpe.chunks_exact(4)) {
+            *color = Rgb([entry[0], entry[1], entry[2]]);
+        }
+
+        Self {
+            groups,
+            megatiles,
+            minitiles: vr4,
+            palette,
+        }
+    }
+
+    fn load(data_path: &Path, name: &'static str) -> Result<Self, RenderError> {
+        let read = |extension: &str| {
+            std::fs::read(
+                data_path
+                    .join("tileset")
+                    .join(format!("{name}.{extension}")),
+            )
+            .map_err(|source| RenderError::LoadTileset { name, source })
+        };
+        // Remastered data has extended megatile data, which can reference more minitiles
+        let (vx4, extended_vx4) = match read("vx4ex") {
+            Ok(vx4) => (vx4, true),
+            Err(_) => (read("vx4")?, false),
+        };
+        Ok(Self::from_data(
+            &read("cv5")?,
+            &vx4,
+            extended_vx4,
+            read("vr4")?,
+            &read("wpe")?,
+        ))
+    }
+
+    /// Returns the pixel at (`x`, `y`) within the tile with ID `tile`. Tiles that don't exist in
+    /// the tileset are black, as they are in game.
+    fn tile_pixel(&self, tile: u16, x: u32, y: u32) -> Rgb<u8> {
+        let black = Rgb([0, 0, 0]);
+        let Some(megatile) = self
+            .groups
+            .get((tile >> 4) as usize)
+            .and_then(|g| self.megatiles.get(g[(tile & 0xF) as usize] as usize))
+        else {
+            return black;
+        };
+        let minitile = megatile[((y / MINITILE_SIZE) * 4 + x / MINITILE_SIZE) as usize];
+        let (x, y) = (x % MINITILE_SIZE, y % MINITILE_SIZE);
+        let x = if minitile & 1 != 0 {
+            MINITILE_SIZE - 1 - x
+        } else {
+            x
+        };
+        let offset =
+            (minitile >> 1) as usize * MINITILE_DATA_SIZE + (y * MINITILE_SIZE + x) as usize;
+        self.minitiles
+            .get(offset)
+            .map(|&index| self.palette[index as usize])
+            .unwrap_or(black)
+    }
+
+    /// Renders a tile scaled down to `size`x`size` pixels (`size` must evenly divide
+    /// [`TILE_SIZE`]), averaging the pixels that make up each scaled pixel.
+    fn render_tile(&self, tile: u16, size: u32) -> Vec<Rgb<u8>> {
+        let scale = TILE_SIZE / size;
+        let mut pixels = Vec::with_capacity((size * size) as usize);
+        for y in 0..size {
+            for x in 0..size {
+                let mut sum = [0u32; 3];
+                for sy in 0..scale {
+                    for sx in 0..scale {
+                        let Rgb(p) = self.tile_pixel(tile, x * scale + sx, y * scale + sy);
+                        for (s, c) in sum.iter_mut().zip(p) {
+                            *s += c as u32;
+                        }
+                    }
+                }
+                pixels.push(Rgb(sum.map(|s| (s / (scale * scale)) as u8)));
+            }
+        }
+        pixels
+    }
+}
+
+/// An encoded preview image of a map.
+#[derive(Debug, Clone)]
+pub struct MapImage {
+    /// The image's width, one of [`MAP_IMAGE_SIZES`].
+    pub size: u32,
+    /// The JPEG-encoded image.
+    pub data: Vec<u8>,
+}
+
+/// Renders map preview images, loading (and caching) tilesets from StarCraft's data files as
+/// they're needed.
+pub struct MapRenderer {
+    data_path: PathBuf,
+    tilesets: Mutex<HashMap<u16, Arc<Tileset>>>,
+}
+
+impl MapRenderer {
+    pub fn new(data_path: PathBuf) -> Self {
+        Self {
+            data_path,
+            tilesets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn tileset(&self, id: u16) -> Result<Arc<Tileset>, RenderError> {
+        if let Some(tileset) = self.tilesets.lock().unwrap().get(&id) {
+            return Ok(tileset.clone());
+        }
+        let name = *TILESET_NAMES
+            .get(id as usize)
+            .ok_or(RenderError::UnknownTileset(id))?;
+        // Loading is done without holding the lock. Two renders may both load a tileset the first
+        // time it's needed, but that's cheaper than blocking every other render on it.
+        let tileset = Arc::new(Tileset::load(&self.data_path, name)?);
+        self.tilesets.lock().unwrap().insert(id, tileset.clone());
+        Ok(tileset)
+    }
+
+    /// Renders a map's preview image at each of [`MAP_IMAGE_SIZES`]. This is CPU-bound (and reads
+    /// tileset files the first time they're used), so it should be run off of the async runtime.
+    pub fn render(&self, map: &ParsedChk) -> Result<Vec<MapImage>, RenderError> {
+        let tileset = self.tileset(map.tileset)?;
+        render_map_images(map, &tileset)
+    }
+}
+
+pub fn render_map_images(map: &ParsedChk, tileset: &Tileset) -> Result<Vec<MapImage>, RenderError> {
+    let largest = MAP_IMAGE_SIZES.iter().copied().max().unwrap();
+    // Render at the smallest power of 2 tile size that gives at least as many pixels as the
+    // largest image needs, so everything below full resolution is a simple box filter
+    let tile_size = (largest.div_ceil(map.width as u32))
+        .next_power_of_two()
+        .min(TILE_SIZE);
+    let mut full = render_terrain(map, tileset, tile_size);
+    draw_markers(&mut full, &map.units, tile_size);
+
+    MAP_IMAGE_SIZES
+        .iter()
+        .map(|&size| {
+            let height = ((size as u64 * map.height as u64 + map.width as u64 / 2)
+                / map.width as u64)
+                .max(1) as u32;
+            let image = image::imageops::resize(&full, size, height, FilterType::Triangle);
+            let mut data = Vec::new();
+            JpegEncoder::new_with_quality(Cursor::new(&mut data), JPEG_QUALITY)
+                .encode_image(&image)?;
+            Ok(MapImage { size, data })
+        })
+        .collect()
+}
+
+/// Renders a map's terrain with each tile scaled to `tile_size` pixels.
+fn render_terrain(map: &ParsedChk, tileset: &Tileset, tile_size: u32) -> RgbImage {
+    let mut image = RgbImage::new(map.width as u32 * tile_size, map.height as u32 * tile_size);
+    let mut rendered_tiles = HashMap::new();
+    for (i, &tile) in map.tiles.iter().enumerate() {
+        let pixels = rendered_tiles
+            .entry(tile)
+            .or_insert_with(|| tileset.render_tile(tile, tile_size));
+        let tile_x = (i % map.width as usize) as u32 * tile_size;
+        let tile_y = (i / map.width as usize) as u32 * tile_size;
+        for (j, &pixel) in pixels.iter().enumerate() {
+            let j = j as u32;
+            image.put_pixel(tile_x + j % tile_size, tile_y + j / tile_size, pixel);
+        }
+    }
+    image
+}
+
+/// Draws markers for start locations and resources onto a rendered map.
+fn draw_markers(image: &mut RgbImage, units: &[PlacedUnit], tile_size: u32) {
+    let scale = tile_size as f32 / TILE_SIZE as f32;
+    // Start locations go on top, since they're what players look for first
+    let resources = units.iter().filter_map(|u| {
+        if UNIT_MINERAL_FIELDS.contains(&u.unit_id) {
+            Some((u, (64, 32), MINERAL_COLOR))
+        } else if u.unit_id == UNIT_VESPENE_GEYSER {
+            Some((u, (128, 64), GEYSER_COLOR))
+        } else {
+            None
+        }
+    });
+    let start_locations = units.iter().filter_map(|u| {
+        (u.unit_id == UNIT_START_LOCATION)
+            .then(|| PLAYER_COLORS.get(u.owner as usize))
+            .flatten()
+            .map(|&color| (u, (128, 96), color))
+    });
+
+    for (unit, (width, height), color) in resources.chain(start_locations) {
+        let left = ((unit.x as f32 - width as f32 / 2.0) * scale)
+            .round()
+            .max(0.0) as u32;
+        let top = ((unit.y as f32 - height as f32 / 2.0) * scale)
+            .round()
+            .max(0.0) as u32;
+        let right = (((unit.x as f32 + width as f32 / 2.0) * scale).round() as u32)
+            .min(image.width())
+            .max(left + 1);
+        let bottom = (((unit.y as f32 + height as f32 / 2.0) * scale).round() as u32)
+            .min(image.height())
+            .max(top + 1);
+        for y in top..bottom.min(image.height()) {
+            for x in left..right.min(image.width()) {
+                image.put_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRASS: Rgb<u8> = Rgb([20, 120, 20]);
+    const DIRT: Rgb<u8> = Rgb([120, 80, 40]);
+
+    /// A tileset with three tiles: tile 0 is grass, tile 1 is dirt on its left half and grass on
+    /// its right half, and tile 2 repeats a half dirt/half grass minitile, flipped horizontally.
+    fn test_tileset() -> Tileset {
+        let mut cv5 = vec![0; CV5_ENTRY_SIZE];
+        for megatile in [1u16, 2] {
+            let offset = CV5_MEGATILES_OFFSET + megatile as usize * 2;
+            cv5[offset..offset + 2].copy_from_slice(&megatile.to_le_bytes());
+        }
+
+        // Minitile 0 is all grass, minitile 1 is dirt on its left half, minitile 2 is all dirt
+        let mut vr4 = vec![0; MINITILE_DATA_SIZE * 3];
+        for y in 0..8 {
+            for x in 0..4 {
+                vr4[MINITILE_DATA_SIZE + y * 8 + x] = 1;
+            }
+        }
+        vr4[MINITILE_DATA_SIZE * 2..].fill(1);
+
+        let megatiles: [[u16; 16]; 3] =
+            [[0; 16], [4, 4, 0, 0].repeat(4).try_into().unwrap(), [3; 16]];
+        let vx4 = megatiles
+            .iter()
+            .flatten()
+            .flat_map(|m| m.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        let mut wpe = vec![0; 256 * 4];
+        wpe[..3].copy_from_slice(&GRASS.0);
+        wpe[4..7].copy_from_slice(&DIRT.0);
+        Tileset::from_data(&cv5, &vx4, false, vr4, &wpe)
+    }
+
+    fn test_map(width: u16, height: u16, tiles: Vec<u16>, units: Vec<PlacedUnit>) -> ParsedChk {
+        ParsedChk {
+            title: String::new(),
+            description: String::new(),
+            width,
+            height,
+            tileset: 0,
+            melee_players: 0,
+            ums_players: 0,
+            is_eud: false,
+            forces: Vec::new(),
+            tiles,
+            units,
+        }
+    }
+
+    #[test]
+    fn renders_tiles_from_tileset() {
+        let tileset = test_tileset();
+        assert_eq!(tileset.tile_pixel(0, 0, 0), GRASS);
+        assert_eq!(tileset.tile_pixel(1, 0, 5), DIRT);
+        assert_eq!(tileset.tile_pixel(1, 12, 31), DIRT);
+        assert_eq!(tileset.tile_pixel(1, 16, 0), GRASS);
+        assert_eq!(tileset.tile_pixel(2, 0, 0), GRASS);
+        assert_eq!(tileset.tile_pixel(2, 7, 0), DIRT);
+        // Tiles outside of the tileset are black
+        assert_eq!(tileset.tile_pixel(0x50, 0, 0), Rgb([0, 0, 0]));
+
+        let scaled = tileset.render_tile(1, 2);
+        assert_eq!(scaled, vec![DIRT, GRASS, DIRT, GRASS]);
+    }
+
+    #[test]
+    fn draws_markers_over_terrain() {
+        let units = vec![
+            PlacedUnit {
+                unit_id: UNIT_START_LOCATION,
+                x: 64,
+                y: 48,
+                owner: 1,
+            },
+            PlacedUnit {
+                unit_id: UNIT_MINERAL_FIELDS[0],
+                x: 200,
+                y: 200,
+                owner: 11,
+            },
+        ];
+        let map = test_map(8, 8, vec![1; 8 * 8], units);
+
+        let mut image = render_terrain(&map, &test_tileset(), 32);
+        draw_markers(&mut image, &map.units, 32);
+        assert_eq!(image.dimensions(), (256, 256));
+        assert_eq!(*image.get_pixel(10, 10), PLAYER_COLORS[1]);
+        assert_eq!(*image.get_pixel(200, 200), MINERAL_COLOR);
+        assert_eq!(*image.get_pixel(140, 140), DIRT);
+        assert_eq!(*image.get_pixel(156, 140), GRASS);
+    }
+
+    #[test]
+    fn renders_every_size() {
+        let map = test_map(256, 128, vec![0; 256 * 128], Vec::new());
+        let images = render_map_images(&map, &test_tileset()).unwrap();
+        assert_eq!(
+            images.iter().map(|i| i.size).collect::<Vec<_>>(),
+            MAP_IMAGE_SIZES
+        );
+        let small = image::load_from_memory(&images[0].data).unwrap();
+        assert_eq!((small.width(), small.height()), (256, 128));
+    }
+}
@@ -0,0 +1,579 @@
+//! Searching uploaded maps, plus the per-user favorites and curator-applied tags that searches can
+//! be filtered by.
+
+use std::collections::HashMap;
+
+use async_graphql::connection::{Connection, Edge, query};
+use async_graphql::dataloader::Loader;
+use async_graphql::{Context, Enum, InputObject, Object, Result};
+use color_eyre::eyre::{self, WrapErr};
+use sqlx::{PgPool, QueryBuilder};
+use uuid::Uuid;
+
+use super::{MapVisibility, SbMapId, UploadedMap};
+use crate::graphql::errors::graphql_error;
+use crate::graphql::limits::page_complexity;
+use crate::users::permissions::RequiredPermission;
+use crate::users::{CurrentUser, SbUserId};
+
+/// The longest a map tag can be, in characters.
+const MAX_TAG_LENGTH: usize = 32;
+/// The most tags a single map can have.
+const MAX_TAGS_PER_MAP: usize = 10;
+
+#[derive(Enum, Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum MapSortOrder {
+    /// Alphabetical by name.
+    #[default]
+    Name,
+    /// Most recently uploaded first.
+    UploadDate,
+}
+
+impl MapSortOrder {
+    /// The columns a search is ordered by, which its cursors compare against.
+    fn columns(self) -> &'static str {
+        match self {
+            MapSortOrder::Name => "name, id",
+            MapSortOrder::UploadDate => "upload_date, id",
+        }
+    }
+
+    /// Whether the order is ascending when paging forward.
+    fn ascending(self) -> bool {
+        match self {
+            MapSortOrder::Name => true,
+            MapSortOrder::UploadDate => false,
+        }
+    }
+}
+
+#[derive(InputObject, Debug, Clone, Default)]
+pub struct MapSearchFilter {
+    /// Text to search for in map names and descriptions.
+    pub query: Option<String>,
+    /// Only include maps with this visibility. Private maps are only ever the current user's own.
+    /// If unset, includes all public and official maps, as well as the current user's private maps.
+    pub visibility: Option<MapVisibility>,
+    /// Only include maps using one of these tilesets.
+    pub tilesets: Option<Vec<i32>>,
+    /// Only include maps for one of these numbers of players. Uses the number of UMS slots if the
+    /// map has any, and the number of start locations otherwise.
+    pub num_players: Option<Vec<i32>>,
+    /// Only include maps whose width and height are both at least this large.
+    pub min_size: Option<i32>,
+    /// Only include maps whose width and height are both at most this large.
+    pub max_size: Option<i32>,
+    /// Only include EUD maps (if `true`) or non-EUD maps (if `false`).
+    pub is_eud: Option<bool>,
+    /// Only include maps the current user has favorited. This can include maps that their uploader
+    /// has since removed.
+    pub favorited: Option<bool>,
+    /// Only include maps that have all of these tags.
+    pub tags: Option<Vec<String>>,
+}
+
+/// Escapes the characters that have special meaning in a `LIKE` pattern.
+fn escape_like(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Normalizes a tag into the form it's stored in (lowercase, with words separated by `-`), or
+/// returns `None` if it isn't a valid tag.
+fn normalize_tag(tag: &str) -> Option<String> {
+    let normalized = tag
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase();
+    let valid = !normalized.is_empty()
+        && normalized.chars().count() <= MAX_TAG_LENGTH
+        && normalized.chars().all(|c| c.is_alphanumeric() || c == '-');
+    valid.then_some(normalized)
+}
+
+struct MapSearch {
+    filter: MapSearchFilter,
+    sort: MapSortOrder,
+    current_user: Option<SbUserId>,
+}
+
+impl MapSearch {
+    /// Adds the filter's conditions to a query that selects from `uploaded_maps um` joined with
+    /// `maps m`, after a `WHERE`.
+    fn push_conditions(&self, builder: &mut QueryBuilder<sqlx::Postgres>) -> Result<()> {
+        let filter = &self.filter;
+        if filter.favorited == Some(true) {
+            let Some(user_id) = self.current_user else {
+                return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+            };
+            builder.push(
+                " AND EXISTS(SELECT 1 FROM favorited_maps fm WHERE fm.map_id = um.id AND \
+                fm.favorited_by = ",
+            );
+            builder.push_bind(user_id.0);
+            builder.push(")");
+        } else {
+            builder.push(" AND um.removed_at IS NULL");
+            if filter.favorited == Some(false)
+                && let Some(user_id) = self.current_user
+            {
+                builder.push(
+                    " AND NOT EXISTS(SELECT 1 FROM favorited_maps fm WHERE fm.map_id = um.id AND \
+                    fm.favorited_by = ",
+                );
+                builder.push_bind(user_id.0);
+                builder.push(")");
+            }
+        }
+
+        match (filter.visibility, self.current_user) {
+            (Some(MapVisibility::Private), Some(user_id)) => {
+                builder.push(" AND um.visibility = 'PRIVATE' AND um.uploaded_by = ");
+                builder.push_bind(user_id.0);
+            }
+            (Some(MapVisibility::Private), None) => {
+                return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+            }
+            (Some(visibility), _) => {
+                builder.push(" AND um.visibility = ");
+                builder.push_bind(visibility);
+            }
+            (None, Some(user_id)) => {
+                builder.push(" AND (um.visibility <> 'PRIVATE' OR um.uploaded_by = ");
+                builder.push_bind(user_id.0);
+                builder.push(")");
+            }
+            (None, None) => {
+                builder.push(" AND um.visibility <> 'PRIVATE'");
+            }
+        }
+
+        if let Some(query) = filter.query.as_deref().map(str::trim)
+            && !query.is_empty()
+        {
+            let pattern = format!("%{}%", escape_like(query));
+            builder.push(" AND (um.name ILIKE ");
+            builder.push_bind(pattern.clone());
+            builder.push(" OR um.description ILIKE ");
+            builder.push_bind(pattern);
+            builder.push(")");
+        }
+        if let Some(tilesets) = &filter.tilesets {
+            builder.push(" AND m.tileset = ANY(");
+            builder.push_bind(tilesets.clone());
+            builder.push(")");
+        }
+        if let Some(num_players) = &filter.num_players {
+            // Some maps (ICCup's Hannibal, for example) have no UMS slots, so their start locations
+            // are used instead
+            builder.push(" AND (m.players_ums = ANY(");
+            builder.push_bind(num_players.clone());
+            builder.push(") OR m.players_ums = 0 AND m.players_melee = ANY(");
+            builder.push_bind(num_players.clone());
+            builder.push("))");
+        }
+        if let Some(min_size) = filter.min_size {
+            builder.push(" AND LEAST(m.width, m.height) >= ");
+            builder.push_bind(min_size);
+        }
+        if let Some(max_size) = filter.max_size {
+            builder.push(" AND GREATEST(m.width, m.height) <= ");
+            builder.push_bind(max_size);
+        }
+        if let Some(is_eud) = filter.is_eud {
+            builder.push(" AND m.is_eud = ");
+            builder.push_bind(is_eud);
+        }
+        if let Some(tags) = &filter.tags {
+            let tags = tags
+                .iter()
+                .map(|tag| normalize_tag(tag).ok_or_else(|| invalid_tag_error(tag)))
+                .collect::<Result<Vec<_>>>()?;
+            if !tags.is_empty() {
+                builder.push(
+                    " AND (SELECT count(*) FROM uploaded_map_tags t WHERE t.map_id = um.id AND \
+                    t.tag = ANY(",
+                );
+                builder.push_bind(tags.clone());
+                builder.push(")) = ");
+                builder.push_bind(tags.len() as i64);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Loads a page of `count` results. `after` and `before` are cursors (map IDs), and `inverted`
+    /// loads the page nearest to `before` (i.e. for `last`). Returns whether there are previous and
+    /// next pages, along with the maps.
+    async fn load_page(
+        &self,
+        db: &PgPool,
+        after: Option<Uuid>,
+        before: Option<Uuid>,
+        count: usize,
+        inverted: bool,
+    ) -> Result<(bool, bool, Vec<UploadedMap>)> {
+        let mut builder = QueryBuilder::new(
+            r#"
+                SELECT um.id, um.map_hash, um.name, um.description, um.uploaded_by, um.upload_date,
+                    um.visibility
+                FROM uploaded_maps um
+                JOIN maps m ON m.hash = um.map_hash
+                WHERE true
+            "#,
+        );
+        self.push_conditions(&mut builder)?;
+
+        // Keyset cursors, compared as tuples so that equal names/dates still page deterministically
+        let columns = self.sort.columns();
+        let (after_op, before_op) = if self.sort.ascending() {
+            (">", "<")
+        } else {
+            ("<", ">")
+        };
+        for (cursor, op) in [(after, after_op), (before, before_op)] {
+            if let Some(cursor) = cursor {
+                builder.push(format!(
+                    " AND (um.{}) {op} (SELECT {columns} FROM uploaded_maps WHERE id = ",
+                    columns.replace(", ", ", um.")
+                ));
+                builder.push_bind(cursor);
+                builder.push(")");
+            }
+        }
+
+        // For backward pagination fetch the window nearest the cursor, then re-sort below
+        let direction = if self.sort.ascending() != inverted {
+            "ASC"
+        } else {
+            "DESC"
+        };
+        let order = columns
+            .split(", ")
+            .map(|column| format!("um.{column} {direction}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        builder.push(format!(" ORDER BY {order} LIMIT "));
+        builder.push_bind(count as i64 + 1);
+
+        let mut maps: Vec<UploadedMap> = builder
+            .build_query_as()
+            .fetch_all(db)
+            .await
+            .wrap_err("Failed to search maps")?;
+
+        // The extra (count + 1)th row tells us there are more in the fetch direction
+        let has_extra = maps.len() > count;
+        maps.truncate(count);
+        if inverted {
+            maps.reverse();
+        }
+
+        let (has_prev_page, has_next_page) = if inverted {
+            (has_extra, before.is_some())
+        } else {
+            (after.is_some(), has_extra)
+        };
+        Ok((has_prev_page, has_next_page, maps))
+    }
+}
+
+fn invalid_tag_error(tag: &str) -> async_graphql::Error {
+    graphql_error("BAD_REQUEST", format!("Invalid tag: {tag}"))
+}
+
+fn current_user_id(ctx: &Context<'_>) -> Result<Option<SbUserId>> {
+    Ok(ctx.data::<Option<CurrentUser>>()?.as_ref().map(|u| u.id))
+}
+
+async fn load_uploaded_map(db: &PgPool, id: SbMapId) -> eyre::Result<Option<UploadedMap>> {
+    sqlx::query_as!(
+        UploadedMap,
+        r#"
+            SELECT id as "id: _", map_hash as "map_hash: _", name, description,
+                uploaded_by as "uploaded_by: _", upload_date, visibility as "visibility: _"
+            FROM uploaded_maps
+            WHERE id = $1
+        "#,
+        id as _,
+    )
+    .fetch_optional(db)
+    .await
+    .wrap_err("Failed to load map")
+}
+
+#[derive(Default)]
+pub struct MapsQuery;
+
+#[Object]
+impl MapsQuery {
+    /// Searches the maps visible to the current user.
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(complexity = "page_complexity(first.or(last), 30, child_complexity)")]
+    async fn maps(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<MapSearchFilter>,
+        sort: Option<MapSortOrder>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Connection<Uuid, UploadedMap>> {
+        let db = ctx.data::<PgPool>()?;
+        let search = MapSearch {
+            filter: filter.unwrap_or_default(),
+            sort: sort.unwrap_or_default(),
+            current_user: current_user_id(ctx)?,
+        };
+
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after, before, first, last| async move {
+                let first = first.map(|f| f.clamp(1, 100));
+                let last = last.map(|l| l.clamp(1, 100));
+                let count = first.or(last).unwrap_or(30);
+
+                let (has_prev_page, has_next_page, maps) = search
+                    .load_page(db, after, before, count, last.is_some() && first.is_none())
+                    .await?;
+
+                let mut connection = Connection::new(has_prev_page, has_next_page);
+                connection
+                    .edges
+                    .extend(maps.into_iter().map(|m| Edge::new(m.id.0, m)));
+                Ok::<_, async_graphql::Error>(connection)
+            },
+        )
+        .await
+    }
+
+    /// All of the tags that have been applied to maps, alphabetically.
+    async fn map_tags(&self, ctx: &Context<'_>) -> Result<Vec<String>> {
+        let db = ctx.data::<PgPool>()?;
+        Ok(
+            sqlx::query_scalar!(r#"SELECT DISTINCT tag FROM uploaded_map_tags ORDER BY tag"#)
+                .fetch_all(db)
+                .await
+                .wrap_err("Failed to load map tags")?,
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct MapsMutation;
+
+#[Object]
+impl MapsMutation {
+    /// Adds a map to the current user's favorites.
+    async fn favorite_map(&self, ctx: &Context<'_>, id: SbMapId) -> Result<UploadedMap> {
+        let Some(user_id) = current_user_id(ctx)? else {
+            return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+        };
+        let db = ctx.data::<PgPool>()?;
+        let map = load_uploaded_map(db, id)
+            .await?
+            .filter(|m| m.visibility != MapVisibility::Private || m.uploaded_by == user_id)
+            .ok_or_else(|| graphql_error("NOT_FOUND", "Map not found"))?;
+
+        sqlx::query!(
+            r#"
+                INSERT INTO favorited_maps (map_id, favorited_by, favorited_date)
+                VALUES ($1, $2, CURRENT_TIMESTAMP AT TIME ZONE 'UTC')
+                ON CONFLICT DO NOTHING
+            "#,
+            id as _,
+            user_id as _,
+        )
+        .execute(db)
+        .await
+        .wrap_err("Failed to favorite map")?;
+
+        Ok(map)
+    }
+
+    /// Removes a map from the current user's favorites.
+    async fn unfavorite_map(&self, ctx: &Context<'_>, id: SbMapId) -> Result<UploadedMap> {
+        let Some(user_id) = current_user_id(ctx)? else {
+            return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+        };
+        let db = ctx.data::<PgPool>()?;
+        // Removed maps can still be unfavorited, so this doesn't check visibility
+        let map = load_uploaded_map(db, id)
+            .await?
+            .ok_or_else(|| graphql_error("NOT_FOUND", "Map not found"))?;
+
+        sqlx::query!(
+            "DELETE FROM favorited_maps WHERE map_id = $1 AND favorited_by = $2",
+            id as _,
+            user_id as _,
+        )
+        .execute(db)
+        .await
+        .wrap_err("Failed to unfavorite map")?;
+
+        Ok(map)
+    }
+
+    /// Replaces the tags on a map. Tags are normalized to lowercase, with words separated by `-`.
+    #[graphql(guard = RequiredPermission::ManageMaps)]
+    async fn set_map_tags(
+        &self,
+        ctx: &Context<'_>,
+        id: SbMapId,
+        tags: Vec<String>,
+    ) -> Result<UploadedMap> {
+        let Some(user_id) = current_user_id(ctx)? else {
+            return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+        };
+        let mut normalized = tags
+            .iter()
+            .map(|tag| normalize_tag(tag).ok_or_else(|| invalid_tag_error(tag)))
+            .collect::<Result<Vec<_>>>()?;
+        normalized.sort();
+        normalized.dedup();
+        if normalized.len() > MAX_TAGS_PER_MAP {
+            return Err(graphql_error(
+                "BAD_REQUEST",
+                format!("Maps can have at most {MAX_TAGS_PER_MAP} tags"),
+            ));
+        }
+
+        let db = ctx.data::<PgPool>()?;
+        let map = load_uploaded_map(db, id)
+            .await?
+            .ok_or_else(|| graphql_error("NOT_FOUND", "Map not found"))?;
+
+        // Tags that are kept retain who originally applied them
+        let mut tx = db.begin().await?;
+        sqlx::query!(
+            "DELETE FROM uploaded_map_tags WHERE map_id = $1 AND NOT (tag = ANY($2))",
+            id as _,
+            &normalized,
+        )
+        .execute(&mut *tx)
+        .await
+        .wrap_err("Failed to remove map tags")?;
+        sqlx::query!(
+            r#"
+                INSERT INTO uploaded_map_tags (map_id, tag, tagged_by)
+                SELECT $1, tag, $3
+                FROM UNNEST($2::text[]) AS tag
+                ON CONFLICT DO NOTHING
+            "#,
+            id as _,
+            &normalized,
+            user_id as _,
+        )
+        .execute(&mut *tx)
+        .await
+        .wrap_err("Failed to add map tags")?;
+        tx.commit().await?;
+
+        Ok(map)
+    }
+}
+
+/// Loads the tags applied to maps, sorted alphabetically.
+pub struct MapTagsLoader {
+    db: PgPool,
+}
+
+impl MapTagsLoader {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+impl Loader<SbMapId> for MapTagsLoader {
+    type Value = Vec<String>;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[SbMapId]) -> Result<HashMap<SbMapId, Self::Value>> {
+        let rows = sqlx::query!(
+            r#"
+                SELECT map_id as "map_id: SbMapId", array_agg(tag ORDER BY tag) as "tags!"
+                FROM uploaded_map_tags
+                WHERE map_id = ANY($1)
+                GROUP BY map_id
+            "#,
+            keys as _,
+        )
+        .fetch_all(&self.db)
+        .await?;
+        Ok(rows.into_iter().map(|r| (r.map_id, r.tags)).collect())
+    }
+}
+
+/// Loads whether users have favorited maps, keyed by `(user, map)`. Only favorited pairs are
+/// present in the result.
+pub struct MapFavoritesLoader {
+    db: PgPool,
+}
+
+impl MapFavoritesLoader {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+impl Loader<(SbUserId, SbMapId)> for MapFavoritesLoader {
+    type Value = ();
+    type Error = async_graphql::Error;
+
+    async fn load(
+        &self,
+        keys: &[(SbUserId, SbMapId)],
+    ) -> Result<HashMap<(SbUserId, SbMapId), Self::Value>> {
+        let (users, maps): (Vec<_>, Vec<_>) = keys.iter().map(|(u, m)| (u.0, m.0)).unzip();
+        let rows = sqlx::query!(
+            r#"
+                SELECT fm.favorited_by as "favorited_by: SbUserId", fm.map_id as "map_id: SbMapId"
+                FROM favorited_maps fm
+                JOIN UNNEST($1::integer[], $2::uuid[]) AS k(user_id, map_id)
+                    ON fm.favorited_by = k.user_id AND fm.map_id = k.map_id
+            "#,
+            &users,
+            &maps,
+        )
+        .fetch_all(&self.db)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| ((r.favorited_by, r.map_id), ()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_like_patterns() {
+        assert_eq!(escape_like("Fighting Spirit"), "Fighting Spirit");
+        assert_eq!(escape_like(r"100%_\"), r"100\%\_\\");
+    }
+
+    #[test]
+    fn normalizes_tags() {
+        assert_eq!(normalize_tag("  Island "), Some("island".to_string()));
+        assert_eq!(normalize_tag("Team  Melee"), Some("team-melee".to_string()));
+        assert_eq!(normalize_tag("ASL-2024"), Some("asl-2024".to_string()));
+        assert_eq!(normalize_tag("   "), None);
+        assert_eq!(normalize_tag("no_underscores"), None);
+        assert_eq!(normalize_tag(&"a".repeat(MAX_TAG_LENGTH + 1)), None);
+    }
+}
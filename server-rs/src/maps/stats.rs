@@ -0,0 +1,716 @@
+//! Per-map balance statistics for matchmaking: win rates per matchup and per start location, and
+//! average game lengths. These are too expensive to aggregate from `games`/`games_users` on every
+//! request, so [`map_stats_loop`] periodically recomputes them per season into their own tables.
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use async_graphql::dataloader::Loader;
+use async_graphql::{ComplexObject, SimpleObject};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{self, WrapErr};
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use super::SbMapId;
+use crate::matchmaking::MatchmakingType;
+
+/// How often to recompute the stats for the current season.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The balance stats for a map in one matchmaking type and season.
+#[derive(SimpleObject, Debug, Clone)]
+pub struct MapBalanceStats {
+    pub matchmaking_type: MatchmakingType,
+    pub season_id: i32,
+    /// When these stats were last computed. `None` if they haven't been computed for this season
+    /// yet.
+    pub refreshed_at: Option<DateTime<Utc>>,
+    pub matchups: Vec<MapMatchupStats>,
+    pub start_locations: Vec<MapStartLocationStats>,
+}
+
+#[derive(SimpleObject, Debug, Clone)]
+#[graphql(complex)]
+pub struct MapMatchupStats {
+    /// The races of each team, e.g. `t-z` or `pt-zz`. Each team's races are sorted, as are the
+    /// teams.
+    pub matchup: String,
+    pub games: i32,
+    /// Wins for the first team in `matchup`. For mirror matchups, this counts every decided game.
+    pub team_a_wins: i32,
+    /// Wins for the second team in `matchup`. For mirror matchups, this counts every decided game.
+    pub team_b_wins: i32,
+    #[graphql(skip)]
+    pub total_game_length: i64,
+    #[graphql(skip)]
+    pub timed_games: i32,
+}
+
+#[ComplexObject]
+impl MapMatchupStats {
+    /// The average length of these games, in milliseconds.
+    async fn average_game_length(&self) -> Option<i32> {
+        average_game_length(self.total_game_length, self.timed_games)
+    }
+}
+
+/// Returns the average length of `timed_games` games that took `total_game_length` milliseconds
+/// altogether, or `None` if none of the games were timed.
+fn average_game_length(total_game_length: i64, timed_games: i32) -> Option<i32> {
+    (timed_games > 0).then(|| (total_game_length / timed_games as i64) as i32)
+}
+
+#[derive(SimpleObject, Debug, Clone)]
+pub struct MapStartLocationStats {
+    /// The start location, identified by the index of the player slot that owns it in the map.
+    pub start_location: i32,
+    pub games: i32,
+    pub wins: i32,
+}
+
+/// Identifies the stats to load for a map. A `season_id` of `None` means the current season.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MapStatsKey {
+    pub map_id: SbMapId,
+    pub matchmaking_type: MatchmakingType,
+    pub season_id: Option<i32>,
+}
+
+/// Returns the seasons whose stats are out of date: the current season (which always is), and any
+/// past season that hasn't been refreshed since it ended.
+async fn seasons_needing_refresh(db: &PgPool) -> eyre::Result<Vec<i32>> {
+    sqlx::query_scalar!(
+        r#"
+            WITH seasons AS (
+                SELECT s.id, lead(s.start_date) OVER (ORDER BY s.start_date) AS end_date
+                FROM matchmaking_seasons s
+                WHERE s.start_date <= CURRENT_TIMESTAMP AT TIME ZONE 'UTC'
+            )
+            SELECT s.id as "id!"
+            FROM seasons s
+            LEFT JOIN map_stats_refreshes r ON r.season_id = s.id
+            WHERE s.end_date IS NULL OR r.refreshed_at IS NULL
+                OR r.refreshed_at < s.end_date AT TIME ZONE 'UTC'
+            ORDER BY s.id
+        "#
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load seasons")
+}
+
+/// Recomputes all of the map stats for a season, replacing the existing ones.
+async fn refresh_season(db: &PgPool, season_id: i32) -> eyre::Result<()> {
+    let mut tx = db.begin().await?;
+    // Take the timestamp before aggregating, so games reconciled while this runs aren't counted as
+    // being included
+    let refreshed_at = Utc::now();
+
+    sqlx::query!(
+        "DELETE FROM map_matchup_stats WHERE season_id = $1",
+        season_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM map_start_location_stats WHERE season_id = $1",
+        season_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    // `assigned_matchup` is only set once a game's results are reconciled, and only for games
+    // without computers that weren't disputed, which are exactly the games worth counting
+    sqlx::query!(
+        r#"
+            WITH season AS (
+                SELECT start_date AT TIME ZONE 'UTC' AS start_date,
+                    (SELECT min(n.start_date) FROM matchmaking_seasons n
+                        WHERE n.start_date > s.start_date) AT TIME ZONE 'UTC' AS end_date
+                FROM matchmaking_seasons s
+                WHERE s.id = $1
+            ), season_games AS (
+                SELECT g.id, g.map_id, g.assigned_matchup, g.game_length,
+                    (g.config->'gameSourceExtra'->>'type')::matchmaking_type AS matchmaking_type
+                FROM games g, season
+                WHERE g.config->>'gameSource' = 'MATCHMAKING' AND g.results IS NOT NULL
+                    AND g.start_time >= season.start_date
+                    AND (season.end_date IS NULL OR g.start_time < season.end_date)
+                    AND g.assigned_matchup IS NOT NULL
+            )
+            INSERT INTO map_matchup_stats (map_id, matchmaking_type, season_id, matchup, games,
+                team_a_wins, team_b_wins, total_game_length, timed_games)
+            SELECT sg.map_id, sg.matchmaking_type, $1, sg.assigned_matchup, count(*),
+                count(*) FILTER (WHERE w.races = split_part(sg.assigned_matchup, '-', 1)),
+                count(*) FILTER (WHERE w.races = split_part(sg.assigned_matchup, '-', 2)),
+                coalesce(sum(sg.game_length), 0), count(sg.game_length)
+            FROM season_games sg
+            JOIN uploaded_maps um ON um.id = sg.map_id
+            LEFT JOIN LATERAL (
+                SELECT string_agg(gu.assigned_race::text, '' ORDER BY gu.assigned_race::text)
+                    AS races
+                FROM games_users gu
+                WHERE gu.game_id = sg.id AND gu.result = 'win'
+            ) w ON true
+            GROUP BY sg.map_id, sg.matchmaking_type, sg.assigned_matchup
+        "#,
+        season_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .wrap_err("Failed to compute matchup stats")?;
+
+    // Start locations come from the raw (v2) result reports, which list every player's BW player
+    // ID. Any report from the game will do, they all agree on these.
+    sqlx::query!(
+        r#"
+            WITH season AS (
+                SELECT start_date AT TIME ZONE 'UTC' AS start_date,
+                    (SELECT min(n.start_date) FROM matchmaking_seasons n
+                        WHERE n.start_date > s.start_date) AT TIME ZONE 'UTC' AS end_date
+                FROM matchmaking_seasons s
+                WHERE s.id = $1
+            ), season_games AS (
+                SELECT g.id, g.map_id,
+                    (g.config->'gameSourceExtra'->>'type')::matchmaking_type AS matchmaking_type
+                FROM games g, season
+                WHERE g.config->>'gameSource' = 'MATCHMAKING' AND g.results IS NOT NULL
+                    AND g.start_time >= season.start_date
+                    AND (season.end_date IS NULL OR g.start_time < season.end_date)
+                    AND g.assigned_matchup IS NOT NULL
+            )
+            INSERT INTO map_start_location_stats (map_id, matchmaking_type, season_id,
+                start_location, games, wins)
+            SELECT sg.map_id, sg.matchmaking_type, $1, sl.start_location, count(*),
+                count(*) FILTER (WHERE gu.result = 'win')
+            FROM season_games sg
+            JOIN uploaded_maps um ON um.id = sg.map_id
+            JOIN games_users gu ON gu.game_id = sg.id
+            JOIN LATERAL (
+                SELECT (p->>'bwPlayerId')::smallint AS start_location
+                FROM games_users r,
+                    jsonb_array_elements(CASE
+                        WHEN jsonb_typeof(r.reported_results->'players') = 'array'
+                        THEN r.reported_results->'players'
+                        ELSE '[]'::jsonb
+                    END) p
+                WHERE r.game_id = sg.id AND r.reported_results->>'version' = '2'
+                    AND p->>'userId' = gu.user_id::text
+                LIMIT 1
+            ) sl ON true
+            WHERE gu.result IN ('win', 'loss')
+            GROUP BY sg.map_id, sg.matchmaking_type, sl.start_location
+        "#,
+        season_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .wrap_err("Failed to compute start location stats")?;
+
+    sqlx::query!(
+        r#"
+            INSERT INTO map_stats_refreshes (season_id, refreshed_at)
+            VALUES ($1, $2)
+            ON CONFLICT (season_id) DO UPDATE SET refreshed_at = $2
+        "#,
+        season_id,
+        refreshed_at,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Runs forever, periodically recomputing the map stats for any seasons that are out of date. Meant
+/// to be spawned once per server instance.
+pub async fn map_stats_loop(db: PgPool) {
+    let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+    loop {
+        interval.tick().await;
+        let seasons = match seasons_needing_refresh(&db).await {
+            Ok(seasons) => seasons,
+            Err(e) => {
+                error!("Failed to check for outdated map stats: {e:?}");
+                continue;
+            }
+        };
+        for season_id in seasons {
+            match refresh_season(&db, season_id).await {
+                Ok(()) => info!("Refreshed map stats for season {season_id}"),
+                Err(e) => error!("Refreshing map stats for season {season_id} failed: {e:?}"),
+            }
+        }
+    }
+}
+
+pub struct MapStatsLoader {
+    db: PgPool,
+}
+
+impl MapStatsLoader {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+impl Loader<MapStatsKey> for MapStatsLoader {
+    type Value = MapBalanceStats;
+    type Error = async_graphql::Error;
+
+    async fn load(
+        &self,
+        keys: &[MapStatsKey],
+    ) -> Result<HashMap<MapStatsKey, Self::Value>, Self::Error> {
+        let current_season = if keys.iter().any(|k| k.season_id.is_none()) {
+            sqlx::query_scalar!(
+                r#"
+                    SELECT id
+                    FROM matchmaking_seasons
+                    WHERE start_date <= CURRENT_TIMESTAMP AT TIME ZONE 'UTC'
+                    ORDER BY start_date DESC
+                    LIMIT 1
+                "#
+            )
+            .fetch_optional(&self.db)
+            .await?
+        } else {
+            None
+        };
+        // Keys for the current season are left out if there isn't one yet
+        let resolved = keys
+            .iter()
+            .filter_map(|&k| {
+                k.season_id
+                    .or(current_season)
+                    .map(|season_id| (k, season_id))
+            })
+            .collect::<Vec<_>>();
+        if resolved.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        // Several keys can resolve to the same stats (e.g. the current season, both implicitly and
+        // by ID). Each set of stats is only queried once and then copied to all of its keys.
+        let mut by_stats_key = HashMap::new();
+        for &(key, season_id) in &resolved {
+            by_stats_key
+                .entry((key.map_id, key.matchmaking_type, season_id))
+                .or_insert_with(Vec::new)
+                .push(key);
+        }
+        let map_ids = by_stats_key
+            .keys()
+            .map(|&(map_id, _, _)| map_id.0)
+            .collect::<Vec<_>>();
+        let types = by_stats_key
+            .keys()
+            .map(|&(_, matchmaking_type, _)| matchmaking_type)
+            .collect::<Vec<_>>();
+        let season_ids = by_stats_key
+            .keys()
+            .map(|&(_, _, season_id)| season_id)
+            .collect::<Vec<_>>();
+
+        let matchups = sqlx::query!(
+            r#"
+                SELECT s.map_id as "map_id: SbMapId",
+                    s.matchmaking_type as "matchmaking_type: MatchmakingType", s.season_id,
+                    s.matchup, s.games, s.team_a_wins, s.team_b_wins, s.total_game_length,
+                    s.timed_games
+                FROM map_matchup_stats s
+                JOIN UNNEST($1::uuid[], $2::matchmaking_type[], $3::integer[])
+                    AS k(map_id, matchmaking_type, season_id)
+                    ON s.map_id = k.map_id AND s.matchmaking_type = k.matchmaking_type
+                        AND s.season_id = k.season_id
+                ORDER BY s.games DESC, s.matchup
+            "#,
+            &map_ids,
+            &types as &[MatchmakingType],
+            &season_ids,
+        )
+        .fetch_all(&self.db)
+        .await?;
+        let start_locations = sqlx::query!(
+            r#"
+                SELECT s.map_id as "map_id: SbMapId",
+                    s.matchmaking_type as "matchmaking_type: MatchmakingType", s.season_id,
+                    s.start_location, s.games, s.wins
+                FROM map_start_location_stats s
+                JOIN UNNEST($1::uuid[], $2::matchmaking_type[], $3::integer[])
+                    AS k(map_id, matchmaking_type, season_id)
+                    ON s.map_id = k.map_id AND s.matchmaking_type = k.matchmaking_type
+                        AND s.season_id = k.season_id
+                ORDER BY s.start_location
+            "#,
+            &map_ids,
+            &types as &[MatchmakingType],
+            &season_ids,
+        )
+        .fetch_all(&self.db)
+        .await?;
+        let unique_seasons = season_ids
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let refreshes = sqlx::query!(
+            r#"
+                SELECT season_id, refreshed_at
+                FROM map_stats_refreshes
+                WHERE season_id = ANY($1)
+            "#,
+            &unique_seasons,
+        )
+        .fetch_all(&self.db)
+        .await?
+        .into_iter()
+        .map(|r| (r.season_id, r.refreshed_at))
+        .collect::<HashMap<_, _>>();
+
+        let mut stats = HashMap::with_capacity(resolved.len());
+        for &(key, season_id) in &resolved {
+            stats.insert(
+                key,
+                MapBalanceStats {
+                    matchmaking_type: key.matchmaking_type,
+                    season_id,
+                    refreshed_at: refreshes.get(&season_id).copied(),
+                    matchups: Vec::new(),
+                    start_locations: Vec::new(),
+                },
+            );
+        }
+        for row in matchups {
+            let matchup = MapMatchupStats {
+                matchup: row.matchup,
+                games: row.games,
+                team_a_wins: row.team_a_wins,
+                team_b_wins: row.team_b_wins,
+                total_game_length: row.total_game_length,
+                timed_games: row.timed_games,
+            };
+            for key in &by_stats_key[&(row.map_id, row.matchmaking_type, row.season_id)] {
+                stats.get_mut(key).unwrap().matchups.push(matchup.clone());
+            }
+        }
+        for row in start_locations {
+            let start_location = MapStartLocationStats {
+                start_location: row.start_location as i32,
+                games: row.games,
+                wins: row.wins,
+            };
+            for key in &by_stats_key[&(row.map_id, row.matchmaking_type, row.season_id)] {
+                stats
+                    .get_mut(key)
+                    .unwrap()
+                    .start_locations
+                    .push(start_location.clone());
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_user;
+    use crate::users::SbUserId;
+    use uuid::Uuid;
+
+    /// A player in a test game: their user ID, assigned race, result, and BW player ID (which is
+    /// also their start location).
+    type TestPlayer = (SbUserId, &'static str, &'static str, i32);
+
+    /// The season that the migrations create, which starts well before any test game.
+    const SEASON_ID: i32 = 1;
+
+    async fn insert_map(db: &PgPool, uploaded_by: SbUserId) -> SbMapId {
+        let hash = Uuid::new_v4().as_bytes().repeat(2);
+        sqlx::query(
+            r#"
+                INSERT INTO maps (hash, extension, title, description, width, height, tileset,
+                    players_melee, players_ums, lobby_init_data)
+                VALUES ($1, 'scx', 'Map', '', 128, 128, 0, 2, 2, '{}')
+            "#,
+        )
+        .bind(&hash)
+        .execute(db)
+        .await
+        .unwrap();
+        let id = Uuid::new_v4();
+        sqlx::query(
+            r#"
+                INSERT INTO uploaded_maps (id, map_hash, uploaded_by, upload_date, visibility,
+                    name, description)
+                VALUES ($1, $2, $3, NOW(), 'OFFICIAL', 'Map', '')
+            "#,
+        )
+        .bind(id)
+        .bind(&hash)
+        .bind(uploaded_by)
+        .execute(db)
+        .await
+        .unwrap();
+        SbMapId(id)
+    }
+
+    async fn insert_game(
+        db: &PgPool,
+        map_id: SbMapId,
+        game_source: &str,
+        assigned_matchup: Option<&str>,
+        game_length: Option<i32>,
+        players: &[TestPlayer],
+    ) {
+        let config = serde_json::json!({
+            "gameSource": game_source,
+            "gameSourceExtra": { "type": "1v1" },
+        });
+        let game_id: Uuid = sqlx::query_scalar(
+            r#"
+                INSERT INTO games (start_time, map_id, config, disputable, dispute_requested,
+                    dispute_reviewed, game_length, results, assigned_matchup)
+                VALUES (NOW(), $1, $2, false, false, false, $3, '[]', $4)
+                RETURNING id
+            "#,
+        )
+        .bind(map_id)
+        .bind(config)
+        .bind(game_length)
+        .bind(assigned_matchup)
+        .fetch_one(db)
+        .await
+        .unwrap();
+
+        let reported_results = serde_json::json!({
+            "version": "2",
+            "players": players
+                .iter()
+                .map(|&(user, _, _, bw_player_id)| serde_json::json!({
+                    "userId": user.0,
+                    "bwPlayerId": bw_player_id,
+                }))
+                .collect::<Vec<_>>(),
+        });
+        for &(user, race, result, _) in players {
+            sqlx::query(
+                r#"
+                    INSERT INTO games_users (user_id, game_id, start_time, selected_race,
+                        result_code, reported_results, assigned_race, result)
+                    VALUES ($1, $2, NOW(), $3::race, '', $4, $3::race, $5::game_result)
+                "#,
+            )
+            .bind(user)
+            .bind(game_id)
+            .bind(race)
+            .bind(&reported_results)
+            .bind(result)
+            .execute(db)
+            .await
+            .unwrap();
+        }
+    }
+
+    fn key(map_id: SbMapId, season_id: Option<i32>) -> MapStatsKey {
+        MapStatsKey {
+            map_id,
+            matchmaking_type: MatchmakingType::Match1v1,
+            season_id,
+        }
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn refresh_season_aggregates_matchups_and_start_locations(db: PgPool) {
+        let users = [
+            create_user(&db, "one").await,
+            create_user(&db, "two").await,
+            create_user(&db, "three").await,
+            create_user(&db, "four").await,
+        ];
+        let [u1, u2, u3, u4] = users;
+        let map = insert_map(&db, u1).await;
+        let empty_map = insert_map(&db, u1).await;
+
+        insert_game(
+            &db,
+            map,
+            "MATCHMAKING",
+            Some("t-z"),
+            Some(60_000),
+            &[(u1, "t", "win", 0), (u2, "z", "loss", 1)],
+        )
+        .await;
+        insert_game(
+            &db,
+            map,
+            "MATCHMAKING",
+            Some("t-z"),
+            None,
+            &[(u1, "t", "loss", 1), (u2, "z", "win", 0)],
+        )
+        .await;
+        insert_game(
+            &db,
+            map,
+            "MATCHMAKING",
+            Some("z-z"),
+            Some(30_000),
+            &[(u3, "z", "win", 1), (u4, "z", "loss", 0)],
+        )
+        .await;
+        // Unreconciled (or disputed) games and non-matchmaking games aren't counted
+        insert_game(
+            &db,
+            map,
+            "MATCHMAKING",
+            None,
+            Some(10_000),
+            &[(u1, "t", "win", 0), (u3, "z", "loss", 1)],
+        )
+        .await;
+        insert_game(
+            &db,
+            map,
+            "LOBBY",
+            Some("p-t"),
+            Some(10_000),
+            &[(u2, "p", "win", 0), (u4, "t", "loss", 1)],
+        )
+        .await;
+
+        refresh_season(&db, SEASON_ID).await.unwrap();
+
+        let loader = MapStatsLoader::new(db.clone());
+        let keys = [
+            key(map, None),
+            key(map, Some(SEASON_ID)),
+            key(empty_map, None),
+        ];
+        let stats = loader.load(&keys).await.unwrap();
+        assert_eq!(stats.len(), 3);
+
+        // The current season resolves to the same stats as asking for it explicitly
+        for k in &keys[..2] {
+            let s = &stats[k];
+            assert_eq!(s.season_id, SEASON_ID);
+            assert!(s.refreshed_at.is_some());
+
+            let matchups = s
+                .matchups
+                .iter()
+                .map(|m| {
+                    (
+                        m.matchup.as_str(),
+                        m.games,
+                        m.team_a_wins,
+                        m.team_b_wins,
+                        m.total_game_length,
+                        m.timed_games,
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                matchups,
+                vec![("t-z", 2, 1, 1, 60_000, 1), ("z-z", 1, 1, 1, 30_000, 1)]
+            );
+
+            let start_locations = s
+                .start_locations
+                .iter()
+                .map(|l| (l.start_location, l.games, l.wins))
+                .collect::<Vec<_>>();
+            assert_eq!(start_locations, vec![(0, 3, 2), (1, 3, 1)]);
+        }
+
+        let empty = &stats[&keys[2]];
+        assert_eq!(empty.season_id, SEASON_ID);
+        assert!(empty.matchups.is_empty());
+        assert!(empty.start_locations.is_empty());
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn refresh_season_replaces_previous_stats(db: PgPool) {
+        let u1 = create_user(&db, "one").await;
+        let u2 = create_user(&db, "two").await;
+        let map = insert_map(&db, u1).await;
+        insert_game(
+            &db,
+            map,
+            "MATCHMAKING",
+            Some("p-t"),
+            Some(20_000),
+            &[(u1, "p", "win", 0), (u2, "t", "loss", 1)],
+        )
+        .await;
+
+        refresh_season(&db, SEASON_ID).await.unwrap();
+        refresh_season(&db, SEASON_ID).await.unwrap();
+
+        let stats = MapStatsLoader::new(db.clone())
+            .load(&[key(map, Some(SEASON_ID))])
+            .await
+            .unwrap();
+        let matchups = &stats[&key(map, Some(SEASON_ID))].matchups;
+        assert_eq!(matchups.len(), 1);
+        assert_eq!(matchups[0].games, 1);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn only_current_and_unfinished_seasons_need_refreshing(db: PgPool) {
+        assert_eq!(seasons_needing_refresh(&db).await.unwrap(), vec![SEASON_ID]);
+
+        let next_season: i32 = sqlx::query_scalar(
+            r#"
+                INSERT INTO matchmaking_seasons (start_date, name)
+                VALUES (NOW() AT TIME ZONE 'UTC' - INTERVAL '1 day', 'Next')
+                RETURNING id
+            "#,
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        // The first season ended without being refreshed after its end
+        assert_eq!(
+            seasons_needing_refresh(&db).await.unwrap(),
+            vec![SEASON_ID, next_season]
+        );
+
+        refresh_season(&db, SEASON_ID).await.unwrap();
+        refresh_season(&db, next_season).await.unwrap();
+        // The current season always needs refreshing
+        assert_eq!(
+            seasons_needing_refresh(&db).await.unwrap(),
+            vec![next_season]
+        );
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn current_season_keys_are_dropped_without_a_current_season(db: PgPool) {
+        sqlx::query("DELETE FROM matchmaking_seasons")
+            .execute(&db)
+            .await
+            .unwrap();
+        let user = create_user(&db, "one").await;
+        let map = insert_map(&db, user).await;
+
+        let stats = MapStatsLoader::new(db.clone())
+            .load(&[key(map, None)])
+            .await
+            .unwrap();
+        assert!(stats.is_empty());
+    }
+
+    #[test]
+    fn average_game_length_only_counts_timed_games() {
+        assert_eq!(average_game_length(0, 0), None);
+        assert_eq!(average_game_length(90_000, 2), Some(45_000));
+        // Totals over many long games don't fit in an i32, but their averages do
+        assert_eq!(average_game_length(5_000_000_000, 1000), Some(5_000_000));
+    }
+}
@@ -0,0 +1,656 @@
+//! Storing uploaded maps, and keeping the parsed data and images of stored maps up to date as the
+//! parser and renderer change.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    Json, Router,
+    extract::{DefaultBodyLimit, Multipart, State},
+    http::StatusCode,
+    routing::post,
+};
+use color_eyre::eyre::{self, WrapErr};
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::{error, info, warn};
+use typeshare::typeshare;
+
+use super::render::{MAP_RENDERER_VERSION, MapImage, MapRenderer};
+use super::{
+    MAP_PARSER_VERSION, MapExtension, MapHash, MapVisibility, ParsedMap, SbMapId, map_image_path,
+    map_path, parse_map,
+};
+use crate::async_rayon::spawn_rayon;
+use crate::file_blobs::{BlobFile, add_blob_files, store_blob};
+use crate::file_store::{FileStore, FileVisibility};
+use crate::state::AppState;
+use crate::users::{CurrentUser, SbUserId};
+
+// NOTE: If you change this, also change `MAX_MAP_FILE_SIZE_BYTES` in `common/maps.ts`.
+/// The largest map file we accept.
+pub const MAX_MAP_FILE_SIZE_BYTES: usize = 100 * 1024 * 1024;
+
+/// How often to check for maps that were parsed by an older parser version.
+const REPARSE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// How many maps to load at once when re-parsing or re-rendering.
+const REPARSE_BATCH_SIZE: i64 = 20;
+/// How often to check for maps whose images were rendered by an older renderer version.
+const RERENDER_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+pub fn create_maps_api() -> Router<AppState> {
+    Router::new()
+        .route("/", post(upload_map))
+        .route("/official", post(upload_official_map))
+        // Leave some room for the multipart framing around the map
+        .layer(DefaultBodyLimit::max(MAX_MAP_FILE_SIZE_BYTES + 64 * 1024))
+}
+
+#[typeshare]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MapUploadResponse {
+    pub id: SbMapId,
+}
+
+/// Accepts a multipart upload with the map in a `file` field and its format (`scm` or `scx`) in an
+/// `extension` field. The map is only visible to the user that uploaded it.
+async fn upload_map(
+    State(db): State<PgPool>,
+    State(file_store): State<FileStore>,
+    State(map_renderer): State<Option<Arc<MapRenderer>>>,
+    user: CurrentUser,
+    multipart: Multipart,
+) -> Result<Json<MapUploadResponse>, (StatusCode, &'static str)> {
+    handle_upload(
+        &db,
+        &file_store,
+        map_renderer,
+        user.id,
+        MapVisibility::Private,
+        multipart,
+    )
+    .await
+}
+
+/// Like [`upload_map`], but adds the map to the official map list.
+async fn upload_official_map(
+    State(db): State<PgPool>,
+    State(file_store): State<FileStore>,
+    State(map_renderer): State<Option<Arc<MapRenderer>>>,
+    user: CurrentUser,
+    multipart: Multipart,
+) -> Result<Json<MapUploadResponse>, (StatusCode, &'static str)> {
+    if !user.permissions.manage_maps {
+        return Err((StatusCode::FORBIDDEN, "Forbidden"));
+    }
+    handle_upload(
+        &db,
+        &file_store,
+        map_renderer,
+        user.id,
+        MapVisibility::Official,
+        multipart,
+    )
+    .await
+}
+
+async fn handle_upload(
+    db: &PgPool,
+    file_store: &FileStore,
+    map_renderer: Option<Arc<MapRenderer>>,
+    uploaded_by: SbUserId,
+    visibility: MapVisibility,
+    mut multipart: Multipart,
+) -> Result<Json<MapUploadResponse>, (StatusCode, &'static str)> {
+    let invalid_body = |_| (StatusCode::BAD_REQUEST, "Invalid multipart body");
+    let mut file = None;
+    let mut extension = None;
+    while let Some(field) = multipart.next_field().await.map_err(invalid_body)? {
+        match field.name() {
+            Some("file") => {
+                if file.is_some() {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        "A single map file must be provided",
+                    ));
+                }
+                file = Some(field.bytes().await.map_err(invalid_body)?);
+            }
+            Some("extension") => {
+                extension = Some(field.text().await.map_err(invalid_body)?);
+            }
+            _ => {}
+        }
+    }
+    let file = file.ok_or((StatusCode::BAD_REQUEST, "map file must be specified"))?;
+    let extension = extension
+        .ok_or((StatusCode::BAD_REQUEST, "extension must be specified"))?
+        .parse::<MapExtension>()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Unsupported extension"))?;
+
+    let (parsed, file) = spawn_rayon(move || (parse_map(&file, extension), file)).await;
+    let parsed = parsed.map_err(|e| {
+        warn!("Failed to parse uploaded map: {e:?}");
+        (StatusCode::BAD_REQUEST, "Map could not be parsed")
+    })?;
+
+    // A map with no start locations and no active force slots can't seat any players in either
+    // melee or UMS games, so it could never be played
+    if parsed.data.melee_players == 0 && parsed.data.ums_players == 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Map has no start locations or active player slots",
+        ));
+    }
+
+    // Failing to render images shouldn't fail the upload, the map will just be stored without
+    // them and the images will be rendered again later
+    let (parsed, images) = match map_renderer {
+        Some(renderer) => {
+            spawn_rayon(move || {
+                let images = renderer.render(&parsed.data);
+                (parsed, images)
+            })
+            .await
+        }
+        None => (parsed, Ok(Vec::new())),
+    };
+    let images = images.unwrap_or_else(|e| {
+        error!("Failed to render uploaded map: {e:?}");
+        Vec::new()
+    });
+
+    let id = store_map(
+        db,
+        file_store,
+        &parsed,
+        file.into(),
+        images,
+        uploaded_by,
+        visibility,
+    )
+    .await
+    .map_err(|e| {
+        error!("Failed to store uploaded map: {e:?}");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+    })?;
+
+    Ok(Json(MapUploadResponse { id }))
+}
+
+/// Stores a parsed map, writing its file, images and `maps` row if it's new, and adds it to the
+/// uploader's maps (or the official maps) with the given `visibility`. Returns the ID of the
+/// uploaded map.
+///
+/// The file and images are stored as a single blob at the map's path, which the `maps` row
+/// references (through its generated `file_path` column), so they're deleted along with the map.
+async fn store_map(
+    db: &PgPool,
+    file_store: &FileStore,
+    parsed: &ParsedMap,
+    file: Vec<u8>,
+    images: Vec<MapImage>,
+    uploaded_by: SbUserId,
+    visibility: MapVisibility,
+) -> eyre::Result<SbMapId> {
+    let hash = &parsed.hash.0[..];
+    let renderer_version = if images.is_empty() {
+        0
+    } else {
+        MAP_RENDERER_VERSION
+    };
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM maps WHERE hash = $1) as "exists!""#,
+        hash
+    )
+    .fetch_one(db)
+    .await
+    .wrap_err("Failed to check for an existing map")?;
+    // The files are keyed by the map hash, so writing them before the `maps` row exists is safe:
+    // the contents are identical for any given hash, and they're only ever reached through a row.
+    // Existing maps are left alone, since their files may predate blob tracking.
+    if !exists {
+        let path = map_path(&parsed.hash, parsed.extension);
+        let mut files = vec![BlobFile {
+            path: path.clone(),
+            data: file,
+            content_type: "application/octet-stream",
+            visibility: FileVisibility::Private,
+        }];
+        files.extend(map_image_files(&parsed.hash, images));
+        store_blob(db, file_store, &path, parsed.hash.0, files)
+            .await
+            .wrap_err("Failed to store map files")?;
+    }
+
+    let data = &parsed.data;
+    let mut tx = db.begin().await.wrap_err("Failed to start transaction")?;
+    // A concurrent upload of the same map may have inserted it in the meantime, which is fine
+    // since it parsed the same file
+    sqlx::query!(
+        r#"
+            INSERT INTO maps (hash, extension, title, description, width, height, tileset,
+                players_melee, players_ums, lobby_init_data, is_eud, parser_version, image_version,
+                renderer_version)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, 1, $13)
+            ON CONFLICT (hash) DO NOTHING
+        "#,
+        hash,
+        parsed.extension.as_str(),
+        data.title,
+        data.description,
+        data.width as i32,
+        data.height as i32,
+        data.tileset as i32,
+        data.melee_players as i32,
+        data.ums_players as i32,
+        sqlx::types::Json(parsed.lobby_init_data()) as _,
+        data.is_eud,
+        MAP_PARSER_VERSION,
+        renderer_version,
+    )
+    .execute(&mut *tx)
+    .await
+    .wrap_err("Failed to insert map")?;
+
+    let id = sqlx::query_scalar!(
+        r#"
+            INSERT INTO uploaded_maps
+                (id, map_hash, uploaded_by, upload_date, visibility, name, description)
+            VALUES (sb_uuid(), $1, $2, CURRENT_TIMESTAMP AT TIME ZONE 'UTC', $3, $4, $5)
+            ON CONFLICT (map_hash, uploaded_by, visibility)
+            DO UPDATE SET
+                removed_at = NULL,
+                upload_date = CURRENT_TIMESTAMP AT TIME ZONE 'UTC',
+                name = $4,
+                description = $5
+            RETURNING id as "id: SbMapId"
+        "#,
+        hash,
+        uploaded_by as _,
+        visibility as _,
+        data.title,
+        data.description,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .wrap_err("Failed to insert uploaded map")?;
+    tx.commit().await.wrap_err("Failed to commit transaction")?;
+
+    Ok(id)
+}
+
+fn map_image_files(hash: &MapHash, images: Vec<MapImage>) -> impl Iterator<Item = BlobFile> {
+    images.into_iter().map(|image| BlobFile {
+        path: map_image_path(hash, image.size),
+        data: image.data,
+        content_type: "image/jpeg",
+        visibility: FileVisibility::Public,
+    })
+}
+
+/// Re-parses maps that were parsed by an older parser version, updating their parsed data. Maps
+/// in `failed` are skipped, and any that fail to re-parse are added to it, so that a map that can't
+/// be parsed doesn't get retried over and over. Returns the number of maps that were updated.
+async fn reparse_outdated_maps(
+    db: &PgPool,
+    file_store: &FileStore,
+    failed: &mut HashSet<MapHash>,
+) -> eyre::Result<usize> {
+    let mut updated = 0;
+    loop {
+        let skipped = failed.iter().map(|h| h.0.to_vec()).collect::<Vec<_>>();
+        let maps = sqlx::query!(
+            r#"
+                SELECT hash as "hash: MapHash", extension
+                FROM maps
+                WHERE parser_version < $1 AND NOT (hash = ANY($2))
+                ORDER BY hash
+                LIMIT $3
+            "#,
+            MAP_PARSER_VERSION,
+            &skipped,
+            REPARSE_BATCH_SIZE,
+        )
+        .fetch_all(db)
+        .await
+        .wrap_err("Failed to load outdated maps")?;
+        if maps.is_empty() {
+            return Ok(updated);
+        }
+
+        for map in maps {
+            match reparse_map(db, file_store, map.hash, &map.extension).await {
+                Ok(()) => updated += 1,
+                Err(e) => {
+                    warn!(
+                        "Failed to re-parse map {}: {e:?}",
+                        data_encoding::HEXLOWER.encode(&map.hash.0)
+                    );
+                    failed.insert(map.hash);
+                }
+            }
+        }
+    }
+}
+
+/// Reads a stored map's file and parses it.
+async fn load_stored_map(
+    file_store: &FileStore,
+    hash: &MapHash,
+    extension: &str,
+) -> eyre::Result<ParsedMap> {
+    // The column is a `character(3)`, but trim it in case that ever changes
+    let extension = extension
+        .trim()
+        .parse::<MapExtension>()
+        .map_err(|_| eyre::eyre!("Unsupported map extension: {extension}"))?;
+    let file = file_store
+        .read(&map_path(hash, extension))
+        .await
+        .wrap_err("Failed to read map file")?;
+    spawn_rayon(move || parse_map(&file, extension))
+        .await
+        .wrap_err("Failed to parse map")
+}
+
+async fn reparse_map(
+    db: &PgPool,
+    file_store: &FileStore,
+    hash: MapHash,
+    extension: &str,
+) -> eyre::Result<()> {
+    let parsed = load_stored_map(file_store, &hash, extension).await?;
+
+    let data = &parsed.data;
+    sqlx::query!(
+        r#"
+            UPDATE maps
+            SET
+                title = $2,
+                description = $3,
+                width = $4,
+                height = $5,
+                tileset = $6,
+                players_melee = $7,
+                players_ums = $8,
+                lobby_init_data = $9,
+                is_eud = $10,
+                parser_version = $11
+            WHERE hash = $1
+        "#,
+        &hash.0[..],
+        data.title,
+        data.description,
+        data.width as i32,
+        data.height as i32,
+        data.tileset as i32,
+        data.melee_players as i32,
+        data.ums_players as i32,
+        sqlx::types::Json(parsed.lobby_init_data()) as _,
+        data.is_eud,
+        MAP_PARSER_VERSION,
+    )
+    .execute(db)
+    .await
+    .wrap_err("Failed to update map")?;
+
+    Ok(())
+}
+
+/// Runs forever, periodically re-parsing maps that were parsed by an older version of the parser.
+/// Meant to be spawned once per server instance.
+pub async fn map_reparse_loop(db: PgPool, file_store: FileStore) {
+    let mut failed = HashSet::new();
+    let mut interval = tokio::time::interval(REPARSE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match reparse_outdated_maps(&db, &file_store, &mut failed).await {
+            Ok(0) => {}
+            Ok(count) => info!("Re-parsed {count} maps with parser version {MAP_PARSER_VERSION}"),
+            Err(e) => error!("Re-parsing outdated maps failed: {e:?}"),
+        }
+    }
+}
+
+/// Re-renders the images of maps that were rendered by an older renderer version (or not at all).
+/// Like [`reparse_outdated_maps`], maps in `failed` are skipped and any that fail are added to it.
+/// Returns the number of maps that were re-rendered.
+async fn rerender_outdated_maps(
+    db: &PgPool,
+    file_store: &FileStore,
+    renderer: &Arc<MapRenderer>,
+    failed: &mut HashSet<MapHash>,
+) -> eyre::Result<usize> {
+    let mut updated = 0;
+    loop {
+        let skipped = failed.iter().map(|h| h.0.to_vec()).collect::<Vec<_>>();
+        let maps = sqlx::query!(
+            r#"
+                SELECT hash as "hash: MapHash", extension
+                FROM maps
+                WHERE renderer_version < $1 AND NOT (hash = ANY($2))
+                ORDER BY hash
+                LIMIT $3
+            "#,
+            MAP_RENDERER_VERSION,
+            &skipped,
+            REPARSE_BATCH_SIZE,
+        )
+        .fetch_all(db)
+        .await
+        .wrap_err("Failed to load maps with outdated images")?;
+        if maps.is_empty() {
+            return Ok(updated);
+        }
+
+        for map in maps {
+            match rerender_map(db, file_store, renderer, map.hash, &map.extension).await {
+                Ok(()) => updated += 1,
+                Err(e) => {
+                    warn!(
+                        "Failed to re-render map {}: {e:?}",
+                        data_encoding::HEXLOWER.encode(&map.hash.0)
+                    );
+                    failed.insert(map.hash);
+                }
+            }
+        }
+    }
+}
+
+async fn rerender_map(
+    db: &PgPool,
+    file_store: &FileStore,
+    renderer: &Arc<MapRenderer>,
+    hash: MapHash,
+    extension: &str,
+) -> eyre::Result<()> {
+    let parsed = load_stored_map(file_store, &hash, extension).await?;
+    let map_path = map_path(&hash, parsed.extension);
+    let renderer = renderer.clone();
+    let images = spawn_rayon(move || renderer.render(&parsed.data))
+        .await
+        .wrap_err("Failed to render map")?;
+    add_blob_files(
+        db,
+        file_store,
+        &map_path,
+        map_image_files(&hash, images).collect(),
+    )
+    .await
+    .wrap_err("Failed to write map images")?;
+
+    // `image_version` is the cache-buster clients append to image URLs, so it's only bumped once the
+    // new images are in place
+    sqlx::query!(
+        r#"
+            UPDATE maps
+            SET renderer_version = $2, image_version = image_version + 1
+            WHERE hash = $1
+        "#,
+        &hash.0[..],
+        MAP_RENDERER_VERSION,
+    )
+    .execute(db)
+    .await
+    .wrap_err("Failed to update map")?;
+
+    Ok(())
+}
+
+/// Runs forever, periodically re-rendering the images of maps that were rendered by an older
+/// version of the renderer. Meant to be spawned once per server instance.
+pub async fn map_image_render_loop(db: PgPool, file_store: FileStore, renderer: Arc<MapRenderer>) {
+    let mut failed = HashSet::new();
+    let mut interval = tokio::time::interval(RERENDER_INTERVAL);
+    loop {
+        interval.tick().await;
+        match rerender_outdated_maps(&db, &file_store, &renderer, &mut failed).await {
+            Ok(0) => {}
+            Ok(count) => {
+                info!("Re-rendered {count} maps with renderer version {MAP_RENDERER_VERSION}")
+            }
+            Err(e) => error!("Re-rendering outdated map images failed: {e:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::{chk, mpq};
+
+    fn test_map(extension: MapExtension) -> (ParsedMap, Vec<u8>) {
+        let chk = chk::tests::build_chk(&chk::tests::basic_map_sections());
+        let archive = mpq::tests::build_archive(mpq::SCENARIO_CHK_PATH, &chk);
+        (parse_map(&archive, extension).unwrap(), archive)
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn stored_maps_are_referenced_blobs(db: PgPool) {
+        let dir = tempfile::tempdir().unwrap();
+        let file_store = crate::test_utils::local_file_store(dir.path());
+        let first = crate::test_utils::create_user(&db, "first").await;
+        let second = crate::test_utils::create_user(&db, "second").await;
+        let (parsed, file) = test_map(MapExtension::Scx);
+        let path = map_path(&parsed.hash, parsed.extension);
+        let images = vec![MapImage {
+            size: 256,
+            data: b"jpeg".to_vec(),
+        }];
+
+        let first_id = store_map(
+            &db,
+            &file_store,
+            &parsed,
+            file.clone(),
+            images,
+            first,
+            MapVisibility::Private,
+        )
+        .await
+        .unwrap();
+        let second_id = store_map(
+            &db,
+            &file_store,
+            &parsed,
+            file.clone(),
+            Vec::new(),
+            second,
+            MapVisibility::Private,
+        )
+        .await
+        .unwrap();
+        assert_ne!(first_id, second_id);
+
+        // The generated path on the `maps` row matches where the file was written
+        let stored_path: String = sqlx::query_scalar("SELECT file_path FROM maps WHERE hash = $1")
+            .bind(&parsed.hash.0[..])
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(stored_path, path);
+        assert_eq!(file_store.read(&path).await.unwrap(), file);
+
+        let (ref_count, files): (i32, Vec<String>) = sqlx::query_as(
+            r#"
+                SELECT b.ref_count, array_agg(f.path ORDER BY f.path)
+                FROM file_blobs b
+                JOIN file_blob_files f ON f.blob_path = b.path
+                WHERE b.path = $1
+                GROUP BY b.path
+            "#,
+        )
+        .bind(&path)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        // Both uploads share the one `maps` row
+        assert_eq!(ref_count, 1);
+        assert_eq!(files, vec![map_image_path(&parsed.hash, 256), path]);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn outdated_maps_are_reparsed(db: PgPool) {
+        let dir = tempfile::tempdir().unwrap();
+        let file_store = crate::test_utils::local_file_store(dir.path());
+        let user = crate::test_utils::create_user(&db, "pachi").await;
+        // The extension is part of the hash, so these are stored as two different maps
+        let (good, good_file) = test_map(MapExtension::Scx);
+        let (missing, missing_file) = test_map(MapExtension::Scm);
+        for (parsed, file) in [(&good, good_file), (&missing, missing_file)] {
+            store_map(
+                &db,
+                &file_store,
+                parsed,
+                file,
+                Vec::new(),
+                user,
+                MapVisibility::Private,
+            )
+            .await
+            .unwrap();
+        }
+        file_store
+            .delete(&map_path(&missing.hash, missing.extension))
+            .await
+            .unwrap();
+        sqlx::query("UPDATE maps SET parser_version = 0, title = 'Outdated'")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let mut failed = HashSet::new();
+        let updated = reparse_outdated_maps(&db, &file_store, &mut failed)
+            .await
+            .unwrap();
+        assert_eq!(updated, 1);
+        assert_eq!(failed, HashSet::from([missing.hash]));
+
+        let maps: Vec<(Vec<u8>, String, i32)> =
+            sqlx::query_as("SELECT hash, title, parser_version FROM maps ORDER BY title")
+                .fetch_all(&db)
+                .await
+                .unwrap();
+        assert_eq!(
+            maps,
+            vec![
+                (
+                    good.hash.0.to_vec(),
+                    "Fighting Spirit".to_owned(),
+                    MAP_PARSER_VERSION
+                ),
+                (missing.hash.0.to_vec(), "Outdated".to_owned(), 0),
+            ]
+        );
+
+        // Maps that failed aren't retried
+        let updated = reparse_outdated_maps(&db, &file_store, &mut failed)
+            .await
+            .unwrap();
+        assert_eq!(updated, 0);
+    }
+}
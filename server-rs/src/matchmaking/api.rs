@@ -1,7 +1,10 @@
 use crate::matchmaking::backbone::{BackboneRttTable, ServedPairRtt, parse_served_backbone_rtts};
 use crate::matchmaking::config::MatchmakerConfig;
+use crate::matchmaking::map_pools::{
+    MapSelectionError, load_current_map_pools, validate_map_selections,
+};
 use crate::matchmaking::matchmaker::{
-    Matchmaker, Player, PlayerModeRating, QueueEntry, RandomQueueSelector,
+    Match, Matchmaker, Player, PlayerModeRating, QueueEntry, QueueSelector, RandomQueueSelector,
 };
 use crate::matchmaking::{
     MatchFoundMessage, MatchedPlayer, MatchmakingType, PublishedMatchmakingMessage,
@@ -20,6 +23,7 @@ use base64::Engine as _;
 use base64::prelude::BASE64_STANDARD;
 use color_eyre::eyre::{self, Context as _, eyre};
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
@@ -28,10 +32,9 @@ use uuid::Uuid;
 
 use super::matchmaker::MatchmakerError;
 
-/// How many times to attempt publishing a formed match to Redis before treating the failure as
-/// fatal. A formed match has already been removed from the queue, so a few quick retries are worth
-/// it to ride out a transient Redis blip before resorting to the process exit in
-/// [publish_match_or_exit].
+/// How many times to attempt publishing a formed match to Redis before giving up on it. A few quick
+/// retries are worth it to ride out a transient Redis blip before [publish_match] returns the
+/// match's players to the queue.
 const MAX_PUBLISH_ATTEMPTS: u32 = 3;
 
 /// Delay between the publish attempts counted by [MAX_PUBLISH_ATTEMPTS].
@@ -73,6 +76,19 @@ impl IntoResponse for MatchmakerError {
     }
 }
 
+impl IntoResponse for MapSelectionError {
+    fn into_response(self) -> axum::response::Response {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ApiError {
+                code: RsMatchmakerErrorCode::InvalidMapSelections,
+                message: "Map selections don't match the active map pool",
+            }),
+        )
+            .into_response()
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct PlayerModeRatingDto {
@@ -139,6 +155,8 @@ struct MatchmakingApiState {
     /// matchmaker (mirroring `config`), so a refreshed served table takes effect without a restart.
     backbone: Arc<ArcSwap<BackboneRttTable>>,
     process_token: Uuid,
+    /// Used to load the active map pools that queue requests' map selections are validated against.
+    db: PgPool,
 }
 
 /// Locks the shared matchmaker, recovering the guard even if a previous holder panicked and
@@ -305,28 +323,26 @@ async fn search_loop(state: MatchmakingApiState, redis_pool: RedisPool) {
                 max_latency: m.max_latency,
             });
 
-            publish_match_or_exit(&redis_pool, event).await;
+            publish_match(&state, &redis_pool, &m, event).await;
         }
     }
 }
 
-/// Publishes a formed-match event to Redis, retrying briefly before giving up. If every attempt
-/// fails, the match is irrecoverably lost: its players were already removed from the queue (so the
-/// search loop won't re-form their match) but Node.js never learned of the match, so they would
-/// believe they are still searching forever.
+/// Publishes a formed-match event to Redis, retrying briefly before giving up. Matchmaking
+/// messages go to a Redis stream, so once this succeeds Node.js will receive the match even if it's
+/// currently restarting.
 ///
-/// Rather than strand them, we exit the process. The `restart: unless-stopped` policy brings
-/// server-rs back with a fresh `process_token`, which the Node.js watchdog detects (token change,
-/// or the unreachable window during the restart) and uses to eject — and surface a failure to —
-/// every searching player, including the ones from this lost match. They can then immediately
-/// requeue. This is drastic, but a persistent inability to reach Redis means matchmaking can't
-/// function anyway, and a clean restart is the only way to restore consistency between the Rust
-/// queue and Node.js.
-///
-/// NOTE: this must exit the *process*, not panic. A panic here is caught by [supervise_search_loop]
-/// and only restarts the search task within the same process, leaving `process_token` unchanged —
-/// so the watchdog would never fire and the stranded players would stay stranded.
-async fn publish_match_or_exit(redis_pool: &RedisPool, event: PublishedMatchmakingMessage) {
+/// If every attempt fails, the match's players are put back in the queue (see
+/// [requeue_unpublished_match]). They were removed from the queue when the match formed, but
+/// Node.js never learned of the match, so as far as it (and the players) know they are still
+/// searching. Requeueing them with their original queue times makes that true again, and the search
+/// loop will match them again once Redis is reachable.
+async fn publish_match(
+    state: &MatchmakingApiState,
+    redis_pool: &RedisPool,
+    m: &Match,
+    event: PublishedMatchmakingMessage,
+) {
     for attempt in 1..=MAX_PUBLISH_ATTEMPTS {
         match redis_pool.publish(event.clone()).await {
             Ok(()) => return,
@@ -343,13 +359,24 @@ async fn publish_match_or_exit(redis_pool: &RedisPool, event: PublishedMatchmaki
     }
 
     tracing::error!(
-        "Exhausted all attempts to publish a formed match to Redis; exiting so the process \
-         restarts with a fresh token and stranded players are ejected by the Node.js watchdog."
+        "Exhausted all attempts to publish a formed match to Redis; returning its players to the \
+         queue"
     );
-    // std::process::exit skips the periodic/Drop-based Datadog flush, so force the log above out to
-    // Datadog (stdout already has it synchronously) before terminating.
-    crate::telemetry::flush_datadog_logs().await;
-    std::process::exit(1);
+    requeue_unpublished_match(&mut lock_matchmaker(&state.matchmaker), m);
+}
+
+/// Returns the players of a match that couldn't be published to the queue, keeping their original
+/// queue times. A player who can't be requeued (e.g. because they already queued again) is skipped.
+fn requeue_unpublished_match<T: QueueSelector>(matchmaker: &mut Matchmaker<T>, m: &Match) {
+    for entry in m.team_a.iter().chain(m.team_b.iter()) {
+        match matchmaker.requeue_player(entry.player.clone(), entry.queue_time) {
+            Ok(_) => metrics::record_player_requeued(m.mode),
+            Err(e) => tracing::warn!(
+                "Couldn't requeue player {} from an unpublished match: {e:?}",
+                entry.player.id
+            ),
+        }
+    }
 }
 
 /// Periodically fetches the rp2 coordinator's served backbone RTT table and swaps the composed
@@ -443,6 +470,7 @@ async fn get_process_token(State(state): State<MatchmakingApiState>) -> Json<Pro
 }
 
 pub fn create_matchmaking_api(
+    db: PgPool,
     redis_pool: RedisPool,
     config: Arc<ArcSwap<MatchmakerConfig>>,
     coordinator_url: Option<String>,
@@ -466,6 +494,7 @@ pub fn create_matchmaking_api(
         config,
         backbone: backbone.clone(),
         process_token: Uuid::new_v4(),
+        db,
     };
 
     // Spawn the autonomous match-finding loop. It runs for the lifetime of the process, and is
@@ -492,9 +521,10 @@ pub fn create_matchmaking_api(
 
 async fn insert_player(
     State(state): State<MatchmakingApiState>,
-    Json(payload): Json<QueueRequest>,
-) -> Result<StatusCode, MatchmakerError> {
+    Json(mut payload): Json<QueueRequest>,
+) -> Result<StatusCode, axum::response::Response> {
     let modes: Vec<MatchmakingType> = payload.mode_ratings.iter().map(|r| r.mode).collect();
+    validate_queue_map_selections(&state.db, &mut payload).await?;
     let player = build_player(
         payload.id,
         payload.mode_ratings,
@@ -503,7 +533,9 @@ async fn insert_player(
     );
     {
         let mut matchmaker = lock_matchmaker(&state.matchmaker);
-        matchmaker.insert_player(player)?;
+        matchmaker
+            .insert_player(player)
+            .map_err(IntoResponse::into_response)?;
     }
     for mode in modes {
         metrics::record_player_queued(mode);
@@ -511,6 +543,39 @@ async fn insert_player(
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// Checks the map selections in a queue request against each mode's active map pool, dropping any
+/// picks past the pool's limit (see [`validate_map_selections`]). Requeues aren't checked: their
+/// selections were valid when the player first queued, and the pool rotating in the meantime
+/// shouldn't keep them out of the queue.
+async fn validate_queue_map_selections(
+    db: &PgPool,
+    request: &mut QueueRequest,
+) -> Result<(), axum::response::Response> {
+    let modes = request
+        .mode_ratings
+        .iter()
+        .filter(|r| r.map_selections.is_some())
+        .map(|r| r.mode)
+        .collect::<Vec<_>>();
+    if modes.is_empty() {
+        return Ok(());
+    }
+
+    let pools = load_current_map_pools(db, &modes).await.map_err(|e| {
+        tracing::error!("failed to load map pools for a queue request: {e:?}");
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
+    for r in &mut request.mode_ratings {
+        if let Some(selections) = &mut r.map_selections {
+            validate_map_selections(r.mode, pools.get(&r.mode), selections).map_err(|e| {
+                tracing::debug!("rejecting queue request for player {}: {e}", request.id);
+                e.into_response()
+            })?;
+        }
+    }
+    Ok(())
+}
+
 async fn requeue_player(
     State(state): State<MatchmakingApiState>,
     Json(payload): Json<RequeueRequest>,
@@ -591,10 +656,18 @@ async fn cancel(
 
 #[cfg(test)]
 mod tests {
-    use super::{apply_backbone_fetch_result, fetch_served_backbone_rtts};
+    use super::{
+        PlayerModeRatingDto, apply_backbone_fetch_result, build_player, fetch_served_backbone_rtts,
+        requeue_unpublished_match,
+    };
+    use crate::matchmaking::MatchmakingType;
     use crate::matchmaking::backbone::{BackboneRttTable, ServedPairRtt};
+    use crate::matchmaking::config::MatchmakerConfig;
+    use crate::matchmaking::matchmaker::{Match, Matchmaker, QueueEntry};
     use arc_swap::ArcSwap;
     use color_eyre::eyre::eyre;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
 
     fn served(a: &str, b: &str, rtt_ms: f32) -> ServedPairRtt {
         ServedPairRtt {
@@ -717,4 +790,46 @@ mod tests {
         route.assert_async().await;
         assert!(result.is_err());
     }
+
+    #[test]
+    fn unpublished_match_players_are_requeued() {
+        let mode = MatchmakingType::Match1v1;
+        let mut matchmaker =
+            Matchmaker::new(Arc::new(MatchmakerConfig::default()), Default::default());
+        let queue_time = Instant::now() - Duration::from_secs(30);
+        let entry = |id| QueueEntry {
+            queue_time,
+            player: build_player(
+                id,
+                vec![PlayerModeRatingDto {
+                    mode,
+                    rating: 1500.0,
+                    uncertainty: None,
+                    map_selections: None,
+                }],
+                None,
+                None,
+            ),
+            modes: mode.into(),
+        };
+        let m = Match {
+            mode,
+            team_a: vec![entry(1)],
+            team_b: vec![entry(2)],
+            quality: 1.0,
+            skill_variance: 0.0,
+            win_probability: 0.5,
+            team_a_rating: 1500.0,
+            team_b_rating: 1500.0,
+            max_latency: 0.0,
+        };
+        // Player 2 already queued again, so only player 1 is returned to the queue
+        matchmaker.insert_player(entry(2).player).unwrap();
+
+        requeue_unpublished_match(&mut matchmaker, &m);
+
+        assert_eq!(matchmaker.queue_size(mode), 2);
+        assert!(matchmaker.remove_player(1).is_some());
+        assert!(matchmaker.remove_player(2).is_some());
+    }
 }
@@ -0,0 +1,552 @@
+//! Matchmaking map pools: the set of maps each [`MatchmakingType`] is played on, and how many of
+//! them players may veto or pick. A pool becomes active at its `start_date` and stays active until
+//! the next pool for the same type starts, so staff rotate pools (typically alongside a new
+//! season) by scheduling the next one ahead of time.
+//!
+//! The matchmaking API also uses the active pools to validate the map selections in a queue
+//! request, since the matchmaker itself treats map IDs as opaque strings.
+
+use std::collections::{HashMap, HashSet};
+
+use async_graphql::{Context, InputObject, Object, Result, SimpleObject};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{self, WrapErr};
+use sqlx::PgPool;
+use strum::IntoEnumIterator;
+use uuid::Uuid;
+
+use crate::graphql::errors::graphql_error;
+use crate::maps::SbMapId;
+use crate::matchmaking::matchmaker::MapId;
+use crate::matchmaking::{MapSelectionStyle, MatchmakingType};
+use crate::users::permissions::RequiredPermission;
+
+#[derive(SimpleObject, Debug, Clone)]
+pub struct MatchmakingMapPool {
+    pub id: i32,
+    pub matchmaking_type: MatchmakingType,
+    pub start_date: DateTime<Utc>,
+    pub maps: Vec<SbMapId>,
+    /// The most maps a player may veto, for modes where players veto maps.
+    pub max_veto_count: i32,
+    /// The most maps a player may pick, for modes where players pick maps. `None` means any number
+    /// of the pool's maps may be picked.
+    pub max_pick_count: Option<i32>,
+}
+
+/// When a new map pool should start. Exactly one of the fields must be set.
+#[derive(InputObject, Debug, Clone, Default)]
+pub struct MapPoolSchedule {
+    /// An explicit start date, which must be in the future.
+    pub start_date: Option<DateTime<Utc>>,
+    /// Start the pool when this (future) matchmaking season starts.
+    pub season_id: Option<i32>,
+}
+
+#[derive(InputObject, Debug, Clone)]
+pub struct CreateMatchmakingMapPoolInput {
+    pub matchmaking_type: MatchmakingType,
+    pub maps: Vec<SbMapId>,
+    pub max_veto_count: i32,
+    pub max_pick_count: Option<i32>,
+    pub schedule: MapPoolSchedule,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MapSelectionError {
+    #[error("no map pool is active for {0:?}")]
+    NoActivePool(MatchmakingType),
+    #[error("maps can't be selected for {0:?}")]
+    NotSelectable(MatchmakingType),
+    #[error("at least one map must be picked")]
+    NoPicks,
+    #[error("{count} maps were selected but at most {max} are allowed")]
+    TooMany { count: usize, max: usize },
+    #[error("map {0} was selected more than once")]
+    Duplicate(MapId),
+    #[error("map {0} isn't in the active map pool")]
+    NotInPool(MapId),
+}
+
+/// Checks a player's map selections for `mode` against the mode's active map pool (`None` if it
+/// has none). Selections are vetoes or picks depending on the mode's [`MapSelectionStyle`].
+///
+/// Picks past the pool's `max_pick_count` are dropped rather than rejected, since the cap isn't
+/// known to Node or the client (and so isn't applied when preferences are saved). Node already
+/// trims vetoes to `max_veto_count`, so too many vetoes is an error.
+pub fn validate_map_selections(
+    mode: MatchmakingType,
+    pool: Option<&MatchmakingMapPool>,
+    selections: &mut Vec<MapId>,
+) -> Result<(), MapSelectionError> {
+    let style = mode.map_selection_style();
+    if style == MapSelectionStyle::Fixed {
+        return if selections.is_empty() {
+            Ok(())
+        } else {
+            Err(MapSelectionError::NotSelectable(mode))
+        };
+    }
+    let pool = pool.ok_or(MapSelectionError::NoActivePool(mode))?;
+
+    let max_picks = match style {
+        MapSelectionStyle::Veto => {
+            let max = pool.max_veto_count.max(0) as usize;
+            if selections.len() > max {
+                return Err(MapSelectionError::TooMany {
+                    count: selections.len(),
+                    max,
+                });
+            }
+            None
+        }
+        MapSelectionStyle::Pick => {
+            // The matchmaker only pairs players that share a pick, so a player with no picks
+            // could never be matched
+            if selections.is_empty() {
+                return Err(MapSelectionError::NoPicks);
+            }
+            pool.max_pick_count
+        }
+        MapSelectionStyle::Fixed => unreachable!(),
+    };
+
+    let mut seen = HashSet::with_capacity(selections.len());
+    for selection in selections.iter() {
+        let id = Uuid::parse_str(selection)
+            .map(SbMapId)
+            .map_err(|_| MapSelectionError::NotInPool(selection.clone()))?;
+        if !pool.maps.contains(&id) {
+            return Err(MapSelectionError::NotInPool(selection.clone()));
+        }
+        if !seen.insert(id) {
+            return Err(MapSelectionError::Duplicate(selection.clone()));
+        }
+    }
+    if let Some(max) = max_picks {
+        // Pool input validation ensures this is at least 1
+        selections.truncate(max.max(1) as usize);
+    }
+
+    Ok(())
+}
+
+/// Checks the parts of a new map pool that don't require the database, returning a message
+/// describing the problem if there is one.
+fn validate_pool_input(input: &CreateMatchmakingMapPoolInput) -> Result<(), &'static str> {
+    if input.maps.is_empty() {
+        return Err("A map pool must contain at least one map");
+    }
+    if input.maps.iter().collect::<HashSet<_>>().len() != input.maps.len() {
+        return Err("A map pool can't contain the same map more than once");
+    }
+    if input.max_veto_count < 0 {
+        return Err("maxVetoCount can't be negative");
+    }
+    // Vetoing every map would just leave the matchmaker falling back to the least-vetoed ones
+    if input.matchmaking_type.map_selection_style() == MapSelectionStyle::Veto
+        && input.max_veto_count as usize >= input.maps.len()
+    {
+        return Err("maxVetoCount must leave at least one map unvetoed");
+    }
+    if let Some(max_pick_count) = input.max_pick_count
+        && max_pick_count < 1
+    {
+        return Err("maxPickCount must be at least 1");
+    }
+
+    Ok(())
+}
+
+/// Returns the currently active map pool for each of `types`. Types with no active pool are absent
+/// from the result.
+pub async fn load_current_map_pools(
+    db: &PgPool,
+    types: &[MatchmakingType],
+) -> eyre::Result<HashMap<MatchmakingType, MatchmakingMapPool>> {
+    if types.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let pools = sqlx::query_as!(
+        MatchmakingMapPool,
+        r#"
+            SELECT DISTINCT ON (matchmaking_type)
+                id, matchmaking_type as "matchmaking_type: MatchmakingType",
+                start_date AT TIME ZONE 'UTC' as "start_date!: DateTime<Utc>",
+                coalesce(maps, '{}') as "maps!: Vec<SbMapId>", max_veto_count, max_pick_count
+            FROM matchmaking_map_pools
+            WHERE matchmaking_type = ANY($1) AND start_date <= CURRENT_TIMESTAMP AT TIME ZONE 'UTC'
+            ORDER BY matchmaking_type, start_date DESC
+        "#,
+        types as &[MatchmakingType],
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load current map pools")?;
+
+    Ok(pools
+        .into_iter()
+        .map(|pool| (pool.matchmaking_type, pool))
+        .collect())
+}
+
+/// Resolves a [`MapPoolSchedule`] to the date the pool should start.
+async fn resolve_start_date(db: &PgPool, schedule: &MapPoolSchedule) -> Result<DateTime<Utc>> {
+    let start_date = match (schedule.start_date, schedule.season_id) {
+        (Some(start_date), None) => start_date,
+        (None, Some(season_id)) => sqlx::query_scalar!(
+            r#"
+                SELECT start_date AT TIME ZONE 'UTC' as "start_date!: DateTime<Utc>"
+                FROM matchmaking_seasons
+                WHERE id = $1
+            "#,
+            season_id,
+        )
+        .fetch_optional(db)
+        .await
+        .wrap_err("Failed to load matchmaking season")?
+        .ok_or_else(|| graphql_error("NOT_FOUND", "Matchmaking season not found"))?,
+        _ => {
+            return Err(graphql_error(
+                "BAD_REQUEST",
+                "Exactly one of startDate or seasonId must be specified",
+            ));
+        }
+    };
+
+    // Changing the active pool out from under queued players would invalidate their selections
+    if start_date <= Utc::now() {
+        return Err(graphql_error(
+            "BAD_REQUEST",
+            "Map pools can only be scheduled to start in the future",
+        ));
+    }
+    Ok(start_date)
+}
+
+/// Checks that every map in a new pool exists and can seat a full match of `matchmaking_type`.
+async fn validate_pool_maps(
+    db: &PgPool,
+    matchmaking_type: MatchmakingType,
+    maps: &[SbMapId],
+) -> Result<()> {
+    let ids = maps.iter().map(|id| id.0).collect::<Vec<_>>();
+    let rows = sqlx::query!(
+        r#"
+            SELECT um.id as "id: SbMapId", m.players_melee
+            FROM uploaded_maps um
+            JOIN maps m ON m.hash = um.map_hash
+            WHERE um.id = ANY($1) AND um.removed_at IS NULL
+        "#,
+        &ids,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load map pool maps")?;
+
+    if rows.len() != maps.len() {
+        return Err(graphql_error("BAD_REQUEST", "Some maps could not be found"));
+    }
+    if rows
+        .iter()
+        .any(|row| (row.players_melee as usize) < matchmaking_type.total_players())
+    {
+        return Err(graphql_error(
+            "BAD_REQUEST",
+            "Some maps don't have enough start locations for this matchmaking type",
+        ));
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct MatchmakingMapPoolsQuery;
+
+#[Object]
+impl MatchmakingMapPoolsQuery {
+    /// The map pools currently being used for matchmaking, one per matchmaking type that has one.
+    async fn current_matchmaking_map_pools(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Vec<MatchmakingMapPool>> {
+        let db = ctx.data::<PgPool>()?;
+        let types = MatchmakingType::iter().collect::<Vec<_>>();
+        let mut pools = load_current_map_pools(db, &types)
+            .await?
+            .into_values()
+            .collect::<Vec<_>>();
+        pools.sort_by_key(|pool| pool.id);
+        Ok(pools)
+    }
+
+    /// Map pools that have been scheduled but haven't started yet, soonest first.
+    #[graphql(guard = RequiredPermission::ManageMapPools)]
+    async fn upcoming_matchmaking_map_pools(
+        &self,
+        ctx: &Context<'_>,
+        matchmaking_type: Option<MatchmakingType>,
+    ) -> Result<Vec<MatchmakingMapPool>> {
+        let db = ctx.data::<PgPool>()?;
+        let pools = sqlx::query_as!(
+            MatchmakingMapPool,
+            r#"
+                SELECT id, matchmaking_type as "matchmaking_type: MatchmakingType",
+                    start_date AT TIME ZONE 'UTC' as "start_date!: DateTime<Utc>",
+                    coalesce(maps, '{}') as "maps!: Vec<SbMapId>", max_veto_count, max_pick_count
+                FROM matchmaking_map_pools
+                WHERE start_date > CURRENT_TIMESTAMP AT TIME ZONE 'UTC'
+                    AND ($1::matchmaking_type IS NULL OR matchmaking_type = $1)
+                ORDER BY start_date, id
+            "#,
+            matchmaking_type as Option<MatchmakingType>,
+        )
+        .fetch_all(db)
+        .await
+        .wrap_err("Failed to load upcoming map pools")?;
+
+        Ok(pools)
+    }
+}
+
+#[derive(Default)]
+pub struct MatchmakingMapPoolsMutation;
+
+#[Object]
+impl MatchmakingMapPoolsMutation {
+    /// Schedules a new map pool. It replaces the type's current pool once its start date arrives.
+    #[graphql(guard = RequiredPermission::ManageMapPools)]
+    async fn create_matchmaking_map_pool(
+        &self,
+        ctx: &Context<'_>,
+        input: CreateMatchmakingMapPoolInput,
+    ) -> Result<MatchmakingMapPool> {
+        let db = ctx.data::<PgPool>()?;
+        validate_pool_input(&input).map_err(|message| graphql_error("BAD_REQUEST", message))?;
+        let start_date = resolve_start_date(db, &input.schedule).await?;
+        validate_pool_maps(db, input.matchmaking_type, &input.maps).await?;
+
+        let pool = sqlx::query_as!(
+            MatchmakingMapPool,
+            r#"
+                INSERT INTO matchmaking_map_pools
+                    (matchmaking_type, start_date, maps, max_veto_count, max_pick_count)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING id, matchmaking_type as "matchmaking_type: MatchmakingType",
+                    start_date AT TIME ZONE 'UTC' as "start_date!: DateTime<Utc>",
+                    coalesce(maps, '{}') as "maps!: Vec<SbMapId>", max_veto_count, max_pick_count
+            "#,
+            input.matchmaking_type as MatchmakingType,
+            start_date.naive_utc(),
+            &input.maps as &[SbMapId],
+            input.max_veto_count,
+            input.max_pick_count,
+        )
+        .fetch_one(db)
+        .await
+        .wrap_err("Failed to create map pool")?;
+
+        Ok(pool)
+    }
+
+    /// Changes when an upcoming map pool starts.
+    #[graphql(guard = RequiredPermission::ManageMapPools)]
+    async fn reschedule_matchmaking_map_pool(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        schedule: MapPoolSchedule,
+    ) -> Result<MatchmakingMapPool> {
+        let db = ctx.data::<PgPool>()?;
+        let start_date = resolve_start_date(db, &schedule).await?;
+
+        sqlx::query_as!(
+            MatchmakingMapPool,
+            r#"
+                UPDATE matchmaking_map_pools
+                SET start_date = $2
+                WHERE id = $1 AND start_date > CURRENT_TIMESTAMP AT TIME ZONE 'UTC'
+                RETURNING id, matchmaking_type as "matchmaking_type: MatchmakingType",
+                    start_date AT TIME ZONE 'UTC' as "start_date!: DateTime<Utc>",
+                    coalesce(maps, '{}') as "maps!: Vec<SbMapId>", max_veto_count, max_pick_count
+            "#,
+            id,
+            start_date.naive_utc(),
+        )
+        .fetch_optional(db)
+        .await
+        .wrap_err("Failed to reschedule map pool")?
+        .ok_or_else(|| graphql_error("NOT_FOUND", "Upcoming map pool not found"))
+    }
+
+    /// Deletes a map pool that hasn't started yet.
+    #[graphql(guard = RequiredPermission::ManageMapPools)]
+    async fn delete_matchmaking_map_pool(&self, ctx: &Context<'_>, id: i32) -> Result<bool> {
+        let db = ctx.data::<PgPool>()?;
+        let result = sqlx::query!(
+            r#"
+                DELETE FROM matchmaking_map_pools
+                WHERE id = $1 AND start_date > CURRENT_TIMESTAMP AT TIME ZONE 'UTC'
+            "#,
+            id,
+        )
+        .execute(db)
+        .await
+        .wrap_err("Failed to delete map pool")?;
+
+        if result.rows_affected() == 0 {
+            return Err(graphql_error("NOT_FOUND", "Upcoming map pool not found"));
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map_id(n: u128) -> SbMapId {
+        SbMapId(Uuid::from_u128(n))
+    }
+
+    fn pool(matchmaking_type: MatchmakingType) -> MatchmakingMapPool {
+        MatchmakingMapPool {
+            id: 1,
+            matchmaking_type,
+            start_date: Utc::now(),
+            maps: (1..=4).map(map_id).collect(),
+            max_veto_count: 2,
+            max_pick_count: Some(3),
+        }
+    }
+
+    fn selections(ids: &[u128]) -> Vec<MapId> {
+        ids.iter().map(|&n| map_id(n).0.to_string()).collect()
+    }
+
+    #[test]
+    fn vetoes_are_limited_to_the_pool() {
+        let mode = MatchmakingType::Match1v1;
+        let pool = pool(mode);
+        assert_eq!(
+            validate_map_selections(mode, Some(&pool), &mut selections(&[1, 2])),
+            Ok(())
+        );
+        // Not vetoing anything is always fine
+        assert_eq!(
+            validate_map_selections(mode, Some(&pool), &mut Vec::new()),
+            Ok(())
+        );
+        assert_eq!(
+            validate_map_selections(mode, Some(&pool), &mut selections(&[1, 2, 3])),
+            Err(MapSelectionError::TooMany { count: 3, max: 2 })
+        );
+        assert_eq!(
+            validate_map_selections(mode, Some(&pool), &mut selections(&[5])),
+            Err(MapSelectionError::NotInPool(map_id(5).0.to_string()))
+        );
+        assert_eq!(
+            validate_map_selections(mode, Some(&pool), &mut selections(&[1, 1])),
+            Err(MapSelectionError::Duplicate(map_id(1).0.to_string()))
+        );
+        assert_eq!(
+            validate_map_selections(mode, Some(&pool), &mut vec!["not-a-map".to_string()]),
+            Err(MapSelectionError::NotInPool("not-a-map".to_string()))
+        );
+    }
+
+    #[test]
+    fn picks_must_be_non_empty_and_are_trimmed_to_the_limit() {
+        let mode = MatchmakingType::Match1v1Fastest;
+        let mut pool = pool(mode);
+        let mut picks = selections(&[1, 2, 3]);
+        assert_eq!(
+            validate_map_selections(mode, Some(&pool), &mut picks),
+            Ok(())
+        );
+        assert_eq!(picks, selections(&[1, 2, 3]));
+        assert_eq!(
+            validate_map_selections(mode, Some(&pool), &mut Vec::new()),
+            Err(MapSelectionError::NoPicks)
+        );
+
+        // Extra picks are dropped, keeping the player's first choices
+        let mut picks = selections(&[4, 2, 3, 1]);
+        assert_eq!(
+            validate_map_selections(mode, Some(&pool), &mut picks),
+            Ok(())
+        );
+        assert_eq!(picks, selections(&[4, 2, 3]));
+        // ... but the dropped ones still have to be valid
+        assert_eq!(
+            validate_map_selections(mode, Some(&pool), &mut selections(&[1, 2, 3, 5])),
+            Err(MapSelectionError::NotInPool(map_id(5).0.to_string()))
+        );
+
+        pool.max_pick_count = None;
+        let mut picks = selections(&[1, 2, 3, 4]);
+        assert_eq!(
+            validate_map_selections(mode, Some(&pool), &mut picks),
+            Ok(())
+        );
+        assert_eq!(picks.len(), 4);
+    }
+
+    #[test]
+    fn selections_require_an_active_pool_unless_fixed() {
+        assert_eq!(
+            validate_map_selections(
+                MatchmakingType::Match2v2Fastest,
+                None,
+                &mut selections(&[1])
+            ),
+            Err(MapSelectionError::NoActivePool(
+                MatchmakingType::Match2v2Fastest
+            ))
+        );
+        assert_eq!(
+            validate_map_selections(MatchmakingType::Match3v3Bgh, None, &mut Vec::new()),
+            Ok(())
+        );
+        assert_eq!(
+            validate_map_selections(
+                MatchmakingType::Match3v3Bgh,
+                Some(&pool(MatchmakingType::Match3v3Bgh)),
+                &mut selections(&[1])
+            ),
+            Err(MapSelectionError::NotSelectable(
+                MatchmakingType::Match3v3Bgh
+            ))
+        );
+    }
+
+    #[test]
+    fn pool_input_is_validated() {
+        let input = CreateMatchmakingMapPoolInput {
+            matchmaking_type: MatchmakingType::Match1v1,
+            maps: (1..=4).map(map_id).collect(),
+            max_veto_count: 3,
+            max_pick_count: None,
+            schedule: MapPoolSchedule::default(),
+        };
+        assert_eq!(validate_pool_input(&input), Ok(()));
+
+        let mut too_many_vetoes = input.clone();
+        too_many_vetoes.max_veto_count = 4;
+        assert!(validate_pool_input(&too_many_vetoes).is_err());
+
+        let mut duplicates = input.clone();
+        duplicates.maps.push(map_id(1));
+        assert!(validate_pool_input(&duplicates).is_err());
+
+        let mut empty = input.clone();
+        empty.maps.clear();
+        assert!(validate_pool_input(&empty).is_err());
+
+        let mut no_picks = input.clone();
+        no_picks.matchmaking_type = MatchmakingType::Match1v1Fastest;
+        no_picks.max_pick_count = Some(0);
+        assert!(validate_pool_input(&no_picks).is_err());
+    }
+}
@@ -11,6 +11,7 @@ pub mod api;
 pub mod backbone;
 pub mod config;
 pub mod history;
+pub mod map_pools;
 pub mod matchmaker;
 mod metrics;
 
@@ -73,6 +74,21 @@ pub enum RsMatchmakerErrorCode {
     InvalidTicket,
     /// A requeue ticket was issued by a previous process — server-rs has restarted since.
     StaleTicket,
+    /// The queue request's map selections for a mode didn't fit that mode's active map pool (e.g.
+    /// a map that isn't in the pool, or more selections than the pool allows).
+    InvalidMapSelections,
+}
+
+/// How players choose maps in a matchmaking mode. Mirrors `MapSelectionStyle` in
+/// `common/matchmaking.ts`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MapSelectionStyle {
+    /// Players veto maps they don't want to play, up to the pool's `max_veto_count`.
+    Veto,
+    /// Players pick the maps they're willing to play, up to the pool's `max_pick_count` (if any).
+    Pick,
+    /// Players don't choose maps, the match is played on any map in the pool.
+    Fixed,
 }
 
 /// All of the matchmaking types that we support. These values match the enum values used in the
@@ -129,6 +145,22 @@ impl MatchmakingType {
         self.team_size() * 2
     }
 
+    /// How players choose maps in this mode. Must stay in sync with `MATCHMAKING_MODES` in
+    /// `common/matchmaking.ts`.
+    pub fn map_selection_style(&self) -> MapSelectionStyle {
+        match self {
+            MatchmakingType::Match1v1 => MapSelectionStyle::Veto,
+            MatchmakingType::Match1v1Fastest => MapSelectionStyle::Pick,
+            MatchmakingType::Match2v2 => MapSelectionStyle::Veto,
+            MatchmakingType::Match2v2Bgh => MapSelectionStyle::Fixed,
+            MatchmakingType::Match2v2Hunters => MapSelectionStyle::Fixed,
+            MatchmakingType::Match2v2Fastest => MapSelectionStyle::Pick,
+            MatchmakingType::Match3v3Bgh => MapSelectionStyle::Fixed,
+            MatchmakingType::Match3v3Hunters => MapSelectionStyle::Fixed,
+            MatchmakingType::Match3v3Fastest => MapSelectionStyle::Pick,
+        }
+    }
+
     /// A stable, lowercase string identifier for this mode, matching its serde/DB name. Used as a
     /// Prometheus label value (`mode="2v2bgh"`), so it must stay in sync with the `#[serde(rename)]`
     /// attributes above.
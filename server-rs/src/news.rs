@@ -1,18 +1,25 @@
+use crate::configuration::Settings;
 use crate::graphql::errors::graphql_error;
+use crate::graphql::limits::page_complexity;
 use crate::graphql::schema_builder::SchemaBuilderModule;
+use crate::i18n::TranslationLanguage;
 use crate::redis::RedisPool;
 use async_graphql::connection::{Connection, Edge, query};
 use async_graphql::dataloader::DataLoader;
 use async_graphql::{
-    ComplexObject, Context, Guard, InputObject, MaybeUndefined, Object, SchemaBuilder,
+    ComplexObject, Context, Enum, Guard, InputObject, MaybeUndefined, Object, SchemaBuilder,
 };
 use async_graphql::{Result, SimpleObject};
+use base64::Engine as _;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
 use chrono::{DateTime, Utc};
 use color_eyre::eyre;
 use color_eyre::eyre::WrapErr;
 use deadpool_redis::redis::AsyncCommands;
+use rand::{Rng, rng};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, QueryBuilder};
+use std::collections::HashMap;
 use typeshare::typeshare;
 use uuid::Uuid;
 
@@ -41,9 +48,14 @@ pub struct NewsQuery;
 
 #[Object]
 impl NewsQuery {
+    /// Lists news posts, newest first. Posts are translated into `locale`'s language where a
+    /// translation exists, and are in the default language (English) otherwise.
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(complexity = "page_complexity(first.or(last), 10, child_complexity)")]
     async fn news_posts(
         &self,
         ctx: &Context<'_>,
+        locale: Option<String>,
         include_unpublished: Option<bool>,
         after: Option<String>,
         before: Option<String>,
@@ -57,6 +69,7 @@ impl NewsQuery {
 
         let repo = ctx.data::<NewsPostRepo>()?;
         let include_content = news_posts_content_is_selected(ctx);
+        let language = requested_language(locale.as_deref());
 
         query(
             after,
@@ -72,44 +85,77 @@ impl NewsQuery {
                 let first = first.map(|f| f.clamp(1, 100));
                 let last = last.map(|l| l.clamp(1, 100));
 
-                repo.load_many(
-                    include_unpublished.unwrap_or(false),
-                    after,
-                    before,
-                    first,
-                    last,
-                    include_content,
-                )
-                .await
-                .map(|(has_prev_page, has_next_page, posts)| {
-                    let mut connection = Connection::new(has_prev_page, has_next_page);
-                    connection
-                        .edges
-                        .extend(posts.into_iter().map(|post| Edge::new(post.id, post)));
-                    connection
-                })
+                let (has_prev_page, has_next_page, mut posts) = repo
+                    .load_many(
+                        include_unpublished.unwrap_or(false),
+                        after,
+                        before,
+                        first,
+                        last,
+                        include_content,
+                    )
+                    .await?;
+                repo.localize(&mut posts, language, include_content).await?;
+
+                let mut connection = Connection::new(has_prev_page, has_next_page);
+                connection
+                    .edges
+                    .extend(posts.into_iter().map(|post| Edge::new(post.id, post)));
+                Ok::<_, eyre::Report>(connection)
             },
         )
         .await
     }
 
-    /// Retrieves a single news post by ID. Published posts are visible to everyone; unpublished or
+    /// Retrieves a single news post by ID. Published posts are visible to everyone; drafts and
     /// scheduled posts are only returned to users with the ManageNews permission (for draft preview).
-    async fn news_post(&self, ctx: &Context<'_>, id: Uuid) -> Result<Option<NewsPost>> {
+    /// The post is translated into `locale`'s language if it has a translation for it.
+    async fn news_post(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        locale: Option<String>,
+    ) -> Result<Option<NewsPost>> {
         let repo = ctx.data::<NewsPostRepo>()?;
         let Some(post) = repo.load_one(id).await? else {
             return Ok(None);
         };
 
-        let is_published = post.published_at.is_some_and(|p| p <= Utc::now());
-        if is_published || RequiredPermission::ManageNews.check(ctx).await.is_ok() {
-            Ok(Some(post))
+        if post.is_public(Utc::now()) || RequiredPermission::ManageNews.check(ctx).await.is_ok() {
+            Ok(Some(
+                repo.localize_one(post, requested_language(locale.as_deref()))
+                    .await?,
+            ))
         } else {
             Ok(None)
         }
     }
 
-    async fn urgent_message(&self, ctx: &Context<'_>) -> Result<Option<UrgentMessage>> {
+    /// Retrieves a news post through its preview link, whether or not it's public yet. Returns null
+    /// if the link has been revoked or replaced.
+    async fn news_post_preview(
+        &self,
+        ctx: &Context<'_>,
+        token: String,
+        locale: Option<String>,
+    ) -> Result<Option<NewsPost>> {
+        let repo = ctx.data::<NewsPostRepo>()?;
+        let Some(post) = repo.load_by_preview_token(&token).await? else {
+            return Ok(None);
+        };
+        Ok(Some(
+            repo.localize_one(post, requested_language(locale.as_deref()))
+                .await?,
+        ))
+    }
+
+    /// The urgent message at the top of the home page, if there is one, translated into `locale`'s
+    /// language if it has a translation for it.
+    async fn urgent_message(
+        &self,
+        ctx: &Context<'_>,
+        locale: Option<String>,
+    ) -> Result<Option<UrgentMessage>> {
         let redis = ctx.data::<RedisPool>()?;
         let mut redis = redis.get().await.wrap_err("Could not connect to Redis")?;
 
@@ -118,11 +164,35 @@ impl NewsQuery {
             .await
             .wrap_err("Failed to get urgent message")?;
 
-        if let Some(message) = message {
-            Ok(serde_json::from_str(&message).wrap_err("Failed to deserialize urgent message")?)
-        } else {
-            Ok(None)
-        }
+        let Some(message) = message else {
+            return Ok(None);
+        };
+        let message: UrgentMessage =
+            serde_json::from_str(&message).wrap_err("Failed to deserialize urgent message")?;
+        Ok(Some(
+            message.localized(requested_language(locale.as_deref())),
+        ))
+    }
+}
+
+/// The language to show content in for a client-supplied locale, falling back to the default
+/// language for locales we don't have translations for.
+fn requested_language(locale: Option<&str>) -> TranslationLanguage {
+    locale
+        .and_then(TranslationLanguage::from_locale)
+        .unwrap_or(TranslationLanguage::DEFAULT)
+}
+
+/// Parses the locale an admin wants to translate something into. The default language isn't
+/// translatable, since it's what the untranslated content is written in.
+fn translation_language(locale: &str) -> Result<TranslationLanguage> {
+    match TranslationLanguage::from_locale(locale) {
+        Some(TranslationLanguage::DEFAULT) => Err(graphql_error(
+            "BAD_REQUEST",
+            "Content in the default language is edited on the post itself",
+        )),
+        Some(language) => Ok(language),
+        None => Err(graphql_error("BAD_REQUEST", "Unsupported locale")),
     }
 }
 
@@ -197,6 +267,111 @@ impl NewsMutation {
         Ok(updated)
     }
 
+    /// Restores a post's title, summary, content and cover image to what they were as of one of its
+    /// edits. The post's publish state is left as it is. The restore is recorded as a new edit.
+    /// Restoring an edit to a translation restores that translation, and returns the post in its
+    /// language.
+    #[graphql(guard = RequiredPermission::ManageNews)]
+    async fn news_restore_post_edit(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        edit_id: Uuid,
+    ) -> Result<NewsPost> {
+        let Some(user) = ctx.data::<Option<CurrentUser>>()? else {
+            return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+        };
+
+        let repo = ctx.data::<NewsPostRepo>()?;
+        let restored = repo.restore_edit(id, edit_id, user.id).await?;
+
+        ctx.data::<RedisPool>()?
+            .publish(PublishedNewsMessage::NewsPostsChanged(()))
+            .await?;
+
+        Ok(restored)
+    }
+
+    /// Adds or replaces the translation of a post into `locale`'s language. Returns the post in that
+    /// language.
+    #[graphql(guard = RequiredPermission::ManageNews)]
+    async fn news_set_post_translation(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        locale: String,
+        translation: NewsPostTranslationInput,
+    ) -> Result<NewsPost> {
+        let Some(user) = ctx.data::<Option<CurrentUser>>()? else {
+            return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+        };
+        let language = translation_language(&locale)?;
+
+        let repo = ctx.data::<NewsPostRepo>()?;
+        let translated = repo
+            .set_translation(id, language, translation, user.id, None)
+            .await?;
+
+        ctx.data::<RedisPool>()?
+            .publish(PublishedNewsMessage::NewsPostsChanged(()))
+            .await?;
+
+        Ok(translated)
+    }
+
+    /// Removes the translation of a post into `locale`'s language, so readers of that language see
+    /// the default-language post instead.
+    #[graphql(guard = RequiredPermission::ManageNews)]
+    async fn news_delete_post_translation(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        locale: String,
+    ) -> Result<bool> {
+        let language = translation_language(&locale)?;
+        let repo = ctx.data::<NewsPostRepo>()?;
+        repo.delete_translation(id, language).await?;
+
+        ctx.data::<RedisPool>()?
+            .publish(PublishedNewsMessage::NewsPostsChanged(()))
+            .await?;
+
+        Ok(true)
+    }
+
+    /// Creates a preview link for a post, which lets anyone with the link view it before it's
+    /// public. Replaces (and so revokes) any link the post already had. Returns the link's URL.
+    #[graphql(guard = RequiredPermission::ManageNews)]
+    async fn news_create_preview_link(&self, ctx: &Context<'_>, id: Uuid) -> Result<String> {
+        let token = gen_preview_token();
+        if !ctx
+            .data::<NewsPostRepo>()?
+            .set_preview_token(id, Some(&token))
+            .await?
+        {
+            return Err(graphql_error("NOT_FOUND", "News post not found"));
+        }
+
+        Ok(preview_url(
+            &ctx.data::<Settings>()?.canonical_host,
+            id,
+            &token,
+        ))
+    }
+
+    /// Revokes a post's preview link, if it has one.
+    #[graphql(guard = RequiredPermission::ManageNews)]
+    async fn news_revoke_preview_link(&self, ctx: &Context<'_>, id: Uuid) -> Result<bool> {
+        if !ctx
+            .data::<NewsPostRepo>()?
+            .set_preview_token(id, None)
+            .await?
+        {
+            return Err(graphql_error("NOT_FOUND", "News post not found"));
+        }
+        Ok(true)
+    }
+
     #[graphql(guard = RequiredPermission::ManageNews)]
     async fn news_delete_post(&self, ctx: &Context<'_>, id: Uuid) -> Result<bool> {
         let repo = ctx.data::<NewsPostRepo>()?;
@@ -219,12 +394,32 @@ impl NewsMutation {
         // Save the urgent message to redis
         let redis = ctx.data::<RedisPool>()?;
 
-        let message = message.map(|msg| UrgentMessage {
-            id: Uuid::new_v4(),
-            title: msg.title,
-            message: msg.message,
-            published_at: Utc::now(),
-        });
+        let message = message
+            .map(|msg| {
+                let translations = msg
+                    .translations
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|t| {
+                        let language = translation_language(&t.locale)?;
+                        Ok((
+                            language.code().to_owned(),
+                            UrgentMessageTranslation {
+                                title: t.title,
+                                message: t.message,
+                            },
+                        ))
+                    })
+                    .collect::<Result<HashMap<_, _>>>()?;
+                Ok::<_, async_graphql::Error>(UrgentMessage {
+                    id: Uuid::new_v4(),
+                    title: msg.title,
+                    message: msg.message,
+                    published_at: Utc::now(),
+                    translations,
+                })
+            })
+            .transpose()?;
 
         {
             let mut redis = redis.get().await.wrap_err("Could not connect to Redis")?;
@@ -252,6 +447,42 @@ impl NewsMutation {
     }
 }
 
+/// Whether a news post is still being written or is ready to go out. Published posts only become
+/// public once their `publishedAt` time has passed, which is how posts are scheduled.
+#[derive(Enum, Copy, Clone, Debug, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "news_post_status", rename_all = "snake_case")]
+pub enum NewsPostStatus {
+    Draft,
+    Published,
+}
+
+/// The status of a new post. Clients that predate drafts only send `publishedAt`, so without an
+/// explicit status, a post with a publish time is published and one without is a draft.
+fn creation_status(
+    status: Option<NewsPostStatus>,
+    published_at: Option<DateTime<Utc>>,
+) -> NewsPostStatus {
+    status.unwrap_or(if published_at.is_some() {
+        NewsPostStatus::Published
+    } else {
+        NewsPostStatus::Draft
+    })
+}
+
+/// The status change for an update, or `None` to leave it alone. Like [`creation_status`], a
+/// `publishedAt` change without an explicit status implies one: setting a time publishes the post
+/// and clearing it unpublishes.
+fn update_status(
+    status: Option<NewsPostStatus>,
+    published_at: &MaybeUndefined<DateTime<Utc>>,
+) -> Option<NewsPostStatus> {
+    status.or(match published_at {
+        MaybeUndefined::Undefined => None,
+        MaybeUndefined::Null => Some(NewsPostStatus::Draft),
+        MaybeUndefined::Value(_) => Some(NewsPostStatus::Published),
+    })
+}
+
 #[derive(SimpleObject, Clone, Debug, sqlx::FromRow)]
 #[graphql(complex)]
 pub struct NewsPost {
@@ -262,8 +493,30 @@ pub struct NewsPost {
     pub title: String,
     pub summary: String,
     pub content: String,
+    pub status: NewsPostStatus,
+    /// When the post goes (or went) public. Only meaningful for published posts.
     pub published_at: Option<DateTime<Utc>>,
     pub updated_at: DateTime<Utc>,
+    #[graphql(skip)]
+    pub preview_token: Option<String>,
+    /// The code of the language the title, summary and content have been translated into, or
+    /// `None` if they're the default-language originals.
+    #[graphql(skip)]
+    pub translation_locale: Option<String>,
+}
+
+impl NewsPost {
+    /// Whether the post is visible to everyone as of `now`.
+    fn is_public(&self, now: DateTime<Utc>) -> bool {
+        self.status == NewsPostStatus::Published && self.published_at.is_some_and(|p| p <= now)
+    }
+
+    fn apply_translation(&mut self, locale: String, translation: NewsPostTranslationText) {
+        self.title = translation.title;
+        self.summary = translation.summary;
+        self.content = translation.content;
+        self.translation_locale = Some(locale);
+    }
 }
 
 #[ComplexObject]
@@ -296,10 +549,53 @@ impl NewsPost {
         ))
     }
 
+    /// The language code of the title, summary and content (e.g. `en` or `ko`).
+    async fn locale(&self) -> &str {
+        self.translation_locale
+            .as_deref()
+            .unwrap_or(TranslationLanguage::DEFAULT.code())
+    }
+
     #[graphql(guard = RequiredPermission::ManageNews)]
     async fn edits(&self, ctx: &Context<'_>) -> Result<Vec<NewsPostEdit>> {
         Ok(ctx.data::<NewsPostRepo>()?.load_edits(self.id).await?)
     }
+
+    /// Every translation of this post into languages other than the default.
+    #[graphql(guard = RequiredPermission::ManageNews)]
+    async fn translations(&self, ctx: &Context<'_>) -> Result<Vec<NewsPostTranslation>> {
+        Ok(ctx
+            .data::<NewsPostRepo>()?
+            .load_translations(self.id)
+            .await?)
+    }
+
+    /// The URL of this post's preview link, or null if it doesn't have one.
+    #[graphql(guard = RequiredPermission::ManageNews)]
+    async fn preview_url(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+        let Some(token) = self.preview_token.as_deref() else {
+            return Ok(None);
+        };
+        Ok(Some(preview_url(
+            &ctx.data::<Settings>()?.canonical_host,
+            self.id,
+            token,
+        )))
+    }
+}
+
+/// Number of random bytes in a preview link token.
+const PREVIEW_TOKEN_BYTES: usize = 24;
+
+fn gen_preview_token() -> String {
+    let mut buf = [0u8; PREVIEW_TOKEN_BYTES];
+    rng().fill_bytes(&mut buf);
+    BASE64_URL_SAFE_NO_PAD.encode(buf)
+}
+
+/// The client URL for viewing a post through its preview link.
+fn preview_url(canonical_host: &str, id: Uuid, token: &str) -> String {
+    format!("{canonical_host}/news/{id}?preview={token}")
 }
 
 /// Inserts the `_0.5x` size suffix before the file extension of a cover image path
@@ -331,6 +627,8 @@ fn validate_cover_image_path(path: &str) -> Result<()> {
     }
 }
 
+/// A new news post. If `status` is omitted, the post is published if it has a `publishedAt` and is
+/// a draft otherwise. A published post without a `publishedAt` is published immediately.
 #[derive(Clone, InputObject)]
 pub struct NewsPostCreation {
     pub author_id: Option<SbUserId>,
@@ -338,22 +636,50 @@ pub struct NewsPostCreation {
     pub title: String,
     pub summary: String,
     pub content: String,
+    pub status: Option<NewsPostStatus>,
     pub published_at: Option<DateTime<Utc>>,
 }
 
 /// Partial updates for an existing news post. Fields omitted from the input are left unchanged.
-/// For `publishedAt` and `coverImagePath`, an explicit null clears the current value (e.g.
-/// unpublishing a post), so a single mutation covers editing, publishing now, scheduling, and
-/// unpublishing.
+/// For `publishedAt` and `coverImagePath`, an explicit null clears the current value, so a single
+/// mutation covers editing, publishing now, scheduling, and unpublishing. Changing `publishedAt`
+/// without a `status` publishes the post (or, for null, makes it a draft again). Publishing a post
+/// that has no `publishedAt` publishes it immediately.
 #[derive(InputObject)]
 pub struct NewsPostUpdates {
     pub title: Option<String>,
     pub summary: Option<String>,
     pub content: Option<String>,
+    pub status: Option<NewsPostStatus>,
     pub published_at: MaybeUndefined<DateTime<Utc>>,
     pub cover_image_path: MaybeUndefined<String>,
 }
 
+/// A news post's title, summary and content in a language other than the default.
+#[derive(SimpleObject, Clone, Debug)]
+pub struct NewsPostTranslation {
+    /// The language code (e.g. `ko`).
+    pub locale: String,
+    pub title: String,
+    pub summary: String,
+    pub content: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Clone, InputObject)]
+pub struct NewsPostTranslationInput {
+    pub title: String,
+    pub summary: String,
+    pub content: String,
+}
+
+/// The translated parts of a post, as loaded for display.
+struct NewsPostTranslationText {
+    title: String,
+    summary: String,
+    content: String,
+}
+
 #[derive(SimpleObject, Clone, Debug)]
 #[graphql(complex)]
 pub struct NewsPostEdit {
@@ -366,8 +692,14 @@ pub struct NewsPostEdit {
     pub title: String,
     pub summary: String,
     pub content: String,
+    pub status: NewsPostStatus,
     pub published_at: Option<DateTime<Utc>>,
     pub edited_at: DateTime<Utc>,
+    /// The edit this one restored the content of, if it was made by `newsRestorePostEdit`.
+    pub restored_from: Option<Uuid>,
+    /// The language code of the translation this edit was to, or null for an edit to the
+    /// default-language post.
+    pub locale: Option<String>,
 }
 
 #[ComplexObject]
@@ -406,8 +738,9 @@ impl NewsPostRepo {
         sqlx::query_as!(
             NewsPost,
             r#"
-                SELECT id, author_id as "author_id: _", cover_image_path, title, summary, content, published_at,
-                    updated_at
+                SELECT id, author_id as "author_id: _", cover_image_path, title, summary, content,
+                    status as "status: _", published_at, updated_at, preview_token,
+                    NULL::text as "translation_locale"
                 FROM news_posts
                 WHERE id = $1
             "#,
@@ -423,6 +756,36 @@ impl NewsPostRepo {
         .wrap_err("Failed to load news post")
     }
 
+    async fn load_by_preview_token(&self, token: &str) -> eyre::Result<Option<NewsPost>> {
+        sqlx::query_as!(
+            NewsPost,
+            r#"
+                SELECT id, author_id as "author_id: _", cover_image_path, title, summary, content,
+                    status as "status: _", published_at, updated_at, preview_token,
+                    NULL::text as "translation_locale"
+                FROM news_posts
+                WHERE preview_token = $1
+            "#,
+            token
+        )
+        .fetch_optional(&self.db)
+        .await
+        .wrap_err("Failed to load news post by preview token")
+    }
+
+    /// Sets (or clears) a post's preview token. Returns false if the post doesn't exist.
+    async fn set_preview_token(&self, id: Uuid, token: Option<&str>) -> eyre::Result<bool> {
+        let result = sqlx::query!(
+            "UPDATE news_posts SET preview_token = $2 WHERE id = $1",
+            id,
+            token,
+        )
+        .execute(&self.db)
+        .await
+        .wrap_err("Failed to set news post preview token")?;
+        Ok(result.rows_affected() > 0)
+    }
+
     /// Resolves a cursor Uuid into its `published_at` value. Returns `None` if the referenced post
     /// no longer exists, in which case callers treat the cursor as absent.
     async fn resolve_cursor(&self, id: Uuid) -> eyre::Result<Option<ResolvedCursor>> {
@@ -470,7 +833,8 @@ impl NewsPostRepo {
         }
         query.push(
             r#",
-                        published_at, updated_at
+                        status, published_at, updated_at, preview_token,
+                        NULL::text AS translation_locale
                     FROM news_posts
             "#,
         );
@@ -480,7 +844,9 @@ impl NewsPostRepo {
             let mut query = query.separated(" AND ");
 
             if !include_unpublished {
-                query.push("published_at IS NOT NULL AND published_at <= NOW()");
+                query.push(
+                    "status = 'published' AND published_at IS NOT NULL AND published_at <= NOW()",
+                );
             }
             // The (COALESCE(published_at, 'infinity'), id) row-values compare with the same ordering
             // as `ORDER BY published_at DESC, id DESC` (Postgres sorts NULLs first for DESC, i.e. as
@@ -559,6 +925,13 @@ impl NewsPostRepo {
         post: NewsPostCreation,
         creator_id: SbUserId,
     ) -> eyre::Result<NewsPost> {
+        let now = Utc::now();
+        let status = creation_status(post.status, post.published_at);
+        let published_at = match status {
+            NewsPostStatus::Published => Some(post.published_at.unwrap_or(now)),
+            NewsPostStatus::Draft => post.published_at,
+        };
+
         let mut tx = self
             .db
             .begin()
@@ -569,44 +942,26 @@ impl NewsPostRepo {
             NewsPost,
             r#"
                 INSERT INTO news_posts (author_id, cover_image_path, title, summary, content,
-                    published_at, updated_at)
-                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                    status, published_at, updated_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
                 RETURNING id, author_id as "author_id: _", cover_image_path, title, summary,
-                    content, published_at, updated_at
+                    content, status as "status: _", published_at, updated_at, preview_token,
+                    NULL::text as "translation_locale"
             "#,
             post.author_id as _,
             post.cover_image_path,
             post.title,
             post.summary,
             post.content,
-            post.published_at,
-            Utc::now(),
+            status as _,
+            published_at,
+            now,
         )
         .fetch_one(&mut *tx)
         .await
         .wrap_err("Failed to create news post in DB")?;
 
-        sqlx::query!(
-            r#"
-                INSERT INTO news_post_edits
-                    (post_id, editor_id, author_id, cover_image_path, title, summary, content,
-                        published_at, edited_at)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
-            "#,
-            post.id,
-            creator_id as _,
-            post.author_id as _,
-            post.cover_image_path,
-            post.title,
-            post.summary,
-            post.content,
-            post.published_at,
-            post.updated_at,
-        )
-        .execute(&mut *tx)
-        .await
-        .wrap_err("Failed to update edit log in DB")?;
-
+        record_edit(&mut tx, &post, creator_id, None).await?;
         tx.commit().await.wrap_err("Failed to commit transaction")?;
 
         Ok(post)
@@ -620,6 +975,7 @@ impl NewsPostRepo {
     ) -> Result<NewsPost> {
         // The MaybeUndefined fields become a "should set" flag plus a nullable value: undefined
         // leaves the column unchanged, null clears it, and a value sets it.
+        let status = update_status(updates.status, &updates.published_at);
         let (set_published_at, published_at) = match updates.published_at {
             MaybeUndefined::Undefined => (false, None),
             MaybeUndefined::Null => (true, None),
@@ -645,12 +1001,18 @@ impl NewsPostRepo {
                     title = COALESCE($2, title),
                     summary = COALESCE($3, summary),
                     content = COALESCE($4, content),
-                    published_at = CASE WHEN $5 THEN $6 ELSE published_at END,
+                    status = COALESCE($9, status),
+                    -- A published post always has a publish time, defaulting to right now
+                    published_at = COALESCE(
+                        CASE WHEN $5 THEN $6 ELSE published_at END,
+                        CASE WHEN COALESCE($9, status) = 'published' THEN NOW() END
+                    ),
                     cover_image_path = CASE WHEN $7 THEN $8 ELSE cover_image_path END,
                     updated_at = NOW()
                 WHERE id = $1
                 RETURNING id, author_id as "author_id: _", cover_image_path, title, summary,
-                    content, published_at, updated_at
+                    content, status as "status: _", published_at, updated_at, preview_token,
+                    NULL::text as "translation_locale"
             "#,
             id,
             updates.title,
@@ -660,6 +1022,7 @@ impl NewsPostRepo {
             published_at,
             set_cover_image,
             cover_image_path,
+            status as Option<NewsPostStatus>,
         )
         .fetch_one(&mut *tx)
         .await
@@ -670,27 +1033,67 @@ impl NewsPostRepo {
                 .into(),
         })?;
 
-        sqlx::query!(
+        record_edit(&mut tx, &post, editor_id, None).await?;
+        tx.commit().await.wrap_err("Failed to commit transaction")?;
+
+        Ok(post)
+    }
+
+    async fn restore_edit(&self, id: Uuid, edit_id: Uuid, editor_id: SbUserId) -> Result<NewsPost> {
+        let edit = sqlx::query!(
+            "SELECT locale, title, summary, content FROM news_post_edits WHERE id = $1 AND post_id = $2",
+            edit_id,
+            id,
+        )
+        .fetch_optional(&self.db)
+        .await
+        .wrap_err("Failed to load news post edit")?
+        .ok_or_else(|| graphql_error("NOT_FOUND", "News post edit not found"))?;
+
+        if let Some(locale) = edit.locale {
+            let language = TranslationLanguage::from_locale(&locale)
+                .ok_or_else(|| graphql_error("BAD_REQUEST", "Edit is for an unsupported locale"))?;
+            let translation = NewsPostTranslationInput {
+                title: edit.title,
+                summary: edit.summary,
+                content: edit.content,
+            };
+            return self
+                .set_translation(id, language, translation, editor_id, Some(edit_id))
+                .await;
+        }
+
+        let mut tx = self
+            .db
+            .begin()
+            .await
+            .wrap_err("Failed to start transaction")?;
+
+        let post = sqlx::query_as!(
+            NewsPost,
             r#"
-                INSERT INTO news_post_edits
-                    (post_id, editor_id, author_id, cover_image_path, title, summary, content,
-                        published_at, edited_at)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                UPDATE news_posts p
+                SET
+                    title = e.title,
+                    summary = e.summary,
+                    content = e.content,
+                    cover_image_path = e.cover_image_path,
+                    updated_at = NOW()
+                FROM news_post_edits e
+                WHERE p.id = $1 AND e.id = $2 AND e.post_id = p.id AND e.locale IS NULL
+                RETURNING p.id, p.author_id as "author_id: _", p.cover_image_path, p.title,
+                    p.summary, p.content, p.status as "status: _", p.published_at, p.updated_at,
+                    p.preview_token, NULL::text as "translation_locale"
             "#,
-            post.id,
-            editor_id as _,
-            post.author_id as _,
-            post.cover_image_path,
-            post.title,
-            post.summary,
-            post.content,
-            post.published_at,
-            post.updated_at,
+            id,
+            edit_id,
         )
-        .execute(&mut *tx)
+        .fetch_optional(&mut *tx)
         .await
-        .wrap_err("Failed to update edit log in DB")?;
+        .wrap_err("Failed to restore news post in DB")?
+        .ok_or_else(|| graphql_error("NOT_FOUND", "News post edit not found"))?;
 
+        record_edit(&mut tx, &post, editor_id, Some(edit_id)).await?;
         tx.commit().await.wrap_err("Failed to commit transaction")?;
 
         Ok(post)
@@ -709,12 +1112,174 @@ impl NewsPostRepo {
         Ok(())
     }
 
+    /// Replaces the title, summary and content of each post with its translation into `language`,
+    /// for the posts that have one. `include_content` works as it does for `load_many`.
+    pub async fn localize(
+        &self,
+        posts: &mut [NewsPost],
+        language: TranslationLanguage,
+        include_content: bool,
+    ) -> eyre::Result<()> {
+        if language == TranslationLanguage::DEFAULT || posts.is_empty() {
+            return Ok(());
+        }
+
+        let ids = posts.iter().map(|p| p.id).collect::<Vec<_>>();
+        let translations = sqlx::query!(
+            r#"
+                SELECT post_id, title, summary,
+                    CASE WHEN $3 THEN content ELSE '' END as "content!"
+                FROM news_post_translations
+                WHERE locale = $1 AND post_id = ANY($2)
+            "#,
+            language.code(),
+            &ids,
+            include_content,
+        )
+        .fetch_all(&self.db)
+        .await
+        .wrap_err("Failed to load news post translations")?
+        .into_iter()
+        .map(|row| {
+            (
+                row.post_id,
+                NewsPostTranslationText {
+                    title: row.title,
+                    summary: row.summary,
+                    content: row.content,
+                },
+            )
+        })
+        .collect();
+
+        apply_translations(posts, language, translations);
+        Ok(())
+    }
+
+    async fn localize_one(
+        &self,
+        mut post: NewsPost,
+        language: TranslationLanguage,
+    ) -> eyre::Result<NewsPost> {
+        self.localize(std::slice::from_mut(&mut post), language, true)
+            .await?;
+        Ok(post)
+    }
+
+    async fn load_translations(&self, post_id: Uuid) -> eyre::Result<Vec<NewsPostTranslation>> {
+        sqlx::query_as!(
+            NewsPostTranslation,
+            r#"
+                SELECT locale, title, summary, content, updated_at
+                FROM news_post_translations
+                WHERE post_id = $1
+                ORDER BY locale
+            "#,
+            post_id
+        )
+        .fetch_all(&self.db)
+        .await
+        .wrap_err("Failed to load news post translations")
+    }
+
+    /// Adds or replaces a post's translation into `language`, recording it in the edit log. Returns
+    /// the post in that language.
+    async fn set_translation(
+        &self,
+        id: Uuid,
+        language: TranslationLanguage,
+        translation: NewsPostTranslationInput,
+        editor_id: SbUserId,
+        restored_from: Option<Uuid>,
+    ) -> Result<NewsPost> {
+        let mut tx = self
+            .db
+            .begin()
+            .await
+            .wrap_err("Failed to start transaction")?;
+
+        let mut post = sqlx::query_as!(
+            NewsPost,
+            r#"
+                SELECT id, author_id as "author_id: _", cover_image_path, title, summary, content,
+                    status as "status: _", published_at, updated_at, preview_token,
+                    NULL::text as "translation_locale"
+                FROM news_posts
+                WHERE id = $1
+                FOR UPDATE
+            "#,
+            id
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .wrap_err("Failed to load news post")?
+        .ok_or_else(|| graphql_error("NOT_FOUND", "News post not found"))?;
+
+        let updated_at = sqlx::query_scalar!(
+            r#"
+                INSERT INTO news_post_translations (post_id, locale, title, summary, content)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (post_id, locale) DO UPDATE
+                SET
+                    title = EXCLUDED.title,
+                    summary = EXCLUDED.summary,
+                    content = EXCLUDED.content,
+                    updated_at = NOW()
+                RETURNING updated_at
+            "#,
+            id,
+            language.code(),
+            translation.title,
+            translation.summary,
+            translation.content,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .wrap_err("Failed to save news post translation")?;
+
+        post.apply_translation(
+            language.code().to_owned(),
+            NewsPostTranslationText {
+                title: translation.title,
+                summary: translation.summary,
+                content: translation.content,
+            },
+        );
+        post.updated_at = updated_at;
+
+        record_edit(&mut tx, &post, editor_id, restored_from).await?;
+        tx.commit().await.wrap_err("Failed to commit transaction")?;
+
+        Ok(post)
+    }
+
+    async fn delete_translation(&self, id: Uuid, language: TranslationLanguage) -> Result<()> {
+        let result = sqlx::query!(
+            "DELETE FROM news_post_translations WHERE post_id = $1 AND locale = $2",
+            id,
+            language.code(),
+        )
+        .execute(&self.db)
+        .await
+        .wrap_err("Failed to delete news post translation")?;
+
+        if result.rows_affected() == 0 {
+            return Err(graphql_error(
+                "NOT_FOUND",
+                "News post translation not found",
+            ));
+        }
+
+        Ok(())
+    }
+
     async fn load_edits(&self, post_id: Uuid) -> eyre::Result<Vec<NewsPostEdit>> {
         sqlx::query_as!(
             NewsPostEdit,
             r#"
                 SELECT id, editor_id as "editor_id: _", author_id as "author_id: _",
-                    cover_image_path, title, summary, content, published_at, edited_at
+                    cover_image_path, title, summary, content, status as "status: _", published_at,
+                    edited_at, restored_from, locale
                 FROM news_post_edits
                 WHERE post_id = $1
                 ORDER BY edited_at DESC
@@ -727,6 +1292,53 @@ impl NewsPostRepo {
     }
 }
 
+/// Translates each of `posts` that has an entry in `translations` (keyed by post ID).
+fn apply_translations(
+    posts: &mut [NewsPost],
+    language: TranslationLanguage,
+    mut translations: HashMap<Uuid, NewsPostTranslationText>,
+) {
+    for post in posts {
+        if let Some(translation) = translations.remove(&post.id) {
+            post.apply_translation(language.code().to_owned(), translation);
+        }
+    }
+}
+
+/// Adds a snapshot of `post` (as just written) to its edit log. Snapshots of translated posts are
+/// recorded against the translation's locale.
+async fn record_edit(
+    tx: &mut sqlx::PgConnection,
+    post: &NewsPost,
+    editor_id: SbUserId,
+    restored_from: Option<Uuid>,
+) -> eyre::Result<()> {
+    sqlx::query!(
+        r#"
+            INSERT INTO news_post_edits
+                (post_id, editor_id, author_id, cover_image_path, title, summary, content, status,
+                    published_at, edited_at, restored_from, locale)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        "#,
+        post.id,
+        editor_id as _,
+        post.author_id as _,
+        post.cover_image_path,
+        post.title,
+        post.summary,
+        post.content,
+        post.status as _,
+        post.published_at,
+        post.updated_at,
+        restored_from,
+        post.translation_locale,
+    )
+    .execute(tx)
+    .await
+    .wrap_err("Failed to update edit log in DB")?;
+    Ok(())
+}
+
 /// A resolved pagination cursor: the referenced post's id plus its (nullable) publish time.
 struct ResolvedCursor {
     id: Uuid,
@@ -742,12 +1354,45 @@ pub struct UrgentMessage {
     pub message: String,
     /// The time the message was published (in UTC). This will serialize as an RFC 3339 string.
     pub published_at: DateTime<Utc>,
+    /// Translations of the title and message, keyed by language code.
+    #[graphql(skip)]
+    #[typeshare(skip)]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub translations: HashMap<String, UrgentMessageTranslation>,
+}
+
+impl UrgentMessage {
+    /// Returns this message in `language`, or in the default language if it has no translation for
+    /// it.
+    fn localized(mut self, language: TranslationLanguage) -> Self {
+        if let Some(translation) = self.translations.remove(language.code()) {
+            self.title = translation.title;
+            self.message = translation.message;
+        }
+        self.translations.clear();
+        self
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UrgentMessageTranslation {
+    pub title: String,
+    pub message: String,
 }
 
 #[derive(InputObject, Clone, Debug)]
 pub struct UrgentMessageInput {
     pub title: String,
     pub message: String,
+    /// Translations of the title and message into languages other than the default.
+    pub translations: Option<Vec<UrgentMessageTranslationInput>>,
+}
+
+#[derive(InputObject, Clone, Debug)]
+pub struct UrgentMessageTranslationInput {
+    pub locale: String,
+    pub title: String,
+    pub message: String,
 }
 
 #[typeshare]
@@ -868,6 +1513,81 @@ mod tests {
         }
     }
 
+    fn post(status: NewsPostStatus, published_at: Option<DateTime<Utc>>) -> NewsPost {
+        NewsPost {
+            id: Uuid::nil(),
+            author_id: None,
+            cover_image_path: None,
+            title: "title".into(),
+            summary: "summary".into(),
+            content: "content".into(),
+            status,
+            published_at,
+            updated_at: Utc::now(),
+            preview_token: None,
+            translation_locale: None,
+        }
+    }
+
+    #[test]
+    fn only_published_posts_past_their_publish_time_are_public() {
+        let now = Utc::now();
+        let past = Some(now - chrono::Duration::hours(1));
+        let future = Some(now + chrono::Duration::hours(1));
+
+        assert!(post(NewsPostStatus::Published, past).is_public(now));
+        assert!(post(NewsPostStatus::Published, Some(now)).is_public(now));
+        // Scheduled
+        assert!(!post(NewsPostStatus::Published, future).is_public(now));
+        assert!(!post(NewsPostStatus::Published, None).is_public(now));
+        // Drafts stay hidden even if they have a publish time that has passed
+        assert!(!post(NewsPostStatus::Draft, past).is_public(now));
+        assert!(!post(NewsPostStatus::Draft, None).is_public(now));
+    }
+
+    #[test]
+    fn status_is_implied_by_publish_time_when_omitted() {
+        let now = Utc::now();
+        assert_eq!(creation_status(None, None), NewsPostStatus::Draft);
+        assert_eq!(creation_status(None, Some(now)), NewsPostStatus::Published);
+        assert_eq!(
+            creation_status(Some(NewsPostStatus::Draft), Some(now)),
+            NewsPostStatus::Draft
+        );
+
+        assert_eq!(update_status(None, &MaybeUndefined::Undefined), None);
+        assert_eq!(
+            update_status(None, &MaybeUndefined::Null),
+            Some(NewsPostStatus::Draft)
+        );
+        assert_eq!(
+            update_status(None, &MaybeUndefined::Value(now)),
+            Some(NewsPostStatus::Published)
+        );
+        assert_eq!(
+            update_status(Some(NewsPostStatus::Draft), &MaybeUndefined::Value(now)),
+            Some(NewsPostStatus::Draft)
+        );
+    }
+
+    #[test]
+    fn preview_links() {
+        let token = gen_preview_token();
+        assert_eq!(token.len(), 32);
+        assert!(
+            token
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+            "token '{token}' is not URL-safe",
+        );
+        assert_ne!(token, gen_preview_token());
+
+        assert_eq!(
+            preview_url("https://shieldbattery.net", Uuid::nil(), "abc"),
+            "https://shieldbattery.net/news/00000000-0000-0000-0000-000000000000?preview=abc"
+        );
+    }
+
     #[test]
     fn small_variant_paths() {
         // These cases must match the `smallVariantPath` tests in the Node server's
@@ -890,4 +1610,67 @@ mod tests {
             assert_eq!(small_variant_path(input), expected, "input: {input}");
         }
     }
+
+    #[test]
+    fn translations_replace_content_and_fall_back_when_missing() {
+        let mut posts = [
+            post(NewsPostStatus::Published, None),
+            post(NewsPostStatus::Published, None),
+        ];
+        posts[0].id = Uuid::new_v4();
+        let translated_id = posts[0].id;
+        let translations = HashMap::from([(
+            translated_id,
+            NewsPostTranslationText {
+                title: "제목".to_owned(),
+                summary: "요약".to_owned(),
+                content: "내용".to_owned(),
+            },
+        )]);
+
+        apply_translations(&mut posts, TranslationLanguage::Korean, translations);
+
+        assert_eq!(posts[0].title, "제목");
+        assert_eq!(posts[0].content, "내용");
+        assert_eq!(posts[0].translation_locale.as_deref(), Some("ko"));
+        assert_eq!(posts[1].title, "title");
+        assert_eq!(posts[1].translation_locale, None);
+    }
+
+    #[test]
+    fn requested_locales_fall_back_to_the_default_language() {
+        assert_eq!(
+            requested_language(Some("ko-KR")),
+            TranslationLanguage::Korean
+        );
+        assert_eq!(requested_language(Some("de")), TranslationLanguage::DEFAULT);
+        assert_eq!(requested_language(None), TranslationLanguage::DEFAULT);
+
+        assert!(translation_language("es").is_ok());
+        assert!(translation_language("en").is_err());
+        assert!(translation_language("zh-TW").is_err());
+    }
+
+    #[test]
+    fn urgent_messages_are_localized() {
+        let message = UrgentMessage {
+            id: Uuid::new_v4(),
+            title: "Maintenance".to_owned(),
+            message: "Servers are down".to_owned(),
+            published_at: Utc::now(),
+            translations: HashMap::from([(
+                "ru".to_owned(),
+                UrgentMessageTranslation {
+                    title: "Обслуживание".to_owned(),
+                    message: "Серверы недоступны".to_owned(),
+                },
+            )]),
+        };
+
+        let russian = message.clone().localized(TranslationLanguage::Russian);
+        assert_eq!(russian.title, "Обслуживание");
+        assert!(russian.translations.is_empty());
+        let spanish = message.localized(TranslationLanguage::Spanish);
+        assert_eq!(spanish.title, "Maintenance");
+    }
 }
@@ -0,0 +1,560 @@
+//! Machine-readable feeds of the latest public news posts, for feed readers and community bots:
+//! Atom at `/news/feed.xml`, RSS 2.0 at `/news/rss.xml` and JSON Feed 1.1 at `/news/feed.json`.
+//!
+//! Post content is stored as Markdown and rendered to sanitized HTML for the feeds. Responses carry
+//! an `ETag` and `Last-Modified` so pollers can make conditional requests and get a 304 back while
+//! nothing has changed.
+
+use std::time::{Duration, SystemTime};
+
+use axum::Router;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum_extra::TypedHeader;
+use axum_extra::headers::{CacheControl, ETag, IfModifiedSince, IfNoneMatch, LastModified};
+use chrono::{DateTime, SecondsFormat, Utc};
+use color_eyre::eyre;
+use pulldown_cmark::{Options, Parser};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::configuration::Settings;
+use crate::file_store::FileStore;
+use crate::news::{NewsPost, NewsPostRepo};
+use crate::state::AppState;
+
+/// How many of the latest posts a feed includes.
+const FEED_POST_COUNT: usize = 20;
+/// How long clients and caches may reuse a feed without checking back.
+const FEED_MAX_AGE: Duration = Duration::from_secs(5 * 60);
+const FEED_TITLE: &str = "ShieldBattery News";
+const FEED_DESCRIPTION: &str = "News and updates from ShieldBattery";
+
+pub fn create_news_feed_api() -> Router<AppState> {
+    Router::new()
+        .route("/feed.xml", get(atom_feed))
+        .route("/rss.xml", get(rss_feed))
+        .route("/feed.json", get(json_feed))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum FeedFormat {
+    Atom,
+    Rss,
+    Json,
+}
+
+impl FeedFormat {
+    fn path(self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "/news/feed.xml",
+            FeedFormat::Rss => "/news/rss.xml",
+            FeedFormat::Json => "/news/feed.json",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "application/atom+xml; charset=utf-8",
+            FeedFormat::Rss => "application/rss+xml; charset=utf-8",
+            FeedFormat::Json => "application/feed+json; charset=utf-8",
+        }
+    }
+
+    fn render(self, feed: &Feed) -> String {
+        match self {
+            FeedFormat::Atom => render_atom(feed),
+            FeedFormat::Rss => render_rss(feed),
+            FeedFormat::Json => render_json(feed),
+        }
+    }
+}
+
+/// A news post, ready to be written out in any of the feed formats.
+#[derive(Clone, Debug, PartialEq)]
+struct FeedEntry {
+    id: Uuid,
+    url: String,
+    title: String,
+    summary: String,
+    /// The post's content as sanitized HTML, with its cover image (if any) at the top.
+    content_html: String,
+    image_url: Option<String>,
+    published_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Feed {
+    /// The site's base URL (e.g. `https://shieldbattery.net`), with no trailing slash.
+    host: String,
+    /// The path this feed is served from, for its self link.
+    self_path: &'static str,
+    entries: Vec<FeedEntry>,
+}
+
+impl Feed {
+    /// When anything in the feed last changed, or `None` if it has no entries.
+    fn last_modified(&self) -> Option<DateTime<Utc>> {
+        self.entries
+            .iter()
+            .map(|e| e.updated_at.max(e.published_at))
+            .max()
+    }
+}
+
+/// Renders a post's Markdown content to HTML, removing anything that isn't safe to show in a feed
+/// reader (scripts, event handlers, etc.).
+fn render_markdown(content: &str) -> String {
+    let parser = Parser::new_ext(
+        content,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+    );
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, parser);
+    ammonia::clean(&html)
+}
+
+fn feed_entry(post: NewsPost, host: &str, file_store: &FileStore) -> eyre::Result<FeedEntry> {
+    let image_url = post
+        .cover_image_path
+        .as_deref()
+        .map(|path| file_store.url(path))
+        .transpose()?;
+
+    let mut content_html = String::new();
+    if let Some(image_url) = &image_url {
+        content_html.push_str(&format!(
+            "<p><img src=\"{}\" alt=\"\"></p>\n",
+            escape_xml(image_url)
+        ));
+    }
+    content_html.push_str(&render_markdown(&post.content));
+
+    Ok(FeedEntry {
+        id: post.id,
+        url: format!("{host}/news/{}", post.id),
+        title: post.title,
+        summary: post.summary,
+        content_html,
+        image_url,
+        // Only public posts make it into feeds, and those always have a publish time
+        published_at: post.published_at.unwrap_or(post.updated_at),
+        updated_at: post.updated_at,
+    })
+}
+
+/// Escapes text for use in XML content or attribute values. Characters XML doesn't allow at all
+/// (most control characters) are dropped.
+fn escape_xml(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            '\t' | '\n' | '\r' => result.push(c),
+            c if c.is_control() => {}
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn atom_date(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn render_atom(feed: &Feed) -> String {
+    let host = escape_xml(&feed.host);
+    let updated = feed.last_modified().unwrap_or(DateTime::UNIX_EPOCH);
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("  <id>{host}/news</id>\n"));
+    xml.push_str(&format!("  <title>{FEED_TITLE}</title>\n"));
+    xml.push_str(&format!("  <subtitle>{FEED_DESCRIPTION}</subtitle>\n"));
+    xml.push_str(&format!("  <updated>{}</updated>\n", atom_date(updated)));
+    xml.push_str("  <author><name>ShieldBattery</name></author>\n");
+    xml.push_str(&format!(
+        "  <link rel=\"self\" type=\"application/atom+xml\" href=\"{host}{}\"/>\n",
+        feed.self_path
+    ));
+    xml.push_str(&format!(
+        "  <link rel=\"alternate\" type=\"text/html\" href=\"{host}/news\"/>\n"
+    ));
+    for entry in &feed.entries {
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!("    <id>urn:uuid:{}</id>\n", entry.id));
+        xml.push_str(&format!(
+            "    <title>{}</title>\n",
+            escape_xml(&entry.title)
+        ));
+        xml.push_str(&format!(
+            "    <link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>\n",
+            escape_xml(&entry.url)
+        ));
+        xml.push_str(&format!(
+            "    <published>{}</published>\n",
+            atom_date(entry.published_at)
+        ));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            atom_date(entry.updated_at.max(entry.published_at))
+        ));
+        xml.push_str(&format!(
+            "    <summary>{}</summary>\n",
+            escape_xml(&entry.summary)
+        ));
+        xml.push_str(&format!(
+            "    <content type=\"html\">{}</content>\n",
+            escape_xml(&entry.content_html)
+        ));
+        xml.push_str("  </entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn render_rss(feed: &Feed) -> String {
+    let host = escape_xml(&feed.host);
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str("  <channel>\n");
+    xml.push_str(&format!("    <title>{FEED_TITLE}</title>\n"));
+    xml.push_str(&format!("    <link>{host}/news</link>\n"));
+    xml.push_str(&format!(
+        "    <description>{FEED_DESCRIPTION}</description>\n"
+    ));
+    xml.push_str(&format!(
+        "    <atom:link rel=\"self\" type=\"application/rss+xml\" href=\"{host}{}\"/>\n",
+        feed.self_path
+    ));
+    if let Some(last_modified) = feed.last_modified() {
+        xml.push_str(&format!(
+            "    <lastBuildDate>{}</lastBuildDate>\n",
+            last_modified.to_rfc2822()
+        ));
+    }
+    for entry in &feed.entries {
+        xml.push_str("    <item>\n");
+        xml.push_str(&format!(
+            "      <title>{}</title>\n",
+            escape_xml(&entry.title)
+        ));
+        xml.push_str(&format!("      <link>{}</link>\n", escape_xml(&entry.url)));
+        xml.push_str(&format!(
+            "      <guid isPermaLink=\"false\">{}</guid>\n",
+            entry.id
+        ));
+        xml.push_str(&format!(
+            "      <pubDate>{}</pubDate>\n",
+            entry.published_at.to_rfc2822()
+        ));
+        xml.push_str(&format!(
+            "      <description>{}</description>\n",
+            escape_xml(&entry.content_html)
+        ));
+        xml.push_str("    </item>\n");
+    }
+    xml.push_str("  </channel>\n");
+    xml.push_str("</rss>\n");
+    xml
+}
+
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'static str,
+    description: &'static str,
+    home_page_url: String,
+    feed_url: String,
+    items: Vec<JsonFeedItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem<'a> {
+    id: String,
+    url: &'a str,
+    title: &'a str,
+    summary: &'a str,
+    content_html: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<&'a str>,
+    date_published: String,
+    date_modified: String,
+}
+
+fn render_json(feed: &Feed) -> String {
+    let json = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: FEED_TITLE,
+        description: FEED_DESCRIPTION,
+        home_page_url: format!("{}/news", feed.host),
+        feed_url: format!("{}{}", feed.host, feed.self_path),
+        items: feed
+            .entries
+            .iter()
+            .map(|entry| JsonFeedItem {
+                id: entry.id.to_string(),
+                url: &entry.url,
+                title: &entry.title,
+                summary: &entry.summary,
+                content_html: &entry.content_html,
+                image: entry.image_url.as_deref(),
+                date_published: atom_date(entry.published_at),
+                date_modified: atom_date(entry.updated_at.max(entry.published_at)),
+            })
+            .collect(),
+    };
+    // Serializing plain strings can't fail
+    serde_json::to_string(&json).expect("Failed to serialize JSON feed")
+}
+
+/// A strong ETag for a rendered feed, derived from its contents.
+fn feed_etag(body: &str) -> ETag {
+    let digest = Sha256::digest(body.as_bytes());
+    let hex = digest[..16]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    format!("\"{hex}\"")
+        .parse()
+        .expect("hex digests are valid ETags")
+}
+
+/// Whether the client's cached copy (as described by its conditional headers) is still current.
+/// `If-None-Match` takes precedence over `If-Modified-Since` when both are sent.
+fn is_not_modified(
+    if_none_match: Option<&IfNoneMatch>,
+    if_modified_since: Option<&IfModifiedSince>,
+    etag: &ETag,
+    last_modified: SystemTime,
+) -> bool {
+    if let Some(if_none_match) = if_none_match {
+        !if_none_match.precondition_passes(etag)
+    } else if let Some(if_modified_since) = if_modified_since {
+        !if_modified_since.is_modified(last_modified)
+    } else {
+        false
+    }
+}
+
+async fn load_feed(
+    db: PgPool,
+    settings: &Settings,
+    file_store: &FileStore,
+    format: FeedFormat,
+) -> eyre::Result<Feed> {
+    let (_, _, posts) = NewsPostRepo::new(db)
+        .load_many(false, None, None, Some(FEED_POST_COUNT), None, true)
+        .await?;
+    let entries = posts
+        .into_iter()
+        .map(|post| feed_entry(post, &settings.canonical_host, file_store))
+        .collect::<eyre::Result<Vec<_>>>()?;
+    Ok(Feed {
+        host: settings.canonical_host.clone(),
+        self_path: format.path(),
+        entries,
+    })
+}
+
+async fn serve_feed(
+    state: AppState,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
+    format: FeedFormat,
+) -> Response {
+    let feed = match load_feed(
+        state.db_pool.clone(),
+        &state.settings,
+        &state.file_store,
+        format,
+    )
+    .await
+    {
+        Ok(feed) => feed,
+        Err(e) => {
+            error!("Failed to load news feed: {e:?}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error").into_response();
+        }
+    };
+
+    let body = format.render(&feed);
+    let etag = feed_etag(&body);
+    let last_modified = SystemTime::from(feed.last_modified().unwrap_or(DateTime::UNIX_EPOCH));
+    let cache_control = CacheControl::new().with_public().with_max_age(FEED_MAX_AGE);
+    let headers = (
+        TypedHeader(etag.clone()),
+        TypedHeader(LastModified::from(last_modified)),
+        TypedHeader(cache_control),
+    );
+
+    if is_not_modified(
+        if_none_match.as_ref().map(|h| &h.0),
+        if_modified_since.as_ref().map(|h| &h.0),
+        &etag,
+        last_modified,
+    ) {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+    (headers, [(CONTENT_TYPE, format.content_type())], body).into_response()
+}
+
+async fn atom_feed(
+    State(state): State<AppState>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
+) -> Response {
+    serve_feed(state, if_none_match, if_modified_since, FeedFormat::Atom).await
+}
+
+async fn rss_feed(
+    State(state): State<AppState>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
+) -> Response {
+    serve_feed(state, if_none_match, if_modified_since, FeedFormat::Rss).await
+}
+
+async fn json_feed(
+    State(state): State<AppState>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
+) -> Response {
+    serve_feed(state, if_none_match, if_modified_since, FeedFormat::Json).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> FeedEntry {
+        let published_at = DateTime::parse_from_rfc3339("2026-10-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        FeedEntry {
+            id: Uuid::nil(),
+            url: "https://shieldbattery.net/news/00000000-0000-0000-0000-000000000000".into(),
+            title: "Version <9> & more".into(),
+            summary: "It's here".into(),
+            content_html: render_markdown("Hello **world**"),
+            image_url: None,
+            published_at,
+            updated_at: published_at + chrono::Duration::hours(1),
+        }
+    }
+
+    fn feed(self_path: &'static str) -> Feed {
+        Feed {
+            host: "https://shieldbattery.net".into(),
+            self_path,
+            entries: vec![entry()],
+        }
+    }
+
+    #[test]
+    fn markdown_is_rendered_and_sanitized() {
+        assert_eq!(
+            render_markdown("Hello **world**"),
+            "<p>Hello <strong>world</strong></p>\n"
+        );
+        let html = render_markdown(
+            "<script>alert(1)</script>\n\n[link](javascript:alert(1)) <img src=x onerror=alert(1)>",
+        );
+        assert!(!html.contains("<script"), "{html}");
+        assert!(!html.contains("javascript:"), "{html}");
+        assert!(!html.contains("onerror"), "{html}");
+    }
+
+    #[test]
+    fn xml_escaping() {
+        assert_eq!(
+            escape_xml("a < b & \"c\" > 'd'\u{0}"),
+            "a &lt; b &amp; &quot;c&quot; &gt; &apos;d&apos;"
+        );
+    }
+
+    #[test]
+    fn atom_feed_contents() {
+        let xml = render_atom(&feed("/news/feed.xml"));
+        assert!(xml.contains("<title>Version &lt;9&gt; &amp; more</title>"));
+        assert!(xml.contains("<id>urn:uuid:00000000-0000-0000-0000-000000000000</id>"));
+        assert!(xml.contains("<published>2026-10-01T12:00:00Z</published>"));
+        assert!(xml.contains("<updated>2026-10-01T13:00:00Z</updated>"));
+        assert!(xml.contains("href=\"https://shieldbattery.net/news/feed.xml\""));
+        assert!(xml.contains(
+            "<content type=\"html\">&lt;p&gt;Hello &lt;strong&gt;world&lt;/strong&gt;&lt;/p&gt;\n</content>"
+        ));
+    }
+
+    #[test]
+    fn rss_feed_contents() {
+        let xml = render_rss(&feed("/news/rss.xml"));
+        assert!(xml.contains("<pubDate>Thu, 1 Oct 2026 12:00:00 +0000</pubDate>"));
+        assert!(xml.contains("<lastBuildDate>Thu, 1 Oct 2026 13:00:00 +0000</lastBuildDate>"));
+        assert!(
+            xml.contains("<guid isPermaLink=\"false\">00000000-0000-0000-0000-000000000000</guid>")
+        );
+    }
+
+    #[test]
+    fn json_feed_contents() {
+        let json: serde_json::Value =
+            serde_json::from_str(&render_json(&feed("/news/feed.json"))).unwrap();
+        assert_eq!(json["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(json["feed_url"], "https://shieldbattery.net/news/feed.json");
+        let item = &json["items"][0];
+        assert_eq!(item["title"], "Version <9> & more");
+        assert_eq!(item["date_published"], "2026-10-01T12:00:00Z");
+        assert_eq!(item["date_modified"], "2026-10-01T13:00:00Z");
+        assert!(item.get("image").is_none());
+    }
+
+    #[test]
+    fn conditional_requests() {
+        let body = render_atom(&feed("/news/feed.xml"));
+        let etag = feed_etag(&body);
+        let last_modified = SystemTime::from(feed("/news/feed.xml").last_modified().unwrap());
+
+        let matching = IfNoneMatch::from(etag.clone());
+        let other = IfNoneMatch::from(feed_etag("something else"));
+        let since_then = IfModifiedSince::from(last_modified);
+        let before_then = IfModifiedSince::from(last_modified - Duration::from_secs(60));
+
+        assert!(!is_not_modified(None, None, &etag, last_modified));
+        assert!(is_not_modified(Some(&matching), None, &etag, last_modified));
+        assert!(!is_not_modified(Some(&other), None, &etag, last_modified));
+        assert!(is_not_modified(
+            None,
+            Some(&since_then),
+            &etag,
+            last_modified
+        ));
+        assert!(!is_not_modified(
+            None,
+            Some(&before_then),
+            &etag,
+            last_modified
+        ));
+        // If-None-Match wins when both are present
+        assert!(!is_not_modified(
+            Some(&other),
+            Some(&since_then),
+            &etag,
+            last_modified
+        ));
+    }
+}
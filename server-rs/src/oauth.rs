@@ -0,0 +1,1103 @@
+//! Signing in with (and linking) external identities via OAuth: Discord, Google and GitHub.
+//!
+//! This follows the same shape as the Twitch linking flow (see `twitch.rs`): the client asks us for
+//! an authorize URL (`oauthStartLink`/`oauthStartLogin`), which embeds a server-issued, single-use
+//! `state` stored in Redis. The client opens that URL, captures the `code`/`state` from the redirect
+//! back to `<canonical host>/oauth/<provider>/callback`, and hands them to `oauthCompleteLink` or
+//! `oauthCompleteLogin`. The code exchange (which needs the client secret) happens entirely
+//! server-side.
+//!
+//! Starting a flow also returns a PKCE code verifier, which is never part of a URL and which the
+//! client has to hand back when completing the flow. That ties the flow to the client that started
+//! it: a `code` and `state` leaked from the redirect (or planted in someone else's client to sign
+//! them into the wrong account) are useless without it.
+//!
+//! Signing in only works for identities that were previously linked to an account by a logged-in
+//! user; we never create accounts (or attach identities) based on a provider's email address, since
+//! that would let anyone who controls a matching external account take over a ShieldBattery one.
+//! Linked identities are stored in `user_oauth_identities`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_graphql::{Context, Object, SimpleObject};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{self, Context as _, eyre};
+use deadpool_redis::redis::AsyncCommands;
+use jsonwebtoken::EncodingKey;
+use rand::{Rng, rng};
+use reqwest::header;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tracing::error;
+use url::Url;
+use uuid::Uuid;
+
+use crate::configuration::{OAuthProviderSettings, Settings};
+use crate::graphql::errors::graphql_error;
+use crate::redis::RedisPool;
+use crate::sessions::begin_session;
+use crate::users::ban_evasion::ban_user_if_needed;
+use crate::users::{CacheBehavior, CurrentUser, CurrentUserRepo, SbUserId};
+
+/// How long a pending sign-in or link request (the server-issued `state`) stays valid.
+const OAUTH_STATE_TTL_SECONDS: u64 = 600;
+
+/// How long to wait for a connection to a provider.
+const PROVIDER_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a request to a provider (exchanging a code or fetching userinfo) can take in total. The
+/// user's sign-in request waits on these, so a stalled provider shouldn't hold it open for long.
+const PROVIDER_REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// GitHub's API rejects requests without a User-Agent.
+const USER_AGENT: &str = "ShieldBattery";
+
+/// How many random bytes go into a PKCE code verifier. Encoded, this is 43 characters, the minimum
+/// RFC 7636 allows.
+const CODE_VERIFIER_BYTES: usize = 32;
+
+fn oauth_state_key(state: &str) -> String {
+    format!("oauth:state:{state}")
+}
+
+fn gen_code_verifier() -> String {
+    let mut bytes = [0u8; CODE_VERIFIER_BYTES];
+    rng().fill_bytes(&mut bytes);
+    data_encoding::BASE64URL_NOPAD.encode(&bytes)
+}
+
+/// The PKCE `S256` code challenge for a verifier.
+fn code_challenge(code_verifier: &str) -> String {
+    data_encoding::BASE64URL_NOPAD.encode(&Sha256::digest(code_verifier.as_bytes()))
+}
+
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type, async_graphql::Enum,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "oauth_provider", rename_all = "snake_case")]
+pub enum OAuthProvider {
+    Discord,
+    Google,
+    Github,
+}
+
+impl OAuthProvider {
+    const ALL: [OAuthProvider; 3] = [
+        OAuthProvider::Discord,
+        OAuthProvider::Google,
+        OAuthProvider::Github,
+    ];
+
+    /// The provider's name as used in redirect URIs.
+    fn slug(self) -> &'static str {
+        match self {
+            OAuthProvider::Discord => "discord",
+            OAuthProvider::Google => "google",
+            OAuthProvider::Github => "github",
+        }
+    }
+
+    fn display_name(self) -> &'static str {
+        match self {
+            OAuthProvider::Discord => "Discord",
+            OAuthProvider::Google => "Google",
+            OAuthProvider::Github => "GitHub",
+        }
+    }
+
+    /// The scopes we request, which are just enough to identify the user.
+    fn scopes(self) -> &'static str {
+        match self {
+            OAuthProvider::Discord => "identify email",
+            OAuthProvider::Google => "openid email profile",
+            OAuthProvider::Github => "read:user user:email",
+        }
+    }
+
+    fn default_endpoints(self) -> OAuthEndpoints {
+        let (authorize_url, token_url, userinfo_url) = match self {
+            OAuthProvider::Discord => (
+                "https://discord.com/oauth2/authorize",
+                "https://discord.com/api/oauth2/token",
+                "https://discord.com/api/users/@me",
+            ),
+            OAuthProvider::Google => (
+                "https://accounts.google.com/o/oauth2/v2/auth",
+                "https://oauth2.googleapis.com/token",
+                "https://openidconnect.googleapis.com/v1/userinfo",
+            ),
+            OAuthProvider::Github => (
+                "https://github.com/login/oauth/authorize",
+                "https://github.com/login/oauth/access_token",
+                "https://api.github.com/user",
+            ),
+        };
+        OAuthEndpoints {
+            authorize_url: authorize_url.into(),
+            token_url: token_url.into(),
+            userinfo_url: userinfo_url.into(),
+        }
+    }
+
+    /// Parses the provider's userinfo response into the identity we store.
+    fn parse_identity(self, body: &str) -> eyre::Result<ExternalIdentity> {
+        Ok(match self {
+            OAuthProvider::Discord => {
+                let user: DiscordUser = serde_json::from_str(body)?;
+                ExternalIdentity {
+                    provider_user_id: user.id,
+                    username: user.username,
+                    email: user.email.filter(|_| user.verified.unwrap_or(false)),
+                }
+            }
+            OAuthProvider::Google => {
+                let user: GoogleUserInfo = serde_json::from_str(body)?;
+                ExternalIdentity {
+                    provider_user_id: user.sub,
+                    username: user.name.or_else(|| user.email.clone()).unwrap_or_default(),
+                    email: user.email.filter(|_| user.email_verified.unwrap_or(false)),
+                }
+            }
+            OAuthProvider::Github => {
+                let user: GithubUser = serde_json::from_str(body)?;
+                ExternalIdentity {
+                    provider_user_id: user.id.to_string(),
+                    username: user.login,
+                    // The profile email is just whatever the user chose to make public, so it's
+                    // kept for display but isn't treated as verified anywhere.
+                    email: user.email,
+                }
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct OAuthEndpoints {
+    authorize_url: String,
+    token_url: String,
+    userinfo_url: String,
+}
+
+#[derive(Deserialize)]
+struct DiscordUser {
+    id: String,
+    username: String,
+    email: Option<String>,
+    verified: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct GoogleUserInfo {
+    sub: String,
+    name: Option<String>,
+    email: Option<String>,
+    email_verified: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct GithubUser {
+    id: u64,
+    login: String,
+    email: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// Who an access token belongs to, normalized across providers.
+#[derive(Debug, PartialEq, Eq)]
+struct ExternalIdentity {
+    provider_user_id: String,
+    username: String,
+    /// Only set when the provider tells us the address is verified (or, for GitHub, when it's the
+    /// user's public profile email).
+    email: Option<String>,
+}
+
+/// What we stash in Redis for a pending `state`: which provider the flow is for, the redirect URI
+/// baked into the authorize URL (which must be replayed in the token exchange), the PKCE challenge
+/// for the verifier we gave to the client that started it, and, for a link, the user who started
+/// it. `user_id` is `None` for a sign-in.
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingOAuth {
+    provider: OAuthProvider,
+    user_id: Option<i32>,
+    redirect_uri: String,
+    code_challenge: String,
+}
+
+struct ProviderClient {
+    client_id: String,
+    client_secret: SecretString,
+    endpoints: OAuthEndpoints,
+}
+
+/// A client for the OAuth providers we've been configured with. Providers without credentials are
+/// absent, so the client may be empty (in which case none are offered).
+pub struct OAuthClient {
+    http: reqwest::Client,
+    canonical_host: String,
+    providers: HashMap<OAuthProvider, ProviderClient>,
+}
+
+impl OAuthClient {
+    pub fn from_settings(settings: &Settings) -> Arc<Self> {
+        let configured = [
+            (OAuthProvider::Discord, &settings.oauth.discord),
+            (OAuthProvider::Google, &settings.oauth.google),
+            (OAuthProvider::Github, &settings.oauth.github),
+        ];
+        let providers = configured
+            .into_iter()
+            .filter_map(|(provider, s)| {
+                s.as_ref()
+                    .map(|s| (provider, provider.default_endpoints(), s))
+            })
+            .collect::<Vec<_>>();
+        Arc::new(Self::new(&settings.canonical_host, providers))
+    }
+
+    fn new<'a>(
+        canonical_host: &str,
+        providers: impl IntoIterator<Item = (OAuthProvider, OAuthEndpoints, &'a OAuthProviderSettings)>,
+    ) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .connect_timeout(PROVIDER_CONNECT_TIMEOUT)
+                .timeout(PROVIDER_REQUEST_TIMEOUT)
+                .build()
+                .expect("OAuth HTTP client configuration should be valid"),
+            canonical_host: canonical_host.trim_end_matches('/').to_string(),
+            providers: providers
+                .into_iter()
+                .map(|(provider, endpoints, settings)| {
+                    (
+                        provider,
+                        ProviderClient {
+                            client_id: settings.client_id.clone(),
+                            client_secret: settings.client_secret.clone(),
+                            endpoints,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// The providers users can currently sign in with, in a stable order.
+    fn configured_providers(&self) -> Vec<OAuthProvider> {
+        OAuthProvider::ALL
+            .into_iter()
+            .filter(|p| self.providers.contains_key(p))
+            .collect()
+    }
+
+    fn provider(&self, provider: OAuthProvider) -> async_graphql::Result<&ProviderClient> {
+        self.providers.get(&provider).ok_or_else(|| {
+            graphql_error(
+                "OAUTH_PROVIDER_NOT_CONFIGURED",
+                format!(
+                    "Signing in with {} is not available",
+                    provider.display_name()
+                ),
+            )
+        })
+    }
+
+    fn redirect_uri(&self, provider: OAuthProvider) -> String {
+        format!("{}/oauth/{}/callback", self.canonical_host, provider.slug())
+    }
+
+    /// Builds the provider's authorize URL for the given `state`. `redirect_uri` must be reused
+    /// verbatim in `exchange_code`, along with the verifier for `code_challenge`.
+    fn authorize_url(
+        &self,
+        provider: OAuthProvider,
+        state: &str,
+        redirect_uri: &str,
+        code_challenge: &str,
+    ) -> eyre::Result<String> {
+        let client = self
+            .providers
+            .get(&provider)
+            .ok_or_else(|| eyre!("{provider:?} is not configured"))?;
+        let url = Url::parse_with_params(
+            &client.endpoints.authorize_url,
+            &[
+                ("client_id", client.client_id.as_str()),
+                ("redirect_uri", redirect_uri),
+                ("response_type", "code"),
+                ("scope", provider.scopes()),
+                ("state", state),
+                ("code_challenge", code_challenge),
+                ("code_challenge_method", "S256"),
+            ],
+        )?;
+        Ok(url.to_string())
+    }
+
+    /// Exchanges an authorization `code` for an access token, then looks up who it belongs to.
+    async fn fetch_identity(
+        &self,
+        provider: OAuthProvider,
+        code: &str,
+        redirect_uri: &str,
+        code_verifier: &str,
+    ) -> eyre::Result<ExternalIdentity> {
+        let client = self
+            .providers
+            .get(&provider)
+            .ok_or_else(|| eyre!("{provider:?} is not configured"))?;
+
+        let resp = self
+            .http
+            .post(&client.endpoints.token_url)
+            // GitHub responds with a form-encoded body unless asked for JSON
+            .header(header::ACCEPT, "application/json")
+            .form(&[
+                ("client_id", client.client_id.as_str()),
+                ("client_secret", client.client_secret.expose_secret()),
+                ("code", code),
+                ("grant_type", "authorization_code"),
+                ("redirect_uri", redirect_uri),
+                ("code_verifier", code_verifier),
+            ])
+            .send()
+            .await
+            .wrap_err("Failed to send token request")?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(eyre!(
+                "{provider:?} token exchange failed ({status}): {body}"
+            ));
+        }
+        // GitHub reports errors with a 200 and an `error` body, which fails to parse here
+        let token: TokenResponse = resp
+            .json()
+            .await
+            .wrap_err_with(|| format!("Failed to parse {provider:?} token response"))?;
+
+        let resp = self
+            .http
+            .get(&client.endpoints.userinfo_url)
+            .header(header::ACCEPT, "application/json")
+            .header(header::USER_AGENT, USER_AGENT)
+            .bearer_auth(&token.access_token)
+            .send()
+            .await
+            .wrap_err("Failed to send userinfo request")?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(eyre!("{provider:?} userinfo failed ({status}): {body}"));
+        }
+        let body = resp.text().await.wrap_err("Failed to read userinfo")?;
+        provider
+            .parse_identity(&body)
+            .wrap_err_with(|| format!("Failed to parse {provider:?} userinfo response"))
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+// Persistence
+// ---------------------------------------------------------------------------------------------
+
+/// An external identity linked to a ShieldBattery account.
+#[derive(SimpleObject, Clone, Debug)]
+pub struct OAuthIdentity {
+    pub provider: OAuthProvider,
+    /// The user's name on the provider's side, as of when they last linked or signed in.
+    pub provider_username: String,
+    pub linked_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
+}
+
+async fn load_identities(pool: &PgPool, user_id: SbUserId) -> eyre::Result<Vec<OAuthIdentity>> {
+    sqlx::query_as!(
+        OAuthIdentity,
+        r#"
+            SELECT provider as "provider: OAuthProvider", provider_username, linked_at,
+                last_login_at
+            FROM user_oauth_identities
+            WHERE user_id = $1
+            ORDER BY provider
+        "#,
+        user_id as _,
+    )
+    .fetch_all(pool)
+    .await
+    .wrap_err("Failed to load OAuth identities")
+}
+
+/// Links `identity` to a user, replacing any identity they previously linked for the provider.
+async fn upsert_identity(
+    pool: &PgPool,
+    user_id: SbUserId,
+    provider: OAuthProvider,
+    identity: &ExternalIdentity,
+) -> Result<OAuthIdentity, sqlx::Error> {
+    sqlx::query_as!(
+        OAuthIdentity,
+        r#"
+            INSERT INTO user_oauth_identities
+                (user_id, provider, provider_user_id, provider_username, email)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (user_id, provider) DO UPDATE SET
+                provider_user_id = EXCLUDED.provider_user_id,
+                provider_username = EXCLUDED.provider_username,
+                email = EXCLUDED.email,
+                linked_at = CASE
+                    WHEN user_oauth_identities.provider_user_id = EXCLUDED.provider_user_id
+                    THEN user_oauth_identities.linked_at
+                    ELSE now()
+                END
+            RETURNING provider as "provider: OAuthProvider", provider_username, linked_at,
+                last_login_at
+        "#,
+        user_id as _,
+        provider as _,
+        identity.provider_user_id,
+        identity.username,
+        identity.email,
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Finds the (non-deleted) user an identity is linked to.
+async fn find_identity_user(
+    pool: &PgPool,
+    provider: OAuthProvider,
+    identity: &ExternalIdentity,
+) -> eyre::Result<Option<SbUserId>> {
+    sqlx::query_scalar!(
+        r#"
+            SELECT i.user_id as "user_id: SbUserId"
+            FROM user_oauth_identities i
+            JOIN users u ON u.id = i.user_id
+            WHERE i.provider = $1 AND i.provider_user_id = $2 AND u.deleted_at IS NULL
+        "#,
+        provider as _,
+        identity.provider_user_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .wrap_err("Failed to look up OAuth identity")
+}
+
+/// Records a successful sign-in with an identity, refreshing the cached provider details.
+async fn record_identity_login(
+    pool: &PgPool,
+    provider: OAuthProvider,
+    identity: &ExternalIdentity,
+) -> eyre::Result<()> {
+    sqlx::query!(
+        r#"
+            UPDATE user_oauth_identities
+            SET provider_username = $3, email = $4, last_login_at = now()
+            WHERE provider = $1 AND provider_user_id = $2
+        "#,
+        provider as _,
+        identity.provider_user_id,
+        identity.username,
+        identity.email,
+    )
+    .execute(pool)
+    .await
+    .wrap_err("Failed to record OAuth sign-in")?;
+    Ok(())
+}
+
+async fn is_user_banned(pool: &PgPool, user_id: SbUserId) -> eyre::Result<bool> {
+    sqlx::query_scalar!(
+        r#"
+            SELECT EXISTS(
+                SELECT 1 FROM user_bans
+                WHERE user_id = $1 AND start_time <= now() AND end_time > now()
+            ) as "banned!"
+        "#,
+        user_id as _,
+    )
+    .fetch_one(pool)
+    .await
+    .wrap_err("Failed to check ban status")
+}
+
+// ---------------------------------------------------------------------------------------------
+// GraphQL
+// ---------------------------------------------------------------------------------------------
+
+/// The result of starting an OAuth flow: the authorize URL the client should open.
+#[derive(SimpleObject)]
+pub struct OAuthStart {
+    /// The provider's authorize URL the client should open (e.g. in a popup).
+    pub url: String,
+    /// A secret that must be passed back when completing the flow. This should be kept by the
+    /// client that started the flow, and not passed through the provider or any URLs.
+    pub code_verifier: String,
+}
+
+/// The result of signing in with an external identity.
+#[derive(SimpleObject)]
+pub struct OAuthLoginResult {
+    /// A session token to authenticate subsequent requests with, as returned by a password login.
+    pub jwt: String,
+    pub user: CurrentUser,
+}
+
+fn require_current_user<'a>(ctx: &'a Context<'_>) -> async_graphql::Result<&'a CurrentUser> {
+    ctx.data::<Option<CurrentUser>>()?
+        .as_ref()
+        .ok_or_else(|| graphql_error("UNAUTHORIZED", "Unauthorized"))
+}
+
+fn invalid_state_error() -> async_graphql::Error {
+    graphql_error(
+        "OAUTH_INVALID_STATE",
+        "Your sign-in request was invalid or expired. Please try again.",
+    )
+}
+
+/// Creates and stores a pending `state` for a flow, returning the authorize URL to open.
+async fn start_flow(
+    ctx: &Context<'_>,
+    provider: OAuthProvider,
+    user_id: Option<SbUserId>,
+) -> async_graphql::Result<OAuthStart> {
+    let client = ctx.data::<Arc<OAuthClient>>()?;
+    client.provider(provider)?;
+    let redirect_uri = client.redirect_uri(provider);
+
+    let state = Uuid::new_v4().to_string();
+    let code_verifier = gen_code_verifier();
+    let code_challenge = code_challenge(&code_verifier);
+    let pending = serde_json::to_string(&PendingOAuth {
+        provider,
+        user_id: user_id.map(i32::from),
+        redirect_uri: redirect_uri.clone(),
+        code_challenge: code_challenge.clone(),
+    })
+    .wrap_err("Failed to serialize pending OAuth state")?;
+    let mut redis = ctx
+        .data::<RedisPool>()?
+        .get()
+        .await
+        .wrap_err("Could not connect to Redis")?;
+    redis
+        .set_ex::<_, _, ()>(oauth_state_key(&state), pending, OAUTH_STATE_TTL_SECONDS)
+        .await
+        .wrap_err("Failed to store OAuth state")?;
+
+    Ok(OAuthStart {
+        url: client.authorize_url(provider, &state, &redirect_uri, &code_challenge)?,
+        code_verifier,
+    })
+}
+
+/// Consumes and validates a pending `state`, which must have been issued for `user_id` (or for a
+/// sign-in, if `None`) to the client holding `code_verifier`, then exchanges `code` for the
+/// identity it grants.
+async fn complete_flow(
+    ctx: &Context<'_>,
+    code: &str,
+    state: &str,
+    code_verifier: &str,
+    user_id: Option<SbUserId>,
+) -> async_graphql::Result<(OAuthProvider, ExternalIdentity)> {
+    let client = ctx.data::<Arc<OAuthClient>>()?;
+    let mut redis = ctx
+        .data::<RedisPool>()?
+        .get()
+        .await
+        .wrap_err("Could not connect to Redis")?;
+    let key = oauth_state_key(state);
+    // Read and delete atomically, so two concurrent requests can't both use the same state
+    let stored: Option<String> = redis
+        .get_del(&key)
+        .await
+        .wrap_err("Failed to read OAuth state")?;
+
+    let pending = stored
+        .and_then(|s| serde_json::from_str::<PendingOAuth>(&s).ok())
+        .filter(|p| {
+            p.user_id == user_id.map(i32::from) && p.code_challenge == code_challenge(code_verifier)
+        });
+    let Some(pending) = pending else {
+        return Err(invalid_state_error());
+    };
+
+    let identity = client
+        .fetch_identity(pending.provider, code, &pending.redirect_uri, code_verifier)
+        .await
+        .map_err(|e| {
+            error!("OAuth code exchange failed: {e:?}");
+            graphql_error(
+                "OAUTH_EXCHANGE_FAILED",
+                format!(
+                    "Failed to complete signing in with {}",
+                    pending.provider.display_name()
+                ),
+            )
+        })?;
+    Ok((pending.provider, identity))
+}
+
+#[derive(Default)]
+pub struct OAuthQuery;
+
+#[Object]
+impl OAuthQuery {
+    /// The external identity providers that are available to sign in with or link.
+    async fn oauth_providers(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Vec<OAuthProvider>> {
+        Ok(ctx.data::<Arc<OAuthClient>>()?.configured_providers())
+    }
+
+    /// The external identities linked to the current user's account.
+    async fn my_oauth_identities(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Vec<OAuthIdentity>> {
+        let user = require_current_user(ctx)?;
+        Ok(load_identities(ctx.data::<PgPool>()?, user.id).await?)
+    }
+}
+
+#[derive(Default)]
+pub struct OAuthMutation;
+
+#[Object]
+impl OAuthMutation {
+    /// Begins linking an external identity to the current user's account, returning the authorize
+    /// URL the client should open. Completing the flow calls `oauthCompleteLink` with the resulting
+    /// `code` and `state`.
+    async fn oauth_start_link(
+        &self,
+        ctx: &Context<'_>,
+        provider: OAuthProvider,
+    ) -> async_graphql::Result<OAuthStart> {
+        let user = require_current_user(ctx)?;
+        start_flow(ctx, provider, Some(user.id)).await
+    }
+
+    /// Completes linking an external identity using the `code` and `state` returned by the
+    /// provider's redirect, and the `codeVerifier` returned by `oauthStartLink`. Replaces any
+    /// identity previously linked for the same provider.
+    async fn oauth_complete_link(
+        &self,
+        ctx: &Context<'_>,
+        code: String,
+        state: String,
+        #[graphql(secret)] code_verifier: String,
+    ) -> async_graphql::Result<OAuthIdentity> {
+        let user = require_current_user(ctx)?;
+        let (provider, identity) =
+            complete_flow(ctx, &code, &state, &code_verifier, Some(user.id)).await?;
+
+        match upsert_identity(ctx.data::<PgPool>()?, user.id, provider, &identity).await {
+            Ok(linked) => Ok(linked),
+            Err(e) => {
+                if e.as_database_error().and_then(|db| db.constraint())
+                    == Some("user_oauth_identities_provider_provider_user_id_key")
+                {
+                    return Err(graphql_error(
+                        "OAUTH_ALREADY_LINKED",
+                        format!(
+                            "That {} account is already linked to another ShieldBattery account.",
+                            provider.display_name()
+                        ),
+                    ));
+                }
+                error!("Failed to upsert OAuth identity: {e:?}");
+                Err(graphql_error(
+                    "OAUTH_LINK_FAILED",
+                    "Failed to link external account",
+                ))
+            }
+        }
+    }
+
+    /// Removes the current user's linked identity for `provider`. Returns whether one was removed.
+    async fn oauth_unlink(
+        &self,
+        ctx: &Context<'_>,
+        provider: OAuthProvider,
+    ) -> async_graphql::Result<bool> {
+        let user = require_current_user(ctx)?;
+        let result = sqlx::query!(
+            "DELETE FROM user_oauth_identities WHERE user_id = $1 AND provider = $2",
+            user.id as _,
+            provider as _,
+        )
+        .execute(ctx.data::<PgPool>()?)
+        .await
+        .wrap_err("Failed to remove OAuth identity")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Begins signing in with an external identity, returning the authorize URL the client should
+    /// open. Completing the flow calls `oauthCompleteLogin` with the resulting `code` and `state`.
+    async fn oauth_start_login(
+        &self,
+        ctx: &Context<'_>,
+        provider: OAuthProvider,
+    ) -> async_graphql::Result<OAuthStart> {
+        if ctx.data::<Option<CurrentUser>>()?.is_some() {
+            return Err(graphql_error("ALREADY_LOGGED_IN", "Already logged in"));
+        }
+        start_flow(ctx, provider, None).await
+    }
+
+    /// Completes signing in with an external identity using the `code` and `state` returned by the
+    /// provider's redirect, and the `codeVerifier` returned by `oauthStartLogin`, starting a new
+    /// session. Only identities previously linked to an account can be used to sign in.
+    async fn oauth_complete_login(
+        &self,
+        ctx: &Context<'_>,
+        code: String,
+        state: String,
+        #[graphql(secret)] code_verifier: String,
+        #[graphql(default)] stay_logged_in: bool,
+    ) -> async_graphql::Result<OAuthLoginResult> {
+        if ctx.data::<Option<CurrentUser>>()?.is_some() {
+            return Err(graphql_error("ALREADY_LOGGED_IN", "Already logged in"));
+        }
+        let (provider, identity) = complete_flow(ctx, &code, &state, &code_verifier, None).await?;
+
+        let pool = ctx.data::<PgPool>()?;
+        let Some(user_id) = find_identity_user(pool, provider, &identity).await? else {
+            return Err(graphql_error(
+                "OAUTH_NOT_LINKED",
+                format!(
+                    "That {} account isn't linked to a ShieldBattery account. Log in with your \
+                     password and link it from your account settings first.",
+                    provider.display_name()
+                ),
+            ));
+        };
+        if is_user_banned(pool, user_id).await? {
+            return Err(graphql_error("ACCOUNT_BANNED", "This account is banned"));
+        }
+        // Like password logins, this has to come after the account's own ban check, otherwise a
+        // banned user signing in would get banned again for evading their own ban
+        if ban_user_if_needed(pool, ctx.data::<RedisPool>()?, user_id).await? {
+            return Err(graphql_error("ACCOUNT_BANNED", "This account is banned"));
+        }
+        record_identity_login(pool, provider, &identity).await?;
+
+        let settings = ctx.data::<Settings>()?;
+        let mut redis = ctx
+            .data::<RedisPool>()?
+            .get()
+            .await
+            .wrap_err("Could not connect to Redis")?;
+        let jwt = begin_session(
+            &mut redis,
+            &EncodingKey::from_secret(settings.jwt_secret.expose_secret().as_bytes()),
+            user_id,
+            stay_logged_in,
+            settings.session_ttl,
+        )
+        .await?;
+        let user = ctx
+            .data::<CurrentUserRepo>()?
+            .load_cached_user(user_id, CacheBehavior::ForceRefresh)
+            .await?;
+
+        Ok(OAuthLoginResult { jwt, user })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn mock_settings() -> OAuthProviderSettings {
+        OAuthProviderSettings {
+            client_id: "client-id".into(),
+            client_secret: "client-secret".to_string().into(),
+        }
+    }
+
+    /// Builds a client whose `provider` talks to a local mock identity provider instead of the
+    /// real one.
+    fn mock_client(server: &mockito::Server, provider: OAuthProvider) -> OAuthClient {
+        let settings = mock_settings();
+        let endpoints = OAuthEndpoints {
+            authorize_url: format!("{}/authorize", server.url()),
+            token_url: format!("{}/token", server.url()),
+            userinfo_url: format!("{}/userinfo", server.url()),
+        };
+        OAuthClient::new("https://example.org/", [(provider, endpoints, &settings)])
+    }
+
+    async fn mock_token_endpoint(server: &mut mockito::Server) -> mockito::Mock {
+        server
+            .mock("POST", "/token")
+            .match_body(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("code".into(), "the-code".into()),
+                mockito::Matcher::UrlEncoded("client_secret".into(), "client-secret".into()),
+                mockito::Matcher::UrlEncoded(
+                    "redirect_uri".into(),
+                    "https://example.org/oauth/discord/callback".into(),
+                ),
+                mockito::Matcher::UrlEncoded("code_verifier".into(), "the-verifier".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({ "access_token": "the-token", "token_type": "Bearer" }).to_string())
+            .create_async()
+            .await
+    }
+
+    #[test]
+    fn authorize_url_includes_state_and_redirect() {
+        let settings = mock_settings();
+        let client = OAuthClient::new(
+            "https://example.org",
+            [(
+                OAuthProvider::Google,
+                OAuthProvider::Google.default_endpoints(),
+                &settings,
+            )],
+        );
+        let redirect_uri = client.redirect_uri(OAuthProvider::Google);
+        assert_eq!(redirect_uri, "https://example.org/oauth/google/callback");
+
+        let url = Url::parse(
+            &client
+                .authorize_url(OAuthProvider::Google, "abc", &redirect_uri, "challenge")
+                .unwrap(),
+        )
+        .unwrap();
+        let params = url.query_pairs().into_owned().collect::<HashMap<_, _>>();
+        assert_eq!(params["client_id"], "client-id");
+        assert_eq!(params["redirect_uri"], redirect_uri);
+        assert_eq!(params["scope"], "openid email profile");
+        assert_eq!(params["state"], "abc");
+        assert_eq!(params["code_challenge"], "challenge");
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert!(
+            client
+                .authorize_url(OAuthProvider::Discord, "abc", &redirect_uri, "challenge")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn code_verifiers_are_random_and_challenges_match_rfc_7636() {
+        let verifier = gen_code_verifier();
+        assert_eq!(verifier.len(), 43);
+        assert_ne!(verifier, gen_code_verifier());
+
+        // From RFC 7636, Appendix B
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn records_sign_ins_only_when_asked(db: PgPool) {
+        let user_id = crate::test_utils::create_user(&db, "pachi").await;
+        let identity = ExternalIdentity {
+            provider_user_id: "1234".into(),
+            username: "pachi".into(),
+            email: None,
+        };
+        upsert_identity(&db, user_id, OAuthProvider::Discord, &identity)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            find_identity_user(&db, OAuthProvider::Discord, &identity)
+                .await
+                .unwrap(),
+            Some(user_id)
+        );
+        assert_eq!(
+            find_identity_user(&db, OAuthProvider::Google, &identity)
+                .await
+                .unwrap(),
+            None
+        );
+        // Looking up who an identity belongs to doesn't count as signing in
+        assert_eq!(
+            load_identities(&db, user_id).await.unwrap()[0].last_login_at,
+            None
+        );
+
+        let renamed = ExternalIdentity {
+            username: "pachi2".into(),
+            ..identity
+        };
+        record_identity_login(&db, OAuthProvider::Discord, &renamed)
+            .await
+            .unwrap();
+        let identities = load_identities(&db, user_id).await.unwrap();
+        assert!(identities[0].last_login_at.is_some());
+        assert_eq!(identities[0].provider_username, "pachi2");
+    }
+
+    #[test]
+    fn lists_only_configured_providers() {
+        let settings = mock_settings();
+        let client = OAuthClient::new(
+            "https://example.org",
+            [
+                (
+                    OAuthProvider::Github,
+                    OAuthProvider::Github.default_endpoints(),
+                    &settings,
+                ),
+                (
+                    OAuthProvider::Discord,
+                    OAuthProvider::Discord.default_endpoints(),
+                    &settings,
+                ),
+            ],
+        );
+        assert_eq!(
+            client.configured_providers(),
+            vec![OAuthProvider::Discord, OAuthProvider::Github]
+        );
+    }
+
+    #[tokio::test]
+    async fn fetches_identity_from_mock_provider() {
+        let mut server = mockito::Server::new_async().await;
+        let token = mock_token_endpoint(&mut server).await;
+        let userinfo = server
+            .mock("GET", "/userinfo")
+            .match_header("authorization", "Bearer the-token")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({
+                    "id": "80351110224678912",
+                    "username": "pachi",
+                    "email": "pachi@example.org",
+                    "verified": true,
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let client = mock_client(&server, OAuthProvider::Discord);
+        let identity = client
+            .fetch_identity(
+                OAuthProvider::Discord,
+                "the-code",
+                &client.redirect_uri(OAuthProvider::Discord),
+                "the-verifier",
+            )
+            .await
+            .unwrap();
+
+        token.assert_async().await;
+        userinfo.assert_async().await;
+        assert_eq!(
+            identity,
+            ExternalIdentity {
+                provider_user_id: "80351110224678912".into(),
+                username: "pachi".into(),
+                email: Some("pachi@example.org".into()),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_when_mock_provider_rejects_code() {
+        let mut server = mockito::Server::new_async().await;
+        let token = server
+            .mock("POST", "/token")
+            .with_status(400)
+            .with_header("content-type", "application/json")
+            .with_body(json!({ "error": "invalid_grant" }).to_string())
+            .create_async()
+            .await;
+        let userinfo = server
+            .mock("GET", "/userinfo")
+            .expect(0)
+            .create_async()
+            .await;
+
+        let client = mock_client(&server, OAuthProvider::Discord);
+        let res = client
+            .fetch_identity(
+                OAuthProvider::Discord,
+                "bad-code",
+                &client.redirect_uri(OAuthProvider::Discord),
+                "the-verifier",
+            )
+            .await;
+
+        token.assert_async().await;
+        userinfo.assert_async().await;
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn parses_provider_identities() {
+        let google = OAuthProvider::Google
+            .parse_identity(
+                &json!({
+                    "sub": "1234",
+                    "name": "Pachi",
+                    "email": "pachi@example.org",
+                    "email_verified": false,
+                })
+                .to_string(),
+            )
+            .unwrap();
+        assert_eq!(
+            google,
+            ExternalIdentity {
+                provider_user_id: "1234".into(),
+                username: "Pachi".into(),
+                email: None,
+            }
+        );
+
+        let github = OAuthProvider::Github
+            .parse_identity(&json!({ "id": 583231, "login": "octocat", "email": null }).to_string())
+            .unwrap();
+        assert_eq!(
+            github,
+            ExternalIdentity {
+                provider_user_id: "583231".into(),
+                username: "octocat".into(),
+                email: None,
+            }
+        );
+    }
+
+    #[test]
+    fn pending_state_round_trips() {
+        let pending = PendingOAuth {
+            provider: OAuthProvider::Github,
+            user_id: None,
+            redirect_uri: "https://example.org/oauth/github/callback".into(),
+            code_challenge: "challenge".into(),
+        };
+        let json = serde_json::to_string(&pending).unwrap();
+        assert!(json.contains(r#""provider":"github""#));
+        let parsed: PendingOAuth = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.provider, OAuthProvider::Github);
+        assert_eq!(parsed.user_id, None);
+        assert_eq!(parsed.code_challenge, "challenge");
+    }
+}
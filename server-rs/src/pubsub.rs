@@ -1,6 +1,7 @@
 use crate::{
-    game_reports::PublishedGameReportMessage, matchmaking::PublishedMatchmakingMessage,
-    news::PublishedNewsMessage, users::PublishedUserMessage,
+    game_reports::PublishedGameReportMessage, live_stream_feed::PublishedLiveStreamMessage,
+    matchmaking::PublishedMatchmakingMessage, news::PublishedNewsMessage,
+    users::PublishedUserMessage,
 };
 use serde::{Deserialize, Serialize};
 use typeshare::typeshare;
@@ -13,6 +14,7 @@ pub enum PublishedMessage {
     User(PublishedUserMessage),
     Matchmaking(PublishedMatchmakingMessage),
     GameReport(PublishedGameReportMessage),
+    LiveStream(PublishedLiveStreamMessage),
 }
 
 impl PublishedMessage {
@@ -23,6 +25,19 @@ impl PublishedMessage {
             Self::User(_) => "user",
             Self::Matchmaking(_) => "matchmaking",
             Self::GameReport(_) => "gameReport",
+            Self::LiveStream(_) => "liveStream",
+        }
+    }
+
+    /// Whether this message is published to a Redis stream rather than a pub/sub channel. Streams
+    /// keep messages around until each consumer group acknowledges them, so they're used for
+    /// messages that would leave things in a broken state if a consumer missed them (e.g. because
+    /// it was restarting). Consumers must know which one to read from, so this is decided per
+    /// channel, and must match `STREAM_CHANNELS` in `server/lib/redis/redis.ts`.
+    pub fn uses_stream(&self) -> bool {
+        match self {
+            Self::Matchmaking(_) | Self::GameReport(_) => true,
+            Self::News(_) | Self::User(_) | Self::LiveStream(_) => false,
         }
     }
 }
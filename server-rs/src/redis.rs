@@ -1,10 +1,55 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
 use crate::pubsub::PublishedMessage;
+use crate::telemetry::EXPORT_ONLY_TARGET;
 use color_eyre::Result;
 use color_eyre::eyre::WrapErr;
-use deadpool_redis::redis::AsyncCommands;
-use deadpool_redis::{Config, Connection, Pool, Runtime};
+use deadpool_redis::redis::aio::{ConnectionLike, MultiplexedConnection, PubSub};
+use deadpool_redis::redis::streams::{
+    StreamAutoClaimOptions, StreamAutoClaimReply, StreamId, StreamMaxlen, StreamPendingCountReply,
+    StreamReadOptions, StreamReadReply,
+};
+use deadpool_redis::redis::{
+    Arg, AsyncCommands, AsyncConnectionConfig, Cmd, Pipeline, RedisFuture, Value,
+};
+use deadpool_redis::{Config, Pool, Runtime};
+use tracing::{Instrument, Span};
 
 const PUBLISH_FAILURES: &str = "redis_publish_failures_total";
+const STREAM_REDELIVERIES: &str = "redis_stream_redeliveries_total";
+const STREAM_DEAD_LETTERS: &str = "redis_stream_dead_letters_total";
+
+/// Approximately how many entries to keep in each message stream. Entries are only needed until
+/// every consumer group has acknowledged them, so this just has to cover a consumer being down for
+/// a while.
+const STREAM_MAX_LEN: usize = 10_000;
+/// Approximately how many entries to keep in each dead-letter stream.
+const DEAD_LETTER_MAX_LEN: usize = 1_000;
+/// How long a delivered stream entry can go unacknowledged before it's delivered again (to
+/// whichever consumer in the group claims it first).
+const REDELIVERY_IDLE_TIME: Duration = Duration::from_secs(30);
+/// How many times a stream entry will be delivered before it's moved to the dead-letter stream.
+const MAX_DELIVERIES: usize = 5;
+/// How long a stream read waits for new entries. Must be shorter than [REDELIVERY_IDLE_TIME] so
+/// that unacknowledged entries are reclaimed promptly.
+const STREAM_READ_BLOCK: Duration = Duration::from_secs(5);
+/// The maximum number of entries to read (or reclaim) at once.
+const STREAM_READ_COUNT: usize = 100;
+/// The field in a stream entry that holds the JSON-encoded [PublishedMessage].
+const STREAM_MESSAGE_FIELD: &str = "message";
+
+/// Returns the Redis key of the stream that messages for `channel` are published to, for channels
+/// where [PublishedMessage::uses_stream] is true.
+pub fn stream_key(channel: &str) -> String {
+    format!("streams:{channel}")
+}
+
+/// Returns the Redis key of the stream that undeliverable entries from `channel`'s stream are moved
+/// to.
+pub fn dead_letter_stream_key(channel: &str) -> String {
+    format!("streams:{channel}:deadLetter")
+}
 
 /// Registers metric descriptions (the HELP/TYPE text on `/metrics`). Safe to call once at startup;
 /// recording a metric without describing it still works, this just produces nicer output.
@@ -16,10 +61,23 @@ pub fn describe_metrics() {
         Unit::Count,
         "Failed attempts to publish a message to Redis, per channel"
     );
+    ::metrics::describe_counter!(
+        STREAM_REDELIVERIES,
+        Unit::Count,
+        "Redis stream entries delivered again after going unacknowledged, per channel and group"
+    );
+    ::metrics::describe_counter!(
+        STREAM_DEAD_LETTERS,
+        Unit::Count,
+        "Redis stream entries moved to a dead-letter stream, per channel and group"
+    );
 }
 
 #[derive(Clone)]
-pub struct RedisPool(Pool);
+pub struct RedisPool {
+    pool: Pool,
+    url: String,
+}
 
 impl RedisPool {
     /// Creates a new pool of connections to the Redis server at the given URL (e.g.
@@ -28,13 +86,17 @@ impl RedisPool {
         let pool = Config::from_url(url)
             .create_pool(Some(Runtime::Tokio1))
             .wrap_err("Failed to create Redis connection pool")?;
-        Ok(Self(pool))
+        Ok(Self {
+            pool,
+            url: url.to_string(),
+        })
     }
 
     pub async fn get(&self) -> Result<Connection> {
-        self.0
+        self.pool
             .get()
             .await
+            .map(Connection)
             .wrap_err("Failed to get Redis connection")
             .map_err(|e| {
                 tracing::error!("Failed to get Redis connection: {e:?}");
@@ -42,7 +104,59 @@ impl RedisPool {
             })
     }
 
-    /// Publish a message to the given channel. This is a convenience method for retrieving a
+    /// Opens a new connection for subscribing to pub/sub channels. Subscribed connections can't be
+    /// used for anything else, so these are separate from the pool and should be long-lived.
+    pub async fn subscriber(&self) -> Result<PubSub> {
+        let client = deadpool_redis::redis::Client::open(self.url.as_str())
+            .wrap_err("Failed to create Redis client")?;
+        client
+            .get_async_pubsub()
+            .await
+            .wrap_err("Failed to open Redis pub/sub connection")
+    }
+
+    /// Opens a consumer for the stream backing `channel`, as a member of the consumer group
+    /// `group` (creating the group if it doesn't exist yet). Every group receives every message,
+    /// so each distinct consumer of a channel should use its own group. Reads block, so like
+    /// [Self::subscriber] these use their own connection and should be long-lived.
+    pub async fn stream_consumer(
+        &self,
+        channel: &'static str,
+        group: &'static str,
+    ) -> Result<StreamConsumer> {
+        let client = deadpool_redis::redis::Client::open(self.url.as_str())
+            .wrap_err("Failed to create Redis client")?;
+        let mut conn = client
+            .get_multiplexed_async_connection_with_config(
+                &AsyncConnectionConfig::new().set_response_timeout(None),
+            )
+            .await
+            .wrap_err("Failed to open Redis stream connection")?;
+
+        let key = stream_key(channel);
+        // Starting at `$` means a new group only sees messages published after it was created
+        match conn
+            .xgroup_create_mkstream::<_, _, _, ()>(&key, group, "$")
+            .await
+        {
+            Ok(()) => {}
+            Err(e) if e.code() == Some("BUSYGROUP") => {}
+            Err(e) => return Err(e).wrap_err("Failed to create stream consumer group"),
+        }
+
+        Ok(StreamConsumer {
+            conn,
+            channel,
+            key,
+            group,
+            consumer: gethostname::gethostname().to_string_lossy().into_owned(),
+            buffered: VecDeque::new(),
+            last_reclaim: None,
+        })
+    }
+
+    /// Publish a message to the given channel, or to its stream if it [uses
+    /// one](PublishedMessage::uses_stream). This is a convenience method for retrieving a
     /// connection from the pool, serializing a message, and publishing it, since the places that
     /// do this don't often have a need for performing other Redis operations with the same
     /// connection.
@@ -50,17 +164,488 @@ impl RedisPool {
         let mut redis = self.get().await?;
         let message: PublishedMessage = message.into();
         let channel = message.channel();
+        let uses_stream = message.uses_stream();
         let message = serde_json::to_string(&message).wrap_err("Failed to serialize message")?;
-        redis
-            .publish::<_, _, ()>(channel, &message)
+        let result = if uses_stream {
+            redis
+                .xadd_maxlen::<_, _, _, _, ()>(
+                    stream_key(channel),
+                    StreamMaxlen::Approx(STREAM_MAX_LEN),
+                    "*",
+                    &[(STREAM_MESSAGE_FIELD, &message)],
+                )
+                .await
+        } else {
+            redis.publish::<_, _, ()>(channel, &message).await
+        };
+        result.wrap_err("Failed to publish message").map_err(|e| {
+            tracing::error!("Failed to publish message to '{channel:?}': {e:?}");
+            ::metrics::counter!(PUBLISH_FAILURES, "channel" => channel).increment(1);
+            e
+        })?;
+
+        Ok(())
+    }
+}
+
+/// A pooled Redis connection, which traces each command sent over it (if there's a current span
+/// for it to be a part of).
+pub struct Connection<C = deadpool_redis::Connection>(C);
+
+impl<C: ConnectionLike + Send> ConnectionLike for Connection<C> {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        let span = command_span(|| match cmd.args_iter().next() {
+            Some(Arg::Simple(name)) => String::from_utf8_lossy(name).to_uppercase(),
+            _ => "UNKNOWN".to_string(),
+        });
+        Box::pin(self.0.req_packed_command(cmd).instrument(span))
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        let span = command_span(|| "PIPELINE".to_string());
+        Box::pin(
+            self.0
+                .req_packed_commands(cmd, offset, count)
+                .instrument(span),
+        )
+    }
+
+    fn get_db(&self) -> i64 {
+        self.0.get_db()
+    }
+}
+
+fn command_span(operation: impl FnOnce() -> String) -> Span {
+    if Span::current().is_none() {
+        return Span::none();
+    }
+    tracing::info_span!(
+        target: EXPORT_ONLY_TARGET,
+        "redis.command",
+        db.system.name = "redis",
+        db.operation.name = operation(),
+        otel.kind = "client",
+    )
+}
+
+/// A message read from a Redis stream. Once it has been handled, it should be passed to
+/// [StreamConsumer::ack], otherwise it will be delivered again.
+#[derive(Debug, Clone)]
+pub struct StreamEntry {
+    pub id: String,
+    pub message: PublishedMessage,
+}
+
+/// Reads [PublishedMessage]s from a channel's Redis stream as part of a consumer group. Entries that
+/// go unacknowledged for [REDELIVERY_IDLE_TIME] (e.g. because their consumer crashed while handling
+/// them) are delivered again, and entries that can't be parsed or have been delivered
+/// [MAX_DELIVERIES] times are moved to the channel's dead-letter stream.
+pub struct StreamConsumer<C = MultiplexedConnection> {
+    conn: C,
+    channel: &'static str,
+    key: String,
+    group: &'static str,
+    consumer: String,
+    /// Entries that have been read but not yet returned from [Self::next].
+    buffered: VecDeque<StreamEntry>,
+    last_reclaim: Option<Instant>,
+}
+
+impl<C: ConnectionLike + Send + Sync> StreamConsumer<C> {
+    /// Returns the next entry in the stream, waiting for one to be published if necessary.
+    pub async fn next(&mut self) -> Result<StreamEntry> {
+        loop {
+            if let Some(entry) = self.buffered.pop_front() {
+                return Ok(entry);
+            }
+
+            if self
+                .last_reclaim
+                .is_none_or(|t| t.elapsed() >= REDELIVERY_IDLE_TIME)
+            {
+                self.reclaim().await?;
+                self.last_reclaim = Some(Instant::now());
+                continue;
+            }
+
+            let options = StreamReadOptions::default()
+                .group(self.group, &self.consumer)
+                .block(STREAM_READ_BLOCK.as_millis() as usize)
+                .count(STREAM_READ_COUNT);
+            let reply: Option<StreamReadReply> = self
+                .conn
+                .xread_options(&[&self.key], &[">"], &options)
+                .await
+                .wrap_err("Failed to read from stream")?;
+            for id in reply.into_iter().flat_map(|r| r.keys).flat_map(|k| k.ids) {
+                self.accept(id).await?;
+            }
+        }
+    }
+
+    /// Acknowledges that the entry with the given ID has been handled, so it won't be delivered
+    /// again.
+    pub async fn ack(&mut self, id: &str) -> Result<()> {
+        self.conn
+            .xack::<_, _, _, ()>(&self.key, self.group, &[id])
             .await
-            .wrap_err("Failed to publish message")
-            .map_err(|e| {
-                tracing::error!("Failed to publish message to '{channel:?}': {e:?}");
-                ::metrics::counter!(PUBLISH_FAILURES, "channel" => channel).increment(1);
-                e
-            })?;
+            .wrap_err("Failed to acknowledge stream entry")
+    }
+
+    /// Claims entries that were delivered to this group but have gone unacknowledged for too long,
+    /// buffering them to be returned again (or dead-lettering them if they've been retried too many
+    /// times already).
+    async fn reclaim(&mut self) -> Result<()> {
+        let reply: StreamAutoClaimReply = self
+            .conn
+            .xautoclaim_options(
+                &self.key,
+                self.group,
+                &self.consumer,
+                REDELIVERY_IDLE_TIME.as_millis() as usize,
+                "0-0",
+                StreamAutoClaimOptions::default().count(STREAM_READ_COUNT),
+            )
+            .await
+            .wrap_err("Failed to claim idle stream entries")?;
+
+        for id in reply.claimed {
+            let pending: StreamPendingCountReply = self
+                .conn
+                .xpending_count(&self.key, self.group, &id.id, &id.id, 1)
+                .await
+                .wrap_err("Failed to check stream entry deliveries")?;
+            let deliveries = pending.ids.first().map_or(0, |p| p.times_delivered);
+            if deliveries > MAX_DELIVERIES {
+                self.dead_letter(&id, "maxDeliveries").await?;
+            } else {
+                ::metrics::counter!(
+                    STREAM_REDELIVERIES,
+                    "channel" => self.channel,
+                    "group" => self.group,
+                )
+                .increment(1);
+                self.accept(id).await?;
+            }
+        }
 
         Ok(())
     }
+
+    /// Parses a delivered entry and buffers it to be returned, or dead-letters it if it can't be
+    /// parsed (since delivering it again won't help).
+    async fn accept(&mut self, id: StreamId) -> Result<()> {
+        match parse_stream_entry(&id) {
+            Ok(entry) => {
+                self.buffered.push_back(entry);
+                Ok(())
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Failed to parse entry '{}' from the '{}' stream: {e:?}",
+                    id.id,
+                    self.channel
+                );
+                self.dead_letter(&id, "unparseable").await
+            }
+        }
+    }
+
+    /// Moves an entry to the channel's dead-letter stream, acknowledging it so it won't be delivered
+    /// to this group again.
+    async fn dead_letter(&mut self, id: &StreamId, reason: &str) -> Result<()> {
+        tracing::warn!(
+            "Moving entry '{}' from the '{}' stream to its dead-letter stream ({reason}) for group \
+            '{}'",
+            id.id,
+            self.channel,
+            self.group
+        );
+        let message = id.get::<String>(STREAM_MESSAGE_FIELD).unwrap_or_default();
+        self.conn
+            .xadd_maxlen::<_, _, _, _, ()>(
+                dead_letter_stream_key(self.channel),
+                StreamMaxlen::Approx(DEAD_LETTER_MAX_LEN),
+                "*",
+                &[
+                    (STREAM_MESSAGE_FIELD, message.as_str()),
+                    ("sourceId", id.id.as_str()),
+                    ("group", self.group),
+                    ("consumer", self.consumer.as_str()),
+                    ("reason", reason),
+                ],
+            )
+            .await
+            .wrap_err("Failed to write to dead-letter stream")?;
+        ::metrics::counter!(
+            STREAM_DEAD_LETTERS,
+            "channel" => self.channel,
+            "group" => self.group,
+        )
+        .increment(1);
+        self.ack(&id.id).await
+    }
+}
+
+fn parse_stream_entry(id: &StreamId) -> Result<StreamEntry> {
+    let payload = id
+        .get::<String>(STREAM_MESSAGE_FIELD)
+        .ok_or_else(|| color_eyre::eyre::eyre!("Entry has no '{STREAM_MESSAGE_FIELD}' field"))?;
+    let message =
+        serde_json::from_str(&payload).wrap_err("Failed to deserialize published message")?;
+    Ok(StreamEntry {
+        id: id.id.clone(),
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redis_test::{MockCmd, MockRedisConnection};
+    use std::collections::HashMap;
+
+    use crate::game_reports::PublishedGameReportMessage;
+
+    const CHANNEL: &str = "test";
+    const GROUP: &str = "testGroup";
+    const CONSUMER: &str = "test-host";
+
+    fn stream_id(fields: &[(&str, &str)]) -> StreamId {
+        StreamId {
+            id: "1-0".into(),
+            map: fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), Value::BulkString(v.as_bytes().to_vec())))
+                .collect::<HashMap<_, _>>(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_stream_entry_round_trips_published_messages() {
+        let message: PublishedMessage = PublishedGameReportMessage::ReportActioned {
+            reporter_ids: vec![],
+        }
+        .into();
+        let json = serde_json::to_string(&message).unwrap();
+
+        let entry = parse_stream_entry(&stream_id(&[(STREAM_MESSAGE_FIELD, &json)])).unwrap();
+        assert_eq!(entry.id, "1-0");
+        assert!(matches!(
+            entry.message,
+            PublishedMessage::GameReport(PublishedGameReportMessage::ReportActioned { .. })
+        ));
+    }
+
+    #[test]
+    fn parse_stream_entry_rejects_bad_entries() {
+        assert!(parse_stream_entry(&stream_id(&[])).is_err());
+        assert!(parse_stream_entry(&stream_id(&[(STREAM_MESSAGE_FIELD, "{")])).is_err());
+        assert!(
+            parse_stream_entry(&stream_id(&[(
+                STREAM_MESSAGE_FIELD,
+                r#"{"type":"nope","data":{}}"#
+            )]))
+            .is_err()
+        );
+    }
+
+    fn bulk(s: &str) -> Value {
+        Value::BulkString(s.as_bytes().to_vec())
+    }
+
+    fn message_json() -> String {
+        let message: PublishedMessage = PublishedGameReportMessage::ReportActioned {
+            reporter_ids: vec![],
+        }
+        .into();
+        serde_json::to_string(&message).unwrap()
+    }
+
+    /// An entry as it appears in XREADGROUP and XAUTOCLAIM replies.
+    fn entry_value(id: &str, message: &str) -> Value {
+        Value::Array(vec![
+            bulk(id),
+            Value::Array(vec![bulk(STREAM_MESSAGE_FIELD), bulk(message)]),
+        ])
+    }
+
+    fn consumer(commands: Vec<MockCmd>) -> StreamConsumer<MockRedisConnection> {
+        StreamConsumer {
+            conn: MockRedisConnection::new(commands).assert_all_commands_consumed(),
+            channel: CHANNEL,
+            key: stream_key(CHANNEL),
+            group: GROUP,
+            consumer: CONSUMER.to_string(),
+            buffered: VecDeque::new(),
+            last_reclaim: None,
+        }
+    }
+
+    fn xautoclaim(entries: Vec<Value>) -> MockCmd {
+        MockCmd::new(
+            Cmd::xautoclaim_options(
+                stream_key(CHANNEL),
+                GROUP,
+                CONSUMER,
+                REDELIVERY_IDLE_TIME.as_millis() as usize,
+                "0-0",
+                StreamAutoClaimOptions::default().count(STREAM_READ_COUNT),
+            ),
+            Ok(Value::Array(vec![
+                bulk("0-0"),
+                Value::Array(entries),
+                Value::Array(vec![]),
+            ])),
+        )
+    }
+
+    fn xpending(id: &str, deliveries: i64) -> MockCmd {
+        MockCmd::new(
+            Cmd::xpending_count(stream_key(CHANNEL), GROUP, id, id, 1),
+            Ok(Value::Array(vec![Value::Array(vec![
+                bulk(id),
+                bulk(CONSUMER),
+                Value::Int(30_000),
+                Value::Int(deliveries),
+            ])])),
+        )
+    }
+
+    fn xreadgroup(entries: Vec<Value>) -> MockCmd {
+        let options = StreamReadOptions::default()
+            .group(GROUP, CONSUMER)
+            .block(STREAM_READ_BLOCK.as_millis() as usize)
+            .count(STREAM_READ_COUNT);
+        MockCmd::new(
+            Cmd::xread_options(&[stream_key(CHANNEL)], &[">"], &options),
+            Ok(Value::Array(vec![Value::Array(vec![
+                bulk(&stream_key(CHANNEL)),
+                Value::Array(entries),
+            ])])),
+        )
+    }
+
+    fn dead_letter_xadd(id: &str, message: &str, reason: &str) -> MockCmd {
+        MockCmd::new(
+            Cmd::xadd_maxlen(
+                dead_letter_stream_key(CHANNEL),
+                StreamMaxlen::Approx(DEAD_LETTER_MAX_LEN),
+                "*",
+                &[
+                    (STREAM_MESSAGE_FIELD, message),
+                    ("sourceId", id),
+                    ("group", GROUP),
+                    ("consumer", CONSUMER),
+                    ("reason", reason),
+                ],
+            ),
+            Ok(bulk("2-0")),
+        )
+    }
+
+    fn xack(id: &str) -> MockCmd {
+        MockCmd::new(
+            Cmd::xack(stream_key(CHANNEL), GROUP, &[id]),
+            Ok(Value::Int(1)),
+        )
+    }
+
+    #[tokio::test]
+    async fn unacknowledged_entries_are_redelivered() {
+        let message = message_json();
+        let mut consumer = consumer(vec![
+            xautoclaim(vec![entry_value("1-0", &message)]),
+            xpending("1-0", 2),
+            xack("1-0"),
+        ]);
+
+        let entry = consumer.next().await.unwrap();
+        assert_eq!(entry.id, "1-0");
+        consumer.ack(&entry.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn entries_delivered_too_many_times_are_dead_lettered() {
+        let message = message_json();
+        let mut consumer = consumer(vec![
+            xautoclaim(vec![entry_value("1-0", &message)]),
+            xpending("1-0", MAX_DELIVERIES as i64 + 1),
+            dead_letter_xadd("1-0", &message, "maxDeliveries"),
+            xack("1-0"),
+            xreadgroup(vec![entry_value("3-0", &message)]),
+        ]);
+
+        // The dead-lettered entry is skipped in favor of the next one read from the stream
+        let entry = consumer.next().await.unwrap();
+        assert_eq!(entry.id, "3-0");
+    }
+
+    #[tokio::test]
+    async fn unparseable_entries_are_dead_lettered() {
+        let message = message_json();
+        let mut consumer = consumer(vec![
+            xautoclaim(vec![]),
+            xreadgroup(vec![entry_value("1-0", "{"), entry_value("2-0", &message)]),
+            dead_letter_xadd("1-0", "{", "unparseable"),
+            xack("1-0"),
+        ]);
+
+        let entry = consumer.next().await.unwrap();
+        assert_eq!(entry.id, "2-0");
+    }
+
+    /// Runs `f` against a traced connection that expects `commands`, returning the spans exported.
+    fn traced_commands<F>(commands: Vec<MockCmd>, f: F) -> Vec<opentelemetry_sdk::trace::SpanData>
+    where
+        F: AsyncFnOnce(&mut Connection<MockRedisConnection>),
+    {
+        let mut conn =
+            Connection(MockRedisConnection::new(commands).assert_all_commands_consumed());
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        crate::telemetry::export_spans(1.0, || runtime.block_on(f(&mut conn)))
+    }
+
+    #[test]
+    fn commands_are_traced() {
+        let spans = traced_commands(
+            vec![MockCmd::new(Cmd::get("key"), Ok(bulk("value")))],
+            async |conn| {
+                let _entered = tracing::info_span!("request").entered();
+                let value: String = conn.get("key").await.unwrap();
+                assert_eq!(value, "value");
+            },
+        );
+
+        assert_eq!(spans.len(), 2);
+        let (command, request) = (&spans[0], &spans[1]);
+        assert_eq!(command.name, "redis.command");
+        assert_eq!(command.parent_span_id, request.span_context.span_id());
+        assert!(
+            command
+                .attributes
+                .iter()
+                .any(|kv| { kv.key.as_str() == "db.operation.name" && kv.value.as_str() == "GET" })
+        );
+    }
+
+    #[test]
+    fn commands_outside_of_spans_are_not_traced() {
+        let spans = traced_commands(
+            vec![MockCmd::new(Cmd::get("key"), Ok(bulk("value")))],
+            async |conn| {
+                let _: String = conn.get("key").await.unwrap();
+            },
+        );
+
+        assert!(spans.is_empty());
+    }
 }
@@ -33,29 +33,48 @@ use tower_http::sensitive_headers::{
 };
 use tower_http::timeout::TimeoutLayer;
 use tower_http::trace::TraceLayer;
-use tracing::Span;
-
-use crate::configuration::{Env, Settings};
-use crate::email::MailgunClient;
+use tracing::{Instrument, Span};
+
+use crate::configuration::Settings;
+use crate::email::Mailer;
+use crate::email::outbox::email_outbox_loop;
+use crate::email::templates::EmailRenderer;
+use crate::email::transport::transport_from_settings;
+use crate::email::webhooks::create_email_api;
+use crate::file_blobs::file_blob_gc_loop;
 use crate::file_store::file_store_from_config;
 use crate::game_reports::GameReportsModule;
 use crate::games::GamesModule;
 use crate::graphql::errors::ErrorLoggerExtension;
+use crate::graphql::limits::{PersistedQueries, PersistedQueryManifest, QueryLimits};
 use crate::graphql::schema_builder::SchemaBuilderModuleExt;
+use crate::images::create_images_api;
+use crate::live_stream_feed::{LiveStreamFeed, live_stream_feed_loop};
 use crate::maps::MapsModule;
+use crate::maps::render::MapRenderer;
+use crate::maps::stats::map_stats_loop;
+use crate::maps::store::{create_maps_api, map_image_render_loop, map_reparse_loop};
 use crate::matchmaking::api::create_matchmaking_api;
 use crate::matchmaking::config::load_matchmaker_config;
 use crate::news::NewsModule;
+use crate::news_feed::create_news_feed_api;
+use crate::oauth::OAuthClient;
 use crate::redis::RedisPool;
 use crate::schema::{SbSchema, build_schema};
 use crate::sessions::{SbSession, jwt_middleware};
 use crate::state::AppState;
+use crate::telemetry;
 use crate::twitch::{
     TwitchClient, TwitchModule, create_twitch_api, reconcile_subscriptions_loop,
     refresh_live_streams_loop,
 };
+use crate::twitch_chat::{TwitchChatBot, match_result_announcer_loop, match_start_announcer_loop};
+use crate::users::api_tokens::ApiTokenScopeExtension;
+use crate::users::data_export::data_export_loop;
+use crate::users::deletion::account_deletion_loop;
 use crate::users::names::{NameChecker, create_names_api};
-use crate::users::{CurrentUser, CurrentUserRepo, UsersModule};
+use crate::users::{CurrentUserRepo, UsersModule};
+use crate::youtube::{YoutubeClient, youtube_live_poll_loop};
 
 const DATABASE_POOL_CONNECTIONS: &str = "database_pool_connections";
 const DATABASE_POOL_MAX_CONNECTIONS: &str = "database_pool_max_connections";
@@ -288,6 +307,7 @@ fn make_request_span<B>(request: &Request<B>) -> Span {
         HeaderName::from_static("x-real-ip"),
     );
     record_request_header_fields(&span, request.headers());
+    telemetry::set_parent_from_headers(&span, request.headers());
 
     span
 }
@@ -302,22 +322,42 @@ async fn graphql_handler(
     ip: ClientIp,
     user_agent: Option<TypedHeader<UserAgent>>,
     session: SbSession,
-    current_user: Option<CurrentUser>,
+    State(current_user_repo): State<CurrentUserRepo>,
     State(schema): State<SbSchema>,
     req: GraphQLRequest,
 ) -> GraphQLResponse {
+    // Unlike the `CurrentUser` extractor (used by the HTTP routes), this includes the users of API
+    // tokens, whose access is limited to their scopes by `ApiTokenScopeExtension`
+    let current_user = match session.user_id() {
+        Some(user_id) => current_user_repo
+            .load_cached_user(user_id, Default::default())
+            .await
+            .ok(),
+        None => None,
+    };
+    let req = req.into_inner();
+    let span = graphql_operation_span(req.operation_name.as_deref());
     schema
         .execute(
-            req.into_inner()
-                .data(ip)
+            req.data(ip)
                 .data(user_agent)
                 .data(session)
                 .data(current_user),
         )
+        .instrument(span)
         .await
         .into()
 }
 
+/// The span covering the execution of a GraphQL operation. Its name is the same for every operation
+/// (so that trace backends can group them), with the operation's name as an attribute.
+fn graphql_operation_span(operation_name: Option<&str>) -> Span {
+    tracing::info_span!(
+        "graphql_operation",
+        graphql.operation.name = operation_name.unwrap_or("anonymous"),
+    )
+}
+
 async fn graphql_ws_handler(
     State(schema): State<SbSchema>,
     protocol: GraphQLProtocol,
@@ -349,10 +389,18 @@ pub async fn create_app(
     redis_pool: RedisPool,
     settings: Settings,
 ) -> eyre::Result<Router> {
-    let mailgun = Arc::new(MailgunClient::new(
-        settings.mailgun.clone(),
-        settings.canonical_host.clone(),
-    ));
+    let mailer = Mailer::new(settings.email_transport.is_some());
+    if let Some(transport_settings) = &settings.email_transport {
+        let transport = transport_from_settings(transport_settings)
+            .wrap_err("Creating email transport failed")?;
+        let renderer = EmailRenderer::new(settings.canonical_host.clone())
+            .wrap_err("Loading email templates failed")?;
+        tokio::spawn(email_outbox_loop(
+            db_pool.clone(),
+            transport,
+            Arc::new(renderer),
+        ));
+    }
     let file_store = file_store_from_config(&settings)
         .await
         .wrap_err("Creating file store failed")?;
@@ -386,20 +434,106 @@ pub async fn create_app(
         ));
     }
 
+    // Only present when the chat bot (and Twitch) is configured; streamers can't opt in otherwise.
+    let twitch_chat_bot = match TwitchChatBot::from_settings(&settings) {
+        Some((bot, connection)) => {
+            tokio::spawn(connection.run());
+            tokio::spawn(match_start_announcer_loop(
+                bot.clone(),
+                db_pool.clone(),
+                redis_pool.clone(),
+            ));
+            tokio::spawn(match_result_announcer_loop(
+                bot.clone(),
+                db_pool.clone(),
+                redis_pool.clone(),
+            ));
+            Some(bot)
+        }
+        None => None,
+    };
+
+    // Only present when YouTube is configured; disables the integration otherwise.
+    let youtube_client = YoutubeClient::from_settings(&settings);
+    if let Some(youtube_client) = youtube_client.clone() {
+        tokio::spawn(youtube_live_poll_loop(
+            youtube_client,
+            db_pool.clone(),
+            redis_pool.clone(),
+        ));
+    }
+
+    let oauth_client = OAuthClient::from_settings(&settings);
+
+    let live_stream_feed = LiveStreamFeed::new();
+    tokio::spawn(live_stream_feed_loop(
+        live_stream_feed.clone(),
+        db_pool.clone(),
+        redis_pool.clone(),
+    ));
+
+    tokio::spawn(data_export_loop(
+        db_pool.clone(),
+        file_store.clone(),
+        mailer.clone(),
+    ));
+    tokio::spawn(file_blob_gc_loop(db_pool.clone(), file_store.clone()));
+    tokio::spawn(map_reparse_loop(db_pool.clone(), file_store.clone()));
+    tokio::spawn(map_stats_loop(db_pool.clone()));
+    let map_renderer = settings
+        .bw_data_path
+        .clone()
+        .map(|path| Arc::new(MapRenderer::new(path)));
+    if let Some(map_renderer) = map_renderer.clone() {
+        tokio::spawn(map_image_render_loop(
+            db_pool.clone(),
+            file_store.clone(),
+            map_renderer,
+        ));
+    }
+    tokio::spawn(account_deletion_loop(
+        db_pool.clone(),
+        redis_pool.clone(),
+        CurrentUserRepo::new(db_pool.clone(), redis_pool.clone(), file_store.clone()),
+        twitch_client.clone(),
+    ));
+
+    crate::email::outbox::describe_metrics();
     crate::graphql::errors::describe_metrics();
+    crate::graphql::limits::describe_metrics();
     crate::redis::describe_metrics();
 
+    let limits = &settings.graphql_limits;
+    let persisted_queries = match &limits.persisted_queries {
+        Some(path) => {
+            let manifest = PersistedQueryManifest::load(path)?;
+            tracing::info!("Loaded {} persisted GraphQL queries", manifest.len());
+            manifest
+        }
+        None => PersistedQueryManifest::default(),
+    };
+
     let schema = build_schema()
         .extension(Tracing)
+        .extension(PersistedQueries::new(
+            persisted_queries,
+            limits.enforce_persisted_queries,
+        ))
+        .extension(QueryLimits::new(limits.max_complexity, limits.max_depth))
         .extension(ErrorLoggerExtension)
+        .extension(ApiTokenScopeExtension)
         .data(settings.clone())
         .data(db_pool.clone())
         .data(redis_pool.clone())
-        .data(mailgun.clone())
+        .data(mailer.clone())
         .data(file_store.clone())
         .data(name_checker.clone())
         .data(matchmaker_config.clone())
         .data(twitch_client.clone())
+        .data(twitch_chat_bot)
+        .data(youtube_client)
+        .data(oauth_client)
+        .data(live_stream_feed)
         .module(TwitchModule::new(db_pool.clone(), redis_pool.clone()))
         .module(MapsModule::new(db_pool.clone()))
         .module(GamesModule::new(db_pool.clone()))
@@ -410,24 +544,6 @@ pub async fn create_app(
             redis_pool.clone(),
             file_store.clone(),
         ))
-        .limit_depth(if settings.env == Env::Production {
-            // TODO(tec27): Figure out good limits
-            10
-        } else {
-            // NOTE(tec27): GQLi introspection is a pretty deep query so we allow much greater in
-            // dev mode
-            999999
-        })
-        .limit_complexity(if settings.env == Env::Production {
-            // Bound on per-query resolver fan-out (default cost = 1 per selected field,
-            // including fields pulled in through fragment spreads). Sized with ample headroom
-            // over the largest legitimate client query.
-            300
-        } else {
-            // NOTE(tec27): GQLi introspection selects a very large number of fields, so we allow
-            // much greater in dev mode
-            999999
-        })
         .finish();
 
     let sensitive_headers: Arc<[_]> = Arc::new([
@@ -466,6 +582,7 @@ pub async fn create_app(
         .layer(middleware::from_fn(only_unforwarded_clients));
     let names_router = create_names_api().layer(middleware::from_fn(only_unforwarded_clients));
     let matchmaker_router = create_matchmaking_api(
+        db_pool.clone(),
         redis_pool.clone(),
         matchmaker_config,
         settings.rp2_coordinator_url.clone(),
@@ -482,13 +599,14 @@ pub async fn create_app(
         name_checker,
         db_pool,
         redis_pool,
-        mailgun,
+        mailer,
         file_store,
         jwt_key: Arc::new(DecodingKey::from_secret(
             settings.jwt_secret.expose_secret().as_ref(),
         )),
         graphql_schema: schema.clone(),
         twitch_client,
+        map_renderer,
     };
 
     Ok(Router::new()
@@ -499,6 +617,11 @@ pub async fn create_app(
         // by HMAC signature instead), so it stays on the main router rather than behind
         // `only_unforwarded_clients`.
         .nest("/twitch", create_twitch_api())
+        // Email provider webhooks, external-facing for the same reason as the Twitch ones
+        .nest("/email", create_email_api())
+        .nest("/news", create_news_feed_api())
+        .nest("/images", create_images_api())
+        .nest("/maps", create_maps_api())
         .nest("/users/names", names_router)
         .nest("/matchmaker", matchmaker_router)
         .layer(
@@ -829,4 +952,36 @@ mod tests {
         assert!(!fields.contains_key("http.request.headers.origin"));
         assert!(!fields.contains_key("http.request.headers.content-type"));
     }
+
+    #[test]
+    fn graphql_operation_spans_have_a_fixed_name() {
+        let spans = crate::telemetry::export_spans(1.0, || {
+            let _entered = graphql_operation_span(Some("GetUser")).entered();
+        });
+
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].name, "graphql_operation");
+        assert!(spans[0].attributes.iter().any(|kv| {
+            kv.key.as_str() == "graphql.operation.name" && kv.value.as_str() == "GetUser"
+        }));
+    }
+
+    #[test]
+    fn graphql_resolvers_are_traced() {
+        let schema = crate::schema::build_schema().extension(Tracing).finish();
+        let request =
+            async_graphql::Request::new("query Introspect { __schema { queryType { name } } }");
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let spans = crate::telemetry::export_spans(1.0, || {
+            let span = graphql_operation_span(Some("Introspect"));
+            let response = runtime.block_on(schema.execute(request).instrument(span));
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+        });
+
+        let names: Vec<_> = spans.iter().map(|span| span.name.as_ref()).collect();
+        assert!(names.contains(&"field"), "{names:?}");
+        assert!(names.contains(&"execute"), "{names:?}");
+    }
 }
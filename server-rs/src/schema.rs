@@ -2,44 +2,83 @@ use std::fs::File;
 use std::io::Write;
 use std::path::Path;
 
-use async_graphql::{EmptySubscription, MergedObject, Schema, SchemaBuilder};
+use async_graphql::{MergedObject, MergedSubscription, Schema, SchemaBuilder};
 use tokio::io;
 
+use crate::file_blobs::FileBlobsQuery;
 use crate::game_reports::{GameReportsMutation, GameReportsQuery};
 use crate::games::GamesQuery;
 use crate::leagues::LeaguesQuery;
+use crate::live_stream_feed::LiveStreamsSubscription;
+use crate::maps::search::{MapsMutation, MapsQuery};
 use crate::matchmaking::admin::{MatchmakingConfigMutation, MatchmakingConfigQuery};
 use crate::matchmaking::history::MatchmakingHistoryQuery;
+use crate::matchmaking::map_pools::{MatchmakingMapPoolsMutation, MatchmakingMapPoolsQuery};
 use crate::news::{NewsMutation, NewsQuery};
+use crate::oauth::{OAuthMutation, OAuthQuery};
+use crate::stream_analytics::StreamAnalyticsQuery;
 use crate::twitch::{TwitchMutation, TwitchQuery};
+use crate::twitch_chat::{TwitchChatMutation, TwitchChatQuery};
+use crate::users::api_tokens::{ApiTokensMutation, ApiTokensQuery};
+use crate::users::data_export::{DataExportMutation, DataExportQuery};
+use crate::users::deletion::{AccountDeletionMutation, AccountDeletionQuery};
+use crate::users::signup_campaigns::{SignupCampaignsMutation, SignupCampaignsQuery};
 use crate::users::{UsersMutation, UsersQuery};
+use crate::youtube::{YoutubeMutation, YoutubeQuery};
 
-pub type SbSchema = Schema<Query, Mutation, EmptySubscription>;
-pub type SbSchemaBuilder = SchemaBuilder<Query, Mutation, EmptySubscription>;
+pub type SbSchema = Schema<Query, Mutation, Subscription>;
+pub type SbSchemaBuilder = SchemaBuilder<Query, Mutation, Subscription>;
 
 #[derive(MergedObject, Default)]
 pub struct Query(
+    AccountDeletionQuery,
+    ApiTokensQuery,
+    DataExportQuery,
+    FileBlobsQuery,
     GameReportsQuery,
     GamesQuery,
     LeaguesQuery,
+    MapsQuery,
     NewsQuery,
+    OAuthQuery,
+    SignupCampaignsQuery,
+    StreamAnalyticsQuery,
+    TwitchChatQuery,
     TwitchQuery,
     UsersQuery,
+    YoutubeQuery,
     MatchmakingConfigQuery,
     MatchmakingHistoryQuery,
+    MatchmakingMapPoolsQuery,
 );
 
 #[derive(MergedObject, Default)]
 pub struct Mutation(
+    AccountDeletionMutation,
+    ApiTokensMutation,
+    DataExportMutation,
     GameReportsMutation,
+    MapsMutation,
     NewsMutation,
+    OAuthMutation,
+    SignupCampaignsMutation,
+    TwitchChatMutation,
     TwitchMutation,
     UsersMutation,
+    YoutubeMutation,
     MatchmakingConfigMutation,
+    MatchmakingMapPoolsMutation,
 );
 
+#[derive(MergedSubscription, Default)]
+pub struct Subscription(LiveStreamsSubscription);
+
 pub fn build_schema() -> SbSchemaBuilder {
-    Schema::build(Query::default(), Mutation::default(), EmptySubscription)
+    Schema::build(
+        Query::default(),
+        Mutation::default(),
+        Subscription::default(),
+    )
 }
 
 /// Wrties the GraphQL schema to an SDL file at the given path.
@@ -1,6 +1,6 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, LazyLock};
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use axum::Extension;
 use axum::body::Body;
@@ -12,14 +12,20 @@ use axum::response::{IntoResponse, Response};
 use axum_extra::TypedHeader;
 use axum_extra::headers::Authorization;
 use axum_extra::headers::authorization::Bearer;
+use base64::Engine as _;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use color_eyre::eyre::{self, WrapErr};
 use deadpool_redis::redis::AsyncCommands;
-use jsonwebtoken::DecodingKey;
+use jsonwebtoken::{DecodingKey, EncodingKey};
+use rand::{Rng, rng};
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
 use tracing::error;
 
 use crate::configuration::Settings;
 use crate::redis::RedisPool;
 use crate::users::SbUserId;
+use crate::users::api_tokens::{API_TOKEN_PREFIX, ApiTokenSession, authenticate_api_token};
 
 static JWT_VALIDATION: LazyLock<jsonwebtoken::Validation> =
     LazyLock::new(jsonwebtoken::Validation::default);
@@ -35,6 +41,18 @@ struct JwtClaims {
     stay_logged_in: bool,
 }
 
+/// The claims we sign into a session JWT, matching what the Node server's `getJwt` produces.
+#[derive(Serialize)]
+struct SignedJwtClaims<'a> {
+    #[serde(flatten)]
+    claims: &'a JwtClaims,
+    iat: u64,
+    exp: u64,
+}
+
+/// Number of random bytes in a session ID, matching the `uid(24)` the Node server uses.
+const SESSION_ID_BYTES: usize = 24;
+
 #[derive(Clone, Debug)]
 pub struct AuthenticatedSession {
     pub session_id: String,
@@ -75,9 +93,23 @@ impl AuthenticatedSession {
 #[derive(Clone, Debug)]
 pub enum SbSession {
     Authenticated(AuthenticatedSession),
+    /// A request from a third-party tool using one of the user's personal API tokens. Only the
+    /// fields the token's scopes allow can be accessed (see `ApiTokenScopeExtension`).
+    ApiToken(ApiTokenSession),
     Anonymous,
 }
 
+impl SbSession {
+    /// The user this request is acting on behalf of, if any.
+    pub fn user_id(&self) -> Option<SbUserId> {
+        match self {
+            SbSession::Authenticated(session) => Some(session.user_id),
+            SbSession::ApiToken(token) => Some(token.user_id),
+            SbSession::Anonymous => None,
+        }
+    }
+}
+
 fn session_key(user_id: SbUserId, session_id: &str) -> String {
     let user_id: i32 = user_id.into();
     format!("sessions:{user_id}:{session_id}")
@@ -117,6 +149,82 @@ async fn refresh_session_expiration(
     Ok(exists)
 }
 
+/// Deletes every session belonging to a user, forcing any client using one of them to log in
+/// again. Returns how many sessions were listed in the user's session index (some of which may
+/// have already expired).
+pub async fn revoke_user_sessions(
+    redis: &mut impl AsyncCommands,
+    user_id: SbUserId,
+) -> deadpool_redis::redis::RedisResult<usize> {
+    let (session_ids,): (Vec<String>,) = deadpool_redis::redis::pipe()
+        .smembers(user_sessions_key(user_id))
+        .query_async(redis)
+        .await?;
+
+    let mut cleanup = deadpool_redis::redis::pipe();
+    for session_id in &session_ids {
+        cleanup.del(session_key(user_id, session_id)).ignore();
+    }
+    cleanup.del(user_sessions_key(user_id)).ignore();
+    cleanup.query_async::<()>(redis).await?;
+
+    Ok(session_ids.len())
+}
+
+fn gen_session_id() -> String {
+    let mut buf = [0u8; SESSION_ID_BYTES];
+    rng().fill_bytes(&mut buf);
+    BASE64_URL_SAFE_NO_PAD.encode(buf)
+}
+
+/// Starts a new session for a user (e.g. after they sign in through an external identity),
+/// storing it in the same format as the Node server's `beginSession` so either server can load it.
+/// Returns a signed JWT the client should use to authenticate subsequent requests.
+pub async fn begin_session(
+    redis: &mut impl AsyncCommands,
+    jwt_key: &EncodingKey,
+    user_id: SbUserId,
+    stay_logged_in: bool,
+    session_ttl: Duration,
+) -> eyre::Result<String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .wrap_err("System clock is before the unix epoch")?;
+    let claims = JwtClaims {
+        session_id: gen_session_id(),
+        user_id,
+        auth_time: now.as_millis() as u64,
+        stay_logged_in,
+    };
+
+    let ttl_secs = session_ttl.as_secs();
+    deadpool_redis::redis::pipe()
+        .set_ex(
+            session_key(user_id, &claims.session_id),
+            serde_json::to_string(&claims).wrap_err("Failed to serialize session")?,
+            ttl_secs,
+        )
+        .ignore()
+        .sadd(user_sessions_key(user_id), &claims.session_id)
+        .ignore()
+        .expire(user_sessions_key(user_id), ttl_secs as i64)
+        .ignore()
+        .query_async::<()>(redis)
+        .await
+        .wrap_err("Failed to store session")?;
+
+    jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &SignedJwtClaims {
+            claims: &claims,
+            iat: now.as_secs(),
+            exp: now.as_secs() + ttl_secs,
+        },
+        jwt_key,
+    )
+    .wrap_err("Failed to sign session JWT")
+}
+
 async fn load_session(
     redis_pool: &RedisPool,
     jwt_key: Arc<DecodingKey>,
@@ -170,16 +278,29 @@ async fn load_session(
 pub async fn jwt_middleware(
     State(settings): State<Arc<Settings>>,
     State(jwt_key): State<Arc<DecodingKey>>,
+    State(db_pool): State<PgPool>,
     State(redis_pool): State<RedisPool>,
     auth_header: Option<TypedHeader<Authorization<Bearer>>>,
     mut request: Request<Body>,
     next: Next,
 ) -> Response {
-    let session = match load_session(&redis_pool, jwt_key, settings.session_ttl, auth_header).await
-    {
-        Ok(s) => s,
-        Err(r) => {
-            return r.into_response();
+    let api_token = auth_header
+        .as_ref()
+        .map(|h| h.token())
+        .filter(|t| t.starts_with(API_TOKEN_PREFIX));
+    let session = if let Some(token) = api_token {
+        match authenticate_api_token(&db_pool, &redis_pool, token).await {
+            Ok(s) => SbSession::ApiToken(s),
+            Err(e) => {
+                return e.into_response();
+            }
+        }
+    } else {
+        match load_session(&redis_pool, jwt_key, settings.session_ttl, auth_header).await {
+            Ok(s) => s,
+            Err(r) => {
+                return r.into_response();
+            }
         }
     };
 
@@ -227,19 +348,25 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::collections::VecDeque;
+
     use super::*;
     use deadpool_redis::redis::aio::ConnectionLike;
     use deadpool_redis::redis::{Cmd, RedisFuture, Value};
 
     struct FakeRedis {
-        pipeline_response: Option<Vec<Value>>,
+        pipeline_responses: VecDeque<Vec<Value>>,
         pipelines: Vec<Vec<u8>>,
     }
 
     impl FakeRedis {
         fn returning_pipeline(response: Vec<Value>) -> Self {
+            Self::returning_pipelines(vec![response])
+        }
+
+        fn returning_pipelines(responses: Vec<Vec<Value>>) -> Self {
             Self {
-                pipeline_response: Some(response),
+                pipeline_responses: responses.into(),
                 pipelines: Vec::new(),
             }
         }
@@ -258,8 +385,8 @@ mod tests {
         ) -> RedisFuture<'a, Vec<Value>> {
             self.pipelines.push(cmd.get_packed_pipeline());
             let response = self
-                .pipeline_response
-                .take()
+                .pipeline_responses
+                .pop_front()
                 .expect("missing fake Redis pipeline response");
             Box::pin(async move { Ok(response) })
         }
@@ -331,6 +458,75 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn revoke_user_sessions_deletes_indexed_sessions_and_index() {
+        let mut redis = FakeRedis::returning_pipelines(vec![
+            vec![Value::Array(vec![
+                Value::BulkString(b"abc".to_vec()),
+                Value::BulkString(b"def".to_vec()),
+            ])],
+            vec![Value::Int(1), Value::Int(0), Value::Int(1)],
+        ]);
+
+        let revoked = revoke_user_sessions(&mut redis, SbUserId::from(7))
+            .await
+            .unwrap();
+
+        assert_eq!(revoked, 2);
+        assert_eq!(redis.pipelines.len(), 2);
+        let mut expected_cleanup = deadpool_redis::redis::pipe();
+        expected_cleanup
+            .del(session_key(SbUserId::from(7), "abc"))
+            .ignore()
+            .del(session_key(SbUserId::from(7), "def"))
+            .ignore()
+            .del(user_sessions_key(SbUserId::from(7)))
+            .ignore();
+        assert_eq!(redis.pipelines[1], expected_cleanup.get_packed_pipeline());
+    }
+
+    #[tokio::test]
+    async fn begin_session_stores_session_and_signs_jwt() {
+        let mut redis =
+            FakeRedis::returning_pipeline(vec![Value::Okay, Value::Int(1), Value::Int(1)]);
+
+        let jwt = begin_session(
+            &mut redis,
+            &EncodingKey::from_secret(b"secret"),
+            SbUserId::from(7),
+            true,
+            Duration::from_secs(900),
+        )
+        .await
+        .unwrap();
+
+        let claims = jsonwebtoken::decode::<JwtClaims>(
+            &jwt,
+            &DecodingKey::from_secret(b"secret"),
+            &JWT_VALIDATION,
+        )
+        .unwrap()
+        .claims;
+        assert_eq!(claims.user_id, SbUserId::from(7));
+        assert!(claims.stay_logged_in);
+        assert_eq!(claims.session_id.len(), 32);
+
+        let mut expected = deadpool_redis::redis::pipe();
+        expected
+            .set_ex(
+                session_key(SbUserId::from(7), &claims.session_id),
+                serde_json::to_string(&claims).unwrap(),
+                900,
+            )
+            .ignore()
+            .sadd(user_sessions_key(SbUserId::from(7)), &claims.session_id)
+            .ignore()
+            .expire(user_sessions_key(SbUserId::from(7)), 900)
+            .ignore();
+        assert_eq!(redis.pipelines.len(), 1);
+        assert_eq!(redis.pipelines[0], expected.get_packed_pipeline());
+    }
+
     #[test]
     fn destroy_state_is_shared_between_session_clones() {
         let session = AuthenticatedSession::new(JwtClaims {
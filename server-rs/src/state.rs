@@ -1,6 +1,7 @@
 use crate::configuration::Settings;
-use crate::email::MailgunClient;
+use crate::email::Mailer;
 use crate::file_store::FileStore;
+use crate::maps::render::MapRenderer;
 use crate::redis::RedisPool;
 use crate::schema::SbSchema;
 use crate::twitch::TwitchClient;
@@ -16,7 +17,7 @@ pub struct AppState {
     pub settings: Arc<Settings>,
     pub db_pool: PgPool,
     pub redis_pool: RedisPool,
-    pub mailgun: Arc<MailgunClient>,
+    pub mailer: Mailer,
     pub file_store: FileStore,
     pub jwt_key: Arc<DecodingKey>,
     pub graphql_schema: SbSchema,
@@ -24,4 +25,7 @@ pub struct AppState {
     pub name_checker: NameChecker,
     /// `None` when Twitch isn't configured.
     pub twitch_client: Option<Arc<TwitchClient>>,
+    /// `None` when StarCraft's data files aren't configured, in which case maps are stored without
+    /// images.
+    pub map_renderer: Option<Arc<MapRenderer>>,
 }
@@ -0,0 +1,485 @@
+//! Historical stream sessions for linked streamers, and the stats built from them.
+//!
+//! The live-stream state in Redis only knows what's live right now. Every time a provider's live
+//! streams are refreshed (see [`crate::streaming::refresh_provider_live_streams`]), the streams it
+//! reported are also recorded here as sessions: one per broadcast, identified by the provider's
+//! start time, with the viewer count sampled once per refresh. A session ends when a refresh no
+//! longer sees it, so durations are only as precise as the refresh interval.
+
+use std::collections::HashMap;
+
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{ComplexObject, Context, Object, SimpleObject};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{self, Context as _};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::streaming::{StreamingChannel, StreamingProvider};
+use crate::twitch::LiveStreamSummary;
+use crate::users::permissions::RequiredPermission;
+use crate::users::{SbUser, SbUserId, UsersLoader};
+
+/// How many of a user's sessions `SbUser.streamAnalytics` lists.
+const RECENT_SESSIONS_LIMIT: i64 = 20;
+/// The window the top-streamers ranking covers.
+const TOP_STREAMERS_DAYS: i32 = 7;
+
+/// One viewer-count sample of a live stream, attributed to the ShieldBattery user whose channel it
+/// is.
+#[derive(Debug, Clone)]
+struct SessionSample<'a> {
+    user_id: SbUserId,
+    stream: &'a LiveStreamSummary,
+}
+
+/// Matches the streams a provider reported to the users whose channels they're on. Streams from
+/// channels that aren't linked (anymore) are skipped.
+fn session_samples<'a>(
+    connections: &HashMap<SbUserId, StreamingChannel>,
+    live_now: &'a [LiveStreamSummary],
+) -> Vec<SessionSample<'a>> {
+    let users_by_channel = connections
+        .iter()
+        .map(|(user_id, channel)| (channel.id.as_str(), *user_id))
+        .collect::<HashMap<_, _>>();
+    live_now
+        .iter()
+        .filter_map(|stream| {
+            users_by_channel
+                .get(stream.channel_id.as_str())
+                .map(|&user_id| SessionSample { user_id, stream })
+        })
+        .collect()
+}
+
+/// Records one refresh's worth of live streams for `provider`: starts or updates a session for each
+/// stream in `live_now`, and ends any of the provider's open sessions that weren't in it. This
+/// should be given everything the provider reported as live for `connections`, including streams
+/// that don't hold their user's live-stream slot.
+pub(crate) async fn record_live_streams(
+    db: &PgPool,
+    provider: StreamingProvider,
+    connections: &HashMap<SbUserId, StreamingChannel>,
+    live_now: &[LiveStreamSummary],
+) -> eyre::Result<()> {
+    let mut tx = db.begin().await.wrap_err("Failed to start transaction")?;
+
+    let samples = session_samples(connections, live_now);
+    let mut seen = Vec::with_capacity(samples.len());
+    for SessionSample { user_id, stream } in samples {
+        let viewers = stream.viewer_count.clamp(0, i64::from(i32::MAX)) as i32;
+        // `previous` is evaluated against the snapshot from before the upsert, so it holds the
+        // session's title and category as of the last refresh (or nothing for a new session).
+        let row = sqlx::query!(
+            r#"
+                WITH previous AS (
+                    SELECT title, category
+                    FROM stream_sessions
+                    WHERE user_id = $1 AND provider = $2 AND started_at = $4
+                ), upserted AS (
+                    INSERT INTO stream_sessions AS s (user_id, provider, channel_id, started_at,
+                        last_seen_at, title, category, is_starcraft, peak_viewers, viewer_samples,
+                        viewer_sum)
+                    VALUES ($1, $2, $3, $4, NOW(), $5, $6, $7, $8::integer, 1, $8::integer)
+                    ON CONFLICT (user_id, provider, started_at) DO UPDATE
+                    SET
+                        channel_id = EXCLUDED.channel_id,
+                        last_seen_at = EXCLUDED.last_seen_at,
+                        ended_at = NULL,
+                        title = EXCLUDED.title,
+                        category = EXCLUDED.category,
+                        is_starcraft = s.is_starcraft OR EXCLUDED.is_starcraft,
+                        peak_viewers = GREATEST(s.peak_viewers, EXCLUDED.peak_viewers),
+                        viewer_samples = s.viewer_samples + 1,
+                        viewer_sum = s.viewer_sum + EXCLUDED.viewer_sum
+                    RETURNING id
+                )
+                SELECT u.id as "id!", p.title as "previous_title?", p.category as "previous_category?"
+                FROM upserted u
+                LEFT JOIN previous p ON true
+            "#,
+            user_id as _,
+            provider as _,
+            stream.channel_id,
+            stream.started_at,
+            stream.title,
+            stream.game_name,
+            stream.is_starcraft(),
+            viewers,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .wrap_err("Failed to record stream session")?;
+
+        let changed = row.previous_title.as_deref() != Some(stream.title.as_str())
+            || row.previous_category.as_deref() != Some(stream.game_name.as_str());
+        if changed {
+            sqlx::query!(
+                r#"
+                    INSERT INTO stream_session_changes (session_id, changed_at, title, category)
+                    VALUES ($1, NOW(), $2, $3)
+                    ON CONFLICT DO NOTHING
+                "#,
+                row.id,
+                stream.title,
+                stream.game_name,
+            )
+            .execute(&mut *tx)
+            .await
+            .wrap_err("Failed to record stream session change")?;
+        }
+        seen.push(row.id);
+    }
+
+    sqlx::query!(
+        r#"
+            UPDATE stream_sessions
+            SET ended_at = last_seen_at
+            WHERE provider = $1 AND ended_at IS NULL AND NOT (id = ANY($2))
+        "#,
+        provider as _,
+        &seen,
+    )
+    .execute(&mut *tx)
+    .await
+    .wrap_err("Failed to end stream sessions")?;
+
+    tx.commit()
+        .await
+        .wrap_err("Failed to commit stream sessions")?;
+    Ok(())
+}
+
+/// Aggregate stats over a set of stream sessions.
+#[derive(Clone, Debug, Default, PartialEq, SimpleObject)]
+pub struct StreamStats {
+    /// How many sessions there were.
+    pub session_count: i64,
+    /// The total time spent live, in seconds.
+    pub streamed_seconds: i64,
+    /// The highest viewer count seen in any session.
+    pub peak_viewers: i32,
+    /// The average viewer count across every sample from every session.
+    pub average_viewers: f64,
+    /// The total hours watched: each session's average viewer count times its length, summed.
+    pub viewer_hours: f64,
+}
+
+/// A title or category a stream session had, starting at `changed_at`.
+#[derive(Clone, Debug, PartialEq, SimpleObject)]
+pub struct StreamSessionChange {
+    pub changed_at: DateTime<Utc>,
+    pub title: String,
+    pub category: String,
+}
+
+/// A single broadcast by a linked streamer.
+#[derive(Clone, Debug, PartialEq, SimpleObject)]
+pub struct StreamSession {
+    pub id: Uuid,
+    /// The streaming service the session was on.
+    pub provider: StreamingProvider,
+    /// When the broadcast started, according to the streaming service.
+    pub started_at: DateTime<Utc>,
+    /// When the broadcast ended, or `null` if it's still live.
+    pub ended_at: Option<DateTime<Utc>>,
+    /// The session's most recent title.
+    pub title: String,
+    /// The session's most recent category/game (always empty for YouTube).
+    pub category: String,
+    /// Whether the session was a StarCraft stream at any point.
+    pub is_starcraft: bool,
+    pub peak_viewers: i32,
+    pub average_viewers: f64,
+    /// How long the session has been live, in seconds.
+    pub duration_seconds: i64,
+    /// Every title/category the session has had, oldest first (including the current one).
+    pub changes: Vec<StreamSessionChange>,
+}
+
+/// A user's streaming stats over some number of days, with their most recent sessions.
+#[derive(Clone, Debug, PartialEq, SimpleObject)]
+pub struct UserStreamAnalytics {
+    pub stats: StreamStats,
+    /// The user's most recent sessions in the period, newest first (at most 20).
+    pub recent_sessions: Vec<StreamSession>,
+}
+
+/// Site-wide streaming stats over some number of days.
+#[derive(Clone, Debug, PartialEq, SimpleObject)]
+pub struct SiteStreamStats {
+    /// How many different users streamed.
+    pub streamer_count: i64,
+    pub stats: StreamStats,
+}
+
+/// A streamer's place in the top-streamers ranking.
+#[derive(Clone, Debug, PartialEq, SimpleObject)]
+#[graphql(complex)]
+pub struct TopStreamer {
+    #[graphql(skip)]
+    pub user_id: SbUserId,
+    pub viewer_hours: f64,
+    pub streamed_seconds: i64,
+    pub peak_viewers: i32,
+}
+
+#[ComplexObject]
+impl TopStreamer {
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<SbUser>> {
+        ctx.data::<DataLoader<UsersLoader>>()?
+            .load_one(self.user_id)
+            .await
+    }
+}
+
+/// The average of `samples` viewer counts that add up to `sum`.
+fn average_viewers(sum: i64, samples: i64) -> f64 {
+    if samples <= 0 {
+        0.0
+    } else {
+        sum as f64 / samples as f64
+    }
+}
+
+/// Loads the stats for every session started in the last `days` days, optionally limited to one
+/// user. Returns the number of different streamers along with the stats.
+async fn load_stats(
+    db: &PgPool,
+    user_id: Option<SbUserId>,
+    days: i32,
+) -> eyre::Result<(i64, StreamStats)> {
+    let row = sqlx::query!(
+        r#"
+            SELECT
+                COUNT(*) as "session_count!",
+                COUNT(DISTINCT user_id) as "streamer_count!",
+                COALESCE(SUM(EXTRACT(EPOCH FROM last_seen_at - started_at)), 0)::bigint
+                    as "streamed_seconds!",
+                COALESCE(MAX(peak_viewers), 0) as "peak_viewers!",
+                COALESCE(SUM(viewer_sum), 0)::bigint as "viewer_sum!",
+                COALESCE(SUM(viewer_samples), 0)::bigint as "viewer_samples!",
+                COALESCE(SUM(
+                    viewer_sum::float8 / viewer_samples
+                        * EXTRACT(EPOCH FROM last_seen_at - started_at)::float8 / 3600
+                ), 0) as "viewer_hours!"
+            FROM stream_sessions
+            WHERE started_at >= NOW() - make_interval(days => $2)
+                AND ($1::integer IS NULL OR user_id = $1)
+        "#,
+        user_id as _,
+        days,
+    )
+    .fetch_one(db)
+    .await
+    .wrap_err("Failed to load stream stats")?;
+
+    Ok((
+        row.streamer_count,
+        StreamStats {
+            session_count: row.session_count,
+            streamed_seconds: row.streamed_seconds,
+            peak_viewers: row.peak_viewers,
+            average_viewers: average_viewers(row.viewer_sum, row.viewer_samples),
+            viewer_hours: row.viewer_hours,
+        },
+    ))
+}
+
+async fn load_recent_sessions(
+    db: &PgPool,
+    user_id: SbUserId,
+    days: i32,
+) -> eyre::Result<Vec<StreamSession>> {
+    let rows = sqlx::query!(
+        r#"
+            SELECT id, provider as "provider: StreamingProvider", started_at, ended_at, title,
+                category, is_starcraft, peak_viewers, viewer_sum, viewer_samples,
+                EXTRACT(EPOCH FROM last_seen_at - started_at)::bigint as "duration_seconds!"
+            FROM stream_sessions
+            WHERE user_id = $1 AND started_at >= NOW() - make_interval(days => $2)
+            ORDER BY started_at DESC
+            LIMIT $3
+        "#,
+        user_id as _,
+        days,
+        RECENT_SESSIONS_LIMIT,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load stream sessions")?;
+
+    let ids = rows.iter().map(|r| r.id).collect::<Vec<_>>();
+    let mut changes = HashMap::<Uuid, Vec<StreamSessionChange>>::new();
+    let change_rows = sqlx::query!(
+        r#"
+            SELECT session_id, changed_at, title, category
+            FROM stream_session_changes
+            WHERE session_id = ANY($1)
+            ORDER BY changed_at
+        "#,
+        &ids,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load stream session changes")?;
+    for row in change_rows {
+        changes
+            .entry(row.session_id)
+            .or_default()
+            .push(StreamSessionChange {
+                changed_at: row.changed_at,
+                title: row.title,
+                category: row.category,
+            });
+    }
+
+    Ok(rows
+        .into_iter()
+        .map(|r| StreamSession {
+            id: r.id,
+            provider: r.provider,
+            started_at: r.started_at,
+            ended_at: r.ended_at,
+            title: r.title,
+            category: r.category,
+            is_starcraft: r.is_starcraft,
+            peak_viewers: r.peak_viewers,
+            average_viewers: average_viewers(r.viewer_sum, i64::from(r.viewer_samples)),
+            duration_seconds: r.duration_seconds,
+            changes: changes.remove(&r.id).unwrap_or_default(),
+        })
+        .collect())
+}
+
+/// Loads a user's stats and recent sessions for the last `days` days. Backs
+/// `SbUser.streamAnalytics`.
+pub(crate) async fn load_user_stream_analytics(
+    db: &PgPool,
+    user_id: SbUserId,
+    days: i32,
+) -> eyre::Result<UserStreamAnalytics> {
+    let (_, stats) = load_stats(db, Some(user_id), days).await?;
+    let recent_sessions = load_recent_sessions(db, user_id, days).await?;
+    Ok(UserStreamAnalytics {
+        stats,
+        recent_sessions,
+    })
+}
+
+/// Ranks streamers by hours watched on their StarCraft sessions from the last week. Streamers
+/// blocked from the live-streams feed are left out, as they are on the home page.
+async fn load_top_streamers(db: &PgPool, limit: usize) -> eyre::Result<Vec<TopStreamer>> {
+    let rows = sqlx::query_as!(
+        TopStreamer,
+        r#"
+            SELECT
+                s.user_id as "user_id: SbUserId",
+                SUM(
+                    s.viewer_sum::float8 / s.viewer_samples
+                        * EXTRACT(EPOCH FROM s.last_seen_at - s.started_at)::float8 / 3600
+                ) as "viewer_hours!",
+                SUM(EXTRACT(EPOCH FROM s.last_seen_at - s.started_at))::bigint
+                    as "streamed_seconds!",
+                MAX(s.peak_viewers) as "peak_viewers!"
+            FROM stream_sessions s
+            WHERE s.started_at >= NOW() - make_interval(days => $1)
+                AND s.is_starcraft
+                AND NOT EXISTS (SELECT 1 FROM twitch_feed_blocks b WHERE b.user_id = s.user_id)
+            GROUP BY s.user_id
+            ORDER BY 2 DESC, s.user_id
+            LIMIT $2
+        "#,
+        TOP_STREAMERS_DAYS,
+        limit as i64,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load top streamers")?;
+    Ok(rows)
+}
+
+#[derive(Default)]
+pub struct StreamAnalyticsQuery;
+
+#[Object]
+impl StreamAnalyticsQuery {
+    /// Streaming stats across every linked streamer for the last `days` days (1-365).
+    #[graphql(guard = RequiredPermission::ManageLiveStreams)]
+    async fn site_stream_stats(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 7, validator(minimum = 1, maximum = 365))] days: i32,
+    ) -> async_graphql::Result<SiteStreamStats> {
+        let (streamer_count, stats) = load_stats(ctx.data::<PgPool>()?, None, days).await?;
+        Ok(SiteStreamStats {
+            streamer_count,
+            stats,
+        })
+    }
+
+    /// The week's top ShieldBattery streamers, by hours watched on their StarCraft streams (highest
+    /// first). For the home page.
+    #[graphql(complexity = "limit * child_complexity")]
+    async fn top_streamers(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 10, validator(minimum = 1, maximum = 50))] limit: usize,
+    ) -> async_graphql::Result<Vec<TopStreamer>> {
+        Ok(load_top_streamers(ctx.data::<PgPool>()?, limit).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(channel_id: &str) -> LiveStreamSummary {
+        LiveStreamSummary {
+            provider: StreamingProvider::Twitch,
+            channel_id: channel_id.to_owned(),
+            channel_login: channel_id.to_owned(),
+            channel_display_name: channel_id.to_owned(),
+            stream_id: None,
+            title: "ladder".to_owned(),
+            game_id: "11989".to_owned(),
+            game_name: "StarCraft".to_owned(),
+            viewer_count: 10,
+            started_at: Utc::now(),
+            thumbnail_url: String::new(),
+        }
+    }
+
+    fn channel(id: &str) -> StreamingChannel {
+        StreamingChannel {
+            id: id.to_owned(),
+            login: id.to_owned(),
+            display_name: id.to_owned(),
+        }
+    }
+
+    #[test]
+    fn samples_are_attributed_to_linked_users() {
+        let connections = HashMap::from([
+            (SbUserId(1), channel("a")),
+            (SbUserId(2), channel("b")),
+            (SbUserId(3), channel("c")),
+        ]);
+        // "z" isn't linked to anyone (e.g. unlinked since the fetch started), and "c" is offline.
+        let live_now = vec![stream("a"), stream("z"), stream("b")];
+
+        let mut samples = session_samples(&connections, &live_now)
+            .into_iter()
+            .map(|s| (s.user_id.0, s.stream.channel_id.as_str()))
+            .collect::<Vec<_>>();
+        samples.sort();
+        assert_eq!(samples, vec![(1, "a"), (2, "b")]);
+    }
+
+    #[test]
+    fn average_viewers_handles_empty_samples() {
+        assert_eq!(average_viewers(0, 0), 0.0);
+        assert_eq!(average_viewers(30, 4), 7.5);
+    }
+}
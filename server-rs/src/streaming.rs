@@ -0,0 +1,280 @@
+//! The provider-agnostic parts of live-stream tracking. Each streaming service we support (see the
+//! `twitch` and `youtube` modules) implements [`StreamingPlatform`] for linking channels, refreshing
+//! their identities, and looking up which of them are live. Live streams from every service share
+//! one store in Redis (and so one `liveStreams` feed, one set of feed blocks, and one
+//! `SbUser.liveStream`), with each entry recording which service it came from.
+//!
+//! A user can only have one live stream at a time. If they're live on more than one service, the
+//! stream that was picked up first keeps the slot until it ends, so the feed doesn't flip between
+//! them on every refresh.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+
+use color_eyre::eyre;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::redis::RedisPool;
+use crate::stream_analytics::record_live_streams;
+use crate::twitch::{LiveStreamSummary, apply_live_stream_updates, load_live_streams};
+use crate::users::SbUserId;
+
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    async_graphql::Enum,
+    sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "streaming_provider", rename_all = "snake_case")]
+pub enum StreamingProvider {
+    // Entries stored before other services were supported have no provider, and are all Twitch.
+    #[default]
+    Twitch,
+    Youtube,
+}
+
+/// A channel on a streaming service, identified by the service's stable ID. The login and display
+/// name can change, and are refreshed periodically.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamingChannel {
+    pub id: String,
+    /// The channel's URL-safe name (e.g. a Twitch login or YouTube handle).
+    pub login: String,
+    pub display_name: String,
+}
+
+/// A streaming service that users can link a channel from and whose live streams we track.
+pub trait StreamingPlatform: Send + Sync {
+    const PROVIDER: StreamingProvider;
+
+    /// Builds the OAuth authorize URL for linking a channel. `redirect_uri` must be passed to
+    /// `fetch_linked_channel` unchanged when completing the link.
+    fn authorize_url(&self, state: &str, redirect_uri: &str) -> eyre::Result<String>;
+
+    /// Exchanges the `code` from a completed OAuth flow and returns the channel of the user who
+    /// authorized it.
+    fn fetch_linked_channel(
+        &self,
+        code: &str,
+        redirect_uri: &str,
+    ) -> impl Future<Output = eyre::Result<StreamingChannel>> + Send;
+
+    /// Looks up the current identity of a set of channels by ID. Channels the service no longer
+    /// knows about are omitted.
+    fn fetch_channels(
+        &self,
+        ids: &[String],
+    ) -> impl Future<Output = eyre::Result<Vec<StreamingChannel>>> + Send;
+
+    /// Looks up which of `channels` are live right now. Offline channels are omitted.
+    fn fetch_live_streams(
+        &self,
+        channels: &[StreamingChannel],
+    ) -> impl Future<Output = eyre::Result<Vec<LiveStreamSummary>>> + Send;
+}
+
+/// How the currently stored live streams relate to one provider's linked channels.
+struct StoredLiveStreams {
+    /// Users with a stored stream from this provider that matches their linked channel.
+    tracked: HashSet<SbUserId>,
+    /// Users with a stored stream from this provider that doesn't match a linked channel (they
+    /// unlinked, or relinked a different channel, while live). These should be removed.
+    orphaned: Vec<SbUserId>,
+    /// Users whose live-stream slot is held by a stream from another provider.
+    claimed_elsewhere: HashSet<SbUserId>,
+}
+
+impl StoredLiveStreams {
+    fn new(
+        provider: StreamingProvider,
+        connections: &HashMap<SbUserId, StreamingChannel>,
+        stored: Vec<(SbUserId, LiveStreamSummary)>,
+    ) -> Self {
+        let mut result = Self {
+            tracked: HashSet::new(),
+            orphaned: Vec::new(),
+            claimed_elsewhere: HashSet::new(),
+        };
+        for (user_id, summary) in stored {
+            if summary.provider != provider {
+                result.claimed_elsewhere.insert(user_id);
+            } else if connections
+                .get(&user_id)
+                .is_some_and(|c| c.id == summary.channel_id)
+            {
+                result.tracked.insert(user_id);
+            } else {
+                result.orphaned.push(user_id);
+            }
+        }
+        result
+    }
+
+    /// Works out which streams to store and which to clear, given everything from this provider
+    /// that's live right now.
+    fn updates(
+        &self,
+        connections: &HashMap<SbUserId, StreamingChannel>,
+        live_now: Vec<LiveStreamSummary>,
+    ) -> (Vec<(SbUserId, LiveStreamSummary)>, Vec<SbUserId>) {
+        let mut live_now = live_now
+            .into_iter()
+            .map(|s| (s.channel_id.clone(), s))
+            .collect::<HashMap<_, _>>();
+
+        let mut live = Vec::with_capacity(live_now.len());
+        let mut offline = Vec::new();
+        for (user_id, channel) in connections {
+            match live_now.remove(&channel.id) {
+                Some(summary) if !self.claimed_elsewhere.contains(user_id) => {
+                    live.push((*user_id, summary));
+                }
+                Some(_) => {}
+                // Only clear entries we're actually tracking so we don't issue an HDEL per offline
+                // connection every pass.
+                None if self.tracked.contains(user_id) => offline.push(*user_id),
+                None => {}
+            }
+        }
+        (live, offline)
+    }
+}
+
+/// Reconciles the stored live streams for one provider with what the provider reports, given every
+/// linked channel for that provider: refreshes stats for those live, clears anyone no longer live,
+/// and picks up streams we hadn't noticed yet. Everything the provider reports is also recorded as
+/// stream sessions for analytics (see the `stream_analytics` module).
+pub(crate) async fn refresh_provider_live_streams<P: StreamingPlatform>(
+    platform: &P,
+    connections: &HashMap<SbUserId, StreamingChannel>,
+    db: &PgPool,
+    redis: &RedisPool,
+) -> eyre::Result<()> {
+    let stored = StoredLiveStreams::new(P::PROVIDER, connections, load_live_streams(redis).await?);
+
+    // Orphaned entries have nothing left that would clear them (e.g. no more webhooks for an
+    // unlinked Twitch account), and would otherwise stay pinned live for the rest of the stream.
+    // These are cleared even if the provider can't be reached below.
+    apply_live_stream_updates(redis, &[], &stored.orphaned).await?;
+
+    let live_now = if connections.is_empty() {
+        Vec::new()
+    } else {
+        let channels = connections.values().cloned().collect::<Vec<_>>();
+        platform.fetch_live_streams(&channels).await?
+    };
+
+    // Analytics are best-effort, and shouldn't hold up the live state that everything else uses.
+    if let Err(e) = record_live_streams(db, P::PROVIDER, connections, &live_now).await {
+        error!("Failed to record {:?} stream sessions: {e:?}", P::PROVIDER);
+    }
+    if connections.is_empty() {
+        return Ok(());
+    }
+
+    let (live, offline) = stored.updates(connections, live_now);
+    apply_live_stream_updates(redis, &live, &offline).await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn channel(id: &str) -> StreamingChannel {
+        StreamingChannel {
+            id: id.to_owned(),
+            login: format!("login-{id}"),
+            display_name: format!("Channel {id}"),
+        }
+    }
+
+    fn summary(provider: StreamingProvider, channel_id: &str) -> LiveStreamSummary {
+        LiveStreamSummary {
+            provider,
+            channel_id: channel_id.to_owned(),
+            channel_login: format!("login-{channel_id}"),
+            channel_display_name: format!("Channel {channel_id}"),
+            stream_id: None,
+            title: "Ladder".to_owned(),
+            game_id: "11989".to_owned(),
+            game_name: "StarCraft".to_owned(),
+            viewer_count: 5,
+            started_at: Utc::now(),
+            thumbnail_url: String::new(),
+        }
+    }
+
+    #[test]
+    fn stored_streams_are_classified_per_provider() {
+        let connections = HashMap::from([
+            (SbUserId(1), channel("a")),
+            (SbUserId(2), channel("b")),
+            (SbUserId(3), channel("c")),
+        ]);
+        let stored = StoredLiveStreams::new(
+            StreamingProvider::Youtube,
+            &connections,
+            vec![
+                (SbUserId(1), summary(StreamingProvider::Youtube, "a")),
+                // Relinked a different channel while live
+                (SbUserId(2), summary(StreamingProvider::Youtube, "old")),
+                (SbUserId(3), summary(StreamingProvider::Twitch, "twitch-c")),
+                // Unlinked entirely
+                (SbUserId(4), summary(StreamingProvider::Youtube, "d")),
+            ],
+        );
+
+        assert_eq!(stored.tracked, HashSet::from([SbUserId(1)]));
+        let mut orphaned = stored.orphaned.clone();
+        orphaned.sort_by_key(|u| u.0);
+        assert_eq!(orphaned, vec![SbUserId(2), SbUserId(4)]);
+        assert_eq!(stored.claimed_elsewhere, HashSet::from([SbUserId(3)]));
+    }
+
+    #[test]
+    fn updates_respect_streams_from_other_providers() {
+        let connections = HashMap::from([
+            (SbUserId(1), channel("a")),
+            (SbUserId(2), channel("b")),
+            (SbUserId(3), channel("c")),
+            (SbUserId(4), channel("d")),
+        ]);
+        let stored = StoredLiveStreams::new(
+            StreamingProvider::Youtube,
+            &connections,
+            vec![
+                (SbUserId(1), summary(StreamingProvider::Youtube, "a")),
+                (SbUserId(2), summary(StreamingProvider::Youtube, "b")),
+                (SbUserId(3), summary(StreamingProvider::Twitch, "twitch-c")),
+            ],
+        );
+
+        let (live, offline) = stored.updates(
+            &connections,
+            vec![
+                summary(StreamingProvider::Youtube, "a"),
+                summary(StreamingProvider::Youtube, "c"),
+                summary(StreamingProvider::Youtube, "d"),
+            ],
+        );
+
+        // 1 is still live, 2 went offline, 3 is live on Twitch already so its YouTube stream is
+        // ignored, and 4 is newly live.
+        let mut live_ids = live.iter().map(|(u, _)| u.0).collect::<Vec<_>>();
+        live_ids.sort_unstable();
+        assert_eq!(live_ids, vec![1, 4]);
+        assert_eq!(offline, vec![SbUserId(2)]);
+    }
+}
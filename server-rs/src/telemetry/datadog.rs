@@ -14,6 +14,8 @@ use tracing_subscriber::Layer;
 use tracing_subscriber::layer::Context;
 use tracing_subscriber::registry::LookupSpan;
 
+use super::datadog_spool::{DiskSpool, SpoolOptions, SpoolReadError};
+
 const SOURCE: &str = "sb-telemetry-datadog";
 const TAGS: &str = "version:0.1.0";
 const MAX_BATCH_SIZE: usize = 1000;
@@ -24,12 +26,18 @@ const MAX_RETRIES: u8 = 3;
 const HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
 const HTTP_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
 const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(100);
+/// How often to retry sending spooled logs while no new logs are being sent (which would otherwise
+/// trigger the retry).
+const SPOOL_RETRY_INTERVAL: Duration = Duration::from_secs(30);
 
 const QUEUE_SIZE_METRIC: &str = "datadog_log_queue_size";
 const DROPPED_LOGS_METRIC: &str = "datadog_logs_dropped_total";
 const BATCH_SIZE_METRIC: &str = "datadog_log_batch_size";
 const SEND_DURATION_METRIC: &str = "datadog_log_send_duration_seconds";
 const SEND_FAILURES_METRIC: &str = "datadog_log_send_failures_total";
+const SPOOLED_LOGS_METRIC: &str = "datadog_logs_spooled_total";
+const UNSPOOLED_LOGS_METRIC: &str = "datadog_logs_unspooled_total";
+const SPOOL_SIZE_METRIC: &str = "datadog_log_spool_bytes";
 
 /// How long [flush_datadog_logs] waits for the ingestor thread to drain the log channel into its
 /// queue before forcing a send. Logging is asynchronous (`on_event` -> channel -> thread -> queue),
@@ -37,20 +45,20 @@ const SEND_FAILURES_METRIC: &str = "datadog_log_send_failures_total";
 const FLUSH_DRAIN_GRACE: Duration = Duration::from_millis(250);
 
 /// Hard upper bound on how long [flush_datadog_logs] will spend trying to send. This is shorter than
-/// the ingestor's normal HTTP timeout because fatal shutdown is best-effort and time-sensitive.
+/// the ingestor's normal HTTP timeout because shutdown is best-effort and time-sensitive.
 const FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
 
-/// A clone of the running ingestor, stashed so a fatal shutdown path can force a synchronous flush
-/// of buffered logs before the process exits. The clone shares the underlying queue and HTTP client
+/// A clone of the running ingestor, stashed so shutdown can force a synchronous flush of buffered
+/// logs before the process exits. The clone shares the underlying queue and HTTP client
 /// (both `Arc`-backed), so flushing through it drains the same buffer the layer is filling. `None`
 /// when Datadog logging isn't configured.
 static INGESTOR: OnceLock<DatadogIngestor> = OnceLock::new();
 
 /// Forces a best-effort, synchronous flush of any buffered Datadog logs, bounded by
-/// [FLUSH_TIMEOUT]. Intended for fatal paths that call [`std::process::exit`], which skips both the
-/// periodic flush and the Drop-based flush, so a log emitted just before exiting would otherwise
-/// never reach Datadog (it still reaches stdout synchronously). No-op when Datadog logging isn't
-/// configured.
+/// [FLUSH_TIMEOUT]. Intended for when the process is exiting, which skips both the periodic flush
+/// and the Drop-based flush (the global subscriber is never dropped), so a log emitted just before
+/// exiting would otherwise never reach Datadog (it still reaches stdout synchronously). No-op when
+/// Datadog logging isn't configured.
 pub async fn flush_datadog_logs() {
     let Some(ingestor) = INGESTOR.get() else {
         return;
@@ -84,6 +92,9 @@ pub struct DatadogOptions {
     pub region: Option<Region>,
     pub url: Option<String>,
     pub tags: Option<String>,
+    /// Where to keep logs that couldn't be sent, to retry once Datadog is reachable again. If
+    /// `None`, those logs are dropped.
+    pub spool: Option<SpoolOptions>,
 }
 
 impl Default for DatadogOptions {
@@ -94,6 +105,7 @@ impl Default for DatadogOptions {
             region: None,
             url: None,
             tags: None,
+            spool: None,
         }
     }
 }
@@ -128,6 +140,12 @@ impl DatadogOptions {
         self.url = Some(url.into());
         self
     }
+
+    #[must_use]
+    pub fn with_spool(mut self, spool: SpoolOptions) -> Self {
+        self.spool = Some(spool);
+        self
+    }
 }
 
 type Log = Map<String, Value>;
@@ -190,7 +208,7 @@ pub struct DatadogLogLayer {
 impl DatadogLogLayer {
     pub fn new(options: DatadogOptions) -> Self {
         let ingestor = DatadogIngestor::new(options);
-        // Stash a clone (sharing the same queue + client) so a fatal shutdown can force a flush.
+        // Stash a clone (sharing the same queue + client) so shutdown can force a flush.
         // Ignore the error if it's already set — only one layer is ever created per process.
         let _ = INGESTOR.set(ingestor.clone());
 
@@ -322,6 +340,9 @@ struct DatadogIngestor {
     api_key: SecretString,
     client: reqwest::Client,
     queue: Arc<Mutex<LogQueue>>,
+    /// Logs that couldn't be sent. Only accessed while holding `send_lock`, which keeps everything
+    /// being sent in order.
+    spool: Option<Arc<Mutex<DiskSpool>>>,
     send_lock: Arc<Mutex<()>>,
     wake_sender: Arc<Notify>,
 
@@ -342,6 +363,15 @@ enum SendLogsError {
     Rejected(u16),
 }
 
+/// The outcome of trying to deliver a batch of logs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Delivery {
+    /// The logs were sent, or were dropped because retrying them wouldn't help.
+    Done,
+    /// Datadog couldn't be reached, so the logs should be retried later.
+    Unavailable,
+}
+
 impl DatadogIngestor {
     pub fn new(options: DatadogOptions) -> Self {
         let url = options.url.unwrap_or_else(|| {
@@ -364,11 +394,32 @@ impl DatadogIngestor {
             .build()
             .expect("Datadog HTTP client configuration should be valid");
 
+        let spool = options.spool.and_then(|spool_options| {
+            let dir = spool_options.dir.clone();
+            match DiskSpool::open(spool_options) {
+                Ok((spool, corrupt_segments)) => {
+                    if corrupt_segments > 0 {
+                        eprintln!(
+                            "DatadogIngestor found {corrupt_segments} corrupt spool segments in \
+                            {dir:?}, their remaining logs will be skipped"
+                        );
+                    }
+                    ::metrics::gauge!(SPOOL_SIZE_METRIC).set(spool.bytes() as f64);
+                    Some(Arc::new(Mutex::new(spool)))
+                }
+                Err(e) => {
+                    eprintln!("DatadogIngestor failed to open log spool in {dir:?}: {e:?}");
+                    None
+                }
+            }
+        });
+
         Self {
             url,
             api_key: options.api_key,
             client,
             queue: Arc::new(Mutex::new(LogQueue::default())),
+            spool,
             send_lock: Arc::new(Mutex::new(())),
             wake_sender: Arc::new(Notify::new()),
 
@@ -385,7 +436,11 @@ impl DatadogIngestor {
         tokio::spawn(async move {
             loop {
                 let deadline = this.queue.lock().await.next_send_deadline();
-                if let Some(deadline) = deadline {
+                let spool_retry_at = this
+                    .has_spooled_logs()
+                    .await
+                    .then(|| Instant::now() + SPOOL_RETRY_INTERVAL);
+                if let Some(deadline) = deadline.into_iter().chain(spool_retry_at).min() {
                     tokio::select! {
                         _ = tokio::time::sleep_until(tokio::time::Instant::from_std(deadline)) => {}
                         () = this.wake_sender.notified() => {}
@@ -394,6 +449,10 @@ impl DatadogIngestor {
                     this.wake_sender.notified().await;
                 }
                 this.try_send(false).await;
+                if spool_retry_at.is_some_and(|t| Instant::now() >= t) {
+                    let _send_guard = this.send_lock.lock().await;
+                    this.drain_spool().await;
+                }
             }
         });
     }
@@ -442,56 +501,136 @@ impl DatadogIngestor {
             };
             ::metrics::histogram!(BATCH_SIZE_METRIC).record(logs.len() as f64);
 
-            let send_start = Instant::now();
-            let result = self.send_logs(&logs).await;
-            ::metrics::histogram!(SEND_DURATION_METRIC).record(send_start.elapsed().as_secs_f64());
-
-            match result {
-                Err(SendLogsError::PayloadTooLarge) => {
-                    // Split the payload in half and try again
-                    let half = logs.len() / 2;
-                    let (first, second) = logs.split_at(half);
-                    // TODO(tec27): To be super safe we should probably be able to keep splitting
-                    // these if necessary, but doing that without recursion is annoying so I
-                    // haven't implemented it for now (I think that would be a pretty rare case
-                    // anyway given the size of things we log)
-                    if let Err(e) = self.send_logs(first).await {
-                        ::metrics::counter!(SEND_FAILURES_METRIC, "reason" => "split_send")
-                            .increment(1);
-                        ::metrics::counter!(
-                            DROPPED_LOGS_METRIC,
-                            "reason" => "split_send_failed"
-                        )
-                        .increment(first.len() as u64);
-                        eprintln!("DatadogIngestor failed to send split logs: {e:?}");
-                    }
-                    if let Err(e) = self.send_logs(second).await {
-                        ::metrics::counter!(SEND_FAILURES_METRIC, "reason" => "split_send")
-                            .increment(1);
-                        ::metrics::counter!(
-                            DROPPED_LOGS_METRIC,
-                            "reason" => "split_send_failed"
-                        )
-                        .increment(second.len() as u64);
-                        eprintln!("DatadogIngestor failed to send split logs: {e:?}");
-                    }
+            // Anything spooled has to go out first to keep the logs in order, and if it can't, the
+            // intake is probably still down so there's no point trying to send the new ones either
+            if !self.drain_spool().await || self.deliver(&logs).await == Delivery::Unavailable {
+                self.spool_or_drop(&logs).await;
+            }
+        }
+    }
+
+    /// Sends a batch of logs, splitting it if it's too large. Logs that Datadog rejects are
+    /// dropped, since retrying them wouldn't help.
+    async fn deliver(&self, logs: &[Log]) -> Delivery {
+        let send_start = Instant::now();
+        let result = self.send_logs(logs).await;
+        ::metrics::histogram!(SEND_DURATION_METRIC).record(send_start.elapsed().as_secs_f64());
+
+        match result {
+            Err(SendLogsError::PayloadTooLarge) => {
+                // Split the payload in half and try again
+                let half = logs.len() / 2;
+                let (first, second) = logs.split_at(half);
+                // TODO(tec27): To be super safe we should probably be able to keep splitting
+                // these if necessary, but doing that without recursion is annoying so I
+                // haven't implemented it for now (I think that would be a pretty rare case
+                // anyway given the size of things we log)
+                if let Err(e) = self.send_logs(first).await {
+                    ::metrics::counter!(SEND_FAILURES_METRIC, "reason" => "split_send")
+                        .increment(1);
+                    ::metrics::counter!(
+                        DROPPED_LOGS_METRIC,
+                        "reason" => "split_send_failed"
+                    )
+                    .increment(first.len() as u64);
+                    eprintln!("DatadogIngestor failed to send split logs: {e:?}");
                 }
-                Err(SendLogsError::RetriesExceeded) => {
-                    ::metrics::counter!(SEND_FAILURES_METRIC, "reason" => "retries_exceeded")
+                if let Err(e) = self.send_logs(second).await {
+                    ::metrics::counter!(SEND_FAILURES_METRIC, "reason" => "split_send")
                         .increment(1);
-                    ::metrics::counter!(DROPPED_LOGS_METRIC, "reason" => "send_failed")
-                        .increment(logs.len() as u64);
-                    eprintln!("DatadogIngestor failed to send logs after max retries");
+                    ::metrics::counter!(
+                        DROPPED_LOGS_METRIC,
+                        "reason" => "split_send_failed"
+                    )
+                    .increment(second.len() as u64);
+                    eprintln!("DatadogIngestor failed to send split logs: {e:?}");
+                }
+                Delivery::Done
+            }
+            Err(SendLogsError::RetriesExceeded) => {
+                ::metrics::counter!(SEND_FAILURES_METRIC, "reason" => "retries_exceeded")
+                    .increment(1);
+                eprintln!("DatadogIngestor failed to send logs after max retries");
+                Delivery::Unavailable
+            }
+            Err(SendLogsError::Rejected(status)) => {
+                ::metrics::counter!(SEND_FAILURES_METRIC, "reason" => "rejected").increment(1);
+                ::metrics::counter!(DROPPED_LOGS_METRIC, "reason" => "rejected")
+                    .increment(logs.len() as u64);
+                eprintln!("DatadogIngestor failed to send logs: Datadog returned {status}");
+                Delivery::Done
+            }
+            Ok(_) => Delivery::Done,
+        }
+    }
+
+    async fn has_spooled_logs(&self) -> bool {
+        match &self.spool {
+            Some(spool) => !spool.lock().await.is_empty(),
+            None => false,
+        }
+    }
+
+    /// Sends spooled logs, oldest first, until the spool is empty or Datadog can't be reached.
+    /// Returns whether the spool was emptied. Must be called while holding `send_lock`.
+    async fn drain_spool(&self) -> bool {
+        let Some(spool) = &self.spool else {
+            return true;
+        };
+        let mut spool = spool.lock().await;
+        loop {
+            let batch = match spool.peek() {
+                Ok(Some(batch)) => batch,
+                Ok(None) => return true,
+                Err(SpoolReadError::Corrupt(lost)) => {
+                    ::metrics::counter!(DROPPED_LOGS_METRIC, "reason" => "spool_corrupt")
+                        .increment(lost);
+                    ::metrics::gauge!(SPOOL_SIZE_METRIC).set(spool.bytes() as f64);
+                    eprintln!("DatadogIngestor skipped a corrupt spool segment ({lost} logs)");
+                    continue;
+                }
+                Err(SpoolReadError::Io(e)) => {
+                    eprintln!("DatadogIngestor failed to read from log spool: {e:?}");
+                    return false;
                 }
-                Err(SendLogsError::Rejected(status)) => {
-                    ::metrics::counter!(SEND_FAILURES_METRIC, "reason" => "rejected").increment(1);
-                    ::metrics::counter!(DROPPED_LOGS_METRIC, "reason" => "rejected")
-                        .increment(logs.len() as u64);
-                    eprintln!("DatadogIngestor failed to send logs: Datadog returned {status}");
+            };
+
+            if self.deliver(&batch.logs).await == Delivery::Unavailable {
+                return false;
+            }
+            ::metrics::counter!(UNSPOOLED_LOGS_METRIC).increment(batch.logs.len() as u64);
+            if let Err(e) = spool.consume(batch) {
+                eprintln!("DatadogIngestor failed to remove sent logs from spool: {e:?}");
+                return false;
+            }
+            ::metrics::gauge!(SPOOL_SIZE_METRIC).set(spool.bytes() as f64);
+        }
+    }
+
+    /// Writes logs that couldn't be sent to the spool, or drops them if there isn't one. Must be
+    /// called while holding `send_lock`.
+    async fn spool_or_drop(&self, logs: &[Log]) {
+        let Some(spool) = &self.spool else {
+            ::metrics::counter!(DROPPED_LOGS_METRIC, "reason" => "send_failed")
+                .increment(logs.len() as u64);
+            return;
+        };
+        let mut spool = spool.lock().await;
+        match spool.append(logs) {
+            Ok(evicted) => {
+                ::metrics::counter!(SPOOLED_LOGS_METRIC).increment(logs.len() as u64);
+                if evicted > 0 {
+                    ::metrics::counter!(DROPPED_LOGS_METRIC, "reason" => "spool_full")
+                        .increment(evicted);
                 }
-                Ok(_) => {}
+            }
+            Err(e) => {
+                ::metrics::counter!(DROPPED_LOGS_METRIC, "reason" => "spool_write_failed")
+                    .increment(logs.len() as u64);
+                eprintln!("DatadogIngestor failed to spool logs: {e:?}");
             }
         }
+        ::metrics::gauge!(SPOOL_SIZE_METRIC).set(spool.bytes() as f64);
     }
 
     async fn send_logs(&self, logs: &[Log]) -> Result<(), SendLogsError> {
@@ -585,6 +724,7 @@ fn retry_delay(attempt: u8) -> Duration {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::Mutex as StdMutex;
 
     fn event(received_at: Instant) -> LogEvent {
         LogEvent {
@@ -667,4 +807,86 @@ mod tests {
             json!(MAX_BATCH_SIZE)
         );
     }
+
+    fn log_with_id(id: u64) -> Log {
+        let mut log = Map::new();
+        log.insert("id".to_string(), json!(id));
+        log
+    }
+
+    #[tokio::test]
+    async fn spools_logs_during_outage_and_sends_them_in_order_after() {
+        let mut server = mockito::Server::new_async().await;
+        let outage = server
+            .mock("POST", "/logs")
+            .with_status(503)
+            .create_async()
+            .await;
+        let dir = tempfile::tempdir().unwrap();
+        let ingestor = DatadogIngestor::new(
+            DatadogOptions::new("test", "key")
+                .with_url(format!("{}/logs", server.url()))
+                .with_spool(SpoolOptions {
+                    dir: dir.path().to_path_buf(),
+                    max_bytes: 1024 * 1024,
+                }),
+        );
+
+        ingestor.ingest(log_with_id(0)).await;
+        ingestor.flush().await;
+        ingestor.ingest(log_with_id(1)).await;
+        ingestor.flush().await;
+        assert!(ingestor.has_spooled_logs().await);
+        outage.remove_async().await;
+
+        let received = Arc::new(StdMutex::new(Vec::new()));
+        let recovered = {
+            let received = received.clone();
+            server
+                .mock("POST", "/logs")
+                .with_status(202)
+                .with_body_from_request(move |req| {
+                    let logs: Vec<Log> = serde_json::from_slice(req.body().unwrap()).unwrap();
+                    received
+                        .lock()
+                        .unwrap()
+                        .push(logs.iter().map(|l| l["id"].clone()).collect::<Vec<_>>());
+                    Vec::new()
+                })
+                .expect(3)
+                .create_async()
+                .await
+        };
+
+        ingestor.ingest(log_with_id(2)).await;
+        ingestor.flush().await;
+
+        recovered.assert_async().await;
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![vec![json!(0)], vec![json!(1)], vec![json!(2)]]
+        );
+        assert!(!ingestor.has_spooled_logs().await);
+    }
+
+    #[tokio::test]
+    async fn drops_logs_during_outage_without_a_spool() {
+        let mut server = mockito::Server::new_async().await;
+        let outage = server
+            .mock("POST", "/logs")
+            .with_status(503)
+            .expect(MAX_RETRIES as usize)
+            .create_async()
+            .await;
+        let ingestor = DatadogIngestor::new(
+            DatadogOptions::new("test", "key").with_url(format!("{}/logs", server.url())),
+        );
+
+        ingestor.ingest(log_with_id(0)).await;
+        ingestor.flush().await;
+
+        outage.assert_async().await;
+        assert!(!ingestor.has_spooled_logs().await);
+        assert_eq!(ingestor.queue.lock().await.len(), 0);
+    }
 }
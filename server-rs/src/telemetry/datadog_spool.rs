@@ -0,0 +1,415 @@
+//! A bounded on-disk spool for batches of Datadog logs that couldn't be sent, so that an intake
+//! outage doesn't lose them. Batches are appended as checksummed records to a sequence of segment
+//! files, and read back (oldest first) once the intake recovers.
+//!
+//! Delivery is at-least-once: the read position within the oldest segment is only kept in memory,
+//! so if the process exits partway through draining a segment, that segment's already-sent batches
+//! will be sent again after a restart.
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+type Log = Map<String, Value>;
+
+/// The size at which the current segment is closed and a new one started. Space is reclaimed a
+/// segment at a time, so this also bounds how much gets dropped at once when the spool is full.
+const MAX_SEGMENT_BYTES: u64 = 8 * 1024 * 1024;
+const SEGMENT_EXTENSION: &str = "spool";
+/// Record header: payload length, log count, and a CRC-32 of the count and payload (all u32 LE).
+const HEADER_LEN: u64 = 12;
+
+#[derive(Debug, Clone)]
+pub struct SpoolOptions {
+    /// The directory to store segment files in. Created if it doesn't exist. This should be
+    /// dedicated to the spool, as any `.spool` files in it will be treated as segments.
+    pub dir: PathBuf,
+    /// The maximum total size of the segment files. Once it's reached, the oldest segment is
+    /// deleted to make room for new batches.
+    pub max_bytes: u64,
+}
+
+#[derive(Debug)]
+struct Segment {
+    seq: u64,
+    /// The size of the valid records in this segment (anything after them is ignored).
+    bytes: u64,
+    /// The number of logs in this segment's valid records.
+    logs: u64,
+}
+
+#[derive(Debug)]
+pub struct DiskSpool {
+    dir: PathBuf,
+    max_bytes: u64,
+    segments: VecDeque<Segment>,
+    total_bytes: u64,
+    /// How far into the oldest segment has been consumed.
+    read_offset: u64,
+    /// How many logs from the oldest segment have been consumed.
+    read_logs: u64,
+}
+
+/// A batch of logs read from the spool. Pass it to [DiskSpool::consume] once it's been handled.
+#[derive(Debug)]
+pub struct SpooledBatch {
+    pub logs: Vec<Log>,
+    record_len: u64,
+}
+
+impl DiskSpool {
+    /// Opens the spool in `options.dir`, picking up any segments left by a previous process.
+    /// Returns the spool along with the number of segments that contained corrupt records (which
+    /// are ignored from the first corrupt record onward).
+    pub fn open(options: SpoolOptions) -> io::Result<(Self, usize)> {
+        fs::create_dir_all(&options.dir)?;
+
+        let mut seqs = Vec::new();
+        for entry in fs::read_dir(&options.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == SEGMENT_EXTENSION)
+                && let Some(seq) = path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .and_then(|s| s.parse::<u64>().ok())
+            {
+                seqs.push(seq);
+            }
+        }
+        seqs.sort_unstable();
+
+        let mut segments = VecDeque::with_capacity(seqs.len());
+        let mut total_bytes = 0;
+        let mut corrupt_segments = 0;
+        for seq in seqs {
+            let path = segment_path(&options.dir, seq);
+            let (bytes, logs, corrupt) = scan_segment(&path)?;
+            if corrupt {
+                corrupt_segments += 1;
+            }
+            if logs == 0 {
+                fs::remove_file(&path)?;
+                continue;
+            }
+            total_bytes += bytes;
+            segments.push_back(Segment { seq, bytes, logs });
+        }
+
+        Ok((
+            Self {
+                dir: options.dir,
+                max_bytes: options.max_bytes,
+                segments,
+                total_bytes,
+                read_offset: 0,
+                read_logs: 0,
+            },
+            corrupt_segments,
+        ))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// The size of the spooled (and not yet consumed) records.
+    pub fn bytes(&self) -> u64 {
+        self.total_bytes - self.read_offset
+    }
+
+    /// Appends a batch of logs to the spool, deleting the oldest segments if necessary to stay
+    /// within the size limit. Returns how many previously spooled logs were dropped to make room.
+    pub fn append(&mut self, logs: &[Log]) -> io::Result<u64> {
+        let payload = serde_json::to_vec(logs).map_err(io::Error::other)?;
+        let record = encode_record(&payload, logs.len() as u32);
+        let record_len = record.len() as u64;
+        if record_len > self.max_bytes {
+            return Err(io::Error::other("batch is larger than the spool"));
+        }
+
+        let mut dropped = 0;
+        while self.total_bytes + record_len > self.max_bytes {
+            let Some(oldest) = self.segments.pop_front() else {
+                break;
+            };
+            fs::remove_file(segment_path(&self.dir, oldest.seq))?;
+            self.total_bytes -= oldest.bytes;
+            dropped += oldest.logs - self.read_logs;
+            self.read_offset = 0;
+            self.read_logs = 0;
+        }
+
+        let needs_new_segment = self
+            .segments
+            .back()
+            .is_none_or(|s| s.bytes + record_len > MAX_SEGMENT_BYTES);
+        if needs_new_segment {
+            let seq = self.segments.back().map_or(0, |s| s.seq + 1);
+            self.segments.push_back(Segment {
+                seq,
+                bytes: 0,
+                logs: 0,
+            });
+        }
+        let segment = self
+            .segments
+            .back_mut()
+            .expect("a segment was just ensured");
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(segment_path(&self.dir, segment.seq))?;
+        // Write after the last valid record, overwriting anything left by a torn write
+        file.set_len(segment.bytes)?;
+        file.seek(SeekFrom::Start(segment.bytes))?;
+        file.write_all(&record)?;
+        file.sync_data()?;
+
+        segment.bytes += record_len;
+        segment.logs += logs.len() as u64;
+        self.total_bytes += record_len;
+        Ok(dropped)
+    }
+
+    /// Reads the oldest batch in the spool without removing it. If the oldest segment turns out to
+    /// be unreadable, it's deleted and the number of logs lost with it is returned as an error.
+    pub fn peek(&mut self) -> Result<Option<SpooledBatch>, SpoolReadError> {
+        let Some(oldest) = self.segments.front() else {
+            return Ok(None);
+        };
+        let path = segment_path(&self.dir, oldest.seq);
+
+        match read_record(&path, self.read_offset) {
+            Ok(Some((logs, record_len))) => Ok(Some(SpooledBatch { logs, record_len })),
+            Ok(None) | Err(_) => {
+                let lost = oldest.logs - self.read_logs;
+                self.remove_oldest()?;
+                Err(SpoolReadError::Corrupt(lost))
+            }
+        }
+    }
+
+    /// Removes a batch returned by [Self::peek] from the spool.
+    pub fn consume(&mut self, batch: SpooledBatch) -> io::Result<()> {
+        self.read_offset += batch.record_len;
+        self.read_logs += batch.logs.len() as u64;
+        if self
+            .segments
+            .front()
+            .is_some_and(|s| self.read_offset >= s.bytes)
+        {
+            self.remove_oldest()?;
+        }
+        Ok(())
+    }
+
+    fn remove_oldest(&mut self) -> io::Result<()> {
+        if let Some(oldest) = self.segments.pop_front() {
+            self.total_bytes -= oldest.bytes;
+            self.read_offset = 0;
+            self.read_logs = 0;
+            fs::remove_file(segment_path(&self.dir, oldest.seq))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SpoolReadError {
+    #[error("Spool segment was corrupt, losing {0} logs")]
+    Corrupt(u64),
+    #[error("Spool I/O failed")]
+    Io(#[from] io::Error),
+}
+
+fn segment_path(dir: &Path, seq: u64) -> PathBuf {
+    dir.join(format!("{seq:020}.{SEGMENT_EXTENSION}"))
+}
+
+fn checksum(count: u32, payload: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&count.to_le_bytes());
+    hasher.update(payload);
+    hasher.finalize()
+}
+
+fn encode_record(payload: &[u8], count: u32) -> Vec<u8> {
+    let mut record = Vec::with_capacity(HEADER_LEN as usize + payload.len());
+    record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    record.extend_from_slice(&count.to_le_bytes());
+    record.extend_from_slice(&checksum(count, payload).to_le_bytes());
+    record.extend_from_slice(payload);
+    record
+}
+
+/// Reads the next record from `reader`, returning its payload and log count, or `None` if there
+/// isn't a complete record with a valid checksum (i.e. at the end of the segment, or if it's
+/// corrupt).
+fn read_raw_record(reader: &mut impl Read) -> io::Result<Option<(Vec<u8>, u32)>> {
+    let mut header = [0; HEADER_LEN as usize];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_le_bytes(header[0..4].try_into().unwrap());
+    let count = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    let crc = u32::from_le_bytes(header[8..12].try_into().unwrap());
+    if len as u64 > MAX_SEGMENT_BYTES {
+        return Ok(None);
+    }
+
+    let mut payload = vec![0; len as usize];
+    match reader.read_exact(&mut payload) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    if checksum(count, &payload) != crc {
+        return Ok(None);
+    }
+    Ok(Some((payload, count)))
+}
+
+/// Finds the valid records at the start of a segment, returning their total size and log count,
+/// and whether there was anything (other than a torn final write) after them.
+fn scan_segment(path: &Path) -> io::Result<(u64, u64, bool)> {
+    let file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let mut bytes = 0;
+    let mut logs = 0;
+    while let Some((payload, count)) = read_raw_record(&mut reader)? {
+        bytes += HEADER_LEN + payload.len() as u64;
+        logs += count as u64;
+    }
+    // A partial header or payload at the very end is just a write that was interrupted, but a
+    // complete record with a bad checksum (or more data after it) means the file was damaged
+    let remaining = file_len - bytes;
+    let corrupt = remaining > 0 && {
+        let mut reader = reader.into_inner();
+        reader.seek(SeekFrom::Start(bytes))?;
+        let mut header = [0; 4];
+        remaining >= HEADER_LEN
+            && reader.read_exact(&mut header).is_ok()
+            && HEADER_LEN + u32::from_le_bytes(header) as u64 <= remaining
+    };
+    Ok((bytes, logs, corrupt))
+}
+
+fn read_record(path: &Path, offset: u64) -> io::Result<Option<(Vec<Log>, u64)>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let Some((payload, _)) = read_raw_record(&mut BufReader::new(file))? else {
+        return Ok(None);
+    };
+    let logs = serde_json::from_slice(&payload).map_err(io::Error::other)?;
+    Ok(Some((logs, HEADER_LEN + payload.len() as u64)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn batch(ids: std::ops::Range<u32>) -> Vec<Log> {
+        ids.map(|id| {
+            let mut log = Map::new();
+            log.insert("id".to_string(), json!(id));
+            log
+        })
+        .collect()
+    }
+
+    fn ids(logs: &[Log]) -> Vec<u64> {
+        logs.iter().map(|l| l["id"].as_u64().unwrap()).collect()
+    }
+
+    fn open(dir: &Path, max_bytes: u64) -> (DiskSpool, usize) {
+        DiskSpool::open(SpoolOptions {
+            dir: dir.to_path_buf(),
+            max_bytes,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn reads_batches_in_order_across_reopens() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut spool, _) = open(dir.path(), 1024 * 1024);
+        spool.append(&batch(0..2)).unwrap();
+        spool.append(&batch(2..3)).unwrap();
+        drop(spool);
+
+        let (mut spool, corrupt) = open(dir.path(), 1024 * 1024);
+        assert_eq!(corrupt, 0);
+        let first = spool.peek().unwrap().unwrap();
+        assert_eq!(ids(&first.logs), [0, 1]);
+        spool.consume(first).unwrap();
+        spool.append(&batch(3..4)).unwrap();
+        let second = spool.peek().unwrap().unwrap();
+        assert_eq!(ids(&second.logs), [2]);
+        spool.consume(second).unwrap();
+        let third = spool.peek().unwrap().unwrap();
+        assert_eq!(ids(&third.logs), [3]);
+        spool.consume(third).unwrap();
+
+        assert!(spool.is_empty());
+        assert!(spool.peek().unwrap().is_none());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn evicts_oldest_segments_when_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let record_len = encode_record(&serde_json::to_vec(&batch(0..1)).unwrap(), 1).len() as u64;
+        let (mut spool, _) = open(dir.path(), record_len * 2);
+        assert_eq!(spool.append(&batch(0..1)).unwrap(), 0);
+        assert_eq!(spool.append(&batch(1..2)).unwrap(), 0);
+        // Both records are in one segment, so it all gets dropped to make room
+        assert_eq!(spool.append(&batch(2..3)).unwrap(), 2);
+
+        let next = spool.peek().unwrap().unwrap();
+        assert_eq!(ids(&next.logs), [2]);
+        assert_eq!(spool.bytes(), record_len);
+    }
+
+    #[test]
+    fn ignores_torn_writes_and_detects_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut spool, _) = open(dir.path(), 1024 * 1024);
+        spool.append(&batch(0..1)).unwrap();
+        spool.append(&batch(1..2)).unwrap();
+        drop(spool);
+
+        let path = segment_path(dir.path(), 0);
+        let mut contents = fs::read(&path).unwrap();
+        let first_len = encode_record(&serde_json::to_vec(&batch(0..1)).unwrap(), 1).len();
+
+        // A torn write at the end drops just that record, and isn't reported as corruption
+        fs::write(&path, &contents[..contents.len() - 3]).unwrap();
+        let (mut spool, corrupt) = open(dir.path(), 1024 * 1024);
+        assert_eq!(corrupt, 0);
+        assert_eq!(spool.bytes(), first_len as u64);
+        // New records overwrite the torn one
+        spool.append(&batch(2..3)).unwrap();
+        let first = spool.peek().unwrap().unwrap();
+        spool.consume(first).unwrap();
+        assert_eq!(ids(&spool.peek().unwrap().unwrap().logs), [2]);
+        drop(spool);
+
+        // A flipped bit in a complete record is reported as corruption
+        contents = fs::read(&path).unwrap();
+        let last = contents.len() - 1;
+        contents[last] ^= 1;
+        fs::write(&path, &contents).unwrap();
+        let (mut spool, corrupt) = open(dir.path(), 1024 * 1024);
+        assert_eq!(corrupt, 1);
+        assert_eq!(ids(&spool.peek().unwrap().unwrap().logs), [0]);
+    }
+}
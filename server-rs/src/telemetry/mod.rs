@@ -1,48 +1,90 @@
 use std::future::Future;
 
+use color_eyre::eyre;
 use secrecy::ExposeSecret;
 use tokio::task::JoinHandle;
 use tracing::Instrument;
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_error::ErrorLayer;
-use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::filter::{FilterExt, LevelFilter};
 use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::Identity;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::{EnvFilter, Layer};
 
 use crate::configuration::Settings;
 use crate::telemetry::datadog::{DatadogLogLayer, DatadogOptions};
+use crate::telemetry::datadog_spool::SpoolOptions;
 
 mod datadog;
+mod datadog_spool;
+mod otlp;
 
 pub use datadog::flush_datadog_logs;
+#[cfg(test)]
+pub use otlp::export_spans;
+pub use otlp::{EXPORT_ONLY_TARGET, set_parent_from_headers, shutdown_traces};
 
-/// Initialize a subscriber for tracing events. Note that [env_filter] will apply to *all* layers
-/// (e.g. anything it filters out will not be sent to Datadog or stdout).
+/// Initialize a subscriber for tracing events. Note that [env_filter] (or `RUST_LOG`, if set)
+/// applies to *all* layers (e.g. anything it filters out will not be sent to Datadog, stdout, or
+/// the OTLP collector), although the OTLP collector is additionally sent spans for database queries
+/// and Redis commands.
 pub fn init_subscriber<Sink>(
     settings: &Settings,
     name: impl Into<String>,
     env_filter: impl AsRef<str>,
     sink: Sink,
-) where
+) -> eyre::Result<()>
+where
     Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
 {
-    let formatting_layer = BunyanFormattingLayer::new(name.into(), sink);
+    let env_filter = env_filter.as_ref();
+    let base_filter =
+        || EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
+    let log_filter =
+        || base_filter().add_directive(otlp::directive(&format!("{EXPORT_ONLY_TARGET}=off")));
+
+    let tracer = settings
+        .otlp
+        .as_ref()
+        .map(|otlp| otlp::create_tracer("server-rs", otlp))
+        .transpose()?;
+    let global_filter = if tracer.is_some() {
+        otlp::export_directives()
+            .into_iter()
+            .fold(base_filter(), EnvFilter::add_directive)
+    } else {
+        log_filter()
+    };
+
+    let formatting_layer = BunyanFormattingLayer::new(name.into(), sink).with_filter(log_filter());
 
     let registry = tracing_subscriber::registry()
-        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter)))
+        .with(global_filter)
         .with(JsonStorageLayer)
         .with(formatting_layer)
         .with(ErrorLayer::default());
 
-    if let Some(ref key) = settings.datadog_api_key {
-        let options = DatadogOptions::new("server-rs", key.expose_secret());
-        let datadog = DatadogLogLayer::new(options);
-        registry.with(datadog.with_filter(LevelFilter::INFO)).init();
-    } else {
-        registry.init();
+    let datadog = settings.datadog_api_key.as_ref().map(|key| {
+        let mut options = DatadogOptions::new("server-rs", key.expose_secret());
+        if let Some(spool) = &settings.datadog_spool {
+            options = options.with_spool(SpoolOptions {
+                dir: spool.dir.clone(),
+                max_bytes: spool.max_bytes,
+            });
+        }
+        DatadogLogLayer::new(options).with_filter(log_filter().and(LevelFilter::INFO))
+    });
+    // Boxed rather than optional, since `Option` layers don't pass on the dispatcher they're
+    // registered with, which the OTLP layers need
+    let otlp: Box<dyn Layer<_> + Send + Sync> = match tracer {
+        Some(tracer) => Box::new(otlp::layers(tracer, base_filter())),
+        None => Box::new(Identity::new()),
     };
+
+    registry.with(datadog).with(otlp).init();
+    Ok(())
 }
 
 /// Calls [tokio::task::spawn_blocking] in a way that preserves traces from the calling scope.
@@ -0,0 +1,475 @@
+//! Export of tracing spans to an OpenTelemetry collector over OTLP/HTTP, along with W3C Trace
+//! Context (`traceparent`) propagation so traces can continue across services.
+
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+
+use axum::http::{HeaderMap, HeaderName};
+use color_eyre::eyre::{self, WrapErr};
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::{SpanKind, TraceContextExt, Tracer, TracerProvider};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, SdkTracer, SdkTracerProvider, TracerProviderBuilder};
+use tracing::dispatcher::WeakDispatch;
+use tracing::field::{Field, Visit};
+use tracing::{Dispatch, Event, Span, Subscriber};
+use tracing_opentelemetry::{OpenTelemetrySpanExt, get_otel_context};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::Layer;
+use tracing_subscriber::filter::Directive;
+use tracing_subscriber::layer::Context as LayerContext;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::configuration::OtlpSettings;
+
+/// The target of spans that are only worth exporting as traces, not logging, because there are so
+/// many of them (e.g. one per Redis command).
+pub const EXPORT_ONLY_TARGET: &str = "otlp_export_only";
+
+/// The target of the events sqlx logs when a query completes, which [DbQuerySpanLayer] turns into
+/// spans.
+const SQLX_QUERY_TARGET: &str = "sqlx::query";
+
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// Creates the tracer that [layers] export spans through.
+pub fn create_tracer(service_name: &str, settings: &OtlpSettings) -> eyre::Result<SdkTracer> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(&settings.traces_endpoint)
+        .build()
+        .wrap_err("Failed to create OTLP span exporter")?;
+    let provider = provider_builder(service_name, settings.sample_ratio)
+        .with_batch_exporter(exporter)
+        .build();
+    let tracer = provider.tracer(service_name.to_owned());
+
+    PROVIDER
+        .set(provider)
+        .map_err(|_| eyre::eyre!("OTLP tracing was already initialized"))?;
+
+    Ok(tracer)
+}
+
+fn provider_builder(service_name: &str, sample_ratio: f64) -> TracerProviderBuilder {
+    SdkTracerProvider::builder()
+        .with_resource(
+            Resource::builder()
+                .with_service_name(service_name.to_owned())
+                .build(),
+        )
+        // Sampling by trace ID (rather than following the parent's sampled flag) keeps whole
+        // traces together, since their spans share an ID, without letting a caller force its
+        // requests to be traced
+        .with_sampler(Sampler::TraceIdRatioBased(sample_ratio))
+}
+
+/// Creates the layers that export spans through `tracer`: our own spans (filtered like the logs,
+/// by `base_filter`, plus any [EXPORT_ONLY_TARGET] spans), and spans for database queries (see
+/// [DbQuerySpanLayer]). The global filter needs to enable [export_directives] for these to see
+/// everything they export.
+pub fn layers<S>(tracer: SdkTracer, base_filter: EnvFilter) -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    // sqlx logs every query at DEBUG and slow ones at WARN. Only the slow ones are worth adding to
+    // their span as events, the rest just become spans of their own.
+    let spans = tracing_opentelemetry::layer()
+        .with_tracer(tracer.clone())
+        .with_filter(
+            base_filter
+                .add_directive(directive(&format!("{EXPORT_ONLY_TARGET}=info")))
+                .add_directive(directive(&format!("{SQLX_QUERY_TARGET}=warn"))),
+        );
+    // Not filtered, since filtered layers don't get told which dispatcher they're registered with
+    spans.and_then(DbQuerySpanLayer::new(tracer))
+}
+
+/// Returns the filter directives that need to be enabled (on top of the logging filter) for
+/// everything [layers] exports to be recorded.
+pub fn export_directives() -> [Directive; 2] {
+    [
+        directive(&format!("{EXPORT_ONLY_TARGET}=info")),
+        directive(&format!("{SQLX_QUERY_TARGET}=debug")),
+    ]
+}
+
+pub(super) fn directive(directive: &str) -> Directive {
+    directive
+        .parse()
+        .expect("filter directives should be valid")
+}
+
+/// Exports any buffered spans and shuts down the exporter, blocking until that completes. Should be
+/// called just before the process exits, since the exporter's background thread won't get a chance
+/// to.
+pub fn shutdown_traces() {
+    if let Some(provider) = PROVIDER.get()
+        && let Err(e) = provider.shutdown()
+    {
+        tracing::warn!("Failed to shut down trace export: {e:?}");
+    }
+}
+
+/// Makes `span` a child of the trace context described by the `traceparent`/`tracestate` headers
+/// in `headers`, if there are any and they can be trusted (see [trusted_parent_context]). Does
+/// nothing if trace export isn't configured.
+pub fn set_parent_from_headers(span: &Span, headers: &HeaderMap) {
+    if PROVIDER.get().is_none() {
+        return;
+    }
+    if let Some(context) = trusted_parent_context(headers) {
+        // This can only fail if the OpenTelemetry layer isn't registered, which we just checked for
+        let _ = span.set_parent(context);
+    }
+}
+
+/// Returns the trace context described by `headers`, if it came from one of our own services.
+/// Requests from the outside world come through the reverse proxy, which sets `X-Real-IP` (see
+/// `only_unforwarded_clients`), and their trace context is ignored so they can't pick the trace
+/// IDs we sample by or attach spans to someone else's trace.
+fn trusted_parent_context(headers: &HeaderMap) -> Option<Context> {
+    if headers.contains_key(HeaderName::from_static("x-real-ip")) {
+        return None;
+    }
+    let context = TraceContextPropagator::new().extract(&HeaderExtractor(headers));
+    context.span().span_context().is_valid().then_some(context)
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+/// Exports a span for each database query. sqlx doesn't create spans itself, but it does log an
+/// event when each query completes (including how long it took), which this turns into a span
+/// covering the query as a child of the span it ran in. Queries that run outside of any span (e.g.
+/// in background loops) are skipped, since each would be a trace of its own.
+struct DbQuerySpanLayer {
+    tracer: SdkTracer,
+    dispatch: OnceLock<WeakDispatch>,
+}
+
+impl DbQuerySpanLayer {
+    fn new(tracer: SdkTracer) -> Self {
+        Self {
+            tracer,
+            dispatch: OnceLock::new(),
+        }
+    }
+}
+
+impl<S> Layer<S> for DbQuerySpanLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_register_dispatch(&self, subscriber: &Dispatch) {
+        let _ = self.dispatch.set(subscriber.downgrade());
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: LayerContext<'_, S>) {
+        if event.metadata().target() != SQLX_QUERY_TARGET {
+            return;
+        }
+        let Some(dispatch) = self.dispatch.get().and_then(|d| d.upgrade()) else {
+            return;
+        };
+        // The closest span that's being exported, which won't be the event's own span if that was
+        // filtered out
+        let Some(parent_context) = ctx
+            .event_scope(event)
+            .into_iter()
+            .flatten()
+            .find_map(|span| get_otel_context(&span.id(), &dispatch))
+        else {
+            return;
+        };
+        if !parent_context.span().span_context().is_sampled() {
+            return;
+        }
+
+        let mut query = QueryFields::default();
+        event.record(&mut query);
+        let end = SystemTime::now();
+        let start = end
+            .checked_sub(Duration::from_secs_f64(query.elapsed_secs))
+            .unwrap_or(end);
+        // sqlx only includes the full statement if it's longer than the summary (its first few
+        // words, with an ellipsis appended)
+        let text = match query.statement.trim() {
+            "" => query.summary.clone(),
+            statement => statement.to_owned(),
+        };
+        let mut span = self
+            .tracer
+            .span_builder("db.query")
+            .with_kind(SpanKind::Client)
+            .with_start_time(start)
+            .with_attributes([
+                KeyValue::new("db.system.name", "postgresql"),
+                KeyValue::new("db.query.summary", query.summary),
+                KeyValue::new("db.query.text", text),
+                KeyValue::new("db.response.returned_rows", query.rows_returned as i64),
+                KeyValue::new("db.response.affected_rows", query.rows_affected as i64),
+            ])
+            .start_with_context(&self.tracer, &parent_context);
+        opentelemetry::trace::Span::end_with_timestamp(&mut span, end);
+    }
+}
+
+/// The fields of a completed query event from sqlx.
+#[derive(Default)]
+struct QueryFields {
+    summary: String,
+    statement: String,
+    rows_returned: u64,
+    rows_affected: u64,
+    elapsed_secs: f64,
+}
+
+impl Visit for QueryFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "summary" => self.summary = value.to_owned(),
+            "db.statement" => self.statement = value.to_owned(),
+            _ => {}
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "rows_returned" => self.rows_returned = value,
+            "rows_affected" => self.rows_affected = value,
+            _ => {}
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if field.name() == "elapsed_secs" {
+            self.elapsed_secs = value;
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+/// Runs `f` with a subscriber that exports spans like the one created by [super::init_subscriber]
+/// (at the `info` level), and returns the spans it exported.
+#[cfg(test)]
+pub fn export_spans(
+    sample_ratio: f64,
+    f: impl FnOnce(),
+) -> Vec<opentelemetry_sdk::trace::SpanData> {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let exporter = testing::CapturingExporter::default();
+    let provider = provider_builder("test", sample_ratio)
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry()
+        .with(
+            export_directives()
+                .into_iter()
+                .fold(EnvFilter::new("info"), EnvFilter::add_directive),
+        )
+        .with(layers(provider.tracer("test"), EnvFilter::new("info")));
+    tracing::subscriber::with_default(subscriber, f);
+
+    exporter.take()
+}
+
+#[cfg(test)]
+mod testing {
+    use std::sync::{Arc, Mutex};
+
+    use opentelemetry_sdk::error::OTelSdkResult;
+    use opentelemetry_sdk::trace::SpanData;
+
+    #[derive(Debug, Clone, Default)]
+    pub struct CapturingExporter(Arc<Mutex<Vec<SpanData>>>);
+
+    impl CapturingExporter {
+        pub fn take(&self) -> Vec<SpanData> {
+            std::mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    impl opentelemetry_sdk::trace::SpanExporter for CapturingExporter {
+        async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+            self.0.lock().unwrap().extend(batch);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::Value;
+    use opentelemetry_sdk::trace::SpanData;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const PARENT_SPAN_ID: &str = "00f067aa0ba902b7";
+
+    fn traceparent_headers(sampled: bool) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let flags = if sampled { "01" } else { "00" };
+        headers.insert(
+            "traceparent",
+            format!("00-{TRACE_ID}-{PARENT_SPAN_ID}-{flags}")
+                .parse()
+                .unwrap(),
+        );
+        headers
+    }
+
+    fn request_spans(sample_ratio: f64, headers: &HeaderMap) -> Vec<SpanData> {
+        export_spans(sample_ratio, || {
+            let span = tracing::info_span!("request");
+            if let Some(context) = trusted_parent_context(headers) {
+                span.set_parent(context).unwrap();
+            }
+            let _entered = span.enter();
+        })
+    }
+
+    fn attribute<'a>(span: &'a SpanData, key: &str) -> Option<&'a Value> {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| &kv.value)
+    }
+
+    #[test]
+    fn extracts_traceparent_from_internal_requests() {
+        let context = trusted_parent_context(&traceparent_headers(true)).unwrap();
+        let span = context.span();
+        let span_context = span.span_context();
+        assert!(span_context.is_remote());
+        assert!(span_context.is_sampled());
+        assert_eq!(span_context.trace_id().to_string(), TRACE_ID);
+        assert_eq!(span_context.span_id().to_string(), PARENT_SPAN_ID);
+    }
+
+    #[test]
+    fn ignores_missing_traceparent() {
+        assert!(trusted_parent_context(&HeaderMap::new()).is_none());
+    }
+
+    #[test]
+    fn ignores_traceparent_from_forwarded_requests() {
+        let mut headers = traceparent_headers(true);
+        headers.insert("x-real-ip", "203.0.113.7".parse().unwrap());
+        assert!(trusted_parent_context(&headers).is_none());
+
+        let spans = request_spans(1.0, &headers);
+        assert_eq!(spans.len(), 1);
+        assert_ne!(spans[0].span_context.trace_id().to_string(), TRACE_ID);
+    }
+
+    #[test]
+    fn continues_traces_from_internal_requests() {
+        let spans = request_spans(1.0, &traceparent_headers(true));
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].name, "request");
+        assert_eq!(spans[0].span_context.trace_id().to_string(), TRACE_ID);
+        assert_eq!(spans[0].parent_span_id.to_string(), PARENT_SPAN_ID);
+    }
+
+    #[test]
+    fn sampling_ignores_the_callers_sampled_flag() {
+        assert!(request_spans(0.0, &traceparent_headers(true)).is_empty());
+        assert_eq!(request_spans(1.0, &traceparent_headers(false)).len(), 1);
+    }
+
+    #[test]
+    fn sqlx_queries_become_child_spans() {
+        let spans = export_spans(1.0, || {
+            let _entered = tracing::info_span!("request").entered();
+            tracing::debug!(
+                target: "sqlx::query",
+                summary = "select id from users …",
+                db.statement = "\n\nSELECT id FROM users WHERE name = $1\n",
+                rows_affected = 0u64,
+                rows_returned = 1u64,
+                elapsed_secs = 0.25,
+                "slow statement"
+            );
+        });
+
+        assert_eq!(spans.len(), 2);
+        let (query, request) = (&spans[0], &spans[1]);
+        assert_eq!(query.name, "db.query");
+        assert_eq!(query.span_kind, SpanKind::Client);
+        assert_eq!(query.parent_span_id, request.span_context.span_id());
+        assert_eq!(
+            query.span_context.trace_id(),
+            request.span_context.trace_id()
+        );
+        assert_eq!(
+            query.end_time.duration_since(query.start_time).unwrap(),
+            Duration::from_millis(250)
+        );
+        assert_eq!(
+            attribute(query, "db.query.text"),
+            Some(&Value::from("SELECT id FROM users WHERE name = $1"))
+        );
+        assert_eq!(
+            attribute(query, "db.query.summary"),
+            Some(&Value::from("select id from users …"))
+        );
+        assert_eq!(
+            attribute(query, "db.response.returned_rows"),
+            Some(&Value::I64(1))
+        );
+        // The query is exported as a span rather than an event on the request span
+        assert!(request.events.is_empty());
+    }
+
+    #[test]
+    fn short_sqlx_queries_use_their_summary_as_text() {
+        let spans = export_spans(1.0, || {
+            let _entered = tracing::info_span!("request").entered();
+            tracing::debug!(
+                target: "sqlx::query",
+                summary = "BEGIN",
+                db.statement = "",
+                rows_affected = 0u64,
+                rows_returned = 0u64,
+                elapsed_secs = 0.001,
+            );
+        });
+
+        assert_eq!(spans[0].name, "db.query");
+        assert_eq!(
+            attribute(&spans[0], "db.query.text"),
+            Some(&Value::from("BEGIN"))
+        );
+    }
+
+    #[test]
+    fn sqlx_queries_outside_of_spans_are_not_exported() {
+        let spans = export_spans(1.0, || {
+            tracing::debug!(
+                target: "sqlx::query",
+                summary = "BEGIN",
+                db.statement = "",
+                rows_affected = 0u64,
+                rows_returned = 0u64,
+                elapsed_secs = 0.001,
+            );
+        });
+
+        assert!(spans.is_empty());
+    }
+}
@@ -0,0 +1,34 @@
+//! Helpers for tests that run against a database (`#[sqlx::test]`). These need `DATABASE_URL` to
+//! point at a Postgres server whose `template1` has the extensions our migrations depend on (see
+//! `deployment/appserver/db/init-scripts`).
+
+use std::path::Path;
+use std::sync::Arc;
+
+use sqlx::PgPool;
+
+use crate::file_store::{FileStore, LocalFileStore};
+use crate::users::SbUserId;
+
+/// Creates a user with the given name (used for both the login and display name).
+pub async fn create_user(db: &PgPool, name: &str) -> SbUserId {
+    let id: i32 = sqlx::query_scalar(
+        r#"
+            INSERT INTO users (name, login_name, email, created, email_verified)
+            VALUES ($1, $1, $1 || '@example.org', NOW(), true)
+            RETURNING id
+        "#,
+    )
+    .bind(name)
+    .fetch_one(db)
+    .await
+    .unwrap();
+    SbUserId(id)
+}
+
+/// Creates a file store that writes to `dir` (e.g. a `tempfile::tempdir()`).
+pub fn local_file_store(dir: &Path) -> FileStore {
+    FileStore::Local(Arc::new(
+        LocalFileStore::new(dir.to_str().unwrap(), "https://example.org".into()).unwrap(),
+    ))
+}
@@ -10,7 +10,9 @@
 //! When an account is linked we create Twitch EventSub `stream.online`/`stream.offline`
 //! subscriptions (webhook transport) so Twitch pushes live/offline changes to `/twitch/eventsub`.
 //! The persistent link (and the ids of its subscriptions) lives in the `twitch_connections` table;
-//! the ephemeral "who is live right now" state lives in Redis (`twitch:live`).
+//! the ephemeral "who is live right now" state lives in Redis (`twitch:live`). Despite its name,
+//! that hash also holds streams from the other providers (see the `streaming` module), and the
+//! feed, feed blocks and `SbUser.liveStream` here are shared by all of them.
 
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
@@ -43,12 +45,18 @@ use url::Url;
 use uuid::Uuid;
 
 use crate::configuration::Settings;
+use crate::games::{CurrentGameLoader, Game, GamesLoader};
 use crate::graphql::errors::graphql_error;
 use crate::graphql::schema_builder::SchemaBuilderModule;
+use crate::live_stream_feed::PublishedLiveStreamMessage;
 use crate::redis::RedisPool;
 use crate::state::AppState;
+use crate::streaming::{
+    StreamingChannel, StreamingPlatform, StreamingProvider, refresh_provider_live_streams,
+};
 use crate::users::permissions::RequiredPermission;
 use crate::users::{CurrentUser, SbUser, SbUserId, UsersLoader};
+use crate::youtube;
 
 const TWITCH_OAUTH_AUTHORIZE_URL: &str = "https://id.twitch.tv/oauth2/authorize";
 const TWITCH_OAUTH_TOKEN_URL: &str = "https://id.twitch.tv/oauth2/token";
@@ -529,7 +537,7 @@ impl TwitchClient {
     }
 
     /// Best-effort deletion of a set of subscription ids (used on unlink and reconciliation).
-    async fn delete_subscriptions(&self, ids: &[String]) {
+    pub(crate) async fn delete_subscriptions(&self, ids: &[String]) {
         for id in ids {
             if let Err(e) = self.delete_subscription(id).await {
                 warn!("Failed to delete Twitch EventSub subscription {id}: {e:?}");
@@ -538,6 +546,41 @@ impl TwitchClient {
     }
 }
 
+impl StreamingPlatform for TwitchClient {
+    const PROVIDER: StreamingProvider = StreamingProvider::Twitch;
+
+    fn authorize_url(&self, state: &str, redirect_uri: &str) -> eyre::Result<String> {
+        TwitchClient::authorize_url(self, state, redirect_uri)
+    }
+
+    async fn fetch_linked_channel(
+        &self,
+        code: &str,
+        redirect_uri: &str,
+    ) -> eyre::Result<StreamingChannel> {
+        let access_token = self.exchange_code(code, redirect_uri).await?;
+        let user = self.get_authenticated_user(&access_token).await?;
+        Ok(user.into())
+    }
+
+    async fn fetch_channels(&self, ids: &[String]) -> eyre::Result<Vec<StreamingChannel>> {
+        let users = self.get_users_by_id(ids).await?;
+        Ok(users.into_iter().map(StreamingChannel::from).collect())
+    }
+
+    async fn fetch_live_streams(
+        &self,
+        channels: &[StreamingChannel],
+    ) -> eyre::Result<Vec<LiveStreamSummary>> {
+        let ids = channels.iter().map(|c| c.id.clone()).collect::<Vec<_>>();
+        let streams = self.get_streams(&ids).await?;
+        Ok(streams
+            .into_iter()
+            .map(LiveStreamSummary::from_stream)
+            .collect())
+    }
+}
+
 // ---------------------------------------------------------------------------------------------
 // Twitch API response types
 // ---------------------------------------------------------------------------------------------
@@ -560,6 +603,16 @@ struct HelixUser {
     display_name: String,
 }
 
+impl From<HelixUser> for StreamingChannel {
+    fn from(user: HelixUser) -> Self {
+        Self {
+            id: user.id,
+            login: user.login,
+            display_name: user.display_name,
+        }
+    }
+}
+
 #[derive(Deserialize)]
 struct EventSubListResponse {
     data: Vec<EventSubSubscription>,
@@ -634,13 +687,24 @@ pub struct TwitchConnection {
     pub eventsub_subscription_ids: Vec<String>,
 }
 
-/// The ephemeral "currently live" summary stored in Redis for a linked streamer.
+/// The ephemeral "currently live" summary stored in Redis for a linked streamer, from any
+/// streaming provider.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LiveStreamSummary {
-    pub twitch_user_id: String,
-    pub twitch_login: String,
-    pub twitch_display_name: String,
+    #[serde(default)]
+    pub provider: StreamingProvider,
+    /// The provider's stable id for the channel.
+    #[serde(alias = "twitchUserId")]
+    pub channel_id: String,
+    #[serde(alias = "twitchLogin")]
+    pub channel_login: String,
+    #[serde(alias = "twitchDisplayName")]
+    pub channel_display_name: String,
+    /// The provider's id for this particular broadcast, for providers whose watch URL is per-stream
+    /// rather than per-channel (YouTube).
+    #[serde(default)]
+    pub stream_id: Option<String>,
     pub title: String,
     pub game_id: String,
     pub game_name: String,
@@ -652,9 +716,11 @@ pub struct LiveStreamSummary {
 impl LiveStreamSummary {
     fn from_stream(stream: StreamInfo) -> Self {
         Self {
-            twitch_user_id: stream.user_id,
-            twitch_login: stream.user_login,
-            twitch_display_name: stream.user_name,
+            provider: StreamingProvider::Twitch,
+            channel_id: stream.user_id,
+            channel_login: stream.user_login,
+            channel_display_name: stream.user_name,
+            stream_id: None,
             title: stream.title,
             game_id: stream.game_id,
             game_name: stream.game_name,
@@ -664,24 +730,42 @@ impl LiveStreamSummary {
         }
     }
 
-    fn is_starcraft(&self) -> bool {
-        STARCRAFT_CATEGORY_IDS.contains(&self.game_id.as_str())
+    pub(crate) fn is_starcraft(&self) -> bool {
+        match self.provider {
+            StreamingProvider::Twitch => STARCRAFT_CATEGORY_IDS.contains(&self.game_id.as_str()),
+            StreamingProvider::Youtube => youtube::is_starcraft_title(&self.title),
+        }
+    }
+
+    /// The URL viewers can watch the stream at.
+    fn watch_url(&self) -> String {
+        match (self.provider, &self.stream_id) {
+            (StreamingProvider::Youtube, Some(video_id)) => youtube::watch_url(video_id),
+            (StreamingProvider::Youtube, None) => youtube::channel_url(&self.channel_id),
+            (StreamingProvider::Twitch, _) => {
+                format!("https://www.twitch.tv/{}", self.channel_login)
+            }
+        }
     }
 }
 
 /// A ShieldBattery user who is currently live-streaming, for the home-page feed.
-#[derive(Clone, SimpleObject)]
+#[derive(Clone, Debug, PartialEq, SimpleObject)]
 #[graphql(complex)]
 pub struct LiveStream {
     #[graphql(skip)]
     pub user_id: SbUserId,
-    /// The Twitch login name (used in `twitch.tv/<login>` URLs).
-    pub twitch_login: String,
-    /// The Twitch display name.
-    pub twitch_display_name: String,
+    /// The streaming service the stream is on.
+    pub provider: StreamingProvider,
+    /// The channel's login name on the streaming service (a Twitch login or YouTube handle).
+    pub channel_login: String,
+    /// The channel's display name on the streaming service.
+    pub channel_display_name: String,
+    /// The URL the stream can be watched at.
+    pub url: String,
     /// The stream's title.
     pub title: String,
-    /// The Twitch category/game being streamed.
+    /// The category/game being streamed (YouTube doesn't report one, so this is empty there).
     pub game_name: String,
     /// The stream's current viewer count.
     pub viewer_count: i32,
@@ -693,20 +777,51 @@ pub struct LiveStream {
 
 #[ComplexObject]
 impl LiveStream {
+    /// The Twitch login name (used in `twitch.tv/<login>` URLs).
+    #[graphql(deprecation = "Use `channelLogin` and `provider` instead.")]
+    async fn twitch_login(&self) -> &str {
+        &self.channel_login
+    }
+
+    /// The Twitch display name.
+    #[graphql(deprecation = "Use `channelDisplayName` instead.")]
+    async fn twitch_display_name(&self) -> &str {
+        &self.channel_display_name
+    }
+
     /// The ShieldBattery user who is streaming.
     async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<SbUser>> {
         ctx.data::<DataLoader<UsersLoader>>()?
             .load_one(self.user_id)
             .await
     }
+
+    /// The in-progress ShieldBattery game the streamer is playing, if any. This is based on game
+    /// participation alone, so it may be set even if the stream is showing something else.
+    async fn current_game(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Game>> {
+        let Some(game_id) = ctx
+            .data::<DataLoader<CurrentGameLoader>>()?
+            .load_one(self.user_id)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let game = ctx
+            .data::<DataLoader<GamesLoader>>()?
+            .load_one(game_id)
+            .await?;
+        Ok(game.map(Game::from))
+    }
 }
 
 impl LiveStream {
-    fn from_summary(user_id: SbUserId, summary: LiveStreamSummary) -> Self {
+    pub(crate) fn from_summary(user_id: SbUserId, summary: LiveStreamSummary) -> Self {
         Self {
             user_id,
-            twitch_login: summary.twitch_login,
-            twitch_display_name: summary.twitch_display_name,
+            provider: summary.provider,
+            url: summary.watch_url(),
+            channel_login: summary.channel_login,
+            channel_display_name: summary.channel_display_name,
             title: summary.title,
             game_name: summary.game_name,
             viewer_count: summary.viewer_count.clamp(0, i64::from(i32::MAX)) as i32,
@@ -831,7 +946,9 @@ async fn load_live_stream_values_from_connection(
 }
 
 /// Loads every currently-live streamer from Redis (unfiltered).
-async fn load_live_streams(redis: &RedisPool) -> eyre::Result<Vec<(SbUserId, LiveStreamSummary)>> {
+pub(crate) async fn load_live_streams(
+    redis: &RedisPool,
+) -> eyre::Result<Vec<(SbUserId, LiveStreamSummary)>> {
     let mut conn = redis.get().await.wrap_err("Could not connect to Redis")?;
     let entries: HashMap<i32, String> = conn
         .hgetall(LIVE_STREAMS_KEY)
@@ -848,7 +965,7 @@ async fn load_live_streams(redis: &RedisPool) -> eyre::Result<Vec<(SbUserId, Liv
 
 /// Loads only the live-stream summaries for `user_ids`, preserving the input/result alignment long
 /// enough to associate each Redis value with its user before invalid or missing values are omitted.
-async fn load_live_streams_for_users(
+pub(crate) async fn load_live_streams_for_users(
     redis: &RedisPool,
     user_ids: &[SbUserId],
 ) -> eyre::Result<Vec<(SbUserId, LiveStreamSummary)>> {
@@ -924,6 +1041,39 @@ impl Loader<SbUserId> for TwitchChannelLoader {
     }
 }
 
+/// Batches checks for whether users are blocked from the live-streams feed, so that filtering the
+/// streams of a list of games (or of the whole feed) is a single indexed lookup of just those users.
+/// A user is present in the results only if they're blocked.
+pub struct FeedBlockLoader {
+    db: PgPool,
+}
+
+impl FeedBlockLoader {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+impl Loader<SbUserId> for FeedBlockLoader {
+    type Value = ();
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[SbUserId]) -> Result<HashMap<SbUserId, Self::Value>, Self::Error> {
+        Ok(sqlx::query_scalar!(
+            r#"
+                SELECT user_id as "user_id: SbUserId"
+                FROM twitch_feed_blocks
+                WHERE user_id = ANY($1)
+            "#,
+            keys as _,
+        )
+        .fetch(&self.db)
+        .map_ok(|user_id| (user_id, ()))
+        .try_collect()
+        .await?)
+    }
+}
+
 /// Batches per-user live-stream lookups (a single Redis `HMGET`) so that selecting `liveStream` on a
 /// list of users doesn't fan out into one Redis call each. Category-agnostic (any live stream).
 pub struct LiveStreamLoader {
@@ -977,10 +1127,14 @@ impl SchemaBuilderModule for TwitchModule {
                 LiveStreamLoader::new(self.redis_pool.clone()),
                 tokio::spawn,
             ))
+            .data(DataLoader::new(
+                FeedBlockLoader::new(self.db_pool.clone()),
+                tokio::spawn,
+            ))
     }
 }
 
-async fn load_connection(
+pub(crate) async fn load_connection(
     pool: &PgPool,
     user_id: SbUserId,
 ) -> eyre::Result<Option<TwitchConnection>> {
@@ -1033,10 +1187,12 @@ async fn load_all_connections(pool: &PgPool) -> eyre::Result<Vec<TwitchConnectio
 }
 
 /// Loads only the connection identity needed by the periodic live-state refresh.
-async fn load_live_refresh_connections(pool: &PgPool) -> eyre::Result<HashMap<SbUserId, String>> {
+async fn load_live_refresh_connections(
+    pool: &PgPool,
+) -> eyre::Result<HashMap<SbUserId, StreamingChannel>> {
     let rows = sqlx::query!(
         r#"
-            SELECT user_id as "user_id: SbUserId", twitch_user_id
+            SELECT user_id as "user_id: SbUserId", twitch_user_id, twitch_login, twitch_display_name
             FROM twitch_connections
         "#,
     )
@@ -1046,7 +1202,14 @@ async fn load_live_refresh_connections(pool: &PgPool) -> eyre::Result<HashMap<Sb
 
     Ok(rows
         .into_iter()
-        .map(|row| (row.user_id, row.twitch_user_id))
+        .map(|row| {
+            let channel = StreamingChannel {
+                id: row.twitch_user_id,
+                login: row.twitch_login,
+                display_name: row.twitch_display_name,
+            };
+            (row.user_id, channel)
+        })
         .collect())
 }
 
@@ -1126,7 +1289,10 @@ async fn update_subscription_ids(
 
 /// Deletes a user's connection, returning the EventSub subscription ids it had (so the caller can
 /// delete those subscriptions on Twitch), or `None` if there was no connection.
-async fn delete_connection(pool: &PgPool, user_id: SbUserId) -> eyre::Result<Option<Vec<String>>> {
+pub(crate) async fn delete_connection(
+    executor: impl sqlx::PgExecutor<'_>,
+    user_id: SbUserId,
+) -> eyre::Result<Option<Vec<String>>> {
     let row = sqlx::query!(
         r#"
             DELETE FROM twitch_connections
@@ -1135,16 +1301,39 @@ async fn delete_connection(pool: &PgPool, user_id: SbUserId) -> eyre::Result<Opt
         "#,
         user_id as _,
     )
-    .fetch_optional(pool)
+    .fetch_optional(executor)
     .await
     .wrap_err("Failed to delete Twitch connection")?;
     Ok(row.map(|r| r.eventsub_subscription_ids))
 }
 
-/// The set of users an admin has blocked from the live-streams feed. Read on each `live_streams`
-/// poll so a block takes effect on the very next feed refresh; the table holds one row per blocked
-/// user, so this stays small and the read is negligible next to the Redis scan on the same path.
-async fn load_feed_blocked_user_ids(pool: &PgPool) -> eyre::Result<HashSet<SbUserId>> {
+/// Removes a user's Twitch connection along with everything that hangs off of it: their EventSub
+/// subscriptions (best-effort; reconciliation cleans up any deletes that fail) and their live
+/// stream entry. Returns whether there was a connection to remove.
+pub async fn remove_connection(
+    pool: &PgPool,
+    redis: &RedisPool,
+    client: Option<&TwitchClient>,
+    user_id: SbUserId,
+) -> eyre::Result<bool> {
+    let Some(sub_ids) = delete_connection(pool, user_id).await? else {
+        return Ok(false);
+    };
+
+    if let Some(client) = client {
+        client.delete_subscriptions(&sub_ids).await;
+    }
+    if let Err(e) = set_stream_offline(redis, user_id, StreamingProvider::Twitch).await {
+        error!("Failed to clear live stream on unlink: {e:?}");
+    }
+
+    Ok(true)
+}
+
+/// The set of users an admin has blocked from the live-streams feed. Only read when the feed
+/// subscription loop (re)syncs or the blocks change; GraphQL resolvers check just the users they
+/// need through [`FeedBlockLoader`].
+pub(crate) async fn load_feed_blocked_user_ids(pool: &PgPool) -> eyre::Result<HashSet<SbUserId>> {
     let rows = sqlx::query!(r#"SELECT user_id as "user_id: SbUserId" FROM twitch_feed_blocks"#,)
         .fetch_all(pool)
         .await
@@ -1222,24 +1411,59 @@ async fn load_feed_blocks(pool: &PgPool) -> eyre::Result<Vec<BlockedStream>> {
     .wrap_err("Failed to load Twitch feed blocks")
 }
 
+/// The provider of the stream currently stored for `user_id`, if there is a (well-formed) one.
+async fn stored_stream_provider(
+    conn: &mut crate::redis::Connection,
+    user_id: SbUserId,
+) -> eyre::Result<Option<StreamingProvider>> {
+    let json: Option<String> = conn
+        .hget(LIVE_STREAMS_KEY, i32::from(user_id))
+        .await
+        .wrap_err("Failed to load live stream")?;
+    Ok(json
+        .and_then(|json| serde_json::from_str::<LiveStreamSummary>(&json).ok())
+        .map(|summary| summary.provider))
+}
+
+/// Stores `summary` as the user's live stream, unless they're already live on a different provider
+/// (the first stream keeps the slot until it ends). The check and the write aren't atomic, but the
+/// periodic refreshes for each provider repair the rare interleaving that gets this wrong.
 async fn set_stream_live(
     redis: &RedisPool,
     user_id: SbUserId,
     summary: &LiveStreamSummary,
 ) -> eyre::Result<()> {
     let mut conn = redis.get().await.wrap_err("Could not connect to Redis")?;
+    if stored_stream_provider(&mut conn, user_id)
+        .await?
+        .is_some_and(|provider| provider != summary.provider)
+    {
+        return Ok(());
+    }
     let json = serde_json::to_string(summary).wrap_err("Failed to serialize live stream")?;
     conn.hset::<_, _, _, ()>(LIVE_STREAMS_KEY, i32::from(user_id), json)
         .await
         .wrap_err("Failed to store live stream")?;
+    publish_live_streams_changed(redis, vec![user_id]).await;
     Ok(())
 }
 
-async fn set_stream_offline(redis: &RedisPool, user_id: SbUserId) -> eyre::Result<()> {
+/// Clears the user's live stream if it's from `provider`, leaving a stream from any other provider
+/// in place.
+pub(crate) async fn set_stream_offline(
+    redis: &RedisPool,
+    user_id: SbUserId,
+    provider: StreamingProvider,
+) -> eyre::Result<()> {
     let mut conn = redis.get().await.wrap_err("Could not connect to Redis")?;
+    match stored_stream_provider(&mut conn, user_id).await? {
+        Some(stored) if stored == provider => {}
+        _ => return Ok(()),
+    }
     conn.hdel::<_, _, ()>(LIVE_STREAMS_KEY, i32::from(user_id))
         .await
         .wrap_err("Failed to clear live stream")?;
+    publish_live_streams_changed(redis, vec![user_id]).await;
     Ok(())
 }
 
@@ -1271,7 +1495,7 @@ fn live_stream_updates_pipeline(
 }
 
 /// Applies one refresh phase with at most one HSET and one HDEL in a single Redis round trip.
-async fn apply_live_stream_updates(
+pub(crate) async fn apply_live_stream_updates(
     redis: &RedisPool,
     live: &[(SbUserId, LiveStreamSummary)],
     offline: &[SbUserId],
@@ -1285,7 +1509,28 @@ async fn apply_live_stream_updates(
     pipeline
         .exec_async(&mut conn)
         .await
-        .wrap_err("Failed to update live streams")
+        .wrap_err("Failed to update live streams")?;
+
+    let changed = live
+        .iter()
+        .map(|(user_id, _)| *user_id)
+        .chain(offline.iter().copied())
+        .collect();
+    publish_live_streams_changed(redis, changed).await;
+    Ok(())
+}
+
+/// Tells every server's [`LiveStreamFeed`](crate::live_stream_feed::LiveStreamFeed) which streams
+/// to re-check. Redis is the source of truth for live state, so a failed publish only delays
+/// subscribers until the next change is published for that user, and isn't treated as a failure of
+/// the write itself.
+async fn publish_live_streams_changed(redis: &RedisPool, user_ids: Vec<SbUserId>) {
+    if let Err(e) = redis
+        .publish(PublishedLiveStreamMessage::StreamsChanged { user_ids })
+        .await
+    {
+        error!("Failed to publish live stream changes: {e:?}");
+    }
 }
 
 // ---------------------------------------------------------------------------------------------
@@ -1335,7 +1580,12 @@ impl TwitchQuery {
     /// their live state elsewhere -- profile, avatar ring, friend notifications -- is unaffected).
     async fn live_streams(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<LiveStream>> {
         let entries = load_live_streams(ctx.data::<RedisPool>()?).await?;
-        let blocked = load_feed_blocked_user_ids(ctx.data::<PgPool>()?).await?;
+        let blocked = ctx
+            .data::<DataLoader<FeedBlockLoader>>()?
+            .load_many(entries.iter().map(|(user_id, _)| *user_id))
+            .await?
+            .into_keys()
+            .collect();
         Ok(feed_streams(entries, &blocked))
     }
 
@@ -1535,20 +1785,13 @@ impl TwitchMutation {
     /// Removes the current user's Twitch connection. Returns whether a connection was removed.
     async fn twitch_unlink(&self, ctx: &Context<'_>) -> async_graphql::Result<bool> {
         let user = require_current_user(ctx)?;
-        let pool = ctx.data::<PgPool>()?;
-
-        let Some(sub_ids) = delete_connection(pool, user.id).await? else {
-            return Ok(false);
-        };
-
-        if let Some(client) = ctx.data::<Option<Arc<TwitchClient>>>()?.as_ref() {
-            client.delete_subscriptions(&sub_ids).await;
-        }
-        if let Err(e) = set_stream_offline(ctx.data::<RedisPool>()?, user.id).await {
-            error!("Failed to clear live stream on unlink: {e:?}");
-        }
-
-        Ok(true)
+        Ok(remove_connection(
+            ctx.data::<PgPool>()?,
+            ctx.data::<RedisPool>()?,
+            ctx.data::<Option<Arc<TwitchClient>>>()?.as_deref(),
+            user.id,
+        )
+        .await?)
     }
 
     /// Blocks a user's stream from appearing in the live-streams feed (shown on the home page and
@@ -1563,6 +1806,11 @@ impl TwitchMutation {
     ) -> async_graphql::Result<bool> {
         let admin = require_current_user(ctx)?;
         insert_feed_block(ctx.data::<PgPool>()?, user_id, admin.id).await?;
+        ctx.data::<RedisPool>()?
+            .publish(PublishedLiveStreamMessage::FeedBlocksChanged {
+                user_ids: vec![user_id],
+            })
+            .await?;
         Ok(true)
     }
 
@@ -1574,7 +1822,15 @@ impl TwitchMutation {
         ctx: &Context<'_>,
         user_id: SbUserId,
     ) -> async_graphql::Result<bool> {
-        Ok(delete_feed_block(ctx.data::<PgPool>()?, user_id).await?)
+        let removed = delete_feed_block(ctx.data::<PgPool>()?, user_id).await?;
+        if removed {
+            ctx.data::<RedisPool>()?
+                .publish(PublishedLiveStreamMessage::FeedBlocksChanged {
+                    user_ids: vec![user_id],
+                })
+                .await?;
+        }
+        Ok(removed)
     }
 }
 
@@ -1701,7 +1957,7 @@ async fn handle_notification(
             handle_stream_online(client, redis, connection.user_id, &broadcaster_id).await?
         }
         SUB_TYPE_STREAM_OFFLINE => {
-            set_stream_offline(redis, connection.user_id).await?;
+            set_stream_offline(redis, connection.user_id, StreamingProvider::Twitch).await?;
         }
         other => warn!("Unexpected Twitch EventSub notification type: {other}"),
     }
@@ -1756,7 +2012,7 @@ async fn handle_stream_online(
     }
     // Every attempt returned `None`: we rode out the lag and it's genuinely not live (e.g. a stream
     // that ended almost immediately).
-    set_stream_offline(redis, sb_user_id).await
+    set_stream_offline(redis, sb_user_id, StreamingProvider::Twitch).await
 }
 
 /// Reconciles a single user's Redis live state with their actual current Twitch status: stores a
@@ -1772,7 +2028,7 @@ async fn refresh_stream_state(
         Some(stream) => {
             set_stream_live(redis, sb_user_id, &LiveStreamSummary::from_stream(stream)).await
         }
-        None => set_stream_offline(redis, sb_user_id).await,
+        None => set_stream_offline(redis, sb_user_id, StreamingProvider::Twitch).await,
     }
 }
 
@@ -1802,62 +2058,13 @@ async fn refresh_all_live_streams(
     db: &PgPool,
     redis: &RedisPool,
 ) -> eyre::Result<()> {
-    // user_id -> currently linked Twitch user id, for everyone with a connection right now.
-    let connections = load_live_refresh_connections(db).await?;
-
-    let live = load_live_streams(redis).await?;
-
-    // An entry can be orphaned if an unlink raced an in-flight stream.online handler (or the
-    // offline write on unlink failed): no subscription remains to clear it, and since Twitch still
-    // reports the broadcaster live, leaving it in would keep it pinned live for the rest of the
-    // stream. Drop those here instead, checking against who's actually linked right now. Everyone
-    // else we remember as already tracked, so we only issue an offline write below for entries that
-    // actually need clearing.
-    let mut tracked_live: HashSet<SbUserId> = HashSet::new();
-    let mut orphaned = Vec::new();
-    for (user_id, summary) in live {
-        if connections.get(&user_id) == Some(&summary.twitch_user_id) {
-            tracked_live.insert(user_id);
-        } else {
-            orphaned.push(user_id);
-        }
-    }
-    apply_live_stream_updates(redis, &[], &orphaned).await?;
-
-    if connections.is_empty() {
-        return Ok(());
-    }
-
     // Check every linked broadcaster, not just those already tracked as live: this is what recovers
     // a stream whose `stream.online` handling was lost entirely (a crash after the webhook was
     // acked, or a persistent failure that outlasted the retry loop) instead of leaving it invisible
-    // until the broadcaster's next transition.
-    let broadcaster_ids: Vec<String> = connections.values().cloned().collect();
-    let mut live_now: HashMap<String, StreamInfo> = client
-        .get_streams(&broadcaster_ids)
-        .await?
-        .into_iter()
-        .map(|stream| (stream.user_id.clone(), stream))
-        .collect();
-
-    let mut live_updates = Vec::with_capacity(live_now.len());
-    let mut offline_updates = Vec::new();
-    for (user_id, twitch_user_id) in &connections {
-        match live_now.remove(twitch_user_id) {
-            Some(stream) => {
-                live_updates.push((*user_id, LiveStreamSummary::from_stream(stream)));
-            }
-            // Only clear entries we're actually tracking so we don't issue an HDEL per offline
-            // connection every pass.
-            None if tracked_live.contains(user_id) => {
-                offline_updates.push(*user_id);
-            }
-            None => {}
-        }
-    }
-    apply_live_stream_updates(redis, &live_updates, &offline_updates).await?;
-
-    Ok(())
+    // until the broadcaster's next transition. Orphaned entries (an unlink racing an in-flight
+    // stream.online handler, or a failed offline write on unlink) are dropped along the way.
+    let connections = load_live_refresh_connections(db).await?;
+    refresh_provider_live_streams(client, &connections, db, redis).await
 }
 
 async fn eventsub_callback(
@@ -2120,23 +2327,27 @@ async fn refresh_connection_identities(
         .iter()
         .map(|c| c.twitch_user_id.clone())
         .collect();
-    let by_id: HashMap<String, HelixUser> = client
-        .get_users_by_id(&ids)
+    let by_id: HashMap<String, StreamingChannel> = client
+        .fetch_channels(&ids)
         .await?
         .into_iter()
-        .map(|u| (u.id.clone(), u))
+        .map(|c| (c.id.clone(), c))
         .collect();
 
     for conn in connections {
-        let Some(user) = by_id.get(&conn.twitch_user_id) else {
+        let Some(channel) = by_id.get(&conn.twitch_user_id) else {
             continue;
         };
-        if user.login == conn.twitch_login && user.display_name == conn.twitch_display_name {
+        if channel.login == conn.twitch_login && channel.display_name == conn.twitch_display_name {
             continue;
         }
-        if let Err(e) =
-            update_connection_identity(db, &conn.twitch_user_id, &user.login, &user.display_name)
-                .await
+        if let Err(e) = update_connection_identity(
+            db,
+            &conn.twitch_user_id,
+            &channel.login,
+            &channel.display_name,
+        )
+        .await
         {
             error!(
                 "Failed to refresh Twitch identity for user {}: {e:?}",
@@ -2190,9 +2401,11 @@ mod tests {
 
     fn live_stream_summary() -> LiveStreamSummary {
         LiveStreamSummary {
-            twitch_user_id: "123".to_owned(),
-            twitch_login: "streamer".to_owned(),
-            twitch_display_name: "Streamer".to_owned(),
+            provider: StreamingProvider::Twitch,
+            channel_id: "123".to_owned(),
+            channel_login: "streamer".to_owned(),
+            channel_display_name: "Streamer".to_owned(),
+            stream_id: None,
             title: "Ladder".to_owned(),
             game_id: STARCRAFT_CATEGORY_IDS[0].to_owned(),
             game_name: "StarCraft".to_owned(),
@@ -2291,6 +2504,45 @@ mod tests {
         assert_eq!(streams[1].viewer_count, 10);
     }
 
+    #[test]
+    fn summaries_stored_before_providers_existed_are_twitch_streams() {
+        let json = r#"{
+            "twitchUserId": "123",
+            "twitchLogin": "streamer",
+            "twitchDisplayName": "Streamer",
+            "title": "Ladder",
+            "gameId": "11989",
+            "gameName": "StarCraft",
+            "viewerCount": 42,
+            "startedAt": "2026-07-26T12:00:00Z",
+            "thumbnailUrl": "https://example.com/{width}x{height}.jpg"
+        }"#;
+
+        let summary: LiveStreamSummary = serde_json::from_str(json).unwrap();
+
+        assert_eq!(summary.provider, StreamingProvider::Twitch);
+        assert_eq!(summary.channel_id, "123");
+        assert_eq!(summary.channel_login, "streamer");
+        assert_eq!(summary.stream_id, None);
+        assert!(summary.is_starcraft());
+    }
+
+    #[test]
+    fn live_stream_urls_depend_on_the_provider() {
+        let twitch = LiveStream::from_summary(SbUserId(1), live_stream_summary());
+        assert_eq!(twitch.url, "https://www.twitch.tv/streamer");
+        assert_eq!(twitch.thumbnail_url, "https://example.com/320x180.jpg");
+
+        let mut summary = live_stream_summary();
+        summary.provider = StreamingProvider::Youtube;
+        summary.channel_id = "UCabc".to_owned();
+        summary.stream_id = Some("dQw4w9WgXcQ".to_owned());
+        summary.game_id = String::new();
+        let youtube = LiveStream::from_summary(SbUserId(1), summary);
+        assert_eq!(youtube.url, "https://www.youtube.com/watch?v=dQw4w9WgXcQ");
+        assert_eq!(youtube.provider, StreamingProvider::Youtube);
+    }
+
     #[test]
     fn malformed_cleanup_compares_the_value_before_deleting() {
         let entries = vec![
@@ -2316,8 +2568,8 @@ mod tests {
     fn live_stream_refresh_updates_are_batched_by_operation() {
         let first = live_stream_summary();
         let mut second = live_stream_summary();
-        second.twitch_user_id = "456".to_owned();
-        second.twitch_login = "other-streamer".to_owned();
+        second.channel_id = "456".to_owned();
+        second.channel_login = "other-streamer".to_owned();
         let live = vec![(SbUserId(7), first), (SbUserId(8), second)];
         let offline = vec![SbUserId(9), SbUserId(10)];
 
@@ -2410,4 +2662,22 @@ mod tests {
             "sha256=nothex"
         ));
     }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn feed_block_loader_only_returns_blocked_users(db: PgPool) {
+        let blocked = crate::test_utils::create_user(&db, "blocked").await;
+        let streaming = crate::test_utils::create_user(&db, "streaming").await;
+        sqlx::query("INSERT INTO twitch_feed_blocks (user_id) VALUES ($1)")
+            .bind(blocked.0)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let loaded = FeedBlockLoader::new(db)
+            .load(&[blocked, streaming])
+            .await
+            .unwrap();
+
+        assert_eq!(loaded.into_keys().collect::<Vec<_>>(), vec![blocked]);
+    }
 }
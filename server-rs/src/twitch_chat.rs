@@ -0,0 +1,1299 @@
+//! Twitch chat bot: announces linked streamers' matchmaking games in their own Twitch chat.
+//!
+//! The bot is a single Twitch account (configured with `SB_TWITCH_CHAT_BOT_*`) that connects to
+//! Twitch chat over IRC-over-WebSocket, joins a streamer's channel the first time it has something
+//! to say there, and stays connected for the life of the process. Streamers opt in (and can
+//! customize the messages) with `twitchUpdateChatBotPreferences`, which requires a linked Twitch
+//! account.
+//!
+//! Two things get announced:
+//! - Game starts, driven by `MatchFound` messages on the Redis `matchmaking` stream. The match's
+//!   game is only created once every player has accepted, so we wait for it to show up before
+//!   announcing (which also means declined matches are never announced).
+//! - Results, from `matchmaking_rating_changes`. Those are only written once a game's results have
+//!   been reconciled, so streamers' chats never see a result from a disputed player report.
+//!
+//! Every server instance polls the rating changes, and a `MatchFound` message can be redelivered if
+//! it wasn't acknowledged in time, so each announcement is claimed in Redis before it's sent.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_graphql::futures_util::{SinkExt, StreamExt};
+use async_graphql::{Context, InputObject, Object, SimpleObject};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use color_eyre::eyre::{self, Context as _, eyre};
+use deadpool_redis::redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use sqlx::PgPool;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{Connector, MaybeTlsStream, WebSocketStream};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::configuration::{Settings, TwitchChatBotSettings, TwitchSettings};
+use crate::graphql::errors::graphql_error;
+use crate::matchmaking::{MatchFoundMessage, MatchmakingType, PublishedMatchmakingMessage};
+use crate::pubsub::PublishedMessage;
+use crate::redis::RedisPool;
+use crate::twitch;
+use crate::users::{CurrentUser, SbUserId};
+
+const MATCHMAKING_CHANNEL: &str = "matchmaking";
+/// The Redis stream consumer group the match start announcer reads matchmaking messages as.
+const MATCHMAKING_CONSUMER_GROUP: &str = "twitchChat";
+/// How long to wait before reconnecting to Twitch chat or resubscribing to matchmaking messages.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// How long Twitch has to accept our login before we give up on a connection.
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(10);
+/// The minimum time between messages we send. Twitch allows non-moderators 20 messages per 30
+/// seconds across all channels, and going over gets the bot locked out for a while.
+const MESSAGE_INTERVAL: Duration = Duration::from_millis(1600);
+/// How many messages can be waiting to be sent before new ones are dropped.
+const MESSAGE_QUEUE_SIZE: usize = 256;
+/// Twitch's limit on the length of a chat message, in characters.
+const MAX_MESSAGE_LENGTH: usize = 500;
+/// The maximum length of a streamer's template, in characters. Leaves room for the placeholders to
+/// expand without running into `MAX_MESSAGE_LENGTH` in the common case.
+const MAX_TEMPLATE_LENGTH: usize = 300;
+
+/// How often to check for the game of a match that was just found.
+const GAME_LOOKUP_INTERVAL: Duration = Duration::from_secs(5);
+/// How long after a match is found to keep looking for its game. Matches that haven't started by
+/// then were declined or failed to load.
+const GAME_LOOKUP_TIMEOUT: Duration = Duration::from_secs(3 * 60);
+/// How much earlier than the match being found (as seen by us) a game may have started and still
+/// be considered the match's game. Covers clock differences between us and the app server that
+/// creates games.
+const GAME_START_GRACE: chrono::TimeDelta = chrono::TimeDelta::seconds(10);
+/// How often to check for newly reconciled results.
+const RESULT_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// How far back each result poll looks beyond the newest result already seen. Results are dated
+/// when they're computed rather than when they're committed, so one can show up after a later one.
+const RESULT_POLL_OVERLAP: chrono::TimeDelta = chrono::TimeDelta::minutes(2);
+/// How long an announcement stays claimed. Only needs to outlast the result poll overlap and any
+/// redelivery of a match, so a day is plenty.
+const ANNOUNCEMENT_CLAIM_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+const DEFAULT_START_TEMPLATE: &str =
+    "{streamer} is playing {mode} vs {opponent} ({opponent_mmr} MMR) on {map}. GLHF!";
+const DEFAULT_RESULT_TEMPLATE: &str =
+    "{streamer} {result} vs {opponent} on {map}. MMR: {mmr} ({mmr_change})";
+
+const START_PLACEHOLDERS: &[&str] = &["streamer", "opponent", "opponent_mmr", "map", "mode"];
+const RESULT_PLACEHOLDERS: &[&str] = &[
+    "streamer",
+    "opponent",
+    "map",
+    "mode",
+    "result",
+    "mmr",
+    "mmr_change",
+];
+
+fn announcement_claim_key(kind: AnnouncementKind, game_id: Uuid, user_id: SbUserId) -> String {
+    format!(
+        "twitch_chat:announced:{}:{game_id}:{user_id}",
+        kind.as_str()
+    )
+}
+
+// ---------------------------------------------------------------------------------------------
+// Templates
+// ---------------------------------------------------------------------------------------------
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum AnnouncementKind {
+    Start,
+    Result,
+}
+
+impl AnnouncementKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Start => "start",
+            Self::Result => "result",
+        }
+    }
+
+    fn default_template(self) -> &'static str {
+        match self {
+            Self::Start => DEFAULT_START_TEMPLATE,
+            Self::Result => DEFAULT_RESULT_TEMPLATE,
+        }
+    }
+
+    fn placeholders(self) -> &'static [&'static str] {
+        match self {
+            Self::Start => START_PLACEHOLDERS,
+            Self::Result => RESULT_PLACEHOLDERS,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+enum TemplateError {
+    #[error("templates can be at most {MAX_TEMPLATE_LENGTH} characters")]
+    TooLong,
+    #[error("templates can't contain line breaks or other control characters")]
+    ControlCharacter,
+    #[error("'{{' must be closed by a matching '}}'")]
+    Unclosed,
+    #[error("{{{0}}} isn't available here (available: {1})")]
+    UnknownPlaceholder(String, String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Segment<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+/// Splits a template into literal text and `{placeholder}`s, checking that it only uses the
+/// placeholders available for `kind`.
+fn parse_template(
+    kind: AnnouncementKind,
+    template: &str,
+) -> Result<Vec<Segment<'_>>, TemplateError> {
+    if template.chars().count() > MAX_TEMPLATE_LENGTH {
+        return Err(TemplateError::TooLong);
+    }
+    if template.chars().any(char::is_control) {
+        return Err(TemplateError::ControlCharacter);
+    }
+
+    let mut segments = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            segments.push(Segment::Text(&rest[..start]));
+        }
+        let end = rest[start..].find('}').ok_or(TemplateError::Unclosed)? + start;
+        let name = &rest[start + 1..end];
+        if !kind.placeholders().contains(&name) {
+            return Err(TemplateError::UnknownPlaceholder(
+                name.to_string(),
+                kind.placeholders()
+                    .iter()
+                    .map(|p| format!("{{{p}}}"))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ));
+        }
+        segments.push(Segment::Placeholder(name));
+        rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest));
+    }
+    Ok(segments)
+}
+
+/// Renders a streamer's template (or the default, if they haven't set one) with `values`, cutting
+/// it down to Twitch's message length limit if needed.
+fn render_template(
+    kind: AnnouncementKind,
+    template: Option<&str>,
+    values: &HashMap<&str, String>,
+) -> String {
+    // Templates are validated when they're saved, but the placeholders available could have
+    // changed since, so an invalid one falls back to the default rather than being sent as-is
+    let segments = template
+        .and_then(|t| parse_template(kind, t).ok())
+        .unwrap_or_else(|| {
+            parse_template(kind, kind.default_template()).expect("default templates are valid")
+        });
+
+    let mut message = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => message.push_str(text),
+            // Values come from user-controlled names (map titles, usernames), and a line break in
+            // one would let it end our PRIVMSG and send IRC commands of its own
+            Segment::Placeholder(name) => message.extend(
+                values
+                    .get(name)
+                    .into_iter()
+                    .flat_map(|v| v.chars())
+                    .filter(|c| !c.is_control()),
+            ),
+        }
+    }
+    match message.char_indices().nth(MAX_MESSAGE_LENGTH) {
+        Some((index, _)) => message[..index].to_string(),
+        None => message,
+    }
+}
+
+fn format_rating(rating: f32) -> String {
+    format!("{}", rating.round() as i32)
+}
+
+fn format_rating_change(change: f32) -> String {
+    format!("{:+}", change.round() as i32)
+}
+
+// ---------------------------------------------------------------------------------------------
+// Chat connection
+// ---------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+struct ChatEndpoints {
+    token_url: String,
+    irc_url: String,
+}
+
+impl Default for ChatEndpoints {
+    fn default() -> Self {
+        Self {
+            token_url: "https://id.twitch.tv/oauth2/token".into(),
+            irc_url: "wss://irc-ws.chat.twitch.tv:443".into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ChatMessage {
+    /// The login of the channel to send to.
+    channel: String,
+    text: String,
+}
+
+/// A handle for sending messages as the chat bot. Messages are queued and sent in order by the
+/// [`ChatConnection`] created alongside it, so sending never blocks on Twitch.
+pub struct TwitchChatBot {
+    sender: mpsc::Sender<ChatMessage>,
+}
+
+impl TwitchChatBot {
+    /// Builds the bot from settings, returning `None` if it (or the Twitch integration it relies
+    /// on) isn't configured. The returned connection must be spawned for messages to be sent.
+    pub fn from_settings(settings: &Settings) -> Option<(Arc<Self>, ChatConnection)> {
+        let twitch = settings.twitch.as_ref()?;
+        let bot = settings.twitch_chat_bot.as_ref()?;
+        let (bot, connection) = Self::new(twitch, bot, ChatEndpoints::default());
+        Some((Arc::new(bot), connection))
+    }
+
+    fn new(
+        twitch: &TwitchSettings,
+        bot: &TwitchChatBotSettings,
+        endpoints: ChatEndpoints,
+    ) -> (Self, ChatConnection) {
+        let (sender, receiver) = mpsc::channel(MESSAGE_QUEUE_SIZE);
+        let connection = ChatConnection {
+            http: reqwest::Client::new(),
+            client_id: twitch.client_id.clone(),
+            client_secret: twitch.client_secret.clone(),
+            login: bot.login.to_ascii_lowercase(),
+            refresh_token: bot.refresh_token.clone(),
+            tls: tls_connector(),
+            endpoints,
+            receiver,
+        };
+        (Self { sender }, connection)
+    }
+
+    /// Queues `text` to be sent to the chat of the channel with login `channel`. If the queue is
+    /// full (e.g. we've been disconnected from Twitch for a while) the message is dropped.
+    fn say(&self, channel: &str, text: String) {
+        let message = ChatMessage {
+            channel: channel.to_ascii_lowercase(),
+            text,
+        };
+        if let Err(e) = self.sender.try_send(message) {
+            warn!("Dropping Twitch chat message for #{channel}: {e}");
+        }
+    }
+}
+
+/// Builds the TLS configuration for connecting to Twitch chat. More than one rustls crypto backend
+/// is enabled in our dependency tree, so rustls can't pick a default one and it has to be named.
+fn tls_connector() -> Connector {
+    let roots = rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::aws_lc_rs::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .expect("aws-lc-rs supports the default protocol versions")
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    Connector::Rustls(Arc::new(config))
+}
+
+type IrcSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// A parsed IRC line. Tags and the source prefix are skipped since nothing we handle needs them.
+#[derive(Debug, PartialEq, Eq)]
+struct IrcMessage<'a> {
+    command: &'a str,
+    params: Vec<&'a str>,
+}
+
+fn parse_irc_line(line: &str) -> Option<IrcMessage<'_>> {
+    let mut rest = line.trim_end_matches(['\r', '\n']);
+    if let Some(tagged) = rest.strip_prefix('@') {
+        rest = tagged.split_once(' ')?.1;
+    }
+    if let Some(prefixed) = rest.strip_prefix(':') {
+        rest = prefixed.split_once(' ')?.1;
+    }
+    let (middle, trailing) = match rest.split_once(" :") {
+        Some((middle, trailing)) => (middle, Some(trailing)),
+        None => (rest, None),
+    };
+    let mut parts = middle.split(' ').filter(|p| !p.is_empty());
+    let command = parts.next()?;
+    let mut params = parts.collect::<Vec<_>>();
+    params.extend(trailing);
+    Some(IrcMessage { command, params })
+}
+
+async fn send_line(socket: &mut IrcSocket, line: String) -> eyre::Result<()> {
+    socket
+        .send(Message::Text(line.into()))
+        .await
+        .wrap_err("Failed to send to Twitch chat")
+}
+
+/// Reads the IRC lines in a frame from Twitch. Returns `None` once the connection is closed.
+fn frame_lines(
+    frame: Option<Result<Message, tokio_tungstenite::tungstenite::Error>>,
+) -> eyre::Result<Option<Vec<String>>> {
+    match frame {
+        Some(Ok(Message::Text(text))) => Ok(Some(text.lines().map(String::from).collect())),
+        Some(Ok(Message::Close(_))) | None => Ok(None),
+        Some(Ok(_)) => Ok(Some(Vec::new())),
+        Some(Err(e)) => Err(e).wrap_err("Twitch chat connection failed"),
+    }
+}
+
+enum SessionEnd {
+    /// Twitch asked us to reconnect (e.g. for server maintenance).
+    Reconnect,
+    /// Every [`TwitchChatBot`] handle is gone, so there's nothing left to send.
+    Shutdown,
+}
+
+#[derive(Deserialize)]
+struct RefreshTokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+}
+
+/// The bot's connection to Twitch chat, which sends the messages queued by its [`TwitchChatBot`].
+pub struct ChatConnection {
+    http: reqwest::Client,
+    client_id: String,
+    client_secret: SecretString,
+    login: String,
+    /// Twitch can hand back a new refresh token when one is used, so the latest one is kept here
+    /// and used for the next connection.
+    refresh_token: SecretString,
+    tls: Connector,
+    endpoints: ChatEndpoints,
+    receiver: mpsc::Receiver<ChatMessage>,
+}
+
+impl ChatConnection {
+    /// Keeps the bot connected to Twitch chat, sending queued messages as they come in. Messages
+    /// queued while disconnected are sent once we reconnect.
+    pub async fn run(mut self) {
+        loop {
+            match self.run_session().await {
+                Ok(SessionEnd::Shutdown) => return,
+                Ok(SessionEnd::Reconnect) => {}
+                Err(e) => error!("Twitch chat connection failed: {e:?}"),
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    /// Gets a fresh user access token for the bot account. User tokens expire after a few hours,
+    /// but Twitch doesn't disconnect chat sessions when that happens, so a new one is only needed
+    /// per connection.
+    async fn refresh_access_token(&mut self) -> eyre::Result<SecretString> {
+        let resp = self
+            .http
+            .post(&self.endpoints.token_url)
+            .form(&[
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.expose_secret()),
+                ("grant_type", "refresh_token"),
+                ("refresh_token", self.refresh_token.expose_secret()),
+            ])
+            .send()
+            .await
+            .wrap_err("Failed to send Twitch chat bot token request")?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(eyre!(
+                "Twitch chat bot token refresh failed ({status}): {body}"
+            ));
+        }
+        let token: RefreshTokenResponse = resp
+            .json()
+            .await
+            .wrap_err("Failed to parse Twitch chat bot token response")?;
+        if let Some(refresh_token) = token.refresh_token {
+            self.refresh_token = refresh_token.into();
+        }
+        Ok(token.access_token.into())
+    }
+
+    /// Connects and logs in to Twitch chat.
+    async fn connect(&mut self) -> eyre::Result<IrcSocket> {
+        let token = self.refresh_access_token().await?;
+        let (mut socket, _) = tokio_tungstenite::connect_async_tls_with_config(
+            self.endpoints.irc_url.as_str(),
+            None,
+            false,
+            Some(self.tls.clone()),
+        )
+        .await
+        .wrap_err("Failed to connect to Twitch chat")?;
+
+        send_line(&mut socket, format!("PASS oauth:{}", token.expose_secret())).await?;
+        send_line(&mut socket, format!("NICK {}", self.login)).await?;
+        tokio::time::timeout(LOGIN_TIMEOUT, wait_for_welcome(&mut socket))
+            .await
+            .map_err(|_| eyre!("Timed out logging in to Twitch chat"))??;
+        Ok(socket)
+    }
+
+    async fn run_session(&mut self) -> eyre::Result<SessionEnd> {
+        let mut socket = self.connect().await?;
+        let mut joined = HashSet::new();
+        let mut next_send = Instant::now();
+        loop {
+            tokio::select! {
+                frame = socket.next() => {
+                    let Some(lines) = frame_lines(frame)? else {
+                        return Err(eyre!("Twitch chat connection closed"));
+                    };
+                    for line in lines {
+                        match parse_irc_line(&line) {
+                            Some(m) if m.command == "PING" => {
+                                let server = m.params.first().copied().unwrap_or_default();
+                                send_line(&mut socket, format!("PONG :{server}")).await?;
+                            }
+                            Some(m) if m.command == "RECONNECT" => {
+                                return Ok(SessionEnd::Reconnect);
+                            }
+                            Some(m) if m.command == "NOTICE" => {
+                                // e.g. the bot being banned or timed out in a channel
+                                warn!("Twitch chat notice: {line}");
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                message = self.receiver.recv() => {
+                    let Some(message) = message else {
+                        return Ok(SessionEnd::Shutdown);
+                    };
+                    if joined.insert(message.channel.clone()) {
+                        send_line(&mut socket, format!("JOIN #{}", message.channel)).await?;
+                    }
+                    tokio::time::sleep_until(next_send).await;
+                    send_line(
+                        &mut socket,
+                        format!("PRIVMSG #{} :{}", message.channel, message.text),
+                    )
+                    .await?;
+                    next_send = Instant::now() + MESSAGE_INTERVAL;
+                }
+            }
+        }
+    }
+}
+
+/// Waits for Twitch to accept our login. Twitch rejects a bad token with a `NOTICE` and then
+/// closes the connection.
+async fn wait_for_welcome(socket: &mut IrcSocket) -> eyre::Result<()> {
+    loop {
+        let frame = socket.next().await;
+        let Some(lines) = frame_lines(frame)? else {
+            return Err(eyre!("Twitch chat closed the connection while logging in"));
+        };
+        // The welcome usually shares a frame with the rest of the login burst, which still needs
+        // handling (e.g. a PING), so the whole frame is processed before returning
+        let mut welcomed = false;
+        for line in lines {
+            match parse_irc_line(&line) {
+                Some(m) if m.command == "001" => welcomed = true,
+                Some(m) if m.command == "NOTICE" => {
+                    return Err(eyre!(
+                        "Twitch chat login failed: {}",
+                        m.params.last().copied().unwrap_or_default()
+                    ));
+                }
+                Some(m) if m.command == "PING" => {
+                    let server = m.params.first().copied().unwrap_or_default();
+                    send_line(socket, format!("PONG :{server}")).await?;
+                }
+                _ => {}
+            }
+        }
+        if welcomed {
+            return Ok(());
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+// Announcements
+// ---------------------------------------------------------------------------------------------
+
+/// Atomically claims an announcement, returning whether this instance should send it (i.e. no other
+/// instance, or earlier poll, has already).
+async fn claim_announcement(
+    redis: &RedisPool,
+    kind: AnnouncementKind,
+    game_id: Uuid,
+    user_id: SbUserId,
+) -> eyre::Result<bool> {
+    let mut conn = redis.get().await.wrap_err("Could not connect to Redis")?;
+    let opts = SetOptions::default()
+        .conditional_set(ExistenceCheck::NX)
+        .with_expiration(SetExpiry::EX(ANNOUNCEMENT_CLAIM_TTL_SECONDS));
+    let set: Option<String> = conn
+        .set_options(announcement_claim_key(kind, game_id, user_id), 1, opts)
+        .await
+        .wrap_err("Failed to claim Twitch chat announcement")?;
+    Ok(set.is_some())
+}
+
+/// An opted-in streamer whose game start may need announcing.
+struct AnnouncingStreamer {
+    user_id: SbUserId,
+    twitch_login: String,
+    start_template: Option<String>,
+}
+
+async fn load_announcing_streamers(
+    db: &PgPool,
+    user_ids: &[SbUserId],
+) -> eyre::Result<Vec<AnnouncingStreamer>> {
+    sqlx::query_as!(
+        AnnouncingStreamer,
+        r#"
+            SELECT s.user_id as "user_id: SbUserId", tc.twitch_login, s.start_template
+            FROM twitch_chat_bot_settings s
+            JOIN twitch_connections tc ON tc.user_id = s.user_id
+            WHERE s.enabled AND s.user_id = ANY($1)
+        "#,
+        user_ids as _,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load Twitch chat bot streamers")
+}
+
+async fn load_user_names(
+    db: &PgPool,
+    user_ids: &[SbUserId],
+) -> eyre::Result<HashMap<SbUserId, String>> {
+    let rows = sqlx::query!(
+        r#"SELECT id as "id: SbUserId", name::TEXT as "name!" FROM users WHERE id = ANY($1)"#,
+        user_ids as _,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load player names")?;
+    Ok(rows.into_iter().map(|r| (r.id, r.name)).collect())
+}
+
+struct MatchGame {
+    id: Uuid,
+    map_name: String,
+}
+
+/// Looks up the matchmaking game `user_id` started after `since`, if it's been created yet.
+async fn load_match_game(
+    db: &PgPool,
+    user_id: SbUserId,
+    since: DateTime<Utc>,
+) -> eyre::Result<Option<MatchGame>> {
+    sqlx::query_as!(
+        MatchGame,
+        r#"
+            SELECT g.id, COALESCE(um.name, '') as "map_name!"
+            FROM games_users gu
+            JOIN games g ON g.id = gu.game_id
+            LEFT JOIN uploaded_maps um ON um.id = g.map_id
+            WHERE gu.user_id = $1
+                AND g.start_time >= $2
+                AND g.config->>'gameSource' = 'MATCHMAKING'
+            ORDER BY g.start_time DESC
+            LIMIT 1
+        "#,
+        user_id as _,
+        since,
+    )
+    .fetch_optional(db)
+    .await
+    .wrap_err("Failed to load match game")
+}
+
+/// Waits for the game of a match found at `found_at` to be created, returning `None` if it never
+/// is (e.g. a player declined).
+async fn wait_for_match_game(
+    db: &PgPool,
+    user_id: SbUserId,
+    found_at: DateTime<Utc>,
+) -> eyre::Result<Option<MatchGame>> {
+    let deadline = Instant::now() + GAME_LOOKUP_TIMEOUT;
+    while Instant::now() < deadline {
+        tokio::time::sleep(GAME_LOOKUP_INTERVAL).await;
+        if let Some(game) = load_match_game(db, user_id, found_at - GAME_START_GRACE).await? {
+            return Ok(Some(game));
+        }
+    }
+    Ok(None)
+}
+
+fn join_names(ids: &[SbUserId], names: &HashMap<SbUserId, String>) -> String {
+    ids.iter()
+        .filter_map(|id| names.get(id).map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Announces the start of a just-found match to any opted-in streamers playing in it.
+async fn announce_match_start(
+    bot: &TwitchChatBot,
+    db: &PgPool,
+    redis: &RedisPool,
+    found: MatchFoundMessage,
+    found_at: DateTime<Utc>,
+) -> eyre::Result<()> {
+    let team_a = found.team_a.iter().map(|p| p.id).collect::<Vec<_>>();
+    let team_b = found.team_b.iter().map(|p| p.id).collect::<Vec<_>>();
+    let players = team_a.iter().chain(&team_b).copied().collect::<Vec<_>>();
+    let streamers = load_announcing_streamers(db, &players).await?;
+    let Some(first) = streamers.first() else {
+        return Ok(());
+    };
+
+    // Every player is in the same game, so it only needs to be looked up for one of them
+    let Some(game) = wait_for_match_game(db, first.user_id, found_at).await? else {
+        return Ok(());
+    };
+    let names = load_user_names(db, &players).await?;
+
+    for streamer in streamers {
+        let (opponents, opponent_rating) = if team_a.contains(&streamer.user_id) {
+            (&team_b, found.team_b_rating)
+        } else {
+            (&team_a, found.team_a_rating)
+        };
+        let values = HashMap::from([
+            (
+                "streamer",
+                names.get(&streamer.user_id).cloned().unwrap_or_default(),
+            ),
+            ("opponent", join_names(opponents, &names)),
+            ("opponent_mmr", format_rating(opponent_rating)),
+            ("map", game.map_name.clone()),
+            ("mode", found.mode.as_str().to_string()),
+        ]);
+
+        if claim_announcement(redis, AnnouncementKind::Start, game.id, streamer.user_id).await? {
+            bot.say(
+                &streamer.twitch_login,
+                render_template(
+                    AnnouncementKind::Start,
+                    streamer.start_template.as_deref(),
+                    &values,
+                ),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Announces game starts for opted-in streamers as their matches are found, reconnecting if the
+/// Redis stream connection is lost.
+pub async fn match_start_announcer_loop(bot: Arc<TwitchChatBot>, db: PgPool, redis: RedisPool) {
+    loop {
+        if let Err(e) = run_start_announcer(&bot, &db, &redis).await {
+            error!("Twitch chat match consumer failed: {e:?}");
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn run_start_announcer(
+    bot: &Arc<TwitchChatBot>,
+    db: &PgPool,
+    redis: &RedisPool,
+) -> eyre::Result<()> {
+    let mut consumer = redis
+        .stream_consumer(MATCHMAKING_CHANNEL, MATCHMAKING_CONSUMER_GROUP)
+        .await?;
+
+    loop {
+        let entry = consumer.next().await?;
+        if let PublishedMessage::Matchmaking(PublishedMatchmakingMessage::MatchFound(found)) =
+            entry.message
+        {
+            // Waiting for the game can take a while, and shouldn't hold up other matches (or the
+            // acknowledgement, which would get the match redelivered while we're still waiting)
+            let (bot, db, redis) = (bot.clone(), db.clone(), redis.clone());
+            let found_at = Utc::now();
+            tokio::spawn(async move {
+                if let Err(e) = announce_match_start(&bot, &db, &redis, found, found_at).await {
+                    error!("Failed to announce match start in Twitch chat: {e:?}");
+                }
+            });
+        }
+        consumer.ack(&entry.id).await?;
+    }
+}
+
+/// A reconciled result for an opted-in streamer.
+struct StreamerResult {
+    user_id: SbUserId,
+    game_id: Uuid,
+    matchmaking_type: MatchmakingType,
+    outcome: String,
+    rating: f32,
+    rating_change: f32,
+    change_date: NaiveDateTime,
+    name: String,
+    twitch_login: String,
+    result_template: Option<String>,
+    map_name: String,
+    opponents: String,
+}
+
+async fn load_results_since(
+    db: &PgPool,
+    since: NaiveDateTime,
+) -> eyre::Result<Vec<StreamerResult>> {
+    sqlx::query_as!(
+        StreamerResult,
+        r#"
+            SELECT r.user_id as "user_id: SbUserId", r.game_id,
+                r.matchmaking_type as "matchmaking_type: MatchmakingType",
+                r.outcome::TEXT as "outcome!", r.rating, r.rating_change, r.change_date,
+                u.name::TEXT as "name!", tc.twitch_login, s.result_template,
+                COALESCE(um.name, '') as "map_name!",
+                COALESCE((
+                    SELECT string_agg(ou.name::TEXT, ', ' ORDER BY ou.name)
+                    FROM games_users ogu
+                    JOIN users ou ON ou.id = ogu.user_id
+                    WHERE ogu.game_id = r.game_id
+                        AND ogu.user_id <> r.user_id
+                        AND (gu.team IS NULL OR ogu.team IS DISTINCT FROM gu.team)
+                ), '') as "opponents!"
+            FROM matchmaking_rating_changes r
+            JOIN twitch_chat_bot_settings s ON s.user_id = r.user_id AND s.enabled
+            JOIN twitch_connections tc ON tc.user_id = r.user_id
+            JOIN users u ON u.id = r.user_id
+            JOIN games g ON g.id = r.game_id
+            LEFT JOIN games_users gu ON gu.game_id = r.game_id AND gu.user_id = r.user_id
+            LEFT JOIN uploaded_maps um ON um.id = g.map_id
+            WHERE r.change_date > $1
+            ORDER BY r.change_date
+        "#,
+        since,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load reconciled results")
+}
+
+async fn announce_result(
+    bot: &TwitchChatBot,
+    redis: &RedisPool,
+    result: StreamerResult,
+) -> eyre::Result<()> {
+    if !claim_announcement(
+        redis,
+        AnnouncementKind::Result,
+        result.game_id,
+        result.user_id,
+    )
+    .await?
+    {
+        return Ok(());
+    }
+    let outcome = if result.outcome == "win" {
+        "won"
+    } else {
+        "lost"
+    };
+    let values = HashMap::from([
+        ("streamer", result.name),
+        ("opponent", result.opponents),
+        ("map", result.map_name),
+        ("mode", result.matchmaking_type.as_str().to_string()),
+        ("result", outcome.to_string()),
+        ("mmr", format_rating(result.rating)),
+        ("mmr_change", format_rating_change(result.rating_change)),
+    ]);
+    bot.say(
+        &result.twitch_login,
+        render_template(
+            AnnouncementKind::Result,
+            result.result_template.as_deref(),
+            &values,
+        ),
+    );
+    Ok(())
+}
+
+/// Announces opted-in streamers' results as they're reconciled. Only results reconciled after the
+/// process started (less the poll overlap) are announced.
+pub async fn match_result_announcer_loop(bot: Arc<TwitchChatBot>, db: PgPool, redis: RedisPool) {
+    let mut interval = tokio::time::interval(RESULT_POLL_INTERVAL);
+    let mut newest = Utc::now().naive_utc();
+    loop {
+        interval.tick().await;
+        let results = match load_results_since(&db, newest - RESULT_POLL_OVERLAP).await {
+            Ok(results) => results,
+            Err(e) => {
+                error!("Failed to poll for Twitch chat results: {e:?}");
+                continue;
+            }
+        };
+        for result in results {
+            newest = newest.max(result.change_date);
+            if let Err(e) = announce_result(&bot, &redis, result).await {
+                error!("Failed to announce result in Twitch chat: {e:?}");
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+// Persistence
+// ---------------------------------------------------------------------------------------------
+
+/// A streamer's settings for the chat bot.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct TwitchChatBotPreferences {
+    /// Whether the bot announces this user's matchmaking games in their Twitch chat.
+    pub enabled: bool,
+    /// The template for game start announcements, or `null` to use `defaultStartTemplate`.
+    pub start_template: Option<String>,
+    /// The template for result announcements, or `null` to use `defaultResultTemplate`.
+    pub result_template: Option<String>,
+    pub default_start_template: &'static str,
+    pub default_result_template: &'static str,
+}
+
+impl TwitchChatBotPreferences {
+    fn new(enabled: bool, start_template: Option<String>, result_template: Option<String>) -> Self {
+        Self {
+            enabled,
+            start_template,
+            result_template,
+            default_start_template: DEFAULT_START_TEMPLATE,
+            default_result_template: DEFAULT_RESULT_TEMPLATE,
+        }
+    }
+}
+
+async fn load_preferences(
+    db: &PgPool,
+    user_id: SbUserId,
+) -> eyre::Result<TwitchChatBotPreferences> {
+    let row = sqlx::query!(
+        r#"
+            SELECT enabled, start_template, result_template
+            FROM twitch_chat_bot_settings
+            WHERE user_id = $1
+        "#,
+        user_id as _,
+    )
+    .fetch_optional(db)
+    .await
+    .wrap_err("Failed to load Twitch chat bot settings")?;
+    Ok(match row {
+        Some(row) => {
+            TwitchChatBotPreferences::new(row.enabled, row.start_template, row.result_template)
+        }
+        None => TwitchChatBotPreferences::new(false, None, None),
+    })
+}
+
+async fn upsert_preferences(
+    db: &PgPool,
+    user_id: SbUserId,
+    preferences: &TwitchChatBotPreferences,
+) -> eyre::Result<()> {
+    sqlx::query!(
+        r#"
+            INSERT INTO twitch_chat_bot_settings (user_id, enabled, start_template, result_template)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id) DO UPDATE SET
+                enabled = EXCLUDED.enabled,
+                start_template = EXCLUDED.start_template,
+                result_template = EXCLUDED.result_template,
+                updated_at = now()
+        "#,
+        user_id as _,
+        preferences.enabled,
+        preferences.start_template,
+        preferences.result_template,
+    )
+    .execute(db)
+    .await
+    .wrap_err("Failed to save Twitch chat bot settings")?;
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------------------------
+// GraphQL
+// ---------------------------------------------------------------------------------------------
+
+/// New chat bot settings for the current user. Templates are plain text with `{placeholder}`s:
+/// game start templates can use `{streamer}`, `{opponent}`, `{opponent_mmr}`, `{map}` and `{mode}`,
+/// and result templates can use `{streamer}`, `{opponent}`, `{map}`, `{mode}`, `{result}` ("won"
+/// or "lost"), `{mmr}` and `{mmr_change}`.
+#[derive(InputObject)]
+pub struct TwitchChatBotPreferencesInput {
+    pub enabled: bool,
+    /// The template for game start announcements. `null` or blank uses the default.
+    pub start_template: Option<String>,
+    /// The template for result announcements. `null` or blank uses the default.
+    pub result_template: Option<String>,
+}
+
+fn require_current_user<'a>(ctx: &'a Context<'_>) -> async_graphql::Result<&'a CurrentUser> {
+    ctx.data::<Option<CurrentUser>>()?
+        .as_ref()
+        .ok_or_else(|| graphql_error("UNAUTHORIZED", "Unauthorized"))
+}
+
+/// Checks a template from the client, treating a blank one as "use the default".
+fn validate_template(
+    kind: AnnouncementKind,
+    template: Option<String>,
+) -> async_graphql::Result<Option<String>> {
+    let Some(template) = template
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+    else {
+        return Ok(None);
+    };
+    parse_template(kind, &template).map_err(|e| {
+        graphql_error(
+            "INVALID_TEMPLATE",
+            format!("Invalid {} template: {e}", kind.as_str()),
+        )
+    })?;
+    Ok(Some(template))
+}
+
+#[derive(Default)]
+pub struct TwitchChatQuery;
+
+#[Object]
+impl TwitchChatQuery {
+    /// The current user's chat bot settings. Users who have never changed them get the defaults
+    /// (disabled).
+    async fn my_twitch_chat_bot_preferences(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<TwitchChatBotPreferences> {
+        let user = require_current_user(ctx)?;
+        Ok(load_preferences(ctx.data::<PgPool>()?, user.id).await?)
+    }
+}
+
+#[derive(Default)]
+pub struct TwitchChatMutation;
+
+#[Object]
+impl TwitchChatMutation {
+    /// Replaces the current user's chat bot settings. Enabling the bot requires a linked Twitch
+    /// account; if the account is later unlinked, announcements stop until it's linked again.
+    async fn twitch_update_chat_bot_preferences(
+        &self,
+        ctx: &Context<'_>,
+        input: TwitchChatBotPreferencesInput,
+    ) -> async_graphql::Result<TwitchChatBotPreferences> {
+        let user = require_current_user(ctx)?;
+        if ctx.data::<Option<Arc<TwitchChatBot>>>()?.is_none() {
+            return Err(graphql_error(
+                "TWITCH_CHAT_BOT_NOT_CONFIGURED",
+                "The Twitch chat bot is not configured",
+            ));
+        }
+        let pool = ctx.data::<PgPool>()?;
+
+        let preferences = TwitchChatBotPreferences::new(
+            input.enabled,
+            validate_template(AnnouncementKind::Start, input.start_template)?,
+            validate_template(AnnouncementKind::Result, input.result_template)?,
+        );
+        if preferences.enabled && twitch::load_connection(pool, user.id).await?.is_none() {
+            return Err(graphql_error(
+                "TWITCH_NOT_LINKED",
+                "Link your Twitch account before enabling the chat bot",
+            ));
+        }
+
+        upsert_preferences(pool, user.id, &preferences).await?;
+        Ok(preferences)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn irc_lines_are_parsed() {
+        assert_eq!(
+            parse_irc_line("PING :tmi.twitch.tv\r\n"),
+            Some(IrcMessage {
+                command: "PING",
+                params: vec!["tmi.twitch.tv"],
+            })
+        );
+        assert_eq!(
+            parse_irc_line(":tmi.twitch.tv 001 sbbot :Welcome, GLHF!"),
+            Some(IrcMessage {
+                command: "001",
+                params: vec!["sbbot", "Welcome, GLHF!"],
+            })
+        );
+        assert_eq!(
+            parse_irc_line("@msg-id=msg_banned :tmi.twitch.tv NOTICE #streamer :You are banned."),
+            Some(IrcMessage {
+                command: "NOTICE",
+                params: vec!["#streamer", "You are banned."],
+            })
+        );
+        assert_eq!(parse_irc_line(""), None);
+    }
+
+    #[test]
+    fn default_templates_are_valid() {
+        assert!(parse_template(AnnouncementKind::Start, DEFAULT_START_TEMPLATE).is_ok());
+        assert!(parse_template(AnnouncementKind::Result, DEFAULT_RESULT_TEMPLATE).is_ok());
+    }
+
+    #[test]
+    fn templates_are_validated() {
+        assert!(matches!(
+            parse_template(AnnouncementKind::Start, "{streamer} got {mmr_change}"),
+            Err(TemplateError::UnknownPlaceholder(name, _)) if name == "mmr_change"
+        ));
+        assert_eq!(
+            parse_template(AnnouncementKind::Result, "{streamer won"),
+            Err(TemplateError::Unclosed)
+        );
+        assert_eq!(
+            parse_template(AnnouncementKind::Result, "{streamer}\n{result}"),
+            Err(TemplateError::ControlCharacter)
+        );
+        assert_eq!(
+            parse_template(
+                AnnouncementKind::Result,
+                &"a".repeat(MAX_TEMPLATE_LENGTH + 1)
+            ),
+            Err(TemplateError::TooLong)
+        );
+        // Length is counted in characters, not bytes
+        assert!(
+            parse_template(AnnouncementKind::Result, &"스".repeat(MAX_TEMPLATE_LENGTH)).is_ok()
+        );
+    }
+
+    #[test]
+    fn templates_are_rendered() {
+        let values = HashMap::from([
+            ("streamer", "Pachi".to_string()),
+            ("opponent", "Dragon".to_string()),
+            ("map", "Fighting Spirit".to_string()),
+            ("mode", "1v1".to_string()),
+            ("result", "won".to_string()),
+            ("mmr", format_rating(1523.6)),
+            ("mmr_change", format_rating_change(18.2)),
+        ]);
+        assert_eq!(
+            render_template(AnnouncementKind::Result, None, &values),
+            "Pachi won vs Dragon on Fighting Spirit. MMR: 1524 (+18)"
+        );
+        assert_eq!(
+            render_template(
+                AnnouncementKind::Result,
+                Some("GG {opponent}! {mmr_change}"),
+                &values
+            ),
+            "GG Dragon! +18"
+        );
+        // Invalid stored templates fall back to the default
+        assert_eq!(
+            render_template(AnnouncementKind::Result, Some("{opponent_mmr}"), &values),
+            "Pachi won vs Dragon on Fighting Spirit. MMR: 1524 (+18)"
+        );
+    }
+
+    #[test]
+    fn control_characters_are_stripped_from_values() {
+        let values = HashMap::from([("map", "Evil\r\nPRIVMSG #other :spam\u{0}".to_string())]);
+        assert_eq!(
+            render_template(AnnouncementKind::Start, Some("On {map}"), &values),
+            "On EvilPRIVMSG #other :spam"
+        );
+    }
+
+    #[test]
+    fn rendered_messages_fit_in_twitch_chat() {
+        let values = HashMap::from([("opponent", "x".repeat(1000))]);
+        let message = render_template(AnnouncementKind::Start, Some("vs {opponent}"), &values);
+        assert_eq!(message.chars().count(), MAX_MESSAGE_LENGTH);
+    }
+
+    #[test]
+    fn rating_changes_are_signed() {
+        assert_eq!(format_rating_change(12.4), "+12");
+        assert_eq!(format_rating_change(-7.6), "-8");
+        assert_eq!(format_rating_change(0.2), "+0");
+    }
+
+    /// A stand-in for Twitch chat that accepts `password` (and rejects anything else), pings each
+    /// client once it's logged in, and forwards every line it receives.
+    async fn start_fake_irc_server(
+        password: &'static str,
+    ) -> (String, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (lines_tx, lines_rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+                let lines_tx = lines_tx.clone();
+                tokio::spawn(async move {
+                    let mut authenticated = false;
+                    while let Some(Ok(Message::Text(text))) = socket.next().await {
+                        for line in text.lines() {
+                            lines_tx.send(line.to_string()).unwrap();
+                            if let Some(pass) = line.strip_prefix("PASS ") {
+                                authenticated = pass == format!("oauth:{password}");
+                            } else if line.starts_with("NICK ") {
+                                let reply = if authenticated {
+                                    ":tmi.twitch.tv 001 sbbot :Welcome, GLHF!\r\n\
+                                     PING :tmi.twitch.tv"
+                                } else {
+                                    ":tmi.twitch.tv NOTICE * :Login authentication failed"
+                                };
+                                socket.send(Message::Text(reply.into())).await.unwrap();
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        (url, lines_rx)
+    }
+
+    async fn mock_token_refresh(server: &mut mockito::Server, access_token: &str) -> mockito::Mock {
+        server
+            .mock("POST", "/token")
+            .match_body(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("grant_type".into(), "refresh_token".into()),
+                mockito::Matcher::UrlEncoded("refresh_token".into(), "refresh-1".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "access_token": access_token,
+                    "refresh_token": "refresh-2",
+                    "expires_in": 14000,
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await
+    }
+
+    fn test_bot(
+        token_server: &mockito::Server,
+        irc_url: String,
+    ) -> (TwitchChatBot, ChatConnection) {
+        let twitch = TwitchSettings {
+            client_id: "client-id".into(),
+            client_secret: "client-secret".to_string().into(),
+            eventsub_secret: "eventsub-secret".to_string().into(),
+        };
+        let bot = TwitchChatBotSettings {
+            login: "SbBot".into(),
+            refresh_token: "refresh-1".to_string().into(),
+        };
+        let endpoints = ChatEndpoints {
+            token_url: format!("{}/token", token_server.url()),
+            irc_url,
+        };
+        TwitchChatBot::new(&twitch, &bot, endpoints)
+    }
+
+    async fn next_line(lines: &mut mpsc::UnboundedReceiver<String>) -> String {
+        tokio::time::timeout(Duration::from_secs(5), lines.recv())
+            .await
+            .expect("timed out waiting for a line")
+            .expect("fake server stopped")
+    }
+
+    #[tokio::test]
+    async fn messages_are_sent_to_joined_channels() {
+        let mut token_server = mockito::Server::new_async().await;
+        let token_mock = mock_token_refresh(&mut token_server, "access-1").await;
+        let (irc_url, mut lines) = start_fake_irc_server("access-1").await;
+        let (bot, connection) = test_bot(&token_server, irc_url);
+
+        bot.say("Streamer", "GLHF!".into());
+        tokio::spawn(connection.run());
+
+        assert_eq!(next_line(&mut lines).await, "PASS oauth:access-1");
+        assert_eq!(next_line(&mut lines).await, "NICK sbbot");
+        let mut rest = Vec::new();
+        for _ in 0..3 {
+            rest.push(next_line(&mut lines).await);
+        }
+        // The PONG can land anywhere, but a channel has to be joined before it's messaged
+        assert!(rest.contains(&"PONG :tmi.twitch.tv".to_string()));
+        rest.retain(|line| !line.starts_with("PONG"));
+        assert_eq!(rest, vec!["JOIN #streamer", "PRIVMSG #streamer :GLHF!"]);
+
+        // Later messages to the same channel don't join it again
+        bot.say("streamer", "GG".into());
+        assert_eq!(next_line(&mut lines).await, "PRIVMSG #streamer :GG");
+        token_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn rejected_logins_fail_the_connection() {
+        let mut token_server = mockito::Server::new_async().await;
+        mock_token_refresh(&mut token_server, "revoked").await;
+        let (irc_url, _lines) = start_fake_irc_server("access-1").await;
+        let (_bot, mut connection) = test_bot(&token_server, irc_url);
+
+        let err = connection.connect().await.unwrap_err();
+        assert!(
+            format!("{err:?}").contains("Login authentication failed"),
+            "{err:?}"
+        );
+        // The rotated refresh token is kept for the next attempt
+        assert_eq!(connection.refresh_token.expose_secret(), "refresh-2");
+    }
+}
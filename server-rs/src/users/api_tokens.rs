@@ -0,0 +1,771 @@
+//! Personal API tokens, which let users give third-party tools and bots access to the GraphQL API
+//! on their behalf without handing over their password or a browser session.
+//!
+//! Tokens are sent as a bearer token just like a session JWT, and are told apart by their
+//! `sbpat_` prefix (see `jwt_middleware`). Each token carries a set of scopes, and requests made
+//! with one may only select the fields those scopes allow (enforced by
+//! [`ApiTokenScopeExtension`]); everything else, including managing tokens themselves, requires a
+//! real session. Each token also has its own per-minute rate limit, counted in Redis.
+
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextResolve, ResolveInfo,
+};
+use async_graphql::{Context, Object, PathSegment, Pos, Result, ServerResult, SimpleObject, Value};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use base64::Engine as _;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{self, WrapErr};
+use deadpool_redis::redis::AsyncCommands;
+use rand::{Rng, rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::graphql::errors::graphql_error;
+use crate::redis::RedisPool;
+use crate::sessions::SbSession;
+use crate::users::{CurrentUser, SbUserId};
+
+/// Prefix for every personal API token, so they can be distinguished from session JWTs (and
+/// recognized by secret scanners if they get committed somewhere).
+pub const API_TOKEN_PREFIX: &str = "sbpat_";
+/// Number of random bytes in a token (after the prefix).
+const TOKEN_BYTES: usize = 32;
+/// How much of the token (including the prefix) we keep in plaintext for display.
+const TOKEN_DISPLAY_PREFIX_LEN: usize = API_TOKEN_PREFIX.len() + 4;
+/// How many unrevoked tokens a user can have at once.
+const MAX_ACTIVE_TOKENS: i64 = 10;
+const MAX_TOKEN_NAME_LENGTH: usize = 64;
+const MAX_TOKEN_LIFETIME_DAYS: i32 = 365;
+/// Length of the fixed window the per-token rate limit is counted over.
+const RATE_LIMIT_WINDOW_SECONDS: u64 = 60;
+
+fn rate_limit_key(token_id: Uuid, window: u64) -> String {
+    format!("api-tokens:rate:{token_id}:{window}")
+}
+
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type, async_graphql::Enum,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "api_token_scope", rename_all = "snake_case")]
+pub enum ApiTokenScope {
+    /// Read public user profiles (IDs, display names, avatars and linked streams), including the
+    /// token owner's. Account details like email addresses and permissions are never available to
+    /// tokens.
+    ReadProfile,
+    /// Read games and their results.
+    ReadGames,
+    /// Read ranked ratings and rating history.
+    ReadRatings,
+    /// File game reports as the token owner.
+    WriteGameReports,
+}
+
+/// What an API token needs in order to select a field.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum TokenFieldAccess {
+    /// The token must have this scope.
+    Scope(ApiTokenScope),
+    /// Any token that was allowed to select the field's parent can select it.
+    Inherited,
+}
+
+impl ApiTokenScope {
+    /// Returns what a token needs to select a field, or `None` if tokens can't select it at all.
+    /// This applies to the fields of every type, not just the roots: anything not listed here is
+    /// off-limits to tokens, so new fields have to be opted in explicitly.
+    fn required_for(parent_type: &str, field: &str) -> Option<TokenFieldAccess> {
+        use ApiTokenScope::*;
+        use TokenFieldAccess::{Inherited, Scope};
+
+        match (parent_type, field) {
+            ("Query", "currentUser" | "user" | "userByDisplayName") => Some(Scope(ReadProfile)),
+            ("Query", "game" | "liveGames") => Some(Scope(ReadGames)),
+            ("Query", "userRankedModes" | "userRatingHistory") => Some(Scope(ReadRatings)),
+            ("Mutation", "reportGame") => Some(Scope(WriteGameReports)),
+
+            // Only what's shown on a public profile. The owner's account details (email,
+            // permissions, name change state, etc.) and stream analytics need a real session.
+            ("CurrentUser" | "SbUser", "id" | "name" | "avatarUrl") => Some(Inherited),
+            ("SbUser", "twitchChannel" | "liveStream") => Some(Scope(ReadProfile)),
+            ("TwitchChannel", _) => Some(Inherited),
+            (
+                "LiveStream",
+                "provider" | "channelLogin" | "channelDisplayName" | "url" | "title" | "gameName"
+                | "viewerCount" | "startedAt" | "thumbnailUrl" | "twitchLogin"
+                | "twitchDisplayName" | "user",
+            ) => Some(Inherited),
+            ("LiveStream", "currentGame") => Some(Scope(ReadGames)),
+
+            (
+                "Game",
+                "id" | "startTime" | "config" | "disputable" | "disputeRequested"
+                | "disputeReviewed" | "gameLength" | "results" | "map",
+            ) => Some(Inherited),
+            ("Game", "liveStreams") => Some(Scope(ReadProfile)),
+            (
+                "GameConfigDataLobby"
+                | "GameConfigDataMatchmaking"
+                | "GamePlayer"
+                | "LobbyExtra"
+                | "ReconciledPlayerResultEntry"
+                | "ReconciledPlayerResult",
+                _,
+            ) => Some(Inherited),
+            (t, _) if t.starts_with("MatchmakingExtra") => Some(Inherited),
+            (
+                "UploadedMap",
+                "id" | "name" | "description" | "uploadDate" | "visibility" | "uploader"
+                | "mapFile" | "tags" | "balanceStats",
+            ) => Some(Inherited),
+            (
+                "MapFile"
+                | "MapForce"
+                | "MapForcePlayer"
+                | "MapBalanceStats"
+                | "MapMatchupStats"
+                | "MapStartLocationStats",
+                _,
+            ) => Some(Inherited),
+
+            ("UserRankedMode" | "RatingHistory" | "RatingHistoryPoint", _) => Some(Inherited),
+
+            // The report that was just filed, but none of its moderation details
+            (
+                "GameReport",
+                "id" | "reason" | "details" | "createdAt" | "resolvedAt" | "resolution"
+                | "reporter" | "reportedUser",
+            ) => Some(Inherited),
+            ("GameReport", "game") => Some(Scope(ReadGames)),
+
+            _ => None,
+        }
+    }
+}
+
+/// A request authenticated with a personal API token rather than a session.
+#[derive(Clone, Debug)]
+pub struct ApiTokenSession {
+    pub token_id: Uuid,
+    pub user_id: SbUserId,
+    pub scopes: Vec<ApiTokenScope>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ApiTokenAuthError {
+    #[error("invalid API token")]
+    Invalid,
+    #[error("API token rate limit exceeded")]
+    RateLimited { retry_after_secs: u64 },
+    #[error(transparent)]
+    UnexpectedError(#[from] eyre::Error),
+}
+
+impl IntoResponse for ApiTokenAuthError {
+    fn into_response(self) -> Response {
+        match self {
+            ApiTokenAuthError::Invalid => {
+                (StatusCode::UNAUTHORIZED, "Invalid API token").into_response()
+            }
+            ApiTokenAuthError::RateLimited { retry_after_secs } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                "Rate limit exceeded",
+            )
+                .into_response(),
+            ApiTokenAuthError::UnexpectedError(e) => {
+                error!("Failed to authenticate API token: {e:?}");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error").into_response()
+            }
+        }
+    }
+}
+
+fn hash_token(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
+}
+
+fn gen_token() -> String {
+    let mut buf = [0u8; TOKEN_BYTES];
+    rng().fill_bytes(&mut buf);
+    format!("{API_TOKEN_PREFIX}{}", BASE64_URL_SAFE_NO_PAD.encode(buf))
+}
+
+/// Counts a request against a token's rate limit, failing if it's over `limit` for the current
+/// window.
+async fn check_rate_limit(
+    redis: &mut impl AsyncCommands,
+    token_id: Uuid,
+    limit: u64,
+    now_secs: u64,
+) -> Result<(), ApiTokenAuthError> {
+    let window = now_secs / RATE_LIMIT_WINDOW_SECONDS;
+    let key = rate_limit_key(token_id, window);
+    let (count,): (u64,) = deadpool_redis::redis::pipe()
+        .incr(&key, 1)
+        .expire(&key, RATE_LIMIT_WINDOW_SECONDS as i64)
+        .ignore()
+        .query_async(redis)
+        .await
+        .wrap_err("Failed to update API token rate limit")?;
+
+    if count > limit {
+        Err(ApiTokenAuthError::RateLimited {
+            retry_after_secs: RATE_LIMIT_WINDOW_SECONDS - now_secs % RATE_LIMIT_WINDOW_SECONDS,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Looks up the token presented in a request, checking that it's still usable (not revoked or
+/// expired, and its owner isn't banned or deleted) and counting the request against its rate
+/// limit.
+pub async fn authenticate_api_token(
+    db: &PgPool,
+    redis: &RedisPool,
+    token: &str,
+) -> Result<ApiTokenSession, ApiTokenAuthError> {
+    let row = sqlx::query!(
+        r#"
+            SELECT t.id, t.user_id as "user_id: SbUserId",
+                t.scopes as "scopes: Vec<ApiTokenScope>", t.rate_limit_per_minute
+            FROM user_api_tokens t
+            JOIN users u ON u.id = t.user_id
+            WHERE t.token_hash = $1
+                AND t.revoked_at IS NULL
+                AND (t.expires_at IS NULL OR t.expires_at > now())
+                AND u.deleted_at IS NULL
+                AND NOT EXISTS (
+                    SELECT 1 FROM user_bans b
+                    WHERE b.user_id = t.user_id AND b.start_time <= now() AND b.end_time > now()
+                )
+        "#,
+        hash_token(token),
+    )
+    .fetch_optional(db)
+    .await
+    .wrap_err("Failed to look up API token")?;
+    let Some(row) = row else {
+        return Err(ApiTokenAuthError::Invalid);
+    };
+
+    let now_secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .wrap_err("System clock is before the unix epoch")?
+        .as_secs();
+    let mut conn = redis.get().await.wrap_err("Could not connect to Redis")?;
+    check_rate_limit(
+        &mut conn,
+        row.id,
+        row.rate_limit_per_minute.max(0) as u64,
+        now_secs,
+    )
+    .await?;
+
+    // Only write when the stored value is stale, so a busy bot doesn't update the row on every
+    // request
+    if let Err(e) = sqlx::query!(
+        r#"
+            UPDATE user_api_tokens
+            SET last_used_at = now()
+            WHERE id = $1 AND (last_used_at IS NULL OR last_used_at < now() - interval '1 minute')
+        "#,
+        row.id,
+    )
+    .execute(db)
+    .await
+    {
+        error!("Failed to update API token last use: {e:?}");
+    }
+
+    Ok(ApiTokenSession {
+        token_id: row.id,
+        user_id: row.user_id,
+        scopes: row.scopes,
+    })
+}
+
+/// Restricts requests made with an API token to the fields their scopes allow.
+#[derive(Default)]
+pub struct ApiTokenScopeExtension;
+
+#[async_trait::async_trait]
+impl Extension for ApiTokenScopeExtension {
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        if !info.is_for_introspection
+            && let Some(SbSession::ApiToken(token)) = ctx.data_opt::<SbSession>()
+        {
+            let allowed = match ApiTokenScope::required_for(info.parent_type, info.name) {
+                Some(TokenFieldAccess::Scope(scope)) => token.scopes.contains(&scope),
+                // Root fields always need a scope of their own
+                Some(TokenFieldAccess::Inherited) => info.path_node.parent.is_some(),
+                None => false,
+            };
+            if !allowed {
+                let mut err = graphql_error(
+                    "FORBIDDEN",
+                    format!(
+                        "This API token can't access `{}.{}`",
+                        info.parent_type, info.name
+                    ),
+                )
+                .into_server_error(Pos::default());
+                err.locations.clear();
+                err.path = info
+                    .path_node
+                    .to_string_vec()
+                    .into_iter()
+                    .map(|segment| match segment.parse() {
+                        // Field names can't start with a digit, so these are list indexes
+                        Ok(index) => PathSegment::Index(index),
+                        Err(_) => PathSegment::Field(segment),
+                    })
+                    .collect();
+                return Err(err);
+            }
+        }
+
+        next.run(ctx, info).await
+    }
+}
+
+impl ExtensionFactory for ApiTokenScopeExtension {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(ApiTokenScopeExtension)
+    }
+}
+
+#[derive(SimpleObject, Clone, Debug)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub name: String,
+    /// The first few characters of the token, to help the user recognize it.
+    pub token_prefix: String,
+    pub scopes: Vec<ApiTokenScope>,
+    pub rate_limit_per_minute: i32,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Roughly when the token was last used (updated at most once a minute).
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// A newly created token, along with its secret value.
+#[derive(SimpleObject)]
+pub struct CreatedApiToken {
+    pub api_token: ApiToken,
+    /// The token to send as a bearer token. This is the only time it's available; only a hash of it
+    /// is stored.
+    pub token: String,
+}
+
+#[derive(Default)]
+pub struct ApiTokensQuery;
+
+#[Object]
+impl ApiTokensQuery {
+    /// The current user's unrevoked API tokens, newest first.
+    async fn current_user_api_tokens(&self, ctx: &Context<'_>) -> Result<Vec<ApiToken>> {
+        let Some(user) = ctx.data::<Option<CurrentUser>>()? else {
+            return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+        };
+
+        let tokens = sqlx::query_as!(
+            ApiToken,
+            r#"
+                SELECT id, name, token_prefix, scopes as "scopes: Vec<ApiTokenScope>",
+                    rate_limit_per_minute, created_at, expires_at, last_used_at
+                FROM user_api_tokens
+                WHERE user_id = $1 AND revoked_at IS NULL
+                ORDER BY created_at DESC
+            "#,
+            user.id as _,
+        )
+        .fetch_all(ctx.data::<PgPool>()?)
+        .await
+        .wrap_err("Failed to load API tokens")?;
+
+        Ok(tokens)
+    }
+}
+
+#[derive(Default)]
+pub struct ApiTokensMutation;
+
+#[Object]
+impl ApiTokensMutation {
+    /// Creates a personal API token for the current user with the given scopes. If `expiresInDays`
+    /// is omitted, the token is valid until it's revoked.
+    async fn user_create_api_token(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        scopes: Vec<ApiTokenScope>,
+        expires_in_days: Option<i32>,
+    ) -> Result<CreatedApiToken> {
+        let Some(user) = ctx.data::<Option<CurrentUser>>()? else {
+            return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+        };
+
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAX_TOKEN_NAME_LENGTH {
+            return Err(graphql_error(
+                "BAD_REQUEST",
+                format!("Token names must be 1-{MAX_TOKEN_NAME_LENGTH} characters"),
+            ));
+        }
+        let mut scopes = scopes;
+        scopes.sort_by_key(|s| *s as u8);
+        scopes.dedup();
+        if scopes.is_empty() {
+            return Err(graphql_error(
+                "BAD_REQUEST",
+                "Tokens must have at least one scope",
+            ));
+        }
+        if let Some(days) = expires_in_days
+            && !(1..=MAX_TOKEN_LIFETIME_DAYS).contains(&days)
+        {
+            return Err(graphql_error(
+                "BAD_REQUEST",
+                format!("Tokens can expire in 1-{MAX_TOKEN_LIFETIME_DAYS} days"),
+            ));
+        }
+
+        let mut tx = ctx
+            .data::<PgPool>()?
+            .begin()
+            .await
+            .wrap_err("Failed to start transaction")?;
+
+        // Lock the user's row so concurrent creations can't both pass the limit check
+        sqlx::query!(
+            "SELECT id FROM users WHERE id = $1 FOR UPDATE",
+            user.id as _
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .wrap_err("Failed to lock user")?;
+        let active = sqlx::query_scalar!(
+            r#"
+                SELECT COUNT(*) as "count!"
+                FROM user_api_tokens
+                WHERE user_id = $1 AND revoked_at IS NULL
+                    AND (expires_at IS NULL OR expires_at > now())
+            "#,
+            user.id as _,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .wrap_err("Failed to count API tokens")?;
+        if active >= MAX_ACTIVE_TOKENS {
+            return Err(graphql_error(
+                "TOO_MANY_TOKENS",
+                format!(
+                    "You can have at most {MAX_ACTIVE_TOKENS} active tokens. Revoke one \
+                     before creating another."
+                ),
+            ));
+        }
+
+        let token = gen_token();
+        let expires_at =
+            expires_in_days.map(|days| Utc::now() + chrono::Duration::days(days as i64));
+        let api_token = sqlx::query_as!(
+            ApiToken,
+            r#"
+                INSERT INTO user_api_tokens
+                    (user_id, name, token_hash, token_prefix, scopes, expires_at)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                RETURNING id, name, token_prefix, scopes as "scopes: Vec<ApiTokenScope>",
+                    rate_limit_per_minute, created_at, expires_at, last_used_at
+            "#,
+            user.id as _,
+            name,
+            hash_token(&token),
+            &token[..TOKEN_DISPLAY_PREFIX_LEN],
+            &scopes as &[ApiTokenScope],
+            expires_at,
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .wrap_err("Failed to create API token")?;
+
+        tx.commit().await.wrap_err("Failed to commit transaction")?;
+
+        Ok(CreatedApiToken { api_token, token })
+    }
+
+    /// Revokes one of the current user's API tokens, immediately rejecting any further requests
+    /// made with it. Returns whether a token was revoked.
+    async fn user_revoke_api_token(&self, ctx: &Context<'_>, id: Uuid) -> Result<bool> {
+        let Some(user) = ctx.data::<Option<CurrentUser>>()? else {
+            return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+        };
+
+        let result = sqlx::query!(
+            r#"
+                UPDATE user_api_tokens
+                SET revoked_at = now()
+                WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+            id,
+            user.id as _,
+        )
+        .execute(ctx.data::<PgPool>()?)
+        .await
+        .wrap_err("Failed to revoke API token")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::dataloader::DataLoader;
+    use deadpool_redis::redis::aio::ConnectionLike;
+    use deadpool_redis::redis::{Cmd, Pipeline, RedisFuture, Value as RedisValue};
+
+    use super::*;
+    use crate::users::UsersLoader;
+
+    struct FakeRedis {
+        response: Vec<RedisValue>,
+        pipelines: Vec<Vec<u8>>,
+    }
+
+    impl ConnectionLike for FakeRedis {
+        fn req_packed_command<'a>(&'a mut self, _cmd: &'a Cmd) -> RedisFuture<'a, RedisValue> {
+            panic!("rate limiting should issue a pipeline, not single commands")
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            cmd: &'a Pipeline,
+            _offset: usize,
+            _count: usize,
+        ) -> RedisFuture<'a, Vec<RedisValue>> {
+            self.pipelines.push(cmd.get_packed_pipeline());
+            let response = self.response.clone();
+            Box::pin(async move { Ok(response) })
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    #[tokio::test]
+    async fn rate_limit_allows_requests_up_to_limit() {
+        let token_id = Uuid::new_v4();
+        let mut redis = FakeRedis {
+            response: vec![RedisValue::Int(60), RedisValue::Int(1)],
+            pipelines: Vec::new(),
+        };
+
+        let res = check_rate_limit(&mut redis, token_id, 60, 6_000).await;
+
+        assert!(res.is_ok(), "{res:?}");
+        let mut expected = deadpool_redis::redis::pipe();
+        expected
+            .incr(rate_limit_key(token_id, 100), 1)
+            .expire(rate_limit_key(token_id, 100), 60)
+            .ignore();
+        assert_eq!(redis.pipelines, vec![expected.get_packed_pipeline()]);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_rejects_requests_over_limit() {
+        let mut redis = FakeRedis {
+            response: vec![RedisValue::Int(61), RedisValue::Int(1)],
+            pipelines: Vec::new(),
+        };
+
+        let res = check_rate_limit(&mut redis, Uuid::new_v4(), 60, 6_045).await;
+
+        assert!(
+            matches!(
+                res,
+                Err(ApiTokenAuthError::RateLimited {
+                    retry_after_secs: 15
+                })
+            ),
+            "{res:?}"
+        );
+    }
+
+    #[test]
+    fn generated_tokens_are_prefixed_and_unique() {
+        let a = gen_token();
+        let b = gen_token();
+
+        assert!(a.starts_with(API_TOKEN_PREFIX));
+        assert_eq!(a.len(), API_TOKEN_PREFIX.len() + 43);
+        assert_ne!(a, b);
+        assert_ne!(hash_token(&a), hash_token(&b));
+    }
+
+    #[test]
+    fn scopes_only_unlock_listed_fields() {
+        assert_eq!(
+            ApiTokenScope::required_for("Query", "currentUser"),
+            Some(TokenFieldAccess::Scope(ApiTokenScope::ReadProfile))
+        );
+        assert_eq!(
+            ApiTokenScope::required_for("Query", "userRatingHistory"),
+            Some(TokenFieldAccess::Scope(ApiTokenScope::ReadRatings))
+        );
+        assert_eq!(
+            ApiTokenScope::required_for("Mutation", "reportGame"),
+            Some(TokenFieldAccess::Scope(ApiTokenScope::WriteGameReports))
+        );
+        // Account management is never available to tokens
+        assert_eq!(
+            ApiTokenScope::required_for("Mutation", "userCreateApiToken"),
+            None
+        );
+        assert_eq!(
+            ApiTokenScope::required_for("Mutation", "userUpdateCurrent"),
+            None
+        );
+    }
+
+    #[test]
+    fn nested_fields_need_their_own_access() {
+        assert_eq!(
+            ApiTokenScope::required_for("CurrentUser", "name"),
+            Some(TokenFieldAccess::Inherited)
+        );
+        assert_eq!(
+            ApiTokenScope::required_for("Game", "liveStreams"),
+            Some(TokenFieldAccess::Scope(ApiTokenScope::ReadProfile))
+        );
+        assert_eq!(
+            ApiTokenScope::required_for("LiveStream", "currentGame"),
+            Some(TokenFieldAccess::Scope(ApiTokenScope::ReadGames))
+        );
+        assert_eq!(ApiTokenScope::required_for("CurrentUser", "email"), None);
+        assert_eq!(
+            ApiTokenScope::required_for("SbUser", "streamAnalytics"),
+            None
+        );
+        assert_eq!(
+            ApiTokenScope::required_for("GameReport", "resolutionNotes"),
+            None
+        );
+    }
+
+    fn token_user(id: SbUserId) -> CurrentUser {
+        CurrentUser {
+            id,
+            name: "pachi".into(),
+            login_name: "pachi".into(),
+            email: "pachi@example.org".into(),
+            email_verified: true,
+            accepted_privacy_version: 1,
+            accepted_terms_version: 1,
+            accepted_use_policy_version: 1,
+            locale: None,
+            last_login_name_change: None,
+            last_name_change: None,
+            name_change_tokens: 0,
+            avatar_url: None,
+            permissions: crate::users::permissions::SbPermissions {
+                id,
+                edit_permissions: false,
+                debug: false,
+                ban_users: false,
+                manage_leagues: false,
+                manage_maps: false,
+                manage_map_pools: false,
+                manage_matchmaking: false,
+                manage_matchmaking_seasons: false,
+                manage_matchmaking_times: false,
+                mass_delete_maps: false,
+                moderate_chat_channels: false,
+                manage_news: false,
+                manage_bug_reports: false,
+                manage_game_reports: false,
+                manage_restricted_names: false,
+                manage_signup_codes: false,
+                manage_live_streams: false,
+            },
+        }
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn read_profile_tokens_only_see_public_profile_fields(db: PgPool) {
+        let user_id = crate::test_utils::create_user(&db, "pachi").await;
+        let dir = tempfile::tempdir().unwrap();
+        let schema = crate::schema::build_schema()
+            .extension(ApiTokenScopeExtension)
+            .data(DataLoader::new(
+                UsersLoader::new(db.clone(), crate::test_utils::local_file_store(dir.path())),
+                tokio::spawn,
+            ))
+            .finish();
+        let execute = |query: String| {
+            let request = async_graphql::Request::new(query)
+                .data(SbSession::ApiToken(ApiTokenSession {
+                    token_id: Uuid::nil(),
+                    user_id,
+                    scopes: vec![ApiTokenScope::ReadProfile],
+                }))
+                .data(Some(token_user(user_id)));
+            let schema = schema.clone();
+            async move { schema.execute(request).await }
+        };
+
+        let response = execute(format!(
+            "{{ currentUser {{ id name }} user(id: {}) {{ id name }} }}",
+            user_id.0
+        ))
+        .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        for (query, path) in [
+            ("{ currentUser { email } }".to_string(), "currentUser.email"),
+            (
+                "{ currentUser { permissions { id } } }".to_string(),
+                "currentUser.permissions",
+            ),
+            (
+                format!(
+                    "{{ user(id: {}) {{ streamAnalytics {{ stats {{ sessionCount }} }} }} }}",
+                    user_id.0
+                ),
+                "user.streamAnalytics",
+            ),
+        ] {
+            let response = execute(query).await;
+            let paths = response
+                .errors
+                .iter()
+                .map(|e| {
+                    e.path
+                        .iter()
+                        .map(|p| match p {
+                            PathSegment::Field(f) => f.clone(),
+                            PathSegment::Index(i) => i.to_string(),
+                        })
+                        .collect::<Vec<_>>()
+                        .join(".")
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(paths, vec![path]);
+        }
+    }
+}
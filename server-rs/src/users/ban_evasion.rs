@@ -0,0 +1,268 @@
+//! Ban evasion detection, matching `UserIdentifierManager.banUserIfNeeded` on the Node side.
+//!
+//! The game client reports a set of hashed machine identifiers when it logs in, which Node stores
+//! in `user_identifiers`. When a user is banned, their identifiers are banned along with them (in
+//! `user_identifier_bans`), so an account that shares enough of its identifiers with a banned one
+//! is assumed to be the same person and gets banned as well.
+
+use chrono::Duration;
+use color_eyre::eyre::{self, WrapErr};
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::redis::RedisPool;
+use crate::sessions::revoke_user_sessions;
+use crate::users::{PublishedUserMessage, SbUserId};
+
+/// How many identifier types have to match for two accounts to be considered the same machine.
+/// Matches `MIN_IDENTIFIER_MATCHES` in `server/lib/users/client-ids.ts`.
+const MIN_IDENTIFIER_MATCHES: i64 = 4;
+const MIN_BAN_LENGTH: Duration = Duration::hours(1);
+const MAX_BAN_LENGTH: Duration = Duration::days(100 * 365);
+
+/// Identifier type 0 is a browser fingerprint, which is far too likely to collide between people
+/// to ban anyone over.
+const BROWSERPRINT_IDENTIFIER_TYPE: i16 = 0;
+
+/// How long an evading account is banned for: twice the time remaining on the ban it's evading,
+/// so that repeated evasion escalates quickly.
+fn evasion_ban_length(remaining: Duration) -> Duration {
+    (remaining * 2).clamp(MIN_BAN_LENGTH, MAX_BAN_LENGTH)
+}
+
+/// Bans `user_id` (and the accounts connected to it) if it shares enough identifiers with a banned
+/// account. Returns whether it was banned. This should be checked after the user's own ban status,
+/// otherwise a banned user logging in would ban themselves for evading their own ban.
+pub async fn ban_user_if_needed(
+    db: &PgPool,
+    redis: &RedisPool,
+    user_id: SbUserId,
+) -> eyre::Result<bool> {
+    let Some(evaded_ban_remaining) = find_evaded_ban(db, user_id).await? else {
+        return Ok(false);
+    };
+
+    let ban_length = evasion_ban_length(evaded_ban_remaining);
+    let banned_user_ids = enact_ban(db, user_id, ban_length).await?;
+    info!(
+        "Banned user {user_id} (and {} connected accounts) for ban evasion",
+        banned_user_ids.len() - 1
+    );
+
+    // Deleting their sessions forces them to log in again, which is where bans are checked. The
+    // bans are already in place if this fails, and Node closes any open sockets when it hears
+    // about the ban.
+    match redis.get().await {
+        Ok(mut conn) => {
+            for &banned_id in &banned_user_ids {
+                if let Err(e) = revoke_user_sessions(&mut conn, banned_id).await {
+                    error!("Failed to revoke sessions for banned user {banned_id}: {e:?}");
+                }
+            }
+        }
+        Err(e) => error!("Failed to get Redis connection to revoke sessions: {e:?}"),
+    }
+    if let Err(e) = redis
+        .publish(PublishedUserMessage::BannedForBanEvasion {
+            user_id,
+            banned_user_ids,
+            ban_length_hours: ban_length.num_minutes() as f64 / 60.0,
+        })
+        .await
+    {
+        error!("Failed to publish ban evasion message: {e:?}");
+    }
+
+    Ok(true)
+}
+
+/// Checks whether `user_id` shares enough identifiers with a banned account to be considered the
+/// same person. If so, returns the time remaining on the longest ban among the matching
+/// identifiers.
+async fn find_evaded_ban(db: &PgPool, user_id: SbUserId) -> eyre::Result<Option<Duration>> {
+    let matches = sqlx::query!(
+        r#"
+            SELECT
+                COUNT(DISTINCT identifier_type) as "count!",
+                EXTRACT(EPOCH FROM MAX(banned_until) - LOCALTIMESTAMP)::float8 as remaining_seconds
+            FROM user_identifier_bans uib
+            WHERE (uib.identifier_type, uib.identifier_hash) IN (
+                SELECT identifier_type, identifier_hash
+                FROM user_identifiers ui
+                WHERE ui.user_id = $1
+            )
+            AND banned_until > LOCALTIMESTAMP
+            AND uib.identifier_type != $2
+        "#,
+        user_id as _,
+        BROWSERPRINT_IDENTIFIER_TYPE,
+    )
+    .fetch_one(db)
+    .await
+    .wrap_err("Failed to count banned identifiers")?;
+
+    Ok((matches.count >= MIN_IDENTIFIER_MATCHES).then(|| {
+        Duration::milliseconds((matches.remaining_seconds.unwrap_or_default() * 1000.0) as i64)
+    }))
+}
+
+/// Bans `user_id` and every account that shares enough identifiers with it, along with all of
+/// their identifiers. Returns the IDs of the banned users (including `user_id`).
+async fn enact_ban(
+    db: &PgPool,
+    user_id: SbUserId,
+    ban_length: Duration,
+) -> eyre::Result<Vec<SbUserId>> {
+    let mut tx = db.begin().await?;
+
+    let mut users = sqlx::query_scalar!(
+        r#"
+            SELECT user_id as "user_id: SbUserId"
+            FROM user_identifiers ui
+            WHERE ui.user_id != $1
+            AND ui.identifier_type != $3
+            AND (ui.identifier_type, ui.identifier_hash) IN (
+                SELECT identifier_type, identifier_hash
+                FROM user_identifiers ui2
+                WHERE ui2.user_id = $1
+            )
+            GROUP BY ui.user_id
+            HAVING COUNT(DISTINCT identifier_type) >= $2
+        "#,
+        user_id as _,
+        MIN_IDENTIFIER_MATCHES,
+        BROWSERPRINT_IDENTIFIER_TYPE,
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .wrap_err("Failed to find connected users")?;
+    users.push(user_id);
+    let user_ids = users.iter().copied().map(i32::from).collect::<Vec<_>>();
+    let ban_seconds = ban_length.num_seconds() as f64;
+
+    sqlx::query!(
+        r#"
+            INSERT INTO user_bans (user_id, start_time, end_time, reason)
+            SELECT user_id, LOCALTIMESTAMP, LOCALTIMESTAMP + make_interval(secs => $2), 'ban evasion'
+            FROM UNNEST($1::int4[]) AS t(user_id)
+        "#,
+        &user_ids,
+        ban_seconds,
+    )
+    .execute(&mut *tx)
+    .await
+    .wrap_err("Failed to ban users")?;
+    sqlx::query!(
+        r#"
+            INSERT INTO user_identifier_bans AS uib (
+                identifier_type, identifier_hash, time_banned, banned_until, first_user_id
+            )
+            SELECT DISTINCT ON (identifier_type, identifier_hash)
+                identifier_type,
+                identifier_hash,
+                LOCALTIMESTAMP,
+                LOCALTIMESTAMP + make_interval(secs => $2),
+                user_id
+            FROM user_identifiers
+            WHERE user_id = ANY($1)
+            AND identifier_type != $3
+            ON CONFLICT (identifier_type, identifier_hash)
+            DO UPDATE SET banned_until = GREATEST(uib.banned_until, EXCLUDED.banned_until)
+        "#,
+        &user_ids,
+        ban_seconds,
+        BROWSERPRINT_IDENTIFIER_TYPE,
+    )
+    .execute(&mut *tx)
+    .await
+    .wrap_err("Failed to ban identifiers")?;
+
+    tx.commit().await?;
+    Ok(users)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evasion_ban_doubles_remaining_time() {
+        assert_eq!(evasion_ban_length(Duration::hours(5)), Duration::hours(10));
+        assert_eq!(evasion_ban_length(Duration::minutes(5)), MIN_BAN_LENGTH);
+        assert_eq!(evasion_ban_length(Duration::days(90 * 365)), MAX_BAN_LENGTH);
+    }
+
+    async fn add_identifiers(db: &PgPool, user_id: SbUserId, identifier_types: &[i16]) {
+        for &identifier_type in identifier_types {
+            sqlx::query(
+                r#"
+                    INSERT INTO user_identifiers
+                        (user_id, identifier_type, identifier_hash, first_used, last_used)
+                    VALUES ($1, $2, '\x0102', NOW(), NOW())
+                "#,
+            )
+            .bind(user_id.0)
+            .bind(identifier_type)
+            .execute(db)
+            .await
+            .unwrap();
+        }
+    }
+
+    async fn active_ban_hours(db: &PgPool, user_id: SbUserId) -> Option<f64> {
+        sqlx::query_scalar(
+            r#"
+                SELECT EXTRACT(EPOCH FROM end_time - start_time)::float8 / 3600
+                FROM user_bans
+                WHERE user_id = $1 AND start_time <= NOW() AND end_time > NOW()
+            "#,
+        )
+        .bind(user_id.0)
+        .fetch_optional(db)
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn bans_accounts_sharing_banned_identifiers(db: PgPool) {
+        let banned = crate::test_utils::create_user(&db, "banned").await;
+        let evader = crate::test_utils::create_user(&db, "evader").await;
+        let alt = crate::test_utils::create_user(&db, "alt").await;
+        let innocent = crate::test_utils::create_user(&db, "innocent").await;
+        add_identifiers(&db, banned, &[1, 2, 3, 4]).await;
+        add_identifiers(&db, evader, &[1, 2, 3, 4]).await;
+        add_identifiers(&db, alt, &[1, 2, 3, 4, 5]).await;
+        // Only shares a browserprint and a couple of others, which isn't enough
+        add_identifiers(&db, innocent, &[0, 1, 2]).await;
+
+        assert_eq!(find_evaded_ban(&db, evader).await.unwrap(), None);
+        enact_ban(&db, banned, Duration::hours(5)).await.unwrap();
+        // Banning the original account bans its alts right away
+        assert!(active_ban_hours(&db, evader).await.is_some());
+        assert!(active_ban_hours(&db, alt).await.is_some());
+        assert_eq!(active_ban_hours(&db, innocent).await, None);
+        assert_eq!(find_evaded_ban(&db, innocent).await.unwrap(), None);
+
+        // Accounts that pick up the banned identifiers later get caught when they log in
+        let late = crate::test_utils::create_user(&db, "late").await;
+        add_identifiers(&db, late, &[1, 2, 3, 4]).await;
+        let remaining = find_evaded_ban(&db, late).await.unwrap().unwrap();
+        assert!(remaining > Duration::hours(4) && remaining <= Duration::hours(5));
+
+        let mut banned_ids = enact_ban(&db, late, evasion_ban_length(remaining))
+            .await
+            .unwrap();
+        banned_ids.sort_by_key(|id| id.0);
+        assert_eq!(banned_ids, vec![banned, evader, alt, late]);
+        let late_ban = active_ban_hours(&db, late).await.unwrap();
+        assert!(late_ban > 8.0 && late_ban <= 10.0, "{late_ban}");
+
+        let banned_types: Vec<i16> = sqlx::query_scalar(
+            "SELECT identifier_type FROM user_identifier_bans ORDER BY identifier_type",
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        assert_eq!(banned_types, vec![1, 2, 3, 4, 5]);
+    }
+}
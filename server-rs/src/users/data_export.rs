@@ -0,0 +1,1030 @@
+//! Account data exports ("takeouts"): a user requests one, a background job assembles everything
+//! we hold about them into a zip, uploads it to the [`FileStore`] and emails them a signed link to
+//! download it.
+//!
+//! Requests are rows in `user_data_exports`, which doubles as the job queue. Jobs are claimed with
+//! `FOR UPDATE SKIP LOCKED` so that every server instance can run the loop without two of them
+//! building the same archive.
+
+use std::io::{Cursor, Seek, Write};
+use std::time::Duration;
+
+use async_graphql::{Context, Object, Result, SimpleObject};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use color_eyre::eyre::{self, WrapErr, eyre};
+use ipnetwork::IpNetwork;
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::{error, info};
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::async_rayon::spawn_rayon;
+use crate::email::{DataExportData, EmailMessage, EmailTemplate, Mailer};
+use crate::file_store::FileStore;
+use crate::graphql::errors::graphql_error;
+use crate::oauth::OAuthProvider;
+use crate::streaming::StreamingProvider;
+use crate::users::{CurrentUser, DisplayNameAuditEntry, LoginNameAuditEntry, SbUserId};
+
+/// How often the job loop checks for pending exports.
+const EXPORT_POLL_INTERVAL: Duration = Duration::from_secs(60);
+/// How long the emailed download link stays valid. Long enough that someone who doesn't check
+/// their email every day still gets to it.
+const DOWNLOAD_LINK_EXPIRY: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// Minimum time between export requests for a single user. Building an export reads every game a
+/// user has played, so this keeps it from being used to generate load.
+const EXPORT_REQUEST_COOLDOWN: chrono::Duration = chrono::Duration::days(7);
+/// A claimed job that hasn't finished after this long is assumed to belong to an instance that
+/// died mid-build, and becomes claimable again.
+const STALE_CLAIM_TIMEOUT: chrono::Duration = chrono::Duration::minutes(30);
+/// How many times a job will be attempted before it's marked as failed.
+const MAX_ATTEMPTS: i32 = 3;
+/// How many expired archives are deleted per query while cleaning them up.
+const ARCHIVE_CLEANUP_BATCH_SIZE: i64 = 100;
+
+#[derive(SimpleObject, Clone, Debug)]
+pub struct DataExportRequest {
+    pub id: Uuid,
+    pub requested_at: DateTime<Utc>,
+    /// When the archive was built and emailed, or null if it is still pending.
+    pub completed_at: Option<DateTime<Utc>>,
+    /// When the job gave up on building the archive, or null if it hasn't.
+    pub failed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+pub struct DataExportQuery;
+
+#[Object]
+impl DataExportQuery {
+    /// The current user's most recent data export request, if they have made one.
+    async fn current_user_data_export(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Option<DataExportRequest>> {
+        let Some(user) = ctx.data::<Option<CurrentUser>>()? else {
+            return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+        };
+
+        Ok(latest_request(ctx.data::<PgPool>()?, user.id).await?)
+    }
+}
+
+#[derive(Default)]
+pub struct DataExportMutation;
+
+#[Object]
+impl DataExportMutation {
+    /// Requests an export of all of the current user's data. The export is built in the
+    /// background and a download link is emailed to the user's address once it is ready.
+    async fn user_request_data_export(&self, ctx: &Context<'_>) -> Result<DataExportRequest> {
+        let Some(user) = ctx.data::<Option<CurrentUser>>()? else {
+            return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+        };
+        if !user.email_verified {
+            // The link goes to this address, so it needs to actually belong to the user
+            return Err(graphql_error(
+                "EMAIL_NOT_VERIFIED",
+                "Email must be verified before requesting a data export",
+            ));
+        }
+
+        let db = ctx.data::<PgPool>()?;
+        if let Some(latest) = latest_request(db, user.id).await? {
+            if latest.completed_at.is_none() && latest.failed_at.is_none() {
+                // Already queued, no reason to make another one
+                return Ok(latest);
+            }
+            if latest.failed_at.is_none()
+                && Utc::now() - latest.requested_at < EXPORT_REQUEST_COOLDOWN
+            {
+                return Err(graphql_error(
+                    "RATE_LIMITED",
+                    format!(
+                        "Data exports can only be requested once every {} days.",
+                        EXPORT_REQUEST_COOLDOWN.num_days()
+                    ),
+                ));
+            }
+        }
+
+        Ok(queue_export(db, user.id).await?)
+    }
+}
+
+/// Queues a new export for `user_id`, or returns the one they already have queued. The unique
+/// pending index makes the insert a no-op if a concurrent request queued one first.
+async fn queue_export(db: &PgPool, user_id: SbUserId) -> eyre::Result<DataExportRequest> {
+    let request = sqlx::query_as!(
+        DataExportRequest,
+        r#"
+            INSERT INTO user_data_exports (user_id)
+            VALUES ($1)
+            ON CONFLICT DO NOTHING
+            RETURNING id, requested_at, completed_at, failed_at
+        "#,
+        user_id as _,
+    )
+    .fetch_optional(db)
+    .await
+    .wrap_err("Failed to create data export request")?;
+
+    match request {
+        Some(request) => Ok(request),
+        None => latest_request(db, user_id)
+            .await?
+            .ok_or_else(|| eyre!("Conflicting data export request disappeared")),
+    }
+}
+
+async fn latest_request(db: &PgPool, user_id: SbUserId) -> eyre::Result<Option<DataExportRequest>> {
+    sqlx::query_as!(
+        DataExportRequest,
+        r#"
+            SELECT id, requested_at, completed_at, failed_at
+            FROM user_data_exports
+            WHERE user_id = $1
+            ORDER BY requested_at DESC
+            LIMIT 1
+        "#,
+        user_id as _,
+    )
+    .fetch_optional(db)
+    .await
+    .wrap_err("Failed to load data export request")
+}
+
+/// Runs forever, building any pending data exports. Meant to be spawned once per server instance.
+pub async fn data_export_loop(db: PgPool, file_store: FileStore, mailer: Mailer) {
+    let mut interval = tokio::time::interval(EXPORT_POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = fail_abandoned_exports(&db).await {
+            error!("Failing abandoned data exports failed: {e:?}");
+        }
+        if let Err(e) = delete_expired_archives(&db, &file_store).await {
+            error!("Deleting expired data export archives failed: {e:?}");
+        }
+        // Drain everything that's pending rather than doing one per tick, exports are rare enough
+        // that this won't starve anything else.
+        loop {
+            match process_next_export(&db, &file_store, &mailer).await {
+                Ok(true) => continue,
+                Ok(false) => break,
+                Err(e) => {
+                    error!("Processing data export failed: {e:?}");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Marks exports as failed if their final attempt was claimed but never finished (because the
+/// instance building it died). These can't be claimed again, so without this they'd stay pending
+/// forever and the user could never request a new export.
+async fn fail_abandoned_exports(db: &PgPool) -> eyre::Result<()> {
+    let result = sqlx::query!(
+        r#"
+            UPDATE user_data_exports
+            SET
+                started_at = NULL,
+                failed_at = NOW(),
+                error = COALESCE(error, 'Final attempt was abandoned')
+            WHERE completed_at IS NULL
+                AND failed_at IS NULL
+                AND attempts >= $2
+                AND started_at < NOW() - make_interval(mins => $1)
+        "#,
+        STALE_CLAIM_TIMEOUT.num_minutes() as i32,
+        MAX_ATTEMPTS,
+    )
+    .execute(db)
+    .await
+    .wrap_err("Failed to mark abandoned data exports as failed")?;
+
+    if result.rows_affected() > 0 {
+        info!(
+            "Marked {} abandoned data export(s) as failed",
+            result.rows_affected()
+        );
+    }
+    Ok(())
+}
+
+/// Deletes the archives of exports whose download links have expired, since nobody can download
+/// them anymore.
+async fn delete_expired_archives(db: &PgPool, file_store: &FileStore) -> eyre::Result<()> {
+    loop {
+        let expired = sqlx::query!(
+            r#"
+                SELECT id, file_path as "file_path!"
+                FROM user_data_exports
+                WHERE file_path IS NOT NULL
+                    AND completed_at < NOW() - make_interval(secs => $1)
+                ORDER BY completed_at
+                LIMIT $2
+            "#,
+            DOWNLOAD_LINK_EXPIRY.as_secs() as f64,
+            ARCHIVE_CLEANUP_BATCH_SIZE,
+        )
+        .fetch_all(db)
+        .await
+        .wrap_err("Failed to load expired data export archives")?;
+
+        let mut deleted = Vec::with_capacity(expired.len());
+        for export in &expired {
+            // Errors are left for the next pass to retry, rather than stopping the rest of the batch
+            match file_store.delete(&export.file_path).await {
+                Ok(()) => deleted.push(export.id),
+                Err(e) => error!(
+                    "Failed to delete data export archive {}: {e:?}",
+                    export.file_path
+                ),
+            }
+        }
+        if !deleted.is_empty() {
+            sqlx::query!(
+                r#"
+                    UPDATE user_data_exports
+                    SET file_path = NULL
+                    WHERE id = ANY($1)
+                "#,
+                &deleted,
+            )
+            .execute(db)
+            .await
+            .wrap_err("Failed to clear deleted data export archive paths")?;
+            info!("Deleted {} expired data export archive(s)", deleted.len());
+        }
+
+        if (expired.len() as i64) < ARCHIVE_CLEANUP_BATCH_SIZE || deleted.is_empty() {
+            return Ok(());
+        }
+    }
+}
+
+/// Claims and processes a single pending export. Returns whether there was one to process.
+async fn process_next_export(
+    db: &PgPool,
+    file_store: &FileStore,
+    mailer: &Mailer,
+) -> eyre::Result<bool> {
+    let claimed = sqlx::query!(
+        r#"
+            UPDATE user_data_exports
+            SET started_at = NOW(), attempts = attempts + 1
+            WHERE id = (
+                SELECT id
+                FROM user_data_exports
+                WHERE completed_at IS NULL
+                    AND failed_at IS NULL
+                    AND attempts < $2
+                    AND (started_at IS NULL OR started_at < NOW() - make_interval(mins => $1))
+                ORDER BY requested_at
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, user_id as "user_id: SbUserId", attempts
+        "#,
+        STALE_CLAIM_TIMEOUT.num_minutes() as i32,
+        MAX_ATTEMPTS,
+    )
+    .fetch_optional(db)
+    .await
+    .wrap_err("Failed to claim data export")?;
+
+    let Some(claimed) = claimed else {
+        return Ok(false);
+    };
+
+    match build_export(db, file_store, claimed.id, claimed.user_id).await {
+        Ok((file_path, email)) => {
+            // The email is queued alongside marking the export completed, so a failure partway
+            // through can't leave the user with either a duplicate email or none at all
+            let mut tx = db.begin().await?;
+            sqlx::query!(
+                r#"
+                    UPDATE user_data_exports
+                    SET completed_at = NOW(), file_path = $2, error = NULL
+                    WHERE id = $1
+                "#,
+                claimed.id,
+                file_path,
+            )
+            .execute(&mut *tx)
+            .await
+            .wrap_err("Failed to mark data export as completed")?;
+            mailer
+                .queue(&mut *tx, email)
+                .await
+                .wrap_err("Failed to queue data export email")?;
+            tx.commit().await?;
+            info!(
+                "Completed data export {} for user {}",
+                claimed.id, claimed.user_id
+            );
+        }
+        Err(e) => {
+            error!(
+                "Building data export {} for user {} failed (attempt {}): {e:?}",
+                claimed.id, claimed.user_id, claimed.attempts
+            );
+            // Give up after enough attempts, otherwise release the claim so it's retried on a
+            // later tick
+            sqlx::query!(
+                r#"
+                    UPDATE user_data_exports
+                    SET
+                        started_at = NULL,
+                        failed_at = CASE WHEN attempts >= $2 THEN NOW() ELSE NULL END,
+                        error = $3
+                    WHERE id = $1
+                "#,
+                claimed.id,
+                MAX_ATTEMPTS,
+                format!("{e:#}"),
+            )
+            .execute(db)
+            .await
+            .wrap_err("Failed to record data export failure")?;
+        }
+    }
+
+    Ok(true)
+}
+
+/// Builds and stores the export archive, returning its path and the email that tells the user
+/// where to download it.
+async fn build_export(
+    db: &PgPool,
+    file_store: &FileStore,
+    export_id: Uuid,
+    user_id: SbUserId,
+) -> eyre::Result<(String, EmailMessage)> {
+    let data = collect_export_data(db, user_id).await?;
+    let username = data.profile.name.clone();
+    let email = data.profile.email.clone();
+    let locale = data.profile.locale.clone();
+
+    let archive = spawn_rayon(move || build_archive(&data))
+        .await
+        .wrap_err("Failed to build export archive")?;
+
+    let file_path = export_file_path(user_id, export_id);
+    file_store
+        .write(&file_path, archive, "application/zip")
+        .await
+        .wrap_err("Failed to store export archive")?;
+    let download_url = file_store
+        .signed_url_with_disposition(
+            &file_path,
+            &format!("shieldbattery-data-{user_id}.zip"),
+            DOWNLOAD_LINK_EXPIRY,
+        )
+        .await
+        .wrap_err("Failed to sign export download URL")?;
+
+    let email = EmailMessage {
+        to: email,
+        locale,
+        template: EmailTemplate::DataExport(DataExportData {
+            username,
+            download_url,
+            expires_in_days: (DOWNLOAD_LINK_EXPIRY.as_secs() / (24 * 60 * 60)) as i64,
+        }),
+    };
+
+    Ok((file_path, email))
+}
+
+/// Where an export is stored. The export ID is random, so the path can't be guessed even though
+/// the user ID is in it.
+fn export_file_path(user_id: SbUserId, export_id: Uuid) -> String {
+    format!("data-exports/{user_id}/{export_id}.zip")
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ExportedProfile {
+    id: SbUserId,
+    name: String,
+    login_name: String,
+    email: String,
+    email_verified: bool,
+    created: NaiveDateTime,
+    signup_ip_address: Option<IpNetwork>,
+    accepted_privacy_version: i32,
+    accepted_terms_version: i32,
+    accepted_use_policy_version: i32,
+    locale: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ExportedGame {
+    game_id: Uuid,
+    start_time: NaiveDateTime,
+    map_id: Uuid,
+    game_length: Option<i32>,
+    selected_race: String,
+    assigned_race: Option<String>,
+    result: Option<String>,
+    apm: Option<i32>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ExportedRatingChange {
+    game_id: Uuid,
+    matchmaking_type: String,
+    change_date: NaiveDateTime,
+    outcome: String,
+    rating: f32,
+    rating_change: f32,
+    points: Option<f32>,
+    points_change: Option<f32>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ExportedReport {
+    id: Uuid,
+    game_id: Uuid,
+    reported_user_id: SbUserId,
+    reason: String,
+    details: Option<String>,
+    created_at: DateTime<Utc>,
+    resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ExportedNewsEdit {
+    id: Uuid,
+    post_id: Uuid,
+    title: String,
+    summary: String,
+    content: String,
+    locale: Option<String>,
+    edited_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ExportedTwitchConnection {
+    twitch_user_id: String,
+    twitch_login: String,
+    twitch_display_name: String,
+    linked_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ExportedTwitchChatBotSettings {
+    enabled: bool,
+    start_template: Option<String>,
+    result_template: Option<String>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ExportedStreamSession {
+    provider: StreamingProvider,
+    channel_id: String,
+    started_at: DateTime<Utc>,
+    ended_at: Option<DateTime<Utc>>,
+    title: String,
+    category: String,
+    peak_viewers: i32,
+    viewer_samples: i32,
+    viewer_sum: i64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ExportedYoutubeConnection {
+    channel_id: String,
+    channel_login: String,
+    channel_display_name: String,
+    linked_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ExportedOAuthIdentity {
+    provider: OAuthProvider,
+    provider_user_id: String,
+    provider_username: String,
+    email: Option<String>,
+    linked_at: DateTime<Utc>,
+    last_login_at: Option<DateTime<Utc>>,
+}
+
+/// Everything that goes into an export, one field per file in the archive.
+#[derive(Debug)]
+struct ExportData {
+    profile: ExportedProfile,
+    login_name_history: Vec<LoginNameAuditEntry>,
+    display_name_history: Vec<DisplayNameAuditEntry>,
+    games: Vec<ExportedGame>,
+    rating_history: Vec<ExportedRatingChange>,
+    reports_filed: Vec<ExportedReport>,
+    news_edits: Vec<ExportedNewsEdit>,
+    twitch_connection: Option<ExportedTwitchConnection>,
+    twitch_chat_bot_settings: Option<ExportedTwitchChatBotSettings>,
+    youtube_connection: Option<ExportedYoutubeConnection>,
+    stream_sessions: Vec<ExportedStreamSession>,
+    oauth_identities: Vec<ExportedOAuthIdentity>,
+}
+
+async fn collect_export_data(db: &PgPool, user_id: SbUserId) -> eyre::Result<ExportData> {
+    let profile = sqlx::query_as!(
+        ExportedProfile,
+        r#"
+            SELECT id as "id: SbUserId", name::TEXT as "name!",
+                login_name::TEXT as "login_name!", email, email_verified, created,
+                signup_ip_address, accepted_privacy_version, accepted_terms_version,
+                accepted_use_policy_version, locale
+            FROM users
+            WHERE id = $1
+        "#,
+        user_id as _,
+    )
+    .fetch_one(db)
+    .await
+    .wrap_err("Failed to load user profile")?;
+
+    let login_name_history = sqlx::query_as!(
+        LoginNameAuditEntry,
+        r#"
+            SELECT
+                id,
+                user_id as "user_id: SbUserId",
+                old_login_name,
+                new_login_name,
+                changed_at,
+                changed_by_user_id as "changed_by_user_id: _",
+                change_reason,
+                ip_address,
+                user_agent,
+                session_id
+            FROM user_login_name_audit
+            WHERE user_id = $1
+            ORDER BY changed_at
+        "#,
+        user_id as _,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load login name history")?;
+
+    let display_name_history = sqlx::query_as!(
+        DisplayNameAuditEntry,
+        r#"
+            SELECT
+                id,
+                user_id as "user_id: SbUserId",
+                old_name,
+                new_name,
+                changed_at,
+                changed_by_user_id as "changed_by_user_id: _",
+                change_reason,
+                ip_address,
+                user_agent,
+                session_id,
+                used_token
+            FROM user_display_name_audit
+            WHERE user_id = $1
+            ORDER BY changed_at
+        "#,
+        user_id as _,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load display name history")?;
+
+    let games = sqlx::query_as!(
+        ExportedGame,
+        r#"
+            SELECT
+                gu.game_id,
+                gu.start_time,
+                g.map_id,
+                g.game_length,
+                gu.selected_race::TEXT as "selected_race!",
+                gu.assigned_race::TEXT as "assigned_race?",
+                gu.result::TEXT as "result?",
+                gu.apm
+            FROM games_users gu
+            JOIN games g ON g.id = gu.game_id
+            WHERE gu.user_id = $1
+            ORDER BY gu.start_time
+        "#,
+        user_id as _,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load games")?;
+
+    let rating_history = sqlx::query_as!(
+        ExportedRatingChange,
+        r#"
+            SELECT
+                game_id,
+                matchmaking_type::TEXT as "matchmaking_type!",
+                change_date,
+                outcome::TEXT as "outcome!",
+                rating,
+                rating_change,
+                points,
+                points_change
+            FROM matchmaking_rating_changes
+            WHERE user_id = $1
+            ORDER BY change_date
+        "#,
+        user_id as _,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load rating history")?;
+
+    // Resolution details are moderator notes about the report rather than the user's own data, so
+    // only the fact that it was resolved is included.
+    let reports_filed = sqlx::query_as!(
+        ExportedReport,
+        r#"
+            SELECT id, game_id, reported_user_id as "reported_user_id: SbUserId", reason, details,
+                created_at, resolved_at
+            FROM game_reports
+            WHERE reporter_id = $1
+            ORDER BY created_at
+        "#,
+        user_id as _,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load filed reports")?;
+
+    let news_edits = sqlx::query_as!(
+        ExportedNewsEdit,
+        r#"
+            SELECT id, post_id, title, summary, content, locale, edited_at
+            FROM news_post_edits
+            WHERE editor_id = $1
+            ORDER BY edited_at
+        "#,
+        user_id as _,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load news edits")?;
+
+    let twitch_connection = sqlx::query_as!(
+        ExportedTwitchConnection,
+        r#"
+            SELECT twitch_user_id, twitch_login, twitch_display_name, linked_at
+            FROM twitch_connections
+            WHERE user_id = $1
+        "#,
+        user_id as _,
+    )
+    .fetch_optional(db)
+    .await
+    .wrap_err("Failed to load Twitch connection")?;
+
+    let twitch_chat_bot_settings = sqlx::query_as!(
+        ExportedTwitchChatBotSettings,
+        r#"
+            SELECT enabled, start_template, result_template, updated_at
+            FROM twitch_chat_bot_settings
+            WHERE user_id = $1
+        "#,
+        user_id as _,
+    )
+    .fetch_optional(db)
+    .await
+    .wrap_err("Failed to load Twitch chat bot settings")?;
+
+    let youtube_connection = sqlx::query_as!(
+        ExportedYoutubeConnection,
+        r#"
+            SELECT channel_id, channel_login, channel_display_name, linked_at
+            FROM youtube_connections
+            WHERE user_id = $1
+        "#,
+        user_id as _,
+    )
+    .fetch_optional(db)
+    .await
+    .wrap_err("Failed to load YouTube connection")?;
+
+    let stream_sessions = sqlx::query_as!(
+        ExportedStreamSession,
+        r#"
+            SELECT provider as "provider: StreamingProvider", channel_id, started_at, ended_at,
+                title, category, peak_viewers, viewer_samples, viewer_sum
+            FROM stream_sessions
+            WHERE user_id = $1
+            ORDER BY started_at
+        "#,
+        user_id as _,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load stream sessions")?;
+
+    let oauth_identities = sqlx::query_as!(
+        ExportedOAuthIdentity,
+        r#"
+            SELECT provider as "provider: OAuthProvider", provider_user_id, provider_username,
+                email, linked_at, last_login_at
+            FROM user_oauth_identities
+            WHERE user_id = $1
+            ORDER BY provider
+        "#,
+        user_id as _,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load linked OAuth identities")?;
+
+    Ok(ExportData {
+        profile,
+        login_name_history,
+        display_name_history,
+        games,
+        rating_history,
+        reports_filed,
+        news_edits,
+        twitch_connection,
+        twitch_chat_bot_settings,
+        youtube_connection,
+        stream_sessions,
+        oauth_identities,
+    })
+}
+
+/// Builds the zip archive for an export, with one pretty-printed JSON file per kind of data.
+fn build_archive(data: &ExportData) -> eyre::Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+    add_json_file(&mut zip, "profile.json", &data.profile)?;
+    add_json_file(
+        &mut zip,
+        "login_name_history.json",
+        &data.login_name_history,
+    )?;
+    add_json_file(
+        &mut zip,
+        "display_name_history.json",
+        &data.display_name_history,
+    )?;
+    add_json_file(&mut zip, "games.json", &data.games)?;
+    add_json_file(&mut zip, "rating_history.json", &data.rating_history)?;
+    add_json_file(&mut zip, "reports_filed.json", &data.reports_filed)?;
+    add_json_file(&mut zip, "news_edits.json", &data.news_edits)?;
+    add_json_file(&mut zip, "twitch_connection.json", &data.twitch_connection)?;
+    add_json_file(
+        &mut zip,
+        "twitch_chat_bot_settings.json",
+        &data.twitch_chat_bot_settings,
+    )?;
+    add_json_file(
+        &mut zip,
+        "youtube_connection.json",
+        &data.youtube_connection,
+    )?;
+    add_json_file(&mut zip, "stream_sessions.json", &data.stream_sessions)?;
+    add_json_file(&mut zip, "oauth_identities.json", &data.oauth_identities)?;
+
+    let cursor = zip.finish().wrap_err("Failed to finish archive")?;
+    Ok(cursor.into_inner())
+}
+
+fn add_json_file<W: Write + Seek, T: Serialize>(
+    zip: &mut ZipWriter<W>,
+    name: &str,
+    value: &T,
+) -> eyre::Result<()> {
+    let json =
+        serde_json::to_vec_pretty(value).wrap_err_with(|| format!("Failed to serialize {name}"))?;
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file(name, options)
+        .wrap_err_with(|| format!("Failed to start {name}"))?;
+    zip.write_all(&json)
+        .wrap_err_with(|| format!("Failed to write {name}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read as _;
+
+    use super::*;
+
+    fn empty_export() -> ExportData {
+        ExportData {
+            profile: ExportedProfile {
+                id: SbUserId(7),
+                name: "Pachi".into(),
+                login_name: "pachi".into(),
+                email: "pachi@example.org".into(),
+                email_verified: true,
+                created: DateTime::from_timestamp(1_700_000_000, 0)
+                    .unwrap()
+                    .naive_utc(),
+                signup_ip_address: None,
+                accepted_privacy_version: 1,
+                accepted_terms_version: 1,
+                accepted_use_policy_version: 1,
+                locale: Some("en".into()),
+            },
+            login_name_history: Vec::new(),
+            display_name_history: Vec::new(),
+            games: Vec::new(),
+            rating_history: Vec::new(),
+            reports_filed: Vec::new(),
+            news_edits: Vec::new(),
+            twitch_connection: None,
+            twitch_chat_bot_settings: None,
+            youtube_connection: None,
+            stream_sessions: Vec::new(),
+            oauth_identities: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn archive_contains_every_part() {
+        let archive = build_archive(&empty_export()).unwrap();
+        let mut zip = zip::ZipArchive::new(Cursor::new(archive)).unwrap();
+
+        let mut names = zip.file_names().map(String::from).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "display_name_history.json",
+                "games.json",
+                "login_name_history.json",
+                "news_edits.json",
+                "oauth_identities.json",
+                "profile.json",
+                "rating_history.json",
+                "reports_filed.json",
+                "stream_sessions.json",
+                "twitch_chat_bot_settings.json",
+                "twitch_connection.json",
+                "youtube_connection.json",
+            ]
+        );
+
+        let mut profile = String::new();
+        zip.by_name("profile.json")
+            .unwrap()
+            .read_to_string(&mut profile)
+            .unwrap();
+        let profile: serde_json::Value = serde_json::from_str(&profile).unwrap();
+        assert_eq!(profile["loginName"], "pachi");
+        assert_eq!(profile["email"], "pachi@example.org");
+
+        let mut twitch = String::new();
+        zip.by_name("twitch_connection.json")
+            .unwrap()
+            .read_to_string(&mut twitch)
+            .unwrap();
+        assert_eq!(twitch, "null");
+    }
+
+    #[test]
+    fn export_paths_are_scoped_per_user() {
+        let id = Uuid::nil();
+        assert_eq!(
+            export_file_path(SbUserId(42), id),
+            "data-exports/42/00000000-0000-0000-0000-000000000000.zip"
+        );
+    }
+
+    async fn insert_export(
+        db: &PgPool,
+        user_id: SbUserId,
+        attempts: i32,
+        started_ago: Option<chrono::Duration>,
+    ) -> Uuid {
+        sqlx::query_scalar(
+            r#"
+                INSERT INTO user_data_exports (user_id, attempts, started_at)
+                VALUES ($1, $2, NOW() - $3::interval)
+                RETURNING id
+            "#,
+        )
+        .bind(user_id.0)
+        .bind(attempts)
+        .bind(started_ago)
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn fails_abandoned_final_attempts(db: PgPool) {
+        // Separate users, since each user can only have one pending export
+        let stale = Some(STALE_CLAIM_TIMEOUT + chrono::Duration::minutes(1));
+        let pachi = crate::test_utils::create_user(&db, "pachi").await;
+        let abandoned = insert_export(&db, pachi, MAX_ATTEMPTS, stale).await;
+        let flash = crate::test_utils::create_user(&db, "flash").await;
+        let retryable = insert_export(&db, flash, 1, stale).await;
+        let jaedong = crate::test_utils::create_user(&db, "jaedong").await;
+        let in_progress = insert_export(
+            &db,
+            jaedong,
+            MAX_ATTEMPTS,
+            Some(chrono::Duration::minutes(1)),
+        )
+        .await;
+
+        fail_abandoned_exports(&db).await.unwrap();
+
+        let failed: Vec<Uuid> = sqlx::query_scalar(
+            "SELECT id FROM user_data_exports WHERE failed_at IS NOT NULL AND started_at IS NULL",
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        assert_eq!(failed, vec![abandoned]);
+        assert!(!failed.contains(&retryable));
+        assert!(!failed.contains(&in_progress));
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn queueing_returns_the_pending_export(db: PgPool) {
+        let user_id = crate::test_utils::create_user(&db, "pachi").await;
+
+        let first = queue_export(&db, user_id).await.unwrap();
+        let second = queue_export(&db, user_id).await.unwrap();
+        assert_eq!(first.id, second.id);
+
+        let count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM user_data_exports WHERE user_id = $1")
+                .bind(user_id.0)
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn deletes_expired_archives(db: PgPool) {
+        let user_id = crate::test_utils::create_user(&db, "pachi").await;
+        let dir = tempfile::tempdir().unwrap();
+        let file_store = crate::test_utils::local_file_store(dir.path());
+
+        let mut exports = Vec::new();
+        for completed_days_ago in [8, 1] {
+            let id = Uuid::new_v4();
+            let file_path = export_file_path(user_id, id);
+            file_store
+                .write(&file_path, b"zip".to_vec(), "application/zip")
+                .await
+                .unwrap();
+            sqlx::query(
+                r#"
+                    INSERT INTO user_data_exports (id, user_id, completed_at, file_path)
+                    VALUES ($1, $2, NOW() - make_interval(days => $3), $4)
+                "#,
+            )
+            .bind(id)
+            .bind(user_id.0)
+            .bind(completed_days_ago)
+            .bind(&file_path)
+            .execute(&db)
+            .await
+            .unwrap();
+            exports.push((id, file_path));
+        }
+
+        delete_expired_archives(&db, &file_store).await.unwrap();
+
+        let file_path = |id: Uuid| {
+            let db = db.clone();
+            async move {
+                sqlx::query_scalar::<_, Option<String>>(
+                    "SELECT file_path FROM user_data_exports WHERE id = $1",
+                )
+                .bind(id)
+                .fetch_one(&db)
+                .await
+                .unwrap()
+            }
+        };
+        let (expired_id, expired_path) = &exports[0];
+        assert_eq!(file_path(*expired_id).await, None);
+        assert!(!dir.path().join(expired_path).exists());
+
+        let (valid_id, valid_path) = &exports[1];
+        assert_eq!(file_path(*valid_id).await.as_ref(), Some(valid_path));
+        assert!(dir.path().join(valid_path).exists());
+    }
+}
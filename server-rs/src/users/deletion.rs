@@ -0,0 +1,524 @@
+//! User-initiated account deletion.
+//!
+//! Deleting an account doesn't remove the `users` row: games, ratings and reports reference it, and
+//! removing it would punch holes in other players' match histories. Instead, once the cooling-off
+//! period has passed the account is anonymized in place. Its PII is scrubbed, its names are replaced
+//! with a tombstone derived from its ID, its sessions are revoked and its Twitch connection and
+//! linked sign-in identities are removed. Everything that refers to the `SbUserId` keeps working,
+//! it just points at an account nobody can log into and that no longer says anything about who
+//! owned it.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_graphql::{Context, Object, Result, SimpleObject};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{self, WrapErr};
+use secrecy::SecretString;
+use sqlx::{PgConnection, PgPool};
+use tracing::{error, info};
+
+use crate::graphql::errors::graphql_error;
+use crate::redis::RedisPool;
+use crate::sessions::revoke_user_sessions;
+use crate::streaming::StreamingProvider;
+use crate::twitch::{TwitchClient, delete_connection, set_stream_offline};
+use crate::users::auth::{get_stored_credentials, validate_credentials};
+use crate::users::{CurrentUser, CurrentUserRepo, SbUserId};
+
+/// How long after requesting deletion an account is actually anonymized. Gives the user a window
+/// to change their mind (and us a window to notice if the request wasn't really theirs).
+const DELETION_COOLING_OFF: chrono::Duration = chrono::Duration::days(14);
+/// How often the job loop checks for deletions whose cooling-off period has elapsed.
+const DELETION_POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(SimpleObject, Clone, Debug)]
+pub struct AccountDeletionRequest {
+    pub requested_at: DateTime<Utc>,
+    /// When the account will be anonymized, unless the request is cancelled before then.
+    pub scheduled_for: DateTime<Utc>,
+}
+
+#[derive(Default)]
+pub struct AccountDeletionQuery;
+
+#[Object]
+impl AccountDeletionQuery {
+    /// The current user's pending account deletion, if they have requested one.
+    async fn current_user_account_deletion(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Option<AccountDeletionRequest>> {
+        let Some(user) = ctx.data::<Option<CurrentUser>>()? else {
+            return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+        };
+
+        Ok(pending_request(ctx.data::<PgPool>()?, user.id).await?)
+    }
+}
+
+#[derive(Default)]
+pub struct AccountDeletionMutation;
+
+#[Object]
+impl AccountDeletionMutation {
+    /// Schedules the current user's account for deletion. After a cooling-off period the account
+    /// is anonymized: its email, login name and IP history are erased and its display name is
+    /// replaced, but its games and ratings remain so other players' histories stay intact.
+    async fn user_request_account_deletion(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(secret)] current_password: String,
+    ) -> Result<AccountDeletionRequest> {
+        let Some(user) = ctx.data::<Option<CurrentUser>>()? else {
+            return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+        };
+
+        let db = ctx.data::<PgPool>()?;
+        let current_password: SecretString = current_password.into();
+        let stored_credentials = get_stored_credentials(user.id, db)
+            .await
+            .wrap_err("Failed to get stored credentials")?;
+        let credentials_valid = validate_credentials(current_password, stored_credentials)
+            .await
+            .wrap_err("Failed to validate credentials")?;
+        if !credentials_valid {
+            return Err(graphql_error("INVALID_PASSWORD", "Invalid password"));
+        }
+
+        // Re-requesting leaves an existing schedule alone rather than pushing it back
+        let request = sqlx::query_as!(
+            AccountDeletionRequest,
+            r#"
+                INSERT INTO user_deletion_requests (user_id, scheduled_for)
+                VALUES ($1, NOW() + make_interval(days => $2))
+                ON CONFLICT (user_id) DO UPDATE SET user_id = EXCLUDED.user_id
+                RETURNING requested_at, scheduled_for
+            "#,
+            user.id as _,
+            DELETION_COOLING_OFF.num_days() as i32,
+        )
+        .fetch_one(db)
+        .await
+        .wrap_err("Failed to create account deletion request")?;
+
+        Ok(request)
+    }
+
+    /// Cancels the current user's pending account deletion. Returns whether there was a pending
+    /// deletion to cancel.
+    async fn user_cancel_account_deletion(&self, ctx: &Context<'_>) -> Result<bool> {
+        let Some(user) = ctx.data::<Option<CurrentUser>>()? else {
+            return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+        };
+
+        // Once the account has been anonymized there's nothing to cancel anymore, even if the
+        // rest of the deletion is still being retried (and the user's session hasn't been revoked)
+        let result = sqlx::query!(
+            r#"
+                DELETE FROM user_deletion_requests r
+                WHERE user_id = $1
+                    AND completed_at IS NULL
+                    AND NOT EXISTS (
+                        SELECT 1 FROM users u WHERE u.id = r.user_id AND u.deleted_at IS NOT NULL
+                    )
+            "#,
+            user.id as _,
+        )
+        .execute(ctx.data::<PgPool>()?)
+        .await
+        .wrap_err("Failed to cancel account deletion")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+async fn pending_request(
+    db: &PgPool,
+    user_id: SbUserId,
+) -> eyre::Result<Option<AccountDeletionRequest>> {
+    sqlx::query_as!(
+        AccountDeletionRequest,
+        r#"
+            SELECT requested_at, scheduled_for
+            FROM user_deletion_requests
+            WHERE user_id = $1 AND completed_at IS NULL
+        "#,
+        user_id as _,
+    )
+    .fetch_optional(db)
+    .await
+    .wrap_err("Failed to load account deletion request")
+}
+
+/// The display name a deleted account is given. Contains spaces, which the name validators
+/// reject, so it can never collide with (or be claimed as) a real user's name.
+fn tombstone_name(user_id: SbUserId) -> String {
+    format!("Deleted User {user_id}")
+}
+
+/// The login name a deleted account is given. Like [`tombstone_name`], this can't be entered on
+/// the login form, which (along with the credentials being removed) is what keeps the account from
+/// ever being logged into again.
+fn tombstone_login_name(user_id: SbUserId) -> String {
+    format!("deleted user {user_id}")
+}
+
+/// Runs forever, anonymizing accounts whose deletion cooling-off period has elapsed. Meant to be
+/// spawned once per server instance.
+pub async fn account_deletion_loop(
+    db: PgPool,
+    redis: RedisPool,
+    current_user_repo: CurrentUserRepo,
+    twitch_client: Option<Arc<TwitchClient>>,
+) {
+    let mut interval = tokio::time::interval(DELETION_POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) =
+            process_due_deletions(&db, &redis, &current_user_repo, twitch_client.as_deref()).await
+        {
+            error!("Processing account deletions failed: {e:?}");
+        }
+    }
+}
+
+async fn process_due_deletions(
+    db: &PgPool,
+    redis: &RedisPool,
+    current_user_repo: &CurrentUserRepo,
+    twitch_client: Option<&TwitchClient>,
+) -> eyre::Result<()> {
+    let due = sqlx::query!(
+        r#"
+            SELECT user_id as "user_id: SbUserId"
+            FROM user_deletion_requests
+            WHERE completed_at IS NULL AND scheduled_for <= NOW()
+            ORDER BY scheduled_for
+        "#,
+    )
+    .fetch_all(db)
+    .await
+    .wrap_err("Failed to load due account deletions")?;
+
+    for row in due {
+        // One failing account shouldn't hold up the rest, it'll be retried on the next tick
+        if let Err(e) =
+            anonymize_user(db, redis, current_user_repo, twitch_client, row.user_id).await
+        {
+            error!("Failed to delete account {}: {e:?}", row.user_id);
+        } else {
+            info!("Deleted account {}", row.user_id);
+        }
+    }
+
+    Ok(())
+}
+
+/// Scrubs a user's PII and cuts off access to their account. Safe to re-run for a user that was
+/// partially processed: every step is idempotent, and the request is only marked completed once
+/// every step has succeeded, so anything that fails is retried on the next tick.
+async fn anonymize_user(
+    db: &PgPool,
+    redis: &RedisPool,
+    current_user_repo: &CurrentUserRepo,
+    twitch_client: Option<&TwitchClient>,
+    user_id: SbUserId,
+) -> eyre::Result<()> {
+    let mut tx = db.begin().await.wrap_err("Failed to start transaction")?;
+
+    // Lock the request row so two instances don't process the same user at once, and re-check it
+    // in case it was cancelled after we listed it
+    let still_due = sqlx::query!(
+        r#"
+            SELECT user_id
+            FROM user_deletion_requests
+            WHERE user_id = $1 AND completed_at IS NULL AND scheduled_for <= NOW()
+            FOR UPDATE SKIP LOCKED
+        "#,
+        user_id as _,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .wrap_err("Failed to lock account deletion request")?;
+    if still_due.is_none() {
+        return Ok(());
+    }
+
+    let twitch_subscription_ids = scrub_account(&mut tx, user_id).await?;
+    tx.commit().await.wrap_err("Failed to commit transaction")?;
+
+    // Best-effort, Twitch subscription reconciliation cleans up any of these that fail (or that
+    // we no longer know about if this is a retry)
+    if let (Some(client), Some(ids)) = (twitch_client, twitch_subscription_ids) {
+        client.delete_subscriptions(&ids).await;
+    }
+
+    // The rest lives outside the DB. The account can't be logged into anymore (its credentials are
+    // gone), but existing sessions keep working until they're revoked, so these have to succeed
+    // before the deletion counts as done
+    let mut conn = redis
+        .get()
+        .await
+        .wrap_err("Failed to get Redis connection to revoke sessions")?;
+    revoke_user_sessions(&mut conn, user_id)
+        .await
+        .wrap_err("Failed to revoke sessions")?;
+    current_user_repo
+        .invalidate_cached_user(user_id)
+        .await
+        .wrap_err("Failed to invalidate cached user")?;
+    for provider in [StreamingProvider::Twitch, StreamingProvider::Youtube] {
+        set_stream_offline(redis, user_id, provider)
+            .await
+            .wrap_err("Failed to clear live stream")?;
+    }
+
+    sqlx::query!(
+        r#"
+            UPDATE user_deletion_requests
+            SET completed_at = NOW()
+            WHERE user_id = $1
+        "#,
+        user_id as _,
+    )
+    .execute(db)
+    .await
+    .wrap_err("Failed to mark account deletion as completed")?;
+
+    Ok(())
+}
+
+/// Removes the account's PII and everything that could be used to access it from the DB. Returns
+/// the EventSub subscription ids of the Twitch connection it removed, if there was one.
+async fn scrub_account(
+    conn: &mut PgConnection,
+    user_id: SbUserId,
+) -> eyre::Result<Option<Vec<String>>> {
+    sqlx::query!(
+        r#"
+            UPDATE users
+            SET
+                name = $2,
+                login_name = $3,
+                email = '',
+                email_verified = false,
+                signup_ip_address = NULL,
+                locale = NULL,
+                avatar_path = NULL,
+                deleted_at = NOW()
+            WHERE id = $1
+        "#,
+        user_id as _,
+        tombstone_name(user_id),
+        tombstone_login_name(user_id),
+    )
+    .execute(&mut *conn)
+    .await
+    .wrap_err("Failed to anonymize user")?;
+
+    // This has to come after the update above: the name audit triggers fire on it, and the rows
+    // they write contain the names being removed
+    sqlx::query!(
+        r#"
+            UPDATE user_login_name_audit
+            SET
+                old_login_name = $2,
+                new_login_name = $2,
+                ip_address = NULL,
+                user_agent = NULL,
+                session_id = NULL
+            WHERE user_id = $1
+        "#,
+        user_id as _,
+        tombstone_login_name(user_id),
+    )
+    .execute(&mut *conn)
+    .await
+    .wrap_err("Failed to scrub login name audit history")?;
+    sqlx::query!(
+        r#"
+            UPDATE user_display_name_audit
+            SET
+                old_name = $2,
+                new_name = $2,
+                ip_address = NULL,
+                user_agent = NULL,
+                session_id = NULL
+            WHERE user_id = $1
+        "#,
+        user_id as _,
+        tombstone_name(user_id),
+    )
+    .execute(&mut *conn)
+    .await
+    .wrap_err("Failed to scrub display name audit history")?;
+
+    // Credentials and anything keyed on the old email or IPs go away entirely
+    sqlx::query!("DELETE FROM users_private WHERE user_id = $1", user_id as _)
+        .execute(&mut *conn)
+        .await
+        .wrap_err("Failed to delete credentials")?;
+    sqlx::query!("DELETE FROM user_ips WHERE user_id = $1", user_id as _)
+        .execute(&mut *conn)
+        .await
+        .wrap_err("Failed to delete IP history")?;
+    sqlx::query!(
+        "DELETE FROM email_verifications WHERE user_id = $1",
+        user_id as _
+    )
+    .execute(&mut *conn)
+    .await
+    .wrap_err("Failed to delete email verifications")?;
+    sqlx::query!(
+        "DELETE FROM password_resets WHERE user_id = $1",
+        user_id as _
+    )
+    .execute(&mut *conn)
+    .await
+    .wrap_err("Failed to delete password resets")?;
+    sqlx::query!(
+        "DELETE FROM user_api_tokens WHERE user_id = $1",
+        user_id as _
+    )
+    .execute(&mut *conn)
+    .await
+    .wrap_err("Failed to delete API tokens")?;
+    sqlx::query!(
+        "DELETE FROM user_oauth_identities WHERE user_id = $1",
+        user_id as _
+    )
+    .execute(&mut *conn)
+    .await
+    .wrap_err("Failed to delete linked OAuth identities")?;
+    sqlx::query!(
+        "DELETE FROM twitch_chat_bot_settings WHERE user_id = $1",
+        user_id as _
+    )
+    .execute(&mut *conn)
+    .await
+    .wrap_err("Failed to delete Twitch chat bot settings")?;
+    sqlx::query!(
+        "DELETE FROM stream_sessions WHERE user_id = $1",
+        user_id as _
+    )
+    .execute(&mut *conn)
+    .await
+    .wrap_err("Failed to delete stream sessions")?;
+    sqlx::query!(
+        "DELETE FROM user_data_exports WHERE user_id = $1",
+        user_id as _
+    )
+    .execute(&mut *conn)
+    .await
+    .wrap_err("Failed to delete data export requests")?;
+    sqlx::query!(
+        "DELETE FROM youtube_connections WHERE user_id = $1",
+        user_id as _
+    )
+    .execute(&mut *conn)
+    .await
+    .wrap_err("Failed to delete YouTube connection")?;
+
+    delete_connection(&mut *conn, user_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tombstones_are_unique_per_user_and_fit_name_columns() {
+        let max_id = SbUserId(i32::MAX);
+        assert!(tombstone_name(max_id).len() <= 32);
+        assert!(tombstone_login_name(max_id).len() <= 32);
+        assert_ne!(tombstone_name(SbUserId(1)), tombstone_name(SbUserId(2)));
+        assert_ne!(
+            tombstone_login_name(SbUserId(1)),
+            tombstone_login_name(SbUserId(2))
+        );
+    }
+
+    #[test]
+    fn tombstones_cannot_be_registered() {
+        // Mirrors the validators on `UpdateCurrentUserChanges`
+        let name_regex = regex::Regex::new(r"^[A-Za-z0-9`~!$^&*()\[\]\-_+=.{}]+$").unwrap();
+        assert!(!name_regex.is_match(&tombstone_name(SbUserId(1234))));
+        assert!(!name_regex.is_match(&tombstone_login_name(SbUserId(1234))));
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn scrubs_account_and_connections_together(db: PgPool) {
+        let user_id = crate::test_utils::create_user(&db, "pachi").await;
+        let other_id = crate::test_utils::create_user(&db, "other").await;
+        for id in [user_id, other_id] {
+            sqlx::query("INSERT INTO users_private (user_id, password) VALUES ($1, 'hash')")
+                .bind(id.0)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+        sqlx::query(
+            r#"
+                INSERT INTO twitch_connections
+                    (user_id, twitch_user_id, twitch_login, twitch_display_name,
+                        eventsub_subscription_ids)
+                VALUES ($1, '1234', 'pachi', 'Pachi', '{sub-1,sub-2}')
+            "#,
+        )
+        .bind(user_id.0)
+        .execute(&db)
+        .await
+        .unwrap();
+        sqlx::query(
+            r#"
+                INSERT INTO youtube_connections
+                    (user_id, channel_id, channel_login, channel_display_name)
+                VALUES ($1, 'UC1234', '@pachi', 'Pachi')
+            "#,
+        )
+        .bind(user_id.0)
+        .execute(&db)
+        .await
+        .unwrap();
+
+        let mut tx = db.begin().await.unwrap();
+        let subscription_ids = scrub_account(&mut tx, user_id).await.unwrap();
+        tx.commit().await.unwrap();
+        assert_eq!(
+            subscription_ids,
+            Some(vec!["sub-1".to_owned(), "sub-2".to_owned()])
+        );
+
+        let (name, login_name, email): (String, String, String) =
+            sqlx::query_as("SELECT name::text, login_name::text, email FROM users WHERE id = $1")
+                .bind(user_id.0)
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!(name, tombstone_name(user_id));
+        assert_eq!(login_name, tombstone_login_name(user_id));
+        assert_eq!(email, "");
+
+        let remaining: (i64, i64, i64, i64) = sqlx::query_as(
+            r#"
+                SELECT
+                    (SELECT COUNT(*) FROM users_private WHERE user_id = $1),
+                    (SELECT COUNT(*) FROM twitch_connections WHERE user_id = $1),
+                    (SELECT COUNT(*) FROM youtube_connections WHERE user_id = $1),
+                    (SELECT COUNT(*) FROM users_private WHERE user_id = $2)
+            "#,
+        )
+        .bind(user_id.0)
+        .bind(other_id.0)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        assert_eq!(remaining, (0, 0, 0, 1));
+
+        // Re-running for a partially processed deletion is fine
+        let mut tx = db.begin().await.unwrap();
+        assert_eq!(scrub_account(&mut tx, user_id).await.unwrap(), None);
+        tx.commit().await.unwrap();
+    }
+}
@@ -1,5 +1,4 @@
 use std::collections::HashMap;
-use std::sync::Arc;
 use std::time::Duration;
 
 use async_graphql::dataloader::{DataLoader, Loader};
@@ -30,24 +29,32 @@ use tracing::{error, warn};
 use typeshare::typeshare;
 
 use crate::email::{
-    EmailChangeData, EmailVerificationData, LoginNameChangeData, MailgunClient, MailgunMessage,
-    MailgunTemplate, PasswordChangeData,
+    EmailChangeData, EmailMessage, EmailTemplate, EmailVerificationData, LoginNameChangeData,
+    Mailer, PasswordChangeData,
 };
 use crate::file_store::FileStore;
 use crate::graphql::errors::graphql_error;
+use crate::graphql::limits::page_complexity;
 use crate::graphql::schema_builder::SchemaBuilderModule;
 use crate::random_code::gen_random_code;
 use crate::redis::RedisPool;
 use crate::sessions::SbSession;
 use crate::state::AppState;
+use crate::stream_analytics::{UserStreamAnalytics, load_user_stream_analytics};
 use crate::telemetry::spawn_with_tracing;
 use crate::twitch::{LiveStream, LiveStreamLoader, TwitchChannel, TwitchChannelLoader};
 use crate::users::auth::{get_stored_credentials, hash_password, validate_credentials};
 use crate::users::permissions::{PermissionsLoader, RequiredPermission, SbPermissions};
+use crate::users::signup_campaigns::{SignupCodeCampaign, load_campaign};
 
+pub mod api_tokens;
 mod auth;
+pub mod ban_evasion;
+pub mod data_export;
+pub mod deletion;
 pub mod names;
 pub mod permissions;
+pub mod signup_campaigns;
 mod user_id;
 
 pub use user_id::SbUserId;
@@ -123,6 +130,16 @@ impl SbUser {
             .load_one(self.id)
             .await
     }
+
+    /// This user's streaming stats and recent stream sessions over the last `days` days (1-365).
+    #[graphql(guard = RequiredPermission::ManageLiveStreams.or(IsCurrentUser::guard(self.id)))]
+    async fn stream_analytics(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 30, validator(minimum = 1, maximum = 365))] days: i32,
+    ) -> Result<UserStreamAnalytics> {
+        Ok(load_user_stream_analytics(ctx.data::<PgPool>()?, self.id, days).await?)
+    }
 }
 
 impl From<CurrentUser> for SbUser {
@@ -212,6 +229,18 @@ impl CurrentUser {
     }
 }
 
+/// The user a non-GraphQL request is acting on behalf of. API tokens only grant access to the
+/// GraphQL fields their scopes allow (which the GraphQL handler enforces), so requests made with one
+/// don't get a user here.
+fn http_session_user_id(session: &SbSession) -> Option<SbUserId> {
+    match session {
+        SbSession::Authenticated(session) => Some(session.user_id),
+        SbSession::ApiToken(_) | SbSession::Anonymous => None,
+    }
+}
+
+/// Extracts the logged in user for HTTP routes. Requests authenticated with an API token are
+/// rejected, see [`http_session_user_id`].
 impl FromRequestParts<AppState> for CurrentUser {
     type Rejection = (StatusCode, &'static str);
 
@@ -223,13 +252,13 @@ impl FromRequestParts<AppState> for CurrentUser {
             .extract()
             .await
             .map_err(|_| (StatusCode::UNAUTHORIZED, "Unauthorized"))?;
-        let SbSession::Authenticated(session) = session else {
+        let Some(user_id) = http_session_user_id(&session) else {
             return Err((StatusCode::UNAUTHORIZED, "Unauthorized"));
         };
 
         state
             .current_user_repo
-            .load_cached_user(session.user_id, Default::default())
+            .load_cached_user(user_id, Default::default())
             .await
             .map_err(|e| {
                 error!("Failed to load cached user: {e:?}");
@@ -238,6 +267,8 @@ impl FromRequestParts<AppState> for CurrentUser {
     }
 }
 
+/// Like the [`FromRequestParts`] implementation, requests authenticated with an API token are
+/// treated as having no user.
 impl OptionalFromRequestParts<AppState> for CurrentUser {
     type Rejection = (StatusCode, &'static str);
 
@@ -245,13 +276,16 @@ impl OptionalFromRequestParts<AppState> for CurrentUser {
         parts: &mut Parts,
         state: &AppState,
     ) -> Result<Option<Self>, Self::Rejection> {
-        let Ok(Extension(SbSession::Authenticated(session))) = parts.extract().await else {
+        let Ok(Extension(session)) = parts.extract::<Extension<SbSession>>().await else {
+            return Ok(None);
+        };
+        let Some(user_id) = http_session_user_id(&session) else {
             return Ok(None);
         };
 
         Ok(state
             .current_user_repo
-            .load_cached_user(session.user_id, Default::default())
+            .load_cached_user(user_id, Default::default())
             .await
             .ok())
     }
@@ -269,10 +303,20 @@ pub enum PublishedUserMessage {
 
     #[serde(rename_all = "camelCase")]
     EmailChanged { user_id: SbUserId, email: String },
+
+    /// A user was banned for evading another account's ban. `banned_user_ids` includes the user
+    /// along with any accounts connected to it, which were banned at the same time.
+    #[serde(rename_all = "camelCase")]
+    BannedForBanEvasion {
+        user_id: SbUserId,
+        banned_user_ids: Vec<SbUserId>,
+        ban_length_hours: f64,
+    },
 }
 
-#[derive(SimpleObject, sqlx::FromRow)]
+#[derive(SimpleObject, Serialize, Debug, sqlx::FromRow)]
 #[graphql(complex)]
+#[serde(rename_all = "camelCase")]
 pub struct LoginNameAuditEntry {
     pub id: uuid::Uuid,
     pub user_id: SbUserId,
@@ -305,8 +349,9 @@ impl LoginNameAuditEntry {
     }
 }
 
-#[derive(SimpleObject, sqlx::FromRow)]
+#[derive(SimpleObject, Serialize, Debug, sqlx::FromRow)]
 #[graphql(complex)]
+#[serde(rename_all = "camelCase")]
 pub struct DisplayNameAuditEntry {
     pub id: uuid::Uuid,
     pub user_id: SbUserId,
@@ -367,7 +412,10 @@ impl UsersQuery {
         Ok(restrictions)
     }
 
-    #[graphql(guard = RequiredPermission::BanUsers)]
+    #[graphql(
+        guard = RequiredPermission::BanUsers,
+        complexity = "page_complexity(limit, 50, child_complexity)"
+    )]
     async fn user_login_name_audit_history(
         &self,
         ctx: &Context<'_>,
@@ -408,7 +456,10 @@ impl UsersQuery {
         Ok(entries)
     }
 
-    #[graphql(guard = RequiredPermission::BanUsers)]
+    #[graphql(
+        guard = RequiredPermission::BanUsers,
+        complexity = "page_complexity(limit, 50, child_complexity)"
+    )]
     async fn user_display_name_audit_history(
         &self,
         ctx: &Context<'_>,
@@ -463,7 +514,7 @@ impl UsersQuery {
                 SignupCode,
                 r#"
                 SELECT id, code, created_at, created_by as "created_by: _", expires_at, max_uses,
-                    uses, exhausted, notes
+                    uses, exhausted, notes, campaign_id
                 FROM user_signup_codes
                 ORDER BY expires_at DESC
                 -- Protective bound: this is admin-gated but has no pagination, so cap the
@@ -478,7 +529,7 @@ impl UsersQuery {
                 SignupCode,
                 r#"
                 SELECT id, code, created_at, created_by as "created_by: _", expires_at, max_uses,
-                    uses, exhausted, notes
+                    uses, exhausted, notes, campaign_id
                 FROM user_signup_codes
                 WHERE NOT exhausted
                 ORDER BY expires_at DESC
@@ -527,9 +578,10 @@ impl UsersMutation {
         let mut published_messages = Vec::new();
 
         let update_password_query = changes.new_password.map(|new_password| {
-            emails.push(MailgunMessage {
+            emails.push(EmailMessage {
                 to: user.email.clone(),
-                template: MailgunTemplate::PasswordChange(PasswordChangeData {
+                locale: user.locale.clone(),
+                template: EmailTemplate::PasswordChange(PasswordChangeData {
                     username: user.name.clone(),
                 }),
             });
@@ -601,9 +653,10 @@ impl UsersMutation {
                 }
 
                 // Add email notification for login name change
-                emails.push(MailgunMessage {
+                emails.push(EmailMessage {
                     to: user.email.clone(),
-                    template: MailgunTemplate::LoginNameChange(LoginNameChangeData {
+                    locale: user.locale.clone(),
+                    template: EmailTemplate::LoginNameChange(LoginNameChangeData {
                         username: user.name.clone(),
                         old_login_name: user.login_name.clone(),
                         new_login_name: new_login_name.clone(),
@@ -682,9 +735,10 @@ impl UsersMutation {
             if let Some(email) = changes.email
                 && email != user.email
             {
-                emails.push(MailgunMessage {
+                emails.push(EmailMessage {
                     to: user.email.clone(),
-                    template: MailgunTemplate::EmailChange(EmailChangeData {
+                    locale: user.locale.clone(),
+                    template: EmailTemplate::EmailChange(EmailChangeData {
                         username: user.name.clone(),
                     }),
                 });
@@ -702,9 +756,10 @@ impl UsersMutation {
                     code,
                     ip,
                 ));
-                emails.push(MailgunMessage {
+                emails.push(EmailMessage {
                     to: email.clone(),
-                    template: MailgunTemplate::EmailVerification(EmailVerificationData {
+                    locale: user.locale.clone(),
+                    template: EmailTemplate::EmailVerification(EmailVerificationData {
                         username: user.name.clone(),
                         code,
                     }),
@@ -768,6 +823,12 @@ impl UsersMutation {
             }
         }
 
+        // Queued as part of the transaction so emails only go out if the changes are saved
+        let mailer = ctx.data::<Mailer>()?;
+        for email in emails.into_iter() {
+            mailer.queue(&mut *tx, email).await?;
+        }
+
         tx.commit().await.wrap_err("Failed to commit transaction")?;
 
         let redis = ctx.data::<RedisPool>()?.clone();
@@ -779,16 +840,6 @@ impl UsersMutation {
             }
         });
 
-        let mailgun = ctx.data::<Arc<MailgunClient>>()?;
-        for email in emails.into_iter() {
-            let mailgun = mailgun.clone();
-            spawn_with_tracing(async move {
-                if let Err(e) = mailgun.send(email).await {
-                    error!("sending email failed: {e:?}")
-                }
-            });
-        }
-
         if has_update {
             let user = ctx
                 .data::<CurrentUserRepo>()?
@@ -980,7 +1031,7 @@ impl UsersMutation {
                     INSERT INTO user_signup_codes (code, created_by, expires_at, max_uses, notes)
                     VALUES ($1, $2, $3, $4, $5)
                     RETURNING id, code, created_at, created_by as "created_by: _", expires_at,
-                        max_uses, uses, exhausted, notes
+                        max_uses, uses, exhausted, notes, campaign_id
                     "#,
                     generated_code,
                     user.id as _,
@@ -1040,6 +1091,8 @@ pub struct SignupCode {
     pub uses: i32,
     pub exhausted: bool,
     pub notes: Option<String>,
+    #[graphql(skip)]
+    pub campaign_id: Option<uuid::Uuid>,
 }
 
 #[ComplexObject]
@@ -1053,6 +1106,14 @@ impl SignupCode {
             Ok(None)
         }
     }
+
+    /// The campaign this code was created for, if any.
+    async fn campaign(&self, ctx: &Context<'_>) -> Result<Option<SignupCodeCampaign>> {
+        let Some(campaign_id) = self.campaign_id else {
+            return Ok(None);
+        };
+        Ok(load_campaign(ctx.data::<PgPool>()?, campaign_id).await?)
+    }
 }
 
 #[derive(InputObject)]
@@ -1312,6 +1373,19 @@ impl CurrentUserRepo {
 
         Ok(cached_user.into())
     }
+
+    /// Removes a user from the cache, so that the next load reads them from the DB.
+    pub async fn invalidate_cached_user(&self, user_id: SbUserId) -> eyre::Result<()> {
+        let mut redis = self
+            .redis
+            .get()
+            .await
+            .wrap_err("Couldn't get redis connection")?;
+        redis
+            .del::<_, ()>(CurrentUserRepo::user_cache_key(user_id))
+            .await
+            .wrap_err("Failed to delete cached user")
+    }
 }
 
 #[skip_serializing_none]
@@ -1375,6 +1449,19 @@ mod tests {
     use serde_json::Value;
 
     use super::*;
+    use crate::users::api_tokens::{ApiTokenScope, ApiTokenSession};
+
+    #[test]
+    fn http_routes_ignore_api_tokens() {
+        let token = SbSession::ApiToken(ApiTokenSession {
+            token_id: uuid::Uuid::nil(),
+            user_id: SbUserId(1),
+            scopes: vec![ApiTokenScope::ReadProfile],
+        });
+        assert_eq!(token.user_id(), Some(SbUserId(1)));
+        assert_eq!(http_session_user_id(&token), None);
+        assert_eq!(http_session_user_id(&SbSession::Anonymous), None);
+    }
 
     #[test]
     fn self_user_deserialization_all_fields() {
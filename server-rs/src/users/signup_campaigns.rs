@@ -0,0 +1,571 @@
+//! Signup code campaigns, which group a batch of signup codes under a name (e.g. a tournament or a
+//! streamer partnership) so staff can hand them out in bulk and later see how many people each
+//! campaign brought in, and how active those people were afterwards.
+
+use std::collections::HashSet;
+
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{ComplexObject, Context, InputObject, Object, Result, SimpleObject};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{self, WrapErr};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::graphql::errors::graphql_error;
+use crate::random_code::gen_random_code;
+use crate::users::permissions::RequiredPermission;
+use crate::users::{CurrentUser, SbUser, SbUserId, SignupCode, UsersLoader};
+
+/// The most codes that can be created for a campaign in one request.
+const MAX_CODES_PER_BATCH: i32 = 500;
+/// How many times we'll try inserting a batch of codes (replacing the ones that collided with
+/// existing codes) before giving up.
+const MAX_GENERATION_ATTEMPTS: u32 = 10;
+
+#[derive(SimpleObject, sqlx::FromRow, Clone, Debug)]
+#[graphql(complex)]
+pub struct SignupCodeCampaign {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+    #[graphql(skip)]
+    pub created_by: Option<SbUserId>,
+}
+
+#[ComplexObject]
+impl SignupCodeCampaign {
+    async fn created_by_user(&self, ctx: &Context<'_>) -> Result<Option<SbUser>> {
+        if let Some(user_id) = self.created_by {
+            ctx.data::<DataLoader<UsersLoader>>()?
+                .load_one(user_id)
+                .await
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// The signup codes created for this campaign, newest first.
+    async fn codes(&self, ctx: &Context<'_>) -> Result<Vec<SignupCode>> {
+        let codes = sqlx::query_as!(
+            SignupCode,
+            r#"
+                SELECT id, code, created_at, created_by as "created_by: _", expires_at, max_uses,
+                    uses, exhausted, notes, campaign_id
+                FROM user_signup_codes
+                WHERE campaign_id = $1
+                ORDER BY created_at DESC
+            "#,
+            self.id,
+        )
+        .fetch_all(ctx.data::<PgPool>()?)
+        .await?;
+
+        Ok(codes)
+    }
+
+    /// Redemption counts and the subsequent activity of users who signed up with this campaign's
+    /// codes. This is computed on request, so avoid selecting it for long lists of campaigns.
+    async fn stats(&self, ctx: &Context<'_>) -> Result<SignupCodeCampaignStats> {
+        Ok(load_campaign_stats(ctx.data::<PgPool>()?, self.id).await?)
+    }
+}
+
+/// How many users a campaign brought in and how active they've been since. Retention counts are
+/// only over users whose accounts are old enough to have been retained (the `eligible` counts),
+/// so recent campaigns don't look worse than they are.
+#[derive(SimpleObject, sqlx::FromRow, Clone, Debug, Default, PartialEq, Eq)]
+#[graphql(complex)]
+pub struct SignupCodeCampaignStats {
+    /// How many codes belong to the campaign.
+    pub code_count: i64,
+    /// Total uses across all of the campaign's codes.
+    pub redemptions: i64,
+    /// Users whose account was created with one of the campaign's codes.
+    pub signups: i64,
+    /// Of `signups`, how many have played at least one game.
+    pub users_with_games: i64,
+    /// Total games played by users who signed up through the campaign.
+    pub games_played: i64,
+    /// Users who signed up at least 7 days ago.
+    pub eligible_for_week_retention: i64,
+    /// Of `eligible_for_week_retention`, how many played a game 7 or more days after signing up.
+    pub retained_after_week: i64,
+    /// Users who signed up at least 30 days ago.
+    pub eligible_for_month_retention: i64,
+    /// Of `eligible_for_month_retention`, how many played a game 30 or more days after signing up.
+    pub retained_after_month: i64,
+}
+
+#[ComplexObject]
+impl SignupCodeCampaignStats {
+    /// `retainedAfterWeek / eligibleForWeekRetention`, or null if no users are eligible yet.
+    async fn week_retention_rate(&self) -> Option<f64> {
+        retention_rate(self.retained_after_week, self.eligible_for_week_retention)
+    }
+
+    /// `retainedAfterMonth / eligibleForMonthRetention`, or null if no users are eligible yet.
+    async fn month_retention_rate(&self) -> Option<f64> {
+        retention_rate(self.retained_after_month, self.eligible_for_month_retention)
+    }
+}
+
+fn retention_rate(retained: i64, eligible: i64) -> Option<f64> {
+    if eligible > 0 {
+        Some(retained as f64 / eligible as f64)
+    } else {
+        None
+    }
+}
+
+#[derive(InputObject, Clone, Debug)]
+pub struct CreateSignupCodeCampaignInput {
+    #[graphql(validator(min_length = 1, max_length = 64))]
+    pub name: String,
+    #[graphql(validator(max_length = 1000))]
+    pub description: Option<String>,
+}
+
+#[derive(InputObject, Clone, Debug)]
+pub struct CreateCampaignSignupCodesInput {
+    /// How many codes to create.
+    pub count: i32,
+    /// When the codes expire. Must be in the future.
+    pub expires_at: DateTime<Utc>,
+    /// How many times each code can be used, or unlimited if not set.
+    pub max_uses: Option<i32>,
+    pub notes: Option<String>,
+}
+
+impl CreateCampaignSignupCodesInput {
+    fn validate(&self, now: DateTime<Utc>) -> Result<()> {
+        if !(1..=MAX_CODES_PER_BATCH).contains(&self.count) {
+            return Err(graphql_error(
+                "INVALID_CODE_COUNT",
+                format!("Code count must be between 1 and {MAX_CODES_PER_BATCH}"),
+            ));
+        }
+        if self.expires_at <= now {
+            return Err(graphql_error(
+                "INVALID_EXPIRY_DATE",
+                "Expiry date must be in the future",
+            ));
+        }
+        if self.max_uses.is_some_and(|m| m < 1) {
+            return Err(graphql_error(
+                "INVALID_MAX_USES",
+                "Max uses must be at least 1",
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub async fn load_campaign(db: &PgPool, id: Uuid) -> eyre::Result<Option<SignupCodeCampaign>> {
+    sqlx::query_as!(
+        SignupCodeCampaign,
+        r#"
+            SELECT id, name, description, created_at, created_by as "created_by: _"
+            FROM signup_code_campaigns
+            WHERE id = $1
+        "#,
+        id,
+    )
+    .fetch_optional(db)
+    .await
+    .wrap_err("Failed to load signup code campaign")
+}
+
+async fn load_campaign_stats(db: &PgPool, id: Uuid) -> eyre::Result<SignupCodeCampaignStats> {
+    // `users.created` and `games_users.start_time` are both UTC timestamps without a time zone, so
+    // "now" is converted to match before comparing against them.
+    sqlx::query_as!(
+        SignupCodeCampaignStats,
+        r#"
+            WITH campaign_codes AS (
+                SELECT id, uses
+                FROM user_signup_codes
+                WHERE campaign_id = $1
+            ), campaign_users AS (
+                SELECT u.id, u.created
+                FROM users u
+                WHERE u.signup_code_used IN (SELECT id FROM campaign_codes)
+            ), user_activity AS (
+                SELECT
+                    cu.id,
+                    cu.created,
+                    COUNT(gu.game_id) AS games,
+                    MAX(gu.start_time) AS last_game
+                FROM campaign_users cu
+                LEFT JOIN games_users gu ON gu.user_id = cu.id
+                GROUP BY cu.id, cu.created
+            )
+            SELECT
+                (SELECT COUNT(*) FROM campaign_codes) AS "code_count!",
+                (SELECT COALESCE(SUM(uses), 0) FROM campaign_codes)::BIGINT AS "redemptions!",
+                COUNT(ua.id) AS "signups!",
+                COUNT(ua.id) FILTER (WHERE ua.games > 0) AS "users_with_games!",
+                COALESCE(SUM(ua.games), 0)::BIGINT AS "games_played!",
+                COUNT(ua.id) FILTER (
+                    WHERE ua.created <= (now() AT TIME ZONE 'UTC') - INTERVAL '7 days'
+                ) AS "eligible_for_week_retention!",
+                COUNT(ua.id) FILTER (
+                    WHERE ua.created <= (now() AT TIME ZONE 'UTC') - INTERVAL '7 days'
+                        AND ua.last_game >= ua.created + INTERVAL '7 days'
+                ) AS "retained_after_week!",
+                COUNT(ua.id) FILTER (
+                    WHERE ua.created <= (now() AT TIME ZONE 'UTC') - INTERVAL '30 days'
+                ) AS "eligible_for_month_retention!",
+                COUNT(ua.id) FILTER (
+                    WHERE ua.created <= (now() AT TIME ZONE 'UTC') - INTERVAL '30 days'
+                        AND ua.last_game >= ua.created + INTERVAL '30 days'
+                ) AS "retained_after_month!"
+            FROM user_activity ua
+        "#,
+        id,
+    )
+    .fetch_one(db)
+    .await
+    .wrap_err("Failed to load signup code campaign stats")
+}
+
+/// Adds freshly generated codes to `codes` until it holds `count` of them.
+fn fill_codes(codes: &mut HashSet<String>, count: usize) {
+    while codes.len() < count {
+        codes.insert(gen_random_code());
+    }
+}
+
+#[derive(Default)]
+pub struct SignupCampaignsQuery;
+
+#[Object]
+impl SignupCampaignsQuery {
+    #[graphql(guard = RequiredPermission::ManageSignupCodes)]
+    async fn signup_code_campaigns(&self, ctx: &Context<'_>) -> Result<Vec<SignupCodeCampaign>> {
+        let campaigns = sqlx::query_as!(
+            SignupCodeCampaign,
+            r#"
+                SELECT id, name, description, created_at, created_by as "created_by: _"
+                FROM signup_code_campaigns
+                ORDER BY created_at DESC
+                -- Protective bound, matching signupCodes
+                LIMIT 200
+            "#
+        )
+        .fetch_all(ctx.data::<PgPool>()?)
+        .await?;
+
+        Ok(campaigns)
+    }
+
+    #[graphql(guard = RequiredPermission::ManageSignupCodes)]
+    async fn signup_code_campaign(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+    ) -> Result<Option<SignupCodeCampaign>> {
+        Ok(load_campaign(ctx.data::<PgPool>()?, id).await?)
+    }
+}
+
+#[derive(Default)]
+pub struct SignupCampaignsMutation;
+
+#[Object]
+impl SignupCampaignsMutation {
+    #[graphql(guard = RequiredPermission::ManageSignupCodes)]
+    async fn create_signup_code_campaign(
+        &self,
+        ctx: &Context<'_>,
+        input: CreateSignupCodeCampaignInput,
+    ) -> Result<SignupCodeCampaign> {
+        let Some(user) = ctx.data::<Option<CurrentUser>>()? else {
+            return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+        };
+
+        let result = sqlx::query_as!(
+            SignupCodeCampaign,
+            r#"
+                INSERT INTO signup_code_campaigns (name, description, created_by)
+                VALUES ($1, $2, $3)
+                RETURNING id, name, description, created_at, created_by as "created_by: _"
+            "#,
+            input.name.trim(),
+            input.description,
+            user.id as _,
+        )
+        .fetch_one(ctx.data::<PgPool>()?)
+        .await;
+
+        match result {
+            Ok(campaign) => Ok(campaign),
+            Err(e)
+                if e.as_database_error().and_then(|db| db.constraint())
+                    == Some("signup_code_campaigns_name_key") =>
+            {
+                Err(graphql_error(
+                    "CAMPAIGN_NAME_TAKEN",
+                    "A campaign with that name already exists",
+                ))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Creates a batch of signup codes belonging to a campaign. All of the codes share the same
+    /// expiry and max uses.
+    #[graphql(guard = RequiredPermission::ManageSignupCodes)]
+    async fn create_campaign_signup_codes(
+        &self,
+        ctx: &Context<'_>,
+        campaign_id: Uuid,
+        input: CreateCampaignSignupCodesInput,
+    ) -> Result<Vec<SignupCode>> {
+        let Some(user) = ctx.data::<Option<CurrentUser>>()? else {
+            return Err(graphql_error("UNAUTHORIZED", "Unauthorized"));
+        };
+        input.validate(Utc::now())?;
+
+        let db = ctx.data::<PgPool>()?;
+        if load_campaign(db, campaign_id).await?.is_none() {
+            return Err(graphql_error("NOT_FOUND", "Campaign not found"));
+        }
+
+        let mut created = insert_campaign_codes(db, campaign_id, user.id, &input).await?;
+        created.sort_by(|a, b| a.code.cmp(&b.code));
+
+        Ok(created)
+    }
+}
+
+/// Generates and inserts `input.count` codes for a campaign. Codes that collide with an existing
+/// (unexhausted) code, including ones a concurrent request inserted after we generated ours, are
+/// skipped by the insert and replaced with new ones. Either every code is created or none are.
+async fn insert_campaign_codes(
+    db: &PgPool,
+    campaign_id: Uuid,
+    created_by: SbUserId,
+    input: &CreateCampaignSignupCodesInput,
+) -> Result<Vec<SignupCode>> {
+    let count = input.count as usize;
+    let mut tx = db.begin().await.wrap_err("Failed to start transaction")?;
+    let mut created = Vec::with_capacity(count);
+    let mut attempts = 0;
+    while created.len() < count {
+        if attempts >= MAX_GENERATION_ATTEMPTS {
+            return Err(graphql_error(
+                "CODE_GENERATION_FAILED",
+                "Failed to generate unique signup codes after multiple attempts",
+            ));
+        }
+        attempts += 1;
+
+        let mut codes = HashSet::with_capacity(count - created.len());
+        fill_codes(&mut codes, count - created.len());
+        let codes = codes.into_iter().collect::<Vec<_>>();
+        let inserted = sqlx::query_as!(
+            SignupCode,
+            r#"
+                INSERT INTO user_signup_codes
+                    (code, created_by, expires_at, max_uses, notes, campaign_id)
+                SELECT code, $2, $3, $4, $5, $6
+                FROM UNNEST($1::TEXT[]) AS code
+                ON CONFLICT DO NOTHING
+                RETURNING id, code, created_at, created_by as "created_by: _", expires_at,
+                    max_uses, uses, exhausted, notes, campaign_id
+            "#,
+            &codes,
+            created_by as _,
+            input.expires_at,
+            input.max_uses,
+            input.notes,
+            campaign_id,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        created.extend(inserted);
+    }
+    tx.commit().await.wrap_err("Failed to commit transaction")?;
+
+    Ok(created)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn input(count: i32) -> CreateCampaignSignupCodesInput {
+        CreateCampaignSignupCodesInput {
+            count,
+            expires_at: Utc::now() + Duration::days(30),
+            max_uses: Some(1),
+            notes: None,
+        }
+    }
+
+    #[test]
+    fn validate_code_batch_input() {
+        let now = Utc::now();
+        assert!(input(1).validate(now).is_ok());
+        assert!(input(MAX_CODES_PER_BATCH).validate(now).is_ok());
+        assert!(input(0).validate(now).is_err());
+        assert!(input(MAX_CODES_PER_BATCH + 1).validate(now).is_err());
+
+        let expired = CreateCampaignSignupCodesInput {
+            expires_at: now - Duration::seconds(1),
+            ..input(5)
+        };
+        assert!(expired.validate(now).is_err());
+
+        let zero_uses = CreateCampaignSignupCodesInput {
+            max_uses: Some(0),
+            ..input(5)
+        };
+        assert!(zero_uses.validate(now).is_err());
+        let unlimited = CreateCampaignSignupCodesInput {
+            max_uses: None,
+            ..input(5)
+        };
+        assert!(unlimited.validate(now).is_ok());
+    }
+
+    #[test]
+    fn fill_codes_only_tops_up() {
+        let mut codes = HashSet::new();
+        fill_codes(&mut codes, 20);
+        assert_eq!(codes.len(), 20);
+
+        let kept = codes.iter().take(15).cloned().collect::<HashSet<_>>();
+        let mut topped_up = kept.clone();
+        fill_codes(&mut topped_up, 20);
+        assert_eq!(topped_up.len(), 20);
+        assert!(kept.is_subset(&topped_up));
+    }
+
+    #[test]
+    fn retention_rate_needs_eligible_users() {
+        assert_eq!(retention_rate(0, 0), None);
+        assert_eq!(retention_rate(3, 4), Some(0.75));
+    }
+
+    async fn insert_campaign(db: &PgPool, name: &str) -> Uuid {
+        sqlx::query_scalar("INSERT INTO signup_code_campaigns (name) VALUES ($1) RETURNING id")
+            .bind(name)
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    async fn insert_code(db: &PgPool, campaign_id: Uuid, uses: i32) -> Uuid {
+        sqlx::query_scalar(
+            r#"
+                INSERT INTO user_signup_codes (code, expires_at, uses, campaign_id)
+                VALUES ($1, NOW() + INTERVAL '30 days', $2, $3)
+                RETURNING id
+            "#,
+        )
+        .bind(gen_random_code())
+        .bind(uses)
+        .bind(campaign_id)
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    /// Creates a user who signed up with `code` `days_ago` days ago and played a game on each of
+    /// `game_days` (days after signing up).
+    async fn sign_up(db: &PgPool, name: &str, code: Uuid, days_ago: i32, game_days: &[i32]) {
+        let user_id = crate::test_utils::create_user(db, name).await;
+        sqlx::query(
+            r#"
+                UPDATE users
+                SET created = (NOW() AT TIME ZONE 'UTC') - make_interval(days => $2),
+                    signup_code_used = $3
+                WHERE id = $1
+            "#,
+        )
+        .bind(user_id.0)
+        .bind(days_ago)
+        .bind(code)
+        .execute(db)
+        .await
+        .unwrap();
+        for &day in game_days {
+            sqlx::query(
+                r#"
+                    INSERT INTO games_users (user_id, game_id, start_time, selected_race,
+                        result_code)
+                    SELECT id, $2, created + make_interval(days => $3), 'p', ''
+                    FROM users
+                    WHERE id = $1
+                "#,
+            )
+            .bind(user_id.0)
+            .bind(Uuid::new_v4())
+            .bind(day)
+            .execute(db)
+            .await
+            .unwrap();
+        }
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn campaign_stats_count_usage_and_retention(db: PgPool) {
+        let campaign = insert_campaign(&db, "ASL").await;
+        let first_code = insert_code(&db, campaign, 2).await;
+        let second_code = insert_code(&db, campaign, 1).await;
+        let _unused_code = insert_code(&db, campaign, 0).await;
+        let other_campaign = insert_campaign(&db, "KSL").await;
+        let other_code = insert_code(&db, other_campaign, 1).await;
+
+        // Played after both a week and a month
+        sign_up(&db, "pachi", first_code, 40, &[1, 35]).await;
+        // Played after a week, too new to count for a month
+        sign_up(&db, "flash", first_code, 10, &[8]).await;
+        // Only played in the first week
+        sign_up(&db, "jaedong", second_code, 40, &[1]).await;
+        // Too new to count for either, hasn't played
+        sign_up(&db, "bisu", second_code, 2, &[]).await;
+        sign_up(&db, "stork", other_code, 40, &[1, 35]).await;
+
+        let stats = load_campaign_stats(&db, campaign).await.unwrap();
+        assert_eq!(
+            stats,
+            SignupCodeCampaignStats {
+                code_count: 3,
+                redemptions: 3,
+                signups: 4,
+                users_with_games: 3,
+                games_played: 4,
+                eligible_for_week_retention: 3,
+                retained_after_week: 2,
+                eligible_for_month_retention: 2,
+                retained_after_month: 1,
+            }
+        );
+
+        let empty = insert_campaign(&db, "Empty").await;
+        assert_eq!(
+            load_campaign_stats(&db, empty).await.unwrap(),
+            SignupCodeCampaignStats::default()
+        );
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn inserts_the_requested_number_of_codes(db: PgPool) {
+        let user_id = crate::test_utils::create_user(&db, "pachi").await;
+        let campaign = insert_campaign(&db, "ASL").await;
+
+        let codes = insert_campaign_codes(&db, campaign, user_id, &input(50))
+            .await
+            .unwrap();
+
+        assert_eq!(codes.len(), 50);
+        assert!(codes.iter().all(|c| c.campaign_id == Some(campaign)));
+        let unique = codes.iter().map(|c| &c.code).collect::<HashSet<_>>();
+        assert_eq!(unique.len(), 50);
+    }
+}
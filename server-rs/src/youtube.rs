@@ -0,0 +1,1287 @@
+//! YouTube integration: channel linking (Google OAuth) and live-stream tracking (YouTube Data API).
+//!
+//! Linking follows the same shape as the Twitch flow (see `twitch.rs`): `youtubeStartLink` returns
+//! an authorize URL with a server-issued `state`, and the client hands the resulting `code`/`state`
+//! to `youtubeCompleteLink`. Only the channel's identity is kept, in `youtube_connections`.
+//!
+//! Unlike Twitch, YouTube has no webhook for a channel going live, so live streams are found by
+//! polling every linked channel (see `youtube_live_poll_loop`). Live streams are stored alongside
+//! Twitch ones (see the `streaming` module), so they show up in the same feed and on
+//! `SbUser.liveStream`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_graphql::{Context, Object, SimpleObject};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{self, Context as _, eyre};
+use deadpool_redis::redis::AsyncCommands;
+use reqwest::StatusCode;
+use secrecy::{ExposeSecret, SecretString};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::{error, warn};
+use url::Url;
+use uuid::Uuid;
+
+use crate::configuration::{Settings, YoutubeSettings};
+use crate::graphql::errors::graphql_error;
+use crate::redis::RedisPool;
+use crate::streaming::{
+    StreamingChannel, StreamingPlatform, StreamingProvider, refresh_provider_live_streams,
+};
+use crate::twitch::{LiveStreamSummary, set_stream_offline};
+use crate::users::{CurrentUser, SbUserId};
+
+/// Reading the linking user's own channel is all we need the user's token for.
+const YOUTUBE_OAUTH_SCOPE: &str = "https://www.googleapis.com/auth/youtube.readonly";
+
+/// How long a pending link request (the server-issued `state`) stays valid.
+const LINK_STATE_TTL_SECONDS: u64 = 600;
+/// How often every linked channel is polled for live streams, as long as that fits within
+/// [`DAILY_POLL_QUOTA_BUDGET`]. With more linked channels than that allows, polls are spaced out
+/// further (see [`live_poll_interval`]).
+const LIVE_POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// How many units of the API key's daily quota (10,000 by default) live polling may use. The rest
+/// is left for identity refreshes and linking.
+const DAILY_POLL_QUOTA_BUDGET: u64 = 8_000;
+/// How long to stop polling after YouTube reports the daily quota as exhausted. The quota resets at
+/// midnight Pacific time, so there's no use retrying every poll until then.
+const QUOTA_EXCEEDED_BACKOFF: Duration = Duration::from_secs(60 * 60);
+/// Channel identities are refreshed every this many live polls (hourly at the base poll interval),
+/// since channels can change their handle or title while their id stays stable.
+const IDENTITY_REFRESH_POLLS: u32 = 12;
+/// How many of each channel's most recent uploads are checked for a live broadcast. A live stream
+/// is added to the uploads playlist when it starts, so it's almost always the newest entry; a few
+/// extra cover uploads or premieres published while the stream is running.
+const RECENT_UPLOADS_CHECKED: u32 = 5;
+/// The YouTube Data API's limit on ids per list request.
+const MAX_IDS_PER_REQUEST: usize = 50;
+/// Lowercased phrases in a stream title that mark it as a StarCraft stream. YouTube doesn't expose
+/// a stream's game through the Data API, so the title is all we have to go on.
+const STARCRAFT_TITLE_KEYWORDS: &[&str] =
+    &["starcraft", "brood war", "shieldbattery", "스타크래프트"];
+/// Short forms that only count as a whole word, since they're also the start of unrelated words
+/// (e.g. "스타" in 스타벅스 or 스타일).
+const STARCRAFT_TITLE_WORDS: &[&str] = &["스타", "스타1", "스타크"];
+
+fn link_state_key(state: &str) -> String {
+    format!("youtube:link_state:{state}")
+}
+
+/// What we stash in Redis for a pending link `state`. The web callback is our only redirect URI,
+/// so unlike Twitch there's nothing else to remember.
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingLink {
+    user_id: i32,
+}
+
+/// Whether a YouTube stream title looks like a StarCraft stream.
+pub(crate) fn is_starcraft_title(title: &str) -> bool {
+    let title = title.to_lowercase();
+    STARCRAFT_TITLE_KEYWORDS
+        .iter()
+        .any(|keyword| title.contains(keyword))
+        || title
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| STARCRAFT_TITLE_WORDS.contains(&word))
+}
+
+/// The quota a single live poll of `channel_count` channels costs: one playlist read per channel,
+/// plus one video lookup per 50 of the uploads found.
+fn live_poll_cost(channel_count: usize) -> u64 {
+    let videos = channel_count as u64 * u64::from(RECENT_UPLOADS_CHECKED);
+    channel_count as u64 + videos.div_ceil(MAX_IDS_PER_REQUEST as u64)
+}
+
+/// How long to wait between live polls so that a day of polling `channel_count` channels stays
+/// within [`DAILY_POLL_QUOTA_BUDGET`].
+fn live_poll_interval(channel_count: usize) -> Duration {
+    const DAY_SECONDS: u64 = 24 * 60 * 60;
+    let polls_per_day = DAILY_POLL_QUOTA_BUDGET / live_poll_cost(channel_count).max(1);
+    let budgeted = Duration::from_secs(DAY_SECONDS.div_ceil(polls_per_day.max(1)));
+    budgeted.max(LIVE_POLL_INTERVAL)
+}
+
+/// Returned (inside an [`eyre::Report`]) when YouTube rejects a request because the API key's daily
+/// quota is used up.
+#[derive(Debug, thiserror::Error)]
+#[error("YouTube API quota exceeded")]
+struct QuotaExceeded;
+
+fn is_quota_exceeded(err: &eyre::Report) -> bool {
+    err.downcast_ref::<QuotaExceeded>().is_some()
+}
+
+pub(crate) fn watch_url(video_id: &str) -> String {
+    format!("https://www.youtube.com/watch?v={video_id}")
+}
+
+pub(crate) fn channel_url(channel_id: &str) -> String {
+    format!("https://www.youtube.com/channel/{channel_id}")
+}
+
+/// The id of a channel's uploads playlist, which YouTube derives from the channel id by swapping its
+/// `UC` prefix for `UU`. Reading this playlist costs 1 quota unit, compared to 100 for a search.
+fn uploads_playlist_id(channel_id: &str) -> String {
+    match channel_id.strip_prefix("UC") {
+        Some(rest) => format!("UU{rest}"),
+        None => channel_id.to_string(),
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+// YouTube API client
+// ---------------------------------------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+struct YoutubeEndpoints {
+    authorize_url: String,
+    token_url: String,
+    /// The base of the Data API, e.g. `https://www.googleapis.com/youtube/v3`.
+    api_base: String,
+}
+
+impl Default for YoutubeEndpoints {
+    fn default() -> Self {
+        Self {
+            authorize_url: "https://accounts.google.com/o/oauth2/v2/auth".into(),
+            token_url: "https://oauth2.googleapis.com/token".into(),
+            api_base: "https://www.googleapis.com/youtube/v3".into(),
+        }
+    }
+}
+
+pub struct YoutubeClient {
+    http: reqwest::Client,
+    client_id: String,
+    client_secret: SecretString,
+    api_key: SecretString,
+    /// The redirect URI for the linking flow (`<canonical host>/youtube/callback`).
+    redirect_uri: String,
+    endpoints: YoutubeEndpoints,
+}
+
+impl YoutubeClient {
+    /// Builds a client from settings, returning `None` if YouTube isn't configured.
+    pub fn from_settings(settings: &Settings) -> Option<Arc<Self>> {
+        let youtube = settings.youtube.as_ref()?;
+        Some(Arc::new(Self::new(
+            &settings.canonical_host,
+            youtube,
+            YoutubeEndpoints::default(),
+        )))
+    }
+
+    fn new(canonical_host: &str, settings: &YoutubeSettings, endpoints: YoutubeEndpoints) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            client_id: settings.client_id.clone(),
+            client_secret: settings.client_secret.clone(),
+            api_key: settings.api_key.clone(),
+            redirect_uri: format!("{}/youtube/callback", canonical_host.trim_end_matches('/')),
+            endpoints,
+        }
+    }
+
+    fn api_url(&self, path: &str, params: &[(&str, &str)]) -> eyre::Result<Url> {
+        Url::parse_with_params(&format!("{}/{path}", self.endpoints.api_base), params)
+            .wrap_err_with(|| format!("Failed to build YouTube {path} URL"))
+    }
+
+    /// Makes a Data API request with our API key. Returns `None` if the resource doesn't exist.
+    async fn get_with_key<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        params: &[(&str, &str)],
+    ) -> eyre::Result<Option<T>> {
+        let mut url = self.api_url(path, params)?;
+        url.query_pairs_mut()
+            .append_pair("key", self.api_key.expose_secret());
+        let resp = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .wrap_err_with(|| format!("Failed to send YouTube {path} request"))?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            if status == StatusCode::FORBIDDEN && body.contains("quotaExceeded") {
+                return Err(QuotaExceeded.into());
+            }
+            return Err(eyre!("YouTube {path} request failed ({status}): {body}"));
+        }
+        resp.json()
+            .await
+            .map(Some)
+            .wrap_err_with(|| format!("Failed to parse YouTube {path} response"))
+    }
+
+    /// Exchanges an authorization `code` for a user access token.
+    async fn exchange_code(&self, code: &str, redirect_uri: &str) -> eyre::Result<String> {
+        let resp = self
+            .http
+            .post(&self.endpoints.token_url)
+            .form(&[
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.expose_secret()),
+                ("code", code),
+                ("grant_type", "authorization_code"),
+                ("redirect_uri", redirect_uri),
+            ])
+            .send()
+            .await
+            .wrap_err("Failed to send YouTube token request")?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(eyre!("YouTube token exchange failed ({status}): {body}"));
+        }
+        let token: TokenResponse = resp
+            .json()
+            .await
+            .wrap_err("Failed to parse YouTube token response")?;
+        Ok(token.access_token)
+    }
+
+    /// Looks up the ids of the most recent uploads of a channel. A channel whose uploads playlist
+    /// doesn't exist (e.g. it was deleted) has none.
+    async fn recent_upload_ids(&self, channel_id: &str) -> eyre::Result<Vec<String>> {
+        let playlist_id = uploads_playlist_id(channel_id);
+        let max_results = RECENT_UPLOADS_CHECKED.to_string();
+        let items: Option<ListResponse<PlaylistItemResource>> = self
+            .get_with_key(
+                "playlistItems",
+                &[
+                    ("part", "contentDetails"),
+                    ("playlistId", &playlist_id),
+                    ("maxResults", &max_results),
+                ],
+            )
+            .await?;
+        Ok(items
+            .map(|items| {
+                items
+                    .items
+                    .into_iter()
+                    .map(|item| item.content_details.video_id)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Looks up a set of videos by id, batched into the API's 50-ids-per-request limit.
+    async fn get_videos(&self, ids: &[String]) -> eyre::Result<Vec<VideoResource>> {
+        let mut videos = Vec::new();
+        for chunk in ids.chunks(MAX_IDS_PER_REQUEST) {
+            let ids = chunk.join(",");
+            let page: Option<ListResponse<VideoResource>> = self
+                .get_with_key(
+                    "videos",
+                    &[("part", "snippet,liveStreamingDetails"), ("id", &ids)],
+                )
+                .await?;
+            videos.extend(page.into_iter().flat_map(|p| p.items));
+        }
+        Ok(videos)
+    }
+}
+
+impl StreamingPlatform for YoutubeClient {
+    const PROVIDER: StreamingProvider = StreamingProvider::Youtube;
+
+    fn authorize_url(&self, state: &str, redirect_uri: &str) -> eyre::Result<String> {
+        let url = Url::parse_with_params(
+            &self.endpoints.authorize_url,
+            &[
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", redirect_uri),
+                ("response_type", "code"),
+                ("scope", YOUTUBE_OAUTH_SCOPE),
+                ("state", state),
+            ],
+        )?;
+        Ok(url.to_string())
+    }
+
+    async fn fetch_linked_channel(
+        &self,
+        code: &str,
+        redirect_uri: &str,
+    ) -> eyre::Result<StreamingChannel> {
+        let access_token = self.exchange_code(code, redirect_uri).await?;
+        let url = self.api_url("channels", &[("part", "snippet"), ("mine", "true")])?;
+        let resp = self
+            .http
+            .get(url)
+            .bearer_auth(&access_token)
+            .send()
+            .await
+            .wrap_err("Failed to send YouTube channels request")?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(eyre!("YouTube get-channels failed ({status}): {body}"));
+        }
+        let channels: ListResponse<ChannelResource> = resp
+            .json()
+            .await
+            .wrap_err("Failed to parse YouTube channels response")?;
+        // Google accounts without a YouTube channel get an empty list
+        channels
+            .items
+            .into_iter()
+            .next()
+            .map(StreamingChannel::from)
+            .ok_or_else(|| eyre!("YouTube account has no channel"))
+    }
+
+    async fn fetch_channels(&self, ids: &[String]) -> eyre::Result<Vec<StreamingChannel>> {
+        let mut channels = Vec::new();
+        for chunk in ids.chunks(MAX_IDS_PER_REQUEST) {
+            let ids = chunk.join(",");
+            let page: Option<ListResponse<ChannelResource>> = self
+                .get_with_key("channels", &[("part", "snippet"), ("id", &ids)])
+                .await?;
+            channels.extend(
+                page.into_iter()
+                    .flat_map(|p| p.items)
+                    .map(StreamingChannel::from),
+            );
+        }
+        Ok(channels)
+    }
+
+    async fn fetch_live_streams(
+        &self,
+        channels: &[StreamingChannel],
+    ) -> eyre::Result<Vec<LiveStreamSummary>> {
+        // One channel failing (e.g. a deleted or private uploads playlist) shouldn't hide everyone
+        // else's streams, so those are skipped. Running out of quota fails every request though, and
+        // so does YouTube being unreachable, which would otherwise look like every stream ending.
+        let mut video_ids = Vec::new();
+        let mut failures = 0;
+        let mut last_error = None;
+        for channel in channels {
+            match self.recent_upload_ids(&channel.id).await {
+                Ok(ids) => video_ids.extend(ids),
+                Err(e) if is_quota_exceeded(&e) => return Err(e),
+                Err(e) => {
+                    warn!(
+                        "Failed to check YouTube channel {} for streams: {e:?}",
+                        channel.id
+                    );
+                    failures += 1;
+                    last_error = Some(e);
+                }
+            }
+        }
+        if failures == channels.len()
+            && let Some(e) = last_error
+        {
+            return Err(e.wrap_err("Failed to check any YouTube channels for streams"));
+        }
+        if video_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let by_id = channels
+            .iter()
+            .map(|c| (c.id.as_str(), c))
+            .collect::<HashMap<_, _>>();
+        // A channel can technically run more than one live broadcast at once, but a user only gets
+        // one feed entry, so keep their most-watched one.
+        let mut live: HashMap<String, LiveStreamSummary> = HashMap::new();
+        for video in self.get_videos(&video_ids).await? {
+            let Some(channel) = by_id.get(video.snippet.channel_id.as_str()) else {
+                continue;
+            };
+            let Some(summary) = video.into_live_summary(channel) else {
+                continue;
+            };
+            match live.get(&summary.channel_id) {
+                Some(existing) if existing.viewer_count >= summary.viewer_count => {}
+                _ => {
+                    live.insert(summary.channel_id.clone(), summary);
+                }
+            }
+        }
+        Ok(live.into_values().collect())
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+// YouTube API response types
+// ---------------------------------------------------------------------------------------------
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct ListResponse<T> {
+    // Not `#[serde(default)]`, which would require `T: Default`
+    #[serde(default = "Vec::new")]
+    items: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct ChannelResource {
+    id: String,
+    snippet: ChannelSnippet,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChannelSnippet {
+    title: String,
+    /// The channel's handle, e.g. `@shieldbattery`. Older channels may not have one.
+    custom_url: Option<String>,
+}
+
+impl From<ChannelResource> for StreamingChannel {
+    fn from(channel: ChannelResource) -> Self {
+        let login = channel
+            .snippet
+            .custom_url
+            .map(|handle| handle.trim_start_matches('@').to_string())
+            .filter(|handle| !handle.is_empty())
+            .unwrap_or_else(|| channel.id.clone());
+        Self {
+            id: channel.id,
+            login,
+            display_name: channel.snippet.title,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlaylistItemResource {
+    content_details: PlaylistItemContentDetails,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlaylistItemContentDetails {
+    video_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VideoResource {
+    id: String,
+    snippet: VideoSnippet,
+    live_streaming_details: Option<LiveStreamingDetails>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VideoSnippet {
+    channel_id: String,
+    title: String,
+    /// `live`, `upcoming`, or `none`.
+    live_broadcast_content: String,
+    #[serde(default)]
+    thumbnails: HashMap<String, Thumbnail>,
+}
+
+#[derive(Deserialize)]
+struct Thumbnail {
+    url: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LiveStreamingDetails {
+    actual_start_time: Option<DateTime<Utc>>,
+    /// Absent when the broadcaster hides their viewer count. YouTube sends this as a string.
+    concurrent_viewers: Option<String>,
+}
+
+impl VideoResource {
+    /// Converts a video into a live-stream summary, if it's a broadcast that's live right now.
+    fn into_live_summary(self, channel: &StreamingChannel) -> Option<LiveStreamSummary> {
+        if self.snippet.live_broadcast_content != "live" {
+            return None;
+        }
+        let details = self.live_streaming_details?;
+        let started_at = details.actual_start_time?;
+        let viewer_count = details
+            .concurrent_viewers
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        // `medium` is 320x180, which matches the size we use for Twitch thumbnails
+        let mut thumbnails = self.snippet.thumbnails;
+        let thumbnail_url = ["medium", "high", "default"]
+            .into_iter()
+            .find_map(|size| thumbnails.remove(size))
+            .map(|t| t.url)
+            .unwrap_or_default();
+
+        Some(LiveStreamSummary {
+            provider: StreamingProvider::Youtube,
+            channel_id: channel.id.clone(),
+            channel_login: channel.login.clone(),
+            channel_display_name: channel.display_name.clone(),
+            stream_id: Some(self.id),
+            title: self.snippet.title,
+            game_id: String::new(),
+            game_name: String::new(),
+            viewer_count,
+            started_at,
+            thumbnail_url,
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+// Persistent connection (DB)
+// ---------------------------------------------------------------------------------------------
+
+/// A persistent link between a ShieldBattery user and their YouTube channel.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct YoutubeConnection {
+    #[graphql(skip)]
+    pub user_id: SbUserId,
+    /// YouTube's stable channel id.
+    pub channel_id: String,
+    /// The channel's handle (without the leading `@`), or its id if it has no handle.
+    pub channel_login: String,
+    /// The channel's title.
+    pub channel_display_name: String,
+    /// When the channel was first linked.
+    pub linked_at: DateTime<Utc>,
+}
+
+async fn load_connection(
+    pool: &PgPool,
+    user_id: SbUserId,
+) -> eyre::Result<Option<YoutubeConnection>> {
+    sqlx::query_as!(
+        YoutubeConnection,
+        r#"
+            SELECT user_id, channel_id, channel_login, channel_display_name, linked_at
+            FROM youtube_connections
+            WHERE user_id = $1
+        "#,
+        user_id as _,
+    )
+    .fetch_optional(pool)
+    .await
+    .wrap_err("Failed to load YouTube connection")
+}
+
+/// Loads every linked channel, keyed by the user it's linked to, for the live poll.
+async fn load_all_channels(pool: &PgPool) -> eyre::Result<HashMap<SbUserId, StreamingChannel>> {
+    let rows = sqlx::query!(
+        r#"
+            SELECT user_id as "user_id: SbUserId", channel_id, channel_login, channel_display_name
+            FROM youtube_connections
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .wrap_err("Failed to load YouTube connections")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let channel = StreamingChannel {
+                id: row.channel_id,
+                login: row.channel_login,
+                display_name: row.channel_display_name,
+            };
+            (row.user_id, channel)
+        })
+        .collect())
+}
+
+/// Inserts or replaces the channel linked to a user.
+async fn upsert_connection(
+    pool: &PgPool,
+    user_id: SbUserId,
+    channel: &StreamingChannel,
+) -> Result<YoutubeConnection, sqlx::Error> {
+    sqlx::query_as!(
+        YoutubeConnection,
+        r#"
+            INSERT INTO youtube_connections
+                (user_id, channel_id, channel_login, channel_display_name)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id) DO UPDATE SET
+                channel_id = EXCLUDED.channel_id,
+                channel_login = EXCLUDED.channel_login,
+                channel_display_name = EXCLUDED.channel_display_name,
+                updated_at = now()
+            RETURNING user_id, channel_id, channel_login, channel_display_name, linked_at
+        "#,
+        user_id as _,
+        channel.id,
+        channel.login,
+        channel.display_name,
+    )
+    .fetch_one(pool)
+    .await
+}
+
+async fn update_connection_identity(pool: &PgPool, channel: &StreamingChannel) -> eyre::Result<()> {
+    sqlx::query!(
+        r#"
+            UPDATE youtube_connections
+            SET channel_login = $2, channel_display_name = $3, updated_at = now()
+            WHERE channel_id = $1
+        "#,
+        channel.id,
+        channel.login,
+        channel.display_name,
+    )
+    .execute(pool)
+    .await
+    .wrap_err("Failed to update YouTube connection identity")?;
+    Ok(())
+}
+
+/// Removes a user's YouTube connection along with their live stream entry, if it came from YouTube.
+/// Returns whether there was a connection to remove.
+pub async fn remove_connection(
+    pool: &PgPool,
+    redis: &RedisPool,
+    user_id: SbUserId,
+) -> eyre::Result<bool> {
+    let result = sqlx::query!(
+        r#"DELETE FROM youtube_connections WHERE user_id = $1"#,
+        user_id as _,
+    )
+    .execute(pool)
+    .await
+    .wrap_err("Failed to delete YouTube connection")?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    if let Err(e) = set_stream_offline(redis, user_id, StreamingProvider::Youtube).await {
+        error!("Failed to clear live stream on unlink: {e:?}");
+    }
+    Ok(true)
+}
+
+// ---------------------------------------------------------------------------------------------
+// Live polling
+// ---------------------------------------------------------------------------------------------
+
+/// Polls every linked channel for live streams, storing any that are live and clearing any that
+/// have ended. YouTube has no push notifications for broadcasts starting, so this is the only way
+/// streams are picked up. Channel identities are refreshed on a slower cadence in the same loop.
+pub async fn youtube_live_poll_loop(client: Arc<YoutubeClient>, db: PgPool, redis: RedisPool) {
+    let mut polls = 0u32;
+    let mut next_poll = Duration::ZERO;
+    loop {
+        tokio::time::sleep(next_poll).await;
+        let channels = match load_all_channels(&db).await {
+            Ok(channels) => channels,
+            Err(e) => {
+                error!("Failed to load YouTube channels for live poll: {e:?}");
+                next_poll = LIVE_POLL_INTERVAL;
+                continue;
+            }
+        };
+
+        next_poll = live_poll_interval(channels.len());
+        if let Err(e) = refresh_provider_live_streams(client.as_ref(), &channels, &db, &redis).await
+        {
+            if is_quota_exceeded(&e) {
+                warn!(
+                    "YouTube API quota exceeded, pausing live polls for {:?}",
+                    QUOTA_EXCEEDED_BACKOFF
+                );
+                next_poll = next_poll.max(QUOTA_EXCEEDED_BACKOFF);
+            } else {
+                error!("YouTube live-stream poll failed: {e:?}");
+            }
+        }
+
+        if polls.is_multiple_of(IDENTITY_REFRESH_POLLS)
+            && let Err(e) = refresh_channel_identities(&client, &db, &channels).await
+        {
+            error!("Failed to refresh YouTube identities: {e:?}");
+        }
+        polls = polls.wrapping_add(1);
+    }
+}
+
+/// Refreshes the stored handle/title of every linked channel that has changed. Channels YouTube no
+/// longer returns keep their last-known identity.
+async fn refresh_channel_identities(
+    client: &YoutubeClient,
+    db: &PgPool,
+    channels: &HashMap<SbUserId, StreamingChannel>,
+) -> eyre::Result<()> {
+    if channels.is_empty() {
+        return Ok(());
+    }
+
+    let ids = channels.values().map(|c| c.id.clone()).collect::<Vec<_>>();
+    let current = client.fetch_channels(&ids).await?;
+    let stored = channels
+        .values()
+        .map(|c| (c.id.as_str(), c))
+        .collect::<HashMap<_, _>>();
+    for channel in current {
+        if stored.get(channel.id.as_str()) == Some(&&channel) {
+            continue;
+        }
+        if let Err(e) = update_connection_identity(db, &channel).await {
+            warn!(
+                "Failed to refresh YouTube identity for channel {}: {e:?}",
+                channel.id
+            );
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------------------------
+// GraphQL
+// ---------------------------------------------------------------------------------------------
+
+/// The result of starting a YouTube link: the authorize URL the client should open.
+#[derive(SimpleObject)]
+pub struct YoutubeLinkStart {
+    /// The Google OAuth authorize URL the client should open (e.g. in a popup) to begin linking.
+    pub url: String,
+}
+
+fn require_current_user<'a>(ctx: &'a Context<'_>) -> async_graphql::Result<&'a CurrentUser> {
+    ctx.data::<Option<CurrentUser>>()?
+        .as_ref()
+        .ok_or_else(|| graphql_error("UNAUTHORIZED", "Unauthorized"))
+}
+
+fn require_youtube_client<'a>(
+    ctx: &'a Context<'_>,
+) -> async_graphql::Result<&'a Arc<YoutubeClient>> {
+    ctx.data::<Option<Arc<YoutubeClient>>>()?
+        .as_ref()
+        .ok_or_else(|| {
+            graphql_error(
+                "YOUTUBE_NOT_CONFIGURED",
+                "YouTube integration is not configured",
+            )
+        })
+}
+
+#[derive(Default)]
+pub struct YoutubeQuery;
+
+#[Object]
+impl YoutubeQuery {
+    /// The current user's linked YouTube channel, or `null` if they haven't linked one.
+    async fn my_youtube_connection(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<YoutubeConnection>> {
+        let user = require_current_user(ctx)?;
+        Ok(load_connection(ctx.data::<PgPool>()?, user.id).await?)
+    }
+}
+
+#[derive(Default)]
+pub struct YoutubeMutation;
+
+#[Object]
+impl YoutubeMutation {
+    /// Begins linking the current user's YouTube channel, returning the Google OAuth authorize URL
+    /// the client should open. Completing the flow calls `youtubeCompleteLink` with the resulting
+    /// `code` and `state`.
+    async fn youtube_start_link(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<YoutubeLinkStart> {
+        let user = require_current_user(ctx)?;
+        let client = require_youtube_client(ctx)?;
+
+        let state = Uuid::new_v4().to_string();
+        let pending = serde_json::to_string(&PendingLink {
+            user_id: i32::from(user.id),
+        })
+        .wrap_err("Failed to serialize pending YouTube link")?;
+        let mut redis = ctx
+            .data::<RedisPool>()?
+            .get()
+            .await
+            .wrap_err("Could not connect to Redis")?;
+        let _: () = redis
+            .set_ex(link_state_key(&state), pending, LINK_STATE_TTL_SECONDS)
+            .await
+            .wrap_err("Failed to store YouTube link state")?;
+
+        let url = client.authorize_url(&state, &client.redirect_uri)?;
+        Ok(YoutubeLinkStart { url })
+    }
+
+    /// Completes a YouTube link started by `youtubeStartLink`, replacing any channel the user had
+    /// linked before. A stream that's already live is picked up on the next poll.
+    async fn youtube_complete_link(
+        &self,
+        ctx: &Context<'_>,
+        code: String,
+        state: String,
+    ) -> async_graphql::Result<YoutubeConnection> {
+        let user = require_current_user(ctx)?;
+        let client = require_youtube_client(ctx)?;
+        let pool = ctx.data::<PgPool>()?;
+
+        let mut redis = ctx
+            .data::<RedisPool>()?
+            .get()
+            .await
+            .wrap_err("Could not connect to Redis")?;
+        let key = link_state_key(&state);
+        let stored: Option<String> = redis
+            .get(&key)
+            .await
+            .wrap_err("Failed to read YouTube link state")?;
+        // As with Twitch, the state is only consumed once we know it belongs to the caller
+        let pending = stored
+            .and_then(|s| serde_json::from_str::<PendingLink>(&s).ok())
+            .filter(|p| p.user_id == i32::from(user.id));
+        if pending.is_none() {
+            return Err(graphql_error(
+                "YOUTUBE_INVALID_STATE",
+                "Your YouTube linking request was invalid or expired. Please try again.",
+            ));
+        }
+        let _: () = redis
+            .del(&key)
+            .await
+            .wrap_err("Failed to clear YouTube link state")?;
+
+        let channel = client
+            .fetch_linked_channel(&code, &client.redirect_uri)
+            .await
+            .map_err(|e| {
+                error!("YouTube channel lookup failed: {e:?}");
+                graphql_error(
+                    "YOUTUBE_EXCHANGE_FAILED",
+                    "Failed to complete YouTube linking. Make sure your Google account has a \
+                     YouTube channel.",
+                )
+            })?;
+
+        match upsert_connection(pool, user.id, &channel).await {
+            Ok(connection) => Ok(connection),
+            Err(e) => {
+                if e.as_database_error().and_then(|db| db.constraint())
+                    == Some("youtube_connections_channel_id_key")
+                {
+                    return Err(graphql_error(
+                        "YOUTUBE_ALREADY_LINKED",
+                        "That YouTube channel is already linked to another ShieldBattery account.",
+                    ));
+                }
+                error!("Failed to upsert YouTube connection: {e:?}");
+                Err(graphql_error(
+                    "YOUTUBE_LINK_FAILED",
+                    "Failed to link YouTube channel",
+                ))
+            }
+        }
+    }
+
+    /// Unlinks the current user's YouTube channel. Returns whether there was one to unlink.
+    async fn youtube_unlink(&self, ctx: &Context<'_>) -> async_graphql::Result<bool> {
+        let user = require_current_user(ctx)?;
+        Ok(remove_connection(ctx.data::<PgPool>()?, ctx.data::<RedisPool>()?, user.id).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::Matcher;
+    use serde_json::json;
+
+    use super::*;
+
+    fn mock_client(server: &mockito::Server) -> YoutubeClient {
+        let settings = YoutubeSettings {
+            client_id: "client-id".into(),
+            client_secret: "client-secret".to_string().into(),
+            api_key: "api-key".to_string().into(),
+        };
+        let endpoints = YoutubeEndpoints {
+            authorize_url: format!("{}/authorize", server.url()),
+            token_url: format!("{}/token", server.url()),
+            api_base: format!("{}/youtube/v3", server.url()),
+        };
+        YoutubeClient::new("https://example.org/", &settings, endpoints)
+    }
+
+    fn channel(id: &str, login: &str) -> StreamingChannel {
+        StreamingChannel {
+            id: id.into(),
+            login: login.into(),
+            display_name: login.to_uppercase(),
+        }
+    }
+
+    async fn mock_uploads(
+        server: &mut mockito::Server,
+        playlist_id: &str,
+        video_ids: &[&str],
+    ) -> mockito::Mock {
+        let items = video_ids
+            .iter()
+            .map(|id| json!({ "contentDetails": { "videoId": id } }))
+            .collect::<Vec<_>>();
+        server
+            .mock("GET", "/youtube/v3/playlistItems")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("playlistId".into(), playlist_id.into()),
+                Matcher::UrlEncoded("key".into(), "api-key".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({ "items": items }).to_string())
+            .create_async()
+            .await
+    }
+
+    #[test]
+    fn starcraft_titles_are_recognized() {
+        assert!(is_starcraft_title("StarCraft: Remastered ladder grind"));
+        assert!(is_starcraft_title("BROOD WAR pro games"));
+        assert!(is_starcraft_title("빠른무한 스타 방송"));
+        assert!(is_starcraft_title("[스타1] 래더 방송"));
+        assert!(is_starcraft_title("스타크래프트 리마스터"));
+        assert!(!is_starcraft_title("Just chatting"));
+        assert!(!is_starcraft_title("스타벅스 신메뉴 리뷰"));
+        assert!(!is_starcraft_title("가을 스타일 코디"));
+    }
+
+    #[test]
+    fn live_polls_are_spaced_out_to_fit_the_quota_budget() {
+        assert_eq!(live_poll_interval(0), LIVE_POLL_INTERVAL);
+        assert_eq!(live_poll_interval(10), LIVE_POLL_INTERVAL);
+
+        for channel_count in [30, 100, 1000] {
+            let interval = live_poll_interval(channel_count);
+            assert!(interval > LIVE_POLL_INTERVAL);
+            let polls_per_day = (24 * 60 * 60) / interval.as_secs();
+            assert!(
+                polls_per_day * live_poll_cost(channel_count) <= DAILY_POLL_QUOTA_BUDGET,
+                "{channel_count} channels every {interval:?} is over budget"
+            );
+        }
+    }
+
+    #[test]
+    fn uploads_playlist_is_derived_from_the_channel_id() {
+        assert_eq!(uploads_playlist_id("UCabc123"), "UUabc123");
+        assert_eq!(uploads_playlist_id("weird"), "weird");
+    }
+
+    #[test]
+    fn authorize_url_requests_read_only_access() {
+        let server = mockito::Server::new();
+        let client = mock_client(&server);
+        let url = Url::parse(
+            &client
+                .authorize_url("the-state", &client.redirect_uri)
+                .unwrap(),
+        )
+        .unwrap();
+        let params = url.query_pairs().into_owned().collect::<HashMap<_, _>>();
+
+        assert_eq!(params["state"], "the-state");
+        assert_eq!(params["scope"], YOUTUBE_OAUTH_SCOPE);
+        assert_eq!(
+            params["redirect_uri"],
+            "https://example.org/youtube/callback"
+        );
+        assert_eq!(params["client_id"], "client-id");
+    }
+
+    #[tokio::test]
+    async fn fetches_linked_channel_from_mock_server() {
+        let mut server = mockito::Server::new_async().await;
+        let token = server
+            .mock("POST", "/token")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("code".into(), "the-code".into()),
+                Matcher::UrlEncoded(
+                    "redirect_uri".into(),
+                    "https://example.org/youtube/callback".into(),
+                ),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({ "access_token": "the-token" }).to_string())
+            .create_async()
+            .await;
+        let channels = server
+            .mock("GET", "/youtube/v3/channels")
+            .match_query(Matcher::UrlEncoded("mine".into(), "true".into()))
+            .match_header("authorization", "Bearer the-token")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({
+                    "items": [{
+                        "id": "UCabc",
+                        "snippet": { "title": "Pachi Plays", "customUrl": "@pachi" },
+                    }],
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let client = mock_client(&server);
+        let linked = client
+            .fetch_linked_channel("the-code", &client.redirect_uri)
+            .await
+            .unwrap();
+
+        token.assert_async().await;
+        channels.assert_async().await;
+        assert_eq!(
+            linked,
+            StreamingChannel {
+                id: "UCabc".into(),
+                login: "pachi".into(),
+                display_name: "Pachi Plays".into(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn linking_fails_for_accounts_without_a_channel() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/token")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({ "access_token": "the-token" }).to_string())
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/youtube/v3/channels")
+            .match_query(Matcher::Any)
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({ "kind": "youtube#channelListResponse" }).to_string())
+            .create_async()
+            .await;
+
+        let client = mock_client(&server);
+        let result = client
+            .fetch_linked_channel("the-code", &client.redirect_uri)
+            .await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn fetches_channel_identities_with_the_api_key() {
+        let mut server = mockito::Server::new_async().await;
+        let channels = server
+            .mock("GET", "/youtube/v3/channels")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("id".into(), "UCabc,UCdef".into()),
+                Matcher::UrlEncoded("key".into(), "api-key".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({
+                    "items": [
+                        { "id": "UCabc", "snippet": { "title": "Renamed", "customUrl": "@new" } },
+                        { "id": "UCdef", "snippet": { "title": "No Handle" } },
+                    ],
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let client = mock_client(&server);
+        let result = client
+            .fetch_channels(&["UCabc".into(), "UCdef".into()])
+            .await
+            .unwrap();
+
+        channels.assert_async().await;
+        assert_eq!(result[0].login, "new");
+        assert_eq!(result[0].display_name, "Renamed");
+        assert_eq!(result[1].login, "UCdef");
+    }
+
+    #[tokio::test]
+    async fn fetches_live_streams_from_recent_uploads() {
+        let mut server = mockito::Server::new_async().await;
+        let live_uploads = mock_uploads(&mut server, "UUlive", &["live-video", "old-video"]).await;
+        let idle_uploads = mock_uploads(&mut server, "UUidle", &["upcoming-video"]).await;
+        // A deleted channel's uploads playlist 404s, which shouldn't fail the whole poll
+        let deleted_uploads = server
+            .mock("GET", "/youtube/v3/playlistItems")
+            .match_query(Matcher::UrlEncoded("playlistId".into(), "UUgone".into()))
+            .with_status(404)
+            .create_async()
+            .await;
+        let videos = server
+            .mock("GET", "/youtube/v3/videos")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("id".into(), "live-video,old-video,upcoming-video".into()),
+                Matcher::UrlEncoded("key".into(), "api-key".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({
+                    "items": [
+                        {
+                            "id": "live-video",
+                            "snippet": {
+                                "channelId": "UClive",
+                                "title": "StarCraft ladder",
+                                "liveBroadcastContent": "live",
+                                "thumbnails": {
+                                    "default": { "url": "https://i.ytimg.com/default.jpg" },
+                                    "medium": { "url": "https://i.ytimg.com/medium.jpg" },
+                                },
+                            },
+                            "liveStreamingDetails": {
+                                "actualStartTime": "2026-10-19T12:00:00Z",
+                                "concurrentViewers": "1234",
+                            },
+                        },
+                        {
+                            "id": "old-video",
+                            "snippet": {
+                                "channelId": "UClive",
+                                "title": "Yesterday's VOD",
+                                "liveBroadcastContent": "none",
+                            },
+                            "liveStreamingDetails": {
+                                "actualStartTime": "2026-10-18T12:00:00Z",
+                            },
+                        },
+                        {
+                            "id": "upcoming-video",
+                            "snippet": {
+                                "channelId": "UCidle",
+                                "title": "Starting soon",
+                                "liveBroadcastContent": "upcoming",
+                            },
+                            "liveStreamingDetails": {},
+                        },
+                    ],
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let client = mock_client(&server);
+        let streams = client
+            .fetch_live_streams(&[
+                channel("UClive", "live"),
+                channel("UCidle", "idle"),
+                channel("UCgone", "gone"),
+            ])
+            .await
+            .unwrap();
+
+        live_uploads.assert_async().await;
+        idle_uploads.assert_async().await;
+        deleted_uploads.assert_async().await;
+        videos.assert_async().await;
+        assert_eq!(streams.len(), 1);
+        let stream = &streams[0];
+        assert_eq!(stream.provider, StreamingProvider::Youtube);
+        assert_eq!(stream.channel_id, "UClive");
+        assert_eq!(stream.channel_login, "live");
+        assert_eq!(stream.stream_id.as_deref(), Some("live-video"));
+        assert_eq!(stream.viewer_count, 1234);
+        assert_eq!(stream.thumbnail_url, "https://i.ytimg.com/medium.jpg");
+        assert!(stream.is_starcraft());
+    }
+
+    #[tokio::test]
+    async fn live_poll_skips_channels_that_fail() {
+        let mut server = mockito::Server::new_async().await;
+        let live_uploads = mock_uploads(&mut server, "UUlive", &["live-video"]).await;
+        server
+            .mock("GET", "/youtube/v3/playlistItems")
+            .match_query(Matcher::UrlEncoded("playlistId".into(), "UUbroken".into()))
+            .with_status(500)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/youtube/v3/videos")
+            .match_query(Matcher::UrlEncoded("id".into(), "live-video".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({
+                    "items": [{
+                        "id": "live-video",
+                        "snippet": {
+                            "channelId": "UClive",
+                            "title": "스타 래더",
+                            "liveBroadcastContent": "live",
+                        },
+                        "liveStreamingDetails": {
+                            "actualStartTime": "2026-10-19T12:00:00Z",
+                            "concurrentViewers": "10",
+                        },
+                    }],
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let client = mock_client(&server);
+        let streams = client
+            .fetch_live_streams(&[channel("UCbroken", "broken"), channel("UClive", "live")])
+            .await
+            .unwrap();
+        live_uploads.assert_async().await;
+        assert_eq!(streams.len(), 1);
+        assert_eq!(streams[0].channel_id, "UClive");
+
+        // If nothing could be checked, the poll fails rather than reporting every stream as ended
+        let result = client
+            .fetch_live_streams(&[channel("UCbroken", "broken")])
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn live_poll_surfaces_api_errors() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/youtube/v3/playlistItems")
+            .match_query(Matcher::Any)
+            .with_status(403)
+            .with_body(json!({ "error": { "message": "quotaExceeded" } }).to_string())
+            .create_async()
+            .await;
+
+        let client = mock_client(&server);
+        let result = client
+            .fetch_live_streams(&[channel("UClive", "live")])
+            .await;
+
+        assert!(is_quota_exceeded(&result.unwrap_err()));
+    }
+}
@@ -4,5 +4,9 @@
  * successfully parsed before).
  *
  * Incrementing this number will cause older maps to re-parsed on demand.
+ *
+ * NOTE: The Rust server's map parser (`server-rs/src/maps`) uses versions starting at 3, and
+ * re-parses older maps in the background. This should no longer be increased: any further parser
+ * changes should happen there, bumping its `MAP_PARSER_VERSION` instead.
  */
 export const MAP_PARSER_VERSION = 2
@@ -18,8 +18,8 @@ type DbPublishedNewsPostMeta = Dbify<PublishedNewsPostMeta>
 
 /**
  * Returns the id and publish time of the most recently published news post (that is, the newest
- * post whose `published_at` is set and has already passed), or `undefined` if no posts are
- * currently published.
+ * published post whose `published_at` is set and has already passed), or `undefined` if no posts
+ * are currently published.
  */
 export async function getLatestPublishedNewsPost(
   withClient?: DbClient,
@@ -29,7 +29,7 @@ export async function getLatestPublishedNewsPost(
     const result = await client.query<DbLatestPublishedNewsPost>(sql`
       SELECT id, published_at
       FROM news_posts
-      WHERE published_at IS NOT NULL AND published_at <= NOW()
+      WHERE status = 'published' AND published_at IS NOT NULL AND published_at <= NOW()
       ORDER BY published_at DESC, id DESC
       LIMIT 1;
     `)
@@ -46,8 +46,8 @@ export async function getLatestPublishedNewsPost(
 }
 
 /**
- * Returns the earliest future `published_at` among scheduled news posts (those whose publish time
- * hasn't passed yet), or `undefined` if none are scheduled.
+ * Returns the earliest future `published_at` among scheduled news posts (published posts whose
+ * publish time hasn't passed yet), or `undefined` if none are scheduled.
  */
 export async function getNextScheduledNewsPostTime(
   withClient?: DbClient,
@@ -57,7 +57,7 @@ export async function getNextScheduledNewsPostTime(
     const result = await client.query<{ next_published_at: Date | null }>(sql`
       SELECT MIN(published_at) AS next_published_at
       FROM news_posts
-      WHERE published_at > NOW();
+      WHERE status = 'published' AND published_at > NOW();
     `)
     return result.rows[0]?.next_published_at ?? undefined
   } finally {
@@ -78,7 +78,8 @@ export async function getPublishedNewsPostMeta(
     const result = await client.query<DbPublishedNewsPostMeta>(sql`
       SELECT title, summary, cover_image_path, published_at
       FROM news_posts
-      WHERE id = ${id} AND published_at IS NOT NULL AND published_at <= NOW();
+      WHERE id = ${id} AND status = 'published' AND published_at IS NOT NULL
+        AND published_at <= NOW();
     `)
     if (result.rows.length === 0) {
       return undefined
@@ -0,0 +1,164 @@
+import { hostname } from 'os'
+import { beforeEach, describe, expect, test, vi } from 'vitest'
+import { RedisStreamConsumer, StreamRedisClient } from './redis'
+
+vi.mock('../logging/logger', () => ({
+  default: {
+    error: vi.fn(),
+    warn: vi.fn(),
+  },
+}))
+
+const CHANNEL = 'matchmaking'
+const KEY = `streams:${CHANNEL}`
+const DEAD_LETTER_KEY = `streams:${CHANNEL}:deadLetter`
+const MESSAGE = JSON.stringify({ type: CHANNEL, data: { type: 'matchFound' } })
+
+/**
+ * A fake Redis client that records every command and replies to each one with the next reply
+ * scripted for it. Unscripted reads never resolve, which parks the consumer's read loop.
+ */
+class FakeStreamRedis implements StreamRedisClient {
+  readonly commands: Array<Array<string | number>> = []
+  private replies = new Map<string, unknown[]>()
+
+  reply(command: string, reply: unknown) {
+    const replies = this.replies.get(command) ?? []
+    replies.push(reply)
+    this.replies.set(command, replies)
+  }
+
+  call(command: string, ...args: Array<string | number>): Promise<unknown> {
+    this.commands.push([command, ...args])
+    const replies = this.replies.get(command)
+    if (replies?.length) {
+      return Promise.resolve(replies.shift())
+    }
+
+    switch (command) {
+      case 'XREADGROUP':
+        return new Promise(() => {})
+      case 'XAUTOCLAIM':
+        return Promise.resolve(['0-0', [], []])
+      case 'XPENDING':
+        return Promise.resolve([])
+      default:
+        return Promise.resolve('OK')
+    }
+  }
+
+  sent(command: string) {
+    return this.commands.filter(([c]) => c === command)
+  }
+}
+
+describe('redis/RedisStreamConsumer', () => {
+  let redis: FakeStreamRedis
+  let handleMessage: ReturnType<typeof vi.fn<(channel: string, message: unknown) => boolean>>
+  let consumer: RedisStreamConsumer
+
+  beforeEach(() => {
+    redis = new FakeStreamRedis()
+    handleMessage = vi.fn<(channel: string, message: unknown) => boolean>().mockReturnValue(true)
+    consumer = new RedisStreamConsumer(handleMessage, redis)
+  })
+
+  /** Waits until the consumer has handled everything it was given and is reading again. */
+  async function waitForRead(count: number) {
+    await vi.waitFor(() => expect(redis.sent('XREADGROUP')).toHaveLength(count))
+  }
+
+  test('acknowledges entries once they are handled', async () => {
+    redis.reply('XREADGROUP', [[KEY, [['1-0', ['message', MESSAGE]]]]])
+
+    await consumer.add(CHANNEL)
+    await waitForRead(2)
+
+    expect(redis.commands[0]).toEqual(['XGROUP', 'CREATE', KEY, 'server', '$', 'MKSTREAM'])
+    expect(handleMessage).toHaveBeenCalledWith(CHANNEL, JSON.parse(MESSAGE))
+    expect(redis.sent('XACK')).toEqual([['XACK', KEY, 'server', '1-0']])
+    consumer.remove(CHANNEL)
+  })
+
+  test('leaves entries unacknowledged when handling fails', async () => {
+    handleMessage.mockReturnValue(false)
+    redis.reply('XREADGROUP', [[KEY, [['1-0', ['message', MESSAGE]]]]])
+
+    await consumer.add(CHANNEL)
+    await waitForRead(2)
+
+    expect(handleMessage).toHaveBeenCalledTimes(1)
+    expect(redis.sent('XACK')).toEqual([])
+    consumer.remove(CHANNEL)
+  })
+
+  test('redelivers entries that went unacknowledged', async () => {
+    redis.reply('XAUTOCLAIM', ['0-0', [['1-0', ['message', MESSAGE]]], []])
+    redis.reply('XPENDING', [['1-0', hostname(), 30_000, 2]])
+
+    await consumer.add(CHANNEL)
+    await waitForRead(1)
+
+    expect(redis.sent('XPENDING')).toEqual([['XPENDING', KEY, 'server', '1-0', '1-0', 1]])
+    expect(handleMessage).toHaveBeenCalledWith(CHANNEL, JSON.parse(MESSAGE))
+    expect(redis.sent('XACK')).toEqual([['XACK', KEY, 'server', '1-0']])
+    expect(redis.sent('XADD')).toEqual([])
+    consumer.remove(CHANNEL)
+  })
+
+  test('dead-letters entries that have been delivered too many times', async () => {
+    redis.reply('XAUTOCLAIM', ['0-0', [['1-0', ['message', MESSAGE]]], []])
+    redis.reply('XPENDING', [['1-0', hostname(), 30_000, 6]])
+
+    await consumer.add(CHANNEL)
+    await waitForRead(1)
+
+    expect(handleMessage).not.toHaveBeenCalled()
+    expect(redis.sent('XADD')).toEqual([
+      [
+        'XADD',
+        DEAD_LETTER_KEY,
+        'MAXLEN',
+        '~',
+        1000,
+        '*',
+        'message',
+        MESSAGE,
+        'sourceId',
+        '1-0',
+        'group',
+        'server',
+        'consumer',
+        hostname(),
+        'reason',
+        'maxDeliveries',
+      ],
+    ])
+    expect(redis.sent('XACK')).toEqual([['XACK', KEY, 'server', '1-0']])
+    consumer.remove(CHANNEL)
+  })
+
+  test('dead-letters entries that cannot be parsed', async () => {
+    redis.reply('XREADGROUP', [
+      [
+        KEY,
+        [
+          ['1-0', ['message', '{']],
+          ['2-0', ['message', MESSAGE]],
+        ],
+      ],
+    ])
+
+    await consumer.add(CHANNEL)
+    await waitForRead(2)
+
+    expect(handleMessage).toHaveBeenCalledTimes(1)
+    expect(redis.sent('XADD')).toHaveLength(1)
+    expect(redis.sent('XADD')[0]).toContain('unparseable')
+    expect(redis.sent('XACK')).toEqual([
+      ['XACK', KEY, 'server', '1-0'],
+      ['XACK', KEY, 'server', '2-0'],
+    ])
+    consumer.remove(CHANNEL)
+  })
+})
@@ -1,4 +1,5 @@
 import IoRedis from 'ioredis'
+import { hostname } from 'os'
 import { singleton } from 'tsyringe'
 import { appendToMultimap } from '../../../common/data-structures/maps'
 import { PublishedMessage } from '../../../common/typeshare'
@@ -19,12 +20,242 @@ export type SubscriptionHandler<T> = (message: T) => void
 export type PatternSubscriptionHandler<T> = (pattern: string, channel: string, message: T) => void
 
 /**
- * A redis client for use in subscribing to pub/sub messages. All messages are assumed to be
+ * Channels whose messages are published to a Redis stream rather than over pub/sub, so that they
+ * aren't lost if we're not connected when they're published. This must match
+ * `PublishedMessage::uses_stream` in server-rs.
+ */
+const STREAM_CHANNELS: ReadonlySet<string> = new Set<PublishedMessage['type']>([
+  'matchmaking',
+  'gameReport',
+])
+/** The consumer group this server reads streams as. */
+const STREAM_CONSUMER_GROUP = 'server'
+/** The field in a stream entry that holds the JSON-encoded `PublishedMessage`. */
+const STREAM_MESSAGE_FIELD = 'message'
+/** How long a stream entry can go unacknowledged before it gets delivered again. */
+const STREAM_REDELIVERY_IDLE_MS = 30 * 1000
+/** How many times a stream entry will be delivered before it's moved to the dead-letter stream. */
+const STREAM_MAX_DELIVERIES = 5
+/**
+ * How long a stream read waits for new entries. Must be shorter than `STREAM_REDELIVERY_IDLE_MS`
+ * so that unacknowledged entries are reclaimed promptly.
+ */
+const STREAM_READ_BLOCK_MS = 5 * 1000
+const STREAM_READ_COUNT = 100
+const STREAM_ERROR_RETRY_MS = 5 * 1000
+const DEAD_LETTER_MAX_LEN = 1000
+
+function streamKey(channel: string) {
+  return `streams:${channel}`
+}
+
+function deadLetterStreamKey(channel: string) {
+  return `streams:${channel}:deadLetter`
+}
+
+/** A stream entry as returned by XREADGROUP/XAUTOCLAIM: `[id, [field, value, field, value...]]`. */
+type RawStreamEntry = [id: string, fields: string[] | null]
+
+/** The part of the Redis client that `RedisStreamConsumer` uses. */
+export interface StreamRedisClient {
+  call(command: string, ...args: Array<string | number>): Promise<unknown>
+}
+
+/**
+ * Reads messages from the Redis streams of `STREAM_CHANNELS` as part of a consumer group, passing
+ * them to a handler and acknowledging them once that handler returns. Entries that go
+ * unacknowledged (because the handler threw, or we went down before finishing) are delivered again,
+ * and entries that can't be parsed or have been delivered too many times are moved to the
+ * channel's dead-letter stream.
+ */
+export class RedisStreamConsumer {
+  private consumer = hostname()
+  private channels = new Set<string>()
+  private running = false
+  private lastReclaim = 0
+
+  constructor(
+    private handleMessage: (channel: string, message: unknown) => boolean,
+    // Reads block, so this needs its own connection
+    private redis: StreamRedisClient = new IoRedis({
+      port: Number(process.env.SB_REDIS_PORT),
+      host: process.env.SB_REDIS_HOST,
+    }),
+  ) {}
+
+  async add(channel: string): Promise<void> {
+    try {
+      // Starting at `$` means a new group only sees messages published after it was created
+      await this.redis.call(
+        'XGROUP',
+        'CREATE',
+        streamKey(channel),
+        STREAM_CONSUMER_GROUP,
+        '$',
+        'MKSTREAM',
+      )
+    } catch (err) {
+      if (!(err instanceof Error) || !err.message.startsWith('BUSYGROUP')) {
+        throw err
+      }
+    }
+
+    this.channels.add(channel)
+    if (!this.running) {
+      this.running = true
+      this.run().catch(err => logger.error({ err }, 'Redis stream consumer exited unexpectedly'))
+    }
+  }
+
+  remove(channel: string) {
+    // The read loop will stop reading it after the current read completes
+    this.channels.delete(channel)
+  }
+
+  private async run() {
+    while (this.channels.size) {
+      try {
+        if (Date.now() - this.lastReclaim >= STREAM_REDELIVERY_IDLE_MS) {
+          for (const channel of this.channels) {
+            await this.reclaim(channel)
+          }
+          this.lastReclaim = Date.now()
+        }
+
+        const channels = Array.from(this.channels)
+        const reply = (await this.redis.call(
+          'XREADGROUP',
+          'GROUP',
+          STREAM_CONSUMER_GROUP,
+          this.consumer,
+          'COUNT',
+          STREAM_READ_COUNT,
+          'BLOCK',
+          STREAM_READ_BLOCK_MS,
+          'STREAMS',
+          ...channels.map(c => streamKey(c)),
+          ...channels.map(() => '>'),
+        )) as Array<[key: string, entries: RawStreamEntry[]]> | null
+
+        for (const [key, entries] of reply ?? []) {
+          const channel = channels.find(c => streamKey(c) === key)!
+          for (const entry of entries) {
+            await this.handleEntry(channel, entry)
+          }
+        }
+      } catch (err) {
+        logger.error({ err }, 'failed to read from Redis streams')
+        await new Promise(resolve => setTimeout(resolve, STREAM_ERROR_RETRY_MS))
+      }
+    }
+
+    this.running = false
+  }
+
+  /**
+   * Claims entries in `channel`'s stream that were delivered to our group but went unacknowledged
+   * for too long, and handles them again (or dead-letters them if they've been retried too many
+   * times).
+   */
+  private async reclaim(channel: string) {
+    const key = streamKey(channel)
+    const [, entries] = (await this.redis.call(
+      'XAUTOCLAIM',
+      key,
+      STREAM_CONSUMER_GROUP,
+      this.consumer,
+      STREAM_REDELIVERY_IDLE_MS,
+      '0-0',
+      'COUNT',
+      STREAM_READ_COUNT,
+    )) as [next: string, entries: RawStreamEntry[], deleted: string[]]
+
+    for (const entry of entries) {
+      const [id] = entry
+      const pending = (await this.redis.call(
+        'XPENDING',
+        key,
+        STREAM_CONSUMER_GROUP,
+        id,
+        id,
+        1,
+      )) as Array<[id: string, consumer: string, idleMs: number, deliveries: number]>
+      const deliveries = pending[0]?.[3] ?? 0
+      if (deliveries > STREAM_MAX_DELIVERIES) {
+        await this.deadLetter(channel, entry, 'maxDeliveries')
+      } else {
+        logger.warn(`redelivering Redis stream entry '${id}' from '${channel}'`)
+        await this.handleEntry(channel, entry)
+      }
+    }
+  }
+
+  private async handleEntry(channel: string, entry: RawStreamEntry) {
+    const [id, fields] = entry
+    const payload = getStreamField(fields, STREAM_MESSAGE_FIELD)
+
+    let parsed: unknown
+    try {
+      parsed = JSON.parse(payload ?? '')
+    } catch (err) {
+      logger.error({ err }, `failed to parse Redis stream entry '${id}' from '${channel}'`)
+      await this.deadLetter(channel, entry, 'unparseable')
+      return
+    }
+
+    // If a handler failed, leave the entry unacknowledged so it gets delivered again
+    if (this.handleMessage(channel, parsed)) {
+      await this.redis.call('XACK', streamKey(channel), STREAM_CONSUMER_GROUP, id)
+    }
+  }
+
+  private async deadLetter(channel: string, [id, fields]: RawStreamEntry, reason: string) {
+    logger.error(
+      `moving Redis stream entry '${id}' from '${channel}' to its dead-letter stream (${reason})`,
+    )
+    await this.redis.call(
+      'XADD',
+      deadLetterStreamKey(channel),
+      'MAXLEN',
+      '~',
+      DEAD_LETTER_MAX_LEN,
+      '*',
+      STREAM_MESSAGE_FIELD,
+      getStreamField(fields, STREAM_MESSAGE_FIELD) ?? '',
+      'sourceId',
+      id,
+      'group',
+      STREAM_CONSUMER_GROUP,
+      'consumer',
+      this.consumer,
+      'reason',
+      reason,
+    )
+    await this.redis.call('XACK', streamKey(channel), STREAM_CONSUMER_GROUP, id)
+  }
+}
+
+function getStreamField(fields: string[] | null, name: string): string | undefined {
+  if (!fields) {
+    return undefined
+  }
+  for (let i = 0; i + 1 < fields.length; i += 2) {
+    if (fields[i] === name) {
+      return fields[i + 1]
+    }
+  }
+  return undefined
+}
+
+/**
+ * A redis client for use in subscribing to published messages, which are delivered either over
+ * pub/sub or (for `STREAM_CHANNELS`) through a Redis stream. All messages are assumed to be
  * JSON-encoded to reduce processing overhead.
  */
 @singleton()
 export class RedisSubscriber {
   private redis: IoRedis
+  private streamConsumer: RedisStreamConsumer
   private subscriptions = new Map<string, SubscriptionHandler<unknown>[]>()
   private patternSubscriptions = new Map<string, PatternSubscriptionHandler<unknown>[]>()
 
@@ -33,38 +264,21 @@ export class RedisSubscriber {
       port: Number(process.env.SB_REDIS_PORT),
       host: process.env.SB_REDIS_HOST,
     })
+    this.streamConsumer = new RedisStreamConsumer((channel, message) =>
+      this.dispatch(channel, message),
+    )
 
     this.redis
       .on('message', (channel, message) => {
-        const handlers = this.subscriptions.get(channel)
-        if (handlers) {
-          let parsed: unknown
-          try {
-            parsed = JSON.parse(message)
-          } catch (err) {
-            logger.error({ err }, `failed to parse Redis published message to '${channel}'`)
-            return
-          }
-
-          if (!parsed || (parsed as any).type !== channel) {
-            logger.error(
-              `received a Redis published message with mismatched type and channel: ` +
-                `channel='${channel}', type='${(parsed as any)?.type}'`,
-            )
-          }
-
-          const inner = (parsed as PublishedMessage).data
-
-          for (const handler of handlers) {
-            try {
-              handler(inner)
-            } catch (err) {
-              logger.error({ err }, `failed to handle Redis published message to '${channel}'`)
-            }
-          }
-        } else {
-          logger.warn(`received a Redis published message with no handlers: '${channel}'`)
+        let parsed: unknown
+        try {
+          parsed = JSON.parse(message)
+        } catch (err) {
+          logger.error({ err }, `failed to parse Redis published message to '${channel}'`)
+          return
         }
+
+        this.dispatch(channel, parsed)
       })
       .on('pmessage', (pattern, channel, message) => {
         const handlers = this.patternSubscriptions.get(pattern)
@@ -99,12 +313,48 @@ export class RedisSubscriber {
       })
   }
 
+  /**
+   * Passes a parsed published message to the handlers for its channel. Returns whether every
+   * handler completed without throwing.
+   */
+  private dispatch(channel: string, parsed: unknown): boolean {
+    const handlers = this.subscriptions.get(channel)
+    if (!handlers) {
+      logger.warn(`received a Redis published message with no handlers: '${channel}'`)
+      return true
+    }
+
+    if (!parsed || (parsed as any).type !== channel) {
+      logger.error(
+        `received a Redis published message with mismatched type and channel: ` +
+          `channel='${channel}', type='${(parsed as any)?.type}'`,
+      )
+    }
+
+    const inner = (parsed as PublishedMessage).data
+
+    let succeeded = true
+    for (const handler of handlers) {
+      try {
+        handler(inner)
+      } catch (err) {
+        logger.error({ err }, `failed to handle Redis published message to '${channel}'`)
+        succeeded = false
+      }
+    }
+    return succeeded
+  }
+
   async subscribe<C extends PublishedMessage['type'], T extends PublishedMessage & { type: C }>(
     channel: C,
     handler: SubscriptionHandler<T['data']>,
   ): Promise<void> {
     if (!this.subscriptions.has(channel)) {
-      await this.redis.subscribe(channel)
+      if (STREAM_CHANNELS.has(channel)) {
+        await this.streamConsumer.add(channel)
+      } else {
+        await this.redis.subscribe(channel)
+      }
     }
 
     appendToMultimap(this.subscriptions, channel, handler)
@@ -125,7 +375,11 @@ export class RedisSubscriber {
     }
 
     if (handlers.length === 1) {
-      await this.redis.unsubscribe(channel)
+      if (STREAM_CHANNELS.has(channel)) {
+        this.streamConsumer.remove(channel)
+      } else {
+        await this.redis.unsubscribe(channel)
+      }
       this.subscriptions.delete(channel)
     } else {
       handlers.splice(index, 1)
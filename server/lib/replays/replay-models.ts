@@ -111,6 +111,37 @@ export async function insertReplayFile(
   }
 }
 
+/**
+ * Records a stored replay file as a blob (see the `create_file_blobs` migration), so that the file
+ * is deleted once no `replay_files` row refers to it. The `replay_files` row is inserted before its
+ * file is written, so the blob starts out with its existing references already counted.
+ */
+export async function insertReplayFileBlob(
+  path: string,
+  hash: Buffer,
+  size: number,
+  client?: DbClient,
+): Promise<void> {
+  const { client: dbClient, done } = await db(client)
+  try {
+    await dbClient.query(sql`
+      WITH blob AS (
+        INSERT INTO file_blobs (path, content_hash, ref_count, unreferenced_since)
+        SELECT ${path}, ${hash}, COUNT(*), CASE WHEN COUNT(*) = 0 THEN NOW() END
+        FROM replay_files
+        WHERE file_path = ${path}
+        ON CONFLICT (path) DO NOTHING
+        RETURNING path
+      )
+      INSERT INTO file_blob_files (path, blob_path, content_type, size)
+      SELECT path, path, 'application/octet-stream', ${size}
+      FROM blob
+    `)
+  } finally {
+    done()
+  }
+}
+
 /**
  * Gets the "best" replay for a game (longest duration based on frame count).
  */
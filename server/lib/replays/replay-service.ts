@@ -13,6 +13,7 @@ import {
   deleteReplayFile,
   findReplayByHashAndSize,
   insertReplayFile,
+  insertReplayFileBlob,
   REPLAY_PARSER_VERSION,
   ReplayFile,
 } from './replay-models'
@@ -74,6 +75,14 @@ export class ReplayService {
       throw err
     }
 
+    // The file is already stored at this point, so failing to track it just means it'll never be
+    // garbage collected, which isn't worth failing the upload over
+    try {
+      await insertReplayFileBlob(replayPath(replayFile.id), hash, size)
+    } catch (err) {
+      logger.error({ err }, 'failed to record replay file blob')
+    }
+
     // Link to games_users
     await setReplayFileId(context.userId, context.gameId, replayFile.id)
 
@@ -0,0 +1,56 @@
+import { singleton } from 'tsyringe'
+import { SbUserId } from '../../../common/users/sb-user-id'
+import { DiscordWebhookNotifier } from '../discord/webhook-notifier'
+import logger from '../logging/logger'
+import { RedisSubscriber } from '../redis/redis'
+import { UserSocketsManager } from '../websockets/socket-groups'
+import { findUserById } from './user-model'
+
+/**
+ * Handles the parts of a ban evasion ban that live in Node when the ban was enacted by server-rs
+ * (which checks for ban evasion on OAuth sign-ins): closing the banned users' sockets and
+ * notifying the staff channel, the same way `UserIdentifierManager.banUserIfNeeded` does.
+ *
+ * This has no HTTP API of its own, so it's eagerly constructed in `routes.ts` to make sure the
+ * Redis subscription is set up at boot.
+ */
+@singleton()
+export class BanEvasionNotificationService {
+  constructor(
+    private redisSubscriber: RedisSubscriber,
+    private userSocketsManager: UserSocketsManager,
+    private discordNotifier: DiscordWebhookNotifier,
+  ) {
+    this.redisSubscriber
+      .subscribe('user', message => {
+        if (message.type !== 'bannedForBanEvasion') {
+          return
+        }
+
+        for (const userId of message.data.bannedUserIds) {
+          this.userSocketsManager.getById(userId)?.closeAll()
+        }
+        this.notifyStaff(message.data.userId, message.data.banLengthHours).catch(err => {
+          logger.error({ err }, 'Error notifying staff of ban evasion')
+        })
+      })
+      .catch(err => {
+        logger.error({ err }, 'failed to subscribe to Redis user messages')
+      })
+  }
+
+  private async notifyStaff(userId: SbUserId, banLengthHours: number): Promise<void> {
+    const user = await findUserById(userId)
+    if (!user) {
+      logger.warn(`User ${userId} not found for ban evasion notification`)
+      return
+    }
+
+    await this.discordNotifier.notify({
+      content:
+        `User '${user.name}' [${user.id}] banned for ban evasion ` +
+        `for ${Math.round(banLengthHours * 10) / 10} hours.\n\n` +
+        `${process.env.SB_CANONICAL_HOST}/users/${user.id}/${encodeURIComponent(user.name)}/`,
+    })
+  }
+}
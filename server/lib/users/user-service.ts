@@ -65,6 +65,9 @@ export class UserService {
               email: message.data.email,
             })
             break
+          case 'bannedForBanEvasion':
+            // Handled by BanEvasionNotificationService
+            break
           default:
             message satisfies never
         }
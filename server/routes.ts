@@ -21,6 +21,7 @@ import { PageMetadataContext, resolvePageMetadata } from './lib/page-metadata/pa
 import { getCspNonce } from './lib/security/csp'
 import { getJwt } from './lib/session/jwt-session-middleware'
 import { monotonicNow } from './lib/time/monotonic-now'
+import { BanEvasionNotificationService } from './lib/users/ban-evasion-notification-service'
 
 function send404() {
   throw new httpErrors.NotFound()
@@ -41,6 +42,7 @@ export default function applyRoutes(app: Koa, graphqlOrigin: string) {
   container.resolve(NewsService)
   // Has no HTTP API of its own; resolved here so its Redis subscription is set up at boot.
   container.resolve(GameReportNotificationService)
+  container.resolve(BanEvasionNotificationService)
 
   const router = new KoaRouter()
   app.use(router.routes()).use(router.allowedMethods())
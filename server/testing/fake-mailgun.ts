@@ -29,8 +29,12 @@ const sentMessages = new Map<string, SentEmail[]>()
 router
   .post('/v3/:domain/messages', async ctx => {
     const body = (ctx.request.body ?? {}) as Record<string, string>
-    const { to, from, subject, template } = body
-    const templateVariables = body['t:variables'] ? JSON.parse(body['t:variables']) : undefined
+    const { to, from, subject } = body
+    // The Node server sends Mailgun templates, while server-rs renders its emails itself and
+    // attaches the template and its data as custom variables
+    const template = body.template ?? body['v:template']
+    const rawVariables = body['t:variables'] ?? body['v:template-data']
+    const templateVariables = rawVariables ? JSON.parse(rawVariables) : undefined
     if (!to || !from || !subject || !template || !templateVariables) {
       throw new httpErrors.BadRequest('Missing required fields')
     }